@@ -0,0 +1,54 @@
+use std::env;
+use std::path::Path;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Embed the git commit and the build date, which are printed by `--version`
+/// and in the header of each run, to help with debugging user reports
+fn main() {
+    let git_commit = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|commit| commit.trim().to_string())
+        .filter(|commit| !commit.is_empty())
+        .unwrap_or_else(|| String::from("unknown"));
+
+    // Allow reproducible builds, see https://reproducible-builds.org/docs/source-date-epoch/
+    let build_timestamp = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse::<u64>().ok())
+        .unwrap_or_else(|| SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0));
+
+    println!("cargo:rustc-env=IMGSORTER_GIT_COMMIT={}", git_commit);
+    println!("cargo:rustc-env=IMGSORTER_BUILD_DATE={}", format_date(build_timestamp));
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    // Only rebuild when the current commit changes
+    for git_file in [".git/HEAD", ".git/refs"] {
+        if Path::new(git_file).exists() {
+            println!("cargo:rerun-if-changed={}", git_file);
+        }
+    }
+}
+
+/// Format a unix timestamp as YYYY-MM-DD, without any dependencies
+/// Based on http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn format_date(timestamp: u64) -> String {
+    let days = (timestamp / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
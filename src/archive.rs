@@ -0,0 +1,64 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::config::*;
+
+/// The name of the marker file written inside the target folder after files are written to it,
+/// if [Args::protect_target] is enabled. It identifies the folder as an imgsorter archive, which is
+/// then protected in the following runs
+pub const ARCHIVE_MARKER_FILE_NAME: &str = ".imgsorter-archive";
+
+/// Check if the folder contains the archive marker file
+pub fn is_archive(dir: &Path) -> bool {
+    dir.join(ARCHIVE_MARKER_FILE_NAME).is_file()
+}
+
+/// Create the archive marker file inside the folder, if it doesn't exist yet
+pub fn mark_as_archive(dir: &Path) -> Result<(), io::Error> {
+    let marker_path = dir.join(ARCHIVE_MARKER_FILE_NAME);
+    if marker_path.exists() {
+        return Ok(());
+    }
+    fs::write(
+        marker_path,
+        "This folder is an archive created by imgsorter (https://github.com/cristan2/imgsorter).\n\
+         If `protect_target` is enabled, files inside it will never be overwritten or deleted.\n")
+}
+
+/// If the target folder is a protected archive (i.e. [Args::protect_target] is enabled
+/// and the folder contains the archive marker file), check that the current options
+/// would only add new files to it and never change or remove any existing files.
+/// Returns a description of the first conflicting option, if any
+pub fn check_archive_protection(args: &Args) -> Result<(), String> {
+    if !args.protect_target || !is_archive(&args.target_dir) {
+        return Ok(());
+    }
+
+    // Overwriting files would replace the versions already in the archive
+    if matches!(args.collision_policy, CollisionPolicy::Overwrite | CollisionPolicy::KeepLarger) {
+        return Err(format!("the `collision_policy` option is set to '{}', which overwrites existing files",
+                           args.collision_policy.name()));
+    }
+
+    // Moving files deletes the source files, which is not allowed if they're already inside the archive
+    if !args.copy_not_move {
+        let target_dir = canonical_or_same(&args.target_dir);
+        let archived_source = args.source_dirs
+            .iter()
+            .flatten()
+            .find(|source_dir| canonical_or_same(source_dir).starts_with(&target_dir));
+
+        if let Some(source_dir) = archived_source {
+            return Err(format!(
+                "source folder {} is inside the archive and its files would be deleted after moving them",
+                source_dir.display()));
+        }
+    }
+
+    Ok(())
+}
+
+fn canonical_or_same(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
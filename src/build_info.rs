@@ -0,0 +1,29 @@
+/// The version of the program, as set in Cargo.toml
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The short hash of the git commit the program was built from, or "unknown"
+/// if it wasn't built from a git repository (set by the build script)
+pub const GIT_COMMIT: &str = env!("IMGSORTER_GIT_COMMIT");
+
+/// The date the program was built, in YYYY-MM-DD format (set by the build script)
+pub const BUILD_DATE: &str = env!("IMGSORTER_BUILD_DATE");
+
+/// The optional features the program was built with, see the `[features]` section in Cargo.toml
+pub fn enabled_features() -> Vec<&'static str> {
+    let mut features = Vec::new();
+    if cfg!(feature = "par2") {
+        features.push("par2");
+    }
+    features
+}
+
+/// A single line describing this build, useful when reporting problems, e.g.:
+/// `imgsorter 1.0.0 (commit 1f48101, built 2022-03-14, features: par2)`
+pub fn build_info() -> String {
+    let features = enabled_features();
+    format!("imgsorter {} (commit {}, built {}, features: {})",
+            VERSION,
+            GIT_COMMIT,
+            BUILD_DATE,
+            if features.is_empty() { String::from("none") } else { features.join(", ") })
+}
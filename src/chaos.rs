@@ -0,0 +1,57 @@
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Hidden developer option which makes a percentage of file operations fail on purpose,
+/// to check how the rest of the program handles errors without needing real faults.
+/// Use `--chaos` for the default failure rate, or `--chaos=<percent>` for a custom one
+pub const CHAOS_CLI_FLAG: &str = "--chaos";
+
+/// The percentage of failed operations when the `--chaos` flag doesn't specify one
+pub const DEFAULT_CHAOS_FAILURE_RATE: u8 = 10;
+
+/// State of the pseudo-random generator, seeded on first use.
+/// This doesn't need to be a good generator, just a cheap one shared by all threads
+static RANDOM_STATE: AtomicU64 = AtomicU64::new(0);
+
+/// Run the file operation, unless a failure is injected instead, in which case
+/// return an error without running it. `failure_rate` is a percentage between 0 and 100
+pub fn with_chaos<T, F>(failure_rate: u8, operation_name: &str, operation: F) -> Result<T, io::Error>
+where
+    F: FnOnce() -> Result<T, io::Error>,
+{
+    if failure_rate > 0 && next_random_percentage() < failure_rate {
+        Err(io::Error::other(
+            format!("simulated {} failure ({})", operation_name, CHAOS_CLI_FLAG)))
+    } else {
+        operation()
+    }
+}
+
+/// Return a pseudo-random number between 0 and 99 using xorshift
+fn next_random_percentage() -> u8 {
+    let xorshift = |mut x: u64| {
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        x
+    };
+
+    let previous = RANDOM_STATE
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |state| {
+            let seed = if state == 0 { initial_seed() } else { state };
+            Some(xorshift(seed))
+        })
+        .unwrap_or_else(|state| state);
+
+    let seed = if previous == 0 { initial_seed() } else { previous };
+    (xorshift(seed) % 100) as u8
+}
+
+fn initial_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or(0)
+        | 1
+}
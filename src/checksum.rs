@@ -0,0 +1,211 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::archive::ARCHIVE_MARKER_FILE_NAME;
+use crate::exif_report::EXIF_REPORT_FILE_NAME;
+use crate::journal::JOURNAL_DIR_NAME;
+use crate::utils::*;
+
+/// The name of the checksum manifest written inside each target folder.
+/// The format is the same one used by `sha256sum`, so the manifests
+/// can also be checked with `sha256sum -c imgsorter.sha256`
+pub const CHECKSUM_MANIFEST_FILE_NAME: &str = "imgsorter.sha256";
+
+/// Read the file contents in chunks and return its SHA-256 hash as a lowercase hex string
+pub fn hash_file(file_path: &Path) -> Result<String, io::Error> {
+    let mut file = File::open(file_path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Append a single `<hash>  <file name>` line to the manifest inside `dir`,
+/// creating the manifest if it doesn't exist yet
+pub fn append_manifest_entry(dir: &Path, file_name: &str, hash: &str) -> Result<(), io::Error> {
+    let mut manifest = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(CHECKSUM_MANIFEST_FILE_NAME))?;
+    writeln!(manifest, "{}  {}", hash, file_name)
+}
+
+/// Remove all lines of a file from the manifest inside `dir`, e.g. after the file was removed
+/// from the archive. The manifest is removed if no other files are listed in it
+pub fn remove_manifest_entry(dir: &Path, file_name: &str) -> Result<(), io::Error> {
+    let manifest_path = dir.join(CHECKSUM_MANIFEST_FILE_NAME);
+    let contents = fs::read_to_string(&manifest_path)?;
+
+    let remaining_lines: Vec<&str> = contents
+        .lines()
+        .filter(|line| {
+            let listed_name = line.split_once("  ").or_else(|| line.split_once(" *")).map(|(_, name)| name);
+            listed_name != Some(file_name)
+        })
+        .collect();
+
+    if remaining_lines.is_empty() {
+        fs::remove_file(manifest_path)
+    } else {
+        fs::write(manifest_path, remaining_lines.join("\n") + "\n")
+    }
+}
+
+/// Read a manifest file into a map of file names to hashes.
+/// Lines which don't follow the `<hash>  <file name>` format are ignored.
+/// If a file is listed more than once, the last entry wins.
+pub fn read_manifest(manifest_path: &Path) -> Result<BTreeMap<String, String>, io::Error> {
+    let reader = BufReader::new(File::open(manifest_path)?);
+    let mut entries = BTreeMap::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        // `sha256sum` uses a '*' in front of the file name for binary mode
+        if let Some((hash, file_name)) = line.split_once("  ").or_else(|| line.split_once(" *")) {
+            if !hash.is_empty() && !file_name.is_empty() {
+                entries.insert(file_name.to_string(), hash.to_lowercase());
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+/// The outcome of verifying an archive against its checksum manifests
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    /// Files listed in a manifest whose current hash matches the stored one
+    pub verified: usize,
+    /// Files listed in a manifest whose current hash is different (i.e. bit-rot)
+    pub failed: Vec<PathBuf>,
+    /// Files listed in a manifest which no longer exist
+    pub missing: Vec<PathBuf>,
+    /// Files present in the archive but not listed in any manifest
+    pub unlisted: Vec<PathBuf>,
+    /// Files which exist but could not be read
+    pub unreadable: Vec<(PathBuf, String)>,
+    /// Number of manifests which were read
+    pub manifests: usize,
+}
+
+impl VerifyReport {
+    pub fn has_errors(&self) -> bool {
+        !(self.failed.is_empty() && self.missing.is_empty() && self.unreadable.is_empty())
+    }
+
+    pub fn print_summary(&self) {
+        let max_digits = get_integer_char_count(*[
+            self.verified,
+            self.failed.len(),
+            self.missing.len(),
+            self.unlisted.len(),
+            self.unreadable.len()]
+            .iter()
+            .max()
+            .unwrap_or(&0) as i32);
+
+        let colored_count = |count: usize, color: fn(&str) -> String| {
+            let padded = LeftPadding::space(count.to_string(), max_digits);
+            if count > 0 { color(padded.as_str()) } else { padded }
+        };
+
+        println!("──────────────────────────────────────────────");
+        println!("Manifests read:     {}", self.manifests);
+        println!("──────────────────────────────────────────────");
+        println!("Files verified:     {}", colored_count(self.verified, ColoredString::green));
+        println!("Files failed:       {}", colored_count(self.failed.len(), ColoredString::red));
+        println!("Files missing:      {}", colored_count(self.missing.len(), ColoredString::red));
+        println!("Files unreadable:   {}", colored_count(self.unreadable.len(), ColoredString::red));
+        println!("Files unlisted:     {}", colored_count(self.unlisted.len(), ColoredString::orange));
+        println!("──────────────────────────────────────────────");
+    }
+}
+
+/// Walk the archive recursively and re-hash every file listed in the
+/// checksum manifests, comparing it with the stored hash. Files which are not
+/// listed in the manifest of their own folder are reported as unlisted.
+pub fn verify_archive(archive_dir: &Path, verbose: bool) -> Result<VerifyReport, io::Error> {
+    let mut report = VerifyReport::default();
+    verify_dir(archive_dir, archive_dir, verbose, &mut report)?;
+    Ok(report)
+}
+
+fn verify_dir(
+    archive_dir: &Path,
+    current_dir: &Path,
+    verbose: bool,
+    report: &mut VerifyReport,
+) -> Result<(), io::Error> {
+    let mut subdirs: Vec<PathBuf> = Vec::new();
+    let mut files: Vec<(String, PathBuf)> = Vec::new();
+
+    for entry in fs::read_dir(current_dir)?.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if path.is_dir() {
+            if entry.file_name() != JOURNAL_DIR_NAME {
+                subdirs.push(path);
+            }
+        } else if entry.file_name() != CHECKSUM_MANIFEST_FILE_NAME
+            && entry.file_name() != ARCHIVE_MARKER_FILE_NAME
+            && entry.file_name() != EXIF_REPORT_FILE_NAME {
+            files.push((entry.file_name().to_string_lossy().to_string(), path));
+        }
+    }
+
+    let manifest_path = current_dir.join(CHECKSUM_MANIFEST_FILE_NAME);
+    let mut manifest = if manifest_path.exists() {
+        report.manifests += 1;
+        read_manifest(&manifest_path)?
+    } else {
+        BTreeMap::new()
+    };
+
+    let display_path = |path: &Path| {
+        path.strip_prefix(archive_dir).unwrap_or(path).display().to_string()
+    };
+
+    files.sort();
+    for (file_name, file_path) in files {
+        match manifest.remove(&file_name) {
+            Some(expected_hash) => match hash_file(&file_path) {
+                Ok(actual_hash) if actual_hash == expected_hash => {
+                    if verbose {
+                        println!("{} ... {}", display_path(&file_path), ColoredString::green("ok"));
+                    }
+                    report.verified += 1;
+                }
+                Ok(_) => {
+                    println!("{} ... {}", display_path(&file_path), ColoredString::red("checksum mismatch"));
+                    report.failed.push(file_path);
+                }
+                Err(e) => {
+                    println!("{} ... {}", display_path(&file_path), ColoredString::red(format!("could not read file: {}", e).as_str()));
+                    report.unreadable.push((file_path, e.to_string()));
+                }
+            },
+            None => {
+                if verbose {
+                    println!("{} ... {}", display_path(&file_path), ColoredString::orange("not listed in manifest"));
+                }
+                report.unlisted.push(file_path);
+            }
+        }
+    }
+
+    // Anything left in the manifest was not found on disk
+    for file_name in manifest.into_keys() {
+        let file_path = current_dir.join(file_name);
+        println!("{} ... {}", display_path(&file_path), ColoredString::red("missing"));
+        report.missing.push(file_path);
+    }
+
+    subdirs.sort();
+    for subdir in subdirs {
+        verify_dir(archive_dir, &subdir, verbose, report)?;
+    }
+
+    Ok(())
+}
@@ -1,37 +1,1067 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fs::DirEntry;
-use std::path::PathBuf;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Instant;
-use std::{env, fs};
-
+use std::{env, fmt, fs};
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{FixedOffset, NaiveDate};
+
+use crate::archive::is_archive;
+use crate::chaos::*;
+use crate::filename_date::{validate_filename_date_pattern, FilenameDateOrder};
+use crate::gpx::GpxTrack;
+use crate::plan::PLAN_LOCK_FILE_NAME;
+use crate::import_index::ImportIndex;
+use crate::takeout::TakeoutIndex;
+use crate::target_index::{list_file_names, TargetIndex};
+use crate::template::{validate_batch_dir_template, validate_date_dir_template, validate_file_name_template};
 use crate::utils::*;
 
 use toml::*;
 
+/// The name of the config file, which is searched in several folders, see [get_config_file_path]
+pub const CONFIG_FILE_NAME: &str = "imgsorter.toml";
+
+/// The config file shipped with the program, with a description of each option, written by [Command::Init]
+pub const DEFAULT_CONFIG_FILE: &str = include_str!("../imgsorter.toml");
+
 // Config defaults
 const DEFAULT_MIN_COUNT: i64 = 1;
+const DEFAULT_MIN_FILES_PER_DEVICE_DIR: usize = 1;
 const DEFAULT_ALWAYS_CREATE_DEVICE_DIR: bool = false;
 const DEFAULT_COMPACTING_MIN_COUNT: usize = 0;
 const DEFAULT_COPY: bool = true;
+const DEFAULT_REMOVE_EMPTY_SOURCE_DIRS: bool = false;
+const DEFAULT_RETRY_FILES_IN_USE: bool = true;
+const DEFAULT_WATCH_DEBOUNCE_SECONDS: u64 = 5;
 const DEFAULT_SILENT: bool = false;
 const DEFAULT_DRY_RUN: bool = true;
 const DEFAULT_VERBOSE: bool = false;
 const DEFAULT_ALIGN_OUTPUT: bool = true;
+const DEFAULT_SHOW_DRY_RUN_LEGEND: bool = true;
+const DEFAULT_SHOW_LOCATION: bool = false;
+const DEFAULT_SHOW_THUMBNAILS: bool = false;
+const DEFAULT_COPY_PROGRESS_MIN_MB: u64 = 100;
+const DEFAULT_READ_TAKEOUT_JSON: bool = true;
+const DEFAULT_GPX_MAX_GAP_MINUTES: u32 = 10;
 const DEFAULT_SOURCE_RECURSIVE: bool = true;
+const DEFAULT_EXCLUDE_DIRS: &[&str] = &[
+    ".git", "$RECYCLE.BIN", "RECYCLER", "System Volume Information", "lost+found",
+    ".Trash", ".Trashes", ".Spotlight-V100", ".fseventsd", "@eaDir", ".thumbnails"];
+const DEFAULT_SKIP_HIDDEN_DIRS: bool = true;
 const DEFAULT_INCLUDE_DEVICE_MAKE: bool = true;
-static DEFAULT_ONEOFFS_DIR_NAME: &str = "Miscellaneous";
+const DEFAULT_WRITE_CHECKSUMS: bool = false;
+const DEFAULT_WRITE_OPERATION_LOG: bool = true;
+const DEFAULT_WRITE_EXIF_REPORT: bool = false;
+const DEFAULT_SKIP_UNCHANGED_SOURCES: bool = false;
+const DEFAULT_LOCK_PLAN: bool = false;
+const DEFAULT_DETECT_TYPE_FROM_CONTENT: bool = true;
+const DEFAULT_FILENAME_DATE_PATTERNS: &[&str] = &[
+    "{year}{month}{day}", "{year}-{month}-{day}", "{year}_{month}_{day}", "{year}.{month}.{day}",
+    "{day}-{month}-{year}", "{day}.{month}.{year}"];
+const DEFAULT_FILENAME_DATE_ORDER: FilenameDateOrder = FilenameDateOrder::Auto;
+const DEFAULT_PROTECT_TARGET: bool = false;
+const DEFAULT_CORRUPT_FILES_ACTION: CorruptFilesAction = CorruptFilesAction::Quarantine;
+const DEFAULT_UNREADABLE_FILES_ACTION: UnreadableFilesAction = UnreadableFilesAction::Quarantine;
+const DEFAULT_CLOUD_PLACEHOLDERS_ACTION: CloudPlaceholdersAction = CloudPlaceholdersAction::Skip;
+const DEFAULT_HEIC_JPEG_PAIRS: HeicJpegPairPolicy = HeicJpegPairPolicy::KeepBoth;
+const DEFAULT_MARK_PROBABLE_DUPLICATES: bool = false;
+const DEFAULT_DUPLICATE_FILES_ACTION: DuplicateFilesAction = DuplicateFilesAction::Skip;
+const DEFAULT_SKIP_EXIFLESS_TWINS: bool = false;
+const DEFAULT_TARGET_DIR_TIMESTAMPS: TargetDirTimestamps = TargetDirTimestamps::Unchanged;
+const DEFAULT_DRY_RUN_VIEW: DryRunView = DryRunView::Target;
+const DEFAULT_COLLISION_POLICY: CollisionPolicy = CollisionPolicy::Skip;
+const DEFAULT_ONEOFFS_DIR_NAME: &str = "Miscellaneous";
+const DEFAULT_ALBUM_FROM_SOURCE_FOLDER: bool = false;
+const DEFAULT_DATE_DIR_TEMPLATE: &str = "{year}.{month}.{day}";
+const DEFAULT_MONTH_NAMES_LOCALE: MonthNameLocale = MonthNameLocale::English;
+const DEFAULT_SEASON_HEMISPHERE: Hemisphere = Hemisphere::Northern;
+const DEFAULT_DATE_DIR_LAYOUT: DateDirLayout = DateDirLayout::Flat;
+const DEFAULT_EVENT_MAX_GAP_DAYS: usize = 0;
+const DEFAULT_LENS_GROUPING: LensGrouping = LensGrouping::Ignore;
+const DEFAULT_ANIMATION_MAX_SECONDS: u32 = 5;
+const DEFAULT_DOCUMENT_EXTENSIONS: &[&str] = &["pdf", "djvu"];
+const DEFAULT_SIDECAR_EXTENSIONS: &[&str] = &["xmp", "aae", "thm", "srt"];
 
 pub const IMAGE: &str = "image";
 pub const VIDEO: &str = "video";
 pub const AUDIO: &str = "audio";
+pub const ANIMATION: &str = "animation";
+pub const DOCUMENT: &str = "document";
+pub const SIDECAR: &str = "sidecar";
 
 // Unexposed defaults
 const DBG_ON: bool = false;
+const CONFIG_OVERLAY_FILE_NAME: &str = "imgsorter.local.toml";
+const CONFIG_OVERLAY_CLI_FLAG: &str = "--config-extra";
+const SHOW_ONLY_CLI_FLAG: &str = "--show-only";
+pub const FORCE_ROOT_CLI_FLAG: &str = "--force-root";
+pub const VERSION_CLI_FLAG: &str = "--version";
+pub const HELP_CLI_FLAG: &str = "--help";
+const SOURCE_CLI_FLAG: &str = "--source";
+const TARGET_CLI_FLAG: &str = "--target";
+const DRY_RUN_CLI_FLAG: &str = "--dry-run";
+const WRITE_CLI_FLAG: &str = "--write";
+const COPY_CLI_FLAG: &str = "--copy";
+const MOVE_CLI_FLAG: &str = "--move";
+const SILENT_CLI_FLAG: &str = "--silent";
+const RECURSIVE_CLI_FLAG: &str = "--recursive";
+const NO_RECURSIVE_CLI_FLAG: &str = "--no-recursive";
+const MIN_FILES_CLI_FLAG: &str = "--min-files";
+pub const ON_COLLISION_CLI_FLAG: &str = "--on-collision";
+const REPLAN_CLI_FLAG: &str = "--replan";
+const SINCE_LAST_RUN_CLI_FLAG: &str = "--since-last-run";
+const THUMBNAILS_CLI_FLAG: &str = "--thumbnails";
+const EMIT_SCRIPT_CLI_FLAG: &str = "--emit-script";
+const METRICS_CLI_FLAG: &str = "--metrics";
+const FILES_FROM_CLI_FLAG: &str = "--files-from";
+/// The value of [FILES_FROM_CLI_FLAG] which reads the list of files from the standard input
+const FILES_FROM_STDIN: &str = "-";
 const DEFAULT_TARGET_SUBDIR: &str = "imgsorted";
 pub const DEFAULT_UNKNOWN_DEVICE_DIR_NAME: &str = "Unknown";
 pub const DEFAULT_NO_DATE_STR: &str = "no date";
+pub const DEFAULT_CORRUPT_DIR_NAME: &str = "Corrupt";
+pub const DEFAULT_QUARANTINE_DIR_NAME: &str = "Quarantine";
+pub const DEFAULT_PROBABLE_DUPLICATES_DIR_NAME: &str = "Probable duplicates";
+pub const DEFAULT_DUPLICATES_DIR_NAME: &str = "Duplicates";
 pub const DATE_DIR_FORMAT: &str = "%Y.%m.%d";
 pub const DEFAULT_MAX_THREADS: usize = 10;
+pub const DEFAULT_WRITE_THREADS: usize = 1;
+const DEFAULT_STREAM_WRITES: bool = false;
+pub const DEFAULT_RECOVERY_REDUNDANCY: u8 = 0;
+
+/// What to do with files which appear to be corrupt,
+/// i.e. both their header and their EXIF data are unreadable
+#[derive(Debug, Clone, PartialEq)]
+pub enum CorruptFilesAction {
+    /// Copy or move them to a separate [DEFAULT_CORRUPT_DIR_NAME] folder for review
+    Quarantine,
+    /// Leave them in the source folder
+    Skip,
+    /// Don't check for corrupt files, process them like any other file
+    Ignore,
+}
+
+impl CorruptFilesAction {
+    /// The value used for this action in the config file
+    pub const fn name(&self) -> &'static str {
+        match self {
+            CorruptFilesAction::Quarantine => "quarantine",
+            CorruptFilesAction::Skip => "skip",
+            CorruptFilesAction::Ignore => "ignore",
+        }
+    }
+}
+
+/// What to do with files which are empty or can't be opened, e.g. because of their permissions
+#[derive(Debug, Clone, PartialEq)]
+pub enum UnreadableFilesAction {
+    /// Copy or move the empty files to a separate [DEFAULT_QUARANTINE_DIR_NAME] folder for review.
+    /// Files which can't be opened can't be copied either, so they're always skipped
+    Quarantine,
+    /// Leave them in the source folder
+    Skip,
+}
+
+impl UnreadableFilesAction {
+    /// The value used for this action in the config file
+    pub const fn name(&self) -> &'static str {
+        match self {
+            UnreadableFilesAction::Quarantine => "quarantine",
+            UnreadableFilesAction::Skip => "skip",
+        }
+    }
+}
+
+/// What to do with source files which have the same name and contents as another source file
+/// written to the same target folder, e.g. the same photo copied into several source folders
+#[derive(Debug, Clone, PartialEq)]
+pub enum DuplicateFilesAction {
+    /// Leave them in the source folder, only the first of them is written
+    Skip,
+    /// Copy or move them to a separate [DEFAULT_DUPLICATES_DIR_NAME] folder for review
+    Review,
+}
+
+impl DuplicateFilesAction {
+    /// The value used for this action in the config file
+    pub const fn name(&self) -> &'static str {
+        match self {
+            DuplicateFilesAction::Skip => "skip",
+            DuplicateFilesAction::Review => "review",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "skip" => Some(DuplicateFilesAction::Skip),
+            "review" => Some(DuplicateFilesAction::Review),
+            _ => None,
+        }
+    }
+}
+
+/// What to do with cloud placeholders, i.e. files synced by OneDrive, iCloud Drive and similar apps
+/// which are listed with their full size, but whose contents are only downloaded when they are read
+#[derive(Debug, Clone, PartialEq)]
+pub enum CloudPlaceholdersAction {
+    /// Leave them in the source folder, without reading them
+    Skip,
+    /// Download each of them before reading it, then process them like any other file
+    Download,
+}
+
+impl CloudPlaceholdersAction {
+    /// The value used for this action in the config file
+    pub const fn name(&self) -> &'static str {
+        match self {
+            CloudPlaceholdersAction::Skip => "skip",
+            CloudPlaceholdersAction::Download => "download",
+        }
+    }
+}
+
+/// Which file to keep when the same photo is found as both a HEIC and a JPEG file,
+/// e.g. iCloud exports which include both the HEIC originals and their JPEG conversions
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeicJpegPairPolicy {
+    KeepHeic,
+    KeepJpeg,
+    KeepBoth,
+}
+
+impl HeicJpegPairPolicy {
+    /// The value used for this policy in the config file
+    pub const fn name(&self) -> &'static str {
+        match self {
+            HeicJpegPairPolicy::KeepHeic => "keep_heic",
+            HeicJpegPairPolicy::KeepJpeg => "keep_jpeg",
+            HeicJpegPairPolicy::KeepBoth => "keep_both",
+        }
+    }
+}
+
+/// Which modified time to set on the target date and device folders after writing files to them,
+/// so that sorting folders by date in a file manager orders them chronologically
+#[derive(Debug, Clone, PartialEq)]
+pub enum TargetDirTimestamps {
+    /// Leave the modified time as set by the operating system, i.e. the time of the last write
+    Unchanged,
+    /// Use the date the folder represents, or the newest file inside for other folders (e.g. one-offs)
+    FolderDate,
+    /// Use the modified time of the newest file inside the folder
+    NewestFile,
+}
+
+impl TargetDirTimestamps {
+    /// The value used for this option in the config file
+    pub const fn name(&self) -> &'static str {
+        match self {
+            TargetDirTimestamps::Unchanged => "unchanged",
+            TargetDirTimestamps::FolderDate => "folder_date",
+            TargetDirTimestamps::NewestFile => "newest_file",
+        }
+    }
+}
+
+/// What to do when a file with the same name already exists in the target folder
+#[derive(Debug, Clone, PartialEq)]
+pub enum CollisionPolicy {
+    /// Leave the source file in place and keep the target file
+    Skip,
+    /// Replace the target file with the source file
+    Overwrite,
+    /// Write the source file with a number added to its name, e.g. `IMG_0001 (2).jpg`,
+    /// unless it has the same contents as the target file, which is then skipped
+    Rename,
+    /// Place the source file in the `Duplicates` folder, to be checked by the user
+    Review,
+    /// Replace the target file if the source file is larger, and keep both files otherwise
+    KeepLarger,
+}
+
+impl CollisionPolicy {
+    /// The value used for this policy in the config file
+    pub const fn name(&self) -> &'static str {
+        match self {
+            CollisionPolicy::Skip => "skip",
+            CollisionPolicy::Overwrite => "overwrite",
+            CollisionPolicy::Rename => "rename",
+            CollisionPolicy::Review => "review",
+            CollisionPolicy::KeepLarger => "keep_larger",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<CollisionPolicy> {
+        match name.trim().to_lowercase().as_str() {
+            "skip" => Some(CollisionPolicy::Skip),
+            "overwrite" => Some(CollisionPolicy::Overwrite),
+            "rename" => Some(CollisionPolicy::Rename),
+            "review" => Some(CollisionPolicy::Review),
+            "keep_larger" => Some(CollisionPolicy::KeepLarger),
+            _ => None,
+        }
+    }
+}
+
+/// How the files are grouped in the dry run output
+#[derive(Debug, Clone, PartialEq)]
+pub enum DryRunView {
+    /// Show each target folder, with the source files which will be written into it
+    Target,
+    /// Show each source folder, with the target path of each of its files
+    Source,
+}
+
+impl DryRunView {
+    /// The value used for this option in the config file
+    pub const fn name(&self) -> &'static str {
+        match self {
+            DryRunView::Target => "target",
+            DryRunView::Source => "source",
+        }
+    }
+}
+
+/// The kind of operation status of a file, used to filter the dry run output
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatusCategory {
+    /// The file will be copied or moved
+    Written,
+    /// The file will be left in the source folder
+    Skipped,
+    /// The file can't be read, or can't be fully moved
+    Errors,
+}
+
+impl StatusCategory {
+    /// The value used for this category in the config file and on the command line
+    pub const fn name(&self) -> &'static str {
+        match self {
+            StatusCategory::Written => "written",
+            StatusCategory::Skipped => "skipped",
+            StatusCategory::Errors => "errors",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<StatusCategory> {
+        match name.trim().to_lowercase().as_str() {
+            "written" => Some(StatusCategory::Written),
+            "skipped" => Some(StatusCategory::Skipped),
+            "errors" => Some(StatusCategory::Errors),
+            _ => None,
+        }
+    }
+}
+
+/// The language used for month names in date dirs, see [Args::date_dir_template]
+#[derive(Debug, Clone, PartialEq)]
+pub enum MonthNameLocale {
+    English,
+    German,
+    French,
+    Spanish,
+    Italian,
+    Portuguese,
+    Dutch,
+    Romanian,
+}
+
+impl MonthNameLocale {
+    /// The value used for this language in the config file
+    pub const fn name(&self) -> &'static str {
+        match self {
+            MonthNameLocale::English => "en",
+            MonthNameLocale::German => "de",
+            MonthNameLocale::French => "fr",
+            MonthNameLocale::Spanish => "es",
+            MonthNameLocale::Italian => "it",
+            MonthNameLocale::Portuguese => "pt",
+            MonthNameLocale::Dutch => "nl",
+            MonthNameLocale::Romanian => "ro",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<MonthNameLocale> {
+        match name.trim().to_lowercase().as_str() {
+            "en" => Some(MonthNameLocale::English),
+            "de" => Some(MonthNameLocale::German),
+            "fr" => Some(MonthNameLocale::French),
+            "es" => Some(MonthNameLocale::Spanish),
+            "it" => Some(MonthNameLocale::Italian),
+            "pt" => Some(MonthNameLocale::Portuguese),
+            "nl" => Some(MonthNameLocale::Dutch),
+            "ro" => Some(MonthNameLocale::Romanian),
+            _ => None,
+        }
+    }
+}
+
+/// The hemisphere used to name the seasons in date dirs, see [Args::date_dir_template]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Hemisphere {
+    Northern,
+    Southern,
+}
+
+impl Hemisphere {
+    /// The value used for this hemisphere in the config file
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Hemisphere::Northern => "north",
+            Hemisphere::Southern => "south",
+        }
+    }
+}
+
+/// Whether the date dirs are grouped in year and month dirs, see [Args::date_dir_layout]
+#[derive(Debug, Clone, PartialEq)]
+pub enum DateDirLayout {
+    /// All date dirs directly inside the target dir, e.g. `2021.05.14`
+    Flat,
+    /// Date dirs inside a dir for their year, e.g. `2021/2021.05.14`
+    Year,
+    /// Date dirs inside a dir for their month, inside a dir for their year, e.g. `2021/2021.05/2021.05.14`
+    YearMonth,
+}
+
+impl DateDirLayout {
+    /// The value used for this layout in the config file
+    pub const fn name(&self) -> &'static str {
+        match self {
+            DateDirLayout::Flat => "flat",
+            DateDirLayout::Year => "year",
+            DateDirLayout::YearMonth => "year_month",
+        }
+    }
+}
+
+/// How photos taken with the different lenses of the same phone are grouped into device dirs,
+/// see [Args::lens_grouping]
+#[derive(Debug, Clone, PartialEq)]
+pub enum LensGrouping {
+    /// Use the device name as read from EXIF, whether or not it mentions the lens
+    Ignore,
+    /// Remove lens descriptors from the device name, e.g. `Pixel 7 Pro front camera` becomes `Pixel 7 Pro`
+    Merge,
+    /// Add the lens to the device name, e.g. `iPhone 12 Pro back triple camera 6mm f2`
+    Split,
+}
+
+impl LensGrouping {
+    /// The value used for this grouping in the config file
+    pub const fn name(&self) -> &'static str {
+        match self {
+            LensGrouping::Ignore => "ignore",
+            LensGrouping::Merge => "merge",
+            LensGrouping::Split => "split",
+        }
+    }
+}
+
+/// A date range with a name, whose files are sorted into a single folder named after its first date
+/// and its name, e.g. `2021.08.10 Greece trip`, see [Args::named_events]
+#[derive(Debug, Clone, PartialEq)]
+pub struct NamedEvent {
+    pub first_date: NaiveDate,
+    pub last_date: NaiveDate,
+    pub name: String,
+}
+
+impl NamedEvent {
+    /// Parse a date range in the `2021.08.10..2021.08.17` format, or a single date, e.g. `2021.08.10`
+    pub fn parse(date_range: &str, name: &str) -> Result<NamedEvent, String> {
+        let parse_date = |date_str: &str| NaiveDate::parse_from_str(date_str.trim(), DATE_DIR_FORMAT)
+            .map_err(|_| format!("'{}' is not a date in the YYYY.MM.DD format", date_str.trim()));
+
+        let (first_date, last_date) = match date_range.split_once("..") {
+            Some((first_date, last_date)) => (parse_date(first_date)?, parse_date(last_date)?),
+            None => (parse_date(date_range)?, parse_date(date_range)?),
+        };
+
+        if last_date < first_date {
+            return Err(format!("the range '{}' ends before it starts", date_range));
+        }
+
+        let name = name.trim();
+        if name.is_empty() || name.contains(['/', '\\']) {
+            return Err(format!("'{}' can't be used as a folder name", name));
+        }
+
+        Ok(NamedEvent { first_date, last_date, name: name.to_string() })
+    }
+
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.first_date <= date && date <= self.last_date
+    }
+
+    pub fn dir_name(&self) -> String {
+        format!("{} {}", self.first_date.format(DATE_DIR_FORMAT), self.name)
+    }
+}
+
+/// The type and default value of a config key, used for documenting and validating the config file
+#[derive(Debug)]
+pub enum ConfigValue {
+    Boolean(bool),
+    Integer(i64),
+    String(&'static str),
+    Array(&'static [&'static str]),
+    Table,
+}
+
+impl ConfigValue {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            ConfigValue::Boolean(_) => "boolean",
+            ConfigValue::Integer(_) => "integer",
+            ConfigValue::String(_) => "string",
+            ConfigValue::Array(_) => "array",
+            ConfigValue::Table => "table",
+        }
+    }
+}
+
+/// Display the value the same way it would be written in the config file
+impl fmt::Display for ConfigValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigValue::Boolean(value) => write!(f, "{}", value),
+            ConfigValue::Integer(value) => write!(f, "{}", value),
+            ConfigValue::String(value) => write!(f, "'{}'", value),
+            ConfigValue::Array(values) => write!(f, "[{}]",
+                values.iter().map(|value| format!(" '{}' ", value)).collect::<Vec<_>>().join(",")),
+            ConfigValue::Table => write!(f, "{{}}"),
+        }
+    }
+}
+
+/// A single key recognized in the config file
+#[derive(Debug)]
+pub struct ConfigKey {
+    /// The table which contains the key, e.g. `options` or `custom.extensions`
+    pub section: &'static str,
+    pub name: &'static str,
+    /// The type of the value and the default used when the key is missing
+    pub default: ConfigValue,
+    pub description: &'static str,
+    /// Dev-only keys are recognized, but not documented
+    pub hidden: bool,
+}
+
+impl ConfigKey {
+    const fn new(section: &'static str, name: &'static str, default: ConfigValue, description: &'static str) -> ConfigKey {
+        ConfigKey { section, name, default, description, hidden: false }
+    }
+
+    const fn hidden(section: &'static str, name: &'static str, default: ConfigValue) -> ConfigKey {
+        ConfigKey { section, name, default, description: "", hidden: true }
+    }
+}
+
+const FOLDERS: &str = "folders";
+const OPTIONS: &str = "options";
+const CUSTOM: &str = "custom";
+const CUSTOM_EXTENSIONS: &str = "custom.extensions";
+const ADVANCED: &str = "advanced";
+
+/// All keys recognized in the config file, in the order they are documented.
+/// This is used for printing the config help and for detecting unknown keys.
+/// Any new config key must be added here as well as in [Args::new_from_toml]
+pub const CONFIG_KEYS: &[ConfigKey] = &[
+    ConfigKey::new(FOLDERS, "source_dirs", ConfigValue::Array(&[]),
+        "(Mandatory) The folders containing the files to be sorted"),
+    ConfigKey::new(FOLDERS, "target_dir", ConfigValue::String(""),
+        "The destination folder; if empty, the folder from which the program is launched"),
+    ConfigKey::new(FOLDERS, "min_files_per_dir", ConfigValue::Integer(DEFAULT_MIN_COUNT),
+        "Dates with fewer files than this are placed together in a single folder"),
+    ConfigKey::new(FOLDERS, "min_files_per_device_dir", ConfigValue::Integer(DEFAULT_MIN_FILES_PER_DEVICE_DIR as i64),
+        "Devices with fewer files than this for a date are placed directly in the date folder"),
+    ConfigKey::new(FOLDERS, "exclude_dirs", ConfigValue::Array(DEFAULT_EXCLUDE_DIRS),
+        "Names of subfolders which are never read from the source folders, e.g. '.git' or '$RECYCLE.BIN'"),
+    ConfigKey::new(FOLDERS, "skip_hidden_dirs", ConfigValue::Boolean(DEFAULT_SKIP_HIDDEN_DIRS),
+        "Don't read hidden or system subfolders from the source folders"),
+    ConfigKey::new(FOLDERS, "min_files_before_compacting_output", ConfigValue::Integer(DEFAULT_COMPACTING_MIN_COUNT as i64),
+        "Truncate dry run output for more consecutive files with the same status; 0 to disable"),
+    ConfigKey::new(FOLDERS, "target_oneoffs_subdir_name", ConfigValue::String(DEFAULT_ONEOFFS_DIR_NAME),
+        "The name of the folder for dates with fewer files than `min_files_per_dir`"),
+    ConfigKey::new(FOLDERS, "date_dir_template", ConfigValue::String(DEFAULT_DATE_DIR_TEMPLATE),
+        "The name of the date folders, using placeholders like {year}, {month}, {day}, {month_name}, {week}, {season} or {device}"),
+    ConfigKey::new(FOLDERS, "video_date_dir_template", ConfigValue::String(""),
+        "Same as `date_dir_template`, but only for videos, e.g. '{year}.{month} Videos'; if empty, `date_dir_template` is used"),
+    ConfigKey::new(FOLDERS, "audio_date_dir_template", ConfigValue::String(""),
+        "Same as `date_dir_template`, but only for audio files; if empty, `date_dir_template` is used"),
+    ConfigKey::new(FOLDERS, "file_name_template", ConfigValue::String(""),
+        "Rename the files in the target folders, using placeholders like {date}, {time}, {device} or {orig}; if empty, files keep their names"),
+    ConfigKey::new(FOLDERS, "batch_subdir", ConfigValue::String(""),
+        "Place the folders of each run inside a new folder, using placeholders like {today} or {time}, e.g. 'import {today}'; if empty, all runs are merged"),
+    ConfigKey::new(FOLDERS, "animations_subdir_name", ConfigValue::String(""),
+        "Place animated GIFs and short videos without sound in a separate folder with this name inside each date folder"),
+    ConfigKey::new(FOLDERS, "animation_max_seconds", ConfigValue::Integer(DEFAULT_ANIMATION_MAX_SECONDS as i64),
+        "Videos without sound up to this many seconds long are counted as animations, e.g. Boomerangs; 0 to disable"),
+    ConfigKey::new(FOLDERS, "raw_subdir_name", ConfigValue::String(""),
+        "Place RAW photos in a subfolder with this name inside the folder of their device, e.g. next to their JPEG"),
+    ConfigKey::new(FOLDERS, "documents_dir_name", ConfigValue::String(""),
+        "Sort documents, e.g. scans, into date folders inside a folder with this name; if empty, documents are skipped"),
+    ConfigKey::new(FOLDERS, "document_extensions", ConfigValue::Array(DEFAULT_DOCUMENT_EXTENSIONS),
+        "The file extensions sorted as documents, if `documents_dir_name` is set"),
+    ConfigKey::new(FOLDERS, "sidecar_extensions", ConfigValue::Array(DEFAULT_SIDECAR_EXTENSIONS),
+        "The file extensions of sidecars, e.g. XMP edits, which are moved or copied together with the photo or video with the same name"),
+    ConfigKey::new(FOLDERS, "month_names_locale", ConfigValue::String(DEFAULT_MONTH_NAMES_LOCALE.name()),
+        "The language of {month_name} and {season}: 'en', 'de', 'fr', 'es', 'it', 'pt', 'nl' or 'ro'"),
+    ConfigKey::new(FOLDERS, "season_hemisphere", ConfigValue::String(DEFAULT_SEASON_HEMISPHERE.name()),
+        "The hemisphere used for {season}: 'north' or 'south'"),
+    ConfigKey::new(FOLDERS, "date_dir_layout", ConfigValue::String(DEFAULT_DATE_DIR_LAYOUT.name()),
+        "Group the date folders by year ('year') or by year and month ('year_month'), or not at all ('flat')"),
+    ConfigKey::new(FOLDERS, "event_max_gap_days", ConfigValue::Integer(DEFAULT_EVENT_MAX_GAP_DAYS as i64),
+        "Merge date folders which are at most this many days apart into a single event folder; 0 to disable"),
+    ConfigKey::new(FOLDERS, "album_from_source_folder", ConfigValue::Boolean(DEFAULT_ALBUM_FROM_SOURCE_FOLDER),
+        "Keep each subfolder of the source folders as a separate target folder, with the date folders inside"),
+    ConfigKey::hidden(FOLDERS, "source_subdir", ConfigValue::String("")),
+    ConfigKey::new(OPTIONS, "dry_run", ConfigValue::Boolean(DEFAULT_DRY_RUN),
+        "Simulate the process without writing any files; only used if `silent` is true"),
+    ConfigKey::new(OPTIONS, "verbose", ConfigValue::Boolean(DEFAULT_VERBOSE),
+        "Print additional information during processing"),
+    ConfigKey::new(OPTIONS, "align_file_output", ConfigValue::Boolean(DEFAULT_ALIGN_OUTPUT),
+        "Align file lists for pretty outputs"),
+    ConfigKey::new(OPTIONS, "show_dry_run_legend", ConfigValue::Boolean(DEFAULT_SHOW_DRY_RUN_LEGEND),
+        "Print a legend explaining each operation status and its color during dry runs"),
+    ConfigKey::new(OPTIONS, "show_location", ConfigValue::Boolean(DEFAULT_SHOW_LOCATION),
+        "Show where each photo was taken, read from its GPS data, in a column after the dry run status"),
+    ConfigKey::new(OPTIONS, "show_thumbnails", ConfigValue::Boolean(DEFAULT_SHOW_THUMBNAILS),
+        "Show the thumbnail embedded in the EXIF data of each photo listed by `inspect` or before confirming to overwrite newer files, in terminals which support inline images"),
+    ConfigKey::new(OPTIONS, "copy_progress_min_mb", ConfigValue::Integer(DEFAULT_COPY_PROGRESS_MIN_MB as i64),
+        "Show the progress and speed of copying each file of at least this many MB, e.g. long videos; 0 to disable"),
+    ConfigKey::new(OPTIONS, "gpx_files", ConfigValue::Array(&[]),
+        "GPX files, or folders with GPX files, used to find where photos without GPS data were taken"),
+    ConfigKey::new(OPTIONS, "gpx_max_gap_minutes", ConfigValue::Integer(DEFAULT_GPX_MAX_GAP_MINUTES as i64),
+        "Only use a GPX track point recorded at most this many minutes before or after a photo was taken"),
+    ConfigKey::new(OPTIONS, "gpx_time_offset", ConfigValue::String(""),
+        "The time zone of the camera clock for the GPX tracks, e.g. '+02:00'; if empty, the computer's time zone is used"),
+    ConfigKey::new(OPTIONS, "import_index", ConfigValue::String(""),
+        "A file which lists every file imported so far, which are then skipped by later runs even if the target folder was reorganized"),
+    ConfigKey::new(OPTIONS, "read_takeout_json", ConfigValue::Boolean(DEFAULT_READ_TAKEOUT_JSON),
+        "Read the date and location of photos exported from Google Photos from the JSON file next to each of them"),
+    ConfigKey::new(OPTIONS, "show_only", ConfigValue::Array(&[]),
+        "Only list files with these statuses during dry runs: 'written', 'skipped' or 'errors'; empty for all"),
+    ConfigKey::new(OPTIONS, "dry_run_view", ConfigValue::String(DEFAULT_DRY_RUN_VIEW.name()),
+        "Group the files of dry runs by 'target' folder or by 'source' folder"),
+    ConfigKey::new(OPTIONS, "always_create_device_subdirs", ConfigValue::Boolean(DEFAULT_ALWAYS_CREATE_DEVICE_DIR),
+        "Create device folders even if there's a single device or the device is unknown"),
+    ConfigKey::new(OPTIONS, "source_recursive", ConfigValue::Boolean(DEFAULT_SOURCE_RECURSIVE),
+        "Also read all subfolders of the source folders"),
+    ConfigKey::new(OPTIONS, "include_device_make", ConfigValue::Boolean(DEFAULT_INCLUDE_DEVICE_MAKE),
+        "Prepend the device make to the device name, e.g. 'Samsung SM-A415F'"),
+    ConfigKey::new(OPTIONS, "lens_grouping", ConfigValue::String(DEFAULT_LENS_GROUPING.name()),
+        "How photos from the different lenses of a phone are grouped: 'ignore', 'merge' or 'split'"),
+    ConfigKey::new(OPTIONS, "copy_not_move", ConfigValue::Boolean(DEFAULT_COPY),
+        "Copy files instead of moving them; if false, source files are deleted after copying"),
+    ConfigKey::new(OPTIONS, "remove_empty_source_dirs", ConfigValue::Boolean(DEFAULT_REMOVE_EMPTY_SOURCE_DIRS),
+        "When moving files, remove the source subfolders which are left empty"),
+    ConfigKey::new(OPTIONS, "retry_files_in_use", ConfigValue::Boolean(DEFAULT_RETRY_FILES_IN_USE),
+        "Try once more at the end of the run to write the files which were open in another program"),
+    ConfigKey::new(OPTIONS, "watch_debounce_seconds", ConfigValue::Integer(DEFAULT_WATCH_DEBOUNCE_SECONDS as i64),
+        "With `imgsorter watch`, sort the new files once none were added or changed for this many seconds"),
+    ConfigKey::new(OPTIONS, "silent", ConfigValue::Boolean(DEFAULT_SILENT),
+        "Don't ask for confirmation before processing files"),
+    ConfigKey::new(OPTIONS, "write_checksums", ConfigValue::Boolean(DEFAULT_WRITE_CHECKSUMS),
+        "Save a checksum for each written file, to be checked later with `imgsorter verify`"),
+    ConfigKey::new(OPTIONS, "write_exif_report", ConfigValue::Boolean(DEFAULT_WRITE_EXIF_REPORT),
+        "Save the date, device, size, ISO, exposure, aperture and focal length of each written photo"),
+    ConfigKey::new(OPTIONS, "write_operation_log", ConfigValue::Boolean(DEFAULT_WRITE_OPERATION_LOG),
+        "Append a record of each copied, moved or skipped file to a log inside the target folder"),
+    ConfigKey::new(OPTIONS, "lock_plan", ConfigValue::Boolean(DEFAULT_LOCK_PLAN),
+        "Refuse to write the files if the sources changed since the last dry run"),
+    ConfigKey::new(OPTIONS, "skip_unchanged_sources", ConfigValue::Boolean(DEFAULT_SKIP_UNCHANGED_SOURCES),
+        "Exit right away if no source file changed since the last successful run"),
+    ConfigKey::new(OPTIONS, "detect_type_from_content", ConfigValue::Boolean(DEFAULT_DETECT_TYPE_FROM_CONTENT),
+        "Check the contents of files to find their actual type, instead of relying only on extensions"),
+    ConfigKey::new(OPTIONS, "filename_date_patterns", ConfigValue::Array(DEFAULT_FILENAME_DATE_PATTERNS),
+        "Patterns of dates in file names, used for files without an EXIF date"),
+    ConfigKey::new(OPTIONS, "filename_date_order", ConfigValue::String(DEFAULT_FILENAME_DATE_ORDER.name()),
+        "How to read dates in file names which end with the year, e.g. 03-04-2021: 'auto', 'day_first' or 'month_first'"),
+    ConfigKey::new(OPTIONS, "corrupt_files", ConfigValue::String(DEFAULT_CORRUPT_FILES_ACTION.name()),
+        "What to do with corrupt files: 'quarantine', 'skip' or 'ignore'"),
+    ConfigKey::new(OPTIONS, "unreadable_files", ConfigValue::String(DEFAULT_UNREADABLE_FILES_ACTION.name()),
+        "What to do with empty files and files which can't be opened: 'quarantine' or 'skip'"),
+    ConfigKey::new(OPTIONS, "cloud_placeholders", ConfigValue::String(DEFAULT_CLOUD_PLACEHOLDERS_ACTION.name()),
+        "What to do with cloud files which are not downloaded yet: 'skip' or 'download'"),
+    ConfigKey::new(OPTIONS, "protect_target", ConfigValue::Boolean(DEFAULT_PROTECT_TARGET),
+        "Never overwrite or delete files already inside an archive created by a previous run"),
+    ConfigKey::new(OPTIONS, "heic_jpeg_pairs", ConfigValue::String(DEFAULT_HEIC_JPEG_PAIRS.name()),
+        "Which file to keep when a photo exists as both HEIC and JPEG: 'keep_heic', 'keep_jpeg' or 'keep_both'"),
+    ConfigKey::new(OPTIONS, "skip_exifless_twins", ConfigValue::Boolean(DEFAULT_SKIP_EXIFLESS_TWINS),
+        "Skip photos without EXIF data which are most likely re-shared copies of another photo from the same date"),
+    ConfigKey::new(OPTIONS, "mark_probable_duplicates", ConfigValue::Boolean(DEFAULT_MARK_PROBABLE_DUPLICATES),
+        "Place photos with the same device, capture time and size as another photo in a separate folder"),
+    ConfigKey::new(OPTIONS, "duplicate_files", ConfigValue::String(DEFAULT_DUPLICATE_FILES_ACTION.name()),
+        "What to do with source files identical to another source file: 'skip' or 'review'"),
+    ConfigKey::new(OPTIONS, "target_dir_timestamps", ConfigValue::String(DEFAULT_TARGET_DIR_TIMESTAMPS.name()),
+        "The modified time to set on target folders: 'unchanged', 'folder_date' or 'newest_file'"),
+    ConfigKey::new(OPTIONS, "collision_policy", ConfigValue::String(DEFAULT_COLLISION_POLICY.name()),
+        "What to do when a file with the same name exists in the target folder: 'skip', 'overwrite', 'rename', 'review' or 'keep_larger'"),
+    ConfigKey::hidden(OPTIONS, "debug", ConfigValue::Boolean(DBG_ON)),
+    ConfigKey::new(CUSTOM, "devices", ConfigValue::Table,
+        "Custom names for device models, e.g. 'SM-A415F'=\"Maria's phone\""),
+    ConfigKey::new(CUSTOM, "exif_date_formats", ConfigValue::Array(&[]),
+        "Extra formats of EXIF dates for cameras which don't use the standard one, e.g. '%Y/%m/%d %H:%M:%S'"),
+    ConfigKey::new(CUSTOM, "device_exif_date_formats", ConfigValue::Table,
+        "Extra formats of EXIF dates for a single device model, e.g. 'PowerShot A95'=['%d.%m.%Y %H:%M']"),
+    ConfigKey::new(CUSTOM, "events", ConfigValue::Table,
+        "Names for date ranges, whose files are sorted into a single folder, e.g. '2021.08.10..2021.08.17'=\"Greece trip\""),
+    ConfigKey::new(CUSTOM_EXTENSIONS, IMAGE, ConfigValue::Array(&[]),
+        "Additional file extensions to be sorted as images"),
+    ConfigKey::new(CUSTOM_EXTENSIONS, VIDEO, ConfigValue::Array(&[]),
+        "Additional file extensions to be sorted as videos"),
+    ConfigKey::new(CUSTOM_EXTENSIONS, AUDIO, ConfigValue::Array(&[]),
+        "Additional file extensions to be sorted as audio files"),
+    ConfigKey::new(ADVANCED, "max_threads", ConfigValue::Integer(DEFAULT_MAX_THREADS as i64),
+        "The number of threads used for reading files"),
+    ConfigKey::new(ADVANCED, "write_threads", ConfigValue::Integer(DEFAULT_WRITE_THREADS as i64),
+        "The number of target folders written at the same time, e.g. 4 for a network drive"),
+    ConfigKey::new(ADVANCED, "stream_writes", ConfigValue::Boolean(DEFAULT_STREAM_WRITES),
+        "In silent runs, write each file as soon as it's read, without the steps which need all files, e.g. grouping one-off dates"),
+    ConfigKey::new(ADVANCED, "recovery_redundancy", ConfigValue::Integer(DEFAULT_RECOVERY_REDUNDANCY as i64),
+        "The size of PAR2 recovery data for each date folder, as a percentage; 0 to disable"),
+];
+
+/// Print all documented config keys, grouped by section, with their type, default value and description
+pub fn print_config_help() {
+    let documented_keys = || CONFIG_KEYS.iter().filter(|key| !key.hidden);
+
+    let max_len = documented_keys()
+        .map(|key| get_string_char_count(format!("{} = {}", key.name, key.default)))
+        .max()
+        .unwrap_or(0);
+
+    println!("Keys recognized in imgsorter.toml, with their default values:");
+
+    let mut current_section = "";
+    for key in documented_keys() {
+        if key.section != current_section {
+            current_section = key.section;
+            println!();
+            println!("{}", ColoredString::bold_white(format!("[{}]", current_section).as_str()));
+        }
+
+        println!("{} {}",
+                 RightPadding::space(format!("{} = {}", key.name, key.default), max_len),
+                 ColoredString::cyan(format!("# ({}) {}", key.default.type_name(), key.description).as_str()));
+    }
+}
+
+/// The command given as the first command line argument, which decides what the program does
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    /// No command: read the source files, then ask whether to do a dry run or to write the files
+    Interactive,
+    /// Write the files without asking for confirmation
+    Sort,
+    /// Do a dry run without asking for confirmation
+    DryRun,
+    /// Show the target folders which would be created, without listing each file like a dry run
+    Scan,
+    /// List the date, device and type read from each source file, without any target statuses
+    Inspect,
+    /// Print a summary of the source files by type, device and year
+    Stats,
+    /// List the source files which have the same contents, without changing any of them
+    Dedupe,
+    /// Check the files of an archive against their stored checksums, or
+    /// without an archive folder, compare the target folder with the source files
+    Verify,
+    /// Check the files of an archive against their stored checksums,
+    /// using the target folder from the config file if no folder is given
+    Audit,
+    /// Reverse the last run which wrote files into a target folder, using its journal
+    Undo,
+    /// Copy or move the files exactly as planned by an earlier dry run, see [crate::script::ExportedPlan]
+    Apply,
+    /// Keep watching the source folders and sort the new files as they're added
+    Watch,
+    /// Sort a generated library of photos into a temporary folder and check the result, see [crate::selftest]
+    Selftest,
+    /// Create a config file with the default values, see [DEFAULT_CONFIG_FILE]
+    Init,
+    /// List all keys recognized in the config file
+    HelpConfig,
+}
+
+impl Command {
+    /// The name used for this command on the command line
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Command::Interactive => "",
+            Command::Sort => "sort",
+            Command::DryRun => "dry-run",
+            Command::Scan => "scan",
+            Command::Inspect => "inspect",
+            Command::Stats => "stats",
+            Command::Dedupe => "dedupe",
+            Command::Verify => "verify",
+            Command::Audit => "audit",
+            Command::Undo => "undo",
+            Command::Apply => "apply",
+            Command::Watch => "watch",
+            Command::Selftest => "selftest",
+            Command::Init => "init",
+            Command::HelpConfig => "help-config",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Command> {
+        CLI_COMMANDS
+            .iter()
+            .map(|cli_command| cli_command.command)
+            .find(|command| command.name() == name)
+    }
+
+    /// Whether the program runs without asking for any user input
+    pub fn is_unattended(&self) -> bool {
+        !matches!(self, Command::Interactive | Command::Undo | Command::Apply)
+    }
+}
+
+/// A single command recognized on the command line, with the arguments it accepts
+#[derive(Debug)]
+pub struct CliCommand {
+    pub command: Command,
+    pub arguments: &'static str,
+    pub description: &'static str,
+}
+
+/// All commands recognized on the command line, which must be the first argument
+pub const CLI_COMMANDS: &[CliCommand] = &[
+    CliCommand { command: Command::Sort, arguments: "[options] [source folder]",
+        description: "Sort the files without asking for confirmation" },
+    CliCommand { command: Command::DryRun, arguments: "[options] [source folder]",
+        description: "Show what would be done without asking for confirmation" },
+    CliCommand { command: Command::Scan, arguments: "[options] [source folder]",
+        description: "Show the folders the files would be sorted into, without listing each file" },
+    CliCommand { command: Command::Inspect, arguments: "[options] [source folder]",
+        description: "List the date, device and type read from each file" },
+    CliCommand { command: Command::Stats, arguments: "[options] [source folder]",
+        description: "Count the files by type, device and year" },
+    CliCommand { command: Command::Dedupe, arguments: "[options] [source folder]",
+        description: "List the files with the same contents, without changing any of them" },
+    CliCommand { command: Command::Verify, arguments: "[options] [archive folder]",
+        description: "Check a sorted archive for corrupted or missing files, or without a folder, \
+            compare the target folder with the source files" },
+    CliCommand { command: Command::Audit, arguments: "[options] [archive folder]",
+        description: "Check a sorted archive for corrupted or missing files, by default the target folder" },
+    CliCommand { command: Command::Undo, arguments: "[options] [target folder]",
+        description: "Reverse the last run which copied or moved files into the target folder" },
+    CliCommand { command: Command::Apply, arguments: "[options] <plan file>",
+        description: "Copy or move the files as listed in a plan saved with `dry-run --emit-script plan.json`" },
+    CliCommand { command: Command::Watch, arguments: "[options] [source folder]",
+        description: "Keep watching the source folders and sort the new files as they're added, until stopped with Ctrl+C" },
+    CliCommand { command: Command::Selftest, arguments: "",
+        description: "Sort a generated library of photos into a temporary folder and check that nothing went wrong" },
+    CliCommand { command: Command::Init, arguments: "[folder]",
+        description: "Create imgsorter.toml with the default values in this folder or the current one" },
+    CliCommand { command: Command::HelpConfig, arguments: "",
+        description: "List all keys recognized in imgsorter.toml" },
+];
+
+/// Read the command from the first command line argument, if it's one of [CLI_COMMANDS]
+pub fn get_cli_command() -> Command {
+    env::args()
+        .nth(1)
+        .and_then(|arg| Command::from_name(&arg))
+        .unwrap_or(Command::Interactive)
+}
+
+/// A single option recognized on the command line
+#[derive(Debug)]
+pub struct CliOption {
+    pub name: &'static str,
+    /// The name of the value which must follow the option, if any
+    pub value_name: Option<&'static str>,
+    pub description: &'static str,
+    /// Dev-only options are recognized, but not documented
+    pub hidden: bool,
+}
+
+impl CliOption {
+    const fn flag(name: &'static str, description: &'static str) -> CliOption {
+        CliOption { name, value_name: None, description, hidden: false }
+    }
+
+    const fn with_value(name: &'static str, value_name: &'static str, description: &'static str) -> CliOption {
+        CliOption { name, value_name: Some(value_name), description, hidden: false }
+    }
+}
+
+/// All options recognized on the command line. Options with a value can be written
+/// either as `--option <value>` or as `--option=<value>`
+pub const CLI_OPTIONS: &[CliOption] = &[
+    CliOption::with_value(SOURCE_CLI_FLAG, "path",
+        "Read files from this folder instead of `source_dirs`; can be repeated"),
+    CliOption::with_value(TARGET_CLI_FLAG, "path",
+        "Sort files into this folder instead of `target_dir`"),
+    CliOption::with_value(FILES_FROM_CLI_FLAG, "path",
+        "Sort only the files listed in this file, one on each line, instead of reading the source folders; `-` to read the list from the standard input"),
+    CliOption::flag(DRY_RUN_CLI_FLAG,
+        "Together with --silent, only show what would be done, without writing any files"),
+    CliOption::flag(WRITE_CLI_FLAG,
+        "Together with --silent, copy or move the files without a dry run"),
+    CliOption::flag(COPY_CLI_FLAG,
+        "Copy the files and leave the source files untouched"),
+    CliOption::flag(MOVE_CLI_FLAG,
+        "Move the files, i.e. delete the source files after copying them"),
+    CliOption::flag(SILENT_CLI_FLAG,
+        "Proceed without asking for confirmation"),
+    CliOption::flag(RECURSIVE_CLI_FLAG,
+        "Also read all subfolders of the source folders"),
+    CliOption::flag(NO_RECURSIVE_CLI_FLAG,
+        "Only read the files directly inside the source folders"),
+    CliOption::with_value(MIN_FILES_CLI_FLAG, "count",
+        "Dates with fewer files than this are placed together in a single folder"),
+    CliOption::with_value(ON_COLLISION_CLI_FLAG, "policy",
+        "What to do with files which already exist in the target folder instead of `collision_policy`: 'skip', 'overwrite', 'rename', 'review' or 'keep_larger'"),
+    CliOption::with_value(SHOW_ONLY_CLI_FLAG, "statuses",
+        "Only list files with these statuses during dry runs, e.g. `skipped,errors`"),
+    CliOption::flag(THUMBNAILS_CLI_FLAG,
+        "Show the EXIF thumbnail of each photo listed by `inspect` or before confirming to overwrite newer files, in terminals which support inline images"),
+    CliOption::flag(SINCE_LAST_RUN_CLI_FLAG,
+        "Only read the source files added or changed since the last successful run of each source folder"),
+    CliOption::flag(REPLAN_CLI_FLAG,
+        "Write the files even if the sources changed since the last dry run, see `lock_plan`"),
+    CliOption::with_value(EMIT_SCRIPT_CLI_FLAG, "path",
+        "Save the operations of a dry run as a script, e.g. `plan.sh`, `plan.ps1` or `plan.cmd`, or as `plan.json` for `apply`"),
+    CliOption::with_value(METRICS_CLI_FLAG, "path",
+        "Save detailed timings of the run as JSON, e.g. `metrics.json`, to find out what makes it slow"),
+    CliOption::with_value(CONFIG_OVERLAY_CLI_FLAG, "path",
+        "Read another config file, whose values override imgsorter.toml"),
+    CliOption::flag(FORCE_ROOT_CLI_FLAG,
+        "Allow reading drive roots, home folders and system folders recursively"),
+    CliOption::flag(VERSION_CLI_FLAG,
+        "Print the version and build info, then exit"),
+    CliOption::flag(HELP_CLI_FLAG,
+        "Print this help, then exit"),
+    CliOption { name: CHAOS_CLI_FLAG, value_name: None, description: "", hidden: true },
+];
+
+/// Print the usage and all documented command line options
+pub fn print_cli_help() {
+    let documented_options = || CLI_OPTIONS.iter().filter(|option| !option.hidden);
+    let format_option = |option: &CliOption| match option.value_name {
+        Some(value_name) => format!("{} <{}>", option.name, value_name),
+        None => String::from(option.name),
+    };
+
+    let max_len = documented_options()
+        .map(|option| get_string_char_count(format_option(option)))
+        .max()
+        .unwrap_or(0);
+
+    let commands: Vec<(String, &str)> = std::iter::once((
+            String::from("imgsorter [options] [source folder]"),
+            "Read the files, then ask whether to do a dry run or to sort them"))
+        .chain(CLI_COMMANDS.iter().map(|cli_command| (
+            format!("imgsorter {} {}", cli_command.command.name(), cli_command.arguments).trim_end().to_string(),
+            cli_command.description)))
+        .collect();
+
+    let max_command_len = commands
+        .iter()
+        .map(|(usage, _)| get_string_char_count(usage.clone()))
+        .max()
+        .unwrap_or(0);
+
+    println!("Usage:");
+    for (usage, description) in commands {
+        println!("  {} {}", RightPadding::space(usage, max_command_len), description);
+    }
+    println!();
+    println!("If a source folder is given without any option, it's used as both the source and the target folder.");
+    println!("These options can be used with every command and override the values from imgsorter.toml for a single run:");
+
+    for option in documented_options() {
+        println!("  {} {}",
+                 RightPadding::space(format_option(option), max_len),
+                 ColoredString::cyan(option.description));
+    }
+}
+
+/// A key used by older versions of the config file, which has since been renamed
+pub struct LegacyConfigKey {
+    pub section: &'static str,
+    pub old_name: &'static str,
+    pub new_name: &'static str,
+    /// Convert the old value to the format expected by the new key
+    convert: fn(Value) -> Value,
+}
+
+/// Legacy keys are replaced with their current names before the config is parsed
+pub const LEGACY_CONFIG_KEYS: &[LegacyConfigKey] = &[
+    LegacyConfigKey {
+        section: FOLDERS,
+        old_name: "source_dir",
+        new_name: "source_dirs",
+        // a single source folder becomes a list with a single element
+        convert: |value| Value::Array(vec![value]),
+    },
+    LegacyConfigKey {
+        section: OPTIONS,
+        old_name: "debug_on",
+        new_name: "debug",
+        convert: |value| value,
+    },
+];
+
+/// Replace any legacy keys in the config file with their current names, see [LEGACY_CONFIG_KEYS].
+/// If both the legacy key and the current key are present, the current one is used.
+/// Return the legacy keys which were found
+fn migrate_legacy_config_keys(toml_content: &mut toml::map::Map<String, Value>) -> Vec<&'static LegacyConfigKey> {
+    let mut migrated_keys = Vec::new();
+
+    for legacy_key in LEGACY_CONFIG_KEYS {
+        if let Some(section) = toml_content.get_mut(legacy_key.section).and_then(|section| section.as_table_mut()) {
+            if let Some(old_value) = section.remove(legacy_key.old_name) {
+                if !section.contains_key(legacy_key.new_name) {
+                    section.insert(legacy_key.new_name.to_string(), (legacy_key.convert)(old_value));
+                }
+                migrated_keys.push(legacy_key);
+            }
+        }
+    }
+
+    migrated_keys
+}
+
+/// Return all keys in the config file which are not in [CONFIG_KEYS], in `section.key` format
+fn find_unknown_config_keys(toml_content: &toml::map::Map<String, Value>) -> Vec<String> {
+    let is_known_section = |section: &str| CONFIG_KEYS.iter().any(|key| key.section == section);
+    let is_known_key = |section: &str, name: &str| CONFIG_KEYS.iter().any(|key| key.section == section && key.name == name);
+
+    fn collect_unknown(
+        section: &str,
+        table: &toml::map::Map<String, Value>,
+        unknown_keys: &mut Vec<String>,
+        is_known_section: &dyn Fn(&str) -> bool,
+        is_known_key: &dyn Fn(&str, &str) -> bool,
+    ) {
+        for (name, value) in table {
+            let full_name = if section.is_empty() { name.clone() } else { format!("{}.{}", section, name) };
+            match value.as_table() {
+                // Tables which are not keys themselves (e.g. `custom.devices`) contain other keys
+                Some(subtable) if is_known_section(&full_name) && !is_known_key(section, name) =>
+                    collect_unknown(&full_name, subtable, unknown_keys, is_known_section, is_known_key),
+                _ if !is_known_key(section, name) =>
+                    unknown_keys.push(full_name),
+                _ => {}
+            }
+        }
+    }
+
+    let mut unknown_keys = Vec::new();
+    collect_unknown("", toml_content, &mut unknown_keys, &is_known_section, &is_known_key);
+    unknown_keys
+}
 
 #[derive(Debug, Clone)]
 pub struct Args {
@@ -42,10 +1072,18 @@ pub struct Args {
     /// will hold all subdirectories of those paths
     pub source_dirs: Vec<Vec<PathBuf>>,
 
+    /// The command given on the command line, if any
+    /// Not exposed in config
+    pub command: Command,
+
     /// Set to true only if we received a source_dir from the CLI
     /// Not exposed in config, only used during config parsing
     using_cli_source: bool,
 
+    /// Set to true only if we received a target_dir from the CLI
+    /// Not exposed in config, only used during config parsing
+    using_cli_target: bool,
+
     /// The recursive option might result in multiple sources (subdirs) being used even if
     ///   the configuration has a single source dir, so store the actual count after checking sources
     /// Not exposed in config, internal only
@@ -56,16 +1094,102 @@ pub struct Args {
     /// If the target does not exist, it will be created
     /// If the target *does* exist, a subdirectory called
     /// [DEFAULT_TARGET_SUBDIR] will be created and used,
-    /// instead of directly placed in the target_dir
+    /// instead of directly placed in the target_dir,
+    /// unless the target is an archive created by a previous run
     pub target_dir: PathBuf,
 
     /// If this is enabled, descend into subdirectories recursively
     pub source_recursive: bool,
 
+    /// The names of subdirs which are never read when reading the source dirs recursively,
+    /// e.g. version control or recycle bin dirs. Stored in lowercase, since they're matched case-insensitive
+    pub exclude_dirs: Vec<String>,
+
+    /// Whether to skip hidden subdirs (i.e. starting with a dot, or with the hidden
+    /// or system attribute on Windows) when reading the source dirs recursively
+    pub skip_hidden_dirs: bool,
+
     /// The minimum number of files with the same date necessary
     /// for a dedicated subdir to be created
     pub min_files_per_dir: i64,
 
+    /// The minimum number of files of a device with the same date necessary
+    /// for a dedicated device subdir to be created inside the date dir
+    pub min_files_per_device_dir: usize,
+
+    /// The name of the date dirs, built from the date of each file by replacing these placeholders:
+    /// `{year}`, `{month}`, `{day}` (zero-padded numbers), `{month_name}` (see [Self::month_names_locale]),
+    /// `{week}` and `{week_year}` (the ISO week number and the year it belongs to),
+    /// `{quarter}` (Q1 to Q4) and `{season}` (see [Self::season_hemisphere]).
+    /// It can contain path separators to create nested dirs, e.g. `{year}/{month} {month_name}`
+    pub date_dir_template: String,
+
+    /// Overrides [Self::date_dir_template] for video files, e.g. to group them by month
+    /// in dirs like `2023.05 Videos`, since there are usually only a few videos for each day
+    pub video_date_dir_template: Option<String>,
+
+    /// Overrides [Self::date_dir_template] for audio files
+    pub audio_date_dir_template: Option<String>,
+
+    /// Rename the files written to the target dir using this template, e.g. `{date}_{time}_{device}_{orig}`,
+    /// with the placeholders in [crate::template::FILE_NAME_PLACEHOLDERS]; the extension is always kept.
+    /// Files whose new names are the same are renamed like other name collisions
+    pub file_name_template: Option<String>,
+
+    /// Place all target dirs of a run inside a batch dir named using this template, e.g. `import {today}`,
+    /// with the placeholders in [crate::template::BATCH_DIR_PLACEHOLDERS], which use the time the run started.
+    /// Files are then only compared with the target files of the same batch, not of earlier runs
+    pub batch_dir_template: Option<String>,
+
+    /// The name of the batch dir of the current run, rendered from [Self::batch_dir_template] when the run starts
+    pub batch_dir_name: Option<String>,
+
+    /// The name of the subdir inside each date dir where animations are placed, e.g. `Animations`,
+    /// like the subdir of a device. If not set, animations are sorted together with the other files
+    pub animations_dir_name: Option<String>,
+
+    /// Videos without sound which are at most this many seconds long are animations, e.g. Boomerangs
+    /// or the animations made by Google Photos, instead of actual videos. Disabled if 0.
+    /// Animated GIFs are always animations
+    pub animation_max_seconds: u32,
+
+    /// The name of the subdir where RAW files are placed, inside the dir where the other files of
+    /// their device are written, e.g. `2023.05.14/Canon EOS R6/RAW`. If not set, RAW files are placed
+    /// together with the other files. RAW+JPEG pairs are always sorted into the same date and device dir
+    pub raw_dir_name: Option<String>,
+
+    /// The name of the dir inside the target dir where documents are sorted into their own date dirs,
+    /// parallel to the date dirs of photos, e.g. `Documents/2023.05.14`.
+    /// If not set, documents are skipped as files with unknown extensions
+    pub documents_dir_name: Option<String>,
+
+    /// The extensions of files sorted as documents, e.g. scans saved as PDF, see [Self::documents_dir_name]
+    pub document_extensions: Vec<String>,
+
+    /// The extensions of sidecar files, e.g. `IMG_0001.xmp` or `IMG_0001.AAE`, which are written
+    /// into the same dir as the photo or video with the same name, and skipped if there is none
+    pub sidecar_extensions: Vec<String>,
+
+    /// The language used for the `{month_name}` and `{season}` placeholders in [Self::date_dir_template]
+    pub month_names_locale: MonthNameLocale,
+
+    /// The hemisphere used for the `{season}` placeholder in [Self::date_dir_template],
+    /// since the seasons are the opposite in the southern hemisphere
+    pub season_hemisphere: Hemisphere,
+
+    /// Whether the date dirs are placed inside dirs for their year and month,
+    /// e.g. `2021/2021.05/2021.05.14`, instead of directly inside the target dir
+    pub date_dir_layout: DateDirLayout,
+
+    /// Date dirs which are at most this many days apart are merged into a single event dir,
+    /// named after its first and last date dirs, e.g. `2021.08.10 - 2021.08.17`. Disabled if 0
+    pub event_max_gap_days: usize,
+
+    /// Whether each subdir of the configured source dirs (e.g. `2023 Iceland`) becomes a top-level
+    /// target dir with the usual date dirs inside, to keep albums which were already organized by hand.
+    /// Files placed directly inside the source dirs are sorted into date dirs as usual
+    pub album_from_source_folder: bool,
+
     /// Always create device subdirs, even if there's only a single one
     pub always_create_device_subdirs: bool,
 
@@ -86,6 +1210,22 @@ pub struct Args {
     /// Whether files are copied instead of moved to the sorted subdirs
     pub copy_not_move: bool,
 
+    /// Whether to remove the source subfolders left empty after moving their files
+    pub remove_empty_source_dirs: bool,
+
+    /// Whether to try writing the files which were in use by another program once more at the end
+    /// of the run, e.g. photos which a phone sync app is still writing, see [crate::filesystem::is_file_in_use]
+    pub retry_files_in_use: bool,
+
+    /// With the `watch` command, new files are only sorted once no files were added or changed
+    /// in the source folders for this many seconds, so that files still being written aren't read
+    pub watch_debounce_seconds: u64,
+
+    /// With the `watch` command, the names of the files already in the target folders, kept up to date
+    /// from the filesystem events of the target folder, so that each batch doesn't list them again.
+    /// Not read from the config file
+    pub target_index: Option<Arc<TargetIndex>>,
+
     /// Whether to do a simulation of the process, without writing any files
     /// This will display additional information, like the resulting dir structure
     /// including the total number of devices, files and file size
@@ -97,13 +1237,186 @@ pub struct Args {
     /// Whether to align file lists for pretty outputs
     pub align_file_output: bool,
 
+    /// Whether to print a legend explaining each operation status below the dry run header
+    pub show_dry_run_legend: bool,
+
+    /// Whether to print the GPS coordinates of each photo in a separate column during dry runs
+    pub show_location: bool,
+
+    /// Whether to show the EXIF thumbnail of each photo listed by the `inspect` command, or by the confirmation
+    /// to overwrite newer target files, up to [crate::thumbnail::MAX_LISTED_THUMBNAILS], also set with the `--thumbnails` option
+    pub show_thumbnails: bool,
+
+    /// The size in bytes from which each file is copied in chunks, advancing the progress bar of the written files
+    /// while it's copied, e.g. for videos of several GB, instead of only once it's done. 0 to disable
+    pub copy_progress_min_size: u64,
+
+    /// The track points read from the configured GPX files, which give photos without GPS data
+    /// the location recorded at the same time, e.g. by a phone carried along with the camera
+    pub gpx_track: Option<GpxTrack>,
+
+    /// Only track points recorded at most this many minutes before or after a photo are used for its location
+    pub gpx_max_gap_minutes: u32,
+
+    /// The time zone of the camera clock, as its offset from UTC, since GPX tracks are recorded in UTC.
+    /// If not set, the camera is expected to use the same time zone as the computer
+    pub gpx_time_offset: Option<FixedOffset>,
+
+    /// The file which lists every file written by earlier runs, see [crate::import_index::ImportedFile].
+    /// Files with the same contents as one of them are skipped, even if the target folder was reorganized since
+    pub import_index_path: Option<PathBuf>,
+
+    /// Not exposed in config, the files read from [Self::import_index_path]
+    pub import_index: ImportIndex,
+
+    /// Whether to read the JSON files which Google Takeout exports next to each photo and video,
+    /// as the date and location of files without their own, instead of skipping them as unknown files
+    pub read_takeout_json: bool,
+
+    /// Not exposed in config, the metadata read from the Google Takeout JSON files in the source dirs,
+    /// if [Self::read_takeout_json] is set
+    pub takeout_index: TakeoutIndex,
+
+    /// Only list files with these statuses during dry runs. If empty, all files are listed
+    pub show_only: Vec<StatusCategory>,
+
+    /// Whether dry runs list the files grouped by target folder or by source folder
+    pub dry_run_view: DryRunView,
+
     /// Whether to print much more additional information during processing
     /// Not exposed in config, for dev-only
     pub debug: bool,
 
+    /// The percentage of copy and delete operations which should fail on purpose
+    /// Not exposed in config, for dev-only, set with the hidden `--chaos` CLI flag
+    pub chaos_failure_rate: u8,
+
+    /// Whether to read source folders recursively even if they are drive roots, the user's
+    /// home folder or system folders, see [find_dangerous_source_dirs]
+    /// Not exposed in config, set with the `--force-root` CLI flag
+    pub force_root: bool,
+
+    /// The source folders which are too broad to be read recursively without `--force-root`,
+    /// each with a description of why. If any are found, the program won't read any source folders
+    pub dangerous_source_dirs: Vec<String>,
+
+    /// The command line options which are unknown, are missing their value or have an invalid value,
+    /// each with a description of the problem. If any are found, the program won't read any source folders
+    pub invalid_cli_options: Vec<String>,
+
+    /// The largest file which can be written to the target folder, e.g. on FAT32 drives
+    /// Not exposed in config, detected from the filesystem of the target folder
+    pub target_max_file_size: Option<u64>,
+
     /// Whether to also include device Make in addition to the device name
     pub include_device_make: bool,
 
+    /// Whether photos taken with the different lenses of a phone are placed in the same device dir
+    /// (if the phone writes the lens in the device model) or in a separate device dir for each lens
+    pub lens_grouping: LensGrouping,
+
+    /// Whether to store a checksum for each written file in a manifest file inside its
+    /// target folder, so the archive can be checked for bit-rot later with `imgsorter verify`
+    pub write_checksums: bool,
+
+    /// Whether to save the EXIF details of each written photo, such as ISO and exposure time,
+    /// in a report inside its target folder, so the archive can be searched without a photo manager
+    pub write_exif_report: bool,
+
+    /// Whether to append a record of each operation, including skipped files and errors, to the
+    /// operation log inside the target folder, so that past runs can be audited later
+    pub write_operation_log: bool,
+
+    /// Whether to save the plan of each dry run and refuse to write the files if the next run
+    /// has a different plan, e.g. because source files were added, removed or changed in between
+    pub lock_plan: bool,
+
+    /// Set with the `--replan` option to write the files even if the plan changed since the last dry run
+    pub replan: bool,
+
+    /// Set with the `--since-last-run` option to only read the source files modified or created
+    /// since the start of the last successful run of their source folder into the target folder
+    pub since_last_run: bool,
+
+    /// Not exposed in config, the plan of the last dry run is saved next to the config file
+    pub plan_lock_path: PathBuf,
+
+    /// Not exposed in config, the plan file applied by the `apply` command, which is saved in the marker
+    /// of the run, so that an interrupted run can be resumed by applying the same plan again
+    pub applied_plan_path: Option<PathBuf>,
+
+    /// Not exposed in config, the journal of the interrupted run of the `apply` command which is resumed,
+    /// whose files are not written again, see [Args::applied_plan_path]
+    pub resumed_journal_path: Option<PathBuf>,
+
+    /// Set with the `--emit-script` option to save the operations of a dry run as a script,
+    /// whose format is chosen based on its extension, see [crate::script::ScriptFormat]
+    pub emit_script_path: Option<PathBuf>,
+
+    /// Set with the `--metrics` option to save the detailed timings of the run as JSON,
+    /// e.g. the time spent reading EXIF data, copying and hashing files, see [crate::metrics::RunMetrics]
+    pub metrics_report_path: Option<PathBuf>,
+
+    /// Set with the `--files-from` option to sort only these files, instead of all files in the source dirs.
+    /// The source dirs are then the dirs which contain these files, see [listed_file_dir]
+    pub source_files_list: Option<Vec<PathBuf>>,
+
+    /// Whether to exit right away if the path, size and modified time of all source files
+    /// are the same as in the last successful run into the same target folder
+    pub skip_unchanged_sources: bool,
+
+    /// Whether to also read the first bytes of each file to check its actual type,
+    /// instead of relying only on the file extension
+    pub detect_type_from_content: bool,
+
+    /// The patterns of dates in file names, e.g. `{year}{month}{day}` for `IMG_20190128_123456.jpg`,
+    /// tried in order for files without an EXIF date, before falling back to the modified date.
+    /// No date is read from file names if this is empty
+    pub filename_date_patterns: Vec<String>,
+
+    /// Whether the dates in file names which are written with the year last, e.g. `03-04-2021`,
+    /// have the day or the month first, see [FilenameDateOrder]
+    pub filename_date_order: FilenameDateOrder,
+
+    /// Not exposed in config, the order of the dates in file names learned for each source dir
+    /// from the files which also have an EXIF date, if [Self::filename_date_order] is `auto`
+    pub learned_filename_date_orders: HashMap<PathBuf, FilenameDateOrder>,
+
+    /// What to do with files which appear to be corrupt
+    pub corrupt_files: CorruptFilesAction,
+
+    /// What to do with files which are empty or can't be opened
+    pub unreadable_files: UnreadableFilesAction,
+
+    /// What to do with cloud placeholder files, whose contents are not stored locally
+    pub cloud_placeholders: CloudPlaceholdersAction,
+
+    /// If the target dir is an existing archive (contains the archive marker file),
+    /// refuse any options which would overwrite or delete files already inside it.
+    /// The marker file is only written into the target dir if this is enabled
+    pub protect_target: bool,
+
+    /// Which file to keep when the same photo is found as both a HEIC and a JPEG file
+    /// with the same name and date
+    pub heic_jpeg_pairs: HeicJpegPairPolicy,
+
+    /// Whether to skip photos without EXIF data which have the same dimensions as a photo
+    /// with EXIF data from the same date, since they're most likely copies re-shared via messaging apps
+    pub skip_exifless_twins: bool,
+
+    /// Whether to place photos which have the same device, capture time and dimensions
+    /// as another photo in a separate folder for review, since they are most likely re-encoded copies
+    pub mark_probable_duplicates: bool,
+
+    /// What to do with source files which have the same name and contents as another source file
+    pub duplicate_files: DuplicateFilesAction,
+
+    /// Which modified time to set on the target folders after writing files to them
+    pub target_dir_timestamps: TargetDirTimestamps,
+
+    /// What to do when a file with the same name already exists in the target folder
+    pub collision_policy: CollisionPolicy,
+
     /// EXIF-retrieved names of device models can be replaced with custom names
     /// for improved clarity, e.g. "Samsung A41" instead of "SM-A415F"
     /// This is a simple mapping from device name to custom name.
@@ -114,11 +1427,40 @@ pub struct Args {
     /// "raw" device names, i.e. those that do not have a custom name defined
     pub non_custom_device_names: HashSet<String>,
 
+    /// Extra formats of EXIF dates, for cameras which write them in another format than the standard
+    /// `YYYY:MM:DD HH:MM:SS`, tried after the standard format and after [Self::device_exif_date_formats]
+    pub exif_date_formats: Vec<String>,
+
+    /// Extra formats of EXIF dates for specific device models, with or without their make,
+    /// tried before [Self::exif_date_formats]. Keys are stored in lowercase, like [Self::custom_device_names]
+    pub device_exif_date_formats: HashMap<String, Vec<String>>,
+
+    /// Date ranges with a name, e.g. a holiday, whose files are sorted into a single folder
+    /// named after the first date and the name, instead of a folder for each date.
+    /// The ranges never overlap, see [NamedEvent::parse]
+    pub named_events: Vec<NamedEvent>,
+
     /// User-defined extensions for files to be processed which otherwise the program would skip
     pub custom_extensions: HashMap<String, Vec<String>>,
 
     /// The number of threads to use when doing threaded work like parsing source files
     pub max_threads: usize,
+
+    /// The number of threads which copy or move files, each writing a different target folder,
+    /// which is mostly useful when writing to a network drive, whose transfers are slower than the disks
+    pub write_threads: usize,
+
+    /// Write each file as soon as it's parsed, instead of parsing all files into the target tree first,
+    /// which is faster for large libraries. Only used by silent runs which write files, since there's
+    /// no preview to confirm, see [Self::is_streaming]. Pairs of files are still kept together and existing
+    /// targets are still checked, but the options which need all files can't be used with it,
+    /// see [Self::find_stream_writes_conflicts]
+    pub stream_writes: bool,
+
+    /// The size of the PAR2 recovery data created for each written date dir,
+    /// as a percentage of the files size. Set to 0 to disable.
+    /// Only used if the program is built with the `par2` feature
+    pub recovery_redundancy: u8,
 }
 
 impl Args {
@@ -134,26 +1476,104 @@ impl Args {
 
         Ok(Args {
             source_dirs: vec![vec![cwd.clone()]],
+            command: Command::Interactive,
             using_cli_source: false,
+            using_cli_target: false,
             source_dirs_count: 0,
             target_dir: cwd.clone().join(DEFAULT_TARGET_SUBDIR),
             source_recursive: DEFAULT_SOURCE_RECURSIVE,
+            exclude_dirs: DEFAULT_EXCLUDE_DIRS.iter().map(|dir| dir.to_lowercase()).collect(),
+            skip_hidden_dirs: DEFAULT_SKIP_HIDDEN_DIRS,
             min_files_per_dir: DEFAULT_MIN_COUNT,
+            min_files_per_device_dir: DEFAULT_MIN_FILES_PER_DEVICE_DIR,
+            date_dir_template: DEFAULT_DATE_DIR_TEMPLATE.to_string(),
+            video_date_dir_template: None,
+            animations_dir_name: None,
+            animation_max_seconds: DEFAULT_ANIMATION_MAX_SECONDS,
+            raw_dir_name: None,
+            documents_dir_name: None,
+            document_extensions: DEFAULT_DOCUMENT_EXTENSIONS.iter().map(|ext| ext.to_string()).collect(),
+            sidecar_extensions: DEFAULT_SIDECAR_EXTENSIONS.iter().map(|ext| ext.to_string()).collect(),
+            audio_date_dir_template: None,
+            file_name_template: None,
+            batch_dir_template: None,
+            batch_dir_name: None,
+            month_names_locale: DEFAULT_MONTH_NAMES_LOCALE,
+            season_hemisphere: DEFAULT_SEASON_HEMISPHERE,
+            date_dir_layout: DEFAULT_DATE_DIR_LAYOUT,
+            event_max_gap_days: DEFAULT_EVENT_MAX_GAP_DAYS,
+            album_from_source_folder: DEFAULT_ALBUM_FROM_SOURCE_FOLDER,
             always_create_device_subdirs: DEFAULT_ALWAYS_CREATE_DEVICE_DIR,
             compacting_threshold: DEFAULT_COMPACTING_MIN_COUNT,
             oneoffs_dir_name: String::from(DEFAULT_ONEOFFS_DIR_NAME),
             cwd,
             silent: DEFAULT_SILENT,
             copy_not_move: DEFAULT_COPY,
+            remove_empty_source_dirs: DEFAULT_REMOVE_EMPTY_SOURCE_DIRS,
+            retry_files_in_use: DEFAULT_RETRY_FILES_IN_USE,
+            watch_debounce_seconds: DEFAULT_WATCH_DEBOUNCE_SECONDS,
+            target_index: None,
             dry_run: DEFAULT_DRY_RUN,
             verbose: DEFAULT_VERBOSE,
             align_file_output: DEFAULT_ALIGN_OUTPUT,
+            show_dry_run_legend: DEFAULT_SHOW_DRY_RUN_LEGEND,
+            show_location: DEFAULT_SHOW_LOCATION,
+            show_thumbnails: DEFAULT_SHOW_THUMBNAILS,
+            copy_progress_min_size: DEFAULT_COPY_PROGRESS_MIN_MB * 1024 * 1024,
+            gpx_track: None,
+            gpx_max_gap_minutes: DEFAULT_GPX_MAX_GAP_MINUTES,
+            gpx_time_offset: None,
+            import_index_path: None,
+            import_index: ImportIndex::default(),
+            read_takeout_json: DEFAULT_READ_TAKEOUT_JSON,
+            takeout_index: TakeoutIndex::default(),
+            show_only: Vec::new(),
+            dry_run_view: DEFAULT_DRY_RUN_VIEW,
             debug: DBG_ON,
+            chaos_failure_rate: 0,
+            force_root: false,
+            dangerous_source_dirs: Vec::new(),
+            invalid_cli_options: Vec::new(),
+            target_max_file_size: None,
             include_device_make: DEFAULT_INCLUDE_DEVICE_MAKE,
+            lens_grouping: DEFAULT_LENS_GROUPING,
+            write_checksums: DEFAULT_WRITE_CHECKSUMS,
+            write_exif_report: DEFAULT_WRITE_EXIF_REPORT,
+            write_operation_log: DEFAULT_WRITE_OPERATION_LOG,
+            skip_unchanged_sources: DEFAULT_SKIP_UNCHANGED_SOURCES,
+            lock_plan: DEFAULT_LOCK_PLAN,
+            replan: false,
+            since_last_run: false,
+            plan_lock_path: PathBuf::from(PLAN_LOCK_FILE_NAME),
+            applied_plan_path: None,
+            resumed_journal_path: None,
+            emit_script_path: None,
+            metrics_report_path: None,
+            source_files_list: None,
+            detect_type_from_content: DEFAULT_DETECT_TYPE_FROM_CONTENT,
+            filename_date_patterns: DEFAULT_FILENAME_DATE_PATTERNS.iter().map(|pattern| pattern.to_string()).collect(),
+            filename_date_order: DEFAULT_FILENAME_DATE_ORDER,
+            learned_filename_date_orders: HashMap::new(),
+            corrupt_files: DEFAULT_CORRUPT_FILES_ACTION,
+            unreadable_files: DEFAULT_UNREADABLE_FILES_ACTION,
+            cloud_placeholders: DEFAULT_CLOUD_PLACEHOLDERS_ACTION,
+            protect_target: DEFAULT_PROTECT_TARGET,
+            heic_jpeg_pairs: DEFAULT_HEIC_JPEG_PAIRS,
+            skip_exifless_twins: DEFAULT_SKIP_EXIFLESS_TWINS,
+            mark_probable_duplicates: DEFAULT_MARK_PROBABLE_DUPLICATES,
+            duplicate_files: DEFAULT_DUPLICATE_FILES_ACTION,
+            target_dir_timestamps: DEFAULT_TARGET_DIR_TIMESTAMPS,
+            collision_policy: DEFAULT_COLLISION_POLICY,
             custom_device_names: HashMap::new(),
             non_custom_device_names: HashSet::new(),
+            exif_date_formats: Vec::new(),
+            device_exif_date_formats: HashMap::new(),
+            named_events: Vec::new(),
             custom_extensions,
             max_threads: DEFAULT_MAX_THREADS,
+            write_threads: DEFAULT_WRITE_THREADS,
+            stream_writes: DEFAULT_STREAM_WRITES,
+            recovery_redundancy: DEFAULT_RECOVERY_REDUNDANCY,
         })
     }
 
@@ -163,6 +1583,7 @@ impl Args {
     ///   - launch using program name only - uses source in config file
     ///   - launch using program name and "." - uses current dir as path override
     ///   - launch using program name and any path - uses that path as path override
+    ///
     /// In all cases, config file should be read from the executable location, if present,
     /// otherwise fallback to relative path, which likely will fall as well (should only work for debug builds in IDE)\
     /// and will end up not using the config file and just use the preset defaults
@@ -174,15 +1595,27 @@ impl Args {
         let mut verbose_messages: Vec<String> = Vec::new();
         let mut missing_vals: Vec<String> = Vec::new();
         let mut invalid_vals: Vec<(String, String)> = Vec::new();
+        let mut unknown_keys: Vec<String> = Vec::new();
+        let mut legacy_keys: Vec<&LegacyConfigKey> = Vec::new();
 
         let (config_file_path, message) = get_config_file_path(config_file);
         verbose_messages.push(message);
+        args.plan_lock_path = config_file_path.with_file_name(PLAN_LOCK_FILE_NAME);
+
+        if let Some(chaos_failure_rate) = get_cli_chaos_failure_rate() {
+            args.chaos_failure_rate = chaos_failure_rate;
+        }
+
+        args.command = get_cli_command();
+        args.force_root = has_cli_flag(FORCE_ROOT_CLI_FLAG);
+        args.invalid_cli_options = find_invalid_cli_options();
 
         // The program can receive a source path from the CLI, either a path directly provided by user
         // or the current working directory from the system when launched from the Windows explorer context menu
         // If we receive this, use it as both the source and target dirs and toggle the [using_cli_source] flag to skip
         // reading the source and target values from config. Otherwise, do nothing and fallback to config.
-        if let Some(cli_source) = get_cli_source_path() {
+        // The path given to the `apply` command is the plan file instead
+        if let Some(cli_source) = get_cli_path().filter(|_| args.command != Command::Apply) {
             let cli_src_path = vec![PathBuf::from(cli_source.clone())];
             match validate_source_paths(cli_src_path) {
                 Ok((valid_paths, _)) => {
@@ -190,6 +1623,7 @@ impl Args {
                     args.set_source_paths(vec![valid_paths]);
                     args.set_target_dir(cli_source);
                     args.using_cli_source = true;
+                    args.using_cli_target = true;
                 }
                 _ => {
                     let message = ColoredString::orange(format!(
@@ -199,13 +1633,85 @@ impl Args {
             }
         }
 
+        // The `--source` and `--target` options override the config values separately,
+        // so that either one can still be read from the config file
+        let cli_sources = get_cli_option_values(SOURCE_CLI_FLAG);
+        if !cli_sources.is_empty() {
+            match validate_source_paths(get_paths(cli_sources)) {
+                Err(all_invalid_sources) => {
+                    args.invalid_cli_options.push(format!(
+                        "{}: none of the source folders exist: {}", SOURCE_CLI_FLAG, paths_to_str(all_invalid_sources)));
+                }
+                Ok((valid_paths, invalid_paths)) => {
+                    if !invalid_paths.is_empty() {
+                        println!("{}", ColoredString::orange(
+                            format!(
+                                "Some source folders were invalid and were ignored:\n {}",
+                                paths_to_str(invalid_paths)).as_str()));
+                    }
+                    println!("Using source paths from the command line.");
+                    args.set_source_paths(valid_paths.into_iter().map(|path| vec![path]).collect());
+                    args.using_cli_source = true;
+                }
+            }
+        }
+
+        if let Some(cli_target) = get_cli_option_value(TARGET_CLI_FLAG) {
+            args.set_target_dir(cli_target);
+            args.using_cli_target = true;
+        }
+
+        // An explicit list of files replaces the source folders, which are then only the folders containing these files
+        if let Some(files_from) = get_cli_option_value(FILES_FROM_CLI_FLAG) {
+            let is_stdin = files_from == FILES_FROM_STDIN;
+            if args.using_cli_source {
+                args.invalid_cli_options.push(format!(
+                    "{} can't be used together with a source folder", FILES_FROM_CLI_FLAG));
+            } else if is_stdin && !args.command.is_unattended() {
+                args.invalid_cli_options.push(format!(
+                    "{} {} can only be used with the `{}` or `{}` commands, since the confirmation would be read from the same input",
+                    FILES_FROM_CLI_FLAG, FILES_FROM_STDIN, Command::Sort.name(), Command::DryRun.name()));
+            } else {
+                match read_files_list(&files_from) {
+                    Err(e) => args.invalid_cli_options.push(format!(
+                        "{}: could not read the list of files from '{}': {}", FILES_FROM_CLI_FLAG, files_from, e)),
+                    Ok(listed_files) => {
+                        let (valid_files, invalid_files): (Vec<PathBuf>, Vec<PathBuf>) = listed_files
+                            .into_iter()
+                            .partition(|listed_file| listed_file.is_file());
+
+                        if !invalid_files.is_empty() {
+                            println!("{}", ColoredString::orange(
+                                format!(
+                                    "Some listed files don't exist or are not files and were ignored:\n {}",
+                                    paths_to_str(invalid_files)).as_str()));
+                        }
+
+                        if valid_files.is_empty() {
+                            args.invalid_cli_options.push(format!(
+                                "{}: none of the listed files exist", FILES_FROM_CLI_FLAG));
+                        } else {
+                            println!("Using the list of source files from {}.",
+                                     if is_stdin { "the standard input" } else { files_from.as_str() });
+                            let listed_dirs: BTreeSet<PathBuf> = valid_files
+                                .iter()
+                                .map(|listed_file| listed_file_dir(listed_file))
+                                .collect();
+                            args.set_source_paths(listed_dirs.into_iter().map(|listed_dir| vec![listed_dir]).collect());
+                            args.using_cli_source = true;
+                            args.source_files_list = Some(valid_files);
+                        }
+                    }
+                }
+            }
+        }
+
         type TomlMap = toml::map::Map<String, toml::Value>;
 
         fn get_boolean_value(toml_table: &TomlMap, key: &str, missing_vals: &mut Vec<String>) -> Option<bool> {
             let bool_opt = toml_table
                 .get(key)
-                .map(|toml_value| toml_value.as_bool())
-                .flatten();
+                .and_then(|toml_value| toml_value.as_bool());
 
             if bool_opt.is_none() { missing_vals.push(String::from(key))  };
             bool_opt
@@ -216,8 +1722,7 @@ impl Args {
         fn get_boolean_value_silent(toml_table: &TomlMap, key: &str) -> Option<bool> {
             toml_table
                 .get(key)
-                .map(|toml_value| toml_value.as_bool())
-                .flatten()
+                .and_then(|toml_value| toml_value.as_bool())
         }
 
         // Will always return a positive integer. If the number is negative, will return None
@@ -229,8 +1734,7 @@ impl Args {
         ) -> Option<i64> {
             let value = toml_table
                 .get(key)
-                .map(|toml_value| toml_value.as_integer())
-                .flatten();
+                .and_then(|toml_value| toml_value.as_integer());
 
             match value {
                 None => {
@@ -251,8 +1755,7 @@ impl Args {
         fn get_string_value(toml_table: &TomlMap, key: &str, missing_vals: &mut Vec<String>) -> Option<String> {
             let string_opt = toml_table
                 .get(key)
-                .map(|toml_value| toml_value.as_str())
-                .flatten()
+                .and_then(|toml_value| toml_value.as_str())
                 .map(String::from);
 
             if string_opt.is_none() { missing_vals.push(String::from(key)) };
@@ -262,8 +1765,7 @@ impl Args {
         fn get_array_value(toml_table: &TomlMap, key: &str, missing_vals: &mut Vec<String>) -> Option<Vec<String>> {
             let vec_opt = toml_table
                 .get(key)
-                .map(|toml_value| toml_value.as_array())
-                .flatten()
+                .and_then(|toml_value| toml_value.as_array())
                 .map(|strings_vec| {
                     strings_vec
                         .iter()
@@ -279,8 +1781,7 @@ impl Args {
         fn get_strings_dict_value(toml_table: &TomlMap, key: &str, missing_vals: &mut Vec<String>) -> Option<HashMap<String, String>> {
             let dict_opt = toml_table
                 .get(key)
-                .map(|toml_dict|{ toml_dict.as_table()})
-                .flatten()
+                .and_then(|toml_dict|{ toml_dict.as_table()})
                 .map(|key_values| {
                     key_values
                         .into_iter()
@@ -309,10 +1810,22 @@ impl Args {
             Ok(file_contents) => {
                 println!("Using config file at: {}", &config_file_path.display().to_string());
                 match file_contents.parse::<Value>() {
-                    Ok(raw_toml) => {
+                    Ok(mut raw_toml) => {
+                        if let Some(toml_content) = raw_toml.as_table_mut() {
+                            legacy_keys = migrate_legacy_config_keys(toml_content);
+
+                            // Values from the overlay file replace those from the main config file
+                            if let Some(mut overlay_toml) = read_config_overlay(&config_file_path) {
+                                legacy_keys.extend(migrate_legacy_config_keys(&mut overlay_toml));
+                                merge_config_overlay(toml_content, overlay_toml);
+                            }
+                        }
+
                         match raw_toml.as_table() {
                             Some(toml_content) => {
 
+                                unknown_keys = find_unknown_config_keys(toml_content);
+
                                 /* --- Parse source/target folders --- */
 
                                 match toml_content.get("folders") {
@@ -320,19 +1833,18 @@ impl Args {
                                         if let Some(folders) = folders_opt.as_table() {
 
                                             fn print_source_folders_help() {
-                                                println!("{}",
-                                                    format!(
-                                                        // TODO 5f: use OS-specific path separators
-                                                        "{}\n-----{}\n{}\n{}\n{}\n{}\n-----",
-                                                        "Edit imgsorter.toml and add valid source folders like this:",
-                                                        "[folders]",
-                                                        "source_dirs = [",
-                                                        "  'D:\\Example dir\\Pictures',",
-                                                        "  'E:\\My dir\\Pictures',",
-                                                        "]"));
-                                            }
-
-                                            // Use config source and target paths only if we didn't receive a CLI path override
+                                                // TODO 5f: use OS-specific path separators
+                                                println!("{}", [
+                                                    "Edit imgsorter.toml and add valid source folders like this:",
+                                                    "-----[folders]",
+                                                    "source_dirs = [",
+                                                    "  'D:\\Example dir\\Pictures',",
+                                                    "  'E:\\My dir\\Pictures',",
+                                                    "]",
+                                                    "-----"].join("\n"));
+                                            }
+
+                                            // Use config source and target paths only if we didn't receive CLI path overrides
                                             if !args.using_cli_source {
                                                 // If no valid source paths are found, use current working directory and print a red warning
                                                 // otherwise, use whatever sources are valid and print a yellow warning for the rest
@@ -360,99 +1872,485 @@ impl Args {
                                                                 print_source_folders_help()
                                                             }
 
-                                                            let path_vecs: Vec<Vec<PathBuf>> =
-                                                                valid_paths
-                                                                    .into_iter()
-                                                                    .map(|path|vec![path])
-                                                                    .collect();
+                                                            let path_vecs: Vec<Vec<PathBuf>> =
+                                                                valid_paths
+                                                                    .into_iter()
+                                                                    .map(|path|vec![path])
+                                                                    .collect();
+
+                                                            args.set_source_paths(path_vecs);
+                                                        }
+                                                    }
+                                                } else {
+                                                    println!("{}", ColoredString::red("No source folders found!"));
+                                                    print_source_folders_help();
+                                                    println!("Using current working directory for now: {}", args.source_dirs[0][0].display());
+                                                }
+
+                                                // Not exposed in config; use for dev only
+                                                // source_subdir = 'test_pics'
+                                                if let Some(source_subdir) = get_string_value(folders, "source_subdir", &mut missing_vals) {
+                                                    // get_string_value already filters out empty strings, but just to be safe
+                                                    if !source_subdir.is_empty() {
+                                                        args.append_source_subdir(source_subdir.as_str());
+                                                    }
+                                                }
+
+                                            } // end if !args.using_cli_source
+
+                                            if !args.using_cli_target {
+                                                if let Some(target_dir) = get_string_value(folders, "target_dir", &mut missing_vals) {
+                                                    // get_string_value already filters out empty strings, but just to be safe
+                                                    if !target_dir.is_empty() {
+                                                        args.set_target_dir(target_dir);
+                                                    }
+                                                }
+                                            }
+
+                                            if let Some(min_files_per_dir) = get_positive_integer_value(folders, "min_files_per_dir", &mut missing_vals, &mut invalid_vals) {
+                                                args.min_files_per_dir = min_files_per_dir;
+                                            }
+
+                                            if let Some(min_files_per_device_dir) = get_positive_integer_value(folders, "min_files_per_device_dir", &mut missing_vals, &mut invalid_vals) {
+                                                args.min_files_per_device_dir = min_files_per_device_dir as usize;
+                                            }
+
+                                            if let Some(exclude_dirs) = get_array_value(folders, "exclude_dirs", &mut missing_vals) {
+                                                args.exclude_dirs = exclude_dirs
+                                                    .iter()
+                                                    .filter(|dir| !dir.trim().is_empty())
+                                                    .map(|dir| dir.trim().to_lowercase())
+                                                    .collect();
+                                            }
+
+                                            if let Some(skip_hidden_dirs) = get_boolean_value(folders, "skip_hidden_dirs", &mut missing_vals) {
+                                                args.skip_hidden_dirs = skip_hidden_dirs;
+                                            }
+
+                                            if let Some(compacting_threshold) = get_positive_integer_value(folders, "min_files_before_compacting_output", &mut missing_vals, &mut invalid_vals) {
+                                                args.compacting_threshold = compacting_threshold as usize;
+                                            }
+
+                                            if let Some(date_dir_template) = get_string_value(folders, "date_dir_template", &mut missing_vals) {
+                                                match validate_date_dir_template(&date_dir_template) {
+                                                    Ok(_) => args.date_dir_template = date_dir_template,
+                                                    Err(message) => invalid_vals.push((String::from("date_dir_template"), message)),
+                                                }
+                                            }
+
+                                            // These are optional, so don't report them as missing
+                                            for (key, type_template) in [
+                                                ("video_date_dir_template", &mut args.video_date_dir_template),
+                                                ("audio_date_dir_template", &mut args.audio_date_dir_template),
+                                            ] {
+                                                if let Some(template) = folders.get(key).and_then(|value| value.as_str()).filter(|value| !value.is_empty()) {
+                                                    match validate_date_dir_template(template) {
+                                                        Ok(_) => *type_template = Some(template.to_string()),
+                                                        Err(message) => invalid_vals.push((String::from(key), message)),
+                                                    }
+                                                }
+                                            }
+
+                                            // This is optional, so don't report it as missing
+                                            if let Some(template) = folders
+                                                .get("file_name_template")
+                                                .and_then(|value| value.as_str())
+                                                .map(|value| value.trim())
+                                                .filter(|value| !value.is_empty())
+                                            {
+                                                match validate_file_name_template(template) {
+                                                    Ok(_) => args.file_name_template = Some(template.to_string()),
+                                                    Err(message) => invalid_vals.push((String::from("file_name_template"), message)),
+                                                }
+                                            }
+
+                                            // This is optional, so don't report it as missing
+                                            if let Some(template) = folders
+                                                .get("batch_subdir")
+                                                .and_then(|value| value.as_str())
+                                                .map(|value| value.trim())
+                                                .filter(|value| !value.is_empty())
+                                            {
+                                                match validate_batch_dir_template(template) {
+                                                    Ok(_) => args.batch_dir_template = Some(template.to_string()),
+                                                    Err(message) => invalid_vals.push((String::from("batch_subdir"), message)),
+                                                }
+                                            }
+
+                                            // This is optional, so don't report it as missing
+                                            args.animations_dir_name = folders
+                                                .get("animations_subdir_name")
+                                                .and_then(|value| value.as_str())
+                                                .map(|value| value.trim().to_string())
+                                                .filter(|value| !value.is_empty());
+
+                                            if let Some(animation_max_seconds) = get_positive_integer_value(folders, "animation_max_seconds", &mut missing_vals, &mut invalid_vals) {
+                                                args.animation_max_seconds = animation_max_seconds as u32;
+                                            }
+
+                                            // This is optional, so don't report it as missing
+                                            args.raw_dir_name = folders
+                                                .get("raw_subdir_name")
+                                                .and_then(|value| value.as_str())
+                                                .map(|value| value.trim().to_string())
+                                                .filter(|value| !value.is_empty());
+
+                                            // This is optional, so don't report it as missing
+                                            args.documents_dir_name = folders
+                                                .get("documents_dir_name")
+                                                .and_then(|value| value.as_str())
+                                                .map(|value| value.trim().to_string())
+                                                .filter(|value| !value.is_empty());
+
+                                            if let Some(document_extensions) = get_array_value(folders, "document_extensions", &mut missing_vals) {
+                                                args.document_extensions = document_extensions
+                                                    .iter()
+                                                    .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+                                                    .filter(|ext| !ext.is_empty())
+                                                    .collect();
+                                            }
+
+                                            if let Some(sidecar_extensions) = get_array_value(folders, "sidecar_extensions", &mut missing_vals) {
+                                                args.sidecar_extensions = sidecar_extensions
+                                                    .iter()
+                                                    .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+                                                    .filter(|ext| !ext.is_empty())
+                                                    .collect();
+                                            }
+
+                                            if let Some(month_names_locale) = get_string_value(folders, "month_names_locale", &mut missing_vals) {
+                                                match MonthNameLocale::from_name(&month_names_locale) {
+                                                    Some(locale) => args.month_names_locale = locale,
+                                                    None => invalid_vals.push((
+                                                        String::from("month_names_locale"),
+                                                        String::from("Value must be one of 'en', 'de', 'fr', 'es', 'it', 'pt', 'nl' or 'ro'"),
+                                                    )),
+                                                }
+                                            }
+
+                                            if let Some(season_hemisphere) = get_string_value(folders, "season_hemisphere", &mut missing_vals) {
+                                                match season_hemisphere.to_lowercase().as_str() {
+                                                    "north" => args.season_hemisphere = Hemisphere::Northern,
+                                                    "south" => args.season_hemisphere = Hemisphere::Southern,
+                                                    _ => invalid_vals.push((
+                                                        String::from("season_hemisphere"),
+                                                        String::from("Value must be either 'north' or 'south'"),
+                                                    )),
+                                                }
+                                            }
+
+                                            if let Some(date_dir_layout) = get_string_value(folders, "date_dir_layout", &mut missing_vals) {
+                                                match date_dir_layout.to_lowercase().as_str() {
+                                                    "flat" => args.date_dir_layout = DateDirLayout::Flat,
+                                                    "year" => args.date_dir_layout = DateDirLayout::Year,
+                                                    "year_month" => args.date_dir_layout = DateDirLayout::YearMonth,
+                                                    _ => invalid_vals.push((
+                                                        String::from("date_dir_layout"),
+                                                        String::from("Value must be one of 'flat', 'year' or 'year_month'"),
+                                                    )),
+                                                }
+                                            }
+
+                                            if let Some(event_max_gap_days) = get_positive_integer_value(folders, "event_max_gap_days", &mut missing_vals, &mut invalid_vals) {
+                                                args.event_max_gap_days = event_max_gap_days as usize;
+                                            }
+
+                                            if let Some(album_from_source_folder) = get_boolean_value(folders, "album_from_source_folder", &mut missing_vals) {
+                                                args.album_from_source_folder = album_from_source_folder;
+                                            }
+
+                                            if let Some(oneoffs_dir_name) = get_string_value(folders, "target_oneoffs_subdir_name", &mut missing_vals) {
+                                                // get_string_value already filters out empty strings, but just to be safe
+                                                if !oneoffs_dir_name.is_empty() {
+                                                    args.oneoffs_dir_name = oneoffs_dir_name;
+                                                }
+                                            }
+                                        } // end if let Some(folders)
+                                    } // end Some(folders_opt)
+                                    None =>
+                                      missing_vals.push(String::from("folders"))
+                                } // end config folders
+
+                                /* --- Parse options --- */
+
+                                match toml_content.get("options") {
+                                    Some(options_opt) => {
+                                        if let Some(options) = options_opt.as_table() {
+
+                                            // Not exposed in config; use for dev only
+                                            // debug_on = true
+                                            if let Some(debug_on) = get_boolean_value_silent(options, "debug") {
+                                                args.debug = debug_on;
+                                                args.verbose = debug_on;
+                                            } else if let Some(verbose) = get_boolean_value(options, "verbose", &mut missing_vals) {
+                                                args.verbose = verbose;
+                                            }
+
+                                            if let Some(source_recursive) = get_boolean_value(options, "source_recursive", &mut missing_vals) {
+                                                args.source_recursive = source_recursive;
+                                            }
+
+                                            if let Some(dry_run) = get_boolean_value(options, "dry_run", &mut missing_vals) {
+                                                args.dry_run = dry_run;
+                                            }
+
+                                            if let Some(align_file_output) = get_boolean_value(options, "align_file_output", &mut missing_vals) {
+                                                args.align_file_output = align_file_output;
+                                            }
+
+                                            if let Some(show_dry_run_legend) = get_boolean_value(options, "show_dry_run_legend", &mut missing_vals) {
+                                                args.show_dry_run_legend = show_dry_run_legend;
+                                            }
+
+                                            if let Some(show_location) = get_boolean_value(options, "show_location", &mut missing_vals) {
+                                                args.show_location = show_location;
+                                            }
+
+                                            if let Some(show_thumbnails) = get_boolean_value(options, "show_thumbnails", &mut missing_vals) {
+                                                args.show_thumbnails = show_thumbnails;
+                                            }
+
+                                            if let Some(copy_progress_min_mb) = get_positive_integer_value(options, "copy_progress_min_mb", &mut missing_vals, &mut invalid_vals) {
+                                                args.copy_progress_min_size = copy_progress_min_mb as u64 * 1024 * 1024;
+                                            }
+
+                                            if let Some(gpx_files) = get_array_value(options, "gpx_files", &mut missing_vals) {
+                                                let gpx_paths: Vec<PathBuf> = gpx_files
+                                                    .iter()
+                                                    .filter(|gpx_file| !gpx_file.trim().is_empty())
+                                                    .map(PathBuf::from)
+                                                    .collect();
+                                                if !gpx_paths.is_empty() {
+                                                    match GpxTrack::read(&gpx_paths) {
+                                                        Ok(gpx_track) => args.gpx_track = Some(gpx_track),
+                                                        Err(message) => invalid_vals.push((String::from("gpx_files"), message)),
+                                                    }
+                                                }
+                                            }
+
+                                            if let Some(gpx_max_gap_minutes) = get_positive_integer_value(options, "gpx_max_gap_minutes", &mut missing_vals, &mut invalid_vals) {
+                                                args.gpx_max_gap_minutes = gpx_max_gap_minutes as u32;
+                                            }
+
+                                            // This is optional, so don't report it as missing
+                                            if let Some(gpx_time_offset) = options.get("gpx_time_offset").and_then(|value| value.as_str()).filter(|value| !value.is_empty()) {
+                                                match parse_utc_offset(gpx_time_offset) {
+                                                    Some(offset) => args.gpx_time_offset = Some(offset),
+                                                    None => invalid_vals.push((
+                                                        String::from("gpx_time_offset"),
+                                                        String::from("Value must be an offset from UTC, e.g. '+02:00' or '-05:30'"),
+                                                    )),
+                                                }
+                                            }
+
+                                            // This is optional, so don't report it as missing
+                                            if let Some(import_index) = options.get("import_index").and_then(|value| value.as_str()).filter(|value| !value.trim().is_empty()) {
+                                                let import_index_path = PathBuf::from(import_index.trim());
+                                                match ImportIndex::read(&import_index_path) {
+                                                    Ok(import_index) => {
+                                                        args.import_index = import_index;
+                                                        args.import_index_path = Some(import_index_path);
+                                                    }
+                                                    Err(message) => invalid_vals.push((String::from("import_index"), message)),
+                                                }
+                                            }
+
+                                            if let Some(read_takeout_json) = get_boolean_value(options, "read_takeout_json", &mut missing_vals) {
+                                                args.read_takeout_json = read_takeout_json;
+                                            }
+
+                                            if let Some(show_only) = get_array_value(options, "show_only", &mut missing_vals) {
+                                                match parse_status_categories(&show_only) {
+                                                    Ok(categories) => args.show_only = categories,
+                                                    Err(invalid_category) => invalid_vals.push((
+                                                        String::from("show_only"),
+                                                        format!("Unknown status '{}'. Values must be 'written', 'skipped' or 'errors'", invalid_category),
+                                                    )),
+                                                }
+                                            }
+
+                                            if let Some(dry_run_view) = get_string_value(options, "dry_run_view", &mut missing_vals) {
+                                                match dry_run_view.to_lowercase().as_str() {
+                                                    "target" => args.dry_run_view = DryRunView::Target,
+                                                    "source" => args.dry_run_view = DryRunView::Source,
+                                                    _ => invalid_vals.push((
+                                                        String::from("dry_run_view"),
+                                                        String::from("Value must be either 'target' or 'source'"),
+                                                    )),
+                                                }
+                                            }
+
+                                            if let Some(include_device_make) = get_boolean_value(options, "include_device_make", &mut missing_vals) {
+                                                args.include_device_make = include_device_make;
+                                            }
+
+                                            if let Some(lens_grouping) = get_string_value(options, "lens_grouping", &mut missing_vals) {
+                                                match lens_grouping.to_lowercase().as_str() {
+                                                    "ignore" => args.lens_grouping = LensGrouping::Ignore,
+                                                    "merge" => args.lens_grouping = LensGrouping::Merge,
+                                                    "split" => args.lens_grouping = LensGrouping::Split,
+                                                    _ => invalid_vals.push((
+                                                        String::from("lens_grouping"),
+                                                        String::from("Value must be one of 'ignore', 'merge' or 'split'"),
+                                                    )),
+                                                }
+                                            }
+
+                                            if let Some(always_create_device_subdirs) = get_boolean_value(options, "always_create_device_subdirs", &mut missing_vals) {
+                                                args.always_create_device_subdirs = always_create_device_subdirs;
+                                            }
+
+                                            if let Some(copy_not_move) = get_boolean_value(options, "copy_not_move", &mut missing_vals) {
+                                                args.copy_not_move = copy_not_move;
+                                            }
+
+                                            if let Some(remove_empty_source_dirs) = get_boolean_value(options, "remove_empty_source_dirs", &mut missing_vals) {
+                                                args.remove_empty_source_dirs = remove_empty_source_dirs;
+                                            }
+
+                                            if let Some(retry_files_in_use) = get_boolean_value(options, "retry_files_in_use", &mut missing_vals) {
+                                                args.retry_files_in_use = retry_files_in_use;
+                                            }
+
+                                            if let Some(watch_debounce_seconds) = get_positive_integer_value(options, "watch_debounce_seconds", &mut missing_vals, &mut invalid_vals) {
+                                                args.watch_debounce_seconds = watch_debounce_seconds as u64;
+                                            }
+
+                                            if let Some(silent) = get_boolean_value(options, "silent", &mut missing_vals) {
+                                                args.silent = silent;
+                                            }
+
+                                            if let Some(write_checksums) = get_boolean_value(options, "write_checksums", &mut missing_vals) {
+                                                args.write_checksums = write_checksums;
+                                            }
+
+                                            if let Some(write_exif_report) = get_boolean_value(options, "write_exif_report", &mut missing_vals) {
+                                                args.write_exif_report = write_exif_report;
+                                            }
 
-                                                            args.set_source_paths(path_vecs);
-                                                        }
-                                                    }
-                                                } else {
-                                                    println!("{}", ColoredString::red("No source folders found!"));
-                                                    print_source_folders_help();
-                                                    println!("Using current working directory for now: {}", args.source_dirs[0][0].display());
-                                                }
+                                            if let Some(write_operation_log) = get_boolean_value(options, "write_operation_log", &mut missing_vals) {
+                                                args.write_operation_log = write_operation_log;
+                                            }
 
-                                                // Not exposed in config; use for dev only
-                                                // source_subdir = 'test_pics'
-                                                if let Some(source_subdir) = get_string_value(folders, "source_subdir", &mut missing_vals) {
-                                                    // get_string_value already filters out empty strings, but just to be safe
-                                                    if !source_subdir.is_empty() {
-                                                        args.append_source_subdir(source_subdir.as_str());
-                                                    }
-                                                }
+                                            if let Some(lock_plan) = get_boolean_value(options, "lock_plan", &mut missing_vals) {
+                                                args.lock_plan = lock_plan;
+                                            }
 
-                                                if let Some(target_dir) = get_string_value(folders, "target_dir", &mut missing_vals) {
-                                                    // get_string_value already filters out empty strings, but just to be safe
-                                                    if !target_dir.is_empty() {
-                                                        args.set_target_dir(target_dir);
-                                                    }
-                                                }
-                                            } // end if !args.using_cli_source
+                                            if let Some(skip_unchanged_sources) = get_boolean_value(options, "skip_unchanged_sources", &mut missing_vals) {
+                                                args.skip_unchanged_sources = skip_unchanged_sources;
+                                            }
 
-                                            if let Some(min_files_per_dir) = get_positive_integer_value(folders, "min_files_per_dir", &mut missing_vals, &mut invalid_vals) {
-                                                args.min_files_per_dir = min_files_per_dir;
+                                            if let Some(detect_type_from_content) = get_boolean_value(options, "detect_type_from_content", &mut missing_vals) {
+                                                args.detect_type_from_content = detect_type_from_content;
                                             }
 
-                                            if let Some(compacting_threshold) = get_positive_integer_value(folders, "min_files_before_compacting_output", &mut missing_vals, &mut invalid_vals) {
-                                                args.compacting_threshold = compacting_threshold as usize;
+                                            if let Some(filename_date_patterns) = get_array_value(options, "filename_date_patterns", &mut missing_vals) {
+                                                match filename_date_patterns.iter().map(|pattern| validate_filename_date_pattern(pattern)).find_map(Result::err) {
+                                                    Some(error) => invalid_vals.push((String::from("filename_date_patterns"), error)),
+                                                    None => args.filename_date_patterns = filename_date_patterns,
+                                                }
                                             }
 
-                                            if let Some(oneoffs_dir_name) = get_string_value(folders, "target_oneoffs_subdir_name", &mut missing_vals) {
-                                                // get_string_value already filters out empty strings, but just to be safe
-                                                if !oneoffs_dir_name.is_empty() {
-                                                    args.oneoffs_dir_name = oneoffs_dir_name;
+                                            if let Some(filename_date_order) = get_string_value(options, "filename_date_order", &mut missing_vals) {
+                                                match filename_date_order.to_lowercase().as_str() {
+                                                    "auto" => args.filename_date_order = FilenameDateOrder::Auto,
+                                                    "day_first" => args.filename_date_order = FilenameDateOrder::DayFirst,
+                                                    "month_first" => args.filename_date_order = FilenameDateOrder::MonthFirst,
+                                                    _ => invalid_vals.push((
+                                                        String::from("filename_date_order"),
+                                                        String::from("Value must be one of 'auto', 'day_first' or 'month_first'"),
+                                                    )),
                                                 }
                                             }
-                                        } // end if let Some(folders)
-                                    } // end Some(folders_opt)
-                                    None =>
-                                      missing_vals.push(String::from("folders"))
-                                } // end config folders
 
-                                /* --- Parse options --- */
+                                            if let Some(corrupt_files) = get_string_value(options, "corrupt_files", &mut missing_vals) {
+                                                match corrupt_files.to_lowercase().as_str() {
+                                                    "quarantine" => args.corrupt_files = CorruptFilesAction::Quarantine,
+                                                    "skip" => args.corrupt_files = CorruptFilesAction::Skip,
+                                                    "ignore" => args.corrupt_files = CorruptFilesAction::Ignore,
+                                                    _ => invalid_vals.push((
+                                                        String::from("corrupt_files"),
+                                                        String::from("Value must be one of 'quarantine', 'skip' or 'ignore'"),
+                                                    )),
+                                                }
+                                            }
 
-                                match toml_content.get("options") {
-                                    Some(options_opt) => {
-                                        if let Some(options) = options_opt.as_table() {
+                                            if let Some(unreadable_files) = get_string_value(options, "unreadable_files", &mut missing_vals) {
+                                                match unreadable_files.to_lowercase().as_str() {
+                                                    "quarantine" => args.unreadable_files = UnreadableFilesAction::Quarantine,
+                                                    "skip" => args.unreadable_files = UnreadableFilesAction::Skip,
+                                                    _ => invalid_vals.push((
+                                                        String::from("unreadable_files"),
+                                                        String::from("Value must be either 'quarantine' or 'skip'"),
+                                                    )),
+                                                }
+                                            }
 
-                                            // Not exposed in config; use for dev only
-                                            // debug_on = true
-                                            if let Some(debug_on) = get_boolean_value_silent(options, "debug") {
-                                                args.debug = debug_on;
-                                                args.verbose = debug_on;
-                                            } else if let Some(verbose) = get_boolean_value(options, "verbose", &mut missing_vals) {
-                                                args.verbose = verbose;
+                                            if let Some(cloud_placeholders) = get_string_value(options, "cloud_placeholders", &mut missing_vals) {
+                                                match cloud_placeholders.to_lowercase().as_str() {
+                                                    "skip" => args.cloud_placeholders = CloudPlaceholdersAction::Skip,
+                                                    "download" => args.cloud_placeholders = CloudPlaceholdersAction::Download,
+                                                    _ => invalid_vals.push((
+                                                        String::from("cloud_placeholders"),
+                                                        String::from("Value must be one of 'skip' or 'download'"),
+                                                    )),
+                                                }
                                             }
 
-                                            if let Some(source_recursive) = get_boolean_value(options, "source_recursive", &mut missing_vals) {
-                                                args.source_recursive = source_recursive;
+                                            if let Some(protect_target) = get_boolean_value(options, "protect_target", &mut missing_vals) {
+                                                args.protect_target = protect_target;
                                             }
 
-                                            if let Some(dry_run) = get_boolean_value(options, "dry_run", &mut missing_vals) {
-                                                args.dry_run = dry_run;
+                                            if let Some(heic_jpeg_pairs) = get_string_value(options, "heic_jpeg_pairs", &mut missing_vals) {
+                                                match heic_jpeg_pairs.to_lowercase().as_str() {
+                                                    "keep_heic" => args.heic_jpeg_pairs = HeicJpegPairPolicy::KeepHeic,
+                                                    "keep_jpeg" => args.heic_jpeg_pairs = HeicJpegPairPolicy::KeepJpeg,
+                                                    "keep_both" => args.heic_jpeg_pairs = HeicJpegPairPolicy::KeepBoth,
+                                                    _ => invalid_vals.push((
+                                                        String::from("heic_jpeg_pairs"),
+                                                        String::from("Value must be one of 'keep_heic', 'keep_jpeg' or 'keep_both'"),
+                                                    )),
+                                                }
                                             }
 
-                                            if let Some(align_file_output) = get_boolean_value(options, "align_file_output", &mut missing_vals) {
-                                                args.align_file_output = align_file_output;
+                                            if let Some(skip_exifless_twins) = get_boolean_value(options, "skip_exifless_twins", &mut missing_vals) {
+                                                args.skip_exifless_twins = skip_exifless_twins;
                                             }
 
-                                            if let Some(include_device_make) = get_boolean_value(options, "include_device_make", &mut missing_vals) {
-                                                args.include_device_make = include_device_make;
+                                            if let Some(mark_probable_duplicates) = get_boolean_value(options, "mark_probable_duplicates", &mut missing_vals) {
+                                                args.mark_probable_duplicates = mark_probable_duplicates;
                                             }
 
-                                            if let Some(always_create_device_subdirs) = get_boolean_value(options, "always_create_device_subdirs", &mut missing_vals) {
-                                                args.always_create_device_subdirs = always_create_device_subdirs;
+                                            if let Some(duplicate_files) = get_string_value(options, "duplicate_files", &mut missing_vals) {
+                                                match DuplicateFilesAction::from_name(duplicate_files.to_lowercase().as_str()) {
+                                                    Some(duplicate_files) => args.duplicate_files = duplicate_files,
+                                                    None => invalid_vals.push((
+                                                        String::from("duplicate_files"),
+                                                        String::from("Value must be one of 'skip' or 'review'"),
+                                                    )),
+                                                }
                                             }
 
-                                            if let Some(copy_not_move) = get_boolean_value(options, "copy_not_move", &mut missing_vals) {
-                                                args.copy_not_move = copy_not_move;
+                                            if let Some(target_dir_timestamps) = get_string_value(options, "target_dir_timestamps", &mut missing_vals) {
+                                                match target_dir_timestamps.to_lowercase().as_str() {
+                                                    "unchanged" => args.target_dir_timestamps = TargetDirTimestamps::Unchanged,
+                                                    "folder_date" => args.target_dir_timestamps = TargetDirTimestamps::FolderDate,
+                                                    "newest_file" => args.target_dir_timestamps = TargetDirTimestamps::NewestFile,
+                                                    _ => invalid_vals.push((
+                                                        String::from("target_dir_timestamps"),
+                                                        String::from("Value must be one of 'unchanged', 'folder_date' or 'newest_file'"),
+                                                    )),
+                                                }
                                             }
 
-                                            if let Some(silent) = get_boolean_value(options, "silent", &mut missing_vals) {
-                                                args.silent = silent;
+                                            if let Some(collision_policy) = get_string_value(options, "collision_policy", &mut missing_vals) {
+                                                match CollisionPolicy::from_name(&collision_policy) {
+                                                    Some(collision_policy) => args.collision_policy = collision_policy,
+                                                    None => invalid_vals.push((
+                                                        String::from("collision_policy"),
+                                                        String::from("Value must be one of 'skip', 'overwrite', 'rename', 'review' or 'keep_larger'"),
+                                                    )),
+                                                }
                                             }
                                         }
                                     }
@@ -469,6 +2367,49 @@ impl Args {
                                                 args.custom_device_names = devices_dict;
                                             }
 
+                                            // These are optional, so don't report them as missing
+                                            if let Some(exif_date_formats) = custom_data.get("exif_date_formats").and_then(|value| value.as_array()) {
+                                                match parse_date_formats(exif_date_formats) {
+                                                    Ok(formats) => args.exif_date_formats = formats,
+                                                    Err(msg) => invalid_vals.push((String::from("exif_date_formats"), msg)),
+                                                }
+                                            }
+                                            if let Some(device_formats) = custom_data.get("device_exif_date_formats").and_then(|value| value.as_table()) {
+                                                for (device_name, formats) in device_formats {
+                                                    let formats = formats
+                                                        .as_array()
+                                                        .ok_or_else(|| format!("The formats of '{}' must be a list", device_name))
+                                                        .and_then(|formats| parse_date_formats(formats));
+                                                    match formats {
+                                                        Ok(formats) => { args.device_exif_date_formats.insert(device_name.to_lowercase(), formats); }
+                                                        Err(msg) => invalid_vals.push((String::from("device_exif_date_formats"), msg)),
+                                                    }
+                                                }
+                                            }
+
+                                            if let Some(events_dict) = get_strings_dict_value(custom_data, "events", &mut missing_vals) {
+                                                let mut named_events: Vec<NamedEvent> = Vec::new();
+                                                for (date_range, name) in events_dict {
+                                                    match NamedEvent::parse(&date_range, &name) {
+                                                        Ok(event) => named_events.push(event),
+                                                        Err(msg) => invalid_vals.push((String::from("events"), msg)),
+                                                    }
+                                                }
+                                                named_events.sort_by_key(|event| event.first_date);
+
+                                                // A date in two events would have to be sorted into both folders
+                                                let overlapping_events = named_events
+                                                    .windows(2)
+                                                    .filter(|events| events[1].first_date <= events[0].last_date)
+                                                    .map(|events| format!("'{}' overlaps '{}'", events[1].name, events[0].name))
+                                                    .collect::<Vec<_>>();
+                                                if overlapping_events.is_empty() {
+                                                    args.named_events = named_events;
+                                                } else {
+                                                    invalid_vals.push((String::from("events"), overlapping_events.join(", ")));
+                                                }
+                                            }
+
                                             match custom_data.get("extensions") {
                                                 Some(custom_extensions_opt) => {
                                                     if let Some(custom_extensions) = custom_extensions_opt.as_table() {
@@ -503,6 +2444,25 @@ impl Args {
                                             if let Some(max_threads) = get_positive_integer_value(advanced, "max_threads", &mut missing_vals, &mut invalid_vals) {
                                                 args.max_threads = max_threads as usize;
                                             }
+
+                                            if let Some(write_threads) = get_positive_integer_value(advanced, "write_threads", &mut missing_vals, &mut invalid_vals) {
+                                                args.write_threads = write_threads as usize;
+                                            }
+
+                                            if let Some(stream_writes) = get_boolean_value(advanced, "stream_writes", &mut missing_vals) {
+                                                args.stream_writes = stream_writes;
+                                            }
+
+                                            if let Some(recovery_redundancy) = get_positive_integer_value(advanced, "recovery_redundancy", &mut missing_vals, &mut invalid_vals) {
+                                                if recovery_redundancy > 100 {
+                                                    invalid_vals.push((
+                                                        String::from("recovery_redundancy"),
+                                                        String::from("Number must be between 0 and 100"),
+                                                    ));
+                                                } else {
+                                                    args.recovery_redundancy = recovery_redundancy as u8;
+                                                }
+                                            }
                                         }
                                     },
 
@@ -531,6 +2491,26 @@ impl Args {
             }
         };
 
+        // Old config files still work, but suggest the new names once
+        if !legacy_keys.is_empty() {
+            println!("{}", ColoredString::orange(format!(
+                "> The config file uses some old key names, please rename them: {}",
+                legacy_keys
+                    .iter()
+                    .map(|key| format!("'{}' to '{}' in [{}]", key.old_name, key.new_name, key.section))
+                    .collect::<Vec<_>>()
+                    .join(", ")).as_str()));
+        }
+
+        // Unknown keys are most likely typos, so always print them
+        unknown_keys.iter().for_each(|key|
+            println!("{}", ColoredString::orange(format!(
+                "> Config key '{}' is not recognized and will be ignored. Run `imgsorter help-config` to list all keys.", key).as_str()))
+        );
+
+        // Command line options override the config file
+        args.apply_cli_options();
+
         // Print missing and invalid values
         if args.verbose {
             missing_vals.iter().for_each(|key|
@@ -542,9 +2522,17 @@ impl Args {
             });
         }
 
+        // Reading a drive root or the home folder recursively would walk through most of the disk,
+        // which is most likely a mistake (e.g. the program was launched from the wrong folder)
+        if args.source_recursive && !args.force_root && args.source_files_list.is_none() {
+            args.dangerous_source_dirs = find_dangerous_source_dirs(&args);
+        }
+
         // Once all source folders and options are read, check if we need to
         // recursively read subdirectories and set all sources
-        if args.source_recursive {
+        // Undoing a run only needs the target folder, so there's no need to read the sources
+        if args.source_recursive && args.dangerous_source_dirs.is_empty() && args.invalid_cli_options.is_empty()
+            && args.command != Command::Undo && args.source_files_list.is_none() {
 
             if args.verbose { println!("> Fetching source directories list recursively..."); }
             let _time_fetching_dirs = Instant::now();
@@ -569,6 +2557,100 @@ impl Args {
         Ok(args)
     }
 
+    /// Override the config values with the values of the command line options, if provided.
+    /// The source and target folders are read before the config file, see [Args::new_from_toml]
+    fn apply_cli_options(&mut self) {
+        if has_cli_flag(DRY_RUN_CLI_FLAG) {
+            self.dry_run = true;
+        }
+        if has_cli_flag(WRITE_CLI_FLAG) {
+            self.dry_run = false;
+        }
+        if has_cli_flag(COPY_CLI_FLAG) {
+            self.copy_not_move = true;
+        }
+        if has_cli_flag(MOVE_CLI_FLAG) {
+            self.copy_not_move = false;
+        }
+        if has_cli_flag(SILENT_CLI_FLAG) {
+            self.silent = true;
+        }
+        if has_cli_flag(RECURSIVE_CLI_FLAG) {
+            self.source_recursive = true;
+        }
+        if has_cli_flag(NO_RECURSIVE_CLI_FLAG) {
+            self.source_recursive = false;
+        }
+        if has_cli_flag(REPLAN_CLI_FLAG) {
+            self.replan = true;
+        }
+        if has_cli_flag(SINCE_LAST_RUN_CLI_FLAG) {
+            self.since_last_run = true;
+        }
+        if has_cli_flag(THUMBNAILS_CLI_FLAG) {
+            self.show_thumbnails = true;
+        }
+        if let Some(script_path) = get_cli_option_value(EMIT_SCRIPT_CLI_FLAG) {
+            self.emit_script_path = Some(PathBuf::from(script_path));
+        }
+        if let Some(metrics_path) = get_cli_option_value(METRICS_CLI_FLAG) {
+            self.metrics_report_path = Some(PathBuf::from(metrics_path));
+        }
+
+        // Commands which don't ask for confirmation decide the operation themselves
+        match self.command {
+            Command::Sort | Command::Watch if has_cli_flag(DRY_RUN_CLI_FLAG) => self.invalid_cli_options.push(format!(
+                "{} can't be used with the `{}` command", DRY_RUN_CLI_FLAG, self.command.name())),
+            Command::DryRun if has_cli_flag(WRITE_CLI_FLAG) => self.invalid_cli_options.push(format!(
+                "{} can't be used with the `{}` command", WRITE_CLI_FLAG, Command::DryRun.name())),
+            Command::Sort | Command::Watch if self.emit_script_path.is_some() => self.invalid_cli_options.push(format!(
+                "{} can't be used with the `{}` command, the script is only saved by dry runs",
+                EMIT_SCRIPT_CLI_FLAG, self.command.name())),
+            Command::Watch if self.source_files_list.is_some() => self.invalid_cli_options.push(format!(
+                "{} can't be used with the `{}` command, which reads the files added to the source folders",
+                FILES_FROM_CLI_FLAG, Command::Watch.name())),
+            Command::Sort | Command::Watch => self.dry_run = false,
+            Command::DryRun => self.dry_run = true,
+            _ => {}
+        }
+        if self.command.is_unattended() {
+            self.silent = true;
+        }
+
+        // Contradicting options are most likely a mistake in a script, so don't guess which one was intended
+        [(DRY_RUN_CLI_FLAG, WRITE_CLI_FLAG), (COPY_CLI_FLAG, MOVE_CLI_FLAG), (RECURSIVE_CLI_FLAG, NO_RECURSIVE_CLI_FLAG)]
+            .iter()
+            .filter(|(first, second)| has_cli_flag(first) && has_cli_flag(second))
+            .for_each(|(first, second)| self.invalid_cli_options.push(format!(
+                "{} and {} can't be used together", first, second)));
+
+        if let Some(min_files) = get_cli_option_value(MIN_FILES_CLI_FLAG) {
+            match min_files.parse::<i64>() {
+                Ok(min_files) if min_files >= 0 => self.min_files_per_dir = min_files,
+                _ => self.invalid_cli_options.push(format!(
+                    "{}: '{}' is not a positive number", MIN_FILES_CLI_FLAG, min_files)),
+            }
+        }
+
+        if let Some(collision_policy) = get_cli_option_value(ON_COLLISION_CLI_FLAG) {
+            match CollisionPolicy::from_name(&collision_policy) {
+                Some(collision_policy) => self.collision_policy = collision_policy,
+                None => self.invalid_cli_options.push(format!(
+                    "{}: '{}' must be one of 'skip', 'overwrite', 'rename', 'review' or 'keep_larger'", ON_COLLISION_CLI_FLAG, collision_policy)),
+            }
+        }
+
+        if let Some(cli_show_only) = get_cli_option_value(SHOW_ONLY_CLI_FLAG) {
+            let values: Vec<String> = cli_show_only.split(',').map(String::from).collect();
+            match parse_status_categories(&values) {
+                Ok(categories) => self.show_only = categories,
+                Err(invalid_category) => self.invalid_cli_options.push(format!(
+                    "{}: unknown status '{}', values must be 'written', 'skipped' or 'errors'",
+                    SHOW_ONLY_CLI_FLAG, invalid_category)),
+            }
+        }
+    }
+
     fn set_source_paths(&mut self, sources: Vec<Vec<PathBuf>>) {
         if !(sources.is_empty() || sources.iter().all(|v|v.is_empty())) {
             self.source_dirs = sources;
@@ -576,10 +2658,11 @@ impl Args {
     }
 
     // Create the target path from the provided target_path_str
-    // If the path already exists, create subdirectory DEFAULT_TARGET_SUBDIR inside it
+    // If the path already exists, create subdirectory DEFAULT_TARGET_SUBDIR inside it,
+    // unless it's an archive created by a previous run, in which case it's used directly
     fn set_target_dir(&mut self, target_path_str: String) {
         let target_path = PathBuf::from(target_path_str);
-        self.target_dir = if target_path.exists() {
+        self.target_dir = if target_path.exists() && !is_archive(&target_path) {
             target_path.join(DEFAULT_TARGET_SUBDIR)
         } else {
             target_path
@@ -592,6 +2675,128 @@ impl Args {
         }
     }
 
+    /// Read the source folders of an interrupted run which is resumed, instead of the configured ones,
+    /// or only the files it sorted, if it didn't read whole source folders, e.g. with [FILES_FROM_CLI_FLAG].
+    /// Subfolders and files which were removed since, e.g. because they were already moved, are left out
+    pub fn set_resumed_sources(&mut self, source_dirs: Vec<Vec<PathBuf>>, source_files: Option<Vec<PathBuf>>) {
+        if let Some(source_files) = source_files {
+            self.set_listed_source_files(source_files.into_iter().filter(|source_file| source_file.is_file()).collect());
+            return;
+        }
+
+        let existing_source_dirs: Vec<Vec<PathBuf>> = source_dirs
+            .into_iter()
+            .map(|source_dirs| source_dirs.into_iter().filter(|source_dir| source_dir.exists()).collect::<Vec<_>>())
+            .filter(|source_dirs| !source_dirs.is_empty())
+            .collect();
+        self.set_source_paths(existing_source_dirs);
+        self.source_dirs_count = self.source_dirs.iter().map(|v|v.len()).sum();
+        self.source_files_list = None;
+    }
+
+    /// Whether each file is written as soon as it's read, see [Self::stream_writes]. Only silent runs
+    /// which write files have nothing to preview or confirm, so the other runs always read all files first
+    pub fn is_streaming(&self) -> bool {
+        self.stream_writes
+            && self.silent
+            && !self.dry_run
+            && matches!(self.command, Command::Interactive | Command::Sort | Command::Watch)
+    }
+
+    /// The options which need all files to be read before any of them is written,
+    /// so they can't be used together with [Self::stream_writes], each with the reason
+    pub fn find_stream_writes_conflicts(&self) -> Vec<String> {
+        [
+            (self.lock_plan, "`lock_plan` compares the plan of all files with the last dry run"),
+            (self.min_files_per_dir > 1, "`min_files_per_dir` counts the files of each date"),
+            (self.min_files_per_device_dir > 1, "`min_files_per_device_dir` counts the files of each device"),
+            (self.event_max_gap_days > 0, "`event_max_gap_days` merges the dates of each event"),
+            (self.heic_jpeg_pairs != HeicJpegPairPolicy::KeepBoth,
+             "`heic_jpeg_pairs` looks for the other file of each pair in all source folders"),
+            (self.skip_exifless_twins, "`skip_exifless_twins` compares each photo without EXIF data with the other photos of its date"),
+            (self.mark_probable_duplicates, "`mark_probable_duplicates` compares the capture times of all photos"),
+            (!self.always_create_device_subdirs,
+             "`always_create_device_subdirs = false` counts the devices and files of each date before creating their folders"),
+            (self.file_name_template.is_some(),
+             "`file_name_template` can give files with different names the same name, which is only found once all files are read"),
+        ]
+            .iter()
+            .filter(|(is_conflict, _)| *is_conflict)
+            .map(|(_, reason)| reason.to_string())
+            .collect()
+    }
+
+    /// The lowercase names of the files and folders in this target folder, or `None` if it doesn't exist,
+    /// read from [Self::target_index] if there's one, instead of listing the folder
+    pub fn target_file_names(&self, target_dir: &Path) -> Option<HashSet<String>> {
+        match &self.target_index {
+            Some(target_index) => target_index.file_names(target_dir),
+            None => list_file_names(target_dir),
+        }
+    }
+
+    /// The arguments for sorting the files found by the `watch` command, which are read like
+    /// the files listed with [FILES_FROM_CLI_FLAG], instead of reading all source folders again
+    pub fn for_watched_files(&self, watched_files: Vec<PathBuf>) -> Args {
+        let mut args = self.clone();
+        args.set_listed_source_files(watched_files);
+        args
+    }
+
+    /// Sort only these files, reading the dirs which contain them as the source dirs, see [Args::source_files_list]
+    fn set_listed_source_files(&mut self, listed_files: Vec<PathBuf>) {
+        let listed_dirs: BTreeSet<PathBuf> = listed_files
+            .iter()
+            .map(|listed_file| listed_file_dir(listed_file))
+            .collect();
+        self.set_source_paths(listed_dirs.into_iter().map(|listed_dir| vec![listed_dir]).collect());
+        self.source_dirs_count = self.source_dirs.iter().map(|v|v.len()).sum();
+        self.source_files_list = Some(listed_files);
+    }
+
+    /// Write exactly the files of a plan saved by an earlier dry run into its target folder, instead of the
+    /// configured sources and target, see [Command::Apply]. The other options are still read from the config file,
+    /// e.g. whether to save checksums or to keep an operation log
+    pub fn set_applied_plan(&mut self, plan_path: &Path, target_dir: &Path, source_files: Vec<PathBuf>) {
+        self.applied_plan_path = Some(fs::canonicalize(plan_path).unwrap_or_else(|_| plan_path.to_path_buf()));
+        self.target_dir = target_dir.to_path_buf();
+        self.dry_run = false;
+        self.set_listed_source_files(source_files);
+    }
+
+    /// The arguments for sorting the synthetic library of the `selftest` command, which uses the default options
+    /// instead of the config file, so that the folders each file is sorted into are known, see [crate::selftest]
+    pub fn for_selftest(source_dir: &Path, target_dir: &Path, command: Command) -> Result<Args, std::io::Error> {
+        let mut args = Args::new()?;
+        args.command = command;
+        args.dry_run = command == Command::DryRun;
+        args.silent = true;
+        args.copy_not_move = true;
+        args.source_dirs = vec![vec![source_dir.to_path_buf()]];
+        args.target_dir = target_dir.to_path_buf();
+        args.set_source_paths(walk_source_dirs_recursively(&args));
+        args.source_dirs_count = args.source_dirs.iter().map(|v|v.len()).sum();
+        Ok(args)
+    }
+
+    /// Whether the file found by the `watch` command is inside a subfolder of its source folder
+    /// which is not read, see [is_excluded_dir]. The source folders themselves are never excluded
+    pub fn is_in_excluded_dir(&self, file_path: &Path) -> bool {
+        let source_dir = match self.source_dirs
+            .iter()
+            .filter_map(|source_dirs| source_dirs.first())
+            .find(|source_dir| file_path.starts_with(source_dir)) {
+            Some(source_dir) => source_dir,
+            None => return false,
+        };
+
+        file_path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir_path| *dir_path != source_dir.as_path())
+            .any(|dir_path| is_excluded_dir(dir_path, self))
+    }
+
     pub fn has_multiple_sources(&self) -> bool {
         self.source_dirs_count > 1
     }
@@ -599,35 +2804,263 @@ impl Args {
     pub fn is_compacting_enabled(&self) -> bool {
         self.compacting_threshold > 0
     }
+
+    /// The named event which contains this date, if any, see [Args::named_events]
+    pub fn find_named_event(&self, date: NaiveDate) -> Option<&NamedEvent> {
+        self.named_events.iter().find(|event| event.contains(date))
+    }
 }
 
+/// Search for the config file in these folders, in order, and use the first one found:
+/// 1. the folder containing the program executable
+/// 2. the current working directory (as a relative path)
+/// 3. the platform config folder, i.e. `%APPDATA%\imgsorter\` on Windows
+///    or `$XDG_CONFIG_HOME/imgsorter/` (usually `~/.config/imgsorter/`) on other systems
+///
+/// If the config file is not found anywhere, the relative path is returned
 fn get_config_file_path(config_file_name: &str) -> (PathBuf, String) {
     let cfg_relative_path = PathBuf::from(config_file_name);
+    let mut searched_dirs: Vec<String> = Vec::new();
 
     match get_program_executable_path() {
         Ok(path) => {
             let config_path = path.join(config_file_name);
             if config_path.exists() {
                 let message = format!("Found config file at: {}", &path.display().to_string());
-                (config_path, message)
-            } else {
-                let message = ColoredString::orange(format!(
-                    "Trying relative path. Config file not found at: {}.", &path.display().to_string()).as_str());
-                (cfg_relative_path, message)
+                return (config_path, message);
             }
+            searched_dirs.push(path.display().to_string());
         }
         Err(path_reading_err) => {
-            (cfg_relative_path, path_reading_err)
+            searched_dirs.push(path_reading_err);
+        }
+    }
+
+    if cfg_relative_path.exists() {
+        let message = String::from("Found config file in the current working directory");
+        return (cfg_relative_path, message);
+    }
+    searched_dirs.push(String::from("current working directory"));
+
+    if let Some(platform_config_dir) = get_platform_config_dir() {
+        let config_path = platform_config_dir.join(config_file_name);
+        if config_path.exists() {
+            let message = format!("Found config file at: {}", &platform_config_dir.display().to_string());
+            return (config_path, message);
+        }
+        searched_dirs.push(platform_config_dir.display().to_string());
+    }
+
+    let message = ColoredString::orange(format!(
+        "Config file not found in: {}.", searched_dirs.join(", ")).as_str());
+    (cfg_relative_path, message)
+}
+
+/// The folder for user config files specific to each platform, without using any external crates
+fn get_platform_config_dir() -> Option<PathBuf> {
+    let non_empty_var = |name: &str| env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+
+    let config_home = if cfg!(windows) {
+        non_empty_var("APPDATA")
+    } else {
+        non_empty_var("XDG_CONFIG_HOME")
+            .or_else(|| non_empty_var("HOME").map(|home| home.join(".config")))
+    };
+
+    config_home.map(|dir| dir.join("imgsorter"))
+}
+
+/// The folder given on the command line, i.e. the source folder, or the target folder for `undo`.
+/// The command and any options starting with `--` and their values are ignored,
+/// the path is the first argument which is not an option
+pub fn get_cli_path() -> Option<String> {
+    let skipped_args = if get_cli_command() == Command::Interactive { 1 } else { 2 };
+    let mut cli_args = env::args().skip(skipped_args);
+    while let Some(arg) = cli_args.next() {
+        if get_cli_option(&arg).is_some_and(|option| option.value_name.is_some()) {
+            cli_args.next();
+        } else if !arg.starts_with("--") {
+            return Some(arg);
         }
     }
+    None
+}
+
+/// Find the recognized option for an argument, which can be either `--option` or `--option=<value>`
+fn get_cli_option(arg: &str) -> Option<&'static CliOption> {
+    let option_name = arg.split_once('=').map_or(arg, |(name, _)| name);
+    CLI_OPTIONS.iter().find(|option| option.name == option_name)
+}
+
+fn has_cli_flag(flag: &str) -> bool {
+    env::args().skip(1).any(|arg| arg == flag)
+}
+
+/// Check if the `--silent` flag was given, for commands which don't read the config file
+pub fn has_silent_cli_flag() -> bool {
+    has_cli_flag(SILENT_CLI_FLAG)
+}
+
+// Read all values from `--option <value>` or `--option=<value>`, for options which can be repeated
+fn get_cli_option_values(option: &str) -> Vec<String> {
+    let cli_args: Vec<String> = env::args().skip(1).collect();
+    let option_with_value = format!("{}=", option);
+
+    cli_args
+        .iter()
+        .enumerate()
+        .filter_map(|(i, arg)| {
+            if arg == option {
+                cli_args.get(i + 1).filter(|value| !value.starts_with("--")).cloned()
+            } else {
+                arg.strip_prefix(&option_with_value).map(String::from)
+            }
+        })
+        .collect()
+}
+
+// If an option is provided more than once, the last value is used
+fn get_cli_option_value(option: &str) -> Option<String> {
+    get_cli_option_values(option).pop()
 }
 
-fn get_cli_source_path() -> Option<String> {
-    let cli_args: Vec<String> = env::args().collect();
+/// Check all command line options, and describe the ones which are unknown or are missing their value
+fn find_invalid_cli_options() -> Vec<String> {
+    let cli_args: Vec<String> = env::args().skip(1).collect();
 
     cli_args
-        .get(1)
-        .cloned()
+        .iter()
+        .enumerate()
+        .filter(|(_, arg)| arg.starts_with("--"))
+        .filter_map(|(i, arg)| match get_cli_option(arg) {
+            None =>
+                Some(format!("{}: unknown option", arg)),
+            // Hidden options are parsed leniently
+            Some(option) if option.hidden =>
+                None,
+            Some(option) => match option.value_name {
+                Some(value_name) => {
+                    let has_value = arg.contains('=') || cli_args
+                        .get(i + 1)
+                        .is_some_and(|value| !value.starts_with("--"));
+                    if has_value { None } else { Some(format!("{}: missing <{}>", option.name, value_name)) }
+                }
+                None if arg.contains('=') =>
+                    Some(format!("{}: this option doesn't take a value", option.name)),
+                None =>
+                    None,
+            },
+        })
+        .collect()
+}
+
+/// Read a list of chrono date formats, e.g. `%Y/%m/%d %H:%M:%S`, returning a description of the first invalid one, if any
+fn parse_date_formats(values: &[Value]) -> Result<Vec<String>, String> {
+    values
+        .iter()
+        .map(|value| {
+            let format = value
+                .as_str()
+                .map(|format| format.trim())
+                .filter(|format| !format.is_empty())
+                .ok_or_else(|| format!("'{}' must be a non-empty string", value))?;
+            if StrftimeItems::new(format).any(|item| item == Item::Error) {
+                return Err(format!("'{}' is not a valid date format", format));
+            }
+            Ok(format.to_string())
+        })
+        .collect()
+}
+
+/// Parse a list of status category names, returning the first unknown name if any
+fn parse_status_categories(names: &[String]) -> Result<Vec<StatusCategory>, String> {
+    names
+        .iter()
+        .filter(|name| !name.trim().is_empty())
+        .map(|name| StatusCategory::from_name(name).ok_or_else(|| name.clone()))
+        .collect()
+}
+
+/// Parse an offset from UTC in the `+HH:MM` or `-HH:MM` format, e.g. `+02:00`
+fn parse_utc_offset(offset: &str) -> Option<FixedOffset> {
+    let offset = offset.trim();
+    let sign = match offset.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let (hours, minutes) = offset[1..].split_once(':')?;
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    if hours > 14 || minutes > 59 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Read the optional config overlay file, which can be provided with `--config-extra <path>`.
+/// Otherwise, use [CONFIG_OVERLAY_FILE_NAME] from the same folder as the main config file, if it exists
+fn read_config_overlay(config_file_path: &Path) -> Option<toml::map::Map<String, Value>> {
+    let overlay_path = match get_cli_option_value(CONFIG_OVERLAY_CLI_FLAG).map(PathBuf::from) {
+        Some(cli_overlay_path) => cli_overlay_path,
+        None => {
+            let default_overlay_path = config_file_path.with_file_name(CONFIG_OVERLAY_FILE_NAME);
+            if !default_overlay_path.exists() {
+                return None;
+            }
+            default_overlay_path
+        }
+    };
+
+    let overlay_contents = match fs::read_to_string(&overlay_path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            println!("{}", ColoredString::red(format!(
+                "Could not read config overlay file at {}: {}", overlay_path.display(), e).as_str()));
+            return None;
+        }
+    };
+
+    match overlay_contents.parse::<Value>() {
+        Ok(Value::Table(overlay_toml)) => {
+            println!("Using config overlay file at: {}", overlay_path.display());
+            Some(overlay_toml)
+        }
+        Ok(_) => None,
+        Err(e) => {
+            println!("{}", ColoredString::red(format!(
+                "Could not parse config overlay file at {}, ignoring it: {}", overlay_path.display(), e).as_str()));
+            None
+        }
+    }
+}
+
+/// Merge the overlay config into the base config. Tables are merged key by key,
+/// while all other values (including arrays, e.g. `source_dirs`) are replaced entirely
+fn merge_config_overlay(base: &mut toml::map::Map<String, Value>, overlay: toml::map::Map<String, Value>) {
+    for (key, overlay_value) in overlay {
+        match (base.get_mut(&key), overlay_value) {
+            (Some(Value::Table(base_table)), Value::Table(overlay_table)) =>
+                merge_config_overlay(base_table, overlay_table),
+            (_, overlay_value) => {
+                base.insert(key, overlay_value);
+            }
+        }
+    }
+}
+
+// Read the failure rate from `--chaos` or `--chaos=<percent>`
+// If the percent is missing or invalid, use the default rate
+fn get_cli_chaos_failure_rate() -> Option<u8> {
+    env::args()
+        .skip(1)
+        .find(|arg| arg == CHAOS_CLI_FLAG || arg.starts_with(&format!("{}=", CHAOS_CLI_FLAG)))
+        .map(|arg| {
+            arg.split_once('=')
+                .and_then(|(_, rate)| rate.parse::<u8>().ok())
+                .filter(|rate| *rate <= 100)
+                .unwrap_or(DEFAULT_CHAOS_FAILURE_RATE)
+        })
 }
 
 fn get_program_executable_path() -> Result<PathBuf, String> {
@@ -648,6 +3081,48 @@ fn get_program_executable_path() -> Result<PathBuf, String> {
     }
 }
 
+/// Find the configured source folders which are drive roots, the user's home folder or system folders.
+/// Returns a description of each such folder, e.g. `C:\ (drive root)`
+fn find_dangerous_source_dirs(args: &Args) -> Vec<String> {
+    let canonical = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+
+    let home_dir = env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" })
+        .filter(|home| !home.is_empty())
+        .map(|home| canonical(Path::new(&home)));
+
+    let system_dirs: Vec<PathBuf> = if cfg!(windows) {
+        ["SystemRoot", "ProgramFiles", "ProgramFiles(x86)", "ProgramData"]
+            .iter()
+            .filter_map(env::var_os)
+            .map(|dir| canonical(Path::new(&dir)))
+            .collect()
+    } else {
+        ["/bin", "/boot", "/dev", "/etc", "/lib", "/proc", "/sbin", "/sys", "/usr", "/var",
+            "/Applications", "/Library", "/System"]
+            .iter()
+            .map(|dir| canonical(Path::new(dir)))
+            .collect()
+    };
+
+    args.source_dirs
+        .iter()
+        .flatten()
+        .filter_map(|source_dir| {
+            let source_path = canonical(source_dir);
+            let reason = if source_path.parent().is_none() {
+                "drive root"
+            } else if home_dir.as_ref() == Some(&source_path) {
+                "home folder"
+            } else if system_dirs.contains(&source_path) {
+                "system folder"
+            } else {
+                return None;
+            };
+            Some(format!("{} ({})", source_dir.display(), reason))
+        })
+        .collect()
+}
+
 /// Check if the provided sources exist and return a `valid_path`
 /// Vec only if there's at least one valid source path
 fn validate_source_paths(sources: Vec<PathBuf>) -> Result<(Vec<PathBuf>, Vec<PathBuf>), Vec<PathBuf>> {
@@ -661,19 +3136,77 @@ fn validate_source_paths(sources: Vec<PathBuf>) -> Result<(Vec<PathBuf>, Vec<Pat
     }
 }
 
+/// Read the paths of the files to sort, one on each line, from this file or from the standard input for [FILES_FROM_STDIN].
+/// Empty lines are ignored, but any other whitespace is kept, since it can be part of a file name
+fn read_files_list(files_from: &str) -> Result<Vec<PathBuf>, io::Error> {
+    let contents = if files_from == FILES_FROM_STDIN {
+        let mut contents = String::new();
+        io::stdin().read_to_string(&mut contents)?;
+        contents
+    } else {
+        fs::read_to_string(files_from)?
+    };
+
+    Ok(contents
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// The source dir of a file from [Args::source_files_list], which is the current dir for file names without a dir
+pub fn listed_file_dir(listed_file: &Path) -> PathBuf {
+    listed_file
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."))
+        .to_path_buf()
+}
+
 fn paths_to_str(paths: Vec<PathBuf>) -> String {
     paths
         .iter()
-        .flat_map(|s| s.to_str())
+        .map(|path| path.display().to_string())
         .collect::<Vec<_>>()
         .join("\n ")
 }
 
+/// Check if a source subdir should not be read, either because its name is listed in
+/// [Args::exclude_dirs] or because it's hidden and [Args::skip_hidden_dirs] is enabled
+fn is_excluded_dir(dir_path: &Path, args: &Args) -> bool {
+    let dir_name = dir_path
+        .file_name()
+        .map(|dir_name| dir_name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if args.exclude_dirs.contains(&dir_name) {
+        return true;
+    }
+
+    if !args.skip_hidden_dirs {
+        return false;
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+        const FILE_ATTRIBUTE_SYSTEM: u32 = 0x4;
+        let is_hidden_or_system = fs::metadata(dir_path)
+            .map(|metadata| metadata.file_attributes() & (FILE_ATTRIBUTE_HIDDEN | FILE_ATTRIBUTE_SYSTEM) != 0)
+            .unwrap_or(false);
+        if is_hidden_or_system {
+            return true;
+        }
+    }
+
+    dir_name.starts_with('.')
+}
+
 /// For each configured source directory, read all its inner subdirectories
 /// recursively into a separate Vec, so the end result will be a 2D Vec where
 /// the outer elements hold all subdirs of each of the configured source dirs,
 /// while the inner elements represent the actual subdir paths, e.g.:
-/// ```
+/// ```text
 /// [
 ///   [src_dir_1, src_dir_1/subdir1, src_dir_1/subdir2],
 ///   [src_dir_2, src_dir_2/subdir1, src_dir_2/subdir2/another_subdir_level],
@@ -690,9 +3223,15 @@ fn walk_source_dirs_recursively(args: &Args) -> Vec<Vec<PathBuf>> {
         }
 
         let subdirs: Vec<DirEntry> = fs::read_dir(&source_dir)?
-            .into_iter()
             .filter_map(|s| s.ok())
             .filter(|entry| entry.path().is_dir())
+            .filter(|entry| {
+                let is_excluded = is_excluded_dir(&entry.path(), args);
+                if is_excluded && args.verbose {
+                    println!("> Skipping excluded folder '{}'", entry.path().display());
+                }
+                !is_excluded
+            })
             .collect::<Vec<_>>();
 
         vec_accum.push(source_dir);
@@ -0,0 +1,51 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::checksum::hash_file;
+
+/// Source files which have the same contents, e.g. the same photo copied into several folders
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdenticalFiles {
+    /// The size of each of the files, in bytes
+    pub size: u64,
+    pub paths: Vec<PathBuf>,
+}
+
+impl IdenticalFiles {
+    /// The space which would be freed by keeping only one of the files
+    pub fn redundant_size(&self) -> u64 {
+        self.size * (self.paths.len() as u64 - 1)
+    }
+}
+
+/// Find the files with the same contents, given the path and size of each file.
+/// Only the files with the same size are hashed, so most files are never read.
+/// Empty files and files which can't be read are left out.
+/// Both the groups and the files inside each group are sorted by path
+pub fn find_identical_files(files: impl IntoIterator<Item = (PathBuf, u64)>) -> Vec<IdenticalFiles> {
+    let mut files_by_size: BTreeMap<u64, Vec<PathBuf>> = BTreeMap::new();
+    for (path, size) in files.into_iter().filter(|(_, size)| *size > 0) {
+        files_by_size.entry(size).or_default().push(path);
+    }
+
+    let mut identical_files: Vec<IdenticalFiles> = Vec::new();
+    for (size, paths) in files_by_size.into_iter().filter(|(_, paths)| paths.len() > 1) {
+        let mut files_by_hash: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+        for path in paths {
+            if let Ok(hash) = hash_file(&path) {
+                files_by_hash.entry(hash).or_default().push(path);
+            }
+        }
+
+        identical_files.extend(files_by_hash
+            .into_values()
+            .filter(|paths| paths.len() > 1)
+            .map(|mut paths| {
+                paths.sort();
+                IdenticalFiles { size, paths }
+            }));
+    }
+
+    identical_files.sort_by(|group1, group2| group1.paths.cmp(&group2.paths));
+    identical_files
+}
@@ -0,0 +1,122 @@
+use crate::config::*;
+
+/// The files of a single device inside a date dir, as seen by the [DevicePolicy]
+#[derive(Debug, Clone, Copy)]
+pub struct DeviceFiles {
+    /// Whether the device is known, i.e. not the group of files placed directly inside the date dir
+    pub is_device_dir: bool,
+    /// Whether this is the dir for animations instead of an actual device, see [Args::animations_dir_name]
+    pub is_animations_dir: bool,
+    /// The number of files for this device
+    pub file_count: usize,
+    /// The number of devices (including the group of files without a device) in the same date dir
+    pub date_device_count: usize,
+    /// The number of files for all devices in the same date dir
+    pub date_file_count: usize,
+}
+
+/// Decides whether the files of a device are placed in a separate device subdir inside
+/// their date dir, or directly inside the date dir. This is decided once for each device
+/// after all files are read, so that every step of the program uses the same target paths
+#[derive(Debug, Clone)]
+pub struct DevicePolicy {
+    always_create_device_subdirs: bool,
+    min_files_per_device_dir: usize,
+}
+
+impl DevicePolicy {
+    pub fn new(args: &Args) -> DevicePolicy {
+        DevicePolicy {
+            always_create_device_subdirs: args.always_create_device_subdirs,
+            min_files_per_device_dir: args.min_files_per_device_dir,
+        }
+    }
+
+    pub fn has_device_subdir(&self, device: DeviceFiles) -> bool {
+        // Animations are only grouped together if they should be kept apart from the other files
+        if self.always_create_device_subdirs || device.is_animations_dir {
+            return true;
+        }
+
+        // This condition helps prevent creating a redundant device subdir if
+        // there's only a single Some("device") device (without any "None" device files)
+        // Before                 After
+        // ------                 -----
+        // [date_dir]             [date_dir]
+        //  └─ [device_dir]        │
+        //      ├─ file01.ext      ├─ file01.ext
+        //      └─ file02.ext      └─ file02.ext
+        let has_at_least_one_distinct_device = device.date_device_count > 1 && device.is_device_dir;
+
+        // This condition helps prevent creating a device subdir for a single file, if there's also
+        // a "None" device with a single file. In practice, this is most likely to be a situation where
+        // a picture taken with a camera (computed device is Some("device") based on EXIF) is sent
+        // via a messenger app and would end up in a "Sent" folder without EXIF info (computed device is None)
+        // Before                 After
+        // ------                 -----
+        // [date_dir]             [date_dir]
+        //  └─ [device_dir]        |
+        //  │   └─ file01.ext      └─ file01.ext
+        //  └─ file02.ext          └─ file02.ext
+        // See also Args::skip_exifless_twins, which can skip copying the file without EXIF info
+        let has_double_file = device.date_device_count == 2 && device.date_file_count == 2;
+
+        // Devices with only a few files for this date are not worth a separate subdir
+        let has_minimum_files = device.file_count >= self.min_files_per_device_dir;
+
+        has_at_least_one_distinct_device && !has_double_file && has_minimum_files
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(always_create_device_subdirs: bool, min_files_per_device_dir: usize) -> DevicePolicy {
+        let mut args = Args::new().unwrap();
+        args.always_create_device_subdirs = always_create_device_subdirs;
+        args.min_files_per_device_dir = min_files_per_device_dir;
+        DevicePolicy::new(&args)
+    }
+
+    fn device(file_count: usize, date_device_count: usize, date_file_count: usize) -> DeviceFiles {
+        DeviceFiles {
+            is_device_dir: true,
+            is_animations_dir: false,
+            file_count,
+            date_device_count,
+            date_file_count,
+        }
+    }
+
+    #[test]
+    fn creates_subdirs_only_if_there_are_several_devices() {
+        let policy = policy(false, 1);
+
+        assert!(!policy.has_device_subdir(device(5, 1, 5)));
+        assert!(policy.has_device_subdir(device(5, 2, 8)));
+        assert!(!policy.has_device_subdir(DeviceFiles { is_device_dir: false, ..device(3, 2, 8) }));
+    }
+
+    #[test]
+    fn keeps_a_single_file_next_to_a_file_without_device() {
+        let policy = policy(false, 1);
+
+        assert!(!policy.has_device_subdir(device(1, 2, 2)));
+        assert!(policy.has_device_subdir(device(1, 3, 3)));
+    }
+
+    #[test]
+    fn requires_the_minimum_number_of_files() {
+        let policy = policy(false, 3);
+
+        assert!(!policy.has_device_subdir(device(2, 2, 8)));
+        assert!(policy.has_device_subdir(device(3, 2, 8)));
+    }
+
+    #[test]
+    fn always_creates_subdirs_if_configured_or_for_animations() {
+        assert!(policy(true, 3).has_device_subdir(device(1, 1, 1)));
+        assert!(policy(false, 3).has_device_subdir(DeviceFiles { is_animations_dir: true, ..device(1, 1, 1) }));
+    }
+}
@@ -0,0 +1,98 @@
+use std::fs::{DirEntry, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::config::*;
+use crate::exif::ExifDateDevice;
+
+/// The document information dictionary is usually written either at the start of the file,
+/// or at its end, before the trailer, so only these parts are searched for the creation date
+const PDF_SEARCH_LEN: u64 = 64 * 1024;
+
+/// The key of the creation date in the PDF document information dictionary
+const PDF_CREATION_DATE_KEY: &[u8] = b"/CreationDate";
+
+/// Read the date a document was created from its metadata:
+/// - PDF files: the `CreationDate` of the document information dictionary, e.g. `(D:20230514103000+02'00')`,
+///   which scanning apps set to the time of the scan. The local time is used as it is, like for photos.
+/// - other documents, e.g. DJVU files, have no date which can be read, so their date is
+///   read from the file name or the file system instead
+///
+/// See also [crate::video_meta::read_video_date()]
+pub fn read_document_date(file: &DirEntry, args: &Args) -> ExifDateDevice {
+    let mut document_data = ExifDateDevice::new();
+    let file_path = file.path();
+
+    match read_pdf_creation_date(&file_path) {
+        Some(creation_time) => {
+            document_data.date = Some(creation_time.format(DATE_DIR_FORMAT).to_string());
+        }
+        None => {
+            if args.debug {
+                println!("> could not read the creation date of document {:?}", file_path);
+            }
+        }
+    }
+
+    document_data
+}
+
+fn read_pdf_creation_date(file_path: &Path) -> Option<NaiveDateTime> {
+    let mut file = File::open(file_path).ok()?;
+    let file_len = file.metadata().ok()?.len();
+
+    let mut start = Vec::new();
+    (&mut file).take(PDF_SEARCH_LEN).read_to_end(&mut start).ok()?;
+    if !start.starts_with(b"%PDF-") {
+        return None;
+    }
+
+    find_pdf_creation_date(&start).or_else(|| {
+        if file_len <= PDF_SEARCH_LEN {
+            return None;
+        }
+        let mut end = Vec::new();
+        file.seek(SeekFrom::Start(file_len - PDF_SEARCH_LEN)).ok()?;
+        file.take(PDF_SEARCH_LEN).read_to_end(&mut end).ok()?;
+        find_pdf_creation_date(&end)
+    })
+}
+
+/// Find the first creation date which can be read, since incremental updates may add more dictionaries.
+/// PDF dates have the format `D:YYYYMMDDHHmmSSOHH'mm'`, where everything after the year is optional
+fn find_pdf_creation_date(contents: &[u8]) -> Option<NaiveDateTime> {
+    contents
+        .windows(PDF_CREATION_DATE_KEY.len())
+        .enumerate()
+        .filter(|(_, window)| *window == PDF_CREATION_DATE_KEY)
+        .find_map(|(index, _)| {
+            let value = &contents[index + PDF_CREATION_DATE_KEY.len()..];
+            let value_start = value.iter().position(|byte| !byte.is_ascii_whitespace())?;
+            let value = value[value_start..].strip_prefix(b"(")?;
+            let value = value.strip_prefix(b"D:").unwrap_or(value);
+            let digits: Vec<u32> = value
+                .iter()
+                .take_while(|byte| byte.is_ascii_digit())
+                .map(|byte| (byte - b'0') as u32)
+                .collect();
+            parse_pdf_date_digits(&digits)
+        })
+}
+
+fn parse_pdf_date_digits(digits: &[u32]) -> Option<NaiveDateTime> {
+    if digits.len() < 4 {
+        return None;
+    }
+
+    // Missing fields default to the start of the year, month or day
+    let field = |position: usize, default: u32| match digits.get(position..position + 2) {
+        Some(pair) => pair[0] * 10 + pair[1],
+        None => default,
+    };
+    let year = digits[..4].iter().fold(0, |year, digit| year * 10 + digit) as i32;
+
+    NaiveDate::from_ymd_opt(year, field(4, 1), field(6, 1))?
+        .and_hms_opt(field(8, 0), field(10, 0), field(12, 0))
+}
@@ -0,0 +1,235 @@
+use std::fs::{self, Metadata};
+use std::path::Path;
+
+use crate::archive::is_archive;
+use crate::checksum::hash_file;
+use crate::config::*;
+
+/// What to do with a source file whose target file already exists, see [DuplicatePolicy]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateAction {
+    /// Leave the source file in place and keep the target file
+    Skip,
+    /// Write the source file with a number added to its name, e.g. `IMG_0001 (2).jpg`
+    Rename,
+    /// Replace the target file with the source file
+    Overwrite,
+    /// Place the source file in the `Duplicates` folder inside the target folder, to be checked by the user
+    Review,
+}
+
+/// The source file whose target file already exists
+#[derive(Debug, Clone, Copy)]
+pub struct DuplicateSource<'a> {
+    pub path: &'a Path,
+    pub metadata: &'a Metadata,
+    /// The name of the device which took the photo, if known
+    pub device_name: Option<&'a str>,
+}
+
+/// The existing file in the target folder which has the same name as the source file
+#[derive(Debug, Clone, Copy)]
+pub struct DuplicateTarget<'a> {
+    pub path: &'a Path,
+}
+
+impl DuplicateTarget<'_> {
+    /// Read the metadata of the existing file, which is only done if a policy needs it
+    pub fn metadata(&self) -> Option<Metadata> {
+        fs::metadata(self.path).ok()
+    }
+}
+
+/// The options of the current run, for policies which depend on them
+#[derive(Debug, Clone, Copy)]
+pub struct DuplicateContext<'a> {
+    pub args: &'a Args,
+    /// Whether target files may be replaced, which is never the case inside a protected archive,
+    /// see [Args::protect_target]
+    pub can_overwrite: bool,
+}
+
+impl DuplicateContext<'_> {
+    pub fn new(args: &Args) -> DuplicateContext<'_> {
+        DuplicateContext {
+            args,
+            can_overwrite: !args.protect_target || !is_archive(&args.target_dir),
+        }
+    }
+}
+
+/// Decides what to do with each source file whose target file already exists, so that tools
+/// built on this library can encode their own rules, e.g. always review files from some devices,
+/// and pass them to [decide_duplicate]
+pub trait DuplicatePolicy: Send + Sync {
+    fn resolve(&self, source: &DuplicateSource, target: &DuplicateTarget, context: &DuplicateContext) -> DuplicateAction;
+}
+
+/// What was decided for a source file whose target file already exists, see [decide_duplicate]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateDecision {
+    /// The source file has the same contents as its target file, so it's skipped as already written
+    Identical,
+    /// The action of the policy, which can be carried out
+    Resolved(DuplicateAction),
+}
+
+/// Decide what to do with a source file whose target file already exists with the policy.
+/// Source files with the same contents as their target file are always skipped, unless the policy
+/// overwrites them, which avoids comparing their contents. Overwriting is never allowed if
+/// [DuplicateContext::can_overwrite] isn't set, so these files are skipped instead
+pub fn decide_duplicate(
+    policy: &dyn DuplicatePolicy,
+    source: &DuplicateSource,
+    target: &DuplicateTarget,
+    context: &DuplicateContext,
+) -> DuplicateDecision {
+    let action = match policy.resolve(source, target, context) {
+        DuplicateAction::Overwrite if !context.can_overwrite => DuplicateAction::Skip,
+        action => action,
+    };
+
+    if action != DuplicateAction::Overwrite && is_same_file_contents(source.path, source.metadata, target.path) {
+        DuplicateDecision::Identical
+    } else {
+        DuplicateDecision::Resolved(action)
+    }
+}
+
+/// Check if the target file has the same size and contents as the source file.
+/// Files which can't be read are treated as different, so that none is lost
+pub fn is_same_file_contents(source_path: &Path, source_metadata: &Metadata, target_path: &Path) -> bool {
+    match target_path.metadata() {
+        Ok(target_metadata) if target_metadata.len() == source_metadata.len() => {
+            match (hash_file(source_path), hash_file(target_path)) {
+                (Ok(source_hash), Ok(target_hash)) => source_hash == target_hash,
+                _ => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+/// The built-in policy configured with [Args::collision_policy]
+impl DuplicatePolicy for CollisionPolicy {
+    fn resolve(&self, source: &DuplicateSource, target: &DuplicateTarget, context: &DuplicateContext) -> DuplicateAction {
+        match self {
+            CollisionPolicy::Skip => DuplicateAction::Skip,
+            CollisionPolicy::Overwrite => DuplicateAction::Overwrite,
+            CollisionPolicy::Rename => DuplicateAction::Rename,
+            CollisionPolicy::Review => ReviewDuplicates.resolve(source, target, context),
+            CollisionPolicy::KeepLarger => KeepLargerFile.resolve(source, target, context),
+        }
+    }
+}
+
+/// Places every source file whose target file already exists in the `Duplicates` folder,
+/// so that nothing in the target folder is replaced and the user decides which version to keep
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReviewDuplicates;
+
+impl DuplicatePolicy for ReviewDuplicates {
+    fn resolve(&self, _source: &DuplicateSource, _target: &DuplicateTarget, _context: &DuplicateContext) -> DuplicateAction {
+        DuplicateAction::Review
+    }
+}
+
+/// Replaces the target file if the source file is larger, e.g. the original of a photo whose
+/// target is a smaller copy shared via a messaging app, and keeps both files otherwise
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KeepLargerFile;
+
+impl DuplicatePolicy for KeepLargerFile {
+    fn resolve(&self, source: &DuplicateSource, target: &DuplicateTarget, _context: &DuplicateContext) -> DuplicateAction {
+        match target.metadata() {
+            Some(target_metadata) if source.metadata.len() > target_metadata.len() => DuplicateAction::Overwrite,
+            _ => DuplicateAction::Rename,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::path::PathBuf;
+
+    /// A source file and an existing target file with the same name, written to a new temporary folder
+    struct Duplicate {
+        dir: PathBuf,
+        source_path: PathBuf,
+        target_path: PathBuf,
+    }
+
+    impl Duplicate {
+        fn new(test_name: &str, source_contents: &str, target_contents: &str) -> Duplicate {
+            let dir = env::temp_dir().join(format!("imgsorter-duplicate-policy-{}-{}", test_name, std::process::id()));
+            fs::create_dir_all(dir.join("source")).unwrap();
+            fs::create_dir_all(dir.join("target")).unwrap();
+            let source_path = dir.join("source").join("IMG_1.jpg");
+            let target_path = dir.join("target").join("IMG_1.jpg");
+            fs::write(&source_path, source_contents).unwrap();
+            fs::write(&target_path, target_contents).unwrap();
+            Duplicate { dir, source_path, target_path }
+        }
+
+        fn decide(&self, policy: &dyn DuplicatePolicy, can_overwrite: bool) -> DuplicateDecision {
+            let args = Args::new().unwrap();
+            let metadata = fs::metadata(&self.source_path).unwrap();
+            let source = DuplicateSource { path: &self.source_path, metadata: &metadata, device_name: None };
+            let context = DuplicateContext { args: &args, can_overwrite };
+            decide_duplicate(policy, &source, &DuplicateTarget { path: &self.target_path }, &context)
+        }
+    }
+
+    impl Drop for Duplicate {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn applies_the_configured_collision_policy() {
+        let duplicate = Duplicate::new("collision", "new photo", "old photo");
+
+        assert_eq!(duplicate.decide(&CollisionPolicy::Skip, true), DuplicateDecision::Resolved(DuplicateAction::Skip));
+        assert_eq!(duplicate.decide(&CollisionPolicy::Rename, true), DuplicateDecision::Resolved(DuplicateAction::Rename));
+        assert_eq!(duplicate.decide(&CollisionPolicy::Overwrite, true), DuplicateDecision::Resolved(DuplicateAction::Overwrite));
+        assert_eq!(duplicate.decide(&CollisionPolicy::Review, true), DuplicateDecision::Resolved(DuplicateAction::Review));
+    }
+
+    #[test]
+    fn skips_identical_files_unless_they_are_overwritten() {
+        let duplicate = Duplicate::new("identical", "same photo", "same photo");
+
+        assert_eq!(duplicate.decide(&CollisionPolicy::Rename, true), DuplicateDecision::Identical);
+        assert_eq!(duplicate.decide(&ReviewDuplicates, true), DuplicateDecision::Identical);
+        assert_eq!(duplicate.decide(&CollisionPolicy::Overwrite, true), DuplicateDecision::Resolved(DuplicateAction::Overwrite));
+    }
+
+    #[test]
+    fn never_overwrites_if_not_allowed() {
+        let duplicate = Duplicate::new("protected", "larger new photo", "old photo");
+
+        assert_eq!(duplicate.decide(&CollisionPolicy::Overwrite, false), DuplicateDecision::Resolved(DuplicateAction::Skip));
+        assert_eq!(duplicate.decide(&KeepLargerFile, false), DuplicateDecision::Resolved(DuplicateAction::Skip));
+    }
+
+    #[test]
+    fn reviews_every_different_file() {
+        let duplicate = Duplicate::new("review", "new photo", "old photo");
+
+        assert_eq!(duplicate.decide(&ReviewDuplicates, true), DuplicateDecision::Resolved(DuplicateAction::Review));
+    }
+
+    #[test]
+    fn keeps_the_larger_file() {
+        let larger_source = Duplicate::new("larger-source", "larger new photo", "old photo");
+        let smaller_source = Duplicate::new("smaller-source", "new", "old photo");
+        let same_size = Duplicate::new("same-size", "new photo", "old photo");
+
+        assert_eq!(larger_source.decide(&KeepLargerFile, true), DuplicateDecision::Resolved(DuplicateAction::Overwrite));
+        assert_eq!(smaller_source.decide(&KeepLargerFile, true), DuplicateDecision::Resolved(DuplicateAction::Rename));
+        assert_eq!(same_size.decide(&CollisionPolicy::KeepLarger, true), DuplicateDecision::Resolved(DuplicateAction::Rename));
+    }
+}
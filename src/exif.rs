@@ -1,16 +1,32 @@
 use std::fs::{DirEntry, File};
 use std::io::{Read, Seek, SeekFrom};
+use std::fmt;
 use std::path::Path;
 
-use chrono::NaiveDateTime;
-use exif::{Error, Exif, In, Tag};
-use rexif::{ExifResult, ExifTag};
+use chrono::{NaiveDate, NaiveDateTime};
+use exif::{Error, Exif, In, Tag, Value};
+use rexif::{ExifError, ExifResult, ExifTag};
+use serde::{Deserialize, Serialize};
 
 use crate::config::*;
+use crate::heif::{is_heif, read_heif_exif_data};
+use crate::raw::read_raw_exif_data;
+use crate::text_meta::read_text_metadata_date;
 use crate::utils::*;
 
-const REXIF_DATE_FORMAT: &str = "%Y:%m:%d %H:%M:%S";
+/// The standard format of EXIF dates, as written in the file and as returned by rexif
+pub const EXIF_DATE_FORMAT: &str = "%Y:%m:%d %H:%M:%S";
 const KAMADAK_EXIF_DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+/// The length of a date in [EXIF_DATE_FORMAT], without the NUL which ends it in the file
+const EXIF_DATE_LEN: usize = 19;
+
+/// Number of bytes read from the start of a file when scanning it for EXIF dates, see [scan_exif_date].
+/// The EXIF segment of JPEG files is at most 64 KB, and it's near the start of most other images
+const TAG_SCAN_LEN: u64 = 512 * 1024;
+
+/// Number of bytes read to check if a file needs to be read separately from kamadak-exif,
+/// enough for the `ftyp` box of HEIF images with all its brands, or the header of RAF files
+const CONTAINER_HEADER_LEN: u64 = 256;
 
 /// Selected EXIF Data for a [[SupportedFile]]
 /// Currently includes only the image date and camera model
@@ -19,6 +35,104 @@ pub struct ExifDateDevice {
     pub date: Option<String>,
     pub camera_make: Option<String>,
     pub camera_model: Option<String>,
+    /// The lens used to take the photo, e.g. `iPhone 12 Pro back triple camera 6mm f/2`
+    pub lens_model: Option<String>,
+    /// Set if the file contains an EXIF segment which could not be read, or if the creation time of a video could not be read
+    pub read_error: Option<String>,
+    /// The full date and time when the photo was taken, as read from EXIF
+    pub capture_time: Option<String>,
+    /// The fraction of the second when the photo was taken, e.g. `352`, which tells apart burst shots
+    pub capture_subsec: Option<String>,
+    /// Image width and height in pixels, as read from EXIF
+    pub dimensions: Option<(u32, u32)>,
+    /// The camera settings used to take the photo
+    pub shooting_info: ShootingInfo,
+    /// Where the photo was taken, as read from the GPS tags
+    pub location: Option<GpsLocation>,
+    /// The parser which read the EXIF data, if any, see [read_image_exif_data]
+    pub parser: Option<ExifParser>,
+}
+
+/// The ways of reading EXIF data, tried in this order until one of them finds a date
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExifParser {
+    /// The kamadak-exif crate, which reads most files
+    Kamadak,
+    /// The rexif crate, which reads some EXIF segments which kamadak-exif rejects as damaged
+    Rexif,
+    /// A scan of the raw bytes of the file for dates in the EXIF format, see [scan_exif_date]
+    TagScan,
+}
+
+impl ExifParser {
+    pub const fn name(&self) -> &'static str {
+        match self {
+            ExifParser::Kamadak => "kamadak-exif",
+            ExifParser::Rexif => "rexif",
+            ExifParser::TagScan => "tag scan",
+        }
+    }
+}
+
+/// The camera settings used to take a photo, as read from EXIF
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ShootingInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iso: Option<u32>,
+    /// In seconds, e.g. "1/125" or "2"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exposure_time: Option<String>,
+    /// The aperture, e.g. 1.8 for f/1.8
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub f_number: Option<f64>,
+    /// In millimeters
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub focal_length: Option<f64>,
+}
+
+/// The coordinates where a photo was taken, in decimal degrees,
+/// negative for southern latitudes and western longitudes
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GpsLocation {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl GpsLocation {
+    /// Some phones write zeros when they have no GPS fix, so these are treated as missing.
+    /// Rounded to six decimals, which is more precise than any phone's GPS
+    pub fn new(latitude: f64, longitude: f64) -> Option<GpsLocation> {
+        let round = |degrees: f64| (degrees * 1_000_000.0).round() / 1_000_000.0;
+        let (latitude, longitude) = (round(latitude), round(longitude));
+        let is_valid = (-90.0..=90.0).contains(&latitude)
+            && (-180.0..=180.0).contains(&longitude)
+            && !(latitude == 0.0 && longitude == 0.0);
+        if is_valid { Some(GpsLocation { latitude, longitude }) } else { None }
+    }
+}
+
+impl fmt::Display for GpsLocation {
+    /// Five decimals are precise to about one meter, e.g. `45.43780, 12.33590`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.5}, {:.5}", self.latitude, self.longitude)
+    }
+}
+
+impl ShootingInfo {
+    pub fn is_empty(&self) -> bool {
+        *self == ShootingInfo::default()
+    }
+}
+
+/// The formats of [ExifDateDevice::capture_time], which is usually converted from
+/// the EXIF format, but may also be the EXIF value itself
+const CAPTURE_TIME_FORMATS: [&str; 2] = ["%Y-%m-%d %H:%M:%S", "%Y:%m:%d %H:%M:%S"];
+
+/// Read the date and time of [ExifDateDevice::capture_time]
+pub fn parse_capture_time(capture_time: &str) -> Option<NaiveDateTime> {
+    CAPTURE_TIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(capture_time, format).ok())
 }
 
 impl ExifDateDevice {
@@ -27,6 +141,14 @@ impl ExifDateDevice {
             date: None,
             camera_make: None,
             camera_model: None,
+            lens_model: None,
+            read_error: None,
+            capture_time: None,
+            capture_subsec: None,
+            dimensions: None,
+            shooting_info: ShootingInfo::default(),
+            location: None,
+            parser: None,
         }
     }
 
@@ -35,7 +157,9 @@ impl ExifDateDevice {
     // Otherwise, make return a composite of "make model",
     // unless the model already starts with the make name,
     // e.g. "HUAWEI HUAWEI CAN-L11" should return "HUAWEI CAN-L11"
-    pub fn get_device_name(&self, include_make: bool) -> Option<String> {
+    // Phones with multiple lenses may also be merged into a single device or split by lens,
+    // see [LensGrouping]
+    pub fn get_device_name(&self, include_make: bool, lens_grouping: &LensGrouping) -> Option<String> {
         self.camera_model
             .as_ref()
             .map(|camera_model| {
@@ -54,9 +178,53 @@ impl ExifDateDevice {
                     camera_model.clone()
                 }
             })
+            .map(|device_name| match lens_grouping {
+                LensGrouping::Ignore => device_name,
+                LensGrouping::Merge => strip_lens_descriptors(&device_name),
+                LensGrouping::Split => match self.get_lens_name() {
+                    Some(lens_name) => format!("{} {}", strip_lens_descriptors(&device_name), lens_name),
+                    None => device_name,
+                },
+            })
+    }
+
+    // The lens model usually starts with the camera model, which is left out,
+    // e.g. "iPhone 12 Pro back triple camera 6mm f/2" should return "back triple camera 6mm f2"
+    // The camera model is compared without any lens descriptors, since those describe the lens
+    pub fn get_lens_name(&self) -> Option<String> {
+        let camera_model = self.camera_model.as_deref().map(strip_lens_descriptors);
+        self.lens_model
+            .as_ref()
+            .map(|lens_model| {
+                let camera_model_len = camera_model.as_ref().map_or(0, String::len);
+                let starts_with_model = match (&camera_model, lens_model.get(..camera_model_len)) {
+                    (Some(camera_model), Some(prefix)) => prefix.eq_ignore_ascii_case(camera_model),
+                    _ => false,
+                };
+                let lens_name = if starts_with_model { lens_model[camera_model_len..].trim() } else { lens_model.as_str() };
+                // The lens name is used in folder names, where a slash would create a subfolder,
+                // so the aperture is written without it, e.g. "f/2.2" becomes "f2.2"
+                lens_name.replace(['/', '\\', ':'], "")
+            })
+            .filter(|lens_name| !lens_name.is_empty())
     }
 }
 
+/// Words which some phones append to the camera model to tell their lenses apart,
+/// e.g. "Pixel 7 Pro front camera", see [LensGrouping::Merge]
+const LENS_DESCRIPTOR_WORDS: &[&str] = &[
+    "front", "back", "rear", "selfie", "main", "wide", "ultrawide", "ultra-wide", "ultra",
+    "tele", "telephoto", "macro", "camera", "lens"];
+
+/// Remove the lens descriptors at the end of the device name, but never the whole name
+fn strip_lens_descriptors(device_name: &str) -> String {
+    let mut words: Vec<&str> = device_name.split_whitespace().collect();
+    while words.len() > 1 && words.last().is_some_and(|word| LENS_DESCRIPTOR_WORDS.contains(&word.to_lowercase().as_str())) {
+        words.pop();
+    }
+    words.join(" ")
+}
+
 impl Default for ExifDateDevice {
     fn default() -> Self {
         Self::new()
@@ -64,20 +232,61 @@ impl Default for ExifDateDevice {
 }
 
 /// Parse an EXIF date string into the date string format for our directories: "YYYY.MM.DD"
-fn parse_exif_date(exif_date_str: String, exif_date_format: &str, args: &Args) -> Option<String> {
-    let parsed_date_result =
-        NaiveDateTime::parse_from_str(exif_date_str.as_str(), exif_date_format);
-    match parsed_date_result {
-        Ok(date) => {
-            let formatted_date = date.format(DATE_DIR_FORMAT).to_string();
-            Some(formatted_date)
-        }
-        Err(err) => {
-            if args.debug {
-                println!("> could not parse EXIF date {}: {:?}", exif_date_str, err)
-            }
-            None
-        }
+fn parse_exif_date(exif_date_str: String, exif_date_format: &str, exif_data: &ExifDateDevice, file: &DirEntry, args: &Args) -> Option<String> {
+    parse_exif_date_time(&exif_date_str, exif_date_format, exif_data, file, args)
+        .map(|date| date.format(DATE_DIR_FORMAT).to_string())
+}
+
+/// Parse an EXIF date and time, first in the standard format, then in the extra formats from the config:
+/// those of the device which took the photo, see [Args::device_exif_date_formats], then those of all devices,
+/// see [Args::exif_date_formats]. Formats without a time are read as midnight.
+/// Dates which can't be read are reported in verbose mode, since the file name or the modified date is used instead
+fn parse_exif_date_time(
+    exif_date_str: &str,
+    exif_date_format: &str,
+    exif_data: &ExifDateDevice,
+    file: &DirEntry,
+    args: &Args
+) -> Option<NaiveDateTime> {
+    let exif_date_str = exif_date_str.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+
+    // Devices can be configured by their model, with or without their make
+    let device_formats = exif_data.camera_model
+        .iter()
+        .flat_map(|model| {
+            let make_and_model = exif_data.camera_make.as_ref().map(|make| format!("{} {}", make, model));
+            std::iter::once(model.clone()).chain(make_and_model)
+        })
+        .filter_map(|device_name| args.device_exif_date_formats.get(&device_name.to_lowercase()))
+        .flatten()
+        .map(String::as_str);
+
+    let date_time = std::iter::once(exif_date_format)
+        .chain(device_formats)
+        .chain(args.exif_date_formats.iter().map(String::as_str))
+        .find_map(|format| {
+            NaiveDateTime::parse_from_str(exif_date_str, format)
+                .ok()
+                .or_else(|| NaiveDate::parse_from_str(exif_date_str, format).ok()?.and_hms_opt(0, 0, 0))
+        });
+
+    // Blank dates, e.g. `    :  :     :  :  `, are written by cameras whose clock was never set
+    if date_time.is_none() && (args.verbose || args.debug) && exif_date_str.chars().any(|c| c.is_ascii_digit()) {
+        println!("{} could not read the EXIF date '{}' of {:?}, add its format to `exif_date_formats` to read it",
+                 ColoredString::warn_arrow(), exif_date_str, file.path());
+    }
+
+    date_time
+}
+
+/// The text of a date field, as written in the file, e.g. `2023:05:14 10:30:00`
+fn get_exif_date_text(field: &exif::Field) -> String {
+    match &field.value {
+        Value::Ascii(values) => values
+            .first()
+            .map(|value| String::from_utf8_lossy(value).to_string())
+            .unwrap_or_default(),
+        _ => field.display_value().to_string(),
     }
 }
 
@@ -86,11 +295,18 @@ pub fn read_exif_date_and_device(file: &DirEntry, args: &Args) -> ExifDateDevice
         date: None,
         camera_make: None,
         camera_model: None,
+        lens_model: None,
+        read_error: None,
+        capture_time: None,
+        capture_subsec: None,
+        dimensions: None,
+        shooting_info: ShootingInfo::default(),
+        location: None,
+        parser: None,
     };
 
-    // TODO 5d: handle this unwrap
     // Return early if this is not a file, there's no device name to read
-    if file.metadata().unwrap().is_dir() {
+    if file.path().is_dir() {
         return exif_data;
     }
 
@@ -99,6 +315,7 @@ pub fn read_exif_date_and_device(file: &DirEntry, args: &Args) -> ExifDateDevice
     // match rexif::parse_file(&file_name) {
     match read_exif(file.path()) {
         Ok(exif) => {
+            exif_data.parser = Some(ExifParser::Rexif);
             // Iterate all EXIF entries and filter only the Model and certain *Date tags
             let _ = &exif.entries.iter().for_each(|exif_entry| {
                 match exif_entry.tag {
@@ -125,7 +342,7 @@ pub fn read_exif_date_and_device(file: &DirEntry, args: &Args) -> ExifDateDevice
                         let tag_value = exif_entry.value.to_string();
                         if exif_data.date.is_none() {
                             // Only use this if DateTimeOriginal was not found
-                            exif_data.date = parse_exif_date(tag_value, REXIF_DATE_FORMAT, args);
+                            exif_data.date = parse_exif_date(tag_value, EXIF_DATE_FORMAT, &exif_data, file, args);
                         }
                     }
 
@@ -133,7 +350,11 @@ pub fn read_exif_date_and_device(file: &DirEntry, args: &Args) -> ExifDateDevice
                     // Prefer this over DateTime
                     ExifTag::DateTimeOriginal => {
                         let tag_value = exif_entry.value.to_string();
-                        exif_data.date = parse_exif_date(tag_value, REXIF_DATE_FORMAT, args);
+                        let date_time = parse_exif_date_time(&tag_value, EXIF_DATE_FORMAT, &exif_data, file, args);
+                        if let Some(date_time) = date_time {
+                            exif_data.capture_time = Some(date_time.format(KAMADAK_EXIF_DATE_FORMAT).to_string());
+                            exif_data.date = Some(date_time.format(DATE_DIR_FORMAT).to_string());
+                        }
                     }
 
                     // EXIF:DateTimeDigitized: When the image was converted to digital form.
@@ -155,7 +376,7 @@ pub fn read_exif_date_and_device(file: &DirEntry, args: &Args) -> ExifDateDevice
         Err(e) => {
             if args.debug {
                 println!("{} could not read EXIF for {:?}: {}",
-                         ColoredString::warn_arrow(), file.file_name(), e.to_string());
+                         ColoredString::warn_arrow(), file.file_name(), e);
             }
         }
     }
@@ -167,11 +388,10 @@ pub fn read_exif_date_and_device(file: &DirEntry, args: &Args) -> ExifDateDevice
 /// to bypass `rexif::parse_buffer` which prints warnings to stderr
 fn read_exif<P: AsRef<Path>>(file_name: P) -> ExifResult {
     // let file_name = file_entry.path();
-    // TODO 5d: handle these unwraps
-    let mut file = File::open(file_name).unwrap();
-    let _ = &file.seek(SeekFrom::Start(0)).unwrap();
+    let mut file = File::open(file_name).map_err(ExifError::IoError)?;
+    file.seek(SeekFrom::Start(0)).map_err(ExifError::IoError)?;
     let mut contents: Vec<u8> = Vec::new();
-    let _ = &file.read_to_end(&mut contents);
+    file.read_to_end(&mut contents).map_err(ExifError::IoError)?;
     let (res, _) = rexif::parse_buffer_quiet(&contents);
     res
 }
@@ -181,18 +401,25 @@ pub fn read_kamadak_exif_date_and_device(file: &DirEntry, args: &Args) -> ExifDa
         date: None,
         camera_make: None,
         camera_model: None,
+        lens_model: None,
+        read_error: None,
+        capture_time: None,
+        capture_subsec: None,
+        dimensions: None,
+        shooting_info: ShootingInfo::default(),
+        location: None,
+        parser: None,
     };
 
-    // TODO 5d: handle this unwrap
     // Return early if this is not a file, there's no device name to read
-    if file.metadata().unwrap().is_dir() {
+    if file.path().is_dir() {
         return exif_date_device;
     }
 
     // Some models are retrieved with extra characters which require removal
     // e.g.: "HUAWEI CAN-L11", ""
     // e.g.: "ALLVIEW P5 camera              "  // <-- yes, lots of extra spaces
-    fn clean_device_model_or_make(device_str: &String) -> String {
+    fn clean_device_model_or_make(device_str: &str) -> String {
         device_str
             .replace("\"", "")
             .replace(",", "")
@@ -202,6 +429,7 @@ pub fn read_kamadak_exif_date_and_device(file: &DirEntry, args: &Args) -> ExifDa
 
     match read_kamadak_exif(file.path()) {
         Ok(exif) => {
+            exif_date_device.parser = Some(ExifParser::Kamadak);
 
             exif_date_device.camera_make = exif
                 .get_field(Tag::Make, In::PRIMARY)
@@ -224,20 +452,34 @@ pub fn read_kamadak_exif_date_and_device(file: &DirEntry, args: &Args) -> ExifDa
                 exif_date_device.camera_model = Some(trimmed_model);
             };
 
+            exif_date_device.lens_model = exif
+                .get_field(Tag::LensModel, In::PRIMARY)
+                .map(|lens_model| clean_device_model_or_make(&lens_model.display_value().to_string()))
+                .filter(|lens_model| !lens_model.is_empty());
+
             // EXIF:DateTimeOriginal: When the shutter was clicked. Windows File Explorer will display it as Date Taken.
             // Prefer this over DateTime
-            // The display value of the string returned by kamadak-exif has the format "YYYY-MM-DD HH:MM:SS"
+            // The display value of the string returned by kamadak-exif has the format "YYYY-MM-DD HH:MM:SS",
+            // but only for dates in the standard format, so the text written in the file is parsed instead
             if let Some(date) = exif.get_field(Tag::DateTimeOriginal, In::PRIMARY) {
-                let tag_value = date.display_value().to_string();
-                exif_date_device.date = parse_exif_date(tag_value, KAMADAK_EXIF_DATE_FORMAT, args);
+                let date_time = parse_exif_date_time(&get_exif_date_text(date), EXIF_DATE_FORMAT, &exif_date_device, file, args);
+                exif_date_device.capture_time = Some(match date_time {
+                    Some(date_time) => date_time.format(KAMADAK_EXIF_DATE_FORMAT).to_string(),
+                    None => date.display_value().to_string(),
+                });
+                exif_date_device.date = date_time.map(|date_time| date_time.format(DATE_DIR_FORMAT).to_string());
+                exif_date_device.capture_subsec = exif
+                    .get_field(Tag::SubSecTimeOriginal, In::PRIMARY)
+                    .map(|subsec| get_exif_date_text(subsec).trim().to_string())
+                    .filter(|subsec| !subsec.is_empty());
 
             // EXIF:DateTime: When photo software last modified the image or its metadata.
             // Operating system Date Modified: The time that any application or the camera or
             // operating system itself modified the file.
             // Should prefer DateTimeOriginal over this
             } else if let Some(date) = exif.get_field(Tag::DateTime, In::PRIMARY) {
-                let tag_value = date.display_value().to_string();
-                exif_date_device.date = parse_exif_date(tag_value, KAMADAK_EXIF_DATE_FORMAT, args);
+                let tag_value = get_exif_date_text(date);
+                exif_date_device.date = parse_exif_date(tag_value, EXIF_DATE_FORMAT, &exif_date_device, file, args);
             };
 
             // EXIF:DateTimeDigitized: When the image was converted to digital form.
@@ -246,22 +488,166 @@ pub fn read_kamadak_exif_date_and_device(file: &DirEntry, args: &Args) -> ExifDa
             // while DateTimeOriginal was when the shutter was clicked on the film camera.
             // We don't need DateTimeDigitized for now
 
+            let pixel_dimension = |tag: Tag| exif
+                .get_field(tag, In::PRIMARY)
+                .and_then(|field| field.value.get_uint(0));
+
+            if let (Some(width), Some(height)) = (pixel_dimension(Tag::PixelXDimension), pixel_dimension(Tag::PixelYDimension)) {
+                exif_date_device.dimensions = Some((width, height));
+            }
+
+            // Rounded, since rationals such as 43/10 can't be represented exactly
+            let decimal = |tag: Tag| exif
+                .get_field(tag, In::PRIMARY)
+                .and_then(|field| match &field.value {
+                    Value::Rational(values) => values.first().map(|value| value.to_f64()),
+                    _ => None,
+                })
+                .filter(|value| value.is_finite() && *value > 0.0)
+                .map(|value| (value * 100.0).round() / 100.0);
+
+            exif_date_device.shooting_info = ShootingInfo {
+                iso: exif
+                    .get_field(Tag::PhotographicSensitivity, In::PRIMARY)
+                    .and_then(|field| field.value.get_uint(0)),
+                exposure_time: exif
+                    .get_field(Tag::ExposureTime, In::PRIMARY)
+                    .map(|field| field.display_value().to_string()),
+                f_number: decimal(Tag::FNumber),
+                focal_length: decimal(Tag::FocalLength),
+            };
+
+            // Each coordinate is written as degrees, minutes and seconds, with a separate tag
+            // for the hemisphere, e.g. 'S' for southern latitudes, which is assumed to be
+            // the northern or eastern one if it's missing
+            let coordinate = |tag: Tag, ref_tag: Tag, negative_ref: u8| -> Option<f64> {
+                let degrees = match &exif.get_field(tag, In::PRIMARY)?.value {
+                    Value::Rational(values) if values.len() == 3 =>
+                        values[0].to_f64() + values[1].to_f64() / 60.0 + values[2].to_f64() / 3600.0,
+                    _ => return None,
+                };
+                let is_negative = exif
+                    .get_field(ref_tag, In::PRIMARY)
+                    .is_some_and(|field| match &field.value {
+                        Value::Ascii(values) => values.first().and_then(|value| value.first()) == Some(&negative_ref),
+                        _ => false,
+                    });
+                Some(if is_negative { -degrees } else { degrees }).filter(|degrees| degrees.is_finite())
+            };
+
+            exif_date_device.location = match (
+                coordinate(Tag::GPSLatitude, Tag::GPSLatitudeRef, b'S'),
+                coordinate(Tag::GPSLongitude, Tag::GPSLongitudeRef, b'W'),
+            ) {
+                (Some(latitude), Some(longitude)) => GpsLocation::new(latitude, longitude),
+                _ => None,
+            };
+
             // Ignore other EXIF tags
         }
         Err(e) => {
             if args.debug {
                 println!("{} could not read EXIF for {:?}: {}",
-                         ColoredString::warn_arrow(), file.file_name(), e.to_string());
+                         ColoredString::warn_arrow(), file.file_name(), e);
+            }
+            // A missing EXIF segment is normal for many files, anything else means it's unreadable
+            if !matches!(e, Error::NotFound(_)) {
+                exif_date_device.read_error = Some(e.to_string());
             }
         }
     }
 
+    // PNG and WebP files often don't have EXIF, but can have a date in their text metadata
+    if exif_date_device.date.is_none() {
+        if let Some(created) = read_text_metadata_date(&file.path()) {
+            exif_date_device.capture_time = Some(created.format(KAMADAK_EXIF_DATE_FORMAT).to_string());
+            exif_date_device.date = Some(created.format(DATE_DIR_FORMAT).to_string());
+        }
+    }
+
     exif_date_device
 }
 
+/// Read the EXIF data of an image with each [ExifParser] in turn, until one of them finds a date.
+/// The fallbacks are only tried if kamadak-exif found an EXIF segment but couldn't read it or found no date,
+/// not for the many files which have no EXIF data at all. The date read by a fallback fills in what's missing
+/// from the data read by kamadak-exif, and since the EXIF data could be read after all, the read error is cleared
+pub fn read_image_exif_data(file: &DirEntry, args: &Args) -> ExifDateDevice {
+    let mut exif_data = read_kamadak_exif_date_and_device(file, args);
+    if exif_data.date.is_some() || (exif_data.parser.is_none() && exif_data.read_error.is_none()) {
+        return exif_data;
+    }
+
+    let rexif_data = read_exif_date_and_device(file, args);
+    let fallback = if rexif_data.date.is_some() {
+        Some((ExifParser::Rexif, rexif_data))
+    } else {
+        scan_exif_date(&file.path()).map(|date_time| {
+            let mut scanned_data = ExifDateDevice::new();
+            scanned_data.capture_time = Some(date_time.format(KAMADAK_EXIF_DATE_FORMAT).to_string());
+            scanned_data.date = Some(date_time.format(DATE_DIR_FORMAT).to_string());
+            (ExifParser::TagScan, scanned_data)
+        })
+    };
+
+    if let Some((parser, fallback_data)) = fallback {
+        if args.verbose {
+            println!("{} read the EXIF date of {:?} with {}", ColoredString::warn_arrow(), file.path(), parser.name());
+        }
+        exif_data.date = fallback_data.date;
+        exif_data.capture_time = exif_data.capture_time.or(fallback_data.capture_time);
+        exif_data.camera_make = exif_data.camera_make.or(fallback_data.camera_make);
+        exif_data.camera_model = exif_data.camera_model.or(fallback_data.camera_model);
+        exif_data.read_error = None;
+        exif_data.parser = Some(parser);
+    }
+
+    exif_data
+}
+
+/// Find the earliest EXIF date in the raw bytes at the start of a file, i.e. text in the `YYYY:MM:DD HH:MM:SS`
+/// format ending with a NUL, as written in the DateTimeOriginal, DateTimeDigitized and DateTime tags.
+/// The earliest one is used since DateTime is usually written first, but changes when the photo is edited.
+/// This is the last resort for files whose EXIF data is damaged or in a container which can't be read otherwise
+fn scan_exif_date(file_path: &Path) -> Option<NaiveDateTime> {
+    let mut contents = Vec::new();
+    File::open(file_path).ok()?.take(TAG_SCAN_LEN).read_to_end(&mut contents).ok()?;
+
+    let is_exif_date = |bytes: &[u8]| bytes.iter().enumerate().all(|(index, byte)| match index {
+        4 | 7 | 13 | 16 => *byte == b':',
+        10 => *byte == b' ',
+        19 => *byte == 0,
+        _ => byte.is_ascii_digit(),
+    });
+
+    contents
+        .windows(EXIF_DATE_LEN + 1)
+        .filter(|bytes| is_exif_date(bytes))
+        .filter_map(|bytes| std::str::from_utf8(&bytes[..EXIF_DATE_LEN]).ok())
+        .filter_map(|text| NaiveDateTime::parse_from_str(text, EXIF_DATE_FORMAT).ok())
+        .min()
+}
+
 pub fn read_kamadak_exif<P: AsRef<Path>>(file_name: P) -> Result<Exif, Error> {
     let file = std::fs::File::open(file_name)?;
+    let file_len = file.metadata()?.len();
     let mut bufreader = std::io::BufReader::new(&file);
     let exifreader = exif::Reader::new();
+
+    // kamadak-exif only reads HEIF files which list the `mif1` brand and have less than 64 KB of EXIF data,
+    // which leaves out many photos from phones, so HEIF files are read separately
+    let mut header = Vec::new();
+    (&mut bufreader).take(CONTAINER_HEADER_LEN).read_to_end(&mut header)?;
+    if is_heif(&header) {
+        let exif_data = read_heif_exif_data(&mut bufreader, file_len)?;
+        return exifreader.read_raw(exif_data);
+    }
+
+    // Some RAW formats are not regular TIFF files
+    if let Some(exif_data) = read_raw_exif_data(&mut bufreader, &header)? {
+        return exifreader.read_raw(exif_data);
+    }
+
+    bufreader.seek(SeekFrom::Start(0))?;
     exifreader.read_from_container(&mut bufreader)
 }
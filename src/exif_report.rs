@@ -0,0 +1,71 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::exif::ShootingInfo;
+
+/// The name of the report written inside each target folder, with the EXIF details of each photo,
+/// so that the archive can be searched by camera settings without a photo manager
+pub const EXIF_REPORT_FILE_NAME: &str = "imgsorter.exif.jsonl";
+
+/// A single photo in the report, written as one JSON line, e.g.:
+/// ```text
+/// {"file":"IMG_0001.JPG","taken":"2019-01-28 14:03:10","device":"Canon 100D","width":5184,"height":3456,"iso":200,"exposure_time":"1/125","f_number":5.6,"focal_length":35.0,"latitude":45.4378,"longitude":12.3359}
+/// ```
+#[derive(Debug, Serialize)]
+pub struct ExifReportEntry<'a> {
+    pub file: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub taken: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    #[serde(flatten)]
+    pub shooting_info: &'a ShootingInfo,
+    /// In decimal degrees, see [crate::exif::GpsLocation]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latitude: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub longitude: Option<f64>,
+}
+
+/// Only the file name is needed to remove an entry
+#[derive(Deserialize)]
+struct ReportedFile {
+    file: String,
+}
+
+/// Append the entry to the report inside `dir`, creating the report if it doesn't exist yet
+pub fn append_exif_report_entry(dir: &Path, entry: &ExifReportEntry) -> Result<(), io::Error> {
+    let line = serde_json::to_string(entry)?;
+    let mut report = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(EXIF_REPORT_FILE_NAME))?;
+    writeln!(report, "{}", line)
+}
+
+/// Remove all entries of a file from the report inside `dir`, e.g. after the file was removed
+/// from the archive. The report is removed if no other files are listed in it
+pub fn remove_exif_report_entry(dir: &Path, file_name: &str) -> Result<(), io::Error> {
+    let report_path = dir.join(EXIF_REPORT_FILE_NAME);
+    let contents = fs::read_to_string(&report_path)?;
+
+    let remaining_lines: Vec<&str> = contents
+        .lines()
+        .filter(|line| {
+            serde_json::from_str::<ReportedFile>(line).map_or(true, |reported| reported.file != file_name)
+        })
+        .collect();
+
+    if remaining_lines.is_empty() {
+        fs::remove_file(report_path)
+    } else {
+        fs::write(report_path, remaining_lines.join("\n") + "\n")
+    }
+}
@@ -0,0 +1,277 @@
+use chrono::{Local, NaiveDate};
+
+/// The placeholders which can be used in [crate::config::Args::filename_date_patterns]
+pub const FILENAME_DATE_PLACEHOLDERS: &[&str] = &["{year}", "{month}", "{day}"];
+
+/// Dates before this year are most likely other numbers which happen to look like a date
+const MIN_FILENAME_DATE_YEAR: i32 = 1990;
+
+/// How the day and the month are read from patterns where they're both followed by the year,
+/// e.g. `03-04-2021` is the 3rd of April in most of the world, but March 4th in the US.
+/// Whatever the order, a date which is only valid in the other order is read in the other order,
+/// e.g. `25-12-2021` is always the 25th of December, see [crate::config::Args::filename_date_order]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilenameDateOrder {
+    /// Read the day and month in the order of the placeholders in the pattern,
+    /// unless a different order is learned for the source folder from the EXIF dates of its files
+    Auto,
+    DayFirst,
+    MonthFirst,
+}
+
+impl FilenameDateOrder {
+    /// The value used for this order in the config file
+    pub const fn name(&self) -> &'static str {
+        match self {
+            FilenameDateOrder::Auto => "auto",
+            FilenameDateOrder::DayFirst => "day_first",
+            FilenameDateOrder::MonthFirst => "month_first",
+        }
+    }
+}
+
+/// A part of a filename date pattern, see [parse_pattern]
+#[derive(Debug, Clone, PartialEq)]
+enum PatternPart {
+    Year,
+    Month,
+    Day,
+    Literal(char),
+}
+
+impl PatternPart {
+    fn digit_count(&self) -> usize {
+        match self {
+            PatternPart::Year => 4,
+            PatternPart::Month | PatternPart::Day => 2,
+            PatternPart::Literal(_) => 0,
+        }
+    }
+}
+
+/// Check that the pattern contains each of the [FILENAME_DATE_PLACEHOLDERS] exactly once and no other placeholders.
+/// Returns a description of the first problem found, if any
+pub fn validate_filename_date_pattern(pattern: &str) -> Result<(), String> {
+    let parts = parse_pattern(pattern)?;
+    for (placeholder, part) in FILENAME_DATE_PLACEHOLDERS.iter().zip([PatternPart::Year, PatternPart::Month, PatternPart::Day]) {
+        if parts.iter().filter(|existing| **existing == part).count() != 1 {
+            return Err(format!("Pattern '{}' must contain {} exactly once", pattern, placeholder));
+        }
+    }
+    Ok(())
+}
+
+/// Find a date in the file name, based on the first of the patterns which matches, e.g.
+/// `IMG_20190128_123456.jpg` or `VID-20200505-WA0001.mp4` for `{year}{month}{day}`
+/// and `Screenshot_2021-03-04-10-11-12.png` for `{year}-{month}-{day}`.
+/// A date must not follow other digits, e.g. a date won't be found in `1548678190123.jpg`,
+/// and it must not be in the future
+pub fn find_filename_date(file_name: &str, patterns: &[String], order: FilenameDateOrder) -> Option<NaiveDate> {
+    let today = Local::now().naive_local().date();
+
+    find_pattern_matches(file_name, patterns)
+        .find_map(|(parts, (year, first, second))| {
+            let swap = match order {
+                FilenameDateOrder::Auto => false,
+                FilenameDateOrder::DayFirst => is_year_last(&parts) && !is_day_first(&parts),
+                FilenameDateOrder::MonthFirst => is_year_last(&parts) && is_day_first(&parts),
+            };
+            let (first, second) = if swap { (second, first) } else { (first, second) };
+
+            // Dates which are only valid in the other order can only be read in that order
+            let date = to_date(year, first, second, is_day_first(&parts));
+            let date = if date.is_none() && is_year_last(&parts) {
+                to_date(year, second, first, is_day_first(&parts))
+            } else {
+                date
+            };
+            date.filter(|date| *date <= today)
+        })
+}
+
+/// Find the first date in the file name which can be read both with the day first and with the month first,
+/// i.e. the day is at most 12 and differs from the month, e.g. `03-04-2021`.
+/// Returns the date read with the day first, then the date read with the month first
+pub fn find_ambiguous_filename_date(file_name: &str, patterns: &[String]) -> Option<(NaiveDate, NaiveDate)> {
+    let today = Local::now().naive_local().date();
+
+    find_pattern_matches(file_name, patterns)
+        .filter(|(parts, _)| is_year_last(parts))
+        .find_map(|(_, (year, first, second))| {
+            let day_first = to_date(year, first, second, true)?;
+            let month_first = to_date(year, first, second, false)?;
+            Some((day_first, month_first))
+                .filter(|(day_first, month_first)| day_first != month_first && *day_first <= today && *month_first <= today)
+        })
+}
+
+/// The year and the two other numbers, in the order they're written, of all places in the file name
+/// which match one of the patterns, for each pattern in order.
+/// A match must not follow other digits, e.g. nothing matches in `1548678190123.jpg`
+fn find_pattern_matches<'a>(
+    file_name: &str,
+    patterns: &'a [String]
+) -> impl Iterator<Item = (Vec<PatternPart>, (i32, u32, u32))> + 'a {
+    let chars: Vec<char> = file_name.chars().collect();
+
+    patterns
+        .iter()
+        .filter_map(|pattern| parse_pattern(pattern).ok())
+        .flat_map(move |parts| {
+            let matches: Vec<(i32, u32, u32)> = (0..chars.len())
+                .filter(|start| *start == 0 || !chars[start - 1].is_ascii_digit())
+                .filter_map(|start| match_pattern_at(&chars[start..], &parts))
+                .collect();
+            matches.into_iter().map(move |numbers| (parts.clone(), numbers))
+        })
+}
+
+/// Whether the year comes after both the day and the month, e.g. `{day}.{month}.{year}`,
+/// which is the only order which differs between regions
+fn is_year_last(parts: &[PatternPart]) -> bool {
+    let position = |part: PatternPart| parts.iter().position(|existing| *existing == part);
+    position(PatternPart::Year) > position(PatternPart::Month) && position(PatternPart::Year) > position(PatternPart::Day)
+}
+
+fn is_day_first(parts: &[PatternPart]) -> bool {
+    let position = |part: PatternPart| parts.iter().position(|existing| *existing == part);
+    position(PatternPart::Day) < position(PatternPart::Month)
+}
+
+/// Build a date from the two numbers which are not the year, in the order they're written
+fn to_date(year: i32, first: u32, second: u32, is_day_first: bool) -> Option<NaiveDate> {
+    let (month, day) = if is_day_first { (second, first) } else { (first, second) };
+    NaiveDate::from_ymd_opt(year, month, day)
+}
+
+/// Split the pattern into placeholders and the literal characters between them
+fn parse_pattern(pattern: &str) -> Result<Vec<PatternPart>, String> {
+    let mut parts = Vec::new();
+    let mut remaining = pattern;
+
+    while !remaining.is_empty() {
+        if remaining.starts_with('{') {
+            let end = remaining.find('}')
+                .ok_or_else(|| format!("Missing '}}' after '{}'", remaining))? + 1;
+            parts.push(match &remaining[..end] {
+                "{year}" => PatternPart::Year,
+                "{month}" => PatternPart::Month,
+                "{day}" => PatternPart::Day,
+                placeholder => return Err(format!("Unknown placeholder '{}', must be one of {}",
+                                                  placeholder, FILENAME_DATE_PLACEHOLDERS.join(", "))),
+            });
+            remaining = &remaining[end..];
+        } else {
+            let literal = remaining.chars().next().unwrap_or_default();
+            parts.push(PatternPart::Literal(literal));
+            remaining = &remaining[literal.len_utf8()..];
+        }
+    }
+
+    Ok(parts)
+}
+
+/// Match the pattern against the start of the text and return the year and the other two numbers,
+/// in the order they're written, which may not be a valid date yet
+fn match_pattern_at(text: &[char], parts: &[PatternPart]) -> Option<(i32, u32, u32)> {
+    let mut year = 0;
+    let mut numbers: Vec<u32> = Vec::new();
+    let mut position = 0;
+
+    for part in parts {
+        match part {
+            PatternPart::Literal(literal) => {
+                if text.get(position)? != literal {
+                    return None;
+                }
+                position += 1;
+            }
+            _ => {
+                let digits = text.get(position..position + part.digit_count())?;
+                if !digits.iter().all(char::is_ascii_digit) {
+                    return None;
+                }
+                let value: u32 = digits.iter().collect::<String>().parse().ok()?;
+                match part {
+                    PatternPart::Year => year = value as i32,
+                    _ => numbers.push(value),
+                }
+                position += digits.len();
+            }
+        }
+    }
+
+    if year < MIN_FILENAME_DATE_YEAR {
+        return None;
+    }
+    Some((year, *numbers.first()?, *numbers.get(1)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns(patterns: &[&str]) -> Vec<String> {
+        patterns.iter().map(|pattern| pattern.to_string()).collect()
+    }
+
+    #[test]
+    fn finds_the_date_of_common_file_names() {
+        let patterns = patterns(&["{year}{month}{day}", "{year}-{month}-{day}"]);
+        let find = |file_name| find_filename_date(file_name, &patterns, FilenameDateOrder::Auto);
+
+        assert_eq!(find("IMG_20190128_123456.jpg"), Some(NaiveDate::from_ymd(2019, 1, 28)));
+        assert_eq!(find("VID-20200505-WA0001.mp4"), Some(NaiveDate::from_ymd(2020, 5, 5)));
+        assert_eq!(find("Screenshot_2021-03-04-10-11-12.png"), Some(NaiveDate::from_ymd(2021, 3, 4)));
+        assert_eq!(find("holiday.jpg"), None);
+    }
+
+    #[test]
+    fn skips_dates_which_follow_other_digits() {
+        let patterns = patterns(&["{year}{month}{day}", "{year}-{month}-{day}"]);
+        let find = |file_name| find_filename_date(file_name, &patterns, FilenameDateOrder::Auto);
+
+        assert_eq!(find("1548678190123.jpg"), None);
+        assert_eq!(find("12019-01-28.jpg"), None);
+        assert_eq!(find("1_2019-01-28.jpg"), Some(NaiveDate::from_ymd(2019, 1, 28)));
+    }
+
+    #[test]
+    fn skips_future_dates_but_not_the_later_dates_of_the_same_name() {
+        let patterns = patterns(&["{year}{month}{day}"]);
+        let find = |file_name| find_filename_date(file_name, &patterns, FilenameDateOrder::Auto);
+
+        assert_eq!(find("IMG_29991231.jpg"), None);
+        assert_eq!(find("29991231_20190128.jpg"), Some(NaiveDate::from_ymd(2019, 1, 28)));
+    }
+
+    #[test]
+    fn reads_the_day_and_month_in_the_configured_order() {
+        let patterns = patterns(&["{day}-{month}-{year}"]);
+        let find = |file_name, order| find_filename_date(file_name, &patterns, order);
+
+        assert_eq!(find("03-04-2021.jpg", FilenameDateOrder::Auto), Some(NaiveDate::from_ymd(2021, 4, 3)));
+        assert_eq!(find("03-04-2021.jpg", FilenameDateOrder::MonthFirst), Some(NaiveDate::from_ymd(2021, 3, 4)));
+        // Only valid with the day first
+        assert_eq!(find("25-12-2021.jpg", FilenameDateOrder::MonthFirst), Some(NaiveDate::from_ymd(2021, 12, 25)));
+    }
+
+    #[test]
+    fn finds_ambiguous_dates() {
+        let patterns = patterns(&["{day}-{month}-{year}"]);
+
+        assert_eq!(find_ambiguous_filename_date("03-04-2021.jpg", &patterns),
+                   Some((NaiveDate::from_ymd(2021, 4, 3), NaiveDate::from_ymd(2021, 3, 4))));
+        assert_eq!(find_ambiguous_filename_date("04-04-2021.jpg", &patterns), None);
+        assert_eq!(find_ambiguous_filename_date("25-12-2021.jpg", &patterns), None);
+    }
+
+    #[test]
+    fn validates_patterns() {
+        assert!(validate_filename_date_pattern("{year}-{month}-{day}").is_ok());
+        assert!(validate_filename_date_pattern("{year}-{month}").is_err());
+        assert!(validate_filename_date_pattern("{year}{year}{month}{day}").is_err());
+        assert!(validate_filename_date_pattern("{year}{month}{day}{hour}").is_err());
+        assert!(validate_filename_date_pattern("{year}{month}{day").is_err());
+    }
+}
@@ -0,0 +1,260 @@
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+/// The largest file which can be written to a FAT volume (FAT12, FAT16 or FAT32): 4 GB minus one byte.
+/// exFAT doesn't have this limit, so it's not checked
+pub const FAT_MAX_FILE_SIZE: u64 = 4 * 1024 * 1024 * 1024 - 1;
+
+/// The size of the chunks read and written by [copy_file_with_progress]
+const COPY_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Copy the file in chunks, calling `on_progress` with the size of each chunk once it's written,
+/// e.g. to show the progress of large videos, which [fs::copy] doesn't report until it's done.
+/// Like [fs::copy], the target file is replaced if it exists and gets the permissions of the source file,
+/// as well as its modified time on Windows. Returns the number of bytes copied
+pub fn copy_file_with_progress<F: FnMut(u64)>(source: &Path, target: &Path, mut on_progress: F) -> io::Result<u64> {
+    let mut source_file = File::open(source)?;
+    let source_metadata = source_file.metadata()?;
+    let mut target_file = File::create(target)?;
+
+    let mut buffer = vec![0; COPY_CHUNK_SIZE];
+    let mut copied_size = 0;
+    loop {
+        let chunk_size = match source_file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(chunk_size) => chunk_size,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        target_file.write_all(&buffer[..chunk_size])?;
+        copied_size += chunk_size as u64;
+        on_progress(chunk_size as u64);
+    }
+    target_file.flush()?;
+    drop(target_file);
+
+    fs::set_permissions(target, source_metadata.permissions())?;
+    #[cfg(windows)]
+    filetime::set_file_mtime(target, filetime::FileTime::from_last_modification_time(&source_metadata))?;
+
+    Ok(copied_size)
+}
+
+/// The largest file which can be written in the folder, if it's on a filesystem with a known limit.
+/// The folder doesn't need to exist yet, in which case its closest existing parent folder is checked
+pub fn get_max_file_size(dir: &Path) -> Option<u64> {
+    let existing_dir = dir
+        .ancestors()
+        .map(|path| if path.as_os_str().is_empty() { Path::new(".") } else { path })
+        .find(|path| path.exists())?;
+
+    if is_fat_filesystem(existing_dir) {
+        Some(FAT_MAX_FILE_SIZE)
+    } else {
+        None
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn is_fat_filesystem(path: &Path) -> bool {
+    // The type of f_type differs between architectures
+    #[allow(clippy::unnecessary_cast)]
+    read_statfs(path).is_some_and(|stat| stat.f_type as i64 == libc::MSDOS_SUPER_MAGIC as i64)
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+fn is_fat_filesystem(path: &Path) -> bool {
+    read_statfs(path).is_some_and(|stat| {
+        let fs_name: Vec<u8> = stat.f_fstypename
+            .iter()
+            .take_while(|c| **c != 0)
+            .map(|c| *c as u8)
+            .collect();
+        fs_name == b"msdos" || fs_name == b"msdosfs"
+    })
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+fn read_statfs(path: &Path) -> Option<libc::statfs> {
+    use std::ffi::CString;
+    use std::mem::MaybeUninit;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat = MaybeUninit::<libc::statfs>::uninit();
+
+    // SAFETY: the path is a valid C string and the struct is only read if statfs succeeded
+    unsafe {
+        if libc::statfs(c_path.as_ptr(), stat.as_mut_ptr()) == 0 {
+            Some(stat.assume_init())
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(windows)]
+fn is_fat_filesystem(path: &Path) -> bool {
+    use std::iter::once;
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr::null_mut;
+    use winapi::um::fileapi::{GetVolumeInformationW, GetVolumePathNameW};
+
+    const BUFFER_LEN: usize = 261;
+    let wide_path: Vec<u16> = path.as_os_str().encode_wide().chain(once(0)).collect();
+    let mut volume_path = [0u16; BUFFER_LEN];
+    let mut fs_name = [0u16; BUFFER_LEN];
+
+    // SAFETY: all buffers are valid for the lengths passed, and the path is null terminated
+    let is_read = unsafe {
+        GetVolumePathNameW(wide_path.as_ptr(), volume_path.as_mut_ptr(), BUFFER_LEN as u32) != 0
+            && GetVolumeInformationW(
+                volume_path.as_ptr(),
+                null_mut(), 0,
+                null_mut(), null_mut(), null_mut(),
+                fs_name.as_mut_ptr(), BUFFER_LEN as u32) != 0
+    };
+
+    let fs_name_len = fs_name.iter().position(|c| *c == 0).unwrap_or(BUFFER_LEN);
+    // e.g. "FAT", "FAT32", "exFAT" or "NTFS"
+    is_read && String::from_utf16_lossy(&fs_name[..fs_name_len]).starts_with("FAT")
+}
+
+#[cfg(not(any(windows, target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios", target_os = "freebsd")))]
+fn is_fat_filesystem(_path: &Path) -> bool {
+    false
+}
+
+/// Files created by the operating system inside folders, which don't prevent removing an otherwise empty folder
+const IGNORED_SYSTEM_FILES: &[&str] = &["thumbs.db", "desktop.ini", ".ds_store"];
+
+/// Folders can be briefly locked, e.g. by an antivirus or by a file explorer window
+/// which is still showing them, so try removing them a few times before giving up
+const REMOVE_ATTEMPTS: u32 = 3;
+const REMOVE_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// The result of removing a folder which might be empty
+#[derive(Debug)]
+pub enum RemoveDirResult {
+    Removed,
+    NotEmpty,
+    /// Already removed, e.g. by a cloud sync app or by another program
+    Missing,
+    Failed(io::Error),
+}
+
+/// Remove the folder if it's empty or only contains [IGNORED_SYSTEM_FILES], retrying if it's locked.
+/// Deep folders with paths longer than 260 characters don't need special handling on Windows,
+/// since the standard library switches to extended-length paths (`\\?\`) when needed
+pub fn remove_empty_dir(dir: &Path) -> RemoveDirResult {
+    let entries: Vec<fs::DirEntry> = match fs::read_dir(dir).and_then(|entries| entries.collect()) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return RemoveDirResult::Missing,
+        Err(e) => return RemoveDirResult::Failed(e),
+    };
+
+    let is_ignored_file = |entry: &fs::DirEntry| {
+        entry.file_type().is_ok_and(|file_type| file_type.is_file())
+            && IGNORED_SYSTEM_FILES.contains(&entry.file_name().to_string_lossy().to_lowercase().as_str())
+    };
+    if !entries.iter().all(is_ignored_file) {
+        return RemoveDirResult::NotEmpty;
+    }
+
+    let remove_result = entries
+        .iter()
+        .try_for_each(|entry| remove_with_retry(&entry.path(), |path| fs::remove_file(path)))
+        .and_then(|_| remove_with_retry(dir, |path| fs::remove_dir(path)));
+
+    match remove_result {
+        Ok(_) => RemoveDirResult::Removed,
+        Err(e) => RemoveDirResult::Failed(e),
+    }
+}
+
+fn remove_with_retry(path: &Path, remove: fn(&Path) -> io::Result<()>) -> io::Result<()> {
+    let mut result = remove(path);
+    for _ in 1..REMOVE_ATTEMPTS {
+        match &result {
+            Ok(_) => break,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => {
+                if e.kind() == io::ErrorKind::PermissionDenied {
+                    clear_read_only(path);
+                }
+                thread::sleep(REMOVE_RETRY_DELAY);
+                result = remove(path);
+            }
+        }
+    }
+    result
+}
+
+/// Read-only files and folders can't be removed on Windows
+#[cfg(windows)]
+fn clear_read_only(path: &Path) {
+    if let Ok(metadata) = fs::metadata(path) {
+        let mut permissions = metadata.permissions();
+        if permissions.readonly() {
+            permissions.set_readonly(false);
+            let _ = fs::set_permissions(path, permissions);
+        }
+    }
+}
+
+/// On other systems, removing files depends on the permissions of their parent folder instead
+#[cfg(not(windows))]
+fn clear_read_only(_path: &Path) {}
+
+/// Check if the file is a cloud placeholder, i.e. a file synced by OneDrive, iCloud Drive or a similar app,
+/// which is listed with its full size, but whose contents are only downloaded when it's read
+#[cfg(windows)]
+pub fn is_cloud_placeholder(metadata: &Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    const FILE_ATTRIBUTE_OFFLINE: u32 = 0x1000;
+    const FILE_ATTRIBUTE_RECALL_ON_OPEN: u32 = 0x40000;
+    const FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS: u32 = 0x400000;
+
+    metadata.file_attributes()
+        & (FILE_ATTRIBUTE_OFFLINE | FILE_ATTRIBUTE_RECALL_ON_OPEN | FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS) != 0
+}
+
+/// Check if the file is a cloud placeholder, i.e. a file synced by OneDrive, iCloud Drive or a similar app,
+/// which is listed with its full size, but whose contents are only downloaded when it's read
+#[cfg(target_os = "macos")]
+pub fn is_cloud_placeholder(metadata: &Metadata) -> bool {
+    use std::os::macos::fs::MetadataExt;
+    const SF_DATALESS: u32 = 0x40000000;
+
+    metadata.st_flags() & SF_DATALESS != 0
+}
+
+/// Cloud placeholders can't be told apart from regular files on other systems
+#[cfg(not(any(windows, target_os = "macos")))]
+pub fn is_cloud_placeholder(_metadata: &Metadata) -> bool {
+    false
+}
+
+/// Check if the file could not be read or removed because it's open in another program
+/// which doesn't share it, e.g. a phone sync app which is still writing it
+#[cfg(windows)]
+pub fn is_file_in_use(error: &io::Error) -> bool {
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+    const ERROR_LOCK_VIOLATION: i32 = 33;
+
+    matches!(error.raw_os_error(), Some(ERROR_SHARING_VIOLATION) | Some(ERROR_LOCK_VIOLATION))
+}
+
+/// Other systems don't prevent reading or removing files which are open in another program
+#[cfg(not(windows))]
+pub fn is_file_in_use(_error: &io::Error) -> bool {
+    false
+}
+
+/// Read the whole file, which makes the cloud sync app download its contents
+pub fn download_cloud_file(path: &Path) -> io::Result<u64> {
+    io::copy(&mut File::open(path)?, &mut io::sink())
+}
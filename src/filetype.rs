@@ -0,0 +1,274 @@
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::config::*;
+
+/// Number of bytes read from the start of each file to detect its type,
+/// which includes the start of the second packet of MPEG transport streams, see [MPEG_TS_PACKET_LEN]
+const SIGNATURE_LEN: u64 = 200;
+
+/// MPEG transport streams are made of 188 byte packets, each starting with a sync byte.
+/// The M2TS format used by AVCHD camcorders adds a 4 byte timestamp before each packet
+const MPEG_TS_PACKET_LEN: usize = 188;
+const MPEG_TS_SYNC_BYTE: u8 = 0x47;
+
+/// The type of a file as detected from its contents ("magic numbers"),
+/// regardless of its extension
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetectedFileType {
+    /// One of [IMAGE], [VIDEO] or [AUDIO]
+    pub media_type: &'static str,
+    /// A short name of the detected format, e.g. `jpg` or `mp4`
+    pub format: &'static str,
+    /// Extensions (lowercase) which are expected for this type of content.
+    /// Several formats share the same container (e.g. most RAW files are TIFF-based,
+    /// MOV and MP4 are both ISO media files), so all of these are considered a match
+    extensions: &'static [&'static str],
+}
+
+impl DetectedFileType {
+    const fn new(media_type: &'static str, format: &'static str, extensions: &'static [&'static str]) -> DetectedFileType {
+        DetectedFileType { media_type, format, extensions }
+    }
+
+    /// Check if the extension is one of those expected for this type of content
+    pub fn matches_extension(&self, extension: &str) -> bool {
+        self.extensions.contains(&extension.to_lowercase().as_str())
+    }
+}
+
+const JPEG: DetectedFileType = DetectedFileType::new(IMAGE, "jpg", &["jpg", "jpeg", "jpe", "jfif"]);
+const PNG: DetectedFileType = DetectedFileType::new(IMAGE, "png", &["png"]);
+const GIF: DetectedFileType = DetectedFileType::new(IMAGE, "gif", &["gif"]);
+const WEBP: DetectedFileType = DetectedFileType::new(IMAGE, "webp", &["webp"]);
+const TIFF: DetectedFileType = DetectedFileType::new(IMAGE, "tiff",
+    &["tif", "tiff", "nef", "nrw", "dng", "arw", "srf", "sr2", "pef", "srw", "3fr", "erf", "kdc", "mef", "mos", "cr2"]);
+const CR2: DetectedFileType = DetectedFileType::new(IMAGE, "cr2", &["cr2"]);
+const CRW: DetectedFileType = DetectedFileType::new(IMAGE, "crw", &["crw"]);
+const ORF: DetectedFileType = DetectedFileType::new(IMAGE, "orf", &["orf"]);
+const RW2: DetectedFileType = DetectedFileType::new(IMAGE, "rw2", &["rw2", "raw"]);
+const RAF: DetectedFileType = DetectedFileType::new(IMAGE, "raf", &["raf"]);
+const HEIF: DetectedFileType = DetectedFileType::new(IMAGE, "heic", &["heic", "heif", "hif"]);
+const AVIF: DetectedFileType = DetectedFileType::new(IMAGE, "avif", &["avif"]);
+const ISO_VIDEO: DetectedFileType = DetectedFileType::new(VIDEO, "mp4",
+    &["mp4", "m4v", "mov", "qt", "3gp", "3g2", "mpg4"]);
+const QUICKTIME: DetectedFileType = DetectedFileType::new(VIDEO, "mov", &["mov", "qt", "mp4"]);
+const AVI: DetectedFileType = DetectedFileType::new(VIDEO, "avi", &["avi"]);
+const MATROSKA: DetectedFileType = DetectedFileType::new(VIDEO, "mkv", &["mkv", "webm"]);
+const MPEG_TS: DetectedFileType = DetectedFileType::new(VIDEO, "m2ts", &["mts", "m2ts", "m2t", "ts"]);
+const ISO_AUDIO: DetectedFileType = DetectedFileType::new(AUDIO, "m4a", &["m4a", "m4b", "m4p"]);
+const OGG: DetectedFileType = DetectedFileType::new(AUDIO, "ogg", &["ogg", "oga", "opus"]);
+const AMR: DetectedFileType = DetectedFileType::new(AUDIO, "amr", &["amr"]);
+const WAV: DetectedFileType = DetectedFileType::new(AUDIO, "wav", &["wav"]);
+
+const ALL_TYPES: [DetectedFileType; 21] = [
+    JPEG, PNG, GIF, WEBP, TIFF, CR2, CRW, ORF, RW2, RAF, HEIF, AVIF,
+    ISO_VIDEO, QUICKTIME, AVI, MATROSKA, MPEG_TS, ISO_AUDIO, OGG, AMR, WAV,
+];
+
+/// Check if files with this extension are expected to start with one of the known file signatures.
+/// If they don't, their header is most likely corrupt
+pub fn has_known_signature(extension: &str) -> bool {
+    ALL_TYPES.iter().any(|detected_type| detected_type.matches_extension(extension))
+}
+
+/// Read the first bytes of the file and try to detect its type based on known file signatures.
+/// Returns None if the file can't be read or the signature is not recognized.
+pub fn detect_file_type(file_path: &Path) -> Option<DetectedFileType> {
+    let mut header: Vec<u8> = Vec::with_capacity(SIGNATURE_LEN as usize);
+    File::open(file_path)
+        .ok()?
+        .take(SIGNATURE_LEN)
+        .read_to_end(&mut header)
+        .ok()?;
+
+    detect_file_type_from_bytes(&header)
+}
+
+/// Match the start of a file against known file signatures,
+/// see https://en.wikipedia.org/wiki/List_of_file_signatures
+pub fn detect_file_type_from_bytes(header: &[u8]) -> Option<DetectedFileType> {
+    let has_at = |offset: usize, signature: &[u8]| {
+        header.len() >= offset + signature.len() && &header[offset..offset + signature.len()] == signature
+    };
+    // The first two packets must start with the sync byte, which is not much of a signature on its own
+    let is_mpeg_ts = |packet_start: usize, packet_len: usize| {
+        has_at(packet_start, &[MPEG_TS_SYNC_BYTE]) && has_at(packet_start + packet_len, &[MPEG_TS_SYNC_BYTE])
+    };
+
+    if has_at(0, &[0xFF, 0xD8, 0xFF]) {
+        Some(JPEG)
+    } else if has_at(0, &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+        Some(PNG)
+    } else if has_at(0, b"GIF87a") || has_at(0, b"GIF89a") {
+        Some(GIF)
+    } else if has_at(0, b"RIFF") && has_at(8, b"WEBP") {
+        Some(WEBP)
+    } else if has_at(0, b"RIFF") && has_at(8, b"AVI ") {
+        Some(AVI)
+    } else if has_at(0, b"RIFF") && has_at(8, b"WAVE") {
+        Some(WAV)
+    } else if has_at(0, b"II") && has_at(6, b"HEAPCCDR") {
+        Some(CRW)
+    } else if has_at(0, b"IIRO") || has_at(0, b"IIRS") || has_at(0, b"MMOR") {
+        Some(ORF)
+    } else if has_at(0, &[b'I', b'I', b'U', 0x00]) {
+        Some(RW2)
+    } else if has_at(0, b"FUJIFILMCCD-RAW") {
+        Some(RAF)
+    } else if has_at(0, &[b'I', b'I', b'*', 0x00]) || has_at(0, &[b'M', b'M', 0x00, b'*']) {
+        if has_at(8, b"CR") { Some(CR2) } else { Some(TIFF) }
+    } else if has_at(4, b"ftyp") {
+        detect_iso_media_brand(header.get(8..12)?)
+    } else if [b"moov", b"mdat", b"wide", b"free", b"skip", b"pnot", b"uuid"].iter().any(|atom| has_at(4, *atom)) {
+        // old QuickTime files don't have a `ftyp` box, and may start with any of these atoms
+        Some(QUICKTIME)
+    } else if has_at(0, &[0x1A, 0x45, 0xDF, 0xA3]) {
+        Some(MATROSKA)
+    } else if is_mpeg_ts(0, MPEG_TS_PACKET_LEN) || is_mpeg_ts(4, 4 + MPEG_TS_PACKET_LEN) {
+        Some(MPEG_TS)
+    } else if has_at(0, b"OggS") {
+        Some(OGG)
+    } else if has_at(0, b"#!AMR") {
+        Some(AMR)
+    } else {
+        None
+    }
+}
+
+/// ISO base media files (MP4, MOV, 3GP, HEIC, AVIF, etc) all start with a `ftyp` box
+/// followed by a "major brand" which identifies the actual content
+fn detect_iso_media_brand(brand: &[u8]) -> Option<DetectedFileType> {
+    match brand {
+        b"heic" | b"heix" | b"hevc" | b"hevx" | b"heim" | b"heis" | b"mif1" | b"msf1" => Some(HEIF),
+        b"avif" | b"avis" => Some(AVIF),
+        b"M4A " | b"M4B " | b"M4P " => Some(ISO_AUDIO),
+        b"qt  " => Some(QUICKTIME),
+        // everything else is most likely a video: isom, mp41, mp42, 3gp4, 3gp5, M4V, etc
+        _ => Some(ISO_VIDEO),
+    }
+}
+
+/// Read the width and height in pixels from the header of a JPEG or PNG file,
+/// without relying on EXIF data, which is often removed (e.g. by messaging apps).
+/// Returns None for other file types, or if the header can't be read
+pub fn read_image_dimensions(file_path: &Path) -> Option<(u32, u32)> {
+    let mut reader = BufReader::new(File::open(file_path).ok()?);
+
+    let mut signature = [0_u8; 8];
+    reader.read_exact(&mut signature).ok()?;
+
+    match detect_file_type_from_bytes(&signature)?.format {
+        "png" => {
+            // The IHDR chunk always comes first: length (4), type (4), width (4), height (4)
+            let mut ihdr = [0_u8; 16];
+            reader.read_exact(&mut ihdr).ok()?;
+            let width = u32::from_be_bytes([ihdr[8], ihdr[9], ihdr[10], ihdr[11]]);
+            let height = u32::from_be_bytes([ihdr[12], ihdr[13], ihdr[14], ihdr[15]]);
+            Some((width, height))
+        }
+        "jpg" => {
+            // Skip the SOI marker (2 bytes) and continue from the first segment
+            reader.seek(SeekFrom::Start(2)).ok()?;
+            read_jpeg_dimensions(&mut reader)
+        }
+        _ => None,
+    }
+}
+
+/// Check if the file is a GIF with more than one frame. Static GIFs are sorted as images,
+/// while animated ones are sorted as animations, see [crate::config::Args::animations_dir_name]
+pub fn is_animated_gif(file_path: &Path) -> bool {
+    count_gif_frames(file_path, 2).is_some_and(|frame_count| frame_count > 1)
+}
+
+/// Count the image descriptors of a GIF file, up to `max_count`, skipping over the image data and extensions.
+/// Returns None if the file is not a GIF or can't be read
+fn count_gif_frames(file_path: &Path, max_count: usize) -> Option<usize> {
+    let mut reader = BufReader::new(File::open(file_path).ok()?);
+
+    // signature (6), then the logical screen descriptor: width (2), height (2), flags (1), background (1), aspect (1)
+    let mut header = [0_u8; 13];
+    reader.read_exact(&mut header).ok()?;
+    if detect_file_type_from_bytes(&header)? != GIF {
+        return None;
+    }
+    skip_gif_color_table(&mut reader, header[10])?;
+
+    let mut frame_count = 0;
+    while frame_count < max_count {
+        let mut block_type = [0_u8; 1];
+        if reader.read_exact(&mut block_type).is_err() {
+            break;
+        }
+        match block_type[0] {
+            // Image descriptor: left (2), top (2), width (2), height (2), flags (1),
+            // then the local color table and the LZW minimum code size, followed by the image data
+            0x2C => {
+                let mut descriptor = [0_u8; 9];
+                reader.read_exact(&mut descriptor).ok()?;
+                skip_gif_color_table(&mut reader, descriptor[8])?;
+                reader.seek_relative(1).ok()?;
+                skip_gif_sub_blocks(&mut reader)?;
+                frame_count += 1;
+            }
+            // Extension: label (1), followed by its data
+            0x21 => {
+                reader.seek_relative(1).ok()?;
+                skip_gif_sub_blocks(&mut reader)?;
+            }
+            // Trailer, or anything unexpected
+            _ => break,
+        }
+    }
+
+    Some(frame_count)
+}
+
+/// The color table follows its descriptor if its highest flag is set, with 2^(N+1) RGB colors
+fn skip_gif_color_table<R: Read + Seek>(reader: &mut BufReader<R>, flags: u8) -> Option<()> {
+    if flags & 0x80 != 0 {
+        reader.seek_relative(3 * (1 << ((flags & 0x07) + 1))).ok()?;
+    }
+    Some(())
+}
+
+/// Image data and extensions are split into sub-blocks of up to 255 bytes, each starting with its length,
+/// up to an empty sub-block
+fn skip_gif_sub_blocks<R: Read + Seek>(reader: &mut BufReader<R>) -> Option<()> {
+    loop {
+        let mut block_len = [0_u8; 1];
+        reader.read_exact(&mut block_len).ok()?;
+        if block_len[0] == 0 {
+            return Some(());
+        }
+        reader.seek_relative(block_len[0] as i64).ok()?;
+    }
+}
+
+/// Walk the JPEG segments until the first "start of frame" segment, which contains the dimensions
+fn read_jpeg_dimensions<R: Read + Seek>(reader: &mut R) -> Option<(u32, u32)> {
+    loop {
+        let mut marker = [0_u8; 4];
+        reader.read_exact(&mut marker).ok()?;
+        if marker[0] != 0xFF {
+            return None;
+        }
+
+        let segment_len = u16::from_be_bytes([marker[2], marker[3]]) as i64;
+        match marker[1] {
+            // SOF0 to SOF15, except DHT (C4), JPG (C8) and DAC (CC) which use the same range
+            0xC0..=0xCF if ![0xC4, 0xC8, 0xCC].contains(&marker[1]) => {
+                // precision (1), height (2), width (2)
+                let mut frame = [0_u8; 5];
+                reader.read_exact(&mut frame).ok()?;
+                let height = u16::from_be_bytes([frame[1], frame[2]]) as u32;
+                let width = u16::from_be_bytes([frame[3], frame[4]]) as u32;
+                return Some((width, height));
+            }
+            // the length includes its own 2 bytes
+            _ => { reader.seek(SeekFrom::Current(segment_len - 2)).ok()?; }
+        }
+    }
+}
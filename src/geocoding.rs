@@ -0,0 +1,327 @@
+use crate::exif::GpsLocation;
+
+/// Photos taken further than this from the nearest known city are not given a city name
+const MAX_CITY_DISTANCE_KM: f64 = 50.0;
+
+/// Photos taken further than this from the nearest known city are not given a country name either,
+/// e.g. in the middle of the ocean
+const MAX_COUNTRY_DISTANCE_KM: f64 = 300.0;
+
+/// The mean radius of the Earth
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// The name, latitude and longitude of a city
+type City = (&'static str, f64, f64);
+
+/// The bundled dataset: the capitals, the largest cities and the most visited places of each country,
+/// with coordinates rounded to two decimals, i.e. about one kilometer
+const COUNTRY_CITIES: &[(&str, &[City])] = &[
+    // Europe
+    ("Albania", &[("Tirana", 41.33, 19.82), ("Saranda", 39.88, 20.01)]),
+    ("Andorra", &[("Andorra la Vella", 42.51, 1.52)]),
+    ("Armenia", &[("Yerevan", 40.18, 44.51)]),
+    ("Austria", &[("Vienna", 48.21, 16.37), ("Salzburg", 47.81, 13.04), ("Innsbruck", 47.27, 11.39),
+        ("Graz", 47.07, 15.44), ("Linz", 48.31, 14.29)]),
+    ("Azerbaijan", &[("Baku", 40.41, 49.87)]),
+    ("Belarus", &[("Minsk", 53.90, 27.57)]),
+    ("Belgium", &[("Brussels", 50.85, 4.35), ("Antwerp", 51.22, 4.40), ("Ghent", 51.05, 3.72),
+        ("Bruges", 51.21, 3.22), ("Liège", 50.63, 5.57)]),
+    ("Bosnia and Herzegovina", &[("Sarajevo", 43.86, 18.41), ("Mostar", 43.34, 17.81)]),
+    ("Bulgaria", &[("Sofia", 42.70, 23.32), ("Plovdiv", 42.14, 24.75), ("Varna", 43.21, 27.91),
+        ("Burgas", 42.50, 27.47)]),
+    ("Croatia", &[("Zagreb", 45.81, 15.98), ("Split", 43.51, 16.44), ("Dubrovnik", 42.65, 18.09),
+        ("Zadar", 44.12, 15.23), ("Pula", 44.87, 13.85), ("Rijeka", 45.33, 14.44)]),
+    ("Cyprus", &[("Nicosia", 35.17, 33.36), ("Limassol", 34.68, 33.04), ("Paphos", 34.77, 32.42),
+        ("Larnaca", 34.92, 33.63)]),
+    ("Czechia", &[("Prague", 50.08, 14.44), ("Brno", 49.20, 16.61), ("Karlovy Vary", 50.23, 12.87),
+        ("Český Krumlov", 48.81, 14.32)]),
+    ("Denmark", &[("Copenhagen", 55.68, 12.57), ("Aarhus", 56.16, 10.20), ("Odense", 55.40, 10.39)]),
+    ("Estonia", &[("Tallinn", 59.44, 24.75), ("Tartu", 58.38, 26.72)]),
+    ("Faroe Islands", &[("Tórshavn", 62.01, -6.77)]),
+    ("Finland", &[("Helsinki", 60.17, 24.94), ("Tampere", 61.50, 23.76), ("Turku", 60.45, 22.27),
+        ("Rovaniemi", 66.50, 25.73)]),
+    ("France", &[("Paris", 48.86, 2.35), ("Marseille", 43.30, 5.37), ("Lyon", 45.76, 4.84),
+        ("Toulouse", 43.60, 1.44), ("Nice", 43.70, 7.27), ("Nantes", 47.22, -1.55),
+        ("Strasbourg", 48.57, 7.75), ("Montpellier", 43.61, 3.88), ("Bordeaux", 44.84, -0.58),
+        ("Lille", 50.63, 3.06), ("Rennes", 48.11, -1.68), ("Grenoble", 45.19, 5.72),
+        ("Ajaccio", 41.92, 8.74), ("Chamonix", 45.92, 6.87), ("Avignon", 43.95, 4.81)]),
+    ("Georgia", &[("Tbilisi", 41.72, 44.79), ("Batumi", 41.64, 41.63)]),
+    ("Germany", &[("Berlin", 52.52, 13.40), ("Hamburg", 53.55, 9.99), ("Munich", 48.14, 11.58),
+        ("Cologne", 50.94, 6.96), ("Frankfurt", 50.11, 8.68), ("Stuttgart", 48.78, 9.18),
+        ("Düsseldorf", 51.23, 6.78), ("Dortmund", 51.51, 7.47), ("Leipzig", 51.34, 12.37),
+        ("Bremen", 53.08, 8.80), ("Dresden", 51.05, 13.74), ("Hanover", 52.37, 9.73),
+        ("Nuremberg", 49.45, 11.08), ("Heidelberg", 49.40, 8.69), ("Freiburg", 47.99, 7.85),
+        ("Kiel", 54.32, 10.14), ("Rostock", 54.09, 12.10)]),
+    ("Greece", &[("Athens", 37.98, 23.73), ("Thessaloniki", 40.64, 22.94), ("Heraklion", 35.34, 25.13),
+        ("Chania", 35.51, 24.02), ("Rhodes", 36.43, 28.22), ("Patras", 38.25, 21.73),
+        ("Corfu", 39.62, 19.92), ("Santorini", 36.42, 25.43), ("Mykonos", 37.45, 25.33),
+        ("Ioannina", 39.66, 20.85), ("Kos", 36.89, 27.29), ("Zakynthos", 37.78, 20.90)]),
+    ("Greenland", &[("Nuuk", 64.18, -51.72)]),
+    ("Hungary", &[("Budapest", 47.50, 19.04), ("Debrecen", 47.53, 21.63)]),
+    ("Iceland", &[("Reykjavik", 64.15, -21.94), ("Akureyri", 65.68, -18.09)]),
+    ("Ireland", &[("Dublin", 53.35, -6.26), ("Cork", 51.90, -8.47), ("Galway", 53.27, -9.05),
+        ("Limerick", 52.66, -8.63)]),
+    ("Italy", &[("Rome", 41.90, 12.50), ("Milan", 45.46, 9.19), ("Naples", 40.85, 14.27),
+        ("Turin", 45.07, 7.69), ("Palermo", 38.12, 13.36), ("Genoa", 44.41, 8.93),
+        ("Bologna", 44.49, 11.34), ("Florence", 43.77, 11.26), ("Venice", 45.44, 12.33),
+        ("Verona", 45.44, 10.99), ("Pisa", 43.72, 10.40), ("Bari", 41.12, 16.87),
+        ("Catania", 37.50, 15.09), ("Cagliari", 39.22, 9.12), ("Trieste", 45.65, 13.78),
+        ("Bolzano", 46.50, 11.35), ("Sorrento", 40.63, 14.38), ("Como", 45.81, 9.09)]),
+    ("Kosovo", &[("Pristina", 42.66, 21.17)]),
+    ("Latvia", &[("Riga", 56.95, 24.11)]),
+    ("Liechtenstein", &[("Vaduz", 47.14, 9.52)]),
+    ("Lithuania", &[("Vilnius", 54.69, 25.28), ("Kaunas", 54.90, 23.89)]),
+    ("Luxembourg", &[("Luxembourg", 49.61, 6.13)]),
+    ("Malta", &[("Valletta", 35.90, 14.51)]),
+    ("Moldova", &[("Chișinău", 47.01, 28.86)]),
+    ("Monaco", &[("Monaco", 43.74, 7.42)]),
+    ("Montenegro", &[("Podgorica", 42.44, 19.26), ("Kotor", 42.42, 18.77), ("Budva", 42.29, 18.84)]),
+    ("Netherlands", &[("Amsterdam", 52.37, 4.90), ("Rotterdam", 51.92, 4.48), ("The Hague", 52.08, 4.30),
+        ("Utrecht", 52.09, 5.12), ("Eindhoven", 51.44, 5.48), ("Groningen", 53.22, 6.57),
+        ("Maastricht", 50.85, 5.69)]),
+    ("North Macedonia", &[("Skopje", 42.00, 21.43), ("Ohrid", 41.12, 20.80)]),
+    ("Norway", &[("Oslo", 59.91, 10.75), ("Bergen", 60.39, 5.32), ("Trondheim", 63.43, 10.40),
+        ("Stavanger", 58.97, 5.73), ("Tromsø", 69.65, 18.96)]),
+    ("Poland", &[("Warsaw", 52.23, 21.01), ("Kraków", 50.06, 19.94), ("Gdańsk", 54.35, 18.65),
+        ("Wrocław", 51.11, 17.04), ("Poznań", 52.41, 16.93), ("Łódź", 51.76, 19.46),
+        ("Zakopane", 49.30, 19.95)]),
+    ("Portugal", &[("Lisbon", 38.72, -9.14), ("Porto", 41.15, -8.61), ("Faro", 37.02, -7.93),
+        ("Funchal", 32.65, -16.91), ("Coimbra", 40.21, -8.43), ("Ponta Delgada", 37.74, -25.67)]),
+    ("Romania", &[("Bucharest", 44.43, 26.10), ("Cluj-Napoca", 46.77, 23.59), ("Timișoara", 45.75, 21.23),
+        ("Iași", 47.16, 27.59), ("Constanța", 44.18, 28.63), ("Brașov", 45.66, 25.61),
+        ("Sibiu", 45.79, 24.15), ("Oradea", 47.07, 21.93), ("Craiova", 44.32, 23.80),
+        ("Galați", 45.44, 28.05), ("Ploiești", 44.94, 26.03), ("Arad", 46.18, 21.31),
+        ("Pitești", 44.86, 24.87), ("Bacău", 46.57, 26.91), ("Suceava", 47.65, 26.26),
+        ("Sighișoara", 46.22, 24.79), ("Sinaia", 45.35, 25.55), ("Târgu Mureș", 46.54, 24.56),
+        ("Baia Mare", 47.66, 23.58), ("Tulcea", 45.18, 28.80), ("Alba Iulia", 46.07, 23.58)]),
+    ("Russia", &[("Moscow", 55.76, 37.62), ("Saint Petersburg", 59.94, 30.31), ("Kazan", 55.79, 49.12),
+        ("Novosibirsk", 55.03, 82.92), ("Yekaterinburg", 56.84, 60.61), ("Sochi", 43.60, 39.73),
+        ("Vladivostok", 43.12, 131.89), ("Kaliningrad", 54.71, 20.51)]),
+    ("San Marino", &[("San Marino", 43.94, 12.45)]),
+    ("Serbia", &[("Belgrade", 44.79, 20.45), ("Novi Sad", 45.27, 19.83)]),
+    ("Slovakia", &[("Bratislava", 48.15, 17.11), ("Košice", 48.72, 21.26)]),
+    ("Slovenia", &[("Ljubljana", 46.06, 14.51), ("Bled", 46.37, 14.11)]),
+    ("Spain", &[("Madrid", 40.42, -3.70), ("Barcelona", 41.39, 2.17), ("Valencia", 39.47, -0.38),
+        ("Seville", 37.39, -5.98), ("Málaga", 36.72, -4.42), ("Bilbao", 43.26, -2.93),
+        ("Granada", 37.18, -3.60), ("Palma", 39.57, 2.65), ("Las Palmas", 28.12, -15.44),
+        ("Santa Cruz de Tenerife", 28.46, -16.25), ("Zaragoza", 41.65, -0.89), ("Alicante", 38.35, -0.48),
+        ("Ibiza", 38.91, 1.43), ("Santiago de Compostela", 42.88, -8.54), ("San Sebastián", 43.32, -1.98)]),
+    ("Sweden", &[("Stockholm", 59.33, 18.07), ("Gothenburg", 57.71, 11.97), ("Malmö", 55.60, 13.00),
+        ("Uppsala", 59.86, 17.64), ("Kiruna", 67.86, 20.23)]),
+    ("Switzerland", &[("Zurich", 47.38, 8.54), ("Geneva", 46.20, 6.14), ("Bern", 46.95, 7.45),
+        ("Basel", 47.56, 7.59), ("Lausanne", 46.52, 6.63), ("Lucerne", 47.05, 8.31),
+        ("Zermatt", 46.02, 7.75), ("Interlaken", 46.69, 7.86), ("Lugano", 46.00, 8.95)]),
+    ("Turkey", &[("Istanbul", 41.01, 28.98), ("Ankara", 39.93, 32.86), ("Izmir", 38.42, 27.14),
+        ("Antalya", 36.90, 30.70), ("Bodrum", 37.03, 27.43), ("Göreme", 38.64, 34.83),
+        ("Bursa", 40.19, 29.06), ("Trabzon", 41.00, 39.72)]),
+    ("Ukraine", &[("Kyiv", 50.45, 30.52), ("Lviv", 49.84, 24.03), ("Odesa", 46.48, 30.73),
+        ("Kharkiv", 49.99, 36.23)]),
+    ("United Kingdom", &[("London", 51.51, -0.13), ("Birmingham", 52.49, -1.89), ("Manchester", 53.48, -2.24),
+        ("Liverpool", 53.41, -2.98), ("Leeds", 53.80, -1.55), ("Newcastle", 54.98, -1.61),
+        ("Bristol", 51.45, -2.59), ("Edinburgh", 55.95, -3.19), ("Glasgow", 55.86, -4.25),
+        ("Aberdeen", 57.15, -2.09), ("Inverness", 57.48, -4.22), ("Cardiff", 51.48, -3.18),
+        ("Belfast", 54.60, -5.93), ("Oxford", 51.75, -1.26), ("Cambridge", 52.21, 0.12),
+        ("Brighton", 50.82, -0.14), ("Plymouth", 50.38, -4.14)]),
+
+    // Middle East and Central Asia
+    ("Bahrain", &[("Manama", 26.23, 50.59)]),
+    ("Iran", &[("Tehran", 35.69, 51.39), ("Isfahan", 32.65, 51.67)]),
+    ("Iraq", &[("Baghdad", 33.31, 44.36), ("Erbil", 36.19, 44.01)]),
+    ("Israel", &[("Tel Aviv", 32.09, 34.78), ("Jerusalem", 31.77, 35.21), ("Haifa", 32.79, 34.99),
+        ("Eilat", 29.56, 34.95)]),
+    ("Jordan", &[("Amman", 31.95, 35.93), ("Petra", 30.32, 35.48), ("Aqaba", 29.53, 35.01)]),
+    ("Kazakhstan", &[("Almaty", 43.24, 76.89), ("Astana", 51.17, 71.45)]),
+    ("Kuwait", &[("Kuwait City", 29.38, 47.99)]),
+    ("Lebanon", &[("Beirut", 33.89, 35.50)]),
+    ("Oman", &[("Muscat", 23.59, 58.41)]),
+    ("Qatar", &[("Doha", 25.29, 51.53)]),
+    ("Saudi Arabia", &[("Riyadh", 24.71, 46.68), ("Jeddah", 21.49, 39.19), ("Mecca", 21.42, 39.83)]),
+    ("United Arab Emirates", &[("Dubai", 25.20, 55.27), ("Abu Dhabi", 24.45, 54.38)]),
+    ("Uzbekistan", &[("Tashkent", 41.30, 69.24), ("Samarkand", 39.65, 66.96)]),
+
+    // Africa
+    ("Algeria", &[("Algiers", 36.75, 3.06)]),
+    ("Angola", &[("Luanda", -8.84, 13.23)]),
+    ("Botswana", &[("Gaborone", -24.65, 25.91)]),
+    ("Cameroon", &[("Douala", 4.05, 9.77)]),
+    ("Cape Verde", &[("Praia", 14.93, -23.51)]),
+    ("DR Congo", &[("Kinshasa", -4.44, 15.27)]),
+    ("Egypt", &[("Cairo", 30.04, 31.24), ("Alexandria", 31.20, 29.92), ("Luxor", 25.69, 32.64),
+        ("Aswan", 24.09, 32.90), ("Hurghada", 27.26, 33.81), ("Sharm El Sheikh", 27.92, 34.33)]),
+    ("Ethiopia", &[("Addis Ababa", 9.03, 38.74)]),
+    ("Ghana", &[("Accra", 5.60, -0.19)]),
+    ("Ivory Coast", &[("Abidjan", 5.36, -4.01)]),
+    ("Kenya", &[("Nairobi", -1.29, 36.82), ("Mombasa", -4.04, 39.67)]),
+    ("Libya", &[("Tripoli", 32.89, 13.19)]),
+    ("Madagascar", &[("Antananarivo", -18.88, 47.51)]),
+    ("Mauritius", &[("Port Louis", -20.16, 57.50)]),
+    ("Morocco", &[("Marrakesh", 31.63, -7.99), ("Casablanca", 33.57, -7.59), ("Rabat", 34.02, -6.83),
+        ("Fez", 34.03, -5.00), ("Tangier", 35.76, -5.83), ("Agadir", 30.43, -9.60)]),
+    ("Mozambique", &[("Maputo", -25.97, 32.57)]),
+    ("Namibia", &[("Windhoek", -22.56, 17.08)]),
+    ("Nigeria", &[("Lagos", 6.52, 3.38), ("Abuja", 9.08, 7.40)]),
+    ("Rwanda", &[("Kigali", -1.94, 30.06)]),
+    ("Senegal", &[("Dakar", 14.72, -17.47)]),
+    ("Seychelles", &[("Victoria", -4.62, 55.45)]),
+    ("South Africa", &[("Cape Town", -33.92, 18.42), ("Johannesburg", -26.20, 28.05), ("Durban", -29.86, 31.02),
+        ("Pretoria", -25.75, 28.19), ("Gqeberha", -33.96, 25.60)]),
+    ("Sudan", &[("Khartoum", 15.50, 32.56)]),
+    ("Tanzania", &[("Dar es Salaam", -6.79, 39.21), ("Zanzibar", -6.17, 39.20), ("Arusha", -3.37, 36.68)]),
+    ("Tunisia", &[("Tunis", 36.81, 10.18), ("Sousse", 35.83, 10.64), ("Djerba", 33.88, 10.86)]),
+    ("Uganda", &[("Kampala", 0.35, 32.58)]),
+    ("Zambia", &[("Lusaka", -15.39, 28.32)]),
+    ("Zimbabwe", &[("Harare", -17.83, 31.05), ("Victoria Falls", -17.93, 25.83)]),
+
+    // Asia
+    ("Bangladesh", &[("Dhaka", 23.81, 90.41)]),
+    ("Cambodia", &[("Phnom Penh", 11.56, 104.93), ("Siem Reap", 13.36, 103.86)]),
+    ("China", &[("Beijing", 39.90, 116.41), ("Shanghai", 31.23, 121.47), ("Guangzhou", 23.13, 113.26),
+        ("Shenzhen", 22.54, 114.06), ("Chengdu", 30.57, 104.07), ("Xi'an", 34.34, 108.94),
+        ("Hangzhou", 30.27, 120.16), ("Chongqing", 29.56, 106.55), ("Wuhan", 30.59, 114.31),
+        ("Guilin", 25.27, 110.29), ("Nanjing", 32.06, 118.80), ("Harbin", 45.80, 126.53),
+        ("Kunming", 25.04, 102.71), ("Lhasa", 29.65, 91.17)]),
+    ("Hong Kong", &[("Hong Kong", 22.32, 114.17)]),
+    ("India", &[("Delhi", 28.61, 77.21), ("Mumbai", 19.08, 72.88), ("Bangalore", 12.97, 77.59),
+        ("Chennai", 13.08, 80.27), ("Kolkata", 22.57, 88.36), ("Hyderabad", 17.39, 78.49),
+        ("Agra", 27.18, 78.01), ("Jaipur", 26.91, 75.79), ("Panaji", 15.50, 73.83),
+        ("Varanasi", 25.32, 82.97), ("Udaipur", 24.59, 73.71), ("Kochi", 9.93, 76.27),
+        ("Pune", 18.52, 73.86), ("Ahmedabad", 23.02, 72.57)]),
+    ("Indonesia", &[("Jakarta", -6.21, 106.85), ("Denpasar", -8.65, 115.22), ("Ubud", -8.51, 115.26),
+        ("Yogyakarta", -7.80, 110.36), ("Surabaya", -7.25, 112.75), ("Bandung", -6.92, 107.62),
+        ("Medan", 3.60, 98.67)]),
+    ("Japan", &[("Tokyo", 35.68, 139.69), ("Osaka", 34.69, 135.50), ("Kyoto", 35.01, 135.77),
+        ("Yokohama", 35.44, 139.64), ("Nagoya", 35.18, 136.91), ("Sapporo", 43.06, 141.35),
+        ("Fukuoka", 33.59, 130.40), ("Hiroshima", 34.39, 132.46), ("Nara", 34.69, 135.80),
+        ("Naha", 26.21, 127.68), ("Sendai", 38.27, 140.87), ("Kobe", 34.69, 135.20)]),
+    ("Laos", &[("Vientiane", 17.98, 102.63), ("Luang Prabang", 19.89, 102.13)]),
+    ("Macau", &[("Macau", 22.20, 113.54)]),
+    ("Malaysia", &[("Kuala Lumpur", 3.15, 101.69), ("George Town", 5.41, 100.33), ("Kota Kinabalu", 5.98, 116.07),
+        ("Malacca", 2.19, 102.25), ("Langkawi", 6.35, 99.80)]),
+    ("Maldives", &[("Malé", 4.18, 73.51)]),
+    ("Mongolia", &[("Ulaanbaatar", 47.89, 106.91)]),
+    ("Myanmar", &[("Yangon", 16.87, 96.20), ("Bagan", 21.17, 94.86)]),
+    ("Nepal", &[("Kathmandu", 27.72, 85.32), ("Pokhara", 28.21, 83.99)]),
+    ("Pakistan", &[("Karachi", 24.86, 67.01), ("Lahore", 31.55, 74.34), ("Islamabad", 33.68, 73.05)]),
+    ("Philippines", &[("Manila", 14.60, 120.98), ("Cebu", 10.32, 123.89), ("Davao", 7.19, 125.46),
+        ("El Nido", 11.18, 119.39), ("Boracay", 11.97, 121.92)]),
+    ("Singapore", &[("Singapore", 1.35, 103.82)]),
+    ("South Korea", &[("Seoul", 37.57, 126.98), ("Busan", 35.18, 129.08), ("Jeju", 33.50, 126.53),
+        ("Incheon", 37.46, 126.71)]),
+    ("Sri Lanka", &[("Colombo", 6.93, 79.86), ("Kandy", 7.29, 80.63)]),
+    ("Taiwan", &[("Taipei", 25.03, 121.57), ("Kaohsiung", 22.63, 120.30)]),
+    ("Thailand", &[("Bangkok", 13.76, 100.50), ("Chiang Mai", 18.79, 98.99), ("Phuket", 7.88, 98.39),
+        ("Pattaya", 12.93, 100.88), ("Krabi", 8.09, 98.91), ("Koh Samui", 9.51, 100.01)]),
+    ("Vietnam", &[("Hanoi", 21.03, 105.85), ("Ho Chi Minh City", 10.82, 106.63), ("Da Nang", 16.05, 108.22),
+        ("Hoi An", 15.88, 108.33), ("Hue", 16.46, 107.60), ("Ha Long", 20.95, 107.08),
+        ("Nha Trang", 12.24, 109.20)]),
+
+    // North and Central America
+    ("Aruba", &[("Oranjestad", 12.52, -70.03)]),
+    ("Bahamas", &[("Nassau", 25.04, -77.35)]),
+    ("Barbados", &[("Bridgetown", 13.10, -59.61)]),
+    ("Belize", &[("Belize City", 17.50, -88.20)]),
+    ("Canada", &[("Toronto", 43.65, -79.38), ("Montreal", 45.50, -73.57), ("Vancouver", 49.28, -123.12),
+        ("Calgary", 51.05, -114.07), ("Edmonton", 53.55, -113.49), ("Ottawa", 45.42, -75.70),
+        ("Quebec City", 46.81, -71.21), ("Winnipeg", 49.90, -97.14), ("Halifax", 44.65, -63.57),
+        ("Victoria", 48.43, -123.37), ("Banff", 51.18, -115.57), ("St. John's", 47.56, -52.71)]),
+    ("Costa Rica", &[("San José", 9.93, -84.08)]),
+    ("Cuba", &[("Havana", 23.11, -82.37)]),
+    ("Curaçao", &[("Willemstad", 12.11, -68.93)]),
+    ("Dominican Republic", &[("Santo Domingo", 18.49, -69.93), ("Punta Cana", 18.58, -68.40)]),
+    ("El Salvador", &[("San Salvador", 13.69, -89.22)]),
+    ("Guatemala", &[("Guatemala City", 14.63, -90.51), ("Antigua Guatemala", 14.56, -90.73)]),
+    ("Honduras", &[("Tegucigalpa", 14.07, -87.19)]),
+    ("Jamaica", &[("Kingston", 17.97, -76.79), ("Montego Bay", 18.47, -77.92)]),
+    ("Mexico", &[("Mexico City", 19.43, -99.13), ("Guadalajara", 20.66, -103.35), ("Monterrey", 25.69, -100.32),
+        ("Cancún", 21.16, -86.85), ("Playa del Carmen", 20.63, -87.08), ("Tulum", 20.21, -87.47),
+        ("Oaxaca", 17.07, -96.73), ("Puerto Vallarta", 20.65, -105.23), ("Cabo San Lucas", 22.89, -109.91),
+        ("Mérida", 20.97, -89.62), ("Tijuana", 32.51, -117.04)]),
+    ("Nicaragua", &[("Managua", 12.11, -86.24)]),
+    ("Panama", &[("Panama City", 8.98, -79.52)]),
+    ("Puerto Rico", &[("San Juan", 18.47, -66.11)]),
+    ("United States", &[("New York", 40.71, -74.01), ("Los Angeles", 34.05, -118.24), ("Chicago", 41.88, -87.63),
+        ("Houston", 29.76, -95.37), ("Phoenix", 33.45, -112.07), ("Philadelphia", 39.95, -75.17),
+        ("San Antonio", 29.42, -98.49), ("San Diego", 32.72, -117.16), ("Dallas", 32.78, -96.80),
+        ("Austin", 30.27, -97.74), ("San Francisco", 37.77, -122.42), ("San Jose", 37.34, -121.89),
+        ("Seattle", 47.61, -122.33), ("Portland", 45.52, -122.68), ("Denver", 39.74, -104.99),
+        ("Las Vegas", 36.17, -115.14), ("Salt Lake City", 40.76, -111.89), ("Boston", 42.36, -71.06),
+        ("Washington", 38.91, -77.04), ("Miami", 25.76, -80.19), ("Orlando", 28.54, -81.38),
+        ("Tampa", 27.95, -82.46), ("Atlanta", 33.75, -84.39), ("Nashville", 36.16, -86.78),
+        ("New Orleans", 29.95, -90.07), ("Detroit", 42.33, -83.05), ("Minneapolis", 44.98, -93.27),
+        ("St. Louis", 38.63, -90.20), ("Kansas City", 39.10, -94.58), ("Pittsburgh", 40.44, -80.00),
+        ("Cleveland", 41.50, -81.69), ("Charlotte", 35.23, -80.84), ("Honolulu", 21.31, -157.86),
+        ("Anchorage", 61.22, -149.90), ("Albuquerque", 35.08, -106.65), ("Sacramento", 38.58, -121.49),
+        ("Key West", 24.56, -81.78), ("Santa Fe", 35.69, -105.94), ("Baltimore", 39.29, -76.61),
+        ("Buffalo", 42.89, -78.88)]),
+
+    // South America
+    ("Argentina", &[("Buenos Aires", -34.60, -58.38), ("Córdoba", -31.42, -64.18), ("Mendoza", -32.89, -68.83),
+        ("Bariloche", -41.13, -71.31), ("Ushuaia", -54.80, -68.30), ("El Calafate", -50.34, -72.26),
+        ("Salta", -24.78, -65.41)]),
+    ("Bolivia", &[("La Paz", -16.50, -68.15), ("Sucre", -19.04, -65.26), ("Uyuni", -20.46, -66.83)]),
+    ("Brazil", &[("São Paulo", -23.55, -46.63), ("Rio de Janeiro", -22.91, -43.17), ("Brasília", -15.79, -47.88),
+        ("Salvador", -12.97, -38.50), ("Fortaleza", -3.73, -38.53), ("Recife", -8.05, -34.88),
+        ("Manaus", -3.12, -60.02), ("Belo Horizonte", -19.92, -43.94), ("Curitiba", -25.43, -49.27),
+        ("Porto Alegre", -30.03, -51.23), ("Florianópolis", -27.60, -48.55), ("Foz do Iguaçu", -25.55, -54.59)]),
+    ("Chile", &[("Santiago", -33.45, -70.67), ("Valparaíso", -33.05, -71.62), ("Punta Arenas", -53.16, -70.91),
+        ("San Pedro de Atacama", -22.91, -68.20), ("Puerto Natales", -51.73, -72.51)]),
+    ("Colombia", &[("Bogotá", 4.71, -74.07), ("Medellín", 6.24, -75.58), ("Cartagena", 10.39, -75.48),
+        ("Cali", 3.45, -76.53)]),
+    ("Ecuador", &[("Quito", -0.18, -78.47), ("Guayaquil", -2.19, -79.89)]),
+    ("Paraguay", &[("Asunción", -25.26, -57.58)]),
+    ("Peru", &[("Lima", -12.05, -77.04), ("Cusco", -13.53, -71.97), ("Arequipa", -16.41, -71.54),
+        ("Machu Picchu", -13.15, -72.52)]),
+    ("Uruguay", &[("Montevideo", -34.90, -56.16), ("Punta del Este", -34.96, -54.95)]),
+    ("Venezuela", &[("Caracas", 10.48, -66.90)]),
+
+    // Oceania
+    ("Australia", &[("Sydney", -33.87, 151.21), ("Melbourne", -37.81, 144.96), ("Brisbane", -27.47, 153.03),
+        ("Perth", -31.95, 115.86), ("Adelaide", -34.93, 138.60), ("Gold Coast", -28.02, 153.40),
+        ("Canberra", -35.28, 149.13), ("Hobart", -42.88, 147.33), ("Darwin", -12.46, 130.84),
+        ("Cairns", -16.92, 145.77), ("Alice Springs", -23.70, 133.88)]),
+    ("Fiji", &[("Suva", -18.14, 178.44), ("Nadi", -17.80, 177.42)]),
+    ("French Polynesia", &[("Papeete", -17.53, -149.57)]),
+    ("New Caledonia", &[("Nouméa", -22.28, 166.46)]),
+    ("New Zealand", &[("Auckland", -36.85, 174.76), ("Wellington", -41.29, 174.78), ("Christchurch", -43.53, 172.64),
+        ("Queenstown", -45.03, 168.66), ("Rotorua", -38.14, 176.25), ("Dunedin", -45.87, 170.50)]),
+];
+
+/// Where a photo was taken, according to the nearest city of the bundled dataset
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Place {
+    /// The nearest city, if it's closer than [MAX_CITY_DISTANCE_KM]
+    pub city: Option<&'static str>,
+    /// The country of the nearest city, if it's closer than [MAX_COUNTRY_DISTANCE_KM]
+    pub country: Option<&'static str>,
+}
+
+/// Find the city and country where a photo was taken, without any network access.
+/// The bundled dataset only has a few hundred cities, so photos taken in a small town are named
+/// after the nearest large city and photos taken near a border may get the neighbouring country
+pub fn reverse_geocode(location: GpsLocation) -> Place {
+    let nearest = COUNTRY_CITIES
+        .iter()
+        .flat_map(|(country, cities)| cities
+            .iter()
+            .map(move |(city, latitude, longitude)| (*city, *country, distance_km(location, *latitude, *longitude))))
+        .min_by(|(_, _, distance), (_, _, other_distance)| distance.total_cmp(other_distance));
+
+    match nearest {
+        Some((city, country, distance)) => Place {
+            city: if distance <= MAX_CITY_DISTANCE_KM { Some(city) } else { None },
+            country: if distance <= MAX_COUNTRY_DISTANCE_KM { Some(country) } else { None },
+        },
+        None => Place::default(),
+    }
+}
+
+/// The great-circle distance between two points, using the haversine formula
+fn distance_km(location: GpsLocation, latitude: f64, longitude: f64) -> f64 {
+    let (latitude_1, latitude_2) = (location.latitude.to_radians(), latitude.to_radians());
+    let latitude_delta = latitude_2 - latitude_1;
+    let longitude_delta = (longitude - location.longitude).to_radians();
+
+    let a = (latitude_delta / 2.0).sin().powi(2)
+        + latitude_1.cos() * latitude_2.cos() * (longitude_delta / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
@@ -0,0 +1,168 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Duration, FixedOffset, Local, TimeZone, Utc};
+
+use crate::exif::{parse_capture_time, GpsLocation};
+
+/// The points of one or more GPX tracks, e.g. recorded by a phone or a watch while taking photos
+/// with a camera which doesn't have GPS, used to find where each of these photos was taken
+#[derive(Clone, Default)]
+pub struct GpxTrack {
+    /// Sorted by time
+    points: Vec<(DateTime<Utc>, GpsLocation)>,
+}
+
+impl GpxTrack {
+    /// Read the track points of all GPX files, or of all `.gpx` files directly inside a folder.
+    /// Points without a time are left out, since they can't be matched to a photo.
+    /// Returns a description of the first file which can't be read, or which has no track points
+    pub fn read(paths: &[PathBuf]) -> Result<GpxTrack, String> {
+        let mut points = Vec::new();
+
+        for path in paths {
+            let gpx_files = if path.is_dir() {
+                let mut gpx_files: Vec<PathBuf> = fs::read_dir(path)
+                    .map_err(|e| format!("Could not read the folder {} ({})", path.display(), e))?
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|file_path| file_path.is_file() && has_gpx_extension(file_path))
+                    .collect();
+                gpx_files.sort();
+                gpx_files
+            } else {
+                vec![path.clone()]
+            };
+
+            for gpx_file in gpx_files {
+                let contents = fs::read_to_string(&gpx_file)
+                    .map_err(|e| format!("Could not read {} ({})", gpx_file.display(), e))?;
+                let file_points = parse_track_points(&contents);
+                if file_points.is_empty() {
+                    return Err(format!("{} has no track points with a time", gpx_file.display()));
+                }
+                points.extend(file_points);
+            }
+        }
+
+        points.sort_by_key(|(time, _)| *time);
+        Ok(GpxTrack { points })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Find where a photo was taken from the time it was taken, which is the local time set on the camera,
+    /// while GPX tracks are recorded in UTC. The camera's time zone is given as its offset from UTC,
+    /// or is the same as the computer's, if not given
+    pub fn find_capture_location(
+        &self,
+        capture_time: &str,
+        time_offset: Option<FixedOffset>,
+        max_gap_minutes: u32
+    ) -> Option<GpsLocation> {
+        let capture_time = parse_capture_time(capture_time)?;
+        let utc_time = match time_offset {
+            Some(offset) => offset.from_local_datetime(&capture_time).single()?.with_timezone(&Utc),
+            None => Local.from_local_datetime(&capture_time).earliest()?.with_timezone(&Utc),
+        };
+
+        self.find_location(utc_time, Duration::minutes(max_gap_minutes as i64))
+    }
+
+    /// Find the location at this time, if the track has a point at most `max_gap` before or after it.
+    /// Between two points which are both close enough, the location is interpolated by time
+    pub fn find_location(&self, time: DateTime<Utc>, max_gap: Duration) -> Option<GpsLocation> {
+        let next_index = self.points.partition_point(|(point_time, _)| *point_time < time);
+        let previous = next_index.checked_sub(1).and_then(|index| self.points.get(index))
+            .filter(|(point_time, _)| time - *point_time <= max_gap);
+        let next = self.points.get(next_index)
+            .filter(|(point_time, _)| *point_time - time <= max_gap);
+
+        match (previous, next) {
+            (Some((previous_time, previous_location)), Some((next_time, next_location))) => {
+                let span = (*next_time - *previous_time).num_milliseconds();
+                if span == 0 {
+                    return Some(*next_location);
+                }
+                let ratio = (time - *previous_time).num_milliseconds() as f64 / span as f64;
+                GpsLocation::new(
+                    previous_location.latitude + (next_location.latitude - previous_location.latitude) * ratio,
+                    previous_location.longitude + (next_location.longitude - previous_location.longitude) * ratio)
+            }
+            (Some((_, location)), None) | (None, Some((_, location))) => Some(*location),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Only show the number of points, since tracks can have thousands of them
+impl fmt::Debug for GpxTrack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "GpxTrack {{ points: {} }}", self.points.len())
+    }
+}
+
+fn has_gpx_extension(file_path: &Path) -> bool {
+    file_path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("gpx"))
+}
+
+/// Read the `<trkpt lat=".." lon="..">` elements and the `<time>` inside each of them.
+/// GPX files are simple enough that their elements can be found without a full XML parser
+fn parse_track_points(contents: &str) -> Vec<(DateTime<Utc>, GpsLocation)> {
+    let mut points = Vec::new();
+
+    let mut remaining = contents;
+    while let Some(start) = remaining.find("<trkpt") {
+        remaining = &remaining[start..];
+        let end = remaining.find("</trkpt>").unwrap_or(remaining.len());
+        let track_point = &remaining[..end];
+
+        let tag_end = track_point.find('>').unwrap_or(track_point.len());
+        let attributes = &track_point[..tag_end];
+        let time = find_element_text(track_point, "time")
+            .and_then(|time| DateTime::parse_from_rfc3339(time.trim()).ok())
+            .map(|time| time.with_timezone(&Utc));
+        let location = match (find_attribute(attributes, "lat"), find_attribute(attributes, "lon")) {
+            (Some(latitude), Some(longitude)) => GpsLocation::new(latitude, longitude),
+            _ => None,
+        };
+        if let (Some(time), Some(location)) = (time, location) {
+            points.push((time, location));
+        }
+
+        remaining = &remaining[end.max(1)..];
+    }
+
+    points
+}
+
+/// Read a numeric attribute, which can be quoted with either single or double quotes, e.g. `lat="45.4378"`
+fn find_attribute(attributes: &str, name: &str) -> Option<f64> {
+    let mut remaining = attributes;
+    while let Some(start) = remaining.find(name) {
+        let is_whole_name = remaining[..start].ends_with(char::is_whitespace);
+        remaining = remaining[start + name.len()..].trim_start();
+        if !is_whole_name {
+            continue;
+        }
+        if let Some(value) = remaining.strip_prefix('=') {
+            let value = value.trim_start();
+            let quote = value.chars().next().filter(|quote| *quote == '"' || *quote == '\'')?;
+            let value = &value[1..];
+            return value[..value.find(quote)?].trim().parse().ok();
+        }
+    }
+    None
+}
+
+fn find_element_text<'a>(contents: &'a str, name: &str) -> Option<&'a str> {
+    let start_tag = format!("<{}>", name);
+    let end_tag = format!("</{}>", name);
+    let start = contents.find(&start_tag)? + start_tag.len();
+    let end = contents[start..].find(&end_tag)? + start;
+    Some(&contents[start..end])
+}
@@ -0,0 +1,221 @@
+use std::convert::TryInto;
+use std::io::{Read, Seek, SeekFrom};
+
+use exif::Error;
+
+use crate::isobmff::{find_box, find_child_box, read_child_boxes};
+
+/// The brands of HEIF images (HEIC, AVIF, etc), either as the major brand or as one of the compatible brands.
+/// Not all files list the generic `mif1` brand, even if they should
+const HEIF_BRANDS: &[&[u8]] = &[b"heic", b"heix", b"heim", b"heis", b"hevc", b"hevx", b"mif1", b"msf1", b"avif", b"avis"];
+
+/// The metadata of a HEIF image only describes where each item is, so it's small even for large images
+const MAX_META_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Some EXIF blocks include a preview image, so they can be larger than the 64 KB allowed in JPEG files
+const MAX_EXIF_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Check the `ftyp` box at the start of the file for the brands of HEIF images
+pub fn is_heif(header: &[u8]) -> bool {
+    if header.get(4..8) != Some(b"ftyp") {
+        return false;
+    }
+
+    // size (4), type (4), major brand (4), minor version (4), then any number of compatible brands (4 each)
+    let ftyp_len = header[..4].try_into().map_or(0, u32::from_be_bytes) as usize;
+    let major_brand = header.get(8..12);
+    let compatible_brands = header.get(16..ftyp_len.min(header.len())).unwrap_or(&[]);
+
+    major_brand.is_some_and(|brand| HEIF_BRANDS.contains(&brand))
+        || compatible_brands.chunks_exact(4).any(|brand| HEIF_BRANDS.contains(&brand))
+}
+
+/// Read the EXIF data of a HEIF image, i.e. the `Exif` item listed in the `meta` box.
+/// Returns the data in TIFF format, as expected by [exif::Reader::read_raw()]
+pub fn read_heif_exif_data<R: Read + Seek>(reader: &mut R, file_len: u64) -> Result<Vec<u8>, Error> {
+    let (meta_start, meta_end) = find_box(reader, 0, file_len, b"meta")
+        .ok_or(Error::NotFound("HEIF"))?;
+    if meta_end - meta_start > MAX_META_SIZE {
+        return Err(Error::InvalidFormat("HEIF metadata is too large"));
+    }
+
+    let mut meta = vec![0_u8; (meta_end - meta_start) as usize];
+    reader.seek(SeekFrom::Start(meta_start))?;
+    reader.read_exact(&mut meta)?;
+
+    // `meta` is a full box, i.e. its child boxes only start after the version (1) and flags (3)
+    let meta_children = meta.get(4..).ok_or(Error::InvalidFormat("Broken HEIF metadata"))?;
+    let exif_item_id = find_child_box(meta_children, b"iinf")
+        .and_then(find_exif_item_id)
+        .ok_or(Error::NotFound("HEIF"))?;
+    let location = find_child_box(meta_children, b"iloc")
+        .and_then(|iloc| find_item_location(iloc, exif_item_id))
+        .ok_or(Error::InvalidFormat("No location for the Exif item in HEIF file"))?;
+
+    let mut exif_data = Vec::new();
+    for (offset, length) in location.extents {
+        let start = location.base_offset.checked_add(offset)
+            .ok_or(Error::InvalidFormat("Invalid Exif item location"))?;
+        match location.construction_method {
+            // The extents are offsets in the file
+            0 => {
+                // An extent without a length extends to the end of the file
+                let length = if length == 0 { file_len.saturating_sub(start) } else { length };
+                if exif_data.len() as u64 + length > MAX_EXIF_SIZE {
+                    return Err(Error::TooBig("Exif data in HEIF file is too large"));
+                }
+                let mut extent = vec![0_u8; length as usize];
+                reader.seek(SeekFrom::Start(start))?;
+                reader.read_exact(&mut extent)?;
+                exif_data.extend(extent);
+            }
+            // The extents are offsets in the `idat` box of the metadata
+            1 => {
+                let idat = find_child_box(meta_children, b"idat")
+                    .ok_or(Error::InvalidFormat("No item data in HEIF file"))?;
+                let start = start as usize;
+                let end = if length == 0 { idat.len() } else { start.saturating_add(length as usize) };
+                exif_data.extend_from_slice(idat.get(start..end)
+                    .ok_or(Error::InvalidFormat("Invalid Exif item location"))?);
+            }
+            _ => return Err(Error::NotSupported("Exif items which reference other items are not supported")),
+        }
+    }
+
+    // The item starts with the offset of the TIFF header, which is usually preceded by `Exif\0\0`.
+    // Some encoders set the offset to 0 but still include the prefix
+    let tiff_offset = exif_data.get(..4)
+        .and_then(|offset| offset.try_into().ok())
+        .map(u32::from_be_bytes)
+        .ok_or(Error::InvalidFormat("Exif item in HEIF file is too small"))? as usize;
+    let mut tiff_data = exif_data.get(4 + tiff_offset..)
+        .ok_or(Error::InvalidFormat("Invalid Exif header offset in HEIF file"))?;
+    if tiff_data.starts_with(b"Exif\0\0") {
+        tiff_data = &tiff_data[6..];
+    }
+
+    Ok(tiff_data.to_vec())
+}
+
+/// Where an item is stored, as listed in the `iloc` box
+struct ItemLocation {
+    /// 0 for offsets in the file, 1 for offsets in the `idat` box, 2 for offsets in other items
+    construction_method: u8,
+    base_offset: u64,
+    /// The offset and length of each part of the item
+    extents: Vec<(u64, u64)>,
+}
+
+/// Find the ID of the `Exif` item among the item entries (`infe`) listed in the `iinf` box
+fn find_exif_item_id(iinf: &[u8]) -> Option<u32> {
+    let mut iinf = BoxReader::new(iinf);
+    let version = iinf.full_box_version()?;
+    let entry_count = if version == 0 { iinf.u16()? as u32 } else { iinf.u32()? };
+
+    let entries = read_child_boxes(iinf.rest());
+    for (_, infe) in entries.into_iter().filter(|(box_type, _)| box_type == b"infe").take(entry_count as usize) {
+        // Only versions 2 and 3 have an item type, the older versions can't describe EXIF items
+        let mut infe = BoxReader::new(infe);
+        let item_id = match infe.full_box_version()? {
+            2 => infe.u16()? as u32,
+            3 => infe.u32()?,
+            _ => continue,
+        };
+        // item protection index
+        infe.skip(2)?;
+        if infe.bytes(4)? == b"Exif" {
+            return Some(item_id);
+        }
+    }
+
+    None
+}
+
+/// Find the location of the item with this ID among the items listed in the `iloc` box
+fn find_item_location(iloc: &[u8], item_id: u32) -> Option<ItemLocation> {
+    let mut iloc = BoxReader::new(iloc);
+    let version = iloc.full_box_version()?;
+
+    // The size in bytes of each field, as 4 bits each
+    let sizes = iloc.u16()?;
+    let offset_size = (sizes >> 12) as usize;
+    let length_size = (sizes >> 8 & 0xf) as usize;
+    let base_offset_size = (sizes >> 4 & 0xf) as usize;
+    let index_size = if version == 0 { 0 } else { (sizes & 0xf) as usize };
+
+    let item_count = if version < 2 { iloc.u16()? as u32 } else { iloc.u32()? };
+    for _ in 0..item_count {
+        let current_item_id = if version < 2 { iloc.u16()? as u32 } else { iloc.u32()? };
+        let construction_method = if version == 0 { 0 } else { (iloc.u16()? & 0xf) as u8 };
+        let data_reference_index = iloc.u16()?;
+        let base_offset = iloc.sized(base_offset_size)?;
+        let extent_count = iloc.u16()?;
+
+        let mut extents = Vec::new();
+        for _ in 0..extent_count {
+            iloc.sized(index_size)?;
+            let offset = iloc.sized(offset_size)?;
+            let length = iloc.sized(length_size)?;
+            extents.push((offset, length));
+        }
+
+        // Data stored in other files can't be read
+        if current_item_id == item_id && !(construction_method == 0 && data_reference_index != 0) {
+            return Some(ItemLocation { construction_method, base_offset, extents });
+        }
+    }
+
+    None
+}
+
+/// Read the big-endian fields of a box which was already read in memory
+struct BoxReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> BoxReader<'a> {
+    fn new(data: &'a [u8]) -> BoxReader<'a> {
+        BoxReader { data }
+    }
+
+    fn bytes(&mut self, count: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(..count)?;
+        self.data = &self.data[count..];
+        Some(bytes)
+    }
+
+    fn skip(&mut self, count: usize) -> Option<()> {
+        self.bytes(count).map(|_| ())
+    }
+
+    fn rest(&self) -> &'a [u8] {
+        self.data
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.bytes(2)?.try_into().ok().map(u16::from_be_bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.bytes(4)?.try_into().ok().map(u32::from_be_bytes)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.bytes(8)?.try_into().ok().map(u64::from_be_bytes)
+    }
+
+    /// Read a field which has a size of 0, 4 or 8 bytes, as described by the box itself
+    fn sized(&mut self, size: usize) -> Option<u64> {
+        match size {
+            0 => Some(0),
+            4 => self.u32().map(u64::from),
+            8 => self.u64(),
+            _ => None,
+        }
+    }
+
+    /// Read the header of a full box, i.e. the version (1) and flags (3), and return the version
+    fn full_box_version(&mut self) -> Option<u8> {
+        self.bytes(4).map(|header| header[0])
+    }
+}
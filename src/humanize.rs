@@ -0,0 +1,64 @@
+use std::time::Duration;
+
+/// The units of [format_file_size()], each 1024 times larger than the previous one
+const SIZE_UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+
+/// Convert bytes to the largest unit in which the size is at least 1 and append the unit, e.g.:
+/// ```text
+/// 512 B
+/// 3.34 KB
+/// 1.20 GB
+/// ```
+pub fn format_file_size(size: u64) -> String {
+    let mut unit_index = 0;
+    let mut unit_size = 1_u64;
+    while unit_index < SIZE_UNITS.len() - 1 && size >= unit_size * 1024 {
+        unit_index += 1;
+        unit_size *= 1024;
+    }
+
+    if unit_index == 0 {
+        format!("{} {}", size, SIZE_UNITS[0])
+    } else {
+        format!("{:.2} {}", size as f64 / unit_size as f64, SIZE_UNITS[unit_index])
+    }
+}
+
+/// Format a duration as hours, minutes, seconds and milliseconds, e.g. `0:03:25.018`
+pub fn format_duration(duration: Duration) -> String {
+    format!("{}.{:03}", format_duration_seconds(duration), duration.subsec_millis())
+}
+
+/// Format a duration as hours, minutes and seconds, without the milliseconds, e.g. `0:03:25`
+pub fn format_duration_seconds(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_file_sizes_in_the_largest_unit() {
+        assert_eq!(format_file_size(0), "0 B");
+        assert_eq!(format_file_size(1023), "1023 B");
+        assert_eq!(format_file_size(1024), "1.00 KB");
+        assert_eq!(format_file_size(1536 * 1024 * 1024), "1.50 GB");
+        assert_eq!(format_file_size(2048 * 1024 * 1024 * 1024 * 1024), "2048.00 TB");
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(format_duration(Duration::from_secs(0)), "0:00:00.000");
+        assert_eq!(format_duration(Duration::from_millis(59_018)), "0:00:59.018");
+        assert_eq!(format_duration(Duration::from_secs(3600)), "1:00:00.000");
+    }
+
+    #[test]
+    fn formats_durations_without_milliseconds() {
+        assert_eq!(format_duration_seconds(Duration::from_secs(0)), "0:00:00");
+        assert_eq!(format_duration_seconds(Duration::from_millis(59_918)), "0:00:59");
+        assert_eq!(format_duration_seconds(Duration::from_secs(10 * 3600 + 61)), "10:01:01");
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use crate::checksum::hash_file;
+use crate::jsonl::JsonLinesFile;
+
+/// A file written by an earlier run, saved as one JSON line in the import index, e.g.:
+/// ```text
+/// {"hash":"9f86d0...","size":2481331,"date":"2019.01.28","source":"F:\\DCIM\\IMG_0001.JPG","target":"E:\\Pictures\\2019.01.28\\IMG_0001.JPG","imported":"2019-02-03 18:30:12"}
+/// ```
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportedFile {
+    /// The SHA-256 checksum of the contents, which identifies the file even after it's renamed or moved
+    pub hash: String,
+    pub size: u64,
+    /// The date the file was sorted by, in the format of the date folders
+    pub date: String,
+    pub source: PathBuf,
+    pub target: PathBuf,
+    /// When the file was written, in local time
+    pub imported: String,
+}
+
+/// The contents of all files imported by earlier runs, see [crate::config::Args::import_index_path].
+/// Files are identified by their contents only, so they're recognized even if they were renamed
+/// or the target folder was reorganized since. Only the files with the size of an imported file are hashed
+#[derive(Clone, Default)]
+pub struct ImportIndex {
+    hashes_by_size: HashMap<u64, HashSet<String>>,
+}
+
+impl ImportIndex {
+    /// Read the index, which is empty if the file doesn't exist yet.
+    /// Lines which can't be read, e.g. the last line written by a run which was interrupted, are ignored
+    pub fn read(index_path: &Path) -> Result<ImportIndex, String> {
+        let mut hashes_by_size: HashMap<u64, HashSet<String>> = HashMap::new();
+
+        let file = match File::open(index_path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(ImportIndex { hashes_by_size }),
+            Err(e) => return Err(format!("Could not read {} ({})", index_path.display(), e)),
+        };
+
+        BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| serde_json::from_str::<ImportedFile>(&line).ok())
+            .for_each(|imported_file| {
+                hashes_by_size.entry(imported_file.size).or_default().insert(imported_file.hash);
+            });
+
+        Ok(ImportIndex { hashes_by_size })
+    }
+
+    /// Whether a file with the same contents was imported by an earlier run.
+    /// Files which can't be read are never considered imported
+    pub fn contains(&self, file_path: &Path, size: u64) -> bool {
+        match self.hashes_by_size.get(&size) {
+            Some(hashes) => hash_file(file_path).is_ok_and(|hash| hashes.contains(&hash)),
+            None => false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.hashes_by_size.values().map(HashSet::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hashes_by_size.is_empty()
+    }
+}
+
+/// Only show the number of files, since the index grows with every import
+impl fmt::Debug for ImportIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ImportIndex {{ files: {} }}", self.len())
+    }
+}
+
+/// Appends each file written by this run to the import index, as soon as it's written,
+/// so that the files are recognized by the next runs even if this one is interrupted
+#[derive(Debug)]
+pub struct ImportIndexLog {
+    lines: JsonLinesFile,
+}
+
+impl ImportIndexLog {
+    pub fn open(index_path: &Path) -> Result<ImportIndexLog, io::Error> {
+        Ok(ImportIndexLog { lines: JsonLinesFile::open_append(index_path)? })
+    }
+
+    /// An index which doesn't record anything, used for dry runs or if no index is configured
+    pub fn disabled() -> ImportIndexLog {
+        ImportIndexLog { lines: JsonLinesFile::disabled() }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.lines.is_enabled()
+    }
+
+    /// Hash the written file and add it to the index. The file is only hashed if the index is recording
+    pub fn record(&mut self, date: &str, source: &Path, target: &Path) {
+        self.lines.append_with(|| Ok(ImportedFile {
+            hash: hash_file(target)?,
+            size: fs::metadata(target)?.len(),
+            date: date.to_string(),
+            source: source.to_path_buf(),
+            target: target.to_path_buf(),
+            imported: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        }))
+    }
+
+    /// The first error encountered while writing the index, if any
+    pub fn error(&self) -> Option<&io::Error> {
+        self.lines.error.as_ref()
+    }
+}
+
+/// Forget the files written to these target paths, e.g. after the run which wrote them was undone,
+/// so that they're imported again by the next run. Returns the number of files removed from the index
+pub fn remove_imported_files(index_path: &Path, targets: &HashSet<PathBuf>) -> Result<usize, io::Error> {
+    let contents = match fs::read_to_string(index_path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+
+    let (removed, kept): (Vec<&str>, Vec<&str>) = contents
+        .lines()
+        .partition(|line| serde_json::from_str::<ImportedFile>(line)
+            .is_ok_and(|imported_file| targets.contains(&imported_file.target)));
+
+    if !removed.is_empty() {
+        let kept_contents: String = kept.iter().map(|line| format!("{}\n", line)).collect();
+        fs::write(index_path, kept_contents)?;
+    }
+    Ok(removed.len())
+}
@@ -0,0 +1,43 @@
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::utils::ColoredString;
+
+/// The exit code of a run stopped with Ctrl+C, which is the one shells use for programs stopped by SIGINT
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// Set while files are written, see [start_writing]
+static IS_WRITING: AtomicBool = AtomicBool::new(false);
+
+/// Set once Ctrl+C was pressed while files were written, see [is_interrupted]
+static IS_INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Handle Ctrl+C for the whole program. While files are written, the first Ctrl+C only asks the run
+/// to stop once the files being written are complete, so that no half-copied file is left in the target
+/// folder and the stats of the files written so far are still printed. Pressing it again, or pressing it
+/// while nothing is written, e.g. while reading the source files, stops the program right away
+pub fn install_interrupt_handler() -> Result<(), ctrlc::Error> {
+    ctrlc::set_handler(|| {
+        if IS_WRITING.load(Ordering::SeqCst) && !IS_INTERRUPTED.swap(true, Ordering::SeqCst) {
+            println!();
+            println!("{}", ColoredString::orange(
+                "> Stopping once the files being written are complete, press Ctrl+C again to stop right away..."));
+        } else {
+            process::exit(INTERRUPTED_EXIT_CODE);
+        }
+    })
+}
+
+/// Mark the files as being written until [stop_writing] is called, so that Ctrl+C lets them complete
+pub fn start_writing() {
+    IS_WRITING.store(true, Ordering::SeqCst);
+}
+
+pub fn stop_writing() {
+    IS_WRITING.store(false, Ordering::SeqCst);
+}
+
+/// Whether Ctrl+C was pressed while files were written, in which case no other file should be started
+pub fn is_interrupted() -> bool {
+    IS_INTERRUPTED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,74 @@
+use std::convert::{TryFrom, TryInto};
+use std::io::{Read, Seek, SeekFrom};
+
+/// Find the box with this type between the `start` and `end` positions of an ISO base media file
+/// (MP4, MOV, HEIC, etc) and return the positions of its contents.
+/// Boxes are skipped by seeking, since some of them (e.g. the media data) can be several GB
+pub fn find_box<R: Read + Seek>(reader: &mut R, start: u64, end: u64, box_type: &[u8; 4]) -> Option<(u64, u64)> {
+    let mut position = start;
+    while position + 8 <= end {
+        reader.seek(SeekFrom::Start(position)).ok()?;
+        let mut header = [0_u8; 8];
+        reader.read_exact(&mut header).ok()?;
+
+        let size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+        let (header_len, box_len) = match size {
+            // The box extends to the end of the file
+            0 => (8, end - position),
+            // The actual size follows the type, as 64 bits
+            1 => {
+                let mut large_size = [0_u8; 8];
+                reader.read_exact(&mut large_size).ok()?;
+                (16, u64::from_be_bytes(large_size))
+            }
+            _ => (8, size),
+        };
+        if box_len < header_len || position.checked_add(box_len)? > end {
+            return None;
+        }
+
+        if &header[4..8] == box_type {
+            return Some((position + header_len, position + box_len));
+        }
+        position += box_len;
+    }
+
+    None
+}
+
+/// Find the box with this type among the boxes which were already read in memory
+/// and return its contents, e.g. to find the child boxes of a box which was read with [find_box()]
+pub fn find_child_box<'a>(data: &'a [u8], box_type: &[u8; 4]) -> Option<&'a [u8]> {
+    read_child_boxes(data)
+        .into_iter()
+        .find(|(child_type, _)| child_type == box_type)
+        .map(|(_, contents)| contents)
+}
+
+/// Split the boxes which were already read in memory into their type and contents.
+/// Stops at the first box which doesn't fit in the data
+pub fn read_child_boxes(data: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    let mut boxes = Vec::new();
+    let mut position = 0;
+    while position + 8 <= data.len() {
+        let size = u32::from_be_bytes([data[position], data[position + 1], data[position + 2], data[position + 3]]) as u64;
+        let (header_len, box_len) = match size {
+            0 => (8, (data.len() - position) as u64),
+            1 => match data.get(position + 8..position + 16).and_then(|large_size| large_size.try_into().ok()) {
+                Some(large_size) => (16, u64::from_be_bytes(large_size)),
+                None => break,
+            },
+            _ => (8, size),
+        };
+        let box_end = match usize::try_from(box_len).ok().and_then(|box_len| position.checked_add(box_len)) {
+            Some(box_end) if box_len >= header_len && box_end <= data.len() => box_end,
+            _ => break,
+        };
+
+        let box_type = [data[position + 4], data[position + 5], data[position + 6], data[position + 7]];
+        boxes.push((box_type, &data[position + header_len as usize..box_end]));
+        position = box_end;
+    }
+
+    boxes
+}
@@ -0,0 +1,294 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use crate::checksum::remove_manifest_entry;
+use crate::exif_report::remove_exif_report_entry;
+use crate::filesystem::*;
+use crate::interrupt::is_interrupted;
+use crate::jsonl::JsonLinesFile;
+
+/// The folder inside the target folder which holds the journal of each run
+pub const JOURNAL_DIR_NAME: &str = ".imgsorter-journal";
+
+const JOURNAL_EXTENSION: &str = "jsonl";
+
+/// Journals are renamed with this extension after they are undone, so they're not undone twice
+const UNDONE_JOURNAL_EXTENSION: &str = "undone";
+
+/// Saved inside the journal folder while a run is writing files and removed once it's finished,
+/// so that the next run can tell if it was interrupted, see [find_interrupted_run]
+const RUN_MARKER_FILE_NAME: &str = "running.json";
+
+/// A single operation which changed the target or the source folders, written as one JSON line, e.g.:
+/// ```text
+/// {"operation":"create_dir","path":"E:\\Pictures\\2019.01.28"}
+/// {"operation":"move","source":"D:\\Pics\\IMG_0001.JPG","target":"E:\\Pictures\\2019.01.28\\IMG_0001.JPG","overwritten":false}
+/// ```
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "operation", rename_all = "snake_case")]
+pub enum JournalEntry {
+    CreateDir { path: PathBuf },
+    Copy { source: PathBuf, target: PathBuf, overwritten: bool },
+    Move { source: PathBuf, target: PathBuf, overwritten: bool },
+}
+
+/// The journal of a single run, which records each operation as soon as it's done,
+/// so that the run can be undone later, even if the program was interrupted
+#[derive(Debug)]
+pub struct Journal {
+    lines: JsonLinesFile,
+    /// The path of the journal file, empty if the journal is disabled
+    path: PathBuf,
+    /// The marker saved while the run is writing files, see [Journal::mark_running]
+    run_marker_path: Option<PathBuf>,
+}
+
+/// The run which is writing files into the target folder, saved as [RUN_MARKER_FILE_NAME], e.g.:
+/// ```text
+/// {"journal":"2019-02-03_18-30-12.jsonl","source_dirs":[["D:\\Pics","D:\\Pics\\Holiday"]],"started":"2019-02-03 18:30:12"}
+/// ```
+/// Runs which only sorted some files of their source folders, e.g. with `--files-from`, also save these files
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RunMarker {
+    /// The file name of the journal of the run
+    pub journal: String,
+    /// The source folders read by the run, each followed by its subfolders
+    pub source_dirs: Vec<Vec<PathBuf>>,
+    /// The files sorted by the run, if it didn't read whole source folders, see [crate::config::Args::source_files_list].
+    /// Markers saved by older versions don't have them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_files: Option<Vec<PathBuf>>,
+    /// The plan file applied by the run, if it was started by the `apply` command, which resumes it by applying
+    /// the plan again, see [crate::script::ExportedPlan]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan: Option<PathBuf>,
+    /// When the run started writing files, in local time
+    pub started: String,
+}
+
+impl Journal {
+    /// Create a new journal file for this run inside the target folder, named after the current time
+    pub fn create(target_dir: &Path) -> Result<Journal, io::Error> {
+        let journal_dir = target_dir.join(JOURNAL_DIR_NAME);
+        fs::create_dir_all(&journal_dir)?;
+
+        let run_name = Local::now().format("%Y-%m-%d_%H-%M-%S").to_string();
+        let mut attempt = 1;
+        loop {
+            let file_name = if attempt == 1 {
+                format!("{}.{}", run_name, JOURNAL_EXTENSION)
+            } else {
+                format!("{}_{}.{}", run_name, attempt, JOURNAL_EXTENSION)
+            };
+
+            let path = journal_dir.join(file_name);
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => return Ok(Journal { lines: JsonLinesFile::new(file), path, run_marker_path: None }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => attempt += 1,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// A journal which doesn't record anything, used for dry runs
+    pub fn disabled() -> Journal {
+        Journal { lines: JsonLinesFile::disabled(), path: PathBuf::new(), run_marker_path: None }
+    }
+
+    /// Save the marker of a run which is writing files from these source folders, or only these files
+    /// of the source folders, or which is applying a plan, until [Journal::finish] is called
+    pub fn mark_running(
+        &mut self,
+        source_dirs: &[Vec<PathBuf>],
+        source_files: Option<&[PathBuf]>,
+        plan: Option<&Path>,
+    ) -> Result<(), io::Error> {
+        let (journal_dir, journal_name) = match (self.lines.is_enabled(), self.path.parent(), self.path.file_name()) {
+            (true, Some(journal_dir), Some(journal_name)) => (journal_dir, journal_name),
+            _ => return Ok(()),
+        };
+
+        let run_marker = RunMarker {
+            journal: journal_name.to_string_lossy().to_string(),
+            source_dirs: source_dirs.to_vec(),
+            source_files: source_files.map(|source_files| source_files.to_vec()),
+            plan: plan.map(Path::to_path_buf),
+            started: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        };
+        let run_marker_path = journal_dir.join(RUN_MARKER_FILE_NAME);
+        fs::write(&run_marker_path, serde_json::to_string(&run_marker)?)?;
+        self.run_marker_path = Some(run_marker_path);
+        Ok(())
+    }
+
+    /// Remove the marker of the run once all of its files were written
+    pub fn finish(&mut self) -> Result<(), io::Error> {
+        match self.run_marker_path.take() {
+            Some(run_marker_path) => fs::remove_file(run_marker_path),
+            None => Ok(()),
+        }
+    }
+
+    /// Make sure the recorded entries are saved on the disk, e.g. before the program exits after being stopped
+    pub fn sync(&self) -> Result<(), io::Error> {
+        self.lines.sync()
+    }
+
+    pub fn record(&mut self, entry: JournalEntry) {
+        self.lines.append(&entry)
+    }
+
+    /// The first error encountered while writing the journal, if any
+    pub fn error(&self) -> Option<&io::Error> {
+        self.lines.error.as_ref()
+    }
+}
+
+/// Find the journal of the most recent run which wasn't undone yet.
+/// Runs which didn't change anything, e.g. when all files already existed, are ignored
+pub fn find_last_journal(target_dir: &Path) -> Option<PathBuf> {
+    fs::read_dir(target_dir.join(JOURNAL_DIR_NAME))
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.metadata().is_ok_and(|metadata| metadata.len() > 0))
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == JOURNAL_EXTENSION))
+        .max()
+}
+
+/// Find the last run into the target folder which was interrupted before it finished writing its files,
+/// together with the path of its journal. Interrupted runs which were undone, or which didn't write
+/// anything yet, don't need to be resumed, so their marker is removed instead
+pub fn find_interrupted_run(target_dir: &Path) -> Option<(PathBuf, RunMarker)> {
+    let journal_dir = target_dir.join(JOURNAL_DIR_NAME);
+    let run_marker_contents = fs::read_to_string(journal_dir.join(RUN_MARKER_FILE_NAME)).ok()?;
+
+    let interrupted_run = serde_json::from_str::<RunMarker>(&run_marker_contents)
+        .ok()
+        .map(|run_marker| (journal_dir.join(&run_marker.journal), run_marker))
+        .filter(|(journal_path, _)| fs::metadata(journal_path).is_ok_and(|metadata| metadata.len() > 0));
+
+    if interrupted_run.is_none() {
+        let _ = clear_interrupted_run(target_dir);
+    }
+    interrupted_run
+}
+
+/// Forget the interrupted run, once it was resumed or the user chose to ignore it
+pub fn clear_interrupted_run(target_dir: &Path) -> Result<(), io::Error> {
+    match fs::remove_file(target_dir.join(JOURNAL_DIR_NAME).join(RUN_MARKER_FILE_NAME)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Read all entries of a journal. Lines which can't be read, e.g. the last line
+/// of a journal written by a run which was interrupted, are ignored
+pub fn read_journal(journal_path: &Path) -> Result<Vec<JournalEntry>, io::Error> {
+    let reader = BufReader::new(File::open(journal_path)?);
+    Ok(reader
+        .lines()
+        .map_while(Result::ok)
+        .filter_map(|line| serde_json::from_str(&line).ok())
+        .collect())
+}
+
+/// The outcome of undoing a run
+#[derive(Debug, Default)]
+pub struct UndoReport {
+    pub files_restored: usize,
+    pub copies_removed: usize,
+    pub dirs_removed: usize,
+    /// Operations which could not be undone, each with a description of why
+    pub errors: Vec<String>,
+}
+
+/// Undo all operations of a journal, in reverse order: move files back to their
+/// source folders, remove the copied files, then remove the created folders if they're empty.
+/// The journal is marked as undone afterwards, even if some operations could not be undone,
+/// unless the program is stopped with Ctrl+C, so that the remaining operations can be undone later
+pub fn undo_journal(journal_path: &Path) -> Result<UndoReport, io::Error> {
+    let mut report = UndoReport::default();
+
+    for entry in read_journal(journal_path)?.into_iter().rev() {
+        if is_interrupted() {
+            return Ok(report);
+        }
+        match entry {
+            // The previous target file is lost, so there's nothing to restore it from
+            JournalEntry::Copy { target, overwritten: true, .. } | JournalEntry::Move { target, overwritten: true, .. } =>
+                report.errors.push(format!("{} (it replaced another file, which can't be restored)", target.display())),
+
+            JournalEntry::Copy { source, target, .. } => {
+                // Never remove the only remaining copy of a file
+                if !source.exists() {
+                    report.errors.push(format!("{} (the source file no longer exists)", target.display()));
+                    continue;
+                }
+                match fs::remove_file(&target) {
+                    Ok(_) => {
+                        remove_reported_entries(&target);
+                        report.copies_removed += 1;
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                    Err(e) => report.errors.push(format!("{} ({})", target.display(), e)),
+                }
+            }
+
+            JournalEntry::Move { source, target, .. } => {
+                // Already moved back by an undo which was stopped before it was complete
+                if source.exists() && !target.exists() {
+                    continue;
+                }
+                if source.exists() {
+                    report.errors.push(format!("{} (a file already exists at {})", target.display(), source.display()));
+                    continue;
+                }
+                match move_file_back(&target, &source) {
+                    Ok(_) => {
+                        remove_reported_entries(&target);
+                        report.files_restored += 1;
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                    Err(e) => report.errors.push(format!("{} ({})", target.display(), e)),
+                }
+            }
+
+            JournalEntry::CreateDir { path } => match remove_empty_dir(&path) {
+                RemoveDirResult::Removed => report.dirs_removed += 1,
+                RemoveDirResult::NotEmpty | RemoveDirResult::Missing => {}
+                RemoveDirResult::Failed(e) => report.errors.push(format!("{} ({})", path.display(), e)),
+            },
+        }
+    }
+
+    fs::rename(journal_path, journal_path.with_extension(UNDONE_JOURNAL_EXTENSION))?;
+    Ok(report)
+}
+
+/// Move the file back to its source path, recreating the source folders if they were removed.
+/// Renaming only works on the same drive, so otherwise copy the file and delete it
+fn move_file_back(target: &Path, source: &Path) -> Result<(), io::Error> {
+    if !target.exists() {
+        return Err(io::Error::from(io::ErrorKind::NotFound));
+    }
+    if let Some(source_dir) = source.parent() {
+        fs::create_dir_all(source_dir)?;
+    }
+    fs::rename(target, source).or_else(|_| {
+        fs::copy(target, source)?;
+        fs::remove_file(target)
+    })
+}
+
+/// Remove the checksum and the EXIF details of a file which was removed from the archive, if there are any
+fn remove_reported_entries(file_path: &Path) {
+    if let (Some(dir), Some(file_name)) = (file_path.parent(), file_path.file_name()) {
+        let _ = remove_manifest_entry(dir, &file_name.to_string_lossy());
+        let _ = remove_exif_report_entry(dir, &file_name.to_string_lossy());
+    }
+}
@@ -0,0 +1,71 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+/// A file to which each record is appended as one JSON line as soon as it's recorded, so that
+/// the records are kept even if the program is stopped, e.g. the journal of a run, the operation log
+/// or the import index. A disabled file, e.g. for dry runs, doesn't record anything.
+/// If writing fails, the file stops recording and keeps the error, so that it can be reported at the end
+#[derive(Debug)]
+pub struct JsonLinesFile {
+    file: Option<File>,
+    /// The first error encountered while writing the file, if any
+    pub error: Option<io::Error>,
+}
+
+impl JsonLinesFile {
+    pub fn new(file: File) -> JsonLinesFile {
+        JsonLinesFile { file: Some(file), error: None }
+    }
+
+    /// Open the file to append to it, creating the file and its folder if they don't exist yet
+    pub fn open_append(path: &Path) -> Result<JsonLinesFile, io::Error> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(JsonLinesFile::new(file))
+    }
+
+    pub fn disabled() -> JsonLinesFile {
+        JsonLinesFile { file: None, error: None }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.file.is_some()
+    }
+
+    pub fn append<T: Serialize>(&mut self, record: &T) {
+        self.append_with(|| Ok(record))
+    }
+
+    /// Append the record returned by `build_record`, which is only called while the file is recording,
+    /// e.g. if building it needs to read a file. Records which can't be built or saved as JSON,
+    /// e.g. with paths which aren't valid UTF-8, are left out and their error is kept, but the next ones are still recorded
+    pub fn append_with<T: Serialize>(&mut self, build_record: impl FnOnce() -> Result<T, io::Error>) {
+        if let Some(file) = &mut self.file {
+            let line = match build_record().and_then(|record| Ok(serde_json::to_string(&record)?)) {
+                Ok(line) => line,
+                Err(e) => {
+                    self.error.get_or_insert(e);
+                    return;
+                }
+            };
+
+            if let Err(e) = writeln!(file, "{}", line).and_then(|_| file.flush()) {
+                self.error.get_or_insert(e);
+                self.file = None;
+            }
+        }
+    }
+
+    /// Make sure the recorded lines are saved on the disk, e.g. before the program exits after being stopped
+    pub fn sync(&self) -> Result<(), io::Error> {
+        match &self.file {
+            Some(file) => file.sync_all(),
+            None => Ok(()),
+        }
+    }
+}
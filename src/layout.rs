@@ -0,0 +1,552 @@
+use std::cmp::max;
+
+use crate::config::Args;
+use crate::utils::*;
+
+/// Collects the lengths of the source files and target paths while the source files are read.
+/// Once the target dir tree is complete, this is turned into a [DryRunLayout], [SourceViewLayout] or [WriteLayout],
+/// which compute all the widths needed to align the output a single time.
+pub struct Padder {
+    /// Whether there's a single source directory or multiple
+    /// This matters when outputting source paths - for single sources we'd only
+    /// need to output the filname, since the full path will always be the same
+    has_multiple_sources: bool,
+
+    /// The maximum length of filename of all source files,
+    /// without any path information, e.g. `IMG-20190128.jpg`
+    pub source_base_file_max_len: usize,
+
+    /// The maximum length of the absolute path length of all source files,
+    /// including the file name, e.g. `D:\Pics\IMG-20190128.jpg`
+    pub source_path_max_len: usize,
+
+    /// This maximum length of the relative target path from the parent target dir
+    /// This *does not* include the filename length, which can always be read
+    ///   from [source_base_file_max_len] (and adding 1 for the separator char)
+    /// So this will include either the "date\device name", or just the "date",
+    ///   e.g. `2019.01.28\Canon 100` or just `2019.01.28`
+    pub target_relative_path_max_len: usize,
+}
+
+impl Padder {
+    pub fn new(has_multiple_sources: bool) -> Padder {
+        Padder {
+            has_multiple_sources,
+            source_base_file_max_len: 0,
+            source_path_max_len: 0,
+            target_relative_path_max_len: 0,
+        }
+    }
+
+    pub fn set_max_source_filename(&mut self, new_file_len: usize) {
+        self.source_base_file_max_len = max(self.source_base_file_max_len, new_file_len)
+    }
+
+    pub fn set_max_source_path(&mut self, new_path_len: usize) {
+        self.source_path_max_len = max(self.source_path_max_len, new_path_len)
+    }
+
+    pub fn set_max_target_path(&mut self, new_path_len: usize) {
+        self.target_relative_path_max_len = max(self.target_relative_path_max_len, new_path_len)
+    }
+
+    pub fn set_max_source_filename_from_str(&mut self, new_file_name: &str) {
+        self.set_max_source_filename(new_file_name.chars().count());
+    }
+
+    pub fn set_max_source_path_from_str(&mut self, new_path: &str) {
+        self.set_max_source_path(new_path.chars().count());
+    }
+
+    /// The source column shows the full path only if there are multiple source dirs
+    fn get_source_len(&self) -> usize {
+        if self.has_multiple_sources {
+            self.source_path_max_len
+        } else {
+            self.source_base_file_max_len
+        }
+    }
+
+    /// Compute all the widths of the dry run output. The `status_width` is the length of the longest
+    /// status, see [HEADER_OPERATION_STATUS]
+    pub fn build_dry_run_layout(&self, status_width: usize, args: &Args) -> DryRunLayout {
+        // The target file is prefixed by the dir tree symbols of the device dir and the file
+        // TODO 5h FILE_TREE_INDENT is not required when there's only one level (i.e. one single device throughout)
+        let extra_source_chars = DIR_TREE_INDENT_MID.chars().count() + DIR_TREE_ENTRY_LAST.chars().count();
+        let max_target_len = self.source_base_file_max_len + extra_source_chars;
+        let source_len = self.get_source_len();
+
+        let target_header_len = max_target_len
+            + 1 // add +1 for the gap between the target filename and the operation separator
+            + SEPARATOR_DRY_RUN_LEFT_TO_RIGHT.chars().count();
+        let source_header_len = source_len
+            + 1 // add +1 for the gap between the source path and the status separator
+            + SEPARATOR_OP_STATUS.chars().count();
+        let total_padding_len = target_header_len
+            + 1 // add +1 for the gap between the operation separator and the source file/path
+            + source_header_len;
+
+        let mut header = format_header("TARGET FILE", target_header_len, "SOURCE PATH", source_header_len, status_width);
+        let mut header_len = total_padding_len
+            + 1 // add +1 for the gap between the status separator and the status
+            + status_width;
+        if args.show_location {
+            header = format!("{} {}", header, HEADER_LOCATION);
+            header_len += 1 + LOCATION_WIDTH;
+        }
+
+        DryRunLayout {
+            // this is an en-dash, not a dash
+            header_separator: "–".repeat(header_len),
+            header,
+            total_padding_len,
+            file_separator_len: max_target_len + SEPARATOR_DRY_RUN_LEFT_TO_RIGHT.chars().count(),
+            status_separator_len: source_len + SEPARATOR_OP_STATUS.chars().count(),
+            status_width,
+            show_location: args.show_location,
+            align_file_output: args.align_file_output,
+        }
+    }
+
+    /// Compute all the widths of the dry run output grouped by source dir, see [Args::dry_run_view].
+    /// The `status_width` is the length of the longest status, see [HEADER_OPERATION_STATUS]
+    pub fn build_source_view_layout(&self, status_width: usize, args: &Args) -> SourceViewLayout {
+        // The source files are listed by name below their dir, with a single dir tree symbol
+        let max_source_len = self.source_base_file_max_len + DIR_TREE_ENTRY_LAST.chars().count();
+        // add +1 for the separator between the target path and the filename
+        let max_target_len = self.target_relative_path_max_len + 1 + self.source_base_file_max_len;
+
+        let source_header_len = max_source_len
+            + 1 // add +1 for the gap between the source file and the operation separator
+            + SEPARATOR_DRY_RUN_LEFT_TO_RIGHT.chars().count();
+        let target_header_len = max_target_len
+            + 1 // add +1 for the gap between the target path and the status separator
+            + SEPARATOR_OP_STATUS.chars().count();
+        let total_padding_len = source_header_len
+            + 1 // add +1 for the gap between the operation separator and the target path
+            + target_header_len;
+
+        SourceViewLayout {
+            // this is an en-dash, not a dash
+            header_separator: "–".repeat(
+                total_padding_len
+                    + 1 // add +1 for the gap between the status separator and the status
+                    + status_width),
+            header: format_header("SOURCE FILE", source_header_len, "TARGET PATH", target_header_len, status_width),
+            file_separator_len: max_source_len + SEPARATOR_DRY_RUN_LEFT_TO_RIGHT.chars().count(),
+            status_separator_len: max_target_len + SEPARATOR_OP_STATUS.chars().count(),
+            align_file_output: args.align_file_output,
+        }
+    }
+
+    /// Compute all the widths of the copy/move output. The `status_width` is the length of the longest
+    /// status, see [HEADER_OPERATION_STATUS]
+    pub fn build_write_layout(&self, status_width: usize) -> WriteLayout {
+        // The target path is the relative target path plus the base filename,
+        // add +1 for the separator between the path and the filename
+        let max_target_len = self.target_relative_path_max_len + 1 + self.source_base_file_max_len;
+        let source_len = self.get_source_len();
+
+        let source_header_len = source_len
+            + 1 // add +1 for the gap between the source path and the operation separator
+            + SEPARATOR_COPY_MOVE.chars().count();
+        let target_header_len = max_target_len
+            + 1 // add +1 for the gap between the target path and the operation status
+            + SEPARATOR_OP_STATUS.chars().count();
+        let total_padding_len = source_header_len
+            + 1 // add +1 for the gap between the operation separator and the target path
+            + target_header_len;
+
+        WriteLayout {
+            // this is an em-dash, not a dash
+            header_separator: "─".repeat(
+                total_padding_len
+                    + 1 // add +1 for the gap between the status separator and the status
+                    + status_width),
+            header: format_header("SOURCE PATH", source_header_len, "TARGET FILE", target_header_len, status_width),
+            total_padding_len,
+            file_separator_len: source_len + SEPARATOR_COPY_MOVE.chars().count(),
+            status_separator_len: max_target_len + SEPARATOR_OP_STATUS.chars().count(),
+        }
+    }
+}
+
+/// Formats the rows of the operations table, so that the columns are aligned.
+/// The widths are computed once by the [Padder] after all files are read,
+/// then each row only needs to pad its own strings.
+pub trait Layout {
+    /// The line printed above and below the header
+    fn header_separator(&self) -> &str;
+
+    /// The names of the columns
+    fn header(&self) -> &str;
+
+    /// The operation separator between the file on the left and the file on the right,
+    /// padded to align the file on the right
+    fn format_file_separator(&self, left_file: &str) -> String;
+
+    /// The dotted separator between the file on the right and the status of the operation,
+    /// padded to align the status
+    fn format_status_separator(&self, right_file: &str) -> String;
+}
+
+/// Sample output for dry-runs
+/// ```text
+/// ---------------------------------------------------------------------------------
+/// TARGET FILE                     SOURCE PATH                  OPERATION STATUS
+/// ---------------------------------------------------------------------------------
+/// [2019.01.28] (2 devices, 3 files, 3.34 MB) ................. [new folder will be created]
+///  ├── [Canon 100D] .......................................... [new folder will be created]
+///  │    ├── IMG-20190128.jpg <--- D:\Pics\IMG-20190128.jpg ... target file exists, will be skipped
+///  │    └── IMG-20190129.jpg <--- D:\Pics\IMG-20190129.jpg ... file will be copied
+///  └── IMG-20190127.jpg <-------- D:\Pics\IMG-20190127.jpg ... file will be copied
+/// ```
+pub struct DryRunLayout {
+    header_separator: String,
+    header: String,
+
+    /// The length of everything before the status column
+    total_padding_len: usize,
+
+    /// The length of the target file, including the dir tree symbols, plus the operation separator
+    file_separator_len: usize,
+
+    /// The length of the source file or path plus the status separator
+    status_separator_len: usize,
+
+    /// The length of the longest status, which the location column is aligned to
+    status_width: usize,
+
+    /// See [Args::show_location]
+    show_location: bool,
+
+    /// See [Args::align_file_output]
+    align_file_output: bool,
+}
+
+impl DryRunLayout {
+    /// Adds dot padding to the maximum padding length for the date dir, e.g.:
+    /// `[2019.01.28] (2 devices, 3 files, 3.34 MB) .................`
+    pub fn format_date_dir(&self, date_dir_name_with_device_status: String) -> String {
+        if self.align_file_output {
+            RightPadding::dot(date_dir_name_with_device_status, self.total_padding_len)
+        } else {
+            format!("{} {} ", date_dir_name_with_device_status, SEPARATOR_OP_STATUS)
+        }
+    }
+
+    /// Adds dot padding to the maximum padding length for the device dir.
+    /// The device dirs will always have a single dir tree symbol prefix,
+    /// since we don't expect additional sublevels for the devices, e.g.:
+    /// `└── [Canon 100D] ..............................`
+    pub fn format_device_dir(&self, device_dir_name: String, is_last_dir: bool) -> String {
+        let indented_device_dir_name: String = indent_string(
+            // There are no indent levels for device dirs
+            // If it's the last dir, it's also the last element of type dir
+            0, format!("[{}] ", device_dir_name), is_last_dir, is_last_dir);
+
+        if self.align_file_output {
+            RightPadding::dot(indented_device_dir_name, self.total_padding_len)
+        } else {
+            format!("{} {}", indented_device_dir_name, SEPARATOR_OP_STATUS)
+        }
+    }
+
+    /// Adds space padding to the maximum padding length for the snipping output.
+    /// ```text
+    /// ├── IMG-20190128.jpg <--- D:\Pics\IMG-20190128.jpg ... target file exists, will be skipped
+    /// ·-- (snipped output for 2 files with same status)
+    /// └── IMG-20190129.jpg <--- D:\Pics\IMG-20190129.jpg ... file will be copied
+    /// ```
+    pub fn format_snipped_output(&self, skip_count: usize, indent_level: usize, is_last_dir: bool) -> String {
+        format_snipped_output(skip_count, indent_level, is_last_dir)
+    }
+
+    /// Adds the location column after the status, if enabled, e.g.:
+    /// `file will be copied  45.43780, 12.33590`
+    pub fn format_status(&self, status: &str, location: Option<String>) -> String {
+        match location {
+            Some(location) if self.show_location => {
+                // The status is colored, so the color codes are added to the padding
+                let color_codes_len = status.chars().count() - get_visible_char_count(status);
+                format!("{} {}", RightPadding::space(status.to_string(), self.status_width + color_codes_len), location)
+            }
+            _ => status.to_string(),
+        }
+    }
+}
+
+impl Layout for DryRunLayout {
+    fn header_separator(&self) -> &str {
+        &self.header_separator
+    }
+
+    fn header(&self) -> &str {
+        &self.header
+    }
+
+    fn format_file_separator(&self, left_file: &str) -> String {
+        if self.align_file_output {
+            // If for some reason the target file is longer than the maximum length,
+            // just use the minimum length for a separator
+            let padding_len = self.file_separator_len
+                .checked_sub(left_file.chars().count())
+                .filter(|padding_len| *padding_len > 0)
+                .unwrap_or_else(|| SEPARATOR_DRY_RUN_LEFT_TO_RIGHT.chars().count());
+            let padded_separator = RightPadding::dash(
+                // Add a space to the left so there's a gap between the previous file and the separator
+                format!(" {}", SEPARATOR_DRY_RUN_RIGHT_TO_LEFT),
+                // add +1 for the space added before the separator
+                padding_len + 1);
+            // Add a space to the right so there's a gap between the separator and the next file
+            ColoredString::cyan(format!("{} ", padded_separator).as_str())
+        } else {
+            ColoredString::cyan(format!(" {} ", SEPARATOR_DRY_RUN_RIGHT_TO_LEFT).as_str())
+        }
+    }
+
+    fn format_status_separator(&self, right_file: &str) -> String {
+        if self.align_file_output {
+            let padded_separator = RightPadding::dot(
+                // Add a space to the left so there's a gap between the source file and the separator
+                format!(" {}", SEPARATOR_OP_STATUS),
+                // add +1 for the space added before the separator
+                self.status_separator_len.saturating_sub(right_file.chars().count()) + 1);
+            // Add a space to the right so there's a gap between the separator and the status
+            format!("{} ", padded_separator)
+        } else {
+            ColoredString::cyan(format!(" {} ", SEPARATOR_OP_STATUS).as_str())
+        }
+    }
+}
+
+/// Sample output for copy/move operations
+/// ```text
+/// ──────────────────────────────────────────────────────────────────────────────────────────
+/// SOURCE PATH                   TARGET FILE                                OPERATION STATUS
+/// ──────────────────────────────────────────────────────────────────────────────────────────
+/// [2019.01.28] (2 devices, 3 files, 3.34 MB) ............................ [new folder created]
+///  └── [Canon 100D] ....................................................... [new folder created]
+///
+/// D:\Pics\IMG-20190127.jpg ───> 2019.01.28\IMG-20190127.jpg .............. ok
+/// D:\Pics\IMG-20190128.jpg ───> 2019.01.28\Canon 100D\IMG-20190128.jpg ... already exists
+/// D:\Pics\IMG-20190129.jpg ───> 2019.01.28\Canon 100D\IMG-20190129.jpg ... ok
+/// ```
+pub struct WriteLayout {
+    header_separator: String,
+    header: String,
+
+    /// The length of everything before the status column
+    total_padding_len: usize,
+
+    /// The length of the source file or path plus the operation separator
+    file_separator_len: usize,
+
+    /// The length of the relative target path plus the status separator
+    status_separator_len: usize,
+}
+
+impl WriteLayout {
+    /// Adds dot padding to the maximum padding length for the date dir when writing files,
+    /// so its status is aligned with the status of the files, e.g.:
+    /// `[2019.01.28] (2 devices, 3 files, 3.34 MB) .................`
+    pub fn format_date_dir(&self, date_dir_name_with_device_status: String) -> String {
+        RightPadding::dot(date_dir_name_with_device_status, self.total_padding_len)
+    }
+
+    /// Adds dot padding to the maximum padding length for the device dir when writing files.
+    /// Same as for dry runs, device dirs have a single dir tree symbol prefix, e.g.:
+    /// `└── [Canon 100D] ..............................`
+    pub fn format_device_dir(&self, device_dir_name: String, is_last_dir: bool) -> String {
+        let indented_device_dir_name: String = indent_string(
+            0, format!("[{}] ", device_dir_name), is_last_dir, is_last_dir);
+
+        RightPadding::dot(indented_device_dir_name, self.total_padding_len)
+    }
+}
+
+impl Layout for WriteLayout {
+    fn header_separator(&self) -> &str {
+        &self.header_separator
+    }
+
+    fn header(&self) -> &str {
+        &self.header
+    }
+
+    fn format_file_separator(&self, left_file: &str) -> String {
+        let padded_separator = LeftPadding::em_dash(
+            // Add a space to the left so there's a gap between the file and the separator
+            format!("{} ", SEPARATOR_COPY_MOVE),
+            // add +1 for the space added before the separator
+            self.file_separator_len.saturating_sub(left_file.chars().count()) + 1);
+        // Add a space to the right so there's a gap between the separator and the target file
+        format!(" {}", padded_separator)
+    }
+
+    fn format_status_separator(&self, right_file: &str) -> String {
+        let padded_separator = RightPadding::dot(
+            // Add a space to the left so there's a gap between the target file and the separator
+            format!(" {}", SEPARATOR_OP_STATUS),
+            // add +1 for the space added before the separator
+            self.status_separator_len.saturating_sub(right_file.chars().count()) + 1);
+        // Add a space to the right so there's a gap between the separator and the status
+        format!("{} ", padded_separator)
+    }
+}
+
+/// Sample output for dry-runs grouped by source dir
+/// ```text
+/// ---------------------------------------------------------------------------------
+/// SOURCE FILE                 TARGET PATH                               OPERATION STATUS
+/// ---------------------------------------------------------------------------------
+/// [D:\Pics] (3 files, 3.34 MB)
+///  ├── IMG-20190127.jpg ---> 2019.01.28\IMG-20190127.jpg .............. file will be copied
+///  ├── IMG-20190128.jpg ---> 2019.01.28\Canon 100D\IMG-20190128.jpg ... target file exists, will be skipped
+///  └── IMG-20190129.jpg ---> 2019.01.28\Canon 100D\IMG-20190129.jpg ... file will be copied
+/// ```
+pub struct SourceViewLayout {
+    header_separator: String,
+    header: String,
+
+    /// The length of the source file, including the dir tree symbol, plus the operation separator
+    file_separator_len: usize,
+
+    /// The length of the relative target path plus the status separator
+    status_separator_len: usize,
+
+    /// See [Args::align_file_output]
+    align_file_output: bool,
+}
+
+impl SourceViewLayout {
+    /// Same as [DryRunLayout::format_snipped_output()]
+    pub fn format_snipped_output(&self, skip_count: usize, indent_level: usize, is_last_dir: bool) -> String {
+        format_snipped_output(skip_count, indent_level, is_last_dir)
+    }
+}
+
+impl Layout for SourceViewLayout {
+    fn header_separator(&self) -> &str {
+        &self.header_separator
+    }
+
+    fn header(&self) -> &str {
+        &self.header
+    }
+
+    fn format_file_separator(&self, left_file: &str) -> String {
+        if self.align_file_output {
+            let padded_separator = LeftPadding::dash(
+                // Add a space to the right so there's a gap between the separator and the target path
+                format!("{} ", SEPARATOR_DRY_RUN_LEFT_TO_RIGHT),
+                // add +1 for the space added after the separator
+                self.file_separator_len.saturating_sub(left_file.chars().count()) + 1);
+            // Add a space to the left so there's a gap between the source file and the separator
+            ColoredString::cyan(format!(" {}", padded_separator).as_str())
+        } else {
+            ColoredString::cyan(format!(" {} ", SEPARATOR_DRY_RUN_LEFT_TO_RIGHT).as_str())
+        }
+    }
+
+    fn format_status_separator(&self, right_file: &str) -> String {
+        if self.align_file_output {
+            let padded_separator = RightPadding::dot(
+                // Add a space to the left so there's a gap between the target path and the separator
+                format!(" {}", SEPARATOR_OP_STATUS),
+                // add +1 for the space added before the separator
+                self.status_separator_len.saturating_sub(right_file.chars().count()) + 1);
+            // Add a space to the right so there's a gap between the separator and the status
+            format!("{} ", padded_separator)
+        } else {
+            ColoredString::cyan(format!(" {} ", SEPARATOR_OP_STATUS).as_str())
+        }
+    }
+}
+
+/// The length of the longest location, e.g. `-33.85678, -151.21530`
+const LOCATION_WIDTH: usize = 21;
+
+fn format_snipped_output(skip_count: usize, indent_level: usize, is_last_dir: bool) -> String {
+    let snip_text = ColoredString::italic_dim(
+        format!("(snipped output for {} files with same status)", skip_count).as_str());
+
+    indent_string_snipped(indent_level, snip_text, is_last_dir)
+}
+
+fn format_header(left_column: &str, left_len: usize, right_column: &str, right_len: usize, status_width: usize) -> String {
+    format!("{} {} {}",
+            RightPadding::space(String::from(left_column), left_len),
+            RightPadding::space(String::from(right_column), right_len),
+            RightPadding::space(String::from(HEADER_OPERATION_STATUS), status_width))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATUS_WIDTH: usize = 20;
+
+    fn padder(has_multiple_sources: bool) -> Padder {
+        let mut padder = Padder::new(has_multiple_sources);
+        padder.set_max_source_filename_from_str("IMG-20190128.jpg");
+        padder.set_max_source_path_from_str("D:\\Pics\\IMG-20190128.jpg");
+        padder.set_max_target_path("2019.01.28\\Canon 100D".chars().count());
+        padder
+    }
+
+    fn args(align_file_output: bool) -> Args {
+        let mut args = Args::new().unwrap();
+        args.align_file_output = align_file_output;
+        args
+    }
+
+    /// The visible length of each file followed by its separator, which is the same for all files if they're aligned
+    fn aligned_lengths(files: &[&str], format_separator: impl Fn(&str) -> String) -> Vec<usize> {
+        files
+            .iter()
+            .map(|file| file.chars().count() + get_visible_char_count(&format_separator(file)))
+            .collect()
+    }
+
+    #[test]
+    fn dry_run_layout_aligns_the_columns() {
+        let layout = padder(true).build_dry_run_layout(STATUS_WIDTH, &args(true));
+
+        let target_files = [" └── a.jpg", " │    └── IMG-20190128.jpg"];
+        let lengths = aligned_lengths(&target_files, |file| layout.format_file_separator(file));
+        assert_eq!(lengths[0], lengths[1]);
+
+        let source_paths = ["D:\\a.jpg", "D:\\Pics\\IMG-20190128.jpg"];
+        let lengths = aligned_lengths(&source_paths, |file| layout.format_status_separator(file));
+        assert_eq!(lengths[0], lengths[1]);
+
+        assert_eq!(layout.header().chars().count(), layout.header_separator().chars().count());
+        assert_eq!(get_visible_char_count(&layout.format_date_dir(String::from("[2019.01.28] "))), layout.total_padding_len);
+    }
+
+    #[test]
+    fn dry_run_layout_only_separates_the_columns_if_not_aligned() {
+        let layout = padder(false).build_dry_run_layout(STATUS_WIDTH, &args(false));
+
+        let separator_len = |file| get_visible_char_count(&layout.format_file_separator(file));
+        assert_eq!(separator_len("a.jpg"), separator_len("IMG-20190128.jpg"));
+        assert_eq!(layout.format_date_dir(String::from("[2019.01.28]")), format!("[2019.01.28] {} ", SEPARATOR_OP_STATUS));
+    }
+
+    #[test]
+    fn write_layout_aligns_the_columns() {
+        let layout = padder(false).build_write_layout(STATUS_WIDTH);
+
+        let source_files = ["a.jpg", "IMG-20190128.jpg"];
+        let lengths = aligned_lengths(&source_files, |file| layout.format_file_separator(file));
+        assert_eq!(lengths[0], lengths[1]);
+
+        let target_files = ["2019.01.28\\a.jpg", "2019.01.28\\Canon 100D\\IMG-20190128.jpg"];
+        let lengths = aligned_lengths(&target_files, |file| layout.format_status_separator(file));
+        assert_eq!(lengths[0], lengths[1]);
+
+        assert_eq!(layout.header().chars().count(), layout.header_separator().chars().count());
+        assert_eq!(get_visible_char_count(&layout.format_device_dir(String::from("Canon 100D"), true)), layout.total_padding_len);
+    }
+}
@@ -1,3 +1,44 @@
+pub mod archive;
+pub mod build_info;
+pub mod chaos;
+pub mod checksum;
 pub mod config;
+pub mod device_policy;
+pub mod dedupe;
+pub mod duplicate_policy;
+pub mod document_meta;
 pub mod exif;
+pub mod exif_report;
+pub mod filename_date;
+pub mod filesystem;
+pub mod filetype;
+pub mod geocoding;
+pub mod gpx;
+pub mod heif;
+pub mod humanize;
+pub mod import_index;
+pub mod interrupt;
+pub mod isobmff;
+pub mod journal;
+pub mod jsonl;
+pub mod layout;
+pub mod metrics;
+pub mod operation_log;
+pub mod output;
+pub mod plan;
+pub mod progress;
+pub mod raw;
+#[cfg(feature = "par2")]
+pub mod recovery;
+pub mod script;
+pub mod selftest;
+pub mod snapshot;
+pub mod takeout;
+pub mod target_index;
+pub mod template;
+pub mod text_meta;
+pub mod thumbnail;
 pub mod utils;
+pub mod verify;
+pub mod video_meta;
+pub mod watch;
@@ -1,25 +1,93 @@
+use std::cell::Cell;
 use std::cmp::max;
-use std::collections::{BTreeMap, HashSet};
-use std::ffi::OsString;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
 use std::fmt::Formatter;
 use std::fs::{DirEntry, Metadata};
 use std::iter::FromIterator;
 use std::path::{Path, PathBuf};
-use std::time::{Duration, Instant};
-use std::{fmt, fs, io, thread};
+use std::sync::atomic::{AtomicI32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::{env, fmt, fs, io, process, thread};
 use std::io::Read;
 use std::ops::Add;
 use itertools::Itertools;
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, Utc};
+use filetime::FileTime;
 use filesize::PathExt;
 
+use imgsorter::archive::*;
+use imgsorter::build_info::*;
+use imgsorter::chaos::*;
+use imgsorter::checksum::*;
 use imgsorter::config::*;
+use imgsorter::dedupe::*;
+use imgsorter::duplicate_policy::*;
+use imgsorter::device_policy::*;
+use imgsorter::document_meta::*;
 use imgsorter::exif::*;
+use imgsorter::exif_report::*;
+use imgsorter::filename_date::*;
+use imgsorter::filesystem::*;
+use imgsorter::filetype::*;
+use imgsorter::geocoding::*;
+use imgsorter::humanize::*;
+use imgsorter::import_index::*;
+use imgsorter::interrupt::*;
+use imgsorter::journal::*;
+use imgsorter::layout::*;
+use imgsorter::metrics::*;
+use imgsorter::operation_log::*;
+use imgsorter::output::*;
+use imgsorter::plan::*;
+use imgsorter::progress::*;
+#[cfg(feature = "par2")]
+use imgsorter::recovery::*;
+use imgsorter::script::*;
+use imgsorter::selftest::*;
+use imgsorter::snapshot::*;
+use imgsorter::takeout::*;
+use imgsorter::template::*;
+use imgsorter::thumbnail::*;
 use imgsorter::utils::*;
+use imgsorter::verify::*;
+use imgsorter::video_meta::*;
+use imgsorter::watch::*;
 use OutputColor::*;
 
-pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+// Reasons for skipping files, used to group skipped files in stats
+const SKIP_REASON_TARGET_EXISTS: &str = "target file exists";
+const SKIP_REASON_IDENTICAL_TARGET: &str = "identical file exists in target";
+const SKIP_REASON_DIFFERENT_TARGET: &str = "different file with the same name exists in target";
+const SKIP_REASON_DUPLICATE_SOURCE: &str = "duplicate source file";
+const SKIP_REASON_UNKNOWN_EXTENSION: &str = "unknown extension";
+const SKIP_REASON_CORRUPT: &str = "corrupt file";
+const SKIP_REASON_UNREADABLE: &str = "unreadable file";
+const SKIP_REASON_NEWER_TARGET: &str = "target file is newer or larger";
+const SKIP_REASON_EXIFLESS_TWIN: &str = "EXIF-less copy of another photo";
+const SKIP_REASON_CLOUD_PLACEHOLDER: &str = "cloud file not downloaded";
+const SKIP_REASON_CLOUD_DOWNLOAD_FAILED: &str = "cloud file could not be downloaded";
+const SKIP_REASON_ORPHAN_SIDECAR: &str = "sidecar without its photo or video";
+const SKIP_REASON_ALREADY_IMPORTED: &str = "already imported";
+const SKIP_REASON_FILE_IN_USE: &str = "file is in use";
+
+// The indent of each level of year and month dirs in dry runs, see [Args::date_dir_layout]
+const DATE_PARENT_DIR_INDENT: &str = "  ";
+
+// The RAW image extensions which are read by default, see [get_file_type]
+const RAW_EXTENSIONS: &[&str] = &["nef", "nrw", "cr2", "crw", "arw", "dng", "orf", "rw2", "raf"];
+
+// The number of files with EXIF dates which are enough to learn the order of dates in the file names of a dir
+const MAX_FILENAME_DATE_ORDER_SAMPLES: usize = 20;
+
+// Files which belong together, e.g. Live Photos or RAW+JPEG pairs, have the same source dir and file stem
+type PairKey = (PathBuf, String);
+// The date dir, date and device of the file whose dirs are used for the whole pair
+type PairDir = (String, Option<NaiveDate>, DirEntryType);
 
 
 /// Convenience wrapper over a map holding all files for a given device
@@ -28,6 +96,8 @@ pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 struct DeviceTree {
     file_tree: BTreeMap<DirEntryType, Vec<SupportedFile>>,
     max_dir_path_len: usize,
+    // devices which have a separate subdir inside the date dir, set by [DeviceTree::apply_device_policy]
+    device_subdirs: BTreeSet<DirEntryType>,
 }
 
 impl DeviceTree {
@@ -35,8 +105,69 @@ impl DeviceTree {
         DeviceTree {
             file_tree: BTreeMap::new(),
             max_dir_path_len: 0,
+            device_subdirs: BTreeSet::new(),
         }
     }
+
+    fn file_count(&self) -> usize {
+        self.file_tree.values().map(Vec::len).sum()
+    }
+
+    /// Decide once which devices get a separate device subdir inside the date dir, see [DevicePolicy]
+    fn apply_device_policy(&mut self, device_policy: &DevicePolicy, animations_dir_name: Option<&str>) {
+        let date_device_count = self.file_tree.len();
+        let date_file_count = self.file_count();
+
+        self.device_subdirs = self.file_tree
+            .iter()
+            .filter(|(device_name, files)| device_policy.has_device_subdir(DeviceFiles {
+                is_device_dir: **device_name != DirEntryType::Files,
+                is_animations_dir: matches!(device_name, DirEntryType::Directory(name) if Some(name.as_str()) == animations_dir_name),
+                file_count: files.len(),
+                date_device_count,
+                date_file_count,
+            }))
+            .map(|(device_name, _)| device_name.clone())
+            .collect();
+    }
+
+    /// Move the RAW files of each device into a subdir inside the dir where the other files of the device
+    /// are written, e.g. `2023.05.14/Canon EOS R6/RAW`, or `2023.05.14/RAW` for devices without a subdir.
+    /// This must be called after [Self::apply_device_policy], see [Args::raw_dir_name]
+    fn move_raw_files_to_subdirs(&mut self, date_dir_name: &str, raw_dir_name: &str) {
+        let mut raw_subdirs: BTreeMap<DirEntryType, Vec<SupportedFile>> = BTreeMap::new();
+        for (device_name, files) in self.file_tree.iter_mut() {
+            let (raw_files, others): (Vec<SupportedFile>, Vec<SupportedFile>) =
+                files.drain(..).partition(|file| is_raw_file(file) || is_raw_sidecar_file(file));
+            *files = others;
+            if raw_files.is_empty() {
+                continue;
+            }
+
+            let raw_subdir = if self.device_subdirs.contains(device_name) {
+                Path::new(&device_name.to_string()).join(raw_dir_name).display().to_string()
+            } else {
+                raw_dir_name.to_string()
+            };
+            raw_subdirs.entry(DirEntryType::Directory(raw_subdir)).or_default().extend(raw_files);
+        }
+        self.file_tree.retain(|_, files| !files.is_empty());
+
+        let date_dir_name_len = get_string_char_count(date_dir_name.to_string());
+        for (raw_subdir, raw_files) in raw_subdirs {
+            self.max_dir_path_len = max(
+                self.max_dir_path_len,
+                date_dir_name_len + 1 + get_string_char_count(raw_subdir.to_string()));
+            self.device_subdirs.insert(raw_subdir.clone());
+            self.file_tree.entry(raw_subdir).or_default().extend(raw_files);
+        }
+    }
+
+    /// Check if the files of this device are placed in a separate device subdir
+    /// inside the date dir, or directly inside the date dir
+    fn has_device_subdir(&self, device_name: &DirEntryType) -> bool {
+        self.device_subdirs.contains(device_name)
+    }
 }
 
 /// A wrapper over a map of maps to represent the directory tree as described below.
@@ -58,6 +189,18 @@ impl DeviceTree {
 struct TargetDateDeviceTree {
     dir_tree: BTreeMap<String, DeviceTree>,
     unknown_extensions: HashSet<String>,
+    content_type_warnings: Vec<String>,
+    corrupt_files: Vec<String>,
+    unreadable_files: Vec<String>,
+    probable_duplicates: Vec<String>,
+    duplicate_sources: Vec<String>,
+    // files whose target file already exists, placed in the Duplicates dir for review, see [DuplicateAction::Review]
+    review_targets: Vec<String>,
+    exifless_twins: Vec<String>,
+    renamed_files: Vec<String>,
+    // files skipped because their target file already exists, by whether it has the same contents
+    identical_targets: Vec<String>,
+    different_targets: Vec<String>,
 }
 
 /// Just output a simple list of filenames for now
@@ -74,7 +217,7 @@ impl fmt::Display for TargetDateDeviceTree {
                         let device_files = files
                             .iter()
                             .map(|file| {
-                                format!("{} -> {}", device_dir, file.file_path.display().to_string())
+                                format!("{} -> {}", device_dir, file.file_path.display())
                             })
                             .collect::<Vec<String>>();
                         device_files
@@ -93,6 +236,16 @@ impl TargetDateDeviceTree {
         TargetDateDeviceTree {
             dir_tree: BTreeMap::new(),
             unknown_extensions: HashSet::new(),
+            content_type_warnings: Vec::new(),
+            corrupt_files: Vec::new(),
+            unreadable_files: Vec::new(),
+            probable_duplicates: Vec::new(),
+            duplicate_sources: Vec::new(),
+            review_targets: Vec::new(),
+            exifless_twins: Vec::new(),
+            renamed_files: Vec::new(),
+            identical_targets: Vec::new(),
+            different_targets: Vec::new(),
         }
     }
 
@@ -123,26 +276,46 @@ impl TargetDateDeviceTree {
             all_files_count < args.min_files_per_dir as usize
         };
 
+        let is_named_event_dir = |device_tree: &DeviceTree| {
+            device_tree.file_tree
+                .values()
+                .flatten()
+                .any(|file| file.date.is_some_and(|date| args.find_named_event(date).is_some()))
+        };
+
         let has_oneoff_files = |device_tree: &DeviceTree| {
             _has_single_device(device_tree) && _has_minimum_files(device_tree)
         };
 
         // TODO 5h: this is inefficient, optimize to a single iteration and non-consuming method
         let mut devices_tree: BTreeMap<String, DeviceTree> = BTreeMap::new();
-        let mut oneoffs_tree = DeviceTree::new();
-        let mut oneoff_files: Vec<SupportedFile> = Vec::new();
+        // One-offs are kept inside their album dir, if any, see [Args::album_from_source_folder]
+        let mut oneoff_files: BTreeMap<String, Vec<SupportedFile>> = BTreeMap::new();
 
         self.dir_tree
             .into_iter()
             .for_each(|(device_dir, device_tree)| {
                 // Move single files from the current date dir to a separate dir,
                 // which will be joined again later under a different key
-                if has_oneoff_files(&device_tree) {
+                // Files for review are kept together in their own dirs regardless of count
+                let is_review_dir = device_dir == DEFAULT_CORRUPT_DIR_NAME
+                    || device_dir == DEFAULT_QUARANTINE_DIR_NAME
+                    || device_dir == DEFAULT_PROBABLE_DUPLICATES_DIR_NAME
+                    || device_dir == DEFAULT_DUPLICATES_DIR_NAME;
+                // Named events are configured explicitly, so they're kept even if they have few files
+                if !is_review_dir && !is_named_event_dir(&device_tree) && has_oneoff_files(&device_tree) {
+                    let oneoffs_dir_name = match get_album_dir(&device_dir) {
+                        Some(album_dir) => album_dir.join(&args.oneoffs_dir_name).display().to_string(),
+                        None => args.oneoffs_dir_name.clone(),
+                    };
                     // TODO 6g handle max_len and possible file duplicates
                     device_tree
                         .file_tree
                         .into_iter()
-                        .for_each(|(_, src_files)| oneoff_files.extend(src_files));
+                        .for_each(|(_, src_files)| oneoff_files
+                            .entry(oneoffs_dir_name.clone())
+                            .or_default()
+                            .extend(src_files));
 
                 // keep the existing date-device structure
                 } else {
@@ -150,9 +323,10 @@ impl TargetDateDeviceTree {
                 }
             });
 
-        if !oneoff_files.is_empty() {
-            oneoffs_tree.file_tree.insert(DirEntryType::Files, oneoff_files);
-            devices_tree.insert(args.oneoffs_dir_name.clone(), oneoffs_tree);
+        for (oneoffs_dir_name, files) in oneoff_files {
+            let mut oneoffs_tree = DeviceTree::new();
+            oneoffs_tree.file_tree.insert(DirEntryType::Files, files);
+            devices_tree.insert(oneoffs_dir_name, oneoffs_tree);
         }
 
         self.dir_tree = devices_tree;
@@ -160,855 +334,3171 @@ impl TargetDateDeviceTree {
         self
     }
 
-    /// Find the maximum length of the path string that may be present in the output
-    /// This can only be computed after the tree has been filled with devices and files
-    /// because of the requirement to only create device subdirs if there are at least 2 devices
-    ///   (unless always_create_device_subdirs is true, in which case >1 is ignored)
-    /// The resulting value covers two cases:
-    /// - there's at least one date dir with >1 device subdirs -> target path length will be formed of `date/device_name`
-    /// - there's no date dir with >1 devices -> target path will just include `date`
-    /// Note: this must be called AFTER [Self::isolate_single_images()] so that the length of
-    /// the oneoffs directory can be taken into account, if present
-    fn compute_max_path_len(&mut self, args: &Args) -> usize {
-
-        let has_minimum_required_subdirs = |device_tree: &DeviceTree| {
-            args.always_create_device_subdirs ||
-                device_tree.file_tree.keys().clone().len() > 1
-        };
-
-        let max_date_dir_path_len = &self
-            .dir_tree
-            .iter()
-            // filter only date dirs with at least 2 devices
-            .filter(|(_, device_tree)| has_minimum_required_subdirs(device_tree))
-            // now search all devices for the max path len
-            .map(|(_, device_tree)| device_tree.max_dir_path_len)
-            .max();
-
-        // We also need to account for the presence of a a oneoff directory. This is computed separately
-        // and would not have been considered when setting `max_dir_path_len` during the initial iteration
-        // If present, we compare its length now to the previous max. If not, assume 0 so we can ignore it
-        let has_oneoffs_dir = &self.dir_tree.contains_key(args.oneoffs_dir_name.as_str());
-        let oneoffs_dir_len = if *has_oneoffs_dir {
-            get_string_char_count(args.oneoffs_dir_name.clone())
-        } else {
-            0
-        };
-
-        match *max_date_dir_path_len {
-            Some(max_dir_path_len) =>
-                max(max_dir_path_len, oneoffs_dir_len),
-            None =>
-                // default 10 for the length of date dirs, e.g. 2016.12.29
-                max(10, oneoffs_dir_len)
+    /// Merge date dirs which are at most [Args::event_max_gap_days] apart into a single event dir,
+    /// named after the first and the last date dir, e.g. `2021.08.10 - 2021.08.17`.
+    /// Only date dirs inside the same parent dir are merged, e.g. inside the same album or year dir,
+    /// and only if all of their files have a date, so review dirs and the no date dir are never merged.
+    /// Named events are never merged either, see [Args::named_events]
+    fn merge_events(&mut self, args: &Args) {
+        if args.event_max_gap_days == 0 {
+            return;
         }
-    }
-
-    // Merge two TargetDateDeviceTree
-    fn extend(&mut self, other: TargetDateDeviceTree) {
-        // append devices and files
-        other.dir_tree
-            .into_iter()
-            .for_each(|(other_date_dir, other_device_tree)|{
 
-                match self.dir_tree.get_mut(&other_date_dir) {
-                    // if this date already exists, append devices to it
-                    Some(devicetree_for_this_date) => {
-
-                        other_device_tree.file_tree
-                            .into_iter()
-                            .for_each(| (other_device, other_files) |{
+        // The first and last date of each date dir, grouped by their parent dir
+        let mut date_ranges: BTreeMap<PathBuf, Vec<(NaiveDate, NaiveDate, String)>> = BTreeMap::new();
+        for (date_dir_name, device_tree) in &self.dir_tree {
+            let dates: Option<Vec<NaiveDate>> = device_tree.file_tree
+                .values()
+                .flatten()
+                .map(|file| file.date)
+                .collect();
+            let (first_date, last_date) = match dates.as_ref().and_then(|dates| dates.iter().min().zip(dates.iter().max())) {
+                Some((first_date, last_date)) => (*first_date, *last_date),
+                None => continue,
+            };
 
-                                match devicetree_for_this_date.file_tree.get_mut(&other_device) {
+            // Named events already have their own dir, see [Args::named_events]
+            if args.find_named_event(first_date).is_some() {
+                continue;
+            }
 
-                                    // if this device already exists, append files to it
-                                    Some(all_files_for_this_device) => {
-                                        all_files_for_this_device.extend(other_files);
-                                    },
+            let parent_dir = Path::new(date_dir_name).parent().map(Path::to_path_buf).unwrap_or_default();
+            date_ranges
+                .entry(parent_dir)
+                .or_default()
+                .push((first_date, last_date, date_dir_name.clone()));
+        }
 
-                                    // if this device is not found, just insert the other's entire file list
-                                    None => {
-                                        devicetree_for_this_date.file_tree.insert(other_device, other_files);
-                                    }
-                                }
-                            });
+        for (parent_dir, mut ranges) in date_ranges {
+            ranges.sort();
+
+            // Each event is a list of date dir names, together with the date dir of its last date
+            let mut events: Vec<(Vec<String>, NaiveDate, String)> = Vec::new();
+            for (first_date, last_date, date_dir_name) in ranges {
+                match events.last_mut() {
+                    Some((event_dirs, event_last_date, event_last_dir))
+                    if (first_date - *event_last_date).num_days() <= args.event_max_gap_days as i64 => {
+                        if last_date > *event_last_date {
+                            *event_last_date = last_date;
+                            *event_last_dir = date_dir_name.clone();
+                        }
+                        event_dirs.push(date_dir_name);
                     }
+                    _ => events.push((vec![date_dir_name.clone()], last_date, date_dir_name)),
+                }
+            }
 
-                    // if this date is not found, just insert the other's entire device tree
-                    None => {
-                        self.dir_tree.insert(other_date_dir, other_device_tree);
+            for (event_dirs, _, last_dir) in events.into_iter().filter(|(event_dirs, _, _)| event_dirs.len() > 1) {
+                let dir_name = |date_dir_name: &str| Path::new(date_dir_name)
+                    .file_name()
+                    .map(|file_name| file_name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let event_name = format!("{} - {}", dir_name(&event_dirs[0]), dir_name(&last_dir));
+                let event_dir_name = parent_dir.join(event_name).display().to_string();
+
+                let mut event_tree = DeviceTree::new();
+                for date_dir_name in &event_dirs {
+                    if let Some(device_tree) = self.dir_tree.remove(date_dir_name) {
+                        for (device_name, files) in device_tree.file_tree {
+                            event_tree.file_tree.entry(device_name).or_default().extend(files);
+                        }
                     }
                 }
-            });
-
-        // append devices and files
-        self.unknown_extensions.extend(other.unknown_extensions);
-    }
-}
-
-#[derive(Debug)]
-pub enum DirType {
-    Date,
-    Device,
-}
-
-#[derive(Debug)]
-pub enum FileType {
-    Unknown(String),
-    Image,
-    Video,
-    Audio,
-}
-
-pub enum ConfirmationType {
-    Proceed,
-    DryRun,
-    Cancel,
-    Error,
-}
 
-/// Struct used to keep track of file statuses (i.e. future write restrictions)
-/// when doing dry runs with output compacting enabled
-#[derive(Debug)]
-struct CompactCounter {
-    compacting_threshold: usize,
-    current_status_count: usize,
-    current_status: String,
-    skipped_status_count: usize,
-}
+                // The event dir name is longer than the names of the date dirs it replaces
+                let event_dir_name_len = get_string_char_count(event_dir_name.clone());
+                event_tree.max_dir_path_len = event_tree.file_tree
+                    .keys()
+                    .map(|device_name| event_dir_name_len + 1 + get_string_char_count(device_name.to_string()))
+                    .max()
+                    .unwrap_or(event_dir_name_len);
 
-impl CompactCounter {
-    fn new(compacting_threshold: usize) -> CompactCounter {
-        CompactCounter {
-            compacting_threshold,
-            current_status_count: 0,
-            current_status: "".to_owned(),
-            skipped_status_count: 0,
+                self.dir_tree.insert(event_dir_name, event_tree);
+            }
         }
     }
 
-    fn reset_status(&mut self, new_status: String) {
-        self.current_status_count = 0;
-        self.current_status = new_status;
-        self.skipped_status_count = 0;
-    }
+    /// Move the video of each Live Photo, i.e. a HEIC or JPEG photo and a MOV or MP4 video with the same name
+    /// (except the extension) in the same source dir, into the date and device dir of the photo.
+    /// The video usually has no device and its creation time may be missing, so it would otherwise
+    /// be sorted by its modified date, possibly into another dir than the photo
+    fn keep_live_photos_together(&mut self) {
+        let is_still = |file: &SupportedFile| matches!(file.file_type, FileType::Image)
+            && matches!(file.get_extension_lowercase().as_str(), "heic" | "heif" | "jpg" | "jpeg");
+        let is_companion = |file: &SupportedFile| matches!(file.file_type, FileType::Video | FileType::Animation)
+            && matches!(file.get_extension_lowercase().as_str(), "mov" | "mp4");
+
+        // The dirs of each photo which can have a companion video
+        let stills: HashMap<PairKey, PairDir> = self.dir_tree
+            .values()
+            .flat_map(|device_tree| device_tree.file_tree.values().flatten())
+            .filter(|file| !file.is_corrupt && is_still(file))
+            .filter_map(|file| get_pair_key(file).map(|key| (key, get_pair_dir(file))))
+            .collect();
 
-    fn inc_current_status(&mut self) {
-        self.current_status_count += 1;
+        self.move_to_pair_dirs(&stills, is_companion);
     }
 
-    fn inc_skipped_status(&mut self) {
-        self.skipped_status_count += 1;
-    }
+    /// Move both files of each RAW+JPEG pair, i.e. a RAW and a JPEG photo with the same name
+    /// (except the extension) in the same source dir, into the same date and device dir.
+    /// The JPEG's dir is used, unless only the RAW file has an EXIF capture time, e.g. if the
+    /// camera wrote an EXIF-less preview JPEG, or if the RAW file couldn't be read
+    fn keep_raw_jpeg_pairs_together(&mut self) {
+        let is_pair_file = |file: &SupportedFile| !file.is_corrupt && matches!(file.file_type, FileType::Image);
+
+        let mut jpeg_files: HashMap<PairKey, &SupportedFile> = HashMap::new();
+        let mut raw_files: HashMap<PairKey, &SupportedFile> = HashMap::new();
+        for file in self.dir_tree.values().flat_map(|device_tree| device_tree.file_tree.values().flatten()) {
+            match get_pair_key(file) {
+                Some(key) if is_pair_file(file) && is_jpeg_file(file) => { jpeg_files.insert(key, file); }
+                Some(key) if is_pair_file(file) && is_raw_file(file) => { raw_files.insert(key, file); }
+                _ => {}
+            }
+        }
 
-    fn has_reached_threshold(&self) -> bool {
-        self.current_status_count >= self.compacting_threshold
-    }
+        let pair_dirs: HashMap<PairKey, PairDir> = jpeg_files
+            .into_iter()
+            .filter_map(|(key, jpeg_file)| {
+                let raw_file = raw_files.get(&key)?;
+                let kept_file = if jpeg_file.capture_time.is_none() && raw_file.capture_time.is_some() {
+                    raw_file
+                } else {
+                    jpeg_file
+                };
+                Some((key, get_pair_dir(kept_file)))
+            })
+            .collect();
 
-    fn has_skipped_statuses(&self) -> bool {
-        self.skipped_status_count > 0
+        self.move_to_pair_dirs(&pair_dirs, |file| is_pair_file(file) && (is_jpeg_file(file) || is_raw_file(file)));
     }
 
-    fn is_same_status(&self, new_status: &str) -> bool {
-        self.current_status == *new_status
-    }
-}
+    /// Move each sidecar file into the date and device dir of the photo or video it belongs to,
+    /// i.e. the file with the same name in the same source dir, either with or without its extension,
+    /// e.g. `IMG_0001.CR2.xmp` or `IMG_0001.xmp` for `IMG_0001.CR2`. Photos are preferred over videos
+    /// with the same name. Sidecars without a photo or video are skipped, since they can't be used alone
+    fn keep_sidecars_together(&mut self) {
+        let is_sidecar = |file: &SupportedFile| matches!(file.file_type, FileType::Sidecar);
+        let is_image = |file: &SupportedFile| matches!(file.file_type, FileType::Image);
+
+        let mut media_by_name: HashMap<PairKey, &SupportedFile> = HashMap::new();
+        let mut media_by_stem: HashMap<PairKey, &SupportedFile> = HashMap::new();
+        for file in self.dir_tree.values().flat_map(|device_tree| device_tree.file_tree.values().flatten()) {
+            if is_sidecar(file) {
+                continue;
+            }
+            let source_dir = match file.file_path.parent() {
+                Some(source_dir) => source_dir.to_path_buf(),
+                None => continue,
+            };
+            let name_key = (source_dir, file.file_name.to_string_lossy().to_lowercase());
+            for (media_files, key) in [(&mut media_by_name, Some(name_key)), (&mut media_by_stem, get_pair_key(file))] {
+                if let Some(key) = key {
+                    let existing = media_files.entry(key).or_insert(file);
+                    if !is_image(existing) && is_image(file) {
+                        *existing = file;
+                    }
+                }
+            }
+        }
 
-#[derive(Debug)]
-pub struct FileStats {
-    files_count_total: i32,
-    file_size_total: u64,
-    img_moved: i32,
-    img_copied: i32,
-    img_skipped: i32,
-    vid_moved: i32,
-    vid_copied: i32,
-    vid_skipped: i32,
-    aud_moved: i32,
-    aud_copied: i32,
-    aud_skipped: i32,
-    unknown_skipped: i32,
-    // source dirs which are skipped from reading
-    dirs_ignored: i32,
-    date_dirs_total: i32,
-    date_dirs_created: i32,
-    device_dirs_total: i32,
-    device_dirs_created: i32,
-    error_file_create: i32,
-    error_file_delete: i32,
-    error_date_dir_create: i32,
-    error_device_dir_create: i32,
-    time_fetch_files: Duration,
-    time_fetch_dirs: Duration,
-    time_parse_files: Duration,
-    time_write_files: Duration,
-    time_total: Duration,
-}
+        let mut pair_dirs: HashMap<PairKey, PairDir> = HashMap::new();
+        let mut orphan_keys: HashSet<PairKey> = HashSet::new();
+        for file in self.dir_tree.values().flat_map(|device_tree| device_tree.file_tree.values().flatten()) {
+            let key = match get_pair_key(file) {
+                Some(key) if is_sidecar(file) => key,
+                _ => continue,
+            };
+            let stem_key = Path::new(&key.1)
+                .file_stem()
+                .map(|stem| (key.0.clone(), stem.to_string_lossy().to_string()));
+            let media_file = media_by_name.get(&key)
+                .or_else(|| media_by_stem.get(&key))
+                .or_else(|| stem_key.and_then(|stem_key| media_by_stem.get(&stem_key)));
+            match media_file {
+                Some(media_file) => { pair_dirs.insert(key, get_pair_dir(media_file)); }
+                None => { orphan_keys.insert(key); }
+            }
+        }
 
-impl FileStats {
-    pub fn new() -> FileStats {
-        FileStats {
-            files_count_total: 0,
-            file_size_total: 0,
-            img_moved: 0,
-            img_copied: 0,
-            img_skipped: 0,
-            vid_moved: 0,
-            vid_copied: 0,
-            vid_skipped: 0,
-            aud_moved: 0,
-            aud_copied: 0,
-            aud_skipped: 0,
-            unknown_skipped: 0,
-            dirs_ignored: 0,
-            date_dirs_total: 0,
-            date_dirs_created: 0,
-            device_dirs_total: 0,
-            device_dirs_created: 0,
-            error_file_create: 0,
-            error_file_delete: 0,
-            error_date_dir_create: 0,
-            error_device_dir_create: 0,
-            time_fetch_files: Duration::new(0, 0),
-            time_fetch_dirs: Duration::new(0, 0),
-            time_parse_files: Duration::new(0, 0),
-            time_write_files: Duration::new(0, 0),
-            time_total: Duration::new(0, 0),
-        }
-    }
-
-    pub fn inc_files_total(&mut self, count: usize) { self.files_count_total += count as i32}
-    pub fn inc_files_size(&mut self, size: u64) { self.file_size_total += size }
-    fn inc_img_moved(&mut self) { self.img_moved += 1 }
-    fn inc_img_copied(&mut self) { self.img_copied += 1 }
-    fn inc_img_skipped(&mut self) { self.img_skipped += 1 }
-    fn inc_vid_moved(&mut self) { self.vid_moved += 1 }
-    fn inc_vid_copied(&mut self) { self.vid_copied += 1 }
-    fn inc_vid_skipped(&mut self) { self.vid_skipped += 1 }
-    fn inc_aud_moved(&mut self) { self.aud_moved += 1 }
-    fn inc_aud_copied(&mut self) { self.aud_copied += 1 }
-    fn inc_aud_skipped(&mut self) { self.aud_skipped += 1 }
-    pub fn inc_unknown_skipped(&mut self) { self.unknown_skipped += 1 }
-    pub fn inc_dirs_ignored(&mut self) { self.dirs_ignored += 1 }
-    fn inc_date_dirs_total(&mut self) { self.date_dirs_total += 1 }
-    fn inc_date_dirs_created(&mut self) { self.date_dirs_created += 1 }
-    fn inc_device_dirs_total(&mut self) { self.device_dirs_total += 1 }
-    fn inc_device_dirs_created(&mut self) { self.device_dirs_created += 1 }
-    pub fn inc_error_file_create(&mut self) { self.error_file_create += 1 }
-    pub fn inc_error_file_delete(&mut self) { self.error_file_delete += 1 }
-    pub fn inc_error_date_dir_create(&mut self) { self.error_date_dir_create += 1 }
-    pub fn inc_error_device_dir_create(&mut self) { self.error_device_dir_create += 1 }
-    pub fn set_time_fetch_files(&mut self, elapsed: Duration) { self.time_fetch_files = elapsed }
-    pub fn set_time_fetch_dirs(&mut self, elapsed: Duration) { self.time_fetch_dirs = elapsed }
-    pub fn set_time_parse_files(&mut self, elapsed: Duration) { self.time_parse_files = elapsed }
-    pub fn set_time_write_files(&mut self, elapsed: Duration) { self.time_write_files = elapsed }
-    pub fn set_time_total(&mut self, elapsed: Duration) { self.time_total = elapsed }
-
-    pub fn inc_dir_total_by_type(&mut self, dir: &DirType) {
-        match dir {
-            DirType::Date => self.inc_date_dirs_total(),
-            DirType::Device => self.inc_device_dirs_total(),
+        for file in self.dir_tree.values_mut().flat_map(|device_tree| device_tree.file_tree.values_mut().flatten()) {
+            if is_sidecar(file) && get_pair_key(file).is_some_and(|key| orphan_keys.contains(&key)) {
+                file.skip_reason = Some(SKIP_REASON_ORPHAN_SIDECAR.to_string());
+            }
         }
+
+        self.move_to_pair_dirs(&pair_dirs, is_sidecar);
     }
 
-    pub fn inc_dir_created_by_type(&mut self, dir: &DirType) {
-        match dir {
-            DirType::Date => self.inc_date_dirs_created(),
-            DirType::Device => self.inc_device_dirs_created(),
+    /// Move the companion files which have the same pair key as another file into the
+    /// date and device dir of that file, see [Self::keep_live_photos_together]
+    fn move_to_pair_dirs(&mut self, pair_dirs: &HashMap<PairKey, PairDir>, is_companion: impl Fn(&SupportedFile) -> bool) {
+        if pair_dirs.is_empty() {
+            return;
         }
-    }
 
-    pub fn inc_error_dir_create_by_type(&mut self, dir: &DirType) {
-        match dir {
-            DirType::Date => self.inc_error_date_dir_create(),
-            DirType::Device => self.inc_error_device_dir_create(),
+        let pair_dir_of = |file: &SupportedFile| {
+            Some(file)
+                .filter(|file| is_companion(file))
+                .and_then(get_pair_key)
+                .and_then(|key| pair_dirs.get(&key))
+                .filter(|(date_str, _, device_name)| *date_str != file.date_str || *device_name != file.device_name)
+        };
+
+        // Move the companion files out of their own date and device dirs
+        let mut companion_files: Vec<SupportedFile> = Vec::new();
+        for device_tree in self.dir_tree.values_mut() {
+            for files in device_tree.file_tree.values_mut() {
+                let (companions, others): (Vec<SupportedFile>, Vec<SupportedFile>) =
+                    files.drain(..).partition(|file| pair_dir_of(file).is_some());
+                *files = others;
+                companion_files.extend(companions);
+            }
+            device_tree.file_tree.retain(|_, files| !files.is_empty());
         }
-    }
+        self.dir_tree.retain(|_, device_tree| !device_tree.file_tree.is_empty());
 
-    pub fn inc_copied_by_type(&mut self, file: &SupportedFile) {
-        match file.file_type {
-            FileType::Image => self.inc_img_copied(),
-            FileType::Video => self.inc_vid_copied(),
-            FileType::Audio => self.inc_aud_copied(),
-            // don't record any stats for this, shouldn't get one here anyway
-            FileType::Unknown(_) => (),
+        // The date and device dirs of the paired files already exist, so their path lengths don't change
+        for mut file in companion_files {
+            let (date_str, date, device_name) = match pair_dir_of(&file) {
+                Some(pair_dir) => pair_dir.clone(),
+                None => continue,
+            };
+            file.date_str = date_str.clone();
+            file.date = date;
+            file.device_name = device_name.clone();
+            self.dir_tree
+                .entry(date_str)
+                .or_insert_with(DeviceTree::new)
+                .file_tree
+                .entry(device_name)
+                .or_default()
+                .push(file);
         }
     }
 
-    pub fn inc_moved_by_type(&mut self, file: &SupportedFile) {
-        match file.file_type {
-            FileType::Image => self.inc_img_moved(),
-            FileType::Video => self.inc_vid_moved(),
-            FileType::Audio => self.inc_aud_moved(),
-            // don't record any stats for this, shouldn't get one here anyway
-            FileType::Unknown(_) => (),
+    /// Find photos which exist as both a HEIC and a JPEG file, i.e. files with the same name
+    /// (except the extension) and the same date, and mark the one which should not be kept
+    /// as skipped, according to [Args::heic_jpeg_pairs]
+    fn skip_heic_jpeg_pairs(&mut self, args: &Args) {
+        let extension_to_skip: &[&str] = match args.heic_jpeg_pairs {
+            HeicJpegPairPolicy::KeepBoth => return,
+            HeicJpegPairPolicy::KeepHeic => &["jpg", "jpeg"],
+            HeicJpegPairPolicy::KeepJpeg => &["heic", "heif"],
+        };
+
+        let pair_key = |file: &SupportedFile| {
+            file.file_path
+                .file_stem()
+                .map(|stem| (file.date_str.clone(), stem.to_string_lossy().to_lowercase()))
+        };
+
+        let all_files = || self.dir_tree.values().flat_map(|device_tree| device_tree.file_tree.values().flatten());
+
+        let mut heic_files: HashSet<(String, String)> = HashSet::new();
+        let mut jpeg_files: HashSet<(String, String)> = HashSet::new();
+        for file in all_files() {
+            match (file.get_extension_lowercase().as_str(), pair_key(file)) {
+                ("heic" | "heif", Some(key)) => { heic_files.insert(key); }
+                ("jpg" | "jpeg", Some(key)) => { jpeg_files.insert(key); }
+                _ => {}
+            }
+        }
+
+        let pairs: HashSet<&(String, String)> = heic_files.intersection(&jpeg_files).collect();
+        if pairs.is_empty() {
+            return;
         }
+
+        self.dir_tree
+            .values_mut()
+            .flat_map(|device_tree| device_tree.file_tree.values_mut().flatten())
+            .filter(|file| file.skip_reason.is_none())
+            .filter(|file| extension_to_skip.contains(&file.get_extension_lowercase().as_str()))
+            .filter(|file| pair_key(file).is_some_and(|key| pairs.contains(&key)))
+            .for_each(|file| {
+                let kept_type = if let HeicJpegPairPolicy::KeepHeic = args.heic_jpeg_pairs { "HEIC" } else { "JPEG" };
+                file.skip_reason = Some(format!("{} version exists", kept_type));
+            });
     }
 
-    pub fn inc_skipped_by_type(&mut self, file: &SupportedFile) {
-        match file.file_type {
-            FileType::Image => self.inc_img_skipped(),
-            FileType::Video => self.inc_vid_skipped(),
-            FileType::Audio => self.inc_aud_skipped(),
-            // don't record any stats for this, shouldn't get one here anyway
-            FileType::Unknown(_) => (),
+    /// Mark the files with the same contents as a file imported by an earlier run as skipped,
+    /// see [Args::import_index_path]. This runs before the files are paired or grouped,
+    /// so that the files which were already imported don't affect the others
+    fn skip_imported_files(&mut self, args: &Args) {
+        if args.import_index.is_empty() {
+            return;
         }
+
+        self.dir_tree
+            .values_mut()
+            .flat_map(|device_tree| device_tree.file_tree.values_mut().flatten())
+            .filter(|file| file.skip_reason.is_none())
+            .filter(|file| args.import_index.contains(&file.file_path, file.metadata.len()))
+            .for_each(|file| file.skip_reason = Some(SKIP_REASON_ALREADY_IMPORTED.to_string()));
     }
 
-    pub fn padded_color_if_non_zero(err_stat: i32, level: OutputColor, padding_width: usize) -> String {
+    /// Find photos without EXIF data which have the same dimensions as a photo with EXIF data
+    /// from the same date and are not larger than it, i.e. most likely a copy of the original
+    /// re-shared via a messaging app, and mark them as skipped, see [Args::skip_exifless_twins]
+    fn skip_exifless_twins(&mut self, args: &Args) {
+        if !args.skip_exifless_twins {
+            return;
+        }
 
-        let padded_int = LeftPadding::space(err_stat.to_string(), padding_width);
+        let is_image = |file: &SupportedFile| matches!(file.file_type, FileType::Image) && file.skip_reason.is_none();
 
-        if err_stat > 0 {
-            match level {
-                OutputColor::Error   => ColoredString::red(padded_int.as_str()),
-                OutputColor::Warning => ColoredString::orange(padded_int.as_str()),
-                Neutral              => ColoredString::bold_white(padded_int.as_str()),
-                OutputColor::Good    => ColoredString::green(padded_int.as_str()),
+        let exifless_twins = &mut self.exifless_twins;
+        for device_tree in self.dir_tree.values_mut() {
+            // The originals which can still be matched with a twin: (dimensions, size, path)
+            let mut originals: Vec<((u32, u32), u64, PathBuf)> = device_tree
+                .file_tree
+                .iter()
+                .filter(|(device_dir, _)| matches!(device_dir,
+                    DirEntryType::Directory(device) if device != DEFAULT_UNKNOWN_DEVICE_DIR_NAME))
+                .flat_map(|(_, files)| files.iter())
+                .filter(|file| is_image(file) && file.capture_time.is_some())
+                .filter_map(|file| file.dimensions
+                    .or_else(|| read_image_dimensions(&file.file_path))
+                    .map(|dimensions| (dimensions, file.metadata.len(), file.file_path.clone())))
+                .collect();
+
+            if originals.is_empty() {
+                continue;
             }
-        } else {
-            padded_int
+
+            device_tree
+                .file_tree
+                .values_mut()
+                .flatten()
+                .filter(|file| is_image(file) && file.capture_time.is_none())
+                .for_each(|file| {
+                    let dimensions = match read_image_dimensions(&file.file_path) {
+                        Some(dimensions) => dimensions,
+                        None => return,
+                    };
+
+                    // Each original can only have a single twin
+                    let file_size = file.metadata.len();
+                    if let Some(index) = originals
+                        .iter()
+                        .position(|(original_dimensions, original_size, _)|
+                            *original_dimensions == dimensions && file_size <= *original_size)
+                    {
+                        let (_, _, original_path) = originals.remove(index);
+                        exifless_twins.push(format!("{} (EXIF-less copy of {})",
+                            file.file_path.display(), original_path.display()));
+                        file.skip_reason = Some(SKIP_REASON_EXIFLESS_TWIN.to_string());
+                    }
+                });
         }
     }
 
-    pub fn color_if_non_zero(err_stat: i32, level: OutputColor) -> String {
-        if err_stat > 0 {
-            match level {
-                OutputColor::Error   => ColoredString::red(err_stat.to_string().as_str()),
-                OutputColor::Warning => ColoredString::orange(err_stat.to_string().as_str()),
-                Neutral              => ColoredString::bold_white(err_stat.to_string().as_str()),
-                OutputColor::Good    => ColoredString::green(err_stat.to_string().as_str()),
-            }
-        } else {
-            err_stat.to_string()
+    /// Find photos with the same device, capture time and dimensions, which are most likely
+    /// copies of each other, even if their contents are different (e.g. re-encoded when shared).
+    /// For each group, the largest file is kept in place, while the others are moved to a
+    /// separate dir for review, see [Args::mark_probable_duplicates]
+    fn route_probable_duplicates(&mut self, args: &Args) {
+        if !args.mark_probable_duplicates {
+            return;
         }
-    }
 
-    pub fn print_stats(&self, args: &Args) {
-        // file count padding
-        let f_max_digits = get_integer_char_count(self.files_count_total);
-        // dir count padding; each should be half of the total file count width
-        let d_max_digits = ((f_max_digits * 3) as f32 / 2_f32).ceil() as usize;
+        type CaptureKey = (String, String, Option<String>, Option<(u32, u32)>);
 
-        let write_general_stats = || {
-            format!(
-"──────────────────────────────────────────────
-Total files:                  {total} ({size})
-──────────────────────────────────────────────
-Images moved|copied|skipped:  │{p_img_move}│{p_img_copy}│{p_img_skip}│
-Videos moved|copied|skipped:  │{p_vid_move}│{p_vid_copy}│{p_vid_skip}│
-Audios moved|copied|skipped:  │{p_aud_move}│{p_aud_copy}│{p_aud_skip}│
-──────────────────────────────────────────────
-Date   folders created|total: │{date_d_create}│{date_d_total}│
-Device folders created|total: │{devc_d_create}│{devc_d_total}│
-Source folders ignored:       {dir_ignore}
-Unknown files skipped:        {f_skip}
-File delete errors:           {fd_err}
-File create errors:           {fc_err}
-Date folders create errors:   {date_c_err}
-Device folders create errors: {devc_c_err}
-──────────────────────────────────────────────
-Time fetching folders:        {tfetch_dir} sec
-Time fetching files:          {tfetch_file} sec
-Time parsing files:           {tparse_file} sec
-Time writing files:           {twrite_file} sec
-──────────────────────────────────────────────
-Total time taken:             {t_total} sec
-──────────────────────────────────────────────",
-            total=FileStats::color_if_non_zero(self.files_count_total, Neutral),
-            size=ColoredString::bold_white(get_file_size_string(self.file_size_total).as_str()),
-
-            p_img_move=FileStats::padded_color_if_non_zero(self.img_moved, Neutral, f_max_digits),
-            p_img_copy=FileStats::padded_color_if_non_zero(self.img_copied, Neutral, f_max_digits),
-            p_img_skip=FileStats::padded_color_if_non_zero(self.img_skipped, Warning, f_max_digits),
-
-            p_vid_move=FileStats::padded_color_if_non_zero(self.vid_moved, Neutral, f_max_digits),
-            p_vid_copy=FileStats::padded_color_if_non_zero(self.vid_copied, Neutral, f_max_digits),
-            p_vid_skip=FileStats::padded_color_if_non_zero(self.vid_skipped, Warning, f_max_digits),
-
-            p_aud_move=FileStats::padded_color_if_non_zero(self.aud_moved, Neutral, f_max_digits),
-            p_aud_copy=FileStats::padded_color_if_non_zero(self.aud_copied, Neutral, f_max_digits),
-            p_aud_skip=FileStats::padded_color_if_non_zero(self.aud_skipped, Warning, f_max_digits),
-
-            date_d_create=FileStats::padded_color_if_non_zero(self.date_dirs_created, Neutral, d_max_digits),
-            date_d_total=FileStats::padded_color_if_non_zero(self.date_dirs_total, Neutral, d_max_digits),
-
-            devc_d_create=FileStats::padded_color_if_non_zero(self.device_dirs_created, Neutral, d_max_digits),
-            devc_d_total=FileStats::padded_color_if_non_zero(self.device_dirs_total, Neutral, d_max_digits),
-
-            dir_ignore=FileStats::color_if_non_zero(self.dirs_ignored, Warning),
-
-            f_skip=FileStats::color_if_non_zero(self.unknown_skipped, Warning),
-
-            fd_err=FileStats::color_if_non_zero(self.error_file_delete, Error),
-            fc_err=FileStats::color_if_non_zero(self.error_file_create, Error),
-            date_c_err=FileStats::color_if_non_zero(self.error_date_dir_create, Error),
-            devc_c_err=FileStats::color_if_non_zero(self.error_device_dir_create, Error),
-
-            tfetch_dir=ColoredString::bold_white(format!("{}:{}",
-                self.time_fetch_dirs.as_secs(),
-                LeftPadding::zeroes3(self.time_fetch_dirs.subsec_millis())).as_str()),
-            tfetch_file=ColoredString::bold_white(format!("{}:{}",
-                self.time_fetch_files.as_secs(),
-                LeftPadding::zeroes3(self.time_fetch_files.subsec_millis())).as_str()),
-            tparse_file=ColoredString::bold_white(format!("{}:{}",
-                self.time_parse_files.as_secs(),
-                LeftPadding::zeroes3(self.time_parse_files.subsec_millis())).as_str()),
-            twrite_file=ColoredString::bold_white(format!("{}:{}",
-                self.time_write_files.as_secs(),
-                LeftPadding::zeroes3(self.time_write_files.subsec_millis())).as_str()),
-            t_total=ColoredString::bold_white(format!("{}:{}",
-                self.time_total.as_secs(),
-                LeftPadding::zeroes3(self.time_total.subsec_millis())).as_str()),
-        )}; // end write_general_stats
+        // Only files with both the device and the capture time read from EXIF can be compared.
+        // Burst shots are taken in the same second, so they're only told apart by the fraction of the second
+        let capture_key = |file: &SupportedFile| -> Option<CaptureKey> {
+            match (&file.device_name, &file.capture_time) {
+                (DirEntryType::Directory(device), Some(capture_time))
+                    if file.skip_reason.is_none() && device != DEFAULT_UNKNOWN_DEVICE_DIR_NAME =>
+                    Some((device.clone(), capture_time.clone(), file.capture_subsec.clone(), file.dimensions)),
+                _ => None,
+            }
+        };
 
-        let dryrun_general_stats = || {
-            format!(
-"––––––––––––––––––––––––––––––––––––––––––––––––––––––
-Total files:                    {total} ({size})
-––––––––––––––––––––––––––––––––––––––––––––––––––––––
-Images to move|copy|skip:       │{p_img_move}│{p_img_copy}│{p_img_skip}│
-Videos to move|copy|skip:       │{p_vid_move}│{p_vid_copy}│{p_vid_skip}│
-Audios to move|copy|skip:       │{p_aud_move}│{p_aud_copy}│{p_aud_skip}│
-––––––––––––––––––––––––––––––––––––––––––––––––––––––
-Date folders   to create|total: │{date_d_create}│{date_d_total}│
-Device folders to create|total: │{devc_d_create}│{devc_d_total}│
-––––––––––––––––––––––––––––––––––––––––––––––––––––––
-Source folders to skip:         {dir_ignore}
-Unknown files to skip:          {f_skip}
-File delete errors:             n/a
-File create errors:             n/a
-Date folders create errors:     n/a
-Device folders create errors:   n/a
------------------------------------------------
-Time fetching folders:          {tfetch_dir} sec
-Time fetching files:            {tfetch_file} sec
-Time parsing files:             {tparse_file} sec
-Time printing files:            {twrite_file} sec
-––––––––––––––––––––––––––––––––––––––––––––––––––––––
-Total time taken:               {t_total} sec
-––––––––––––––––––––––––––––––––––––––––––––––––––––––",
-            total=FileStats::color_if_non_zero(self.files_count_total, Neutral),
-            size=ColoredString::bold_white(get_file_size_string(self.file_size_total).as_str()),
-
-            p_img_move=FileStats::padded_color_if_non_zero(self.img_moved, Neutral, f_max_digits),
-            p_img_copy=FileStats::padded_color_if_non_zero(self.img_copied, Neutral, f_max_digits),
-            p_img_skip=FileStats::padded_color_if_non_zero(self.img_skipped, Warning, f_max_digits),
-
-            p_vid_move=FileStats::padded_color_if_non_zero(self.vid_moved, Neutral, f_max_digits),
-            p_vid_copy=FileStats::padded_color_if_non_zero(self.vid_copied, Neutral, f_max_digits),
-            p_vid_skip=FileStats::padded_color_if_non_zero(self.vid_skipped, Warning, f_max_digits),
-
-            p_aud_move=FileStats::padded_color_if_non_zero(self.aud_moved, Neutral, f_max_digits),
-            p_aud_copy=FileStats::padded_color_if_non_zero(self.aud_copied, Neutral, f_max_digits),
-            p_aud_skip=FileStats::padded_color_if_non_zero(self.aud_skipped, Warning, f_max_digits),
-
-            date_d_create=FileStats::padded_color_if_non_zero(self.date_dirs_created, Neutral, d_max_digits),
-            date_d_total=FileStats::padded_color_if_non_zero(self.date_dirs_total, Neutral, d_max_digits),
-
-            devc_d_create=FileStats::padded_color_if_non_zero(self.device_dirs_created, Neutral, d_max_digits),
-            devc_d_total=FileStats::padded_color_if_non_zero(self.device_dirs_total, Neutral, d_max_digits),
-
-            dir_ignore=FileStats::color_if_non_zero(self.dirs_ignored, Warning),
-
-            f_skip=FileStats::color_if_non_zero(self.unknown_skipped, Warning),
-
-            tfetch_dir=ColoredString::bold_white(format!("{}:{}",
-                self.time_fetch_dirs.as_secs(),
-                LeftPadding::zeroes3(self.time_fetch_dirs.subsec_millis())).as_str()),
-            tfetch_file=ColoredString::bold_white(format!("{}:{}",
-                self.time_fetch_files.as_secs(),
-                LeftPadding::zeroes3(self.time_fetch_files.subsec_millis())).as_str()),
-            tparse_file=ColoredString::bold_white(format!("{}:{}",
-                self.time_parse_files.as_secs(),
-                LeftPadding::zeroes3(self.time_parse_files.subsec_millis())).as_str()),
-            twrite_file=ColoredString::bold_white(format!("{}:{}",
-                self.time_write_files.as_secs(),
-                LeftPadding::zeroes3(self.time_write_files.subsec_millis())).as_str()),
-            t_total=ColoredString::bold_white(format!("{}:{}",
-                self.time_total.as_secs(),
-                LeftPadding::zeroes3(self.time_total.subsec_millis())).as_str()),
-        )}; // end dryrun_general_stats
+        // Find the file to keep for each group: the largest one, or the first by path if equal
+        let mut kept_files: HashMap<CaptureKey, (u64, PathBuf)> = HashMap::new();
+        let mut group_sizes: HashMap<CaptureKey, usize> = HashMap::new();
+        self.dir_tree
+            .values()
+            .flat_map(|device_tree| device_tree.file_tree.values().flatten())
+            .for_each(|file| {
+                if let Some(key) = capture_key(file) {
+                    *group_sizes.entry(key.clone()).or_insert(0) += 1;
+                    let candidate = (file.metadata.len(), file.file_path.clone());
+                    let kept = kept_files.entry(key).or_insert_with(|| candidate.clone());
+                    if candidate.0 > kept.0 || (candidate.0 == kept.0 && candidate.1 < kept.1) {
+                        *kept = candidate;
+                    }
+                }
+            });
 
-        // Print dry run stats
-        if args.dry_run {
-            println!("{}", dryrun_general_stats());
+        let duplicate_of = |file: &SupportedFile| -> Option<PathBuf> {
+            capture_key(file)
+                .filter(|key| group_sizes.get(key).copied().unwrap_or(0) > 1)
+                .and_then(|key| kept_files.get(&key))
+                .filter(|(_, kept_path)| *kept_path != file.file_path)
+                .map(|(_, kept_path)| kept_path.clone())
+        };
 
-        // Print actual stats and other errors encountered when writing files
-        } else {
-            println!("{}", write_general_stats());
+        // Move all duplicates out of their date and device dirs
+        let mut duplicate_files: Vec<SupportedFile> = Vec::new();
+        let mut duplicate_descriptions: Vec<String> = Vec::new();
+        for device_tree in self.dir_tree.values_mut() {
+            for files in device_tree.file_tree.values_mut() {
+                let (duplicates, others): (Vec<SupportedFile>, Vec<SupportedFile>) =
+                    files.drain(..).partition(|file| duplicate_of(file).is_some());
+                *files = others;
+
+                for file in duplicates {
+                    if let Some(kept_path) = duplicate_of(&file) {
+                        duplicate_descriptions.push(format!("{} (probable duplicate of {})",
+                            file.file_path.display(), kept_path.display()));
+                    }
+                    duplicate_files.push(file);
+                }
+            }
+            device_tree.file_tree.retain(|_, files| !files.is_empty());
+        }
+        self.probable_duplicates.extend(duplicate_descriptions);
+        self.dir_tree.retain(|_, device_tree| !device_tree.file_tree.is_empty());
 
-            if self.files_count_total == self.unknown_skipped {
-                println!("{}", ColoredString::orange("No supported files found in source folder."))
+        if !duplicate_files.is_empty() {
+            let mut duplicates_tree = DeviceTree::new();
+            duplicates_tree.file_tree.insert(DirEntryType::Files, duplicate_files);
+            self.dir_tree.insert(DEFAULT_PROBABLE_DUPLICATES_DIR_NAME.to_string(), duplicates_tree);
+        }
+    }
+
+    /// Rename the files written to the target dir according to [Args::file_name_template],
+    /// e.g. `IMG_0001.jpg` to `2023-05-14_103000_Pixel 5_IMG_0001.jpg`, keeping their extensions.
+    /// Sidecars get the new name of their photo or video, matched like in [Self::keep_sidecars_together].
+    /// This must be called before [Self::rename_name_collisions], which also renames the files
+    /// which get the same name from this template
+    fn apply_file_name_template(&mut self, args: &Args) {
+        let template = match &args.file_name_template {
+            Some(template) => template,
+            None => return,
+        };
+        let is_sidecar = |file: &SupportedFile| matches!(file.file_type, FileType::Sidecar);
+
+        // The new name of each photo or video, by its full name, and its new stem, by its stem,
+        // preferring images if a video has the same stem, e.g. for Live Photos
+        let mut new_names_by_name: HashMap<PairKey, String> = HashMap::new();
+        let mut new_stems_by_stem: HashMap<PairKey, (bool, String)> = HashMap::new();
+        for file in self.dir_tree.values_mut().flat_map(|device_tree| device_tree.file_tree.values_mut().flatten()) {
+            if is_sidecar(file) {
+                continue;
+            }
+            // Files without a date, e.g. corrupt files, keep their names
+            let taken_time = match file.get_taken_time() {
+                Some(taken_time) => taken_time,
+                None => continue,
+            };
+            let file_path = Path::new(&file.file_name);
+            let original_stem = file_path.file_stem().unwrap_or(&file.file_name).to_string_lossy();
+            // Like for the date dirs, the size is read from the file header if the EXIF data doesn't include it
+            let dimensions = if uses_image_size_placeholders(template) {
+                file.dimensions.or_else(|| read_image_dimensions(&file.file_path))
             } else {
-                if self.error_file_create > 0 {
-                    println!("{} Some files could not be created in the target path", ColoredString::warn_arrow())
+                None
+            };
+            let new_stem = render_file_name(template, taken_time, dimensions, file.device.as_deref(), &original_stem);
+            let new_name = match file_path.extension() {
+                Some(extension) => format!("{}.{}", new_stem, extension.to_string_lossy()),
+                None => new_stem.clone(),
+            };
+            file.target_file_name = OsString::from(&new_name);
+
+            let source_dir = match file.file_path.parent() {
+                Some(source_dir) => source_dir.to_path_buf(),
+                None => continue,
+            };
+            let is_image = matches!(file.file_type, FileType::Image);
+            new_names_by_name.insert((source_dir, file.file_name.to_string_lossy().to_lowercase()), new_name);
+            if let Some(key) = get_pair_key(file) {
+                let existing = new_stems_by_stem.entry(key).or_insert((is_image, new_stem.clone()));
+                if !existing.0 && is_image {
+                    *existing = (is_image, new_stem);
                 }
+            }
+        }
 
-                if !args.copy_not_move && self.error_file_delete > 0  {
-                    println!("{} Some files were copied but the source files could not be removed", ColoredString::warn_arrow())
+        // Sidecars without their photo or video keep their names, since they're skipped anyway
+        for file in self.dir_tree.values_mut().flat_map(|device_tree| device_tree.file_tree.values_mut().flatten()) {
+            let key = match get_pair_key(file) {
+                Some(key) if is_sidecar(file) => key,
+                _ => continue,
+            };
+            let file_path = Path::new(&file.file_name);
+            let extension = file_path.extension().unwrap_or_default().to_string_lossy();
+            let stem_key = Path::new(&key.1)
+                .file_stem()
+                .map(|stem| (key.0.clone(), stem.to_string_lossy().to_string()));
+
+            let new_name = new_names_by_name.get(&key)
+                .map(|new_name| format!("{}.{}", new_name, extension))
+                .or_else(|| new_stems_by_stem.get(&key).map(|(_, new_stem)| format!("{}.{}", new_stem, extension)))
+                // e.g. `IMG_0001.jpg.xmp` for `IMG_0001.heic`, which keeps its `.jpg` part
+                .or_else(|| {
+                    let (_, new_stem) = new_stems_by_stem.get(&stem_key?)?;
+                    let inner_extension = Path::new(file_path.file_stem()?).extension()?.to_string_lossy();
+                    Some(format!("{}.{}.{}", new_stem, inner_extension, extension))
+                });
+            if let Some(new_name) = new_name {
+                file.target_file_name = OsString::from(new_name);
+            }
+        }
+    }
+
+    /// Find files from different source dirs which would be written with the same name into the same
+    /// target dir, e.g. `a/IMG_1.jpg` and `b/IMG_1.jpg` taken on the same date. Names are compared
+    /// ignoring case, since the target may be on a case-insensitive file system.
+    /// Files are compared in the order of their source paths, so the result is the same on every run:
+    /// the first file keeps its name, files with the same contents as a previous one are skipped,
+    /// see [Self::route_duplicate_sources], while files with different contents are renamed, e.g. `IMG_1 (2).jpg`.
+    /// This must be called after [Self::apply_device_policy], once the target dir of each file is known
+    fn rename_name_collisions(&mut self) {
+        let renamed_files = &mut self.renamed_files;
+
+        for device_tree in self.dir_tree.values_mut() {
+            // Files of devices without a device subdir are all written directly inside the date dir
+            let device_subdirs = &device_tree.device_subdirs;
+            let mut target_dirs: BTreeMap<Option<&DirEntryType>, Vec<&mut SupportedFile>> = BTreeMap::new();
+            for (device_name, files) in device_tree.file_tree.iter_mut() {
+                let device_subdir = Some(device_name).filter(|device_name| device_subdirs.contains(*device_name));
+                target_dirs
+                    .entry(device_subdir)
+                    .or_default()
+                    .extend(files.iter_mut().filter(|file| file.skip_reason.is_none()));
+            }
+
+            for mut files in target_dirs.into_values() {
+                files.sort_by(|file1, file2| file1.file_path.cmp(&file2.file_path));
+
+                let mut used_names: HashSet<String> = files
+                    .iter()
+                    .map(|file| file.target_file_name.to_string_lossy().to_lowercase())
+                    .collect();
+
+                let mut name_groups: BTreeMap<String, Vec<&mut SupportedFile>> = BTreeMap::new();
+                for file in files {
+                    name_groups.entry(file.target_file_name.to_string_lossy().to_lowercase()).or_default().push(file);
+                }
+
+                for group in name_groups.into_values().filter(|group| group.len() > 1) {
+                    // The size, path and hash of each file with distinct contents, which is only
+                    // hashed once another file with the same size is found
+                    let mut distinct_files: Vec<(u64, PathBuf, Option<String>)> = Vec::new();
+
+                    for file in group {
+                        let file_size = file.metadata.len();
+                        let mut file_hash: Option<String> = None;
+                        let mut duplicate_of: Option<PathBuf> = None;
+
+                        for (distinct_size, distinct_path, distinct_hash) in distinct_files.iter_mut() {
+                            if *distinct_size != file_size {
+                                continue;
+                            }
+                            if distinct_hash.is_none() {
+                                *distinct_hash = hash_file(distinct_path).ok();
+                            }
+                            if file_hash.is_none() {
+                                file_hash = hash_file(&file.file_path).ok();
+                            }
+                            // Files which can't be read are treated as different, so that none is lost
+                            if distinct_hash.is_some() && *distinct_hash == file_hash {
+                                duplicate_of = Some(distinct_path.clone());
+                                break;
+                            }
+                        }
+
+                        if duplicate_of.is_some() {
+                            file.skip_reason = Some(SKIP_REASON_DUPLICATE_SOURCE.to_string());
+                        } else {
+                            if !distinct_files.is_empty() {
+                                file.target_file_name = find_unused_file_name(&file.target_file_name, &mut used_names);
+                                renamed_files.push(format!("{} (renamed to {})",
+                                    file.file_path.display(), file.target_file_name.to_string_lossy()));
+                            }
+                            distinct_files.push((file_size, file.file_path.clone(), file_hash));
+                        }
+                    }
                 }
             }
         }
     }
-}
 
-impl Default for FileStats {
-    fn default() -> Self {
-        Self::new()
+    /// Move the source files skipped by [Self::rename_name_collisions] because they have the same contents
+    /// as another source file to a separate dir for review, see [Args::duplicate_files].
+    /// Since most of them have the same name, a number is added to the names already used in this dir,
+    /// e.g. `IMG_1 (2).jpg`, in the order of their source paths
+    fn route_duplicate_sources(&mut self, args: &Args) {
+        if args.duplicate_files != DuplicateFilesAction::Review {
+            return;
+        }
+
+        let is_duplicate_source = |file: &SupportedFile| file.skip_reason.as_deref() == Some(SKIP_REASON_DUPLICATE_SOURCE);
+
+        // Move all duplicates out of their date and device dirs
+        let mut duplicate_files: Vec<SupportedFile> = Vec::new();
+        for device_tree in self.dir_tree.values_mut() {
+            for files in device_tree.file_tree.values_mut() {
+                let (duplicates, others): (Vec<SupportedFile>, Vec<SupportedFile>) =
+                    files.drain(..).partition(is_duplicate_source);
+                *files = others;
+                duplicate_files.extend(duplicates);
+            }
+            device_tree.file_tree.retain(|_, files| !files.is_empty());
+        }
+        self.dir_tree.retain(|_, device_tree| !device_tree.file_tree.is_empty());
+
+        if duplicate_files.is_empty() {
+            return;
+        }
+
+        duplicate_files.sort_by(|file1, file2| file1.file_path.cmp(&file2.file_path));
+        let mut used_names: HashSet<String> = HashSet::new();
+        for file in duplicate_files.iter_mut() {
+            file.skip_reason = None;
+            if !used_names.insert(file.target_file_name.to_string_lossy().to_lowercase()) {
+                file.target_file_name = find_unused_file_name(&file.target_file_name, &mut used_names);
+            }
+            self.duplicate_sources.push(file.file_path.display().to_string());
+        }
+
+        let mut duplicates_tree = DeviceTree::new();
+        duplicates_tree.file_tree.insert(DirEntryType::Files, duplicate_files);
+        self.dir_tree.insert(DEFAULT_DUPLICATES_DIR_NAME.to_string(), duplicates_tree);
+    }
+
+    /// Decide what to do with the files whose target file already exists with [decide_duplicate]
+    /// and the [DuplicatePolicy], which is [Args::collision_policy] unless another policy is used:
+    /// - files which overwrite their target are not compared with it
+    /// - otherwise, files with the same contents as their target are skipped as identical, since they're
+    ///   already in the target dir
+    /// - files with different contents are skipped with [DuplicateAction::Skip], and listed separately,
+    ///   while with [DuplicateAction::Rename], a number is added to their names, e.g. `IMG_0001 (2).jpg`,
+    ///   avoiding the names of both the existing files and the other files written to the same folder,
+    ///   and with [DuplicateAction::Review], they're placed in the [DEFAULT_DUPLICATES_DIR_NAME] dir
+    ///
+    /// This must be called after [Self::rename_name_collisions], once the name of each file is known
+    fn check_existing_targets(&mut self, duplicate_policy: &dyn DuplicatePolicy, args: &Args) {
+        let renamed_files = &mut self.renamed_files;
+        let identical_targets = &mut self.identical_targets;
+        let different_targets = &mut self.different_targets;
+        let mut review_files: HashSet<PathBuf> = HashSet::new();
+
+        let context = DuplicateContext::new(args);
+
+        for (date_dir_name, device_tree) in self.dir_tree.iter_mut() {
+            let date_destination_path = args.target_dir.join(date_dir_name);
+            let device_subdirs = &device_tree.device_subdirs;
+            let mut target_dirs: BTreeMap<PathBuf, Vec<&mut SupportedFile>> = BTreeMap::new();
+            for (device_name, files) in device_tree.file_tree.iter_mut() {
+                let destination_path = if device_subdirs.contains(device_name) {
+                    date_destination_path.join(device_name.to_string())
+                } else {
+                    date_destination_path.clone()
+                };
+                target_dirs
+                    .entry(destination_path)
+                    .or_default()
+                    .extend(files.iter_mut().filter(|file| file.skip_reason.is_none()));
+            }
+
+            for (destination_path, mut files) in target_dirs {
+                // New target dirs have no existing files
+                let existing_names: HashSet<String> = match args.target_file_names(&destination_path) {
+                    Some(existing_names) => existing_names,
+                    None => continue,
+                };
+                files.sort_by(|file1, file2| file1.file_path.cmp(&file2.file_path));
+
+                let mut used_names: HashSet<String> = files
+                    .iter()
+                    .map(|file| file.target_file_name.to_string_lossy().to_lowercase())
+                    .chain(existing_names.iter().cloned())
+                    .collect();
+
+                for file in files {
+                    if !existing_names.contains(&file.target_file_name.to_string_lossy().to_lowercase()) {
+                        continue;
+                    }
+                    let target_path = destination_path.join(&file.target_file_name);
+
+                    let source = DuplicateSource {
+                        path: &file.file_path,
+                        metadata: &file.metadata,
+                        device_name: file.device.as_deref(),
+                    };
+                    match decide_duplicate(duplicate_policy, &source, &DuplicateTarget { path: &target_path }, &context) {
+                        DuplicateDecision::Resolved(DuplicateAction::Overwrite) => {
+                            file.overwrites_target = true;
+                            continue;
+                        }
+                        DuplicateDecision::Identical => {
+                            file.skip_reason = Some(SKIP_REASON_IDENTICAL_TARGET.to_string());
+                            identical_targets.push(format!("{} (identical to {})", file.file_path.display(), target_path.display()));
+                            continue;
+                        }
+                        DuplicateDecision::Resolved(DuplicateAction::Skip) => {
+                            file.skip_reason = Some(SKIP_REASON_DIFFERENT_TARGET.to_string());
+                            different_targets.push(format!("{} (different from {})", file.file_path.display(), target_path.display()));
+                            continue;
+                        }
+                        DuplicateDecision::Resolved(DuplicateAction::Review) => {
+                            review_files.insert(file.file_path.clone());
+                            continue;
+                        }
+                        DuplicateDecision::Resolved(DuplicateAction::Rename) => {}
+                    }
+
+                    let mut counter = 2;
+                    loop {
+                        let new_name = get_numbered_file_name(&file.target_file_name, counter);
+                        let new_name_key = new_name.to_string_lossy().to_lowercase();
+                        if existing_names.contains(&new_name_key) {
+                            // A previous run may have already renamed the same file, which is then skipped
+                            let new_target_path = destination_path.join(&new_name);
+                            if is_same_file_contents(&file.file_path, &file.metadata, &new_target_path) {
+                                file.skip_reason = Some(SKIP_REASON_IDENTICAL_TARGET.to_string());
+                                identical_targets.push(format!("{} (identical to {})",
+                                    file.file_path.display(), new_target_path.display()));
+                                file.target_file_name = new_name;
+                                break;
+                            }
+                        } else if used_names.insert(new_name_key) {
+                            file.target_file_name = new_name;
+                            renamed_files.push(format!("{} (renamed to {}, the target file exists)",
+                                file.file_path.display(), file.target_file_name.to_string_lossy()));
+                            break;
+                        }
+                        counter += 1;
+                    }
+                }
+            }
+        }
+
+        if !review_files.is_empty() {
+            self.route_review_targets(&review_files, args);
+        }
+    }
+
+    /// Move the files whose target file already exists, but which should be reviewed by the user,
+    /// see [DuplicateAction::Review], into the [DEFAULT_DUPLICATES_DIR_NAME] dir, avoiding the names
+    /// of the files already in it
+    fn route_review_targets(&mut self, file_paths: &HashSet<PathBuf>, args: &Args) {
+        let mut review_files: Vec<SupportedFile> = Vec::new();
+        for device_tree in self.dir_tree.values_mut() {
+            for files in device_tree.file_tree.values_mut() {
+                let (reviewed, others): (Vec<SupportedFile>, Vec<SupportedFile>) =
+                    files.drain(..).partition(|file| file_paths.contains(&file.file_path));
+                *files = others;
+                review_files.extend(reviewed);
+            }
+            device_tree.file_tree.retain(|_, files| !files.is_empty());
+        }
+        self.dir_tree.retain(|_, device_tree| !device_tree.file_tree.is_empty());
+
+        let duplicates_dir_name = match &args.batch_dir_name {
+            Some(batch_dir_name) => format!("{}/{}", batch_dir_name, DEFAULT_DUPLICATES_DIR_NAME),
+            None => DEFAULT_DUPLICATES_DIR_NAME.to_string(),
+        };
+        let duplicates_tree = self.dir_tree.entry(duplicates_dir_name.clone()).or_insert_with(DeviceTree::new);
+        let duplicate_files = duplicates_tree.file_tree.entry(DirEntryType::Files).or_default();
+
+        let mut used_names: HashSet<String> = args
+            .target_file_names(&args.target_dir.join(&duplicates_dir_name))
+            .unwrap_or_default();
+        used_names.extend(duplicate_files.iter().map(|file| file.target_file_name.to_string_lossy().to_lowercase()));
+
+        review_files.sort_by(|file1, file2| file1.file_path.cmp(&file2.file_path));
+        for mut file in review_files {
+            if !used_names.insert(file.target_file_name.to_string_lossy().to_lowercase()) {
+                file.target_file_name = find_unused_file_name(&file.target_file_name, &mut used_names);
+            }
+            self.review_targets.push(format!("{} (a different file with the same name exists in the target folder)",
+                file.file_path.display()));
+            duplicate_files.push(file);
+        }
+    }
+
+    /// When overwriting existing target files, find the files whose target is newer or larger
+    /// than the source file, i.e. most likely an edited version which would be lost.
+    /// Returns the source file paths, each with a description of the conflict
+    fn find_newer_targets(&self, args: &Args) -> Vec<(PathBuf, String)> {
+        let mut newer_targets: Vec<(PathBuf, String)> = Vec::new();
+
+        for (date_dir_name, device_tree) in &self.dir_tree {
+            let date_destination_path = args.target_dir.join(date_dir_name);
+
+            for (device_name, files) in &device_tree.file_tree {
+                let destination_path = if device_tree.has_device_subdir(device_name) {
+                    date_destination_path.join(device_name.to_string())
+                } else {
+                    date_destination_path.clone()
+                };
+
+                for file in files.iter().filter(|file| file.skip_reason.is_none() && file.overwrites_target) {
+                    let target_path = destination_path.join(&file.target_file_name);
+                    let target_metadata = match target_path.metadata() {
+                        Ok(target_metadata) => target_metadata,
+                        Err(_) => continue,
+                    };
+
+                    // Copying a file doesn't always keep its modified time, so a target with the
+                    // same size is most likely a previous copy of the same file, even if it's newer
+                    if target_metadata.len() == file.metadata.len() {
+                        continue;
+                    }
+
+                    let is_larger = target_metadata.len() > file.metadata.len();
+                    let is_newer = match (target_metadata.modified(), file.metadata.modified()) {
+                        (Ok(target_time), Ok(source_time)) => target_time > source_time,
+                        _ => false,
+                    };
+
+                    let conflict = match (is_newer, is_larger) {
+                        (true, true) => "newer and larger",
+                        (true, false) => "newer",
+                        (false, true) => "larger",
+                        (false, false) => continue,
+                    };
+
+                    newer_targets.push((
+                        file.file_path.clone(),
+                        format!("{} (target is {}: {})", file.file_path.display(), conflict, target_path.display())));
+                }
+            }
+        }
+
+        newer_targets
+    }
+
+    /// Skip all files with these source paths for the given reason
+    fn skip_files(&mut self, file_paths: &HashSet<PathBuf>, reason: &str) {
+        self.dir_tree
+            .values_mut()
+            .flat_map(|device_tree| device_tree.file_tree.values_mut().flatten())
+            .filter(|file| file_paths.contains(&file.file_path))
+            .for_each(|file| file.skip_reason = Some(reason.to_string()));
+    }
+
+    /// Find the maximum length of the path string that may be present in the output
+    /// This can only be computed after the tree has been filled with devices and files
+    /// because of the requirement to only create device subdirs if there are at least 2 devices
+    ///   (unless always_create_device_subdirs is true, in which case >1 is ignored)
+    /// The resulting value covers two cases:
+    /// - there's at least one date dir with >1 device subdirs -> target path length will be formed of `date/device_name`
+    /// - there's no date dir with >1 devices -> target path will just include `date`
+    ///
+    /// Note: this must be called AFTER [Self::isolate_single_images()] so that the length of
+    /// the oneoffs directory can be taken into account, if present
+    fn compute_max_path_len(&mut self) -> usize {
+        self.dir_tree
+            .iter()
+            .map(|(dir_name, device_tree)| {
+                if device_tree.device_subdirs.is_empty() {
+                    // Dirs without device subdirs, including the oneoffs and probable duplicates dirs,
+                    // which are added after the initial iteration, only need the length of their name.
+                    // This also covers date dirs inside album dirs, e.g. `2023 Iceland/2016.12.29`
+                    get_string_char_count(dir_name.clone())
+                } else {
+                    device_tree.max_dir_path_len
+                }
+            })
+            .max()
+            // default 10 for the length of date dirs, e.g. 2016.12.29
+            .unwrap_or(10)
+    }
+
+    /// The length of the longest name of a file inside the target dirs
+    fn compute_max_file_name_len(&self) -> usize {
+        self.dir_tree
+            .values()
+            .flat_map(|device_tree| device_tree.file_tree.values().flatten())
+            .map(|file| file.get_target_file_name_str().chars().count())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Decide which devices get a separate subdir inside each date dir.
+    /// This must be called after all files are moved to their final date dir
+    fn apply_device_policy(&mut self, args: &Args) {
+        let device_policy = DevicePolicy::new(args);
+        self.dir_tree
+            .values_mut()
+            .for_each(|device_tree| device_tree.apply_device_policy(&device_policy, args.animations_dir_name.as_deref()));
+
+        if let Some(raw_dir_name) = &args.raw_dir_name {
+            for (date_dir_name, device_tree) in self.dir_tree.iter_mut() {
+                device_tree.move_raw_files_to_subdirs(date_dir_name, raw_dir_name);
+            }
+        }
+    }
+
+    /// Place all target dirs inside the batch dir of this run, see [Args::batch_dir_template].
+    /// This must be called after all files are moved to their final date dir
+    fn nest_in_batch_dir(&mut self, args: &Args) {
+        if let Some(batch_dir_name) = &args.batch_dir_name {
+            self.dir_tree = std::mem::take(&mut self.dir_tree)
+                .into_iter()
+                .map(|(date_dir_name, device_tree)| (format!("{}/{}", batch_dir_name, date_dir_name), device_tree))
+                .collect();
+        }
+    }
+
+    // Merge two TargetDateDeviceTree
+    fn extend(&mut self, other: TargetDateDeviceTree) {
+        // append devices and files
+        other.dir_tree
+            .into_iter()
+            .for_each(|(other_date_dir, other_device_tree)|{
+
+                match self.dir_tree.get_mut(&other_date_dir) {
+                    // if this date already exists, append devices to it
+                    Some(devicetree_for_this_date) => {
+                        devicetree_for_this_date.device_subdirs.extend(other_device_tree.device_subdirs);
+
+                        other_device_tree.file_tree
+                            .into_iter()
+                            .for_each(| (other_device, other_files) |{
+
+                                match devicetree_for_this_date.file_tree.get_mut(&other_device) {
+
+                                    // if this device already exists, append files to it
+                                    Some(all_files_for_this_device) => {
+                                        all_files_for_this_device.extend(other_files);
+                                    },
+
+                                    // if this device is not found, just insert the other's entire file list
+                                    None => {
+                                        devicetree_for_this_date.file_tree.insert(other_device, other_files);
+                                    }
+                                }
+                            });
+                    }
+
+                    // if this date is not found, just insert the other's entire device tree
+                    None => {
+                        self.dir_tree.insert(other_date_dir, other_device_tree);
+                    }
+                }
+            });
+
+        // append devices and files
+        self.unknown_extensions.extend(other.unknown_extensions);
+        self.content_type_warnings.extend(other.content_type_warnings);
+        self.corrupt_files.extend(other.corrupt_files);
+        self.unreadable_files.extend(other.unreadable_files);
+        self.probable_duplicates.extend(other.probable_duplicates);
+        self.duplicate_sources.extend(other.duplicate_sources);
+        self.review_targets.extend(other.review_targets);
+        self.exifless_twins.extend(other.exifless_twins);
+        self.renamed_files.extend(other.renamed_files);
+        self.identical_targets.extend(other.identical_targets);
+        self.different_targets.extend(other.different_targets);
     }
 }
 
-/// Enum entries meant to represent the target directories
-/// named after the device name. Derive ordering and
-/// equality traits for more natural ordering when used
-/// as keys in a BTreeMap (will show files after directories)
-#[derive(Clone, Debug, PartialOrd, Ord, PartialEq, Eq)]
-pub enum DirEntryType {
-    Directory(String),
-    Files,
+#[derive(Debug)]
+pub enum DirType {
+    Date,
+    Device,
 }
 
-impl fmt::Display for DirEntryType {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", match self {
-            Self::Directory(dir_name) => dir_name.clone(),
-            Self::Files => "".to_owned()
-        })
+#[derive(Debug)]
+pub enum FileType {
+    Unknown(String),
+    Image,
+    Video,
+    Audio,
+    // animated GIFs and short videos without sound, see [Args::animation_max_seconds]
+    Animation,
+    // scans and other documents, only if enabled, see [Args::documents_dir_name]
+    Document,
+    // files with edits or extra data for the photo or video with the same name, see [Args::sidecar_extensions]
+    Sidecar,
+}
+
+/// Why a file of a known type can't be read, see [Args::unreadable_files]
+#[derive(Debug, Clone)]
+pub enum UnreadableReason {
+    /// The file has no contents, e.g. after an interrupted transfer
+    Empty,
+    /// The file can't be opened, e.g. because of its permissions
+    OpenFailed(String),
+}
+
+impl UnreadableReason {
+    /// Files which can't be opened can't be copied either, so they're skipped even if quarantine is configured
+    fn is_skipped(&self, args: &Args) -> bool {
+        args.unreadable_files == UnreadableFilesAction::Skip || matches!(self, UnreadableReason::OpenFailed(_))
     }
 }
 
+impl fmt::Display for UnreadableReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            UnreadableReason::Empty => write!(f, "empty file"),
+            UnreadableReason::OpenFailed(error) => write!(f, "could not be opened: {}", error),
+        }
+    }
+}
+
+pub enum ConfirmationType {
+    Proceed,
+    DryRun,
+    Cancel,
+    Error,
+}
+
+/// What to do with a previous run which was interrupted, see [check_interrupted_run]
+pub enum InterruptedRunAction {
+    /// Sort the source folders of the interrupted run again, which skips the files it already wrote
+    Resume,
+    /// Undo the interrupted run, then exit
+    RollBack,
+    /// Continue with the configured source folders and forget the interrupted run
+    Ignore,
+}
+
+/// Struct used to keep track of file statuses (i.e. future write restrictions)
+/// when doing dry runs with output compacting enabled
 #[derive(Debug)]
-pub struct SupportedFile {
-    file_name: OsString,
-    file_path: PathBuf,
-    file_type: FileType,
-    extension: Option<String>,
-    // file's modified date in YYYY-MM-DD format
-    date_str: String,
-    metadata: Metadata,
-    device_name: DirEntryType,
+struct CompactCounter {
+    compacting_threshold: usize,
+    current_status_count: usize,
+    current_status: String,
+    skipped_status_count: usize,
+}
+
+impl CompactCounter {
+    fn new(compacting_threshold: usize) -> CompactCounter {
+        CompactCounter {
+            compacting_threshold,
+            current_status_count: 0,
+            current_status: "".to_owned(),
+            skipped_status_count: 0,
+        }
+    }
+
+    fn reset_status(&mut self, new_status: String) {
+        self.current_status_count = 0;
+        self.current_status = new_status;
+        self.skipped_status_count = 0;
+    }
+
+    fn inc_current_status(&mut self) {
+        self.current_status_count += 1;
+    }
+
+    fn inc_skipped_status(&mut self) {
+        self.skipped_status_count += 1;
+    }
+
+    fn has_reached_threshold(&self) -> bool {
+        self.current_status_count >= self.compacting_threshold
+    }
+
+    fn has_skipped_statuses(&self) -> bool {
+        self.skipped_status_count > 0
+    }
+
+    fn is_same_status(&self, new_status: &str) -> bool {
+        self.current_status == *new_status
+    }
+}
+
+/// What a dry run found out about the files checked so far
+#[derive(Default)]
+struct DryRunState {
+    /// The target paths of the files checked so far, to find duplicate source files
+    source_unique_files: HashSet<OsString>,
+    /// The files which would be written, saved as a script if [Args::emit_script_path] is set
+    script_operations: Vec<ScriptOperation>,
+}
+
+impl DryRunState {
+    fn add_script_operation(&mut self, file: &SupportedFile, target_path: &Path, is_move: bool, overwrite: bool) {
+        self.script_operations.push(ScriptOperation {
+            source: file.file_path.clone(),
+            target: target_path.to_path_buf(),
+            is_move,
+            overwrite,
+            location: file.location,
+        });
+    }
+}
+
+/// A statistics counter which can be increased from several threads at once
+#[derive(Debug, Default)]
+struct Counter(AtomicI32);
+
+impl Counter {
+    fn inc(&self) { self.add(1) }
+    fn add(&self, count: i32) { self.0.fetch_add(count, Ordering::Relaxed); }
+    fn get(&self) -> i32 { self.0.load(Ordering::Relaxed) }
+}
+
+/// Lock a statistic which can't be updated atomically. A panic while holding the lock
+/// would only leave a partial statistic behind, so the lock is used even if it's poisoned
+fn lock_stat<T>(stat: &Mutex<T>) -> MutexGuard<'_, T> {
+    stat.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// The statistics of a run, which can be updated from several threads at once,
+/// e.g. while parsing or writing files in parallel
+#[derive(Debug)]
+pub struct FileStats {
+    files_count_total: Counter,
+    file_size_total: AtomicU64,
+    img_moved: Counter,
+    img_copied: Counter,
+    img_skipped: Counter,
+    vid_moved: Counter,
+    vid_copied: Counter,
+    vid_skipped: Counter,
+    aud_moved: Counter,
+    aud_copied: Counter,
+    aud_skipped: Counter,
+    anim_moved: Counter,
+    anim_copied: Counter,
+    anim_skipped: Counter,
+    doc_moved: Counter,
+    doc_copied: Counter,
+    doc_skipped: Counter,
+    sidecar_moved: Counter,
+    sidecar_copied: Counter,
+    sidecar_skipped: Counter,
+    unknown_skipped: Counter,
+    corrupt_skipped: Counter,
+    unreadable_skipped: Counter,
+    // files which could not be read or removed because they're open in another program
+    files_in_use: Counter,
+    // supported files with GPS coordinates, see [Args::show_location]
+    files_with_location: Counter,
+    // images whose EXIF data was read by each parser, see [read_image_exif_data]
+    exif_kamadak: Counter,
+    exif_rexif: Counter,
+    exif_tag_scan: Counter,
+    // supported files which are skipped, counted by the reason for skipping them
+    skipped_by_reason: Mutex<BTreeMap<String, i32>>,
+    // source dirs which are skipped from reading
+    dirs_ignored: Counter,
+    date_dirs_total: Counter,
+    date_dirs_created: Counter,
+    device_dirs_total: Counter,
+    device_dirs_created: Counter,
+    error_file_create: Counter,
+    error_file_read: Counter,
+    error_file_delete: Counter,
+    error_date_dir_create: Counter,
+    error_device_dir_create: Counter,
+    time_fetch_files: Mutex<Duration>,
+    time_fetch_dirs: Mutex<Duration>,
+    time_parse_files: Mutex<Duration>,
+    time_write_files: Mutex<Duration>,
+    time_total: Mutex<Duration>,
+    // detailed timings, which are only saved if requested, see [Args::metrics_report_path]
+    metrics: RunMetrics,
+}
+
+impl FileStats {
+    pub fn new() -> FileStats {
+        FileStats {
+            files_count_total: Counter::default(),
+            file_size_total: AtomicU64::new(0),
+            img_moved: Counter::default(),
+            img_copied: Counter::default(),
+            img_skipped: Counter::default(),
+            vid_moved: Counter::default(),
+            vid_copied: Counter::default(),
+            vid_skipped: Counter::default(),
+            aud_moved: Counter::default(),
+            aud_copied: Counter::default(),
+            aud_skipped: Counter::default(),
+            anim_moved: Counter::default(),
+            anim_copied: Counter::default(),
+            anim_skipped: Counter::default(),
+            doc_moved: Counter::default(),
+            doc_copied: Counter::default(),
+            doc_skipped: Counter::default(),
+            sidecar_moved: Counter::default(),
+            sidecar_copied: Counter::default(),
+            sidecar_skipped: Counter::default(),
+            unknown_skipped: Counter::default(),
+            corrupt_skipped: Counter::default(),
+            unreadable_skipped: Counter::default(),
+            files_in_use: Counter::default(),
+            files_with_location: Counter::default(),
+            exif_kamadak: Counter::default(),
+            exif_rexif: Counter::default(),
+            exif_tag_scan: Counter::default(),
+            skipped_by_reason: Mutex::new(BTreeMap::new()),
+            dirs_ignored: Counter::default(),
+            date_dirs_total: Counter::default(),
+            date_dirs_created: Counter::default(),
+            device_dirs_total: Counter::default(),
+            device_dirs_created: Counter::default(),
+            error_file_create: Counter::default(),
+            error_file_read: Counter::default(),
+            error_file_delete: Counter::default(),
+            error_date_dir_create: Counter::default(),
+            error_device_dir_create: Counter::default(),
+            time_fetch_files: Mutex::new(Duration::new(0, 0)),
+            time_fetch_dirs: Mutex::new(Duration::new(0, 0)),
+            time_parse_files: Mutex::new(Duration::new(0, 0)),
+            time_write_files: Mutex::new(Duration::new(0, 0)),
+            time_total: Mutex::new(Duration::new(0, 0)),
+            metrics: RunMetrics::new(),
+        }
+    }
+
+    pub fn inc_files_total(&self, count: usize) { self.files_count_total.add(count as i32) }
+    pub fn inc_files_size(&self, size: u64) { self.file_size_total.fetch_add(size, Ordering::Relaxed); }
+    fn inc_img_moved(&self) { self.img_moved.inc() }
+    fn inc_img_copied(&self) { self.img_copied.inc() }
+    fn inc_img_skipped(&self) { self.img_skipped.inc() }
+    fn inc_vid_moved(&self) { self.vid_moved.inc() }
+    fn inc_vid_copied(&self) { self.vid_copied.inc() }
+    fn inc_vid_skipped(&self) { self.vid_skipped.inc() }
+    fn inc_aud_moved(&self) { self.aud_moved.inc() }
+    fn inc_aud_copied(&self) { self.aud_copied.inc() }
+    fn inc_aud_skipped(&self) { self.aud_skipped.inc() }
+    fn inc_anim_moved(&self) { self.anim_moved.inc() }
+    fn inc_anim_copied(&self) { self.anim_copied.inc() }
+    fn inc_anim_skipped(&self) { self.anim_skipped.inc() }
+    fn inc_doc_moved(&self) { self.doc_moved.inc() }
+    fn inc_doc_copied(&self) { self.doc_copied.inc() }
+    fn inc_doc_skipped(&self) { self.doc_skipped.inc() }
+    fn inc_sidecar_moved(&self) { self.sidecar_moved.inc() }
+    fn inc_sidecar_copied(&self) { self.sidecar_copied.inc() }
+    fn inc_sidecar_skipped(&self) { self.sidecar_skipped.inc() }
+    pub fn inc_unknown_skipped(&self) { self.unknown_skipped.inc() }
+    pub fn inc_corrupt_skipped(&self) { self.corrupt_skipped.inc() }
+    pub fn inc_unreadable_skipped(&self) { self.unreadable_skipped.inc() }
+    fn inc_files_in_use(&self) { self.files_in_use.inc() }
+    pub fn inc_files_with_location(&self) { self.files_with_location.inc() }
+    pub fn inc_exif_parser(&self, parser: &ExifParser) {
+        match parser {
+            ExifParser::Kamadak => self.exif_kamadak.inc(),
+            ExifParser::Rexif => self.exif_rexif.inc(),
+            ExifParser::TagScan => self.exif_tag_scan.inc(),
+        }
+    }
+    pub fn inc_dirs_ignored(&self) { self.dirs_ignored.inc() }
+    fn inc_date_dirs_total(&self) { self.date_dirs_total.inc() }
+    fn inc_date_dirs_created(&self) { self.date_dirs_created.inc() }
+    fn inc_device_dirs_total(&self) { self.device_dirs_total.inc() }
+    fn inc_device_dirs_created(&self) { self.device_dirs_created.inc() }
+    pub fn inc_error_file_create(&self) { self.error_file_create.inc() }
+    pub fn inc_error_file_read(&self) { self.error_file_read.inc() }
+    pub fn inc_error_file_delete(&self) { self.error_file_delete.inc() }
+    pub fn inc_error_date_dir_create(&self) { self.error_date_dir_create.inc() }
+    pub fn inc_error_device_dir_create(&self) { self.error_device_dir_create.inc() }
+    pub fn set_time_fetch_files(&self, elapsed: Duration) { *lock_stat(&self.time_fetch_files) = elapsed }
+    pub fn set_time_fetch_dirs(&self, elapsed: Duration) { *lock_stat(&self.time_fetch_dirs) = elapsed }
+    pub fn set_time_parse_files(&self, elapsed: Duration) { *lock_stat(&self.time_parse_files) = elapsed }
+    pub fn set_time_write_files(&self, elapsed: Duration) { *lock_stat(&self.time_write_files) = elapsed }
+    pub fn set_time_total(&self, elapsed: Duration) { *lock_stat(&self.time_total) = elapsed }
+    pub fn get_time_fetch_dirs(&self) -> Duration { *lock_stat(&self.time_fetch_dirs) }
+    pub fn get_time_parse_files(&self) -> Duration { *lock_stat(&self.time_parse_files) }
+
+    pub fn inc_dir_total_by_type(&self, dir: &DirType) {
+        match dir {
+            DirType::Date => self.inc_date_dirs_total(),
+            DirType::Device => self.inc_device_dirs_total(),
+        }
+    }
+
+    pub fn inc_dir_created_by_type(&self, dir: &DirType) {
+        match dir {
+            DirType::Date => self.inc_date_dirs_created(),
+            DirType::Device => self.inc_device_dirs_created(),
+        }
+    }
+
+    pub fn has_write_errors(&self) -> bool {
+        self.error_file_create.get() + self.error_file_delete.get() + self.error_date_dir_create.get() + self.error_device_dir_create.get() > 0
+    }
+
+    pub fn inc_error_dir_create_by_type(&self, dir: &DirType) {
+        match dir {
+            DirType::Date => self.inc_error_date_dir_create(),
+            DirType::Device => self.inc_error_device_dir_create(),
+        }
+    }
+
+    pub fn inc_copied_by_type(&self, file: &SupportedFile) {
+        match file.file_type {
+            FileType::Image => self.inc_img_copied(),
+            FileType::Video => self.inc_vid_copied(),
+            FileType::Audio => self.inc_aud_copied(),
+            FileType::Animation => self.inc_anim_copied(),
+            FileType::Document => self.inc_doc_copied(),
+            FileType::Sidecar => self.inc_sidecar_copied(),
+            // don't record any stats for this, shouldn't get one here anyway
+            FileType::Unknown(_) => (),
+        }
+    }
+
+    pub fn inc_moved_by_type(&self, file: &SupportedFile) {
+        match file.file_type {
+            FileType::Image => self.inc_img_moved(),
+            FileType::Video => self.inc_vid_moved(),
+            FileType::Audio => self.inc_aud_moved(),
+            FileType::Animation => self.inc_anim_moved(),
+            FileType::Document => self.inc_doc_moved(),
+            FileType::Sidecar => self.inc_sidecar_moved(),
+            // don't record any stats for this, shouldn't get one here anyway
+            FileType::Unknown(_) => (),
+        }
+    }
+
+    pub fn inc_skipped_by_type(&self, file: &SupportedFile, reason: &str) {
+        *lock_stat(&self.skipped_by_reason).entry(reason.to_string()).or_insert(0) += 1;
+        match file.file_type {
+            FileType::Image => self.inc_img_skipped(),
+            FileType::Video => self.inc_vid_skipped(),
+            FileType::Audio => self.inc_aud_skipped(),
+            FileType::Animation => self.inc_anim_skipped(),
+            FileType::Document => self.inc_doc_skipped(),
+            FileType::Sidecar => self.inc_sidecar_skipped(),
+            // don't record any stats for this, shouldn't get one here anyway
+            FileType::Unknown(_) => (),
+        }
+    }
+
+    pub fn padded_color_if_non_zero(err_stat: i32, level: OutputColor, padding_width: usize) -> String {
+
+        let padded_int = LeftPadding::space(err_stat.to_string(), padding_width);
+
+        if err_stat > 0 {
+            match level {
+                OutputColor::Error   => ColoredString::red(padded_int.as_str()),
+                OutputColor::Warning => ColoredString::orange(padded_int.as_str()),
+                Neutral              => ColoredString::bold_white(padded_int.as_str()),
+                OutputColor::Good    => ColoredString::green(padded_int.as_str()),
+            }
+        } else {
+            padded_int
+        }
+    }
+
+    pub fn color_if_non_zero(err_stat: i32, level: OutputColor) -> String {
+        if err_stat > 0 {
+            match level {
+                OutputColor::Error   => ColoredString::red(err_stat.to_string().as_str()),
+                OutputColor::Warning => ColoredString::orange(err_stat.to_string().as_str()),
+                Neutral              => ColoredString::bold_white(err_stat.to_string().as_str()),
+                OutputColor::Good    => ColoredString::green(err_stat.to_string().as_str()),
+            }
+        } else {
+            err_stat.to_string()
+        }
+    }
+
+    /// Save the detailed timings of the run together with the totals printed by [Self::print_stats]
+    pub fn save_metrics_report(&self, report_path: &Path) -> Result<(), io::Error> {
+        let timings: BTreeMap<&'static str, Duration> = BTreeMap::from([
+            ("fetch_dirs", *lock_stat(&self.time_fetch_dirs)),
+            ("fetch_files", *lock_stat(&self.time_fetch_files)),
+            ("parse_files", *lock_stat(&self.time_parse_files)),
+            ("write_files", *lock_stat(&self.time_write_files)),
+            ("total", *lock_stat(&self.time_total)),
+        ]);
+
+        let counter = |counters: &[&Counter]| counters.iter().map(|counter| counter.get() as i64).sum();
+        let counts: BTreeMap<&'static str, i64> = BTreeMap::from([
+            ("files_total", counter(&[&self.files_count_total])),
+            ("bytes_total", self.file_size_total.load(Ordering::Relaxed) as i64),
+            ("files_moved", counter(&[&self.img_moved, &self.vid_moved, &self.aud_moved, &self.anim_moved, &self.doc_moved, &self.sidecar_moved])),
+            ("files_copied", counter(&[&self.img_copied, &self.vid_copied, &self.aud_copied, &self.anim_copied, &self.doc_copied, &self.sidecar_copied])),
+            ("files_skipped", counter(&[&self.img_skipped, &self.vid_skipped, &self.aud_skipped, &self.anim_skipped, &self.doc_skipped, &self.sidecar_skipped])),
+            ("unknown_skipped", counter(&[&self.unknown_skipped])),
+            ("corrupt_skipped", counter(&[&self.corrupt_skipped])),
+            ("unreadable_skipped", counter(&[&self.unreadable_skipped])),
+            ("date_dirs_created", counter(&[&self.date_dirs_created])),
+            ("device_dirs_created", counter(&[&self.device_dirs_created])),
+            ("errors", counter(&[&self.error_file_read, &self.error_file_create, &self.error_file_delete,
+                &self.error_date_dir_create, &self.error_device_dir_create])),
+        ]);
+
+        self.metrics.report(timings, counts).save(report_path)
+    }
+
+    pub fn print_stats(&self, args: &Args) {
+        let file_size_total = self.file_size_total.load(Ordering::Relaxed);
+        let time_fetch_dirs = *lock_stat(&self.time_fetch_dirs);
+        let time_fetch_files = *lock_stat(&self.time_fetch_files);
+        let time_parse_files = *lock_stat(&self.time_parse_files);
+        let time_write_files = *lock_stat(&self.time_write_files);
+        let time_total = *lock_stat(&self.time_total);
+
+        // file count padding
+        let f_max_digits = get_integer_char_count(self.files_count_total.get());
+        // dir count padding; each should be half of the total file count width
+        let d_max_digits = ((f_max_digits * 3) as f32 / 2_f32).ceil() as usize;
+
+        let write_general_stats = || {
+            format!(
+"──────────────────────────────────────────────
+Total files:                  {total} ({size})
+──────────────────────────────────────────────
+Images moved|copied|skipped:  │{p_img_move}│{p_img_copy}│{p_img_skip}│
+Videos moved|copied|skipped:  │{p_vid_move}│{p_vid_copy}│{p_vid_skip}│
+Audios moved|copied|skipped:  │{p_aud_move}│{p_aud_copy}│{p_aud_skip}│
+Animated moved|copied|skipped:│{p_anim_move}│{p_anim_copy}│{p_anim_skip}│
+Docs   moved|copied|skipped:  │{p_doc_move}│{p_doc_copy}│{p_doc_skip}│
+Sidecars moved|copied|skipped:│{p_sidecar_move}│{p_sidecar_copy}│{p_sidecar_skip}│
+{skipped_by_reason}──────────────────────────────────────────────
+Date   folders created|total: │{date_d_create}│{date_d_total}│
+Device folders created|total: │{devc_d_create}│{devc_d_total}│
+Source folders ignored:       {dir_ignore}
+Unknown files skipped:        {f_skip}
+Corrupt files skipped:        {c_skip}
+Unreadable files skipped:     {u_skip}
+Files in use by other apps:   {in_use}
+Files with GPS location:      {gps_files}
+EXIF via kamadak|rexif|scan:  │{exif_kamadak}│{exif_rexif}│{exif_scan}│
+File read errors:             {fr_err}
+File delete errors:           {fd_err}
+File create errors:           {fc_err}
+Date folders create errors:   {date_c_err}
+Device folders create errors: {devc_c_err}
+──────────────────────────────────────────────
+Time fetching folders:        {tfetch_dir}
+Time fetching files:          {tfetch_file}
+Time parsing files:           {tparse_file}
+Time writing files:           {twrite_file}
+──────────────────────────────────────────────
+Total time taken:             {t_total}
+──────────────────────────────────────────────",
+            total=FileStats::color_if_non_zero(self.files_count_total.get(), Neutral),
+            size=ColoredString::bold_white(format_file_size(file_size_total).as_str()),
+
+            p_img_move=FileStats::padded_color_if_non_zero(self.img_moved.get(), Neutral, f_max_digits),
+            p_img_copy=FileStats::padded_color_if_non_zero(self.img_copied.get(), Neutral, f_max_digits),
+            p_img_skip=FileStats::padded_color_if_non_zero(self.img_skipped.get(), Warning, f_max_digits),
+
+            p_vid_move=FileStats::padded_color_if_non_zero(self.vid_moved.get(), Neutral, f_max_digits),
+            p_vid_copy=FileStats::padded_color_if_non_zero(self.vid_copied.get(), Neutral, f_max_digits),
+            p_vid_skip=FileStats::padded_color_if_non_zero(self.vid_skipped.get(), Warning, f_max_digits),
+
+            p_aud_move=FileStats::padded_color_if_non_zero(self.aud_moved.get(), Neutral, f_max_digits),
+            p_aud_copy=FileStats::padded_color_if_non_zero(self.aud_copied.get(), Neutral, f_max_digits),
+            p_aud_skip=FileStats::padded_color_if_non_zero(self.aud_skipped.get(), Warning, f_max_digits),
+
+            p_anim_move=FileStats::padded_color_if_non_zero(self.anim_moved.get(), Neutral, f_max_digits),
+            p_anim_copy=FileStats::padded_color_if_non_zero(self.anim_copied.get(), Neutral, f_max_digits),
+            p_anim_skip=FileStats::padded_color_if_non_zero(self.anim_skipped.get(), Warning, f_max_digits),
+
+            p_doc_move=FileStats::padded_color_if_non_zero(self.doc_moved.get(), Neutral, f_max_digits),
+            p_doc_copy=FileStats::padded_color_if_non_zero(self.doc_copied.get(), Neutral, f_max_digits),
+            p_doc_skip=FileStats::padded_color_if_non_zero(self.doc_skipped.get(), Warning, f_max_digits),
+
+            p_sidecar_move=FileStats::padded_color_if_non_zero(self.sidecar_moved.get(), Neutral, f_max_digits),
+            p_sidecar_copy=FileStats::padded_color_if_non_zero(self.sidecar_copied.get(), Neutral, f_max_digits),
+            p_sidecar_skip=FileStats::padded_color_if_non_zero(self.sidecar_skipped.get(), Warning, f_max_digits),
+            skipped_by_reason=self.format_skipped_by_reason(),
+
+            date_d_create=FileStats::padded_color_if_non_zero(self.date_dirs_created.get(), Neutral, d_max_digits),
+            date_d_total=FileStats::padded_color_if_non_zero(self.date_dirs_total.get(), Neutral, d_max_digits),
+
+            devc_d_create=FileStats::padded_color_if_non_zero(self.device_dirs_created.get(), Neutral, d_max_digits),
+            devc_d_total=FileStats::padded_color_if_non_zero(self.device_dirs_total.get(), Neutral, d_max_digits),
+
+            dir_ignore=FileStats::color_if_non_zero(self.dirs_ignored.get(), Warning),
+
+            f_skip=FileStats::color_if_non_zero(self.unknown_skipped.get(), Warning),
+            c_skip=FileStats::color_if_non_zero(self.corrupt_skipped.get(), Warning),
+            u_skip=FileStats::color_if_non_zero(self.unreadable_skipped.get(), Warning),
+            in_use=FileStats::color_if_non_zero(self.files_in_use.get(), Warning),
+            gps_files=FileStats::color_if_non_zero(self.files_with_location.get(), Neutral),
+            exif_kamadak=FileStats::padded_color_if_non_zero(self.exif_kamadak.get(), Neutral, f_max_digits),
+            exif_rexif=FileStats::padded_color_if_non_zero(self.exif_rexif.get(), Warning, f_max_digits),
+            exif_scan=FileStats::padded_color_if_non_zero(self.exif_tag_scan.get(), Warning, f_max_digits),
+
+            fr_err=FileStats::color_if_non_zero(self.error_file_read.get(), Error),
+            fd_err=FileStats::color_if_non_zero(self.error_file_delete.get(), Error),
+            fc_err=FileStats::color_if_non_zero(self.error_file_create.get(), Error),
+            date_c_err=FileStats::color_if_non_zero(self.error_date_dir_create.get(), Error),
+            devc_c_err=FileStats::color_if_non_zero(self.error_device_dir_create.get(), Error),
+
+            tfetch_dir=ColoredString::bold_white(format_duration(time_fetch_dirs).as_str()),
+            tfetch_file=ColoredString::bold_white(format_duration(time_fetch_files).as_str()),
+            tparse_file=ColoredString::bold_white(format_duration(time_parse_files).as_str()),
+            twrite_file=ColoredString::bold_white(format_duration(time_write_files).as_str()),
+            t_total=ColoredString::bold_white(format_duration(time_total).as_str()),
+        )}; // end write_general_stats
+
+        let dryrun_general_stats = || {
+            format!(
+"––––––––––––––––––––––––––––––––––––––––––––––––––––––
+Total files:                    {total} ({size})
+––––––––––––––––––––––––––––––––––––––––––––––––––––––
+Images to move|copy|skip:       │{p_img_move}│{p_img_copy}│{p_img_skip}│
+Videos to move|copy|skip:       │{p_vid_move}│{p_vid_copy}│{p_vid_skip}│
+Audios to move|copy|skip:       │{p_aud_move}│{p_aud_copy}│{p_aud_skip}│
+Animations to move|copy|skip:   │{p_anim_move}│{p_anim_copy}│{p_anim_skip}│
+Documents to move|copy|skip:    │{p_doc_move}│{p_doc_copy}│{p_doc_skip}│
+Sidecars to move|copy|skip:     │{p_sidecar_move}│{p_sidecar_copy}│{p_sidecar_skip}│
+{skipped_by_reason}––––––––––––––––––––––––––––––––––––––––––––––––––––––
+Date folders   to create|total: │{date_d_create}│{date_d_total}│
+Device folders to create|total: │{devc_d_create}│{devc_d_total}│
+––––––––––––––––––––––––––––––––––––––––––––––––––––––
+Source folders to skip:         {dir_ignore}
+Unknown files to skip:          {f_skip}
+Corrupt files to skip:          {c_skip}
+Unreadable files to skip:       {u_skip}
+Files in use by other apps:     n/a
+Files with GPS location:        {gps_files}
+EXIF via kamadak|rexif|scan:    │{exif_kamadak}│{exif_rexif}│{exif_scan}│
+File read errors:               {fr_err}
+File delete errors:             n/a
+File create errors:             n/a
+Date folders create errors:     n/a
+Device folders create errors:   n/a
+-----------------------------------------------
+Time fetching folders:          {tfetch_dir}
+Time fetching files:            {tfetch_file}
+Time parsing files:             {tparse_file}
+Time printing files:            {twrite_file}
+––––––––––––––––––––––––––––––––––––––––––––––––––––––
+Total time taken:               {t_total}
+––––––––––––––––––––––––––––––––––––––––––––––––––––––",
+            total=FileStats::color_if_non_zero(self.files_count_total.get(), Neutral),
+            size=ColoredString::bold_white(format_file_size(file_size_total).as_str()),
+
+            p_img_move=FileStats::padded_color_if_non_zero(self.img_moved.get(), Neutral, f_max_digits),
+            p_img_copy=FileStats::padded_color_if_non_zero(self.img_copied.get(), Neutral, f_max_digits),
+            p_img_skip=FileStats::padded_color_if_non_zero(self.img_skipped.get(), Warning, f_max_digits),
+
+            p_vid_move=FileStats::padded_color_if_non_zero(self.vid_moved.get(), Neutral, f_max_digits),
+            p_vid_copy=FileStats::padded_color_if_non_zero(self.vid_copied.get(), Neutral, f_max_digits),
+            p_vid_skip=FileStats::padded_color_if_non_zero(self.vid_skipped.get(), Warning, f_max_digits),
+
+            p_aud_move=FileStats::padded_color_if_non_zero(self.aud_moved.get(), Neutral, f_max_digits),
+            p_aud_copy=FileStats::padded_color_if_non_zero(self.aud_copied.get(), Neutral, f_max_digits),
+            p_aud_skip=FileStats::padded_color_if_non_zero(self.aud_skipped.get(), Warning, f_max_digits),
+
+            p_anim_move=FileStats::padded_color_if_non_zero(self.anim_moved.get(), Neutral, f_max_digits),
+            p_anim_copy=FileStats::padded_color_if_non_zero(self.anim_copied.get(), Neutral, f_max_digits),
+            p_anim_skip=FileStats::padded_color_if_non_zero(self.anim_skipped.get(), Warning, f_max_digits),
+
+            p_doc_move=FileStats::padded_color_if_non_zero(self.doc_moved.get(), Neutral, f_max_digits),
+            p_doc_copy=FileStats::padded_color_if_non_zero(self.doc_copied.get(), Neutral, f_max_digits),
+            p_doc_skip=FileStats::padded_color_if_non_zero(self.doc_skipped.get(), Warning, f_max_digits),
+
+            p_sidecar_move=FileStats::padded_color_if_non_zero(self.sidecar_moved.get(), Neutral, f_max_digits),
+            p_sidecar_copy=FileStats::padded_color_if_non_zero(self.sidecar_copied.get(), Neutral, f_max_digits),
+            p_sidecar_skip=FileStats::padded_color_if_non_zero(self.sidecar_skipped.get(), Warning, f_max_digits),
+            skipped_by_reason=self.format_skipped_by_reason(),
+
+            date_d_create=FileStats::padded_color_if_non_zero(self.date_dirs_created.get(), Neutral, d_max_digits),
+            date_d_total=FileStats::padded_color_if_non_zero(self.date_dirs_total.get(), Neutral, d_max_digits),
+
+            devc_d_create=FileStats::padded_color_if_non_zero(self.device_dirs_created.get(), Neutral, d_max_digits),
+            devc_d_total=FileStats::padded_color_if_non_zero(self.device_dirs_total.get(), Neutral, d_max_digits),
+
+            dir_ignore=FileStats::color_if_non_zero(self.dirs_ignored.get(), Warning),
+
+            f_skip=FileStats::color_if_non_zero(self.unknown_skipped.get(), Warning),
+            c_skip=FileStats::color_if_non_zero(self.corrupt_skipped.get(), Warning),
+            u_skip=FileStats::color_if_non_zero(self.unreadable_skipped.get(), Warning),
+            gps_files=FileStats::color_if_non_zero(self.files_with_location.get(), Neutral),
+            exif_kamadak=FileStats::padded_color_if_non_zero(self.exif_kamadak.get(), Neutral, f_max_digits),
+            exif_rexif=FileStats::padded_color_if_non_zero(self.exif_rexif.get(), Warning, f_max_digits),
+            exif_scan=FileStats::padded_color_if_non_zero(self.exif_tag_scan.get(), Warning, f_max_digits),
+            fr_err=FileStats::color_if_non_zero(self.error_file_read.get(), Error),
+
+            tfetch_dir=ColoredString::bold_white(format_duration(time_fetch_dirs).as_str()),
+            tfetch_file=ColoredString::bold_white(format_duration(time_fetch_files).as_str()),
+            tparse_file=ColoredString::bold_white(format_duration(time_parse_files).as_str()),
+            twrite_file=ColoredString::bold_white(format_duration(time_write_files).as_str()),
+            t_total=ColoredString::bold_white(format_duration(time_total).as_str()),
+        )}; // end dryrun_general_stats
+
+        // Print dry run stats
+        if args.dry_run {
+            println!("{}", dryrun_general_stats());
+
+            if self.error_file_create.get() > 0 {
+                println!("{} Some files are too large for the filesystem of the target folder and will not be written",
+                         ColoredString::warn_arrow())
+            }
+
+        // Print actual stats and other errors encountered when writing files
+        } else {
+            println!("{}", write_general_stats());
+
+            if self.files_count_total.get() == self.unknown_skipped.get() + self.corrupt_skipped.get()
+                + self.unreadable_skipped.get() + self.error_file_read.get() {
+                println!("{}", ColoredString::orange("No supported files found in source folder."))
+            } else {
+                if self.error_file_create.get() > 0 {
+                    println!("{} Some files could not be created in the target path", ColoredString::warn_arrow())
+                }
+
+                if !args.copy_not_move && self.error_file_delete.get() > 0  {
+                    println!("{} Some files were copied but the source files could not be removed", ColoredString::warn_arrow())
+                }
+
+                if self.files_in_use.get() > 0 {
+                    println!("{} Some files were in use by another program, close it and run again to sort them",
+                             ColoredString::warn_arrow())
+                }
+            }
+        }
+    }
+}
+
+impl FileStats {
+    /// Format a breakdown of all skipped files, including unknown and corrupt files, by the reason for skipping them.
+    /// Returns an empty string if no files were skipped
+    fn format_skipped_by_reason(&self) -> String {
+        let skipped_by_reason_map = lock_stat(&self.skipped_by_reason);
+        let mut skipped_by_reason: Vec<(&str, i32)> = skipped_by_reason_map
+            .iter()
+            .map(|(reason, count)| (reason.as_str(), *count))
+            .collect();
+        skipped_by_reason.push((SKIP_REASON_UNKNOWN_EXTENSION, self.unknown_skipped.get()));
+        skipped_by_reason.push((SKIP_REASON_CORRUPT, self.corrupt_skipped.get()));
+        skipped_by_reason.push((SKIP_REASON_UNREADABLE, self.unreadable_skipped.get()));
+        skipped_by_reason.retain(|(_, count)| *count > 0);
+
+        // Show the most common reasons first
+        skipped_by_reason.sort_by(|(_, count1), (_, count2)| count2.cmp(count1));
+
+        let count_width = skipped_by_reason
+            .iter()
+            .map(|(_, count)| get_integer_char_count(*count))
+            .max()
+            .unwrap_or(0);
+
+        skipped_by_reason
+            .iter()
+            .map(|(reason, count)| format!("  {} {}\n",
+                FileStats::padded_color_if_non_zero(*count, Warning, count_width),
+                reason))
+            .fold(String::new(), |mut lines, line| {
+                if lines.is_empty() {
+                    lines.push_str("Skipped files by reason:\n");
+                }
+                lines.push_str(line.as_str());
+                lines
+            })
+    }
+}
+
+impl Default for FileStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Enum entries meant to represent the target directories
+/// named after the device name. Derive ordering and
+/// equality traits for more natural ordering when used
+/// as keys in a BTreeMap (will show files after directories)
+#[derive(Clone, Debug, PartialOrd, Ord, PartialEq, Eq)]
+pub enum DirEntryType {
+    Directory(String),
+    Files,
+}
+
+impl fmt::Display for DirEntryType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", match self {
+            Self::Directory(dir_name) => dir_name.clone(),
+            Self::Files => "".to_owned()
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct SupportedFile {
+    file_name: OsString,
+    // the name of the file inside the target dir, which only differs from the source file name
+    // if it's renamed to avoid replacing another file, see [TargetDateDeviceTree::rename_name_collisions]
+    // and [TargetDateDeviceTree::rename_existing_targets]
+    target_file_name: OsString,
+    file_path: PathBuf,
+    file_type: FileType,
+    extension: Option<String>,
+    // set if the file's contents don't match its extension
+    content_type_warning: Option<String>,
+    // set if the file's header and EXIF data are both unreadable
+    is_corrupt: bool,
+    // set if the file is empty or can't be opened, see [Args::unreadable_files]
+    unreadable_reason: Option<UnreadableReason>,
+    // set if the file should not be written, e.g. if it's a duplicate of another file
+    skip_reason: Option<String>,
+    // set if the file replaces the existing target file with the same name, see [DuplicatePolicy]
+    overwrites_target: bool,
+    // the full date and time when the photo was taken, if read from EXIF
+    capture_time: Option<String>,
+    // the fraction of the second when the photo was taken, if read from EXIF
+    capture_subsec: Option<String>,
+    // width and height in pixels, if read from EXIF
+    dimensions: Option<(u32, u32)>,
+    // the camera settings used to take the photo, if read from EXIF
+    shooting_info: ShootingInfo,
+    // where the photo was taken, if read from EXIF
+    location: Option<GpsLocation>,
+    // the parser which read the EXIF data, if any
+    exif_parser: Option<ExifParser>,
+    // file's modified date in YYYY-MM-DD format
+    date_str: String,
+    // the date which the date dir was built from, unless the file has no date or is placed in a review dir
+    date: Option<NaiveDate>,
+    metadata: Metadata,
+    device_name: DirEntryType,
+    // the name of the device which took the photo, if known, used by [Args::file_name_template]
+    device: Option<String>,
+}
+
+// TODO 5e: find better name
+impl SupportedFile {
+    pub fn parse_from_ref(dir_entry: &DirEntry, args: &Args) -> Result<(SupportedFile, HashSet<String>), io::Error> {
+        let extension = get_extension(dir_entry);
+        let metadata = dir_entry.metadata()?;
+
+        // Reading cloud placeholders would download them, so don't read them at all if they're skipped
+        let extension_file_type = get_file_type(&extension, args);
+        let cloud_skip_reason = check_cloud_placeholder(&dir_entry.path(), &metadata, &extension_file_type, args);
+
+        // Empty files and files which can't be opened have nothing to read either, see [Args::unreadable_files]
+        let unreadable_reason = if cloud_skip_reason.is_none() {
+            check_unreadable_file(&dir_entry.path(), &metadata, &extension_file_type)
+        } else {
+            None
+        };
+        let can_read = cloud_skip_reason.is_none() && unreadable_reason.is_none();
+
+        let content_check = if can_read {
+            check_file_contents(&dir_entry.path(), &extension, extension_file_type, args)
+        } else {
+            ContentCheck::unchanged(extension_file_type)
+        };
+        let file_type = content_check.file_type;
+
+        let mut exif_data = match file_type {
+            // It's much faster if we only try to read EXIF for image files
+            FileType::Image if can_read => read_image_exif_data(dir_entry, args),
+            // Videos don't have EXIF, but most of them have a creation time in their metadata
+            FileType::Video if can_read => read_video_date(dir_entry, args),
+            FileType::Document if can_read => read_document_date(dir_entry, args),
+            _ => ExifDateDevice::new(),
+        };
+
+        // Photos exported from Google Photos often have no date, but it's kept in their Google Takeout JSON file
+        if let Some(takeout_metadata) = args.takeout_index.get(&dir_entry.path()) {
+            takeout_metadata.fill_missing(&mut exif_data, DATE_DIR_FORMAT);
+        }
+
+        // Cameras without GPS can still get a location from a track recorded at the same time
+        if exif_data.location.is_none() {
+            exif_data.location = find_gpx_location(&exif_data, args);
+        }
+
+        // Only consider a file corrupt if there's nothing at all we can read from it
+        let is_corrupt = content_check.has_invalid_header && match file_type {
+            FileType::Image | FileType::Video => exif_data.read_error.is_some(),
+            _ => true,
+        };
+
+        // Animated GIFs and short videos without sound can only be told apart from the others by reading them
+        let file_type = if can_read && !is_corrupt {
+            classify_animation(file_type, &dir_entry.path(), args)
+        } else {
+            file_type
+        };
+
+        let mut non_custom_device_names: HashSet<String> = HashSet::new();
+
+        // Replace EXIF camera model with a custom name, if one was defined in config
+        let device_name: DirEntryType = match &exif_data.get_device_name(args.include_device_make, &args.lens_grouping) {
+            Some(camera_model) =>
+                args
+                    .custom_device_names
+                    .get(camera_model.to_lowercase().as_str())
+                    .map_or(
+                        {
+                            non_custom_device_names.insert(camera_model.clone());
+                            DirEntryType::Directory(camera_model.clone())
+                        },
+                        |custom_camera_name| DirEntryType::Directory(custom_camera_name.clone())
+                    ),
+            None if args.always_create_device_subdirs && !is_corrupt && unreadable_reason.is_none() =>
+                DirEntryType::Directory(DEFAULT_UNKNOWN_DEVICE_DIR_NAME.to_string()),
+            None =>
+                DirEntryType::Files,
+        };
+
+        // Read image date - prefer EXIF tags, then the date in the file name, over system date
+        // Corrupt and unreadable files are placed in separate dirs instead, if configured
+        let (date_str, date, is_device_in_date_dir) = if unreadable_reason.as_ref().is_some_and(|reason| !reason.is_skipped(args)) {
+            (DEFAULT_QUARANTINE_DIR_NAME.to_string(), None, false)
+        } else if is_corrupt && args.corrupt_files == CorruptFilesAction::Quarantine {
+            (DEFAULT_CORRUPT_DIR_NAME.to_string(), None, false)
+        } else {
+            let date_str = exif_data.date.clone()
+                .or_else(|| get_filename_date(dir_entry, args))
+                .or_else(|| get_system_modified_date(&metadata));
+            let date = date_str
+                .as_ref()
+                .and_then(|date_str| NaiveDate::parse_from_str(date_str, DATE_DIR_FORMAT).ok());
+            let is_device_in_date_dir = date_str.is_some()
+                && date.and_then(|date| args.find_named_event(date)).is_none()
+                && uses_device_placeholder(get_date_dir_template(&file_type, args));
+            let date_str = date_str
+                .map(|date_str| build_date_dir_name(date_str, &file_type, &dir_entry.path(), &device_name, &exif_data, args))
+                .unwrap_or_else(|| DEFAULT_NO_DATE_STR.to_string());
+
+            // Place the date dir inside the album dir, if configured
+            let date_str = match get_album_name(&dir_entry.path(), args) {
+                Some(album_name) => Path::new(&album_name).join(date_str).display().to_string(),
+                None => date_str,
+            };
+
+            // Documents have their own tree of date dirs, see [Args::documents_dir_name]
+            let date_str = match &args.documents_dir_name {
+                Some(documents_dir_name) if matches!(file_type, FileType::Document) =>
+                    Path::new(documents_dir_name).join(date_str).display().to_string(),
+                _ => date_str,
+            };
+            (date_str, date, is_device_in_date_dir)
+        };
+
+        // Keep the device for [Args::file_name_template], since the file may still be placed outside its device subdir
+        let device = match &device_name {
+            DirEntryType::Directory(device_name) => Some(device_name.clone()),
+            DirEntryType::Files => None,
+        };
+
+        // The device is already part of the date dir if the template includes it, see [Args::date_dir_template]
+        // Animations are placed in their own subdir instead of the device subdir, if configured
+        let device_name = match &args.animations_dir_name {
+            Some(animations_dir_name) if matches!(file_type, FileType::Animation) =>
+                DirEntryType::Directory(animations_dir_name.clone()),
+            // Documents don't have a device
+            _ if is_device_in_date_dir || matches!(file_type, FileType::Document) => DirEntryType::Files,
+            _ => device_name,
+        };
+
+        Ok((
+            SupportedFile {
+            file_name: dir_entry.file_name(),
+            target_file_name: dir_entry.file_name(),
+            file_path: dir_entry.path(),
+            file_type,
+            extension,
+            content_type_warning: content_check.warning,
+            is_corrupt,
+            unreadable_reason,
+            skip_reason: cloud_skip_reason,
+            overwrites_target: false,
+            capture_time: exif_data.capture_time,
+            capture_subsec: exif_data.capture_subsec,
+            dimensions: exif_data.dimensions,
+            shooting_info: exif_data.shooting_info,
+            location: exif_data.location,
+            exif_parser: exif_data.parser,
+            date_str,
+            date,
+            metadata,
+            device_name,
+            device,
+            },
+            non_custom_device_names
+        ))
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.metadata.is_dir()
+    }
+
+    pub fn get_extension_lowercase(&self) -> String {
+        self.extension.as_deref().unwrap_or("").to_lowercase()
+    }
+
+    pub fn get_file_name_str(&self) -> String {
+        self.file_name.to_string_lossy().to_string()
+    }
+
+    pub fn get_target_file_name_str(&self) -> String {
+        self.target_file_name.to_string_lossy().to_string()
+    }
+
+    /// The date and time when the file was taken, used by [Args::file_name_template]:
+    /// the EXIF capture time, if any, otherwise the date of the file's date dir, e.g. read from its name,
+    /// with the time of day when it was last modified, in UTC like [get_system_modified_date].
+    /// Files without a date, e.g. corrupt files, have none
+    pub fn get_taken_time(&self) -> Option<NaiveDateTime> {
+        if let Some(capture_time) = self.capture_time.as_deref().and_then(parse_capture_time) {
+            return Some(capture_time);
+        }
+        let modified_time: DateTime<Utc> = self.metadata.modified().ok()?.into();
+        self.date.map(|date| date.and_time(modified_time.time()))
+    }
+
+    /// Return a string representation of the source file or path.
+    /// If there are multiple sources, return the full absolute path
+    /// If there is a single source, return only the filename,
+    /// since the full path will always be the same
+    pub fn get_source_display_name_str(&self, args: &Args) -> String {
+        if args.has_multiple_sources() {
+            self.file_path.display().to_string()
+        } else {
+            self.file_name.to_string_lossy().to_string()
+        }
+    }
+}
+
+/// The main program body. This is an overview of the main flows:
+/// * parse config file and set up args/run options
+/// * read list of files in all source dirs
+/// * ask for operation confirmation - dry run or write files
+/// * parse source files and build a model of the destination dir structure (this is where the sorting occurs)
+///   * if dry run, filter only source unique files
+///   * parse files and sort them into the internal target dir model
+///   * isolate one-off images
+///   * calculate max file and path lengths for dry run output padding
+/// * process files based on the destination dir model
+///   * if dry run, only show target dir structure and potential copy status of each file
+///   * if write, copy the files to the destination; if move is enabled, delete source files after copy
+/// * print stats and exit
+fn main() -> Result<(), std::io::Error> {
+
+    let cli_args: Vec<String> = env::args().collect();
+    if cli_args.iter().skip(1).any(|arg| arg == VERSION_CLI_FLAG) {
+        println!("{}", build_info());
+        return Ok(());
+    }
+
+    if cli_args.iter().skip(1).any(|arg| arg == HELP_CLI_FLAG) {
+        print_cli_help();
+        return Ok(());
+    }
+
+    println!("───────────────────────────────────────────────────────────────────────────");
+    println!("                             IMGSORTER v{versn}                            ", versn = VERSION);
+    println!("{}", ColoredString::dim(
+        format!("{:^75}", format!("commit {}, built {}", GIT_COMMIT, BUILD_DATE)).as_str()));
+    println!("───────────────────────────────────────────────────────────────────────────");
+
+    // Without a handler, Ctrl+C would stop the program in the middle of writing a file
+    if let Err(e) = install_interrupt_handler() {
+        println!("{} Could not handle Ctrl+C, stopping the program may leave a partially written file: {}",
+                 ColoredString::warn_arrow(), e);
+    }
+
+    // Commands which work on an existing archive and don't need the config file
+    match get_cli_command() {
+        // Without a folder argument, the target folder is compared with the sources from the config file
+        Command::Verify | Command::Audit => if let Some(archive_dir) = get_cli_path() {
+            return run_verify(Path::new(&archive_dir));
+        },
+        Command::Undo => if let Some(target_dir) = get_cli_path() {
+            return run_undo(Path::new(&target_dir), None, has_silent_cli_flag());
+        },
+        Command::Init => {
+            let config_dir = get_cli_path().map(PathBuf::from).unwrap_or_else(|| PathBuf::from("."));
+            return run_init(&config_dir);
+        }
+        Command::HelpConfig => {
+            print_config_help();
+            return Ok(());
+        }
+        Command::Selftest => {
+            return run_selftest();
+        }
+        _ => {}
+    }
+
+    let args = Args::new_from_toml(CONFIG_FILE_NAME)?;
+
+    // Scripts shouldn't continue with the config values if an option is mistyped
+    if !args.invalid_cli_options.is_empty() {
+        println!("{}", ColoredString::red("Some command line options are invalid:"));
+        args.invalid_cli_options
+            .iter()
+            .for_each(|message| println!("  {}", message));
+        println!("Run `imgsorter {}` to list all options. Exiting.", HELP_CLI_FLAG);
+        process::exit(1);
+    }
+
+    // The plan is written like a normal run, with the options of the config file which don't change what's written
+    if args.command == Command::Apply {
+        return match get_cli_path() {
+            Some(plan_path) => run_apply(Path::new(&plan_path), args),
+            None => {
+                println!("{}", ColoredString::red(format!(
+                    "No plan file was given, e.g. `imgsorter {} plan.json`. Exiting.", Command::Apply.name()).as_str()));
+                process::exit(1);
+            }
+        };
+    }
+
+    // Writing each file as soon as it's read would silently ignore the options which need all files
+    let stream_writes_conflicts = if args.is_streaming() { args.find_stream_writes_conflicts() } else { Vec::new() };
+    if !stream_writes_conflicts.is_empty() {
+        println!("{}", ColoredString::red(
+            "The option `stream_writes` can't be used with these options, which need all files to be read first:"));
+        stream_writes_conflicts
+            .iter()
+            .for_each(|message| println!("  {}", message));
+        println!("Disable `stream_writes` or these options in the config file. Exiting.");
+        process::exit(1);
+    }
+
+    // Without a folder argument, undo the last run into the configured target folder
+    if args.command == Command::Undo {
+        return run_undo(&args.target_dir, args.import_index_path.as_deref(), args.silent);
+    }
+
+    // Without a folder argument, check the configured target folder
+    if args.command == Command::Audit {
+        return run_verify(&args.target_dir);
+    }
+
+    // Refuse to walk through most of the disk unless explicitly requested
+    if !args.dangerous_source_dirs.is_empty() {
+        println!("{}", ColoredString::red(
+            "These source folders are drive roots, home folders or system folders, and reading them \
+            with all their subfolders would most likely scan most of the disk:"));
+        args.dangerous_source_dirs
+            .iter()
+            .for_each(|source_dir| println!("  {}", source_dir));
+        println!("If this is intended, run the program again with the `{}` option. Exiting.", FORCE_ROOT_CLI_FLAG);
+        return Ok(());
+    }
+
+    // Keep sorting the files added to the sources until the program is stopped
+    if args.command == Command::Watch {
+        return run_watch(args);
+    }
+
+    run_sort(args)
+}
+
+/// Read, parse and sort the source files, as configured by the config file and the command line options
+fn run_sort(mut args: Args) -> Result<(), std::io::Error> {
+    // A run which was interrupted left only some of its files sorted, so offer to finish or reverse it first
+    if matches!(args.command, Command::Interactive | Command::Sort | Command::Watch) && !check_interrupted_run(&mut args)? {
+        return Ok(());
+    }
+
+    // Files which are too large for the target filesystem would otherwise only fail after copying most of their data
+    args.target_max_file_size = get_max_file_size(&args.target_dir);
+
+    let stats = FileStats::new();
+
+    if args.verbose { dbg!(&args); }
+
+    if args.chaos_failure_rate > 0 {
+        println!("{}", ColoredString::red(format!(
+            "> Chaos mode is enabled: {}% of copy and delete operations will fail on purpose.",
+            args.chaos_failure_rate).as_str()));
+    }
+
+    // Needs to be created after checking for recursive source dirs,
+    // since we need to pass args.has_multiple_sources()
+    let mut padder = Padder::new(args.has_multiple_sources());
+
+    /*****************************************************************************/
+    /* ---                        Read source files                          --- */
+    /*****************************************************************************/
+
+    let time_fetching_files = Instant::now();
+    // Files added to the sources from now on are read again by the next run, see [Args::since_last_run]
+    let run_start_time = Local::now();
+    args.batch_dir_name = args.batch_dir_template
+        .as_ref()
+        .map(|template| render_batch_dir(template, run_start_time.naive_local()));
+
+    // TODO 5g: instead of Vec<Vec<DirEntry>>, return a `SourceDirTree` struct
+    //   which wraps the Vec's but contains additional metadata, such as no of files or total size
+    // TODO 5p: make this multi-threaded
+    // Read dir contents and filter out error results
+    let source_files: BTreeMap<String, Vec<DirEntry>> = match &args.source_files_list {
+        Some(listed_files) => read_listed_files(listed_files),
+        None => args
+            .source_dirs
+            .iter()
+            .map(|src_dir_vec| {
+                let parent_dir_name = src_dir_vec[0].display().to_string();
+                let dir_contents = src_dir_vec
+                    .iter()
+                    .filter_map(|src_dir|
+                        read_supported_files(src_dir, &stats, &args).ok())
+                    .flatten()
+                    .collect::<Vec<_>>();
+                (parent_dir_name, dir_contents)
+            })
+            .collect::<BTreeMap<_, _>>(),
+    };
+
+    // Google Takeout metadata files are read as the dates of their photos, instead of being sorted themselves
+    let source_files = read_takeout_metadata(source_files, &mut args);
+
+    // Nightly runs on large folders only need the files which are new since the last run
+    let source_names: Vec<String> = source_files.keys().cloned().collect();
+    let source_files = if args.since_last_run {
+        filter_files_since_last_run(source_files, &args)
+    } else {
+        source_files
+    };
+
+    stats.set_time_fetch_files(time_fetching_files.elapsed());
+
+    /*****************************************************************************/
+    /* ---                 Print options before confirmation                 --- */
+    /*****************************************************************************/
+
+    // TODO 5l: use this in parse_source_dirs methods instead of recalculating it
+    let source_files_count: usize = source_files.values().map(|d|d.len()).sum();
+
+    // Exit early if there are no source files
+    if source_files_count < 1 && args.since_last_run {
+        println!("There are no new files in the current source(s) since the last run, exiting.");
+        return Ok(());
+    } else if source_files_count < 1 {
+        println!("{}", ColoredString::red("There are no supported files in the current source(s), exiting."));
+        return Ok(());
+    }
+
+    // Identical files are found by their contents only, so the source files don't need to be parsed
+    if args.command == Command::Dedupe {
+        print_identical_files(source_files.values().flatten());
+        return Ok(());
+    }
+
+    // Scheduled runs don't need to read and plan the same source files again
+    let source_snapshot = if args.skip_unchanged_sources {
+        let source_snapshot = hash_source_snapshot(source_files.values().flatten());
+        if read_source_snapshot(&args.target_dir).as_ref() == Some(&source_snapshot) {
+            println!("No changes in the source folders since the last successful run, exiting.");
+            return Ok(());
+        }
+        Some(source_snapshot)
+    } else {
+        None
+    };
+
+    {
+        let write_op = if args.copy_not_move {
+            ColoredString::orange("copied:")
+        } else {
+            ColoredString::red("moved: ")
+        };
+
+        // Build the string used for printing source directory name(s) before confirmation
+        let source_dirs_list: String = build_source_dirs_list_string(&args);
+
+        println!("═══════════════════════════════════════════════════════════════════════════");
+        println!("{}", source_dirs_list);
+        println!("Target directory:   {}", &args.target_dir.display());
+        println!("Files to be {} {}", write_op, source_files_count);
+        println!("═══════════════════════════════════════════════════════════════════════════");
+        // TODO 1f: print all options for this run?
+    }
+
+    // Refuse to run if any existing files inside a protected archive would be changed
+    if let Err(reason) = check_archive_protection(&args) {
+        println!("{}", ColoredString::red(
+            format!("The target folder is a protected archive and only new files can be added to it, but {}. Exiting.", reason).as_str()));
+        return Ok(());
+    }
+
+    /*****************************************************************************/
+    /* ---       Parse source files and preview the target folders           --- */
+    /*****************************************************************************/
+
+    // TODO 5j: prefilter for Images and Videos only
+    // Iterate files, read modified date and create subdirs
+    // Copy images and videos to subdirs based on modified date
+    let time_parsing_files = Instant::now();
+
+    learn_filename_date_orders(&source_files, &mut args);
+
+    // Silent runs which write files have nothing to preview or confirm, so each file can be written as soon as it's read
+    let is_streaming = args.is_streaming();
+
+    let mut target_dir_tree = if is_streaming {
+        stream_source_files(source_files, &args, &stats, &mut padder)
+    } else {
+        parse_source_dirs(source_files, &mut args, &stats, &mut padder)
+    };
+
+    stats.set_time_parse_files(time_parsing_files.elapsed());
+
+    // Give a quick overview of the resulting folders before confirmation, without a full dry run
+    if !is_streaming {
+        print_target_dir_preview(&target_dir_tree, &args);
+    }
+
+    // Commands which only report on the source files stop here, without writing anything
+    match args.command {
+        // The target folders were already shown by the preview
+        Command::Scan => {
+            return Ok(());
+        }
+        Command::Inspect => {
+            print_inspect_report(&target_dir_tree, &args);
+            return Ok(());
+        }
+        Command::Stats => {
+            print_source_stats(&target_dir_tree);
+            return Ok(());
+        }
+        Command::Verify => {
+            let report = verify_target_against_sources(&target_dir_tree, &args);
+            println!();
+            report.print_summary();
+            if report.has_errors() {
+                process::exit(1);
+            }
+            return Ok(());
+        }
+        _ => {}
+    }
+
+    // Proceed only if silent is enabled or user confirms, otherwise exit
+    if is_streaming {
+        // The files were already written while they were read
+    } else if args.silent {
+        println!("> Silent mode is enabled. Proceeding without user confirmation.");
+        if args.dry_run {
+            println!("> This is a dry run. No folders will be created. No files will be copied or moved.");
+        }
+    } else {
+        match ask_for_op_confirmation(&args) {
+            ConfirmationType::Cancel => {
+                println!("Cancelled by user, exiting.");
+                return Ok(());
+            }
+            ConfirmationType::Error => {
+                println!("Error confirming, exiting.");
+                return Ok(());
+            }
+            ConfirmationType::DryRun => {
+                println!("This is a dry run. No folders will be created. No files will be copied or moved.");
+                args.dry_run = true;
+            }
+            ConfirmationType::Proceed =>
+                args.dry_run = false
+        }
+    }
+
+    // Refuse to write anything if the plan changed since it was reviewed in a dry run
+    let plan_lock = if args.lock_plan && !is_streaming { Some(build_plan_lock(&target_dir_tree, &args)) } else { None };
+    if let Some(plan_lock) = plan_lock.as_ref().filter(|_| !args.dry_run && !args.replan) {
+        if let Some(saved_plan_lock) = PlanLock::read(&args.plan_lock_path) {
+            if saved_plan_lock.hash != plan_lock.hash {
+                print_plan_changes(&saved_plan_lock, plan_lock);
+                println!("Run a new dry run to review the changes, or run the program with the `--replan` option \
+                    to write the files anyway. Exiting.");
+                return Ok(());
+            }
+        }
+    }
+
+    let time_processing = Instant::now();
+
+    println!("–––––––––––––––––––––––––––––––––––––––––––––––––––––––––––––––––––––––––––");
+
+    /*****************************************************************************/
+    /* ---                 Copy/paste or dry run source files                --- */
+    /*****************************************************************************/
+
+    // Overwriting target files which are newer or larger than their source files would most likely
+    // lose edited versions, so list them separately and confirm again before writing
+    let newer_targets = if is_streaming { Vec::new() } else { target_dir_tree.find_newer_targets(&args) };
+    if !newer_targets.is_empty() && !args.dry_run {
+        println!("{}", ColoredString::red(
+            "These target files are newer or larger than the source files which would overwrite them:"));
+        // Show what each source file is before confirming, if configured, see [Args::show_thumbnails]
+        let show_thumbnails = args.show_thumbnails && !args.silent && supports_inline_images();
+        newer_targets
+            .iter()
+            .enumerate()
+            .for_each(|(index, (source_path, description))| {
+                println!("  {}", description);
+                if show_thumbnails && index < MAX_LISTED_THUMBNAILS {
+                    if let Some(thumbnail) = read_exif_thumbnail(source_path) {
+                        println!("  {}", format_inline_image(&thumbnail));
+                    }
+                }
+            });
+
+        let do_overwrite = if args.silent {
+            println!("> Silent mode is enabled. These target files will be kept and the source files skipped.");
+            false
+        } else {
+            ask_for_overwrite_confirmation(&args)
+        };
+
+        if !do_overwrite {
+            let skipped_paths: HashSet<PathBuf> = newer_targets.iter().map(|(path, _)| path.clone()).collect();
+            target_dir_tree.skip_files(&skipped_paths, SKIP_REASON_NEWER_TARGET);
+        }
+    }
+
+    let time_writing_files = Instant::now();
+    if !target_dir_tree.dir_tree.is_empty() && !is_streaming {
+        // Iterate files and either copy/move to subdirs as necessary
+        // or do a dry run to simulate a copy/move pass
+        process_target_dir_files(
+            &mut target_dir_tree,
+            &args,
+            &stats,
+            &padder,
+        );
+    }
+
+    // Remove the source subfolders which were emptied by moving their files
+    if !args.dry_run && !args.copy_not_move && args.remove_empty_source_dirs {
+        remove_empty_source_dirs(&args);
+    }
+
+    // Mark the target folder as an archive, so that it's protected in future runs
+    if !args.dry_run && args.protect_target && args.target_dir.exists() {
+        if let Err(e) = mark_as_archive(&args.target_dir) {
+            println!("{} Could not create the archive marker file in {}: {}",
+                     ColoredString::warn_arrow(), args.target_dir.display(), e);
+        }
+    }
+
+    // Create recovery data for each date folder written in this run, which only covers all of its files if the run was complete
+    if !args.dry_run && args.recovery_redundancy > 0 && !target_dir_tree.dir_tree.is_empty() && !is_interrupted() {
+        create_recovery_data(&target_dir_tree, &args);
+    }
+
+    // Set the modified time of target folders last, since writing anything inside them would change it again
+    if !args.dry_run && args.target_dir_timestamps != TargetDirTimestamps::Unchanged {
+        set_target_dir_timestamps(&target_dir_tree, &args);
+    }
+
+    // Save the plan of a dry run, so that writing the files can be refused if it changes,
+    // or forget it once it was written, so that the next runs don't depend on it
+    if let Some(plan_lock) = plan_lock {
+        let plan_result = if args.dry_run {
+            plan_lock.save(&args.plan_lock_path)
+        } else {
+            PlanLock::remove(&args.plan_lock_path)
+        };
+        match plan_result {
+            Ok(_) if args.dry_run => {
+                println!("The plan of this dry run was saved. The files will only be written if the plan doesn't change.");
+                println!();
+            }
+            Ok(_) => {}
+            Err(e) => println!("{} Could not update the plan in {}: {}",
+                               ColoredString::warn_arrow(), args.plan_lock_path.display(), e),
+        }
+    }
+
+    // Remember when the sources were read, so that the next run can read only the files added since
+    if !args.dry_run && !stats.has_write_errors() && !is_interrupted() {
+        if let Err(e) = save_last_run_times(&args.target_dir, source_names.iter(), run_start_time) {
+            println!("{} Could not save the time of this run in {}: {}",
+                     ColoredString::warn_arrow(), args.target_dir.display(), e);
+        }
+    }
+
+    // Remember the state of the sources, so that the next run can exit early if nothing changed
+    if let Some(source_snapshot) = source_snapshot.filter(|_| !args.dry_run && !stats.has_write_errors() && !is_interrupted()) {
+        if let Err(e) = save_source_snapshot(&args.target_dir, &source_snapshot) {
+            println!("{} Could not save the snapshot of the source folders in {}: {}",
+                     ColoredString::warn_arrow(), args.target_dir.display(), e);
+        }
+    }
+
+    // Record time taken
+    // Dirs fetching occurs before confirmation, while start time starts after confirmation
+    stats.set_time_write_files(time_writing_files.elapsed());
+    stats.set_time_total(time_processing.elapsed() + stats.get_time_fetch_dirs() + stats.get_time_parse_files());
+
+    // Print corrupt files
+    if !target_dir_tree.corrupt_files.is_empty() {
+        let action = match args.corrupt_files {
+            CorruptFilesAction::Skip => String::from("skipped"),
+            _ => format!("sorted into the '{}' folder", DEFAULT_CORRUPT_DIR_NAME),
+        };
+        println!("{}", ColoredString::red(format!("These files appear to be corrupt ({}):", action).as_str()));
+        target_dir_tree.corrupt_files
+            .iter()
+            .for_each(|file_path| println!("  {}", file_path));
+        println!();
+    }
+
+    // Print unreadable files
+    if !target_dir_tree.unreadable_files.is_empty() {
+        let action = match args.unreadable_files {
+            UnreadableFilesAction::Skip => String::from("skipped"),
+            _ => format!("empty files sorted into the '{}' folder, the others skipped", DEFAULT_QUARANTINE_DIR_NAME),
+        };
+        println!("{}", ColoredString::red(format!("These files could not be read ({}):", action).as_str()));
+        target_dir_tree.unreadable_files
+            .iter()
+            .for_each(|file_path| println!("  {}", file_path));
+        println!();
+    }
+
+    // Print target files which would be overwritten even though they're newer or larger
+    if !newer_targets.is_empty() && args.dry_run {
+        println!("{}", ColoredString::red(
+            "These target files are newer or larger than the source files which would overwrite them \
+            (you will be asked to confirm before overwriting them):"));
+        newer_targets
+            .iter()
+            .for_each(|(_, description)| println!("  {}", description));
+        println!();
+    }
+
+    // Print probable duplicates
+    if !target_dir_tree.probable_duplicates.is_empty() {
+        println!("{}", ColoredString::orange(format!(
+            "These files are probable duplicates and were sorted into the '{}' folder for review:",
+            DEFAULT_PROBABLE_DUPLICATES_DIR_NAME).as_str()));
+        target_dir_tree.probable_duplicates
+            .iter()
+            .for_each(|duplicate| println!("  {}", duplicate));
+        println!();
+    }
+
+    // Print duplicate source files kept for review
+    if !target_dir_tree.duplicate_sources.is_empty() {
+        println!("{}", ColoredString::orange(format!(
+            "These files have the same name and contents as another source file and were sorted into the '{}' folder for review:",
+            DEFAULT_DUPLICATES_DIR_NAME).as_str()));
+        target_dir_tree.duplicate_sources
+            .iter()
+            .for_each(|duplicate| println!("  {}", duplicate));
+        println!();
+    }
+
+    // Print files whose target file already exists, kept for review
+    if !target_dir_tree.review_targets.is_empty() {
+        println!("{}", ColoredString::orange(format!(
+            "These files have the same name as a different file already in the target folder and were sorted into the '{}' folder for review:",
+            DEFAULT_DUPLICATES_DIR_NAME).as_str()));
+        target_dir_tree.review_targets
+            .iter()
+            .for_each(|duplicate| println!("  {}", duplicate));
+        println!();
+    }
+
+    // Print files renamed because another file with the same name is written to the same folder or already exists
+    if !target_dir_tree.renamed_files.is_empty() {
+        println!("{}", ColoredString::orange(
+            "These files have the same name as a different file written to the same folder or already in it, so they were renamed:"));
+        target_dir_tree.renamed_files
+            .iter()
+            .for_each(|renamed| println!("  {}", renamed));
+        println!();
+    }
+
+    // Print files which weren't written since a different file with the same name is already in the target folder
+    if !target_dir_tree.different_targets.is_empty() {
+        println!("{}", ColoredString::orange(format!(
+            "A different file with the same name already exists in the target folder, so these files were skipped \
+            (use `{} rename` to keep both):", ON_COLLISION_CLI_FLAG).as_str()));
+        target_dir_tree.different_targets
+            .iter()
+            .for_each(|different| println!("  {}", different));
+        println!();
+    }
+
+    // When moving, the source files which are already in the target folder are left in place, but aren't needed anymore
+    if !target_dir_tree.identical_targets.is_empty() && !args.copy_not_move {
+        println!("{}", ColoredString::orange(
+            "These files were not moved, since an identical file already exists in the target folder, so they can be safely deleted:"));
+        target_dir_tree.identical_targets
+            .iter()
+            .for_each(|identical| println!("  {}", identical));
+        println!();
+    }
+
+    // Print photos skipped as copies of another photo without EXIF data
+    if !target_dir_tree.exifless_twins.is_empty() {
+        println!("{}", ColoredString::orange(
+            "These files have no EXIF data and are most likely copies of another photo from the same date, so they were skipped:"));
+        target_dir_tree.exifless_twins
+            .iter()
+            .for_each(|twin| println!("  {}", twin));
+        println!();
+    }
+
+    // Print files whose extension doesn't match their contents
+    if !target_dir_tree.content_type_warnings.is_empty() {
+        println!("{}", ColoredString::orange(
+            "Warning: these files have an extension which doesn't match their contents and were sorted based on their contents:"));
+        target_dir_tree.content_type_warnings
+            .iter()
+            .for_each(|warning| println!("  {}", warning));
+        println!();
+    }
+
+    // Print unknown extensions
+    if !target_dir_tree.unknown_extensions.is_empty() {
+        println!("Skipped files with these unknown extensions: {}",
+                 target_dir_tree.unknown_extensions
+                     .into_iter()
+                     .map(|s|format!("'{}'", s))
+                     .collect::<Vec<String>>().join(", "));
+        println!();
+    }
+
+    // Print unknown extensions
+    if !args.non_custom_device_names.is_empty() {
+        println!("Device models with non-custom names: {}",
+                 args.non_custom_device_names
+                     .iter()
+                     .map(|s|format!("'{}'", s))
+                     .collect::<Vec<String>>().join(", "));
+        println!();
+    }
+
+    // Print final stats
+    stats.print_stats(&args);
+
+    if let Some(metrics_report_path) = &args.metrics_report_path {
+        match stats.save_metrics_report(metrics_report_path) {
+            Ok(_) => println!("Saved the metrics of this run to {}", metrics_report_path.display()),
+            Err(e) => println!("{} Could not save the metrics of this run to {}: {}",
+                               ColoredString::warn_arrow(), metrics_report_path.display(), e),
+        }
+    }
+
+    // The user stopped the program, so don't wait for another confirmation, and don't sort any other watched files
+    if is_interrupted() {
+        println!();
+        println!("{}", ColoredString::red(
+            "The run was stopped with Ctrl+C, so some files may not have been written. The stats above only include the files written so far."));
+        println!("Run the program again to resume it, or run `imgsorter {}` to roll it back.", Command::Undo.name());
+        process::exit(INTERRUPTED_EXIT_CODE);
+    }
+
+    // Ask user input to prevent console window from closing before reading output.
+    // The `watch` command keeps running after sorting each batch of files
+    if !args.silent {
+        ask_for_exit_confirmation();
+    } else if args.command != Command::Watch {
+        println!("> Silent mode is enabled. Exiting without user confirmation.");
+    }
+
+    Ok(())
+}
+
+/// Check if the last run into the target folder was interrupted, e.g. by closing the program or a power cut,
+/// and ask whether to resume it, roll it back or ignore it. Silent runs only print a warning.
+/// Returns false if the program should exit, i.e. after rolling back the interrupted run
+fn check_interrupted_run(args: &mut Args) -> Result<bool, std::io::Error> {
+    let (journal_path, run_marker) = match find_interrupted_run(&args.target_dir) {
+        Some(interrupted_run) => interrupted_run,
+        None => return Ok(true),
+    };
+
+    println!("{}", ColoredString::orange(format!(
+        "A previous run into {} was interrupted, it started at {}.",
+        args.target_dir.display(), run_marker.started).as_str()));
+
+    if args.silent {
+        println!("> Silent mode is enabled. Continuing, run `imgsorter {}` to roll back the interrupted run.",
+                 Command::Undo.name());
+        println!();
+        return Ok(true);
+    }
+
+    match ask_for_interrupted_run_action() {
+        // Runs of the `apply` command are resumed by applying the same plan again, and other runs by sorting their sources again
+        InterruptedRunAction::Resume if run_marker.plan != args.applied_plan_path => {
+            match &run_marker.plan {
+                Some(plan_path) => println!("The interrupted run applied the plan {}, run `imgsorter {} {}` to resume it. Exiting.",
+                                            plan_path.display(), Command::Apply.name(), plan_path.display()),
+                None => println!("The interrupted run sorted its source folders, run the program again without `{}` to resume it. Exiting.",
+                                 Command::Apply.name()),
+            }
+            Ok(false)
+        }
+        InterruptedRunAction::Resume => {
+            if run_marker.plan.is_some() {
+                args.resumed_journal_path = Some(journal_path);
+            } else {
+                args.set_resumed_sources(run_marker.source_dirs, run_marker.source_files);
+            }
+            clear_interrupted_run(&args.target_dir)?;
+            println!("Resuming the interrupted run, the files it already wrote will be skipped.");
+            println!();
+            Ok(true)
+        }
+        InterruptedRunAction::RollBack => {
+            undo_run(&args.target_dir, &journal_path, args.import_index_path.as_deref(), true)?;
+            clear_interrupted_run(&args.target_dir)?;
+            Ok(false)
+        }
+        InterruptedRunAction::Ignore => {
+            clear_interrupted_run(&args.target_dir)?;
+            println!();
+            Ok(true)
+        }
+    }
+}
+
+fn ask_for_interrupted_run_action() -> InterruptedRunAction {
+    println!("{}",
+             ColoredString::magenta(
+                 "What should be done with the interrupted run? Type one of the options then press Enter:\n\
+                 • 'r' or 'resume' to sort its source folders again, skipping the files it already wrote\n\
+                 • 'b' or 'back' to roll it back, i.e. move the files back and remove the copies, then exit\n\
+                 • 'i' or 'ignore' to continue with the current source folders"));
+    loop {
+        let mut user_input = String::new();
+        match io::stdin().read_line(&mut user_input) {
+            // There's no more input, e.g. if it was piped from another program
+            Ok(0) => return InterruptedRunAction::Ignore,
+            Ok(_) => {}
+            Err(err) => {
+                eprintln!("Error reading user input: {:?}", err);
+                return InterruptedRunAction::Ignore;
+            }
+        }
+        match user_input.trim().to_lowercase().as_str() {
+            "r" | "resume" => return InterruptedRunAction::Resume,
+            "b" | "back" => return InterruptedRunAction::RollBack,
+            "i" | "ignore" => return InterruptedRunAction::Ignore,
+            _ => println!("...press one of 'r/resume', 'b/back' or 'i/ignore', then Enter"),
+        }
+    }
 }
 
-// TODO 5e: find better name
-impl SupportedFile {
-    // TODO 10a - replace with parse_from_ref
-    pub fn parse_from(dir_entry: DirEntry, args: &mut Args) -> SupportedFile {
-        let extension = get_extension(&dir_entry);
-        let file_type = get_file_type(&extension, args);
-        let metadata = dir_entry.metadata().unwrap();
+/// Keep watching the source folders and sort the files added to them, until the program is stopped.
+/// The files are sorted in batches, once no other files were added for [Args::watch_debounce_seconds],
+/// each batch like a separate `sort` run. The files already in the source folders are not sorted.
+/// Once the target folder exists, the names of its files are kept in a [imgsorter::target_index::TargetIndex], updated from
+/// its filesystem events, so that each batch doesn't list the target folders again
+fn run_watch(mut args: Args) -> Result<(), std::io::Error> {
+    let source_dirs: Vec<PathBuf> = args.source_dirs
+        .iter()
+        .filter_map(|source_dirs| source_dirs.first().cloned())
+        .collect();
+
+    let watcher = match SourceWatcher::new(&source_dirs, args.source_recursive, &args.target_dir) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            println!("{}", ColoredString::red(format!("Could not watch the source folders: {}. Exiting.", e).as_str()));
+            return Ok(());
+        }
+    };
 
-        let exif_data = match file_type {
-            // It's much faster if we only try to read EXIF for image files
-            FileType::Image => {
-                // Use kamadak-rexif crate
-                read_kamadak_exif_date_and_device(&dir_entry, args)
-                // Use rexif crate
-                // read_exif_date_and_device(&dir_entry, args)
+    println!("{}", build_source_dirs_list_string(&args));
+    println!("Target directory:   {}", &args.target_dir.display());
+    println!();
+
+    let debounce = Duration::from_secs(args.watch_debounce_seconds);
+    let mut target_watcher: Option<TargetWatcher> = None;
+    let mut is_target_watch_failed = false;
+    loop {
+        // The target folder may only be created by the first batch, so it's watched as soon as it exists
+        if target_watcher.is_none() && !is_target_watch_failed && args.target_dir.is_dir() {
+            match TargetWatcher::new(&args.target_dir) {
+                Ok(watcher) => {
+                    args.target_index = Some(Arc::clone(&watcher.index));
+                    target_watcher = Some(watcher);
+                }
+                Err(e) => {
+                    println!("{} Could not watch the target folder, so its folders will be listed for each batch: {}",
+                             ColoredString::warn_arrow(), e);
+                    is_target_watch_failed = true;
+                }
             }
-            _ => ExifDateDevice::new(),
-        };
+        }
 
-        // Replace EXIF camera model with a custom name, if one was defined in config
-        let device_name: DirEntryType = match &exif_data.get_device_name(args.include_device_make) {
-            Some(camera_model) =>
-                args
-                    .custom_device_names
-                    .get(camera_model.to_lowercase().as_str())
-                    .map_or(
-                        {
-                            args.non_custom_device_names.insert(camera_model.clone());
-                            DirEntryType::Directory(camera_model.clone())
-                        },
-                        |custom_camera_name| DirEntryType::Directory(custom_camera_name.clone())
-                    ),
-            None if args.always_create_device_subdirs =>
-                DirEntryType::Directory(DEFAULT_UNKNOWN_DEVICE_DIR_NAME.to_string()),
-            None =>
-                DirEntryType::Files,
-        };
+        println!("{}", ColoredString::bold_white("Watching the source folders for new files, press Ctrl+C to stop..."));
 
-        // Read image date - prefer EXIF tags over system date
-        let date_str = {
-            exif_data.date
-                .unwrap_or_else(|| get_system_modified_date(&metadata)
-                    .unwrap_or_else(|| DEFAULT_NO_DATE_STR.to_string()))
+        let mut new_files = match watcher.next_batch(debounce) {
+            Ok(new_files) => new_files,
+            Err(e) => {
+                println!("{}", ColoredString::red(format!("Could not watch the source folders: {}. Exiting.", e).as_str()));
+                return Ok(());
+            }
         };
+        new_files.retain(|new_file| !args.is_in_excluded_dir(new_file));
+        if new_files.is_empty() {
+            continue;
+        }
 
-        SupportedFile {
-            file_name: dir_entry.file_name(),
-            file_path: dir_entry.path(),
-            file_type,
-            extension,
-            date_str,
-            metadata,
-            device_name,
+        println!();
+        println!("{} new files found at {}", new_files.len(), Local::now().format("%H:%M:%S"));
+        if let Err(e) = run_sort(args.for_watched_files(new_files)) {
+            println!("{} Could not sort the new files: {}", ColoredString::warn_arrow(), e);
         }
+        println!();
     }
+}
 
-    // TODO 10a - almost-duplicate of parse_from, keep this one
-    pub fn parse_from_ref(dir_entry: &DirEntry, args: &Args) -> (SupportedFile, HashSet<String>) {
-        let extension = get_extension(&dir_entry);
-        let file_type = get_file_type(&extension, args);
-        let metadata = dir_entry.metadata().unwrap();
-
-        let exif_data = match file_type {
-            // It's much faster if we only try to read EXIF for image files
-            FileType::Image => {
-                // Use kamadak-rexif crate
-                read_kamadak_exif_date_and_device(&dir_entry, args)
-                // Use rexif crate
-                // read_exif_date_and_device(&dir_entry, args)
-            }
-            _ => ExifDateDevice::new(),
-        };
-
-        let mut non_custom_device_names: HashSet<String> = HashSet::new();
+/// Remove the source subfolders left empty after moving their files. The deepest folders are removed first,
+/// so that folders which only contained empty subfolders are removed too. Only the subfolders which were read
+/// are removed: the configured source folders themselves and the target folder are never removed
+fn remove_empty_source_dirs(args: &Args) {
+    let target_dir = args.target_dir.canonicalize().unwrap_or_else(|_| args.target_dir.clone());
 
-        // Replace EXIF camera model with a custom name, if one was defined in config
-        let device_name: DirEntryType = match &exif_data.get_device_name(args.include_device_make) {
-            Some(camera_model) =>
-                args
-                    .custom_device_names
-                    .get(camera_model.to_lowercase().as_str())
-                    .map_or(
-                        {
-                            non_custom_device_names.insert(camera_model.clone());
-                            DirEntryType::Directory(camera_model.clone())
-                        },
-                        |custom_camera_name| DirEntryType::Directory(custom_camera_name.clone())
-                    ),
-            None if args.always_create_device_subdirs =>
-                DirEntryType::Directory(DEFAULT_UNKNOWN_DEVICE_DIR_NAME.to_string()),
-            None =>
-                DirEntryType::Files,
-        };
+    let mut source_subdirs: Vec<&PathBuf> = args.source_dirs
+        .iter()
+        .flat_map(|source_dirs| source_dirs.iter().skip(1))
+        .filter(|source_dir| !source_dir
+            .canonicalize()
+            .is_ok_and(|source_dir| source_dir.starts_with(&target_dir)))
+        .collect();
+    source_subdirs.sort_by_key(|source_dir| std::cmp::Reverse(source_dir.components().count()));
+
+    let mut removed_count = 0;
+    let mut failed_dirs: Vec<String> = Vec::new();
+    for source_dir in source_subdirs {
+        match remove_empty_dir(source_dir) {
+            RemoveDirResult::Removed => removed_count += 1,
+            RemoveDirResult::NotEmpty | RemoveDirResult::Missing => {}
+            RemoveDirResult::Failed(e) => failed_dirs.push(format!("{} ({})", source_dir.display(), e)),
+        }
+    }
 
-        // Read image date - prefer EXIF tags over system date
-        let date_str = {
-            exif_data.date
-                .unwrap_or_else(|| get_system_modified_date(&metadata)
-                    .unwrap_or_else(|| DEFAULT_NO_DATE_STR.to_string()))
-        };
+    if removed_count > 0 {
+        println!("Removed {} empty source {}.", removed_count, if removed_count == 1 { "folder" } else { "folders" });
+        println!();
+    }
 
-        (
-            SupportedFile {
-            file_name: dir_entry.file_name(),
-            file_path: dir_entry.path(),
-            file_type,
-            extension,
-            date_str,
-            metadata,
-            device_name,
-            },
-            non_custom_device_names
-        )
+    if !failed_dirs.is_empty() {
+        println!("{}", ColoredString::orange(
+            "These source folders are empty, but could not be removed (they might be open in another program):"));
+        failed_dirs
+            .iter()
+            .for_each(|failed_dir| println!("  {}", failed_dir));
+        println!();
     }
+}
 
-    pub fn is_dir(&self) -> bool {
-        self.metadata.is_dir()
+/// Re-hash all files in the archive and compare them with the stored checksum manifests.
+/// Exits with a non-zero code if the archive folder doesn't exist, or if any file is corrupted, missing or unreadable.
+fn run_verify(archive_dir: &Path) -> Result<(), std::io::Error> {
+    if !archive_dir.is_dir() {
+        println!("{}", ColoredString::red(
+            format!("Target folder does not exist: {}", archive_dir.display()).as_str()));
+        process::exit(1);
     }
 
-    pub fn get_file_name_str(&self) -> String {
-        String::from(self.file_name.to_str().unwrap())
+    println!("Verifying files in {}...", archive_dir.display());
+    println!();
+
+    let report = verify_archive(archive_dir, false)?;
+
+    println!();
+    report.print_summary();
+
+    if report.manifests == 0 {
+        println!("{} No checksum manifests found. Enable the `write_checksums` option to create them when copying or moving files.",
+                 ColoredString::warn_arrow());
     }
 
-    /// Return a string representation of the source file or path.
-    /// If there are multiple sources, return the full absolute path
-    /// If there is a single source, return only the filename,
-    /// since the full path will always be the same
-    pub fn get_source_display_name_str(&self, args: &Args) -> String {
-        if args.has_multiple_sources() {
-            self.file_path.display().to_string()
-        } else {
-            self.file_name.to_str().unwrap().to_string()
-        }
+    if report.has_errors() {
+        process::exit(1);
     }
+
+    Ok(())
 }
 
-/// The main program body. This is an overview of the main flows:
-/// * parse config file and set up args/run options
-/// * read list of files in all source dirs
-/// * ask for operation confirmation - dry run or write files
-/// * parse source files and build a model of the destination dir structure (this is where the sorting occurs)
-///   * if dry run, filter only source unique files
-///   * parse files and sort them into the internal target dir model
-///   * isolate one-off images
-///   * calculate max file and path lengths for dry run output padding
-/// * process files based on the destination dir model
-///   * if dry run, only show target dir structure and potential copy status of each file
-///   * if write, copy the files to the destination; if move is enabled, delete source files after copy
-/// * print stats and exit
-fn main() -> Result<(), std::io::Error> {
+/// Write the default config file into the folder, unless it already contains one
+fn run_init(config_dir: &Path) -> Result<(), std::io::Error> {
+    let config_path = config_dir.join(CONFIG_FILE_NAME);
+    if config_path.exists() {
+        println!("{}", ColoredString::red(
+            format!("A config file already exists at {}, it was not changed. Exiting.", config_path.display()).as_str()));
+        return Ok(());
+    }
 
-    println!("───────────────────────────────────────────────────────────────────────────");
-    println!("                             IMGSORTER v{versn}                            ", versn = VERSION);
-    println!("───────────────────────────────────────────────────────────────────────────");
+    fs::create_dir_all(config_dir)?;
+    fs::write(&config_path, DEFAULT_CONFIG_FILE)?;
+    println!("Created the config file {}", config_path.display());
+    println!("Set at least `source_dirs` and `target_dir`, then run `imgsorter {}` to see what would be done.",
+             Command::DryRun.name());
 
-    let mut args = Args::new_from_toml("imgsorter.toml")?;
+    Ok(())
+}
 
-    let mut stats = FileStats::new();
+/// Generate a synthetic library of photos inside a temporary folder, sort it with a dry run, then copy it twice
+/// into a target folder next to it, and check the result of each step, see [imgsorter::selftest].
+/// The temporary folder is removed if all checks passed, otherwise it's kept to find out what went wrong.
+/// Exits with a non-zero code if any check failed
+fn run_selftest() -> Result<(), std::io::Error> {
+    let selftest_dir = env::temp_dir().join(format!("{}-{}", SELFTEST_DIR_PREFIX, Local::now().format("%Y%m%d-%H%M%S")));
+    let source_dir = selftest_dir.join("library");
+    let target_dir = selftest_dir.join("sorted");
+
+    println!("Generating a synthetic library in {}", source_dir.display());
+    let synthetic_files = generate_synthetic_library(&source_dir)?;
+    let source_hashes = hash_folder_photos(&source_dir)?;
+
+    let mut report = SelftestReport::new();
+    if let Err(e) = run_selftest_steps(&synthetic_files, &source_hashes, &source_dir, &target_dir, &mut report) {
+        report.add_error("Sorting the synthetic library", &e);
+    }
 
-    if args.verbose { dbg!(&args); }
+    println!();
+    report.print_summary();
 
-    // Needs to be created after checking for recursive source dirs,
-    // since we need to pass args.has_multiple_sources()
-    let mut padder = Padder::new(args.has_multiple_sources());
+    if report.has_failures() {
+        println!("The synthetic library and the sorted files were kept in {}", selftest_dir.display());
+        process::exit(1);
+    }
 
-    /*****************************************************************************/
-    /* ---                        Read source files                          --- */
-    /*****************************************************************************/
+    if let Err(e) = fs::remove_dir_all(&selftest_dir) {
+        println!("{} Could not remove the temporary folder {}: {}",
+                 ColoredString::warn_arrow(), selftest_dir.display(), e);
+    }
 
-    let time_fetching_files = Instant::now();
+    Ok(())
+}
 
-    // TODO 5g: instead of Vec<Vec<DirEntry>>, return a `SourceDirTree` struct
-    //   which wraps the Vec's but contains additional metadata, such as no of files or total size
-    // TODO 5p: make this multi-threaded
-    // Read dir contents and filter out error results
-    let source_files: BTreeMap<String, Vec<DirEntry>> = args
-        .source_dirs
-        .iter()
-        .map(|src_dir_vec| {
-            let parent_dir_name = src_dir_vec[0].display().to_string();
-            let dir_contents = src_dir_vec
-                .iter()
-                .filter_map(|src_dir|
-                    read_supported_files(src_dir, &mut stats, &args).ok())
-                .flatten()
-                .collect::<Vec<_>>();
-            (parent_dir_name, dir_contents)
-        })
-        .collect::<BTreeMap<_, _>>();
+fn run_selftest_steps(
+    synthetic_files: &[SyntheticFile],
+    source_hashes: &FolderHashes,
+    source_dir: &Path,
+    target_dir: &Path,
+    report: &mut SelftestReport,
+) -> Result<(), std::io::Error> {
+    let print_step = |step: &str| {
+        println!();
+        println!("{}", ColoredString::bold_white(format!("Selftest: {}", step).as_str()));
+    };
 
-    stats.set_time_fetch_files(time_fetching_files.elapsed());
+    print_step("dry run of the synthetic library");
+    run_sort(Args::for_selftest(source_dir, target_dir, Command::DryRun)?)?;
+    let created_target = if target_dir.exists() { vec![format!("{} was created", target_dir.display())] } else { Vec::new() };
+    report.add("The dry run didn't create the target folder", created_target);
+    report.add("The dry run didn't change the source files",
+               compare_folder_hashes(source_hashes, &hash_folder_photos(source_dir)?));
+
+    print_step("copying the synthetic library");
+    run_sort(Args::for_selftest(source_dir, target_dir, Command::Sort)?)?;
+    let target_hashes = hash_folder_photos(target_dir)?;
+    report.add("Copying didn't change the source files",
+               compare_folder_hashes(source_hashes, &hash_folder_photos(source_dir)?));
+    report.add("Every source file was copied", find_missing_targets(source_hashes, &target_hashes));
+    report.add("Every copied file is identical to its source file", find_unknown_targets(source_hashes, &target_hashes));
+    report.add("Identical source files were copied only once", find_repeated_targets(&target_hashes));
+    report.add("Every file is in the folder of its date and device",
+               find_misplaced_targets(synthetic_files, source_hashes, &target_hashes));
+
+    print_step("copying the synthetic library again");
+    run_sort(Args::for_selftest(source_dir, target_dir, Command::Sort)?)?;
+    report.add("Copying the same files again didn't change the target folder",
+               compare_folder_hashes(&target_hashes, &hash_folder_photos(target_dir)?));
 
-    /*****************************************************************************/
-    /* ---                 Print options before confirmation                 --- */
-    /*****************************************************************************/
+    Ok(())
+}
 
-    // TODO 5l: use this in parse_source_dirs methods instead of recalculating it
-    let source_files_count: usize = source_files.values().map(|d|d.len()).sum();
+/// Reverse the last run which wrote files into the target folder, as recorded in its journal:
+/// moved files are moved back to their source folders, copied files are removed
+/// and the folders created by the run are removed if they're empty.
+/// The written files are also removed from the import index, if given, so that they're imported again
+fn run_undo(target_dir: &Path, import_index_path: Option<&Path>, silent: bool) -> Result<(), std::io::Error> {
+    match find_last_journal(target_dir) {
+        Some(journal_path) => undo_run(target_dir, &journal_path, import_index_path, silent),
+        None => {
+            println!("{}", ColoredString::red(
+                format!("No run to undo was found in {}", target_dir.display()).as_str()));
+            Ok(())
+        }
+    }
+}
 
-    // Exit early if there are no source files
-    if source_files_count < 1 {
-        println!("{}", ColoredString::red("There are no supported files in the current source(s), exiting."));
+/// Reverse the run recorded in the journal, after asking for confirmation unless silent, see [run_undo]
+fn undo_run(target_dir: &Path, journal_path: &Path, import_index_path: Option<&Path>, silent: bool) -> Result<(), std::io::Error> {
+    let entries = read_journal(journal_path)?;
+    let moved_count = entries.iter().filter(|entry| matches!(entry, JournalEntry::Move { .. })).count();
+    let copied_count = entries.iter().filter(|entry| matches!(entry, JournalEntry::Copy { .. })).count();
+
+    println!("Last run into {}: {} moved and {} copied {}, journal {}",
+             target_dir.display(),
+             moved_count,
+             copied_count,
+             if moved_count + copied_count == 1 { "file" } else { "files" },
+             journal_path.file_name().unwrap_or_default().to_string_lossy());
+
+    if !silent && !ask_for_undo_confirmation() {
+        println!("Nothing was changed. Exiting.");
         return Ok(());
     }
 
-    {
-        let write_op = if args.copy_not_move {
-            ColoredString::orange("copied:")
-        } else {
-            ColoredString::red("moved: ")
-        };
-
-        // Build the string used for printing source directory name(s) before confirmation
-        let source_dirs_list: String = build_source_dirs_list_string(&args);
+    // The file being moved back is completed if the program is stopped with Ctrl+C
+    start_writing();
+    let report = undo_journal(journal_path);
+    stop_writing();
+    let report = report?;
 
-        println!("═══════════════════════════════════════════════════════════════════════════");
-        println!("{}", source_dirs_list);
-        println!("Target directory:   {}", &args.target_dir.display());
-        println!("Files to be {} {}", write_op, source_files_count);
-        println!("═══════════════════════════════════════════════════════════════════════════");
-        // TODO 1f: print all options for this run?
+    // The sources changed back, so the next run shouldn't skip them
+    if let Err(e) = remove_source_snapshot(target_dir) {
+        println!("{} Could not remove the snapshot of the source folders: {}", ColoredString::warn_arrow(), e);
     }
-
-    // Proceed only if silent is enabled or user confirms, otherwise exit
-    if args.silent {
-        println!("> Silent mode is enabled. Proceeding without user confirmation.");
-        if args.dry_run {
-            println!("> This is a dry run. No folders will be created. No files will be copied or moved.");
-        }
-    } else {
-        match ask_for_op_confirmation(&args) {
-            ConfirmationType::Cancel => {
-                println!("Cancelled by user, exiting.");
-                return Ok(());
-            }
-            ConfirmationType::Error => {
-                println!("Error confirming, exiting.");
-                return Ok(());
-            }
-            ConfirmationType::DryRun => {
-                println!("This is a dry run. No folders will be created. No files will be copied or moved.");
-                args.dry_run = true;
-            }
-            ConfirmationType::Proceed =>
-                args.dry_run = false
+    if let Err(e) = remove_last_run_times(target_dir) {
+        println!("{} Could not remove the time of the last run: {}", ColoredString::warn_arrow(), e);
+    }
+    if let Some(import_index_path) = import_index_path {
+        let targets: HashSet<PathBuf> = entries
+            .iter()
+            .filter_map(|entry| match entry {
+                JournalEntry::Copy { target, .. } | JournalEntry::Move { target, .. } => Some(target.clone()),
+                JournalEntry::CreateDir { .. } => None,
+            })
+            .collect();
+        if let Err(e) = remove_imported_files(import_index_path, &targets) {
+            println!("{} Could not remove the undone files from the import index {}: {}",
+                     ColoredString::warn_arrow(), import_index_path.display(), e);
         }
     }
 
-    let time_processing = Instant::now();
-
-    println!("–––––––––––––––––––––––––––––––––––––––––––––––––––––––––––––––––––––––––––");
     println!();
+    println!("{} files moved back to their source folders", report.files_restored);
+    println!("{} copied files removed", report.copies_removed);
+    println!("{} empty folders removed", report.dirs_removed);
 
-    /*****************************************************************************/
-    /* ---        Parse source files and copy/paste or dry run them          --- */
-    /*****************************************************************************/
+    if !report.errors.is_empty() {
+        println!();
+        println!("{}", ColoredString::orange(
+            format!("{} files or folders could not be restored:", report.errors.len()).as_str()));
+        report.errors
+            .iter()
+            .for_each(|error| println!("  {}", ColoredString::orange(error.as_str())));
+    }
 
-    // TODO 5j: prefilter for Images and Videos only
-    // Iterate files, read modified date and create subdirs
-    // Copy images and videos to subdirs based on modified date
-    let time_parsing_files = Instant::now();
+    if is_interrupted() {
+        println!();
+        println!("{}", ColoredString::red("The undo was stopped with Ctrl+C before all files were restored."));
+        println!("Run `imgsorter {}` again to restore the remaining files.", Command::Undo.name());
+        process::exit(INTERRUPTED_EXIT_CODE);
+    }
+    if !report.errors.is_empty() {
+        process::exit(1);
+    }
 
-    let mut target_dir_tree = if args.max_threads == 1 {
-        // TODO 10a: this should no longer be necessary
-        parse_source_dirs(source_files, &mut args, &mut stats, &mut padder)
-    } else {
-        parse_source_dirs_threaded(source_files, &mut args, &mut stats, &mut padder)
+    Ok(())
+}
+
+/// Execute a plan saved by an earlier dry run, but only if all of its operations can still be done as planned.
+/// The files are written the same way as by a normal run, so they're recorded in the journal, the operation log
+/// and the import index, and their checksums are saved, if configured. An interrupted run of the same plan
+/// is resumed by leaving out the files it already wrote
+fn run_apply(plan_path: &Path, mut args: Args) -> Result<(), std::io::Error> {
+    let mut plan = match ExportedPlan::read(plan_path) {
+        Ok(plan) => plan,
+        Err(e) => {
+            println!("{}", ColoredString::red(
+                format!("Could not read the plan in {}: {}. Exiting.", plan_path.display(), e).as_str()));
+            process::exit(1);
+        }
     };
 
-    stats.set_time_parse_files(time_parsing_files.elapsed());
+    let source_files: Vec<PathBuf> = plan.operations.iter().map(|operation| operation.source.clone()).collect();
+    args.set_applied_plan(plan_path, &plan.target_dir, source_files);
+    args.silent = has_silent_cli_flag();
+    args.target_max_file_size = get_max_file_size(&args.target_dir);
 
-    let time_writing_files = Instant::now();
-    if !target_dir_tree.dir_tree.is_empty() {
-        // Iterate files and either copy/move to subdirs as necessary
-        // or do a dry run to simulate a copy/move pass
-        process_target_dir_files(
-            &mut target_dir_tree,
-            &args,
-            &mut stats,
-            &mut padder,
-        );
+    if !check_interrupted_run(&mut args)? {
+        return Ok(());
+    }
+    if let Some(journal_path) = &args.resumed_journal_path {
+        let completed_targets: HashSet<PathBuf> = read_journal(journal_path)?
+            .into_iter()
+            .filter_map(|entry| match entry {
+                JournalEntry::Copy { target, .. } | JournalEntry::Move { target, .. } => Some(target),
+                JournalEntry::CreateDir { .. } => None,
+            })
+            .collect();
+        plan.skip_completed(&completed_targets);
+    }
+
+    let moved_count = plan.operations.iter().filter(|operation| operation.is_move).count();
+    let copied_count = plan.operations.len() - moved_count;
+
+    println!("Plan saved by imgsorter v{} for {}: {} moved and {} copied {}",
+             plan.version,
+             plan.target_dir.display(),
+             moved_count,
+             copied_count,
+             if moved_count + copied_count == 1 { "file" } else { "files" });
+
+    // The plan is executed as it is, so don't write anything if any part of it changed
+    let problems = plan.validate();
+    if !problems.is_empty() {
+        println!();
+        println!("{}", ColoredString::red(
+            format!("{} files can no longer be written as planned:", problems.len()).as_str()));
+        problems
+            .iter()
+            .for_each(|problem| println!("  {}", problem));
+        println!("Run a new dry run to save an updated plan. Nothing was changed. Exiting.");
+        process::exit(1);
     }
 
-    // Record time taken
-    // Dirs fetching occurs before confirmation, while start time starts after confirmation
+    // Each operation of the plan either copies or moves its file
+    let mut copy_args = args.clone();
+    copy_args.copy_not_move = true;
+    let mut move_args = args;
+    move_args.copy_not_move = false;
+
+    // Refuse to change any existing files inside a protected archive, like a normal run
+    if let Err(reason) = check_archive_protection(if moved_count > 0 { &move_args } else { &copy_args }) {
+        println!("{}", ColoredString::red(
+            format!("The target folder is a protected archive and only new files can be added to it, but {}. Exiting.", reason).as_str()));
+        process::exit(1);
+    }
+
+    if !copy_args.silent && !ask_for_apply_confirmation() {
+        println!("Nothing was changed. Exiting.");
+        return Ok(());
+    }
+
+    let stats = FileStats::new();
+    let time_writing_files = Instant::now();
+    write_plan_operations(&plan, &copy_args, &move_args, &stats);
     stats.set_time_write_files(time_writing_files.elapsed());
-    stats.set_time_total(time_processing.elapsed() + stats.time_fetch_dirs);
 
-    // Print unknown extensions
-    if !target_dir_tree.unknown_extensions.is_empty() {
-        println!("Skipped files with these unknown extensions: {}",
-                 target_dir_tree.unknown_extensions
-                     .into_iter()
-                     .map(|s|format!("'{}'", s))
-                     .collect::<Vec<String>>().join(", "));
+    stats.print_stats(&copy_args);
+
+    if is_interrupted() {
         println!();
+        println!("{}", ColoredString::red("Applying the plan was stopped with Ctrl+C, so some files were not written."));
+        println!("Run the same command again to resume it, or run `imgsorter {}` to roll back the files written so far.",
+                 Command::Undo.name());
+        process::exit(INTERRUPTED_EXIT_CODE);
+    }
+    if stats.has_write_errors() {
+        process::exit(1);
     }
 
-    // Print unknown extensions
-    if !args.non_custom_device_names.is_empty() {
-        println!("Device models with non-custom names: {}",
-                 args.non_custom_device_names
-                     .iter()
-                     .map(|s|format!("'{}'", s))
-                     .collect::<Vec<String>>().join(", "));
-        println!();
+    Ok(())
+}
+
+/// Write the files of the plan in order, the same way as the files of a normal run, see [write_file_row],
+/// creating the target folder of each file first, unless it was already created for one of the previous files
+fn write_plan_operations(plan: &ExportedPlan, copy_args: &Args, move_args: &Args, stats: &FileStats) {
+    stats.inc_files_total(plan.operations.len());
+
+    let source_paths: Vec<PathBuf> = plan.operations.iter().map(|operation| operation.source.clone()).collect();
+    let source_entries: HashMap<PathBuf, DirEntry> = read_listed_files(&source_paths)
+        .into_values()
+        .flatten()
+        .map(|source_entry| (source_entry.path(), source_entry))
+        .collect();
+
+    let mut padder = Padder::new(copy_args.has_multiple_sources());
+    for operation in &plan.operations {
+        padder.set_max_source_filename_from_str(&operation.source.file_name().unwrap_or_default().to_string_lossy());
+        padder.set_max_source_path_from_str(&operation.source.display().to_string());
+        padder.set_max_target_path(operation.target.strip_prefix(&plan.target_dir).unwrap_or(&operation.target).display().to_string().chars().count());
     }
+    let write_layout = padder.build_write_layout(get_status_width(&TargetDateDeviceTree::new(), move_args, false));
 
-    // Print final stats
-    stats.print_stats(&args);
+    let records = WriteRecords::open(copy_args);
+    let mut created_dirs: HashSet<PathBuf> = HashSet::new();
 
-    // Ask user input to prevent console window from closing before reading output
-    if args.silent {
-        println!("> Silent mode is enabled. Exiting without user confirmation.");
-    } else {
-        ask_for_exit_confirmation();
+    println!();
+    print_table_header(&write_layout);
+
+    for operation in &plan.operations {
+        // The files being written are completed, but no other file is started once the run is stopped
+        if is_interrupted() {
+            break;
+        }
+        let args = if operation.is_move { move_args } else { copy_args };
+
+        let parsed_file = source_entries
+            .get(&operation.source)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "the source file no longer exists"))
+            .and_then(|source_entry| SupportedFile::parse_from_ref(source_entry, args));
+        let mut file = match parsed_file {
+            Ok((file, _)) => file,
+            Err(e) => {
+                stats.inc_error_file_read();
+                println!("{} {} {}", operation.source.display(), SEPARATOR_OP_STATUS,
+                         ColoredString::red(format!("ERROR reading the file: {}", e).as_str()));
+                continue;
+            }
+        };
+        stats.inc_files_size(get_files_size(std::slice::from_ref(&file)));
+
+        // The file is written exactly as planned, whatever the current options would decide
+        file.skip_reason = None;
+        file.overwrites_target = operation.overwrite;
+        file.target_file_name = operation.target.file_name().unwrap_or_default().to_os_string();
+
+        let target_dir = operation.target.parent().unwrap_or(&plan.target_dir);
+        if created_dirs.insert(target_dir.to_path_buf()) {
+            let dir_status = create_subdir_if_required(target_dir, &DirType::Date, stats,
+                                                       &mut lock_stat(&records.journal), &mut lock_stat(&records.operation_log))
+                .unwrap_or_else(|error_status| error_status);
+            let dir_name = target_dir.strip_prefix(&plan.target_dir).unwrap_or(target_dir).display().to_string();
+            println!("{} {}", ColoredString::bold_white(write_layout.format_date_dir(dir_name).as_str()), dir_status);
+        }
+
+        println!("{}", write_file_row(&file, operation.target.clone(), args, stats, &write_layout, &records, None, None));
     }
+    println!();
 
-    Ok(())
+    records.close();
+}
+
+fn ask_for_apply_confirmation() -> bool {
+    println!("{}",
+             ColoredString::magenta(
+                 "Apply this plan? Type one of the options then press Enter:\n\
+                 • 'y' or 'yes' to copy and move the files as planned\n\
+                 • 'n' or 'no' to exit without changing anything"));
+    loop {
+        let mut user_input = String::new();
+        match io::stdin().read_line(&mut user_input) {
+            // There's no more input, e.g. if it was piped from another program
+            Ok(0) => return false,
+            Ok(_) => {}
+            Err(err) => {
+                eprintln!("Error reading user input: {:?}", err);
+                return false;
+            }
+        }
+        match user_input.trim().to_lowercase().as_str() {
+            "n" | "no"  => return false,
+            "y" | "yes" => return true,
+            _ => println!("...press one of 'y/yes' or 'n/no', then Enter"),
+        }
+    }
+}
+
+fn ask_for_undo_confirmation() -> bool {
+    println!("{}",
+             ColoredString::magenta(
+                 "Undo this run? Type one of the options then press Enter:\n\
+                 • 'y' or 'yes' to move the files back and remove the copies\n\
+                 • 'n' or 'no' to exit without changing anything"));
+    loop {
+        let mut user_input = String::new();
+        match io::stdin().read_line(&mut user_input) {
+            // There's no more input, e.g. if it was piped from another program
+            Ok(0) => return false,
+            Ok(_) => {}
+            Err(err) => {
+                eprintln!("Error reading user input: {:?}", err);
+                return false;
+            }
+        }
+        match user_input.trim().to_lowercase().as_str() {
+            "n" | "no"  => return false,
+            "y" | "yes" => return true,
+            _ => println!("...press one of 'y/yes' or 'n/no', then Enter"),
+        }
+    }
 }
 
 fn build_source_dirs_list_string(args: &Args) -> String {
@@ -1091,13 +3581,13 @@ fn build_source_dirs_list_string(args: &Args) -> String {
 /// Read contents of the provided dir but filter out subdirectories or files which failed to read
 fn read_supported_files(
     source_dir: &Path,
-    stats: &mut FileStats,
+    stats: &FileStats,
     args: &Args,
 ) -> Result<Vec<DirEntry>, std::io::Error> {
     // TODO 5d: handle all ?'s
     let dir_entries = fs::read_dir(source_dir)?
-        .into_iter()
-        .filter_map(|entry| entry.ok());
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| !is_archive_metadata_file(entry));
 
     // filter out any source subdirectories...
     let filtered_entries = if args.source_recursive {
@@ -1127,324 +3617,412 @@ fn read_supported_files(
     Ok(filtered_entries)
 }
 
+/// Our own checksum manifests and archive markers are never sorted, even if they're listed or watched
+fn is_archive_metadata_file(entry: &DirEntry) -> bool {
+    entry.file_name() == CHECKSUM_MANIFEST_FILE_NAME || entry.file_name() == ARCHIVE_MARKER_FILE_NAME
+}
+
+/// Read the entries of the files given with the `--files-from` option, grouped by the dir which contains them,
+/// the same as if each of these dirs was a source dir with only these files inside, see [Args::source_files_list]
+fn read_listed_files(listed_files: &[PathBuf]) -> BTreeMap<String, Vec<DirEntry>> {
+    let mut listed_file_names: BTreeMap<PathBuf, HashSet<OsString>> = BTreeMap::new();
+    for listed_file in listed_files {
+        if let Some(file_name) = listed_file.file_name() {
+            listed_file_names
+                .entry(listed_file_dir(listed_file))
+                .or_default()
+                .insert(file_name.to_os_string());
+        }
+    }
+
+    listed_file_names
+        .into_iter()
+        .map(|(listed_dir, file_names)| {
+            let dir_contents = fs::read_dir(&listed_dir)
+                .map(|dir_entries| dir_entries
+                    .filter_map(|entry| entry.ok())
+                    .filter(|entry| file_names.contains(&entry.file_name()) && !is_archive_metadata_file(entry))
+                    .collect::<Vec<DirEntry>>())
+                .unwrap_or_default();
+            (listed_dir.display().to_string(), dir_contents)
+        })
+        .collect()
+}
+
 /// Read directory and parse contents into supported data models
 fn parse_source_dirs(
     source_dirs: BTreeMap<String, Vec<DirEntry>>,
     args: &mut Args,
-    stats: &mut FileStats,
+    stats: &FileStats,
     padder: &mut Padder,
 ) -> TargetDateDeviceTree {
-    let mut new_dir_tree: TargetDateDeviceTree = TargetDateDeviceTree::new();
-
     // TODO 5l: this should already be available from source_dir_contents metadata
     let total_no_files: usize = source_dirs.values().map(|vec| vec.len()).sum();
 
     stats.inc_files_total(total_no_files);
 
-    let mut count_so_far = 0;
-
-    // We'll print reading progress in two ways:
-    // - if verbose, print a progress message in two parts for each source directory with time taken
-    // - if not verbose, print a simple incrementing counter of individual files out of the total
-    if !args.verbose {
-        println!("Reading source files...")
-    }
-
-    for (source_dir_name, source_dir_contents) in source_dirs.into_iter() {
-        let time_parsing_dir = Instant::now();
-
-        let current_file_count = source_dir_contents.len();
-
-        let mut skipped_files: Vec<String> = Vec::new();
-
-        if args.verbose {
-            // This is the first part of the progres line for this directory
-            // See also the next [print_progress] call which prints the time taken to this same line
-            // e.g. `[3566/4239] Parsing 2 files from D:\Temp\source_path\... done (0.018 sec)`
-            print_progress(format!(
-                "[{}/{}] ({}%) Parsing {} files from '{}'... ",
-                count_so_far,
-                total_no_files,
-                simple_percentage(count_so_far, total_no_files),
-                current_file_count,
-                &source_dir_name,
-            ));
-        }
-
-        // Parse each file into its internal representation and add it to the target tree
-        for entry in source_dir_contents.into_iter() {
-            // TODO 10a - replace with parse_from_ref
-            let current_file: SupportedFile = SupportedFile::parse_from(entry, args);
-
-            // Build final target path for this file
-            match &current_file.file_type {
-                FileType::Image | FileType::Video | FileType::Audio => {
-                    let file_date = current_file.date_str.clone();
-                    let file_device = current_file.device_name.clone();
-
-                    // TODO 5i: replace these with single method in DateDeviceTree
-                    // Attach file's date as a new subdirectory to the current target path
-                    let devicetree_for_this_date = {
-                        new_dir_tree
-                            .dir_tree
-                            .entry(file_date)
-                            .or_insert_with(DeviceTree::new)
-                    };
-
-                    // TODO 5i: replace these with single method in DeviceTree
-                    let all_files_for_this_device = {
-                        devicetree_for_this_date
-                            .file_tree
-                            .entry(file_device)
-                            .or_insert_with(Vec::new)
-                    };
-
-                    // Store the string lengths of the file name and path for padding in stdout
-                    let _device_name_len = match &current_file.device_name {
-                        DirEntryType::Directory(dir_name) =>
-                            get_string_char_count(dir_name.clone()),
-                        DirEntryType::Files =>
-                            0
-                    };
-                    let _date_name_str = &current_file.date_str.chars().count();
-                    // add +1 for each path separator character
-                    let total_target_path_len = _date_name_str + 1 + _device_name_len;
-
-                    padder.set_max_source_filename_from_str(
-                        current_file.file_name.clone().to_str().unwrap());
-                    padder.set_max_source_path(get_string_char_count(
-                        current_file.file_path.display().to_string()));
-                    devicetree_for_this_date.max_dir_path_len = max(
-                        devicetree_for_this_date.max_dir_path_len,
-                        total_target_path_len,
-                    );
-
-                    // Add file to dir tree
-                    all_files_for_this_device.push(current_file);
-                }
-
-                FileType::Unknown(ext) => {
-                    stats.inc_unknown_skipped();
-                    new_dir_tree.unknown_extensions.insert(ext.to_lowercase());
-                    skipped_files.push(current_file.get_file_name_str());
-                }
-            }
+    // Files are parsed in any order, so there's no progress message for each source directory when verbose
+    let progress = if args.verbose {
+        println!("> using {} threads for {} files", args.max_threads, total_no_files);
+        None
+    } else {
+        Some(ProgressBar::new("Reading source files", total_no_files as u64, ProgressUnit::Files))
+    };
 
-            if !args.verbose {
-                count_so_far += 1;
+    let source_files = source_dirs
+        .into_values()
+        .flatten()
+        .collect::<Vec<_>>();
 
-                print_progress_overwrite(
-                    format!("{}/{} ({}%)",
-                            count_so_far, total_no_files, simple_percentage(count_so_far, total_no_files)).as_str());
-            };
+    // Each thread parses files into its own chunk, which are merged in the order of the source files,
+    // while threads which finish early take over files from the others
+    let chunk_result = match ThreadPoolBuilder::new().num_threads(args.max_threads).build() {
+        Ok(thread_pool) => {
+            let shared_args: &Args = args;
+            thread_pool.install(|| parse_files_in_parallel(source_files, shared_args, stats, progress.as_ref()))
         }
+        Err(e) => {
+            println!("{} Could not start {} threads, reading files in the current thread instead: {}",
+                     ColoredString::warn_arrow(), args.max_threads, e);
+            let mut chunk_result = ParseChunkResult::new();
+            source_files
+                .into_iter()
+                .for_each(|source_entry| {
+                    parse_source_entry(&mut chunk_result, source_entry, args, stats);
+                    if let Some(progress) = &progress {
+                        progress.advance(1);
+                    }
+                });
+            chunk_result
+        }
+    };
 
-        if args.verbose {
+    if let Some(progress) = progress {
+        progress.finish();
+    }
 
-            // Record progress
-            count_so_far += current_file_count;
+    let new_dir_tree = chunk_result.new_dir_tree;
+    padder.set_max_source_filename(chunk_result.max_source_filename);
+    padder.set_max_source_path(chunk_result.max_source_path);
+    args.non_custom_device_names.extend(chunk_result.non_custom_extensions);
 
-            // This is the second part of the progres line for this directory
-            // See also the previous [print_progress] call which prints the first part of this line
-            // e.g. `[3566/4239] Parsing 2 files from D:\Temp\source_path\... done (0.018 sec)`
-            print_progress(format!("done ({}.{} sec)",
-                                   time_parsing_dir.elapsed().as_secs(),
-                                   LeftPadding::zeroes3(time_parsing_dir.elapsed().subsec_millis())));
-            println!();
-            // Print files indented with two spaces
-            let skipped = skipped_files
-                .into_iter()
-                .filter(|s| !s.is_empty())
-                .collect::<Vec<String>>();
+    // TODO 10a: print skipped files?
 
-            if !skipped.is_empty() {
-                println!("Skipped unknown files:\n {}", skipped.join("\n "));
-            }
-        }
-    }
+    finalize_dir_tree(new_dir_tree, args, padder)
+}
+
+/// Apply the steps which need all files of the dir tree once they're read, e.g. keeping pairs of files together,
+/// deciding the device subdirs and the final target file names, then compute the widths of the target paths
+fn finalize_dir_tree(mut new_dir_tree: TargetDateDeviceTree, args: &Args, padder: &mut Padder) -> TargetDateDeviceTree {
+    new_dir_tree.skip_imported_files(args);
+    new_dir_tree.keep_live_photos_together();
+    new_dir_tree.keep_raw_jpeg_pairs_together();
+    new_dir_tree.keep_sidecars_together();
+    new_dir_tree.skip_heic_jpeg_pairs(args);
+    new_dir_tree.skip_exifless_twins(args);
+    new_dir_tree.route_probable_duplicates(args);
+    new_dir_tree.merge_events(args);
 
     // This is a consuming call for now, so needs reassignment
     // TODO 5n: it shouldn't be consuming
     new_dir_tree = new_dir_tree.isolate_single_images(args);
 
+    new_dir_tree.apply_device_policy(args);
+    new_dir_tree.apply_file_name_template(args);
+    new_dir_tree.rename_name_collisions();
+    new_dir_tree.route_duplicate_sources(args);
+    new_dir_tree.nest_in_batch_dir(args);
+    new_dir_tree.check_existing_targets(&args.collision_policy, args);
+
     // The max path length can only be computed after the tree has been filled with devices and files
     // because of the requirement to only create device subdirs if there are at least 2 devices
-    padder.set_max_target_path(new_dir_tree.compute_max_path_len(args));
+    padder.set_max_target_path(new_dir_tree.compute_max_path_len());
+    // Files renamed by [Args::file_name_template] or to avoid name collisions may have longer names
+    padder.set_max_source_filename(new_dir_tree.compute_max_file_name_len());
 
     new_dir_tree
 }
 
-/// Read directory and parse contents into supported data models
-fn parse_source_dirs_threaded(
+/// The number of parsed files which wait to be written when streaming, see [Args::stream_writes].
+/// Parsing is stopped while the queue is full, so that files are not parsed much faster than they're written
+const STREAM_QUEUE_SIZE: usize = 64;
+
+/// The width of the target paths when streaming, which are only known once each file is parsed,
+/// so the rows of longer paths are not aligned
+const STREAM_TARGET_PATH_WIDTH: usize = 30;
+
+/// Parse the source files and write each one as soon as it's parsed, see [Args::stream_writes].
+/// Files are parsed by a pool of threads which feed a bounded queue, while the current thread
+/// writes them in the order they're parsed. The files which may be a pair, e.g. a Live Photo,
+/// are parsed and written together, so that they're still kept together, and so are the files with the same name
+/// from different source dirs, so that they're renamed or skipped as duplicates like in other runs, see [group_source_file_pairs].
+/// Only the steps which need a single group of files are applied, see [Args::find_stream_writes_conflicts].
+/// Returns the tree of the files which were read, for the steps which follow the writing,
+/// e.g. setting the timestamps of the target folders
+fn stream_source_files(
     source_dirs: BTreeMap<String, Vec<DirEntry>>,
-    args: &mut Args,
-    stats: &mut FileStats,
+    args: &Args,
+    stats: &FileStats,
     padder: &mut Padder,
 ) -> TargetDateDeviceTree {
-    let mut new_dir_tree: TargetDateDeviceTree = TargetDateDeviceTree::new();
+    let source_files = source_dirs
+        .into_values()
+        .flatten()
+        .collect::<Vec<_>>();
+    stats.inc_files_total(source_files.len());
 
-    // TODO 5l: this should already be available from source_dir_contents metadata
-    let total_no_files: usize = source_dirs.values().map(|vec| vec.len()).sum();
+    source_files.iter().for_each(|source_entry| {
+        padder.set_max_source_filename_from_str(&source_entry.file_name().to_string_lossy());
+        padder.set_max_source_path_from_str(&source_entry.path().display().to_string());
+    });
+    padder.set_max_target_path(STREAM_TARGET_PATH_WIDTH);
+    let write_layout = padder.build_write_layout(get_status_width(&TargetDateDeviceTree::new(), args, false));
 
-    stats.inc_files_total(total_no_files);
+    let records = WriteRecords::open(args);
+
+    println!();
+    let start_status = format!("Reading and {} files at the same time...", if args.copy_not_move { "copying" } else { "moving" });
+    println!("{}", ColoredString::bold_white(start_status.as_str()));
+    println!();
+    print_table_header(&write_layout);
+
+    let mut written_tree = TargetDateDeviceTree::new();
+    let mut created_dirs: HashSet<PathBuf> = HashSet::new();
+    let progress = ProgressBar::new("Reading and writing files", source_files.len() as u64, ProgressUnit::Files);
+
+    let source_file_groups = group_source_file_pairs(source_files);
+    let (sender, parsed_files) = mpsc::sync_channel::<(ParseChunkResult, usize)>(STREAM_QUEUE_SIZE);
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            let parse_all = || source_file_groups
+                .into_par_iter()
+                .for_each_with(sender, |sender, source_entries| {
+                    // The remaining files are not written once the run is stopped, so they're not parsed either
+                    if is_interrupted() {
+                        return;
+                    }
+                    let file_count = source_entries.len();
+                    let mut chunk_result = ParseChunkResult::new();
+                    source_entries
+                        .into_iter()
+                        .for_each(|source_entry| parse_source_entry(&mut chunk_result, source_entry, args, stats));
+                    // The files are only left unwritten if the writing thread stopped
+                    let _ = sender.send((chunk_result, file_count));
+                });
+
+            match ThreadPoolBuilder::new().num_threads(args.max_threads).build() {
+                Ok(thread_pool) => thread_pool.install(parse_all),
+                Err(_) => parse_all(),
+            }
+        });
 
-    // let mut count_so_far = 0;
+        for (chunk_result, file_count) in parsed_files {
+            if is_interrupted() {
+                break;
+            }
+            let mut file_tree = chunk_result.new_dir_tree;
+            file_tree.skip_imported_files(args);
+            file_tree.keep_live_photos_together();
+            file_tree.keep_raw_jpeg_pairs_together();
+            file_tree.keep_sidecars_together();
+            // Each device has a subdir, see [Args::find_stream_writes_conflicts], so the policy gives the same dirs as for all files
+            file_tree.apply_device_policy(args);
+            file_tree.apply_file_name_template(args);
+            file_tree.rename_name_collisions();
+            file_tree.route_duplicate_sources(args);
+            file_tree.nest_in_batch_dir(args);
+            // Files written earlier in the run are already in the target folder, so they're found as existing targets
+            file_tree.check_existing_targets(&args.collision_policy, args);
+            // Like in the other silent runs, target files which are newer or larger than their source files are kept
+            let newer_targets: HashSet<PathBuf> = file_tree.find_newer_targets(args).into_iter().map(|(path, _)| path).collect();
+            file_tree.skip_files(&newer_targets, SKIP_REASON_NEWER_TARGET);
+
+            write_streamed_files(&file_tree, args, stats, &write_layout, &records, &mut created_dirs, &progress);
+            written_tree.extend(file_tree);
+            progress.advance(file_count as u64);
+        }
+    });
+    progress.finish();
+    println!();
 
-    let mut skipped_files: Vec<String> = Vec::new();
+    records.close();
+    written_tree
+}
 
-    // We'll print reading progress in two ways:
-    // - if verbose, print a progress message in two parts for each source directory with time taken
-    // - if not verbose, print a simple incrementing counter of individual files out of the total
-    if !args.verbose {
-        println!("Reading source files...")
+/// Group the source files whose names are the same up to the first dot, ignoring case, which includes the files
+/// which may be a pair, e.g. `IMG_0001.HEIC`, `IMG_0001.MOV` and `IMG_0001.HEIC.xmp` (see [get_pair_key]),
+/// and the files which would be written with the same name, e.g. `a/IMG_1.jpg` and `b/IMG_1.jpg`
+fn group_source_file_pairs(source_files: Vec<DirEntry>) -> Vec<Vec<DirEntry>> {
+    let mut groups: BTreeMap<String, Vec<DirEntry>> = BTreeMap::new();
+    for source_entry in source_files {
+        let file_name = source_entry.file_name().to_string_lossy().to_lowercase();
+        let name_start = file_name.split('.').next().unwrap_or_default().to_string();
+        groups.entry(name_start).or_default().push(source_entry);
     }
+    groups.into_values().collect()
+}
 
-    let chunks_count = args.max_threads - 1;
-    if args.verbose {
-        println!("> using {} threads for {} files", chunks_count, total_no_files);
-    }
+/// Write the files of a single parsed file tree while streaming, creating their target folders first,
+/// unless they were already created for one of the previous files, see [stream_source_files]
+fn write_streamed_files(
+    file_tree: &TargetDateDeviceTree,
+    args: &Args,
+    stats: &FileStats,
+    layout: &WriteLayout,
+    records: &WriteRecords,
+    created_dirs: &mut HashSet<PathBuf>,
+    progress: &ProgressBar,
+) {
+    for (date_dir_name, device_tree) in &file_tree.dir_tree {
+        let date_destination_path = args.target_dir.join(date_dir_name);
 
-    // TODO do we still need _source_dir_name?
-    let source_files = source_dirs
-        .into_iter()
-        .flat_map(|(_source_dir_name, source_dir_contents)| { source_dir_contents })
-        .collect::<Vec<_>>();
+        for (device_name, files) in &device_tree.file_tree {
+            stats.inc_files_size(get_files_size(files));
 
-    let mut thread_handles = Vec::new();
+            let device_destination_path = if device_tree.has_device_subdir(device_name) {
+                date_destination_path.join(device_name.to_string())
+            } else {
+                date_destination_path.clone()
+            };
 
-    // split into owned chunks based on itertools and this answer:
-    //   https://stackoverflow.com/questions/66446258/rust-chunks-method-with-owned-values
-    let chunks: Vec<Vec<DirEntry>> = source_files.into_iter().chunks(chunks_count).into_iter().map(|chunk|chunk.collect()).collect();
+            let target_dirs = [(&date_destination_path, DirType::Date), (&device_destination_path, DirType::Device)];
+            for (target_dir, dir_type) in target_dirs {
+                if !created_dirs.insert(target_dir.clone()) {
+                    continue;
+                }
+                let dir_status = create_subdir_if_required(target_dir, &dir_type, stats,
+                                                           &mut lock_stat(&records.journal), &mut lock_stat(&records.operation_log))
+                    .unwrap_or_else(|error_status| error_status);
+                let dir_name = target_dir.strip_prefix(&args.target_dir).unwrap_or(target_dir).display().to_string();
+                progress.print_line(format!("{} {}", ColoredString::bold_white(layout.format_date_dir(dir_name).as_str()), dir_status).as_str());
+            }
 
-    chunks
-        .into_iter()
-        .for_each(|source_entry_chunk| {
-            let args_clone = args.clone();
-            let handle= thread::spawn( move || {
-                // TODO 10a: add progress indicator
-                parse_dir_chunk(source_entry_chunk, &args_clone)
-            });
-            thread_handles.push(handle);
-        });
+            for file in files {
+                let file_destination_path = device_destination_path.join(&file.target_file_name);
+                progress.print_line(&write_file_row(file, file_destination_path, args, stats, layout, records, None, None));
+            }
+        }
+    }
+}
 
-    for handle in thread_handles {
-        let chunk_result = handle.join().unwrap();
+fn parse_files_in_parallel(
+    source_files: Vec<DirEntry>,
+    args: &Args,
+    stats: &FileStats,
+    progress: Option<&ProgressBar>,
+) -> ParseChunkResult {
+    source_files
+        .into_par_iter()
+        .fold(ParseChunkResult::new, |mut chunk_result, source_entry| {
+            parse_source_entry(&mut chunk_result, source_entry, args, stats);
+            if let Some(progress) = progress {
+                progress.advance(1);
+            }
+            chunk_result
+        })
+        .reduce(ParseChunkResult::new, ParseChunkResult::merge)
+}
 
-        new_dir_tree.extend(chunk_result.new_dir_tree);
-        padder.set_max_source_filename(chunk_result.max_source_filename);
-        padder.set_max_source_path(chunk_result.max_source_path);
+/// Parse a single source file into the target tree of the chunk of files read by the same thread
+fn parse_source_entry(chunk_result: &mut ParseChunkResult, source_entry: DirEntry, args: &Args, stats: &FileStats) {
+
+    // A file which can't be read is skipped, instead of stopping the whole run
+    let (current_file, non_custom_ext) = match stats.metrics.measure(STAGE_READ_METADATA, || SupportedFile::parse_from_ref(&source_entry, args)) {
+        Ok(parsed_file) => parsed_file,
+        Err(error) => {
+            stats.inc_error_file_read();
+            chunk_result.new_dir_tree.unreadable_files.push(format!("{} (could not be read: {})", source_entry.path().display(), error));
+            chunk_result.skipped_files.push(source_entry.file_name().to_string_lossy().to_string());
+            return;
+        }
+    };
 
-        skipped_files.extend(chunk_result.skipped_files);
-        stats.unknown_skipped += chunk_result.stats_unknown_skipped;
-        args.non_custom_device_names.extend(chunk_result.non_custom_extensions);
+    chunk_result.non_custom_extensions.extend(non_custom_ext);
 
-        // TODO 10a: print skipped files?
+    if let Some(warning) = &current_file.content_type_warning {
+        chunk_result.new_dir_tree.content_type_warnings.push(warning.clone());
     }
 
-    // This is a consuming call for now, so needs reassignment
-    // TODO 5n: it shouldn't be consuming
-    new_dir_tree = new_dir_tree.isolate_single_images(args);
-
-    // The max path length can only be computed after the tree has been filled with devices and files
-    // because of the requirement to only create device subdirs if there are at least 2 devices
-    padder.set_max_target_path(new_dir_tree.compute_max_path_len(args));
+    if current_file.is_corrupt {
+        chunk_result.new_dir_tree.corrupt_files.push(current_file.file_path.display().to_string());
+    }
 
-    new_dir_tree
-}
+    if let Some(reason) = &current_file.unreadable_reason {
+        chunk_result.new_dir_tree.unreadable_files.push(format!("{} ({})", current_file.file_path.display(), reason));
+    }
 
-fn parse_dir_chunk(source_entry_chunk: Vec<DirEntry>, args: &Args) -> ParseChunkResult {
+    match &current_file.file_type {
+        _ if current_file.unreadable_reason.as_ref().is_some_and(|reason| reason.is_skipped(args)) => {
+            stats.inc_unreadable_skipped();
+            chunk_result.skipped_files.push(current_file.get_file_name_str());
+        }
 
-    let mut skipped_files: Vec<String> = Vec::new();
-    let mut new_dir_tree: TargetDateDeviceTree = TargetDateDeviceTree::new();
-    let mut non_custom_extensions: HashSet<String> = HashSet::new();
-    let mut stats_unknown_skipped: i32 = 0;
-    let mut max_source_filename: usize = 0;
-    let mut max_source_path: usize = 0;
+        _ if current_file.is_corrupt && args.corrupt_files == CorruptFilesAction::Skip => {
+            stats.inc_corrupt_skipped();
+            chunk_result.skipped_files.push(current_file.get_file_name_str());
+        }
 
-    source_entry_chunk
-        .into_iter()
-        .for_each(|source_entry| {
+        FileType::Image | FileType::Video | FileType::Audio | FileType::Animation | FileType::Document | FileType::Sidecar => {
+            if current_file.location.is_some() {
+                stats.inc_files_with_location();
+            }
+            if let Some(parser) = &current_file.exif_parser {
+                stats.inc_exif_parser(parser);
+            }
 
-            let (current_file, non_custom_ext) = SupportedFile::parse_from_ref(&source_entry, args);
+            let file_date = current_file.date_str.clone();
+            let file_device = current_file.device_name.clone();
 
-            non_custom_extensions.extend(non_custom_ext);
+            // TODO 5i: replace these with single method in DateDeviceTree
+            // Attach file's date as a new subdirectory to the current target path
+            let devicetree_for_this_date = {
+                chunk_result.new_dir_tree
+                    .dir_tree
+                    .entry(file_date)
+                    .or_insert_with(DeviceTree::new)
+            };
 
-            match &current_file.file_type {
-                FileType::Image | FileType::Video | FileType::Audio => {
-                    let file_date = current_file.date_str.clone();
-                    let file_device = current_file.device_name.clone();
+            // TODO 5i: replace these with single method in DeviceTree
+            let all_files_for_this_device = {
+                devicetree_for_this_date
+                    .file_tree
+                    .entry(file_device)
+                    .or_insert_with(Vec::new)
+            };
 
-                    // TODO 5i: replace these with single method in DateDeviceTree
-                    // Attach file's date as a new subdirectory to the current target path
-                    let devicetree_for_this_date = {
-                        new_dir_tree
-                            .dir_tree
-                            .entry(file_date)
-                            .or_insert_with(DeviceTree::new)
-                    };
+            // Store the string lengths of the file name and path for padding in stdout
+            let _device_name_len = match &current_file.device_name {
+                DirEntryType::Directory(dir_name) =>
+                    get_string_char_count(dir_name.clone()),
+                DirEntryType::Files =>
+                    0
+            };
+            let _date_name_str = &current_file.date_str.chars().count();
+            // add +1 for each path separator character
+            let total_target_path_len = _date_name_str + 1 + _device_name_len;
 
-                    // TODO 5i: replace these with single method in DeviceTree
-                    let all_files_for_this_device = {
-                        devicetree_for_this_date
-                            .file_tree
-                            .entry(file_device)
-                            .or_insert_with(Vec::new)
-                    };
+            let source_filename_len = get_string_char_count(
+                current_file.file_name.to_string_lossy().to_string());
+            let source_dir_path_len = get_string_char_count(
+                current_file.file_path.display().to_string());
 
-                    // Store the string lengths of the file name and path for padding in stdout
-                    let _device_name_len = match &current_file.device_name {
-                        DirEntryType::Directory(dir_name) =>
-                            get_string_char_count(dir_name.clone()),
-                        DirEntryType::Files =>
-                            0
-                    };
-                    let _date_name_str = &current_file.date_str.chars().count();
-                    // add +1 for each path separator character
-                    let total_target_path_len = _date_name_str + 1 + _device_name_len;
-
-                    let source_filename_len = get_string_char_count(
-                        String::from(
-                            current_file.file_name.clone().to_str().unwrap()));
-                    let source_dir_path_len = get_string_char_count(
-                        String::from(
-                            current_file.file_path.display().to_string()));
-
-                    max_source_filename = max(max_source_filename, source_filename_len);
-                    max_source_path = max(max_source_path, source_dir_path_len);
-
-                    devicetree_for_this_date.max_dir_path_len = max(
-                        devicetree_for_this_date.max_dir_path_len,
-                        total_target_path_len,
-                    );
-
-                    // Add file to dir tree
-                    all_files_for_this_device.push(current_file);
-                }
+            chunk_result.max_source_filename = max(chunk_result.max_source_filename, source_filename_len);
+            chunk_result.max_source_path = max(chunk_result.max_source_path, source_dir_path_len);
 
-                FileType::Unknown(ext) => {
-                    stats_unknown_skipped += 1;
-                    new_dir_tree.unknown_extensions.insert(ext.to_lowercase());
-                    skipped_files.push(current_file.get_file_name_str());
-                }
-            }
+            devicetree_for_this_date.max_dir_path_len = max(
+                devicetree_for_this_date.max_dir_path_len,
+                total_target_path_len,
+            );
 
-            // TODO 10a: redesign for multithreaded
-            // if !args.verbose {
-            //     count_so_far += 1;
-            //
-            //     print_progress_overwrite(
-            //         format!("{}/{} ({}%)",
-            //                 count_so_far, total_no_files, simple_percentage(count_so_far, total_no_files)).as_str());
-            // };
-        });
+            // Add file to dir tree
+            all_files_for_this_device.push(current_file);
+        }
 
-    ParseChunkResult {
-        new_dir_tree,
-        skipped_files,
-        non_custom_extensions,
-        stats_unknown_skipped,
-        max_source_filename,
-        max_source_path
+        FileType::Unknown(ext) => {
+            stats.inc_unknown_skipped();
+            chunk_result.new_dir_tree.unknown_extensions.insert(ext.to_lowercase());
+            chunk_result.skipped_files.push(current_file.get_file_name_str());
+        }
     }
 }
 
@@ -1453,11 +4031,32 @@ struct ParseChunkResult {
     new_dir_tree: TargetDateDeviceTree,
     skipped_files: Vec<String>,
     non_custom_extensions: HashSet<String>,
-    stats_unknown_skipped: i32,
     max_source_filename: usize,
     max_source_path: usize
 }
 
+impl ParseChunkResult {
+    fn new() -> ParseChunkResult {
+        ParseChunkResult {
+            new_dir_tree: TargetDateDeviceTree::new(),
+            skipped_files: Vec::new(),
+            non_custom_extensions: HashSet::new(),
+            max_source_filename: 0,
+            max_source_path: 0
+        }
+    }
+
+    /// Merge the results of two chunks, keeping the files of this chunk first
+    fn merge(mut self, other: ParseChunkResult) -> ParseChunkResult {
+        self.new_dir_tree.extend(other.new_dir_tree);
+        self.skipped_files.extend(other.skipped_files);
+        self.non_custom_extensions.extend(other.non_custom_extensions);
+        self.max_source_filename = max(self.max_source_filename, other.max_source_filename);
+        self.max_source_path = max(self.max_source_path, other.max_source_path);
+        self
+    }
+}
+
 /// Iterate the files according to the projected target structure and
 /// either do a dry run and print resulting dir structure or
 /// write the files to target as configured (copy or move)
@@ -1465,45 +4064,68 @@ fn process_target_dir_files(
     // The target tree representation of files to be copied/moved
     new_dir_tree: &mut TargetDateDeviceTree,
     args: &Args,
-    mut stats: &mut FileStats,
-    padder: &mut Padder,
+    stats: &FileStats,
+    padder: &Padder,
 ) {
     let is_dry_run = args.dry_run;
 
-    // Dry runs will output a dir-tree-like structure, so add the additional
-    // indents and markings to the max length to be taken into account when padding
+    // Record every change, so that the run can be undone later
+    let records = WriteRecords::open(args);
+
+    // All widths are known once the target tree is complete, so they're only computed once for all rows.
+    // Dry runs will output a dir-tree-like structure, so the dry run layout also accounts for the
+    // additional indents and markings of the dir tree when padding
+    let status_width = get_status_width(new_dir_tree, args, is_dry_run);
+    let dry_run_layout = padder.build_dry_run_layout(status_width, args);
+    let source_view_layout = padder.build_source_view_layout(status_width, args);
+    let write_layout = padder.build_write_layout(status_width);
+
+    // When grouping by source, the files are still checked in the order they will be written,
+    // so that the same duplicates are found, and are only printed once all files were checked
+    let is_source_view = is_dry_run && args.dry_run_view == DryRunView::Source;
+    let mut source_view_rows: BTreeMap<PathBuf, Vec<SourceViewRow>> = BTreeMap::new();
+
     if is_dry_run {
-        // TODO 5h need to pre-calculate max-depth length
-        // TODO 5h FILE_TREE_INDENT is not required when there's only one level (i.e. one single device throughout)
-        padder.add_extra_source_chars_from_str(DIR_TREE_INDENT_MID);
-        padder.add_extra_source_chars_from_str(DIR_TREE_ENTRY_LAST);
-
-        // TODO 5i: Refactor operation statuses and calculate this programatically
-        let status_width = 20;
-        let header_separator = padder.format_dryrun_header_separator(status_width);
         println!();
-        println!("{}", ColoredString::bold_white(header_separator.as_str()));
-        println!("{}", ColoredString::bold_white(
-            padder.format_dryrun_header(status_width).as_str()));
-        println!("{}", ColoredString::bold_white(header_separator.as_str()));
+        if is_source_view {
+            print_table_header(&source_view_layout);
+        } else {
+            print_table_header(&dry_run_layout);
+        }
+
+        if args.show_dry_run_legend {
+            print_dry_run_legend(args);
+        }
+
+        if !args.show_only.is_empty() {
+            println!("{}", ColoredString::dim(format!(
+                "Only listing files with these statuses: {}",
+                args.show_only.iter().map(StatusCategory::name).join(", ")).as_str()));
+            println!();
+        }
     } else {
         println!();
         let start_status = format!("Starting to {} files...", { if args.copy_not_move {"copy"} else {"move"}} );
         println!("{}", ColoredString::bold_white(start_status.as_str()));
         println!();
 
-        let status_width = 20;
-        let header_separator = padder.format_write_header_separator(status_width);
-        println!("{}", ColoredString::bold_white(header_separator.as_str()));
-        println!("{}", ColoredString::bold_white(
-            padder.format_write_header(status_width).as_str()));
-        println!("{}", ColoredString::bold_white(header_separator.as_str()));
+        print_table_header(&write_layout);
+
+        // Create all target folders before writing any file, so that any problems are reported up front
+        create_target_dirs(new_dir_tree, args, stats, &write_layout,
+                           &mut lock_stat(&records.journal), &mut lock_stat(&records.operation_log));
     }
 
     // This is useful only for dry runs, where we need to track unique files
     // as we iterate over them to be able to show the status of duplicates.
     // For write operations, this will remain unused and empty.
-    let mut source_unique_files: HashSet<OsString> = HashSet::new();
+    let mut dry_run_state = DryRunState::default();
+
+    // The year and month dirs above the previous date dir, which are only printed once, see [Args::date_dir_layout]
+    let mut printed_parent_dirs: Vec<&OsStr> = Vec::new();
+
+    // The folders are only written once all of them are known, so that several can be written at the same time
+    let mut write_jobs: Vec<FolderWriteJob> = Vec::new();
 
     /*****************************************************************************/
     /* ---             Iterate each date directory to be created             --- */
@@ -1526,37 +4148,56 @@ fn process_target_dir_files(
         // Attach file's date as a new subdirectory to the target path
         let date_destination_path = args.target_dir.clone().join(date_dir_name);
 
+        // The dry run rows of the date dir are only printed once it's known that any of its files are shown,
+        // so that dirs whose files are all hidden by the `show_only` option aren't listed at all
+        let mut date_dir_lines: Vec<String> = Vec::new();
+        let mut date_dir_shown_count = 0;
+        let mut date_parent_dirs: Vec<&OsStr> = Vec::new();
+
         if is_dry_run {
 
-            let _device_count_str = if device_count_for_date == 1 {"device"} else {"devices"};
-            let _file_count_str = if file_count_for_date == 1 {"file"} else {"files"};
-
-            let date_dir_name_with_device_status = {
-                format!(
-                    "[{dirname}] ({devicecount:?} {devicestr}, {filecount:?} {filestr}, {filesize}) ",
-                    dirname = date_dir_name.clone(),
-                    devicecount = device_count_for_date,
-                    devicestr = _device_count_str,
-                    filecount = file_count_for_date,
-                    filestr = _file_count_str,
-                    filesize = get_file_size_string(file_size_for_date))
+            // Print the year and month dirs as a tree above their date dirs, instead of repeating them for each date dir
+            let (date_dir_indent, date_dir_display_name) = if args.date_dir_layout != DateDirLayout::Flat && !is_source_view {
+                let date_dir_path = Path::new(date_dir_name);
+                let parent_dirs: Vec<&OsStr> = date_dir_path
+                    .parent()
+                    .map(|parent_dir| parent_dir.iter().collect())
+                    .unwrap_or_default();
+
+                let common_parent_count = printed_parent_dirs
+                    .iter()
+                    .zip(&parent_dirs)
+                    .take_while(|(printed, parent)| printed == parent)
+                    .count();
+                for (depth, parent_dir) in parent_dirs.iter().enumerate().skip(common_parent_count) {
+                    date_dir_lines.push(ColoredString::bold_white(
+                        format!("{}[{}]", DATE_PARENT_DIR_INDENT.repeat(depth), parent_dir.to_string_lossy()).as_str()));
+                }
+                let date_dir_indent = DATE_PARENT_DIR_INDENT.repeat(parent_dirs.len());
+                date_parent_dirs = parent_dirs;
+
+                (date_dir_indent, date_dir_path.file_name().unwrap_or_default().to_string_lossy().to_string())
+            } else {
+                (String::new(), date_dir_name.clone())
             };
 
+            let date_dir_name_with_device_status = format!("{}{}", date_dir_indent, format_date_dir_summary(
+                &date_dir_display_name, device_count_for_date, file_count_for_date, file_size_for_date));
+
             // Check restrictions - if target exists
             let target_dir_exists =
                 dry_run_check_target_dir_exists(&date_destination_path, &DirType::Date, stats);
 
             // Print everything together
-            println!("{}",
-                ColoredString::bold_white(
-                format!("{dir_devices} {dir_status}",
-                        dir_devices=padder.format_dryrun_date_dir(date_dir_name_with_device_status, args),
-                        dir_status=target_dir_exists)
-                    .as_str())
-            );
-        } else {
-            // Create date subdir
-            create_subdir_if_required(&date_destination_path, &DirType::Date, args, &mut stats);
+            if !is_source_view {
+                date_dir_lines.push(
+                    ColoredString::bold_white(
+                    format!("{dir_devices} {dir_status}",
+                            dir_devices=dry_run_layout.format_date_dir(date_dir_name_with_device_status),
+                            dir_status=target_dir_exists)
+                        .as_str())
+                );
+            }
         }
 
 
@@ -1578,33 +4219,10 @@ fn process_target_dir_files(
             // Date Dir > 0. File
             let mut indent_level: usize = 0;
 
-            // This condition helps prevent creating a redundant device subdir if
-            // there's only a single Some("device") device (without any "None" device files)
-            // Before                 After
-            // ------                 -----
-            // [date_dir]             [date_dir]
-            //  └─ [device_dir]        │
-            //      ├─ file01.ext      ├─ file01.ext
-            //      └─ file02.ext      └─ file02.ext
-            let has_at_least_one_distinct_device = {
-                let _is_dir = device_name_opt.clone() != DirEntryType::Files;
-                device_count_for_date > 1 && _is_dir
-            };
+            let do_create_device_subdirs = devices_files_and_paths.has_device_subdir(device_name_opt);
 
-            // This condition helps prevent creating a device subdir for a single file, if there's also
-            // a "None" device with a single file. In practice, this is most likely to be a situation where
-            // a picture taken with a camera (computed device is Some("device") based on EXIF) is sent
-            // via a messenger app and would end up in a "Sent" folder without EXIF info (computed device is None)
-            // Before                 After
-            // ------                 -----
-            // [date_dir]             [date_dir]
-            //  └─ [device_dir]        |
-            //  │   └─ file01.ext      └─ file01.ext
-            //  └─ file02.ext          └─ file02.ext
-            // TODO 2g: add more logic to this case and maybe skip copying the file without EXIF info
-            let has_double_file = device_count_for_date == 2 && file_count_for_date == 2;
-
-            let do_create_device_subdirs = args.always_create_device_subdirs || has_at_least_one_distinct_device && !has_double_file;
+            // Same as for the date dir, the device dir is only listed if any of its files are shown
+            let mut device_dir_lines: Vec<String> = Vec::new();
 
             // If there's more than one DirEntryType, attach device dir to destination path
             let device_destination_path = if do_create_device_subdirs {
@@ -1620,24 +4238,16 @@ fn process_target_dir_files(
                     indent_level += 1;
 
                     // Add tree indents and padding to dir name
-                    let indented_device_dir_name = padder.format_dryrun_device_dir(
-                        device_dir_name,
-                        is_last_dir,
-                        // if it's last dir, it's also the last element of type dir
-                        is_last_dir,
-                        args,
-                    );
+                    let indented_device_dir_name = dry_run_layout.format_device_dir(device_dir_name, is_last_dir);
 
                     // Check restrictions - if target exists
                     let target_dir_status_check =
                         dry_run_check_target_dir_exists(&device_path, &DirType::Device, stats);
 
                     // Print everything together
-                    println!("{} {}", indented_device_dir_name, target_dir_status_check);
-                } else {
-                    // Create device subdir
-                    create_subdir_if_required(
-                        &device_path, &DirType::Device, args, &mut stats);
+                    if !is_source_view {
+                        device_dir_lines.push(format!("{} {}", indented_device_dir_name, target_dir_status_check));
+                    }
                 }
 
                 device_path
@@ -1653,20 +4263,74 @@ fn process_target_dir_files(
             /*****************************************************************************/
 
             // Output is different for dry-runs and copy/move operations, so process them separately
-            if is_dry_run {
-                process_files_dry_run(files_and_paths_vec, device_destination_path,
-                                      &mut source_unique_files, dir_count_total, curr_dir_ix, indent_level,
-                                      args, &mut stats, padder)
+            if is_source_view {
+                collect_source_view_rows(files_and_paths_vec, device_destination_path,
+                                         &mut dry_run_state, &mut source_view_rows, args, stats);
+            } else if is_dry_run {
+                let shown_count = process_files_dry_run(files_and_paths_vec, device_destination_path,
+                                                        &mut dry_run_state, dir_count_total, curr_dir_ix, indent_level,
+                                                        args, stats, &dry_run_layout, &mut device_dir_lines);
+                if shown_count > 0 {
+                    date_dir_lines.append(&mut device_dir_lines);
+                    date_dir_shown_count += shown_count;
+                }
             } else {
-                process_files_write(files_and_paths_vec, device_destination_path,
-                                    args, &mut stats, padder);
+                write_jobs.push(FolderWriteJob {
+                    files: files_and_paths_vec,
+                    device_destination_path,
+                    folder_index: write_jobs.len(),
+                    is_last_dir,
+                });
             };
         } // end loop device dirs
 
         // leave some empty space before the next date dir
-        println!();
+        if is_dry_run && !is_source_view && date_dir_shown_count > 0 {
+            date_dir_lines.iter().for_each(|line| println!("{}", line));
+            println!();
+            printed_parent_dirs = date_parent_dirs;
+        }
 
     } // end loop date dirs
+
+    let in_use_files = Mutex::new(Vec::new());
+
+    // The rows of each target folder are printed together and in the order of the dir tree,
+    // so that the table stays readable even if several folders are written at the same time
+    let write_size: u64 = write_jobs.iter().flat_map(|job| job.files).map(|file| file.metadata.len()).sum();
+    let write_output = if write_jobs.is_empty() {
+        FolderOutput::new()
+    } else {
+        FolderOutput::with_progress(ProgressBar::new("Writing files", write_size, ProgressUnit::Bytes))
+    };
+
+    write_folders(&write_jobs, args, stats, &write_layout, &records, &in_use_files, &write_output);
+    write_output.flush();
+
+    let in_use_files = in_use_files.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner());
+    retry_files_in_use(in_use_files, args, stats, &write_layout, &records);
+
+    if is_source_view {
+        print_dry_run_by_source(&source_view_rows, args, &source_view_layout);
+    }
+
+    // Save the operations of the dry run as a script, so that they can be reviewed and run separately
+    if let Some(script_path) = &args.emit_script_path {
+        if is_dry_run {
+            match write_script(script_path, &args.target_dir, &dry_run_state.script_operations) {
+                Ok(_) => println!("The operations of this dry run were saved in {}",
+                                  script_path.display()),
+                Err(e) => println!("{} Could not save the script in {}: {}",
+                                   ColoredString::warn_arrow(), script_path.display(), e),
+            }
+        } else {
+            println!("{} The script is only saved by dry runs, {} was not written",
+                     ColoredString::warn_arrow(), script_path.display());
+        }
+        println!();
+    }
+
+    records.close();
 }
 
 /// Iterate all source files and print the estimated target directory structure.
@@ -1687,20 +4351,23 @@ fn process_target_dir_files(
 ///  └── IMG-20190127.jpg <-------- D:\Pics\IMG-20190127.jpg ... file will be copied
 ///  └── IMG-20190127.jpg <-------- D:\Pics - Copy\IMG-20190127.jpg ... duplicate source file, will be skipped
 /// ```
+#[allow(clippy::too_many_arguments)]
 fn process_files_dry_run(
     files_and_paths_vec: &[SupportedFile],
     device_destination_path: PathBuf,
-    source_unique_files: &mut HashSet<OsString>,
+    dry_run_state: &mut DryRunState,
     dir_count_total: usize,
     curr_dir_ix: usize,
     indent_level: usize,
     args: &Args,
-    stats: &mut FileStats,
-    padder: &mut Padder,
-) {
+    stats: &FileStats,
+    layout: &DryRunLayout,
+    output_lines: &mut Vec<String>,
+) -> usize {
     // Count files to know which symbols to use for the dir tree
     // i.e. last entry is prefixed by `└` and the rest by `├`
     let file_count_total = files_and_paths_vec.len();
+    let mut shown_count = 0;
 
     let mut compact_counter = CompactCounter::new(args.compacting_threshold);
 
@@ -1708,17 +4375,16 @@ fn process_files_dry_run(
     // last element in this dir to choose the appropriate dir tree symbol
     for (file_index, file) in files_and_paths_vec.iter().enumerate() {
         let is_last_dir = curr_dir_ix == dir_count_total;
-        let is_first_element = file_index == 0;
         let is_last_element = file_index == file_count_total - 1;
 
         // Attach filename to the directory path
-        let file_destination_path = device_destination_path.clone().join(&file.file_name);
+        let file_destination_path = device_destination_path.clone().join(&file.target_file_name);
 
         // Check restrictions - file exists or is read-only
-        let file_restrictions = dry_run_check_file_restrictions(
+        let (status_category, file_restrictions) = dry_run_check_file_restrictions(
             file,
             &file_destination_path,
-            source_unique_files,
+            dry_run_state,
             args,
             stats,
         );
@@ -1727,62 +4393,91 @@ fn process_files_dry_run(
             // Prepare padded strings for output
             let indented_target_filename = indent_string(
                 indent_level,
-                file.get_file_name_str(),
+                file.get_target_file_name_str(),
                 is_last_dir,
                 is_last_element,
             );
 
-            let file_separator =
-                padder.format_dryrun_file_separator(indented_target_filename.clone(), args);
+            let file_separator = layout.format_file_separator(&indented_target_filename);
 
             let source_path = file.get_source_display_name_str(args);
-            let status_separator =
-                padder.format_dryrun_status_separator_dotted(source_path.clone(), args);
+            let status_separator = layout.format_status_separator(&source_path);
 
             process_files_format_status(
                 indented_target_filename,
                 file_separator,
                 source_path,
                 status_separator,
-                &file_restrictions,
+                &layout.format_status(&file_restrictions, file.location.map(|location| location.to_string())),
             )
         };
 
         let get_snipped_output = |_compact_counter: &CompactCounter| {
-            padder.format_dryrun_snipped_output(
+            layout.format_snipped_output(
                 _compact_counter.skipped_status_count,
                 indent_level,
                 is_last_dir,
             )
         };
 
-        // Output compacting is not enabled, print all file statuses directly
-        // Ignore compacting when debug mode is enabled
-        if !args.is_compacting_enabled() || args.verbose {
+        // Files with statuses which are filtered out are still checked above to count them in stats
+        let is_status_shown = args.show_only.is_empty() || args.show_only.contains(&status_category);
+        if is_status_shown {
+            shown_count += 1;
+        }
+
+        push_file_status_compacted(&mut compact_counter, &file_restrictions, is_status_shown, is_last_element,
+                                   args, get_output_for_file, get_snipped_output, output_lines);
+    } // end loop files
+
+    shown_count
+}
+
+/// Add the status row of a file to the output lines, unless it's hidden by the `show_only` option or compacting
+/// is enabled and too many consecutive files before it had the same status, in which case the files are counted
+/// and replaced with a single "snipped" line once the status changes or after the last file.
+#[allow(clippy::too_many_arguments)]
+fn push_file_status_compacted(
+    compact_counter: &mut CompactCounter,
+    file_restrictions: &str,
+    is_status_shown: bool,
+    is_last_element: bool,
+    args: &Args,
+    get_output_for_file: impl Fn() -> String,
+    get_snipped_output: impl Fn(&CompactCounter) -> String,
+    output_lines: &mut Vec<String>,
+) {
+    // Output compacting is not enabled, print all file statuses directly
+    // Ignore compacting when debug mode is enabled
+    if !args.is_compacting_enabled() || args.verbose {
+        if is_status_shown {
             let output = get_output_for_file();
-            println!("{}", output);
+            output_lines.push(output);
         }
+    }
 
-        // Output compacting is enabled, so print only the first few consecutive
-        // files with the same status as configured under `args.compacting_threshold`
-        else {
-            // First iteration - nothing special to do, just initialize
+    // Output compacting is enabled, so print only the first few consecutive
+    // files with the same status as configured under `args.compacting_threshold`
+    else {
+        // Hidden statuses are ignored, so that the files before and after them can still be compacted
+        if is_status_shown {
+            // First shown file - nothing special to do, just initialize
             // all counters to 0 and move on to the next file
-            if is_first_element {
-                compact_counter.reset_status(file_restrictions.clone());
+            if compact_counter.current_status.is_empty() {
+                compact_counter.reset_status(file_restrictions.to_string());
                 compact_counter.inc_current_status();
                 let output = get_output_for_file();
-                println!("{}", output);
+                output_lines.push(output);
             }
 
             // Next iterations with the same status as before - print line
             // only if we haven't reached `args.compacting_threshold`,
             // otherwise don't print anything, just increment the skip count
-            else if compact_counter.is_same_status(&file_restrictions) {
+            else if compact_counter.is_same_status(file_restrictions) {
                 if !compact_counter.has_reached_threshold() {
                     compact_counter.inc_current_status();
                     let output = get_output_for_file();
-                    println!("{}", output);
+                    output_lines.push(output);
                 } else {
                     compact_counter.inc_skipped_status();
                 }
@@ -1792,23 +4487,139 @@ fn process_files_dry_run(
             // then reset all counters and continue with the current file
             else {
                 if compact_counter.has_skipped_statuses() {
-                    let output = get_snipped_output(&compact_counter);
-                    println!("{}", output);
+                    let output = get_snipped_output(compact_counter);
+                    output_lines.push(output);
                 }
 
-                compact_counter.reset_status(file_restrictions.clone());
+                compact_counter.reset_status(file_restrictions.to_string());
                 compact_counter.inc_current_status();
                 let output = get_output_for_file();
-                println!("{}", output);
+                output_lines.push(output);
             }
+        }
 
-            // After the last file, print any remaining skipped statuses before finishing
-            if is_last_element && compact_counter.has_skipped_statuses() {
-                let output = get_snipped_output(&compact_counter);
-                println!("{}", output);
-            }
-        } // end else args.is_compacting_enabled
-    } // end loop files
+        // After the last file, print any remaining skipped statuses before finishing
+        if is_last_element && compact_counter.has_skipped_statuses() {
+            let output = get_snipped_output(compact_counter);
+            output_lines.push(output);
+        }
+    } // end else args.is_compacting_enabled
+}
+
+/// The dry run status of a file, to be printed below its source dir, see [print_dry_run_by_source()]
+struct SourceViewRow {
+    file_name: String,
+    file_size: u64,
+    /// The path of the file relative to the target dir
+    target_path: String,
+    status_category: StatusCategory,
+    status: String,
+}
+
+/// Check the files in a target dir the same way as [process_files_dry_run()],
+/// but store their status by source dir instead of printing it
+fn collect_source_view_rows(
+    files_and_paths_vec: &[SupportedFile],
+    device_destination_path: PathBuf,
+    dry_run_state: &mut DryRunState,
+    source_view_rows: &mut BTreeMap<PathBuf, Vec<SourceViewRow>>,
+    args: &Args,
+    stats: &FileStats,
+) {
+    for file in files_and_paths_vec {
+        let file_destination_path = device_destination_path.join(&file.target_file_name);
+
+        let (status_category, status) = dry_run_check_file_restrictions(
+            file,
+            &file_destination_path,
+            dry_run_state,
+            args,
+            stats,
+        );
+
+        let source_dir = file.file_path.parent().map(Path::to_path_buf).unwrap_or_default();
+        source_view_rows.entry(source_dir).or_default().push(SourceViewRow {
+            file_name: file.get_file_name_str(),
+            file_size: file.file_path.size_on_disk_fast(&file.metadata).ok().unwrap_or(0),
+            target_path: file_destination_path
+                .strip_prefix(&args.target_dir)
+                .unwrap_or(&file_destination_path)
+                .display()
+                .to_string(),
+            status_category,
+            status,
+        });
+    }
+}
+
+/// Print the dry run status of all files grouped by their source dir, with the target path of each file.
+/// Direction of arrows will be Left-to-Right to reflect the focus on what happens to the source files.
+/// Sample output:
+/// ```
+/// ---------------------------------------------------------------------------------------------------------
+/// SOURCE FILE                 TARGET PATH                               OPERATION STATUS
+/// ---------------------------------------------------------------------------------------------------------
+/// [D:\Pics] (3 files, 3.34 MB)
+///  ├── IMG-20190127.jpg ---> 2019.01.28\IMG-20190127.jpg .............. file will be copied
+///  ├── IMG-20190128.jpg ---> 2019.01.28\Canon 100D\IMG-20190128.jpg ... target file exists, will be skipped
+///  └── IMG-20190129.jpg ---> 2019.01.28\Canon 100D\IMG-20190129.jpg ... file will be copied
+/// ```
+fn print_dry_run_by_source(
+    source_view_rows: &BTreeMap<PathBuf, Vec<SourceViewRow>>,
+    args: &Args,
+    layout: &SourceViewLayout,
+) {
+    for (source_dir, rows) in source_view_rows {
+        // Source dirs whose files are all hidden by the `show_only` option aren't listed at all
+        let is_any_shown = args.show_only.is_empty() || rows.iter().any(|row| args.show_only.contains(&row.status_category));
+        if !is_any_shown {
+            continue;
+        }
+
+        let file_count = rows.len();
+        let file_size: u64 = rows.iter().map(|row| row.file_size).sum();
+        println!("{}", ColoredString::bold_white(format!(
+            "[{}] ({} {}, {})",
+            source_dir.display(),
+            file_count,
+            if file_count == 1 { "file" } else { "files" },
+            format_file_size(file_size)).as_str()));
+
+        let mut compact_counter = CompactCounter::new(args.compacting_threshold);
+        let mut output_lines: Vec<String> = Vec::new();
+
+        let sorted_rows = rows.iter().sorted_by(|row1, row2| row1.file_name.cmp(&row2.file_name));
+        for (row_index, row) in sorted_rows.enumerate() {
+            let is_last_element = row_index == file_count - 1;
+
+            let get_output_for_file = || {
+                let indented_source_filename = indent_string(0, row.file_name.clone(), true, is_last_element);
+                let file_separator = layout.format_file_separator(&indented_source_filename);
+                let status_separator = layout.format_status_separator(&row.target_path);
+
+                process_files_format_status(
+                    indented_source_filename,
+                    file_separator,
+                    row.target_path.clone(),
+                    status_separator,
+                    &row.status,
+                )
+            };
+
+            let get_snipped_output = |_compact_counter: &CompactCounter| {
+                layout.format_snipped_output(_compact_counter.skipped_status_count, 0, true)
+            };
+
+            let is_status_shown = args.show_only.is_empty() || args.show_only.contains(&row.status_category);
+
+            push_file_status_compacted(&mut compact_counter, &row.status, is_status_shown, is_last_element,
+                                       args, get_output_for_file, get_snipped_output, &mut output_lines);
+        }
+        output_lines.iter().for_each(|line| println!("{}", line));
+
+        // leave some empty space before the next source dir
+        println!();
+    }
 }
 
 /// Iterate all source files and write them to target, printing the operation status.
@@ -1820,47 +4631,278 @@ fn process_files_dry_run(
 /// ─────────────────────────────────────────────────────────────────────────────────────────
 /// SOURCE PATH                   TARGET FILE                                OPERATION STATUS
 /// ─────────────────────────────────────────────────────────────────────────────────────────
-/// [Created folder 2019.01.28]
+/// [2019.01.28] (2 devices, 3 files, 3.34 MB) ............................ [new folder created]
+///  └── [Canon 100D] ....................................................... [new folder created]
+///
 /// D:\Pics\IMG-20190127.jpg ───> 2019.01.28\IMG-20190127.jpg .............. ok
 /// D:\Pics\IMG-20190128.jpg ───> 2019.01.28\Canon 100D\IMG-20190128.jpg ... already exists
 /// D:\Pics\IMG-20190129.jpg ───> 2019.01.28\Canon 100D\IMG-20190129.jpg ... ok
 /// ```
-fn process_files_write(
-    files_and_paths_vec: &[SupportedFile],
+fn process_files_write<'a>(
+    job: &FolderWriteJob<'a>,
+    args: &Args,
+    stats: &FileStats,
+    layout: &WriteLayout,
+    records: &WriteRecords,
+    in_use_files: &Mutex<Vec<(&'a SupportedFile, PathBuf)>>,
+    output: &FolderOutput,
+) {
+    for file in job.files.iter() {
+        // The files being written are completed, but no other file is started once the run is stopped
+        if is_interrupted() {
+            break;
+        }
+        let file_destination_path = job.device_destination_path.join(&file.target_file_name);
+
+        // Files which are in use are only skipped if they're not retried at the end of the run
+        let in_use_retries = if args.retry_files_in_use { Some(in_use_files) } else { None };
+        // Large files advance the progress bar while they're copied, and the rest of their size is added once written
+        let copied_size = Cell::new(0);
+        let on_copy_progress = |chunk_size| {
+            copied_size.set(copied_size.get() + chunk_size);
+            output.advance(chunk_size);
+        };
+        let row = write_file_row(file, file_destination_path, args, stats, layout, records, in_use_retries, Some(&on_copy_progress));
+
+        // Print result, together with the other files of the same target folder
+        output.push(job.folder_index, row);
+        output.advance(file.metadata.len().saturating_sub(copied_size.get()));
+    }
+
+    // leave some empty space before the next date dir
+    if job.is_last_dir {
+        output.push(job.folder_index, String::new());
+    }
+    output.finish(job.folder_index);
+}
+
+/// The files of a single target folder, which are written together by the same thread
+struct FolderWriteJob<'a> {
+    files: &'a [SupportedFile],
     device_destination_path: PathBuf,
+    /// The position of the folder in the dir tree, which is also the order its rows are printed in
+    folder_index: usize,
+    /// Whether this is the last device folder of its date folder
+    is_last_dir: bool,
+}
+
+/// The records of the written files, shared by all threads which write files.
+/// Each one is only locked while a single entry is recorded, not while the file is written
+struct WriteRecords {
+    journal: Mutex<Journal>,
+    operation_log: Mutex<OperationLog>,
+    import_log: Mutex<ImportIndexLog>,
+}
+
+impl WriteRecords {
+    /// Open the journal, the operation log and the import index of a run, which are disabled for dry runs,
+    /// and mark the run as started, so that it can be resumed if it's interrupted
+    fn open(args: &Args) -> WriteRecords {
+        let mut journal = if args.dry_run {
+            Journal::disabled()
+        } else {
+            Journal::create(&args.target_dir).unwrap_or_else(|e| {
+                println!("{} Could not create the journal in {}, this run cannot be undone: {}",
+                         ColoredString::warn_arrow(), args.target_dir.display(), e);
+                Journal::disabled()
+            })
+        };
+        if let Err(e) = journal.mark_running(&args.source_dirs, args.source_files_list.as_deref(), args.applied_plan_path.as_deref()) {
+            println!("{} Could not mark the run as started in {}, it can't be resumed if it's interrupted: {}",
+                     ColoredString::warn_arrow(), args.target_dir.display(), e);
+        }
+
+        // Dry runs don't write anything, so Ctrl+C can stop them right away
+        if !args.dry_run {
+            start_writing();
+        }
+
+        // Keep a permanent record of each operation in the target folder, including skipped files and errors
+        let operation_log = if args.dry_run || !args.write_operation_log {
+            OperationLog::disabled()
+        } else {
+            OperationLog::open(&args.target_dir).unwrap_or_else(|e| {
+                println!("{} Could not open the operation log in {}: {}",
+                         ColoredString::warn_arrow(), args.target_dir.display(), e);
+                OperationLog::disabled()
+            })
+        };
+
+        // Add each written file to the import index, so that later runs skip it
+        let import_log = match &args.import_index_path {
+            Some(index_path) if !args.dry_run => ImportIndexLog::open(index_path).unwrap_or_else(|e| {
+                println!("{} Could not open the import index {}, the files of this run will be imported again: {}",
+                         ColoredString::warn_arrow(), index_path.display(), e);
+                ImportIndexLog::disabled()
+            }),
+            _ => ImportIndexLog::disabled(),
+        };
+
+        WriteRecords {
+            journal: Mutex::new(journal),
+            operation_log: Mutex::new(operation_log),
+            import_log: Mutex::new(import_log),
+        }
+    }
+
+    /// Mark the run as finished and report the records which could not be written.
+    /// A run stopped with Ctrl+C stays marked as started, so that the next run offers to resume or roll it back
+    fn close(self) {
+        stop_writing();
+        let (mut journal, operation_log, import_log) = self.into_inner();
+        if is_interrupted() {
+            if let Err(e) = journal.sync() {
+                println!("{} Could not save the journal, this run can only be partially undone: {}",
+                         ColoredString::warn_arrow(), e);
+                println!();
+            }
+        } else if let Err(e) = journal.finish() {
+            println!("{} Could not mark the run as finished, the next run will ask to resume it: {}",
+                     ColoredString::warn_arrow(), e);
+            println!();
+        }
+        if let Some(e) = journal.error() {
+            println!("{} Could not write to the journal, this run can only be partially undone: {}",
+                     ColoredString::warn_arrow(), e);
+            println!();
+        }
+        if let Some(e) = operation_log.error() {
+            println!("{} Could not write to the operation log, some operations were not recorded: {}",
+                     ColoredString::warn_arrow(), e);
+            println!();
+        }
+        if let Some(e) = import_log.error() {
+            println!("{} Could not write to the import index, some files will be imported again: {}",
+                     ColoredString::warn_arrow(), e);
+            println!();
+        }
+    }
+
+    fn record_journal(&self, entry: JournalEntry) {
+        lock_stat(&self.journal).record(entry)
+    }
+
+    fn record_operation(&self, record: OperationRecord) {
+        lock_stat(&self.operation_log).record(record)
+    }
+
+    fn record_import(&self, date: &str, source: &Path, target: &Path) {
+        lock_stat(&self.import_log).record(date, source, target)
+    }
+
+    /// The records are only read once all files were written, even if a writing thread panicked
+    fn into_inner(self) -> (Journal, OperationLog, ImportIndexLog) {
+        (
+            self.journal.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner()),
+            self.operation_log.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner()),
+            self.import_log.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner()),
+        )
+    }
+}
+
+/// Write the files of all target folders, using up to [Args::write_threads] threads.
+/// Each thread writes a whole folder at a time, taking the next one in the order of the dir tree,
+/// and the rows of each folder are printed in that order, see [FolderOutput]
+fn write_folders<'a>(
+    write_jobs: &[FolderWriteJob<'a>],
     args: &Args,
-    mut stats: &mut FileStats,
-    padder: &mut Padder,
+    stats: &FileStats,
+    layout: &WriteLayout,
+    records: &WriteRecords,
+    in_use_files: &Mutex<Vec<(&'a SupportedFile, PathBuf)>>,
+    output: &FolderOutput,
 ) {
-    for file in files_and_paths_vec.iter() {
-        let mut file_destination_path = device_destination_path.clone().join(&file.file_name);
-
-        // Prepare padded strings for output
-        let source_path = file.get_source_display_name_str(args);
-        let padded_separator = padder.format_write_file_separator(source_path.clone());
-        let stripped_target_path = file_destination_path
-            .strip_prefix(&args.target_dir)
-            .unwrap()
-            .display()
-            .to_string();
-        let status_separator =
-            padder.format_write_status_separator_dotted(stripped_target_path.clone());
-
-        // Copy/move file
-        let file_write_status =
-            copy_file_if_not_exists(file, &mut file_destination_path, args, &mut stats);
-
-        // Print result
-        let output = process_files_format_status(
-            source_path,
-            padded_separator,
-            stripped_target_path,
-            status_separator,
-            &file_write_status,
-        );
+    let write_folder = |job: &FolderWriteJob<'a>| {
+        process_files_write(job, args, stats, layout, records, in_use_files, output)
+    };
+
+    let threads_count = args.write_threads.min(write_jobs.len());
+    if threads_count <= 1 {
+        write_jobs.iter().take_while(|_| !is_interrupted()).for_each(write_folder);
+        return;
+    }
+
+    let next_job = AtomicUsize::new(0);
+    thread::scope(|scope| {
+        for _ in 0..threads_count {
+            scope.spawn(|| {
+                while let Some(job) = write_jobs.get(next_job.fetch_add(1, Ordering::Relaxed)) {
+                    if is_interrupted() {
+                        break;
+                    }
+                    write_folder(job);
+                }
+            });
+        }
+    });
+}
+
+/// Give the other program some time to close the files before they're retried
+const IN_USE_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Copy or move the files which were in use by another program once more, after all other files
+/// were written, see [Args::retry_files_in_use]. Files which are still in use are skipped
+fn retry_files_in_use(
+    in_use_files: Vec<(&SupportedFile, PathBuf)>,
+    args: &Args,
+    stats: &FileStats,
+    layout: &WriteLayout,
+    records: &WriteRecords,
+) {
+    if in_use_files.is_empty() || is_interrupted() {
+        return;
+    }
+
+    println!("Retrying {} files which were in use by another program...", in_use_files.len());
+    println!();
+    thread::sleep(IN_USE_RETRY_DELAY);
 
-        println!("{}", output);
+    for (file, file_destination_path) in in_use_files {
+        println!("{}", write_file_row(file, file_destination_path, args, stats, layout, records, None, None));
     }
+    println!();
+}
+
+/// Copy or move a single file and format its row of the output table
+#[allow(clippy::too_many_arguments)]
+fn write_file_row<'a>(
+    file: &'a SupportedFile,
+    mut file_destination_path: PathBuf,
+    args: &Args,
+    stats: &FileStats,
+    layout: &WriteLayout,
+    records: &WriteRecords,
+    in_use_retries: Option<&Mutex<Vec<(&'a SupportedFile, PathBuf)>>>,
+    on_copy_progress: Option<&dyn Fn(u64)>,
+) -> String {
+    // Prepare padded strings for output
+    let source_path = file.get_source_display_name_str(args);
+    let padded_separator = layout.format_file_separator(&source_path);
+    let stripped_target_path = file_destination_path
+        .strip_prefix(&args.target_dir)
+        .unwrap()
+        .display()
+        .to_string();
+    let status_separator = layout.format_status_separator(&stripped_target_path);
+
+    // Copy/move file
+    let file_write_status = copy_file_if_not_exists(
+        file, &mut file_destination_path, args, stats, records, in_use_retries, on_copy_progress);
+
+    process_files_format_status(
+        source_path,
+        padded_separator,
+        stripped_target_path,
+        status_separator,
+        &file_write_status,
+    )
+}
+
+/// Print the names of the columns between two separator lines
+fn print_table_header(layout: &impl Layout) {
+    println!("{}", ColoredString::bold_white(layout.header_separator()));
+    println!("{}", ColoredString::bold_white(layout.header()));
+    println!("{}", ColoredString::bold_white(layout.header_separator()));
 }
 
 fn process_files_format_status(
@@ -1887,11 +4929,32 @@ fn get_files_size(files: &[SupportedFile]) -> u64 {
         .sum()
 }
 
+/// Format the date dir name together with the number of devices, files and their total size, e.g.:
+/// `[2019.01.28] (2 devices, 3 files, 3.34 MB) `
+fn format_date_dir_summary(
+    date_dir_name: &str,
+    device_count: usize,
+    file_count: usize,
+    file_size: u64,
+) -> String {
+    let device_count_str = if device_count == 1 {"device"} else {"devices"};
+    let file_count_str = if file_count == 1 {"file"} else {"files"};
+
+    format!(
+        "[{dirname}] ({devicecount:?} {devicestr}, {filecount:?} {filestr}, {filesize}) ",
+        dirname = date_dir_name,
+        devicecount = device_count,
+        devicestr = device_count_str,
+        filecount = file_count,
+        filestr = file_count_str,
+        filesize = format_file_size(file_size))
+}
+
 /// Read a directory path and return a string signalling if the path exists
 fn dry_run_check_target_dir_exists(
     path: &Path,
     dir_type: &DirType,
-    stats: &mut FileStats,
+    stats: &FileStats,
 ) -> String {
     stats.inc_dir_total_by_type(dir_type);
     if path.exists() {
@@ -1904,6 +4967,116 @@ fn dry_run_check_target_dir_exists(
     }
 }
 
+/// The operation statuses of dry runs which are the same for all files
+const DRYRUN_STATUS_COPY: &str = "file will be copied";
+const DRYRUN_STATUS_MOVE: &str = "file will be moved";
+const DRYRUN_STATUS_OVERWRITE: &str = "target file exists, will be overwritten";
+const DRYRUN_STATUS_DUPLICATE_SOURCE: &str = "duplicate source file, will be skipped";
+const DRYRUN_STATUS_TARGET_EXISTS: &str = "target file exists, will be skipped";
+const DRYRUN_STATUS_TOO_LARGE: &str = "file too large for target, will not be written";
+const DRYRUN_STATUS_READ_ONLY: &str = "source is read only, file will be copied";
+const DRYRUN_STATUS_SOURCE_MISSING: &str = "source file does not exist";
+
+/// The operation statuses of written files which are the same for all files
+const WRITE_STATUS_OK: &str = "ok";
+const WRITE_STATUS_OVERWRITTEN: &str = "ok, overwritten";
+const WRITE_STATUS_SOURCE_REMOVED: &str = " (source file removed)";
+const WRITE_STATUS_TARGET_EXISTS: &str = "already exists";
+const WRITE_STATUS_TOO_LARGE: &str = "ERROR file too large for target";
+const WRITE_STATUS_FILE_IN_USE: &str = "file is in use, skipped";
+const WRITE_STATUS_FILE_IN_USE_RETRY: &str = "file is in use, will retry";
+const WRITE_STATUS_SOURCE_IN_USE: &str = " (source file is in use, not removed)";
+
+fn format_dryrun_skipped_status(skip_reason: &str) -> String {
+    format!("{}, will be skipped", skip_reason)
+}
+
+fn format_write_skipped_status(skip_reason: &str) -> String {
+    format!("skipped, {}", skip_reason)
+}
+
+/// The width of the operation status column, i.e. the length of the longest status any file can have
+/// in this run, including the reasons for skipping files. Error messages are not known in advance,
+/// so these are not included and may still be longer than the column
+fn get_status_width(new_dir_tree: &TargetDateDeviceTree, args: &Args, is_dry_run: bool) -> usize {
+    let fixed_statuses: Vec<String> = if is_dry_run {
+        [DRYRUN_STATUS_COPY, DRYRUN_STATUS_MOVE, DRYRUN_STATUS_OVERWRITE, DRYRUN_STATUS_DUPLICATE_SOURCE,
+            DRYRUN_STATUS_TARGET_EXISTS, DRYRUN_STATUS_TOO_LARGE, DRYRUN_STATUS_READ_ONLY, DRYRUN_STATUS_SOURCE_MISSING]
+            .iter()
+            .map(|status| status.to_string())
+            .collect()
+    } else {
+        let written_status = if args.copy_not_move {
+            WRITE_STATUS_OVERWRITTEN.to_string()
+        } else {
+            format!("{}{}", WRITE_STATUS_OVERWRITTEN, WRITE_STATUS_SOURCE_REMOVED)
+        };
+        vec![written_status, WRITE_STATUS_TARGET_EXISTS.to_string(), WRITE_STATUS_TOO_LARGE.to_string(),
+             WRITE_STATUS_FILE_IN_USE.to_string(), WRITE_STATUS_FILE_IN_USE_RETRY.to_string()]
+    };
+
+    let skipped_statuses = new_dir_tree.dir_tree
+        .values()
+        .flat_map(|device_tree| device_tree.file_tree.values())
+        .flatten()
+        .filter_map(|file| file.skip_reason.as_deref())
+        .map(|skip_reason| if is_dry_run {
+            format_dryrun_skipped_status(skip_reason)
+        } else {
+            format_write_skipped_status(skip_reason)
+        });
+
+    fixed_statuses
+        .into_iter()
+        .chain(skipped_statuses)
+        .map(get_string_char_count)
+        .max()
+        .unwrap_or(0)
+        .max(get_string_char_count(String::from(HEADER_OPERATION_STATUS)))
+}
+
+/// The function used to color a status, the status and its description
+type LegendEntry = (fn(&str) -> String, &'static str, &'static str);
+
+/// The operation statuses of dry runs, with the color used to print them and their meaning.
+/// Skipped files use the same color regardless of the reason, so only the main reasons are listed.
+/// The status of files which will be written depends on the operation, so it's added separately
+const DRY_RUN_STATUS_LEGEND: [LegendEntry; 7] = [
+    (ColoredString::orange, "duplicate source file", "the same file was found in another source folder, only the first one will be written"),
+    (ColoredString::orange, "target file exists", "a file with the same name already exists in the target folder"),
+    (ColoredString::orange, "will be overwritten", "the file will replace the one with the same name in the target folder"),
+    (ColoredString::orange, "..., will be skipped", "the file will be left in the source folder for the reason shown, e.g. a HEIC/JPEG pair"),
+    (ColoredString::red, "source is read only", "the file can be copied, but not deleted from the source folder"),
+    (ColoredString::red, "source file does not exist", "the file could not be found or read, nothing will be written"),
+    (ColoredString::red, "file too large for target", "the target folder is on a FAT32 drive, which can't hold files of 4 GB or larger"),
+];
+
+/// Print a short explanation of each operation status below the dry run header
+fn print_dry_run_legend(args: &Args) {
+    let write_status: LegendEntry = (
+        ColoredString::green,
+        if args.copy_not_move { DRYRUN_STATUS_COPY } else { DRYRUN_STATUS_MOVE },
+        "the file will be written to the target folder");
+
+    let legend = std::iter::once(write_status).chain(DRY_RUN_STATUS_LEGEND.iter().copied()).collect::<Vec<_>>();
+
+    let status_width = legend
+        .iter()
+        .map(|(_, status, _)| get_string_char_count(status.to_string()))
+        .max()
+        .unwrap_or(0);
+
+    println!("{}", ColoredString::dim("Legend:"));
+    for (color, status, description) in legend {
+        println!("  {} {}",
+                 color(RightPadding::space(status.to_string(), status_width).as_str()),
+                 ColoredString::dim(description));
+    }
+    println!("{}", ColoredString::dim(
+        "  (disable this legend by setting the `show_dry_run_legend` option to false)"));
+    println!();
+}
+
 /// Read a path and return a string signalling copy/move restrictions:
 /// * in both cases, check if the source file exists - no copy will take place
 /// * in both cases, check if the target file exists - file will be skipped
@@ -1911,19 +5084,19 @@ fn dry_run_check_target_dir_exists(
 /// * if this is a move, check if the source file is read-only and can't be moved (only copied)
 fn dry_run_check_file_restrictions(
     source_file: &SupportedFile,
-    target_path: &PathBuf,
-    source_unique_files: &mut HashSet<OsString>,
+    target_path: &Path,
+    dry_run_state: &mut DryRunState,
     args: &Args,
-    stats: &mut FileStats,
-) -> String {
+    stats: &FileStats,
+) -> (StatusCategory, String) {
 
     // If this is the first time we've seen this file, store it so we can find duplicates later
     let mut is_source_unique = || {
-        let path_string = target_path.clone().into_os_string();
-        if source_unique_files.contains(&path_string) {
+        let path_string = target_path.as_os_str().to_os_string();
+        if dry_run_state.source_unique_files.contains(&path_string) {
             false
         } else {
-            source_unique_files.insert(path_string);
+            dry_run_state.source_unique_files.insert(path_string);
             true
         }
     };
@@ -1934,15 +5107,30 @@ fn dry_run_check_file_restrictions(
         // The order of checks matters - check for duplicates first, otherwise the reason
         // for skipping it will not be accurate. If the target file actually exists,
         // only the first of the duplicates should show as skipped for that reason.
-        if !is_source_unique() {
-            stats.inc_skipped_by_type(source_file);
-            ColoredString::orange("duplicate source file, will be skipped")
+        if let Some(skip_reason) = &source_file.skip_reason {
+            stats.inc_skipped_by_type(source_file, skip_reason);
+            (StatusCategory::Skipped, ColoredString::orange(format_dryrun_skipped_status(skip_reason).as_str()))
+        } else if !is_source_unique() {
+            stats.inc_skipped_by_type(source_file, SKIP_REASON_DUPLICATE_SOURCE);
+            (StatusCategory::Skipped, ColoredString::orange(DRYRUN_STATUS_DUPLICATE_SOURCE))
+        } else if target_path.exists() && !source_file.overwrites_target {
+            stats.inc_skipped_by_type(source_file, SKIP_REASON_TARGET_EXISTS);
+            (StatusCategory::Skipped, ColoredString::orange(DRYRUN_STATUS_TARGET_EXISTS))
+        } else if is_too_large_for_target(source_file, args) {
+            stats.inc_error_file_create();
+            (StatusCategory::Errors, ColoredString::red(DRYRUN_STATUS_TOO_LARGE))
         } else if target_path.exists() {
-            stats.inc_skipped_by_type(source_file);
-            ColoredString::orange("target file exists, will be skipped")
+            if args.copy_not_move {
+                stats.inc_copied_by_type(source_file);
+            } else {
+                stats.inc_moved_by_type(source_file);
+            }
+            dry_run_state.add_script_operation(source_file, target_path, !args.copy_not_move, true);
+            (StatusCategory::Written, ColoredString::orange(DRYRUN_STATUS_OVERWRITE))
         } else if args.copy_not_move {
             stats.inc_copied_by_type(source_file);
-            ColoredString::green("file will be copied")
+            dry_run_state.add_script_operation(source_file, target_path, false, false);
+            (StatusCategory::Written, ColoredString::green(DRYRUN_STATUS_COPY))
         } else {
             // Check if the source file can be deleted after copy
 
@@ -1950,23 +5138,347 @@ fn dry_run_check_file_restrictions(
                 Ok(metadata) => {
                     let is_read_only = metadata.permissions().readonly();
 
-                    if !args.copy_not_move && is_read_only {
-                        stats.inc_error_file_delete();
-                        stats.inc_copied_by_type(source_file);
-                        ColoredString::red("source is read only, file will be copied")
-                    } else {
-                        stats.inc_moved_by_type(source_file);
-                        ColoredString::green("file will be moved")
-                    }
-                }
-                Err(e) => {
-                    let err_status = format!("error reading metadata: {}", e.to_string());
-                    ColoredString::red(err_status.as_str())
-                }
-            }
+                    if !args.copy_not_move && is_read_only {
+                        stats.inc_error_file_delete();
+                        stats.inc_copied_by_type(source_file);
+                        dry_run_state.add_script_operation(source_file, target_path, false, false);
+                        (StatusCategory::Errors, ColoredString::red(DRYRUN_STATUS_READ_ONLY))
+                    } else {
+                        stats.inc_moved_by_type(source_file);
+                        dry_run_state.add_script_operation(source_file, target_path, true, false);
+                        (StatusCategory::Written, ColoredString::green(DRYRUN_STATUS_MOVE))
+                    }
+                }
+                Err(e) => {
+                    let err_status = format!("error reading metadata: {}", e);
+                    (StatusCategory::Errors, ColoredString::red(err_status.as_str()))
+                }
+            }
+        }
+    } else {
+        (StatusCategory::Errors, ColoredString::red(DRYRUN_STATUS_SOURCE_MISSING))
+    }
+}
+
+/// Print the number of target date and device folders, together with a truncated
+/// dir tree of the first and last few date folders, e.g.:
+/// ```
+/// Target folders: 12 date folders (10 new), 3 device folders (3 new)
+/// [2019.01.28] (2 devices, 5 files, 3.34 MB)
+///  ├── [Canon 100D]
+///  └── [Samsung SM-A415F]
+///  ·-- (8 more date folders)
+/// [2019.03.02] (1 device, 2 files, 4.10 MB)
+/// ```
+fn print_target_dir_preview(new_dir_tree: &TargetDateDeviceTree, args: &Args) {
+    // The number of date folders to show at both the start and the end of the preview
+    const PREVIEW_DATE_DIRS: usize = 3;
+
+    if new_dir_tree.dir_tree.is_empty() {
+        return;
+    }
+
+    let date_dirs_new = new_dir_tree.dir_tree
+        .keys()
+        .filter(|date_dir_name| !args.target_dir.join(date_dir_name).exists())
+        .count();
+
+    let device_dirs: Vec<PathBuf> = new_dir_tree.dir_tree
+        .iter()
+        .flat_map(|(date_dir_name, device_tree)| device_tree.file_tree
+            .keys()
+            .filter(move |device_name| device_tree.has_device_subdir(device_name))
+            .map(move |device_name| args.target_dir.join(date_dir_name).join(device_name.to_string())))
+        .collect();
+    let device_dirs_new = device_dirs.iter().filter(|device_dir| !device_dir.exists()).count();
+
+    println!();
+    println!("{}", ColoredString::bold_white(format!(
+        "Target folders: {} date {} ({} new), {} device {} ({} new)",
+        new_dir_tree.dir_tree.len(),
+        if new_dir_tree.dir_tree.len() == 1 { "folder" } else { "folders" },
+        date_dirs_new,
+        device_dirs.len(),
+        if device_dirs.len() == 1 { "folder" } else { "folders" },
+        device_dirs_new).as_str()));
+
+    let date_dir_count = new_dir_tree.dir_tree.len();
+    let snipped_count = date_dir_count.saturating_sub(PREVIEW_DATE_DIRS * 2);
+
+    for (date_dir_ix, (date_dir_name, device_tree)) in new_dir_tree.dir_tree.iter().enumerate() {
+        if snipped_count > 0 && date_dir_ix >= PREVIEW_DATE_DIRS && date_dir_ix < date_dir_count - PREVIEW_DATE_DIRS {
+            if date_dir_ix == PREVIEW_DATE_DIRS {
+                println!("{}{}", DIR_TREE_SNIP, ColoredString::italic_dim(
+                    format!("({} more date folders)", snipped_count).as_str()));
+            }
+            continue;
+        }
+
+        let file_size_for_date = device_tree.file_tree
+            .values()
+            .map(|files_and_paths| get_files_size(files_and_paths))
+            .sum();
+        println!("{}", format_date_dir_summary(
+            date_dir_name, device_tree.file_tree.len(), device_tree.file_count(), file_size_for_date).trim_end());
+
+        let device_subdirs: Vec<&DirEntryType> = device_tree.file_tree
+            .keys()
+            .filter(|device_name| device_tree.has_device_subdir(device_name))
+            .collect();
+        let device_subdir_count = device_subdirs.len();
+        for (device_dir_ix, device_name) in device_subdirs.into_iter().enumerate() {
+            let is_last_dir = device_dir_ix == device_subdir_count - 1;
+            println!("{}", indent_string(0, format!("[{}]", device_name), is_last_dir, is_last_dir));
+        }
+    }
+    println!();
+}
+
+fn get_file_type_name(file_type: &FileType) -> &'static str {
+    match file_type {
+        FileType::Image => IMAGE,
+        FileType::Video => VIDEO,
+        FileType::Audio => AUDIO,
+        FileType::Animation => ANIMATION,
+        FileType::Document => DOCUMENT,
+        FileType::Sidecar => SIDECAR,
+        FileType::Unknown(_) => "unknown",
+    }
+}
+
+/// List each source file with the target folder it will be sorted into
+/// and the information read from it which decided that folder, e.g.:
+/// ```text
+/// D:\Pics\IMG_0001.JPG ───> 2019.01.28/Canon 100D (image, taken 2019:01:28 14:02:11, 6000x4000)
+/// D:\Pics\VID_0002.MP4 ───> 2019.01.28 (video, no EXIF date)
+/// ```
+fn print_inspect_report(new_dir_tree: &TargetDateDeviceTree, args: &Args) {
+    // The source path and the details of each file, and whether it's an image, whose thumbnail may be shown
+    let mut file_lines: Vec<(String, String, bool)> = Vec::new();
+
+    for (date_dir_name, device_tree) in &new_dir_tree.dir_tree {
+        for (device_name, files) in &device_tree.file_tree {
+            let target_dir = if device_tree.has_device_subdir(device_name) {
+                format!("{}/{}", date_dir_name, device_name)
+            } else {
+                date_dir_name.clone()
+            };
+
+            for file in files {
+                let mut details = vec![get_file_type_name(&file.file_type).to_string()];
+                details.push(match &file.capture_time {
+                    Some(capture_time) => format!("taken {}", capture_time),
+                    None => String::from("no EXIF date"),
+                });
+                if let DirEntryType::Directory(device) = device_name {
+                    details.push(device.clone());
+                }
+                if let Some((width, height)) = file.dimensions {
+                    details.push(format!("{}x{}", width, height));
+                }
+
+                let mut line = format!("{} ({})", target_dir, details.join(", "));
+                if let Some(skip_reason) = &file.skip_reason {
+                    line.push_str(ColoredString::orange(format!(" {}, will be skipped", skip_reason).as_str()).as_str());
+                }
+                file_lines.push((file.file_path.display().to_string(), line, matches!(file.file_type, FileType::Image)));
+            }
+        }
+    }
+
+    file_lines.sort();
+    let max_path_len = file_lines
+        .iter()
+        .map(|(source_path, _, _)| get_string_char_count(source_path.clone()))
+        .max()
+        .unwrap_or(0);
+
+    let show_thumbnails = args.show_thumbnails && supports_inline_images();
+    if args.show_thumbnails && !show_thumbnails {
+        println!("{} This terminal can't show images, so no thumbnails will be shown. Try iTerm2, WezTerm or mintty.",
+                 ColoredString::warn_arrow());
+        println!();
+    }
+
+    let image_count = file_lines.iter().filter(|(_, _, is_image)| *is_image).count();
+    if show_thumbnails && image_count > MAX_LISTED_THUMBNAILS {
+        println!("{} Only the thumbnails of the first {} of {} photos will be shown. Inspect fewer source files to see the others.",
+                 ColoredString::warn_arrow(), MAX_LISTED_THUMBNAILS, image_count);
+        println!();
+    }
+
+    let mut listed_images = 0;
+    for (source_path, line, is_image) in file_lines {
+        let thumbnail = if show_thumbnails && is_image && listed_images < MAX_LISTED_THUMBNAILS {
+            read_exif_thumbnail(Path::new(&source_path))
+        } else {
+            None
+        };
+        listed_images += is_image as usize;
+        println!("{} {} {}", RightPadding::space(source_path, max_path_len), SEPARATOR_COPY_MOVE, line);
+        if let Some(thumbnail) = thumbnail {
+            println!("{}", format_inline_image(&thumbnail));
+        }
+    }
+    println!();
+}
+
+/// Build the plan of this run from the target tree: the state of each source file
+/// and the path it will be written to, or the reason it's skipped
+fn build_plan_lock(new_dir_tree: &TargetDateDeviceTree, args: &Args) -> PlanLock {
+    let mut planned_files: Vec<PlannedFile> = Vec::new();
+
+    for (date_dir_name, device_tree) in &new_dir_tree.dir_tree {
+        for (device_name, files) in &device_tree.file_tree {
+            let target_dir = if device_tree.has_device_subdir(device_name) {
+                args.target_dir.join(date_dir_name).join(device_name.to_string())
+            } else {
+                args.target_dir.join(date_dir_name)
+            };
+
+            planned_files.extend(files.iter().map(|file| PlannedFile {
+                source_path: file.file_path.clone(),
+                size: file.metadata.len(),
+                modified: file.metadata
+                    .modified()
+                    .ok()
+                    .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                    .map_or(0, |since_epoch| since_epoch.as_nanos()),
+                target_path: target_dir.join(&file.target_file_name),
+                skip_reason: file.skip_reason.clone(),
+            }));
+        }
+    }
+
+    PlanLock::new(planned_files)
+}
+
+/// Describe why the current plan is different than the one saved by the last dry run
+fn print_plan_changes(saved_plan_lock: &PlanLock, plan_lock: &PlanLock) {
+    println!("{}", ColoredString::red("The plan changed since the last dry run, no files were written."));
+
+    let (added, removed) = saved_plan_lock.compare_source_files(plan_lock);
+    if added.is_empty() && removed.is_empty() {
+        println!("  Some source files were changed, or the configuration is different.");
+    }
+    if !added.is_empty() {
+        println!("  New source files:");
+        added.iter().for_each(|path| println!("    {}", path.display()));
+    }
+    if !removed.is_empty() {
+        println!("  Source files which no longer exist:");
+        removed.iter().for_each(|path| println!("    {}", path.display()));
+    }
+    println!();
+}
+
+/// Compare each source file with the file at its planned path inside the target folder,
+/// e.g. to check that a large copy completed correctly before deleting the sources
+fn verify_target_against_sources(new_dir_tree: &TargetDateDeviceTree, args: &Args) -> SourceVerifyReport {
+    let mut report = SourceVerifyReport::default();
+
+    println!("Comparing the files in {} with the source files...", args.target_dir.display());
+    println!();
+
+    for (date_dir_name, device_tree) in &new_dir_tree.dir_tree {
+        for (device_name, files) in &device_tree.file_tree {
+            let target_dir = if device_tree.has_device_subdir(device_name) {
+                args.target_dir.join(date_dir_name).join(device_name.to_string())
+            } else {
+                args.target_dir.join(date_dir_name)
+            };
+
+            for file in files {
+                if file.skip_reason.is_some() {
+                    report.skipped += 1;
+                    continue;
+                }
+                let target_path = target_dir.join(&file.target_file_name);
+                let display_name = target_path.strip_prefix(&args.target_dir).unwrap_or(&target_path).display().to_string();
+                report.compare(&file.file_path, &target_path, &display_name, args.verbose);
+            }
+        }
+    }
+
+    report
+}
+
+/// Print the groups of source files with the same contents, and the space taken by the extra copies
+fn print_identical_files<'a>(source_files: impl Iterator<Item = &'a DirEntry>) {
+    println!("Comparing the contents of the source files...");
+    println!();
+
+    let identical_files = find_identical_files(source_files.filter_map(|source_file| {
+        let metadata = source_file.metadata().ok()?;
+        Some((source_file.path(), metadata.len()))
+    }));
+
+    if identical_files.is_empty() {
+        println!("No files with the same contents were found.");
+        return;
+    }
+
+    for group in &identical_files {
+        println!("{}", ColoredString::bold_white(format!(
+            "{} files with the same contents ({} each):", group.paths.len(), format_file_size(group.size)).as_str()));
+        group.paths
+            .iter()
+            .for_each(|path| println!("  {}", path.display()));
+        println!();
+    }
+
+    let redundant_count: usize = identical_files.iter().map(|group| group.paths.len() - 1).sum();
+    let redundant_size: u64 = identical_files.iter().map(IdenticalFiles::redundant_size).sum();
+    println!("Groups of identical files: {}, extra copies: {} ({}). Nothing was changed.",
+             identical_files.len(), redundant_count, format_file_size(redundant_size));
+}
+
+/// Print the number and size of the source files, grouped by type, device and year
+fn print_source_stats(new_dir_tree: &TargetDateDeviceTree) {
+    let mut by_type: BTreeMap<String, (usize, u64)> = BTreeMap::new();
+    let mut by_device: BTreeMap<String, (usize, u64)> = BTreeMap::new();
+    let mut by_year: BTreeMap<String, (usize, u64)> = BTreeMap::new();
+
+    let all_files = new_dir_tree.dir_tree
+        .values()
+        .flat_map(|device_tree| device_tree.file_tree.iter())
+        .flat_map(|(device_name, files)| files.iter().map(move |file| (device_name, file)));
+
+    for (device_name, file) in all_files {
+        let file_size = file.metadata.len();
+        let device = match device_name {
+            DirEntryType::Directory(device) => device.clone(),
+            DirEntryType::Files => String::from("(unknown device)"),
+        };
+        // Prefer the EXIF date, same as when sorting, e.g. `2019:01:28 14:02:11`
+        let year = file.capture_time
+            .as_ref()
+            .map(|capture_time| capture_time.chars().take(4).collect::<String>())
+            .or_else(|| file.metadata.modified().ok().map(|modified_time| {
+                let datetime: DateTime<Utc> = modified_time.into();
+                datetime.format("%Y").to_string()
+            }))
+            .unwrap_or_else(|| DEFAULT_NO_DATE_STR.to_string());
+
+        for (group, key) in [(&mut by_type, get_file_type_name(&file.file_type).to_string()), (&mut by_device, device), (&mut by_year, year)] {
+            let (count, size) = group.entry(key).or_insert((0, 0));
+            *count += 1;
+            *size += file_size;
+        }
+    }
+
+    for (title, group) in [("Files by type", by_type), ("Files by device", by_device), ("Files by year", by_year)] {
+        let max_key_len = group.keys().map(|key| get_string_char_count(key.clone())).max().unwrap_or(0);
+        let max_count_len = group.values().map(|(count, _)| count.to_string().len()).max().unwrap_or(0);
+        println!("{}", ColoredString::bold_white(title));
+        for (key, (count, size)) in group {
+            println!("  {} {} {} ({})",
+                     RightPadding::space(key, max_key_len),
+                     LeftPadding::space(count.to_string(), max_count_len),
+                     if count == 1 { "file " } else { "files" },
+                     format_file_size(size));
         }
-    } else {
-        ColoredString::red("source file does not exist")
+        println!();
     }
 }
 
@@ -2000,18 +5512,82 @@ fn ask_for_op_confirmation(args: &Args) -> ConfirmationType {
     }
 }
 
+/// Ask whether to overwrite target files which are newer or larger than their source files
+fn ask_for_overwrite_confirmation(args: &Args) -> bool {
+    println!("{}",
+             ColoredString::magenta(
+                 "Overwrite these files anyway? Type one of the options then press Enter:\n\
+                 • 'y' or 'yes' to overwrite them\n\
+                 • 'n' or 'no' to keep them and skip their source files"));
+    loop {
+        let mut user_input = String::new();
+        match io::stdin().read_line(&mut user_input) {
+            // There's no more input, e.g. if it was piped from another program
+            Ok(0) => return false,
+            Ok(input) => {
+                if args.debug {
+                    println!("User input: '{:?}'", input)
+                }
+            }
+            Err(err) => {
+                eprintln!("Error reading user input: {:?}", err);
+                return false;
+            }
+        }
+        match user_input.trim().to_lowercase().as_str() {
+            "n" | "no"  => return false,
+            "y" | "yes" => return true,
+            _ => println!("...press one of 'y/yes' or 'n/no', then Enter"),
+        }
+    }
+}
+
 fn ask_for_exit_confirmation() {
     println!("{}", ColoredString::magenta("Press Enter to exit"));
-    io::stdin().read(&mut [0]).unwrap();
+    let _ = io::stdin().read(&mut [0]).unwrap();
+}
+
+/// Check if the file is larger than the filesystem of the target folder allows, see [Args::target_max_file_size]
+fn is_too_large_for_target(file: &SupportedFile, args: &Args) -> bool {
+    args.target_max_file_size.is_some_and(|max_size| file.metadata.len() > max_size)
 }
 
-fn copy_file_if_not_exists(
+/// Copy the file to the target path. Large files are copied in chunks, calling `on_copy_progress`, if given,
+/// with the size of each chunk once it's written, see [Args::copy_progress_min_size]
+fn copy_file<P: AsRef<Path>>(
     file: &SupportedFile,
+    destination_path: P,
+    args: &Args,
+    on_copy_progress: Option<&dyn Fn(u64)>,
+) -> Result<u64, io::Error> {
+    let is_large_file = args.copy_progress_min_size > 0 && file.metadata.len() >= args.copy_progress_min_size;
+    match on_copy_progress {
+        Some(on_copy_progress) if is_large_file =>
+            copy_file_with_progress(&file.file_path, destination_path.as_ref(), on_copy_progress),
+        _ => fs::copy(&file.file_path, destination_path),
+    }
+}
+
+/// Copy or move the file, unless it's skipped or its target file exists. Files which are in use by
+/// another program are added to the retries, if given, instead of being skipped right away.
+/// The progress of copying large files is reported to `on_copy_progress`, if given, see [copy_file]
+fn copy_file_if_not_exists<'a>(
+    file: &'a SupportedFile,
     destination_path: &mut PathBuf,
     args: &Args,
-    stats: &mut FileStats,
+    stats: &FileStats,
+    records: &WriteRecords,
+    in_use_retries: Option<&Mutex<Vec<(&'a SupportedFile, PathBuf)>>>,
+    on_copy_progress: Option<&dyn Fn(u64)>,
 ) -> String {
-    if destination_path.exists() {
+    let operation = if args.copy_not_move { Operation::Copy } else { Operation::Move };
+    let new_record = |status| OperationRecord::new(operation, Some(&file.file_path), destination_path, status);
+
+    if let Some(skip_reason) = &file.skip_reason {
+        records.record_operation(new_record(OperationStatus::Skipped).with_reason(skip_reason));
+        stats.inc_skipped_by_type(file, skip_reason);
+        ColoredString::orange(format_write_skipped_status(skip_reason).as_str())
+    } else if destination_path.exists() && !file.overwrites_target {
         if args.debug {
             println!(
                 "> target file exists: {}",
@@ -2022,33 +5598,47 @@ fn copy_file_if_not_exists(
             );
         }
 
-        stats.inc_skipped_by_type(file);
+        records.record_operation(new_record(OperationStatus::Skipped).with_reason(SKIP_REASON_TARGET_EXISTS));
+        stats.inc_skipped_by_type(file, SKIP_REASON_TARGET_EXISTS);
 
-        ColoredString::orange("already exists")
+        ColoredString::orange(WRITE_STATUS_TARGET_EXISTS)
+    } else if is_too_large_for_target(file, args) {
+        records.record_operation(new_record(OperationStatus::Error)
+            .with_error(String::from("file too large for the target filesystem")));
+        stats.inc_error_file_create();
+        ColoredString::red(WRITE_STATUS_TOO_LARGE)
     } else {
-        let copy_result = fs::copy(&file.file_path, &destination_path);
+        let is_overwrite = destination_path.exists();
+        let copy_result = stats.metrics.measure(STAGE_COPY, || with_chaos(args.chaos_failure_rate, "copy", || {
+            copy_file(file, &destination_path, args, on_copy_progress)
+        }));
 
         match copy_result {
             // File creation was successful
             Ok(_) => {
                 // If this is a MOVE, delete the source file after a successful copy and append status
                 let (_delete_failed_opt, delete_result_str) = if !args.copy_not_move {
-                    let delete_result = fs::remove_file(&file.file_path);
+                    let delete_result = stats.metrics.measure(STAGE_DELETE_SOURCE, || with_chaos(args.chaos_failure_rate, "delete", || {
+                        fs::remove_file(&file.file_path)
+                    }));
 
                     match delete_result {
-                        Ok(_) => (Some(false), String::from(" (source file removed)")),
+                        Ok(_) => (Some(false), String::from(WRITE_STATUS_SOURCE_REMOVED)),
                         Err(e) => {
                             if args.verbose {
                                 eprintln!("File delete error: {:?}: ERROR {:?}", &file.file_path, e)
                             };
                             stats.inc_error_file_delete();
-                            (
-                                Some(true),
-                                ColoredString::red(
-                                    format!(" (error removing source: {:?})", e.to_string())
-                                        .as_str(),
-                                ),
-                            )
+                            records.record_operation(new_record(OperationStatus::Error)
+                                .with_error(format!("could not remove the source file: {}", e)));
+                            // The file was already copied, so it's not retried
+                            let delete_error_str = if is_file_in_use(&e) {
+                                stats.inc_files_in_use();
+                                String::from(WRITE_STATUS_SOURCE_IN_USE)
+                            } else {
+                                format!(" (error removing source: {:?})", e.to_string())
+                            };
+                            (Some(true), ColoredString::red(delete_error_str.as_str()))
                         }
                     }
                 // This is just a COPY operation, there's no delete result
@@ -2056,6 +5646,38 @@ fn copy_file_if_not_exists(
                     (None, String::from(""))
                 };
 
+                // A move which could not delete the source file can be undone like a copy
+                let source = file.file_path.clone();
+                let target = destination_path.clone();
+                records.record_journal(if _delete_failed_opt == Some(false) {
+                    JournalEntry::Move { source, target, overwritten: is_overwrite }
+                } else {
+                    JournalEntry::Copy { source, target, overwritten: is_overwrite }
+                });
+                records.record_import(&file.date_str, &file.file_path, destination_path);
+
+                // Store the checksum of the newly written file so the archive can be verified later
+                let checksum_result_str = if args.write_checksums {
+                    match stats.metrics.measure(STAGE_HASH, || save_checksum(destination_path)) {
+                        Ok(_) => String::from(""),
+                        Err(e) => ColoredString::red(
+                            format!(" (error saving checksum: {:?})", e.to_string()).as_str()),
+                    }
+                } else {
+                    String::from("")
+                };
+
+                // Keep the camera settings of photos next to them, so the archive can be searched without a photo manager
+                let exif_report_result_str = if args.write_exif_report {
+                    match save_exif_report_entry(file, destination_path) {
+                        Ok(_) => String::from(""),
+                        Err(e) => ColoredString::red(
+                            format!(" (error saving EXIF details: {:?})", e.to_string()).as_str()),
+                    }
+                } else {
+                    String::from("")
+                };
+
                 // Record stats for copied or moved files. Pay special attention to cases when the operation
                 // is a move, the target file was created, but the source file was not deleted
                 // If operation is a move, the delete_failed is *defined* and *true* if the deletion failed
@@ -2065,13 +5687,39 @@ fn copy_file_if_not_exists(
                     stats.inc_moved_by_type(file);
                 }
 
-                format!("{}{}", ColoredString::green("ok"), delete_result_str)
+                // Failed deletes were already recorded as errors
+                if !_delete_failed_opt.unwrap_or(false) {
+                    records.record_operation(new_record(
+                        if is_overwrite { OperationStatus::Overwritten } else { OperationStatus::Ok }));
+                }
+
+                let write_result_str = if is_overwrite {
+                    ColoredString::orange(WRITE_STATUS_OVERWRITTEN)
+                } else {
+                    ColoredString::green(WRITE_STATUS_OK)
+                };
+
+                format!("{}{}{}{}", write_result_str, delete_result_str, checksum_result_str, exif_report_result_str)
             }
 
+            // The source file is open in another program, e.g. a phone sync app which is still writing it
+            Err(err) if is_file_in_use(&err) => match in_use_retries {
+                Some(in_use_retries) => {
+                    lock_stat(in_use_retries).push((file, destination_path.clone()));
+                    ColoredString::orange(WRITE_STATUS_FILE_IN_USE_RETRY)
+                }
+                None => {
+                    records.record_operation(new_record(OperationStatus::Skipped).with_reason(SKIP_REASON_FILE_IN_USE));
+                    stats.inc_skipped_by_type(file, SKIP_REASON_FILE_IN_USE);
+                    stats.inc_files_in_use();
+                    ColoredString::orange(WRITE_STATUS_FILE_IN_USE)
+                }
+            },
+
             // Could not create target file, log error and don't even attempt to delete source
             Err(err) => {
                 eprintln!("File copy error: {:?}: ERROR {:?}", &file.file_path, err);
-                // TODO 5c: log error info
+                records.record_operation(new_record(OperationStatus::Error).with_error(err.to_string()));
                 stats.inc_error_file_create();
                 ColoredString::red("ERROR")
             }
@@ -2079,55 +5727,329 @@ fn copy_file_if_not_exists(
     }
 }
 
+/// The path of a date dir inside the target dir, from its key in the dir tree, which includes the album, year
+/// and month or batch dirs it's nested in, separated by `/`, e.g. `2019/2019.01/2019.01.28`
+#[cfg(feature = "par2")]
+fn join_target_subdir(target_dir: &Path, dir_key: &str) -> PathBuf {
+    dir_key
+        .split('/')
+        .filter(|dir_name| !dir_name.is_empty())
+        .fold(target_dir.to_path_buf(), |path, dir_name| path.join(dir_name))
+}
+
+/// Create PAR2 recovery files inside each date dir of the target tree
+#[cfg(feature = "par2")]
+fn create_recovery_data(target_dir_tree: &TargetDateDeviceTree, args: &Args) {
+    if !is_par2_available() {
+        println!("{} Recovery files were not created: the 'par2' program could not be found.",
+                 ColoredString::warn_arrow());
+        println!();
+        return;
+    }
+
+    println!("{}", ColoredString::bold_white(
+        format!("Creating recovery files ({}% redundancy)...", args.recovery_redundancy).as_str()));
+
+    for date_dir_name in target_dir_tree.dir_tree.keys() {
+        let date_dir_path = join_target_subdir(&args.target_dir, date_dir_name);
+        if !date_dir_path.is_dir() {
+            continue;
+        }
+
+        let status = match create_recovery_files(&date_dir_path, args.recovery_redundancy) {
+            Ok(_) => ColoredString::green("ok"),
+            Err(e) => ColoredString::red(format!("ERROR {}", e).as_str()),
+        };
+        println!("[{}] {} {}", date_dir_name, SEPARATOR_OP_STATUS, status);
+    }
+    println!();
+}
+
+#[cfg(not(feature = "par2"))]
+fn create_recovery_data(_target_dir_tree: &TargetDateDeviceTree, _args: &Args) {
+    println!("{} Recovery files were not created: this version of the program was built without the 'par2' feature.",
+             ColoredString::warn_arrow());
+    println!();
+}
+
+/// Set the modified time of each date and device folder of the target tree
+/// according to [Args::target_dir_timestamps]
+fn set_target_dir_timestamps(target_dir_tree: &TargetDateDeviceTree, args: &Args) {
+    let newest_file_time = |files: &mut dyn Iterator<Item=&SupportedFile>| {
+        files.filter_map(|file| file.metadata.modified().ok()).max()
+    };
+
+    for (date_dir_name, devices_files_and_paths) in &target_dir_tree.dir_tree {
+        let date_dir_path = args.target_dir.join(date_dir_name);
+        if !date_dir_path.is_dir() {
+            continue;
+        }
+
+        // Use noon, so that the folder date doesn't change when displayed in a different time zone
+        // Date dirs inside album dirs are named after the date as well, so only the last part is parsed
+        let date_part = Path::new(date_dir_name).file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        let folder_date = NaiveDate::parse_from_str(&date_part, DATE_DIR_FORMAT)
+            .ok()
+            .and_then(|date| date.and_hms_opt(12, 0, 0))
+            .map(|date_time| SystemTime::from(DateTime::<Utc>::from_utc(date_time, Utc)));
+
+        for (device_name, files) in &devices_files_and_paths.file_tree {
+            if devices_files_and_paths.has_device_subdir(device_name) {
+                let device_dir_path = date_dir_path.join(device_name.to_string());
+                let device_dir_time = match args.target_dir_timestamps {
+                    TargetDirTimestamps::FolderDate => folder_date.or_else(|| newest_file_time(&mut files.iter())),
+                    _ => newest_file_time(&mut files.iter()),
+                };
+                set_dir_modified_time(&device_dir_path, device_dir_time);
+            }
+        }
+
+        let date_dir_time = match args.target_dir_timestamps {
+            TargetDirTimestamps::FolderDate => folder_date,
+            _ => None,
+        }.or_else(|| newest_file_time(&mut devices_files_and_paths.file_tree.values().flatten()));
+        set_dir_modified_time(&date_dir_path, date_dir_time);
+    }
+}
+
+fn set_dir_modified_time(dir_path: &Path, modified_time: Option<SystemTime>) {
+    if let Some(modified_time) = modified_time {
+        if let Err(e) = filetime::set_file_mtime(dir_path, FileTime::from_system_time(modified_time)) {
+            println!("{} Could not set the modified time of folder {}: {}",
+                     ColoredString::warn_arrow(), dir_path.display(), e);
+        }
+    }
+}
+
+/// Hash a written file and append it to the checksum manifest of its parent folder
+fn save_checksum(file_path: &Path) -> Result<(), std::io::Error> {
+    let hash = hash_file(file_path)?;
+    let parent_dir = file_path.parent().unwrap_or_else(|| Path::new(""));
+    let file_name = file_path.file_name().map(|f| f.to_string_lossy().to_string()).unwrap_or_default();
+    append_manifest_entry(parent_dir, file_name.as_str(), hash.as_str())
+}
+
+/// Append the EXIF details of a written photo to the report inside its target folder.
+/// Files without any EXIF details, e.g. videos, are not listed
+fn save_exif_report_entry(file: &SupportedFile, file_path: &Path) -> Result<(), std::io::Error> {
+    if file.capture_time.is_none() && file.dimensions.is_none() && file.shooting_info.is_empty() && file.location.is_none() {
+        return Ok(());
+    }
+
+    let parent_dir = file_path.parent().unwrap_or_else(|| Path::new(""));
+    let file_name = file_path.file_name().map(|f| f.to_string_lossy().to_string()).unwrap_or_default();
+    let device = match &file.device_name {
+        DirEntryType::Directory(device) => Some(device.as_str()),
+        DirEntryType::Files => None,
+    };
+
+    append_exif_report_entry(parent_dir, &ExifReportEntry {
+        file: file_name.as_str(),
+        taken: file.capture_time.as_deref(),
+        device,
+        width: file.dimensions.map(|(width, _)| width),
+        height: file.dimensions.map(|(_, height)| height),
+        shooting_info: &file.shooting_info,
+        latitude: file.location.map(|location| location.latitude),
+        longitude: file.location.map(|location| location.longitude),
+    })
+}
+
+/// Create all date and device folders required by the target tree before any file is written,
+/// so that folder creation problems are reported up front rather than between file operations.
+/// The status of each folder is printed in the same dir tree structure as for dry runs:
+/// ```
+/// [2019.01.28] (2 devices, 5 files, 3.34 MB) ............................ [target folder exists]
+///  ├── [Canon 100D] ..................................................... [new folder created]
+///  └── [Samsung SM-A415F] ............................................... [new folder created]
+/// ```
+fn create_target_dirs(
+    new_dir_tree: &TargetDateDeviceTree,
+    args: &Args,
+    stats: &FileStats,
+    layout: &WriteLayout,
+    journal: &mut Journal,
+    operation_log: &mut OperationLog,
+) {
+    let mut failed_dirs = 0;
+
+    for (date_dir_name, devices_files_and_paths) in &new_dir_tree.dir_tree {
+        let date_destination_path = args.target_dir.join(date_dir_name);
+
+        let file_size_for_date = devices_files_and_paths
+            .file_tree
+            .values()
+            .map(|files_and_paths| get_files_size(files_and_paths))
+            .sum();
+        let date_dir_name_with_device_status = format_date_dir_summary(
+            date_dir_name,
+            devices_files_and_paths.file_tree.len(),
+            devices_files_and_paths.file_count(),
+            file_size_for_date);
+
+        let date_dir_status = create_subdir_if_required(&date_destination_path, &DirType::Date, stats, journal, operation_log)
+            .unwrap_or_else(|error_status| {
+                failed_dirs += 1;
+                error_status
+            });
+        println!("{} {}",
+                 ColoredString::bold_white(layout.format_date_dir(date_dir_name_with_device_status).as_str()),
+                 date_dir_status);
+
+        let device_dirs: Vec<&DirEntryType> = devices_files_and_paths
+            .file_tree
+            .keys()
+            .filter(|device_name| devices_files_and_paths.has_device_subdir(device_name))
+            .collect();
+        let device_dir_count = device_dirs.len();
+
+        for (device_dir_ix, device_name) in device_dirs.into_iter().enumerate() {
+            let device_path = date_destination_path.join(device_name.to_string());
+            let device_dir_status = create_subdir_if_required(&device_path, &DirType::Device, stats, journal, operation_log)
+                .unwrap_or_else(|error_status| {
+                    failed_dirs += 1;
+                    error_status
+                });
+            println!("{} {}",
+                     layout.format_device_dir(device_name.to_string(), device_dir_ix == device_dir_count - 1),
+                     device_dir_status);
+        }
+    }
+
+    if failed_dirs > 0 {
+        println!("{}", ColoredString::red(
+            format!("{} {} could not be created, the files inside will not be written",
+                    failed_dirs,
+                    if failed_dirs == 1 { "folder" } else { "folders" }).as_str()));
+    }
+
+    // leave some empty space before the files
+    println!();
+}
+
+/// Create the subdir if it doesn't exist yet and return its status, ready for printing.
+/// Returns an error status if the subdir could not be created
 fn create_subdir_if_required(
     target_subdir: &Path,
     dir_type: &DirType,
-    args: &Args,
-    stats: &mut FileStats
-) {
+    stats: &FileStats,
+    journal: &mut Journal,
+    operation_log: &mut OperationLog,
+) -> Result<String, String> {
 
     stats.inc_dir_total_by_type(dir_type);
 
     if target_subdir.exists() {
         // Don't need any stats here
-
-        match dir_type {
-            DirType::Device => {
-                println!();
-                println!("{}",
-                         ColoredString::orange(
-                             format!("[Folder {} already exists]",
-                                     target_subdir.strip_prefix(&args.target_dir).unwrap().display()).as_str()));
-            },
-            // Don't print anything for date devices, it would be too many
-            _ => {}
-        }
+        Ok(String::from("[target folder exists]"))
     } else {
+        // Record all the parent folders which will be created as well, outermost first
+        let mut new_dirs: Vec<PathBuf> = target_subdir
+            .ancestors()
+            .take_while(|dir| !dir.exists())
+            .map(Path::to_path_buf)
+            .collect();
+        new_dirs.reverse();
+
         match fs::create_dir_all(target_subdir) {
             Ok(_) => {
+                operation_log.record(OperationRecord::new(
+                    Operation::CreateDir, None, target_subdir, OperationStatus::Ok));
+                new_dirs.into_iter().for_each(|path| journal.record(JournalEntry::CreateDir { path }));
                 stats.inc_dir_created_by_type(dir_type);
-                println!();
-                println!("{}",
-                         ColoredString::bold_white(
-                             format!("[Created folder {}]",
-                                 if args.verbose {
-                                     // This was just created successfully, so unwrap should be safe
-                                     let canonical_path = target_subdir.canonicalize().unwrap();
-                                     canonical_path.display().to_string()
-                                 } else {
-                                     target_subdir.strip_prefix(&args.target_dir).unwrap().display().to_string()
-                                 }
-                            ).as_str()));
+                Ok(ColoredString::bold_white("[new folder created]"))
             },
             Err(e) => {
+                operation_log.record(OperationRecord::new(
+                    Operation::CreateDir, None, target_subdir, OperationStatus::Error).with_error(e.to_string()));
                 stats.inc_error_dir_create_by_type(dir_type);
-                // TODO 2f: handle dir creation fail?
-                println!("Failed to create folder {}: {:?}",
-                         target_subdir.strip_prefix(&args.target_dir).unwrap().display(),
-                         e.kind())
+                Err(ColoredString::red(format!("[failed to create folder: {:?}]", e.kind()).as_str()))
             }
         }
+    }
+}
+
+/// If [Args::album_from_source_folder] is enabled, return the name of the subfolder of the
+/// configured source folder which contains the file, e.g. `2023 Iceland` for the file
+/// `E:\Photos\2023 Iceland\Day 1\IMG_0001.JPG` with the source folder `E:\Photos`.
+/// Files placed directly inside the source folder don't have an album
+fn get_album_name(file_path: &Path, args: &Args) -> Option<String> {
+    if !args.album_from_source_folder {
+        return None;
+    }
+
+    // The first dir of each source is the configured source folder, the others are its subfolders
+    // If the source folders are nested, use the closest one to the file
+    args.source_dirs
+        .iter()
+        .filter_map(|source_dirs| source_dirs.first())
+        .filter_map(|source_dir| file_path.strip_prefix(source_dir).ok())
+        .min_by_key(|relative_path| relative_path.components().count())
+        .filter(|relative_path| relative_path.components().count() > 1)
+        .and_then(|relative_path| relative_path.components().next())
+        .map(|album_dir| album_dir.as_os_str().to_string_lossy().to_string())
+}
+
+/// Return the album dir containing a target date dir, if any, see [get_album_name]
+fn get_album_dir(date_dir_name: &str) -> Option<&Path> {
+    Path::new(date_dir_name)
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+}
+
+/// Build the target date dir name from a date in the default format (see [DATE_DIR_FORMAT]),
+/// according to [Args::date_dir_template], or to the override for videos or audios if configured,
+/// inside the year and month dirs of the date if configured, see [Args::date_dir_layout].
+/// Dates inside a named event are placed in the event dir instead, see [Args::named_events].
+/// If the template groups images by size and the EXIF data doesn't include it, the size is read from the file header.
+/// If the template groups photos by place, their GPS location is looked up in the bundled dataset of cities
+fn build_date_dir_name(
+    date_str: String,
+    file_type: &FileType,
+    file_path: &Path,
+    device_name: &DirEntryType,
+    exif_data: &ExifDateDevice,
+    args: &Args
+) -> String {
+    let template = get_date_dir_template(file_type, args);
+
+    let dimensions = if uses_image_size_placeholders(template) {
+        exif_data.dimensions.or_else(|| read_image_dimensions(file_path))
+    } else {
+        None
+    };
+
+    let place = match exif_data.location {
+        Some(location) if uses_place_placeholders(template) => reverse_geocode(location),
+        _ => Place::default(),
+    };
+
+    match NaiveDate::parse_from_str(&date_str, DATE_DIR_FORMAT) {
+        Ok(date) => match args.find_named_event(date) {
+            // Named events replace the template, but are still placed in the year and month dirs of their first date
+            Some(event) => nest_date_dir(event.dir_name(), event.first_date, &args.date_dir_layout),
+            None => {
+                let device_name = match device_name {
+                    DirEntryType::Directory(device_name) => Some(device_name.as_str()),
+                    DirEntryType::Files => None,
+                };
+                let lens_name = exif_data.get_lens_name();
+                let date_dir = render_date_dir(template, date, dimensions, device_name, lens_name.as_deref(), place, args);
+                nest_date_dir(date_dir, date, &args.date_dir_layout)
+            }
+        },
+        Err(_) => date_str,
+    }
+}
+
+/// The template of the target date dir for this type of file, see [Args::date_dir_template]
+fn get_date_dir_template<'a>(file_type: &FileType, args: &'a Args) -> &'a str {
+    let type_template = match file_type {
+        FileType::Video => args.video_date_dir_template.as_ref(),
+        FileType::Audio => args.audio_date_dir_template.as_ref(),
+        _ => None,
     };
+    type_template.unwrap_or(&args.date_dir_template)
 }
 
 /// Read metadata and return the file's modified time in YYYY-MM-DD format
@@ -2141,10 +6063,221 @@ fn get_system_modified_date(file_metadata: &Metadata) -> Option<String> {
     })
 }
 
+/// Find a date in the file name, e.g. `IMG_20190128_123456.jpg`, for files without an EXIF date,
+/// based on the [Args::filename_date_patterns], and return it in YYYY-MM-DD format.
+/// Dates written with the year last are read in the order learned for the file's source dir, if any
+fn get_filename_date(file: &DirEntry, args: &Args) -> Option<String> {
+    let file_path = file.path();
+    let order = file_path
+        .parent()
+        .and_then(|parent| args.learned_filename_date_orders.get(parent))
+        .copied()
+        .unwrap_or(args.filename_date_order);
+    find_filename_date(&file.file_name().to_string_lossy(), &args.filename_date_patterns, order)
+        .map(|date| date.format(DATE_DIR_FORMAT).to_string())
+}
+
+/// If the order of dates in file names is `auto`, learn it for each source dir from the images
+/// whose names contain an ambiguous date, e.g. `IMG 05-06-2021.jpg`, by comparing it with their EXIF date,
+/// so that the other files in the same dir, e.g. scans without EXIF data, are read in the same order
+fn learn_filename_date_orders(source_dirs: &BTreeMap<String, Vec<DirEntry>>, args: &mut Args) {
+    if args.filename_date_order != FilenameDateOrder::Auto {
+        return;
+    }
+
+    let mut votes: HashMap<PathBuf, (usize, usize)> = HashMap::new();
+
+    for dir_entry in source_dirs.values().flatten() {
+        let file_path = dir_entry.path();
+        let parent = match file_path.parent() {
+            Some(parent) => parent.to_path_buf(),
+            None => continue,
+        };
+        let (day_first, month_first) = match votes.get(&parent) {
+            Some((day_first, month_first)) if day_first + month_first >= MAX_FILENAME_DATE_ORDER_SAMPLES => continue,
+            _ => match find_ambiguous_filename_date(&dir_entry.file_name().to_string_lossy(), &args.filename_date_patterns) {
+                Some(dates) => dates,
+                None => continue,
+            },
+        };
+        if !matches!(get_file_type(&get_extension(dir_entry), args), FileType::Image) {
+            continue;
+        }
+
+        let exif_date = read_image_exif_data(dir_entry, args)
+            .date
+            .and_then(|date_str| NaiveDate::parse_from_str(&date_str, DATE_DIR_FORMAT).ok());
+        let dir_votes = votes.entry(parent).or_insert((0, 0));
+        if exif_date == Some(day_first) {
+            dir_votes.0 += 1;
+        } else if exif_date == Some(month_first) {
+            dir_votes.1 += 1;
+        }
+    }
+
+    for (dir, (day_first, month_first)) in votes {
+        let order = match day_first.cmp(&month_first) {
+            std::cmp::Ordering::Greater => FilenameDateOrder::DayFirst,
+            std::cmp::Ordering::Less => FilenameDateOrder::MonthFirst,
+            std::cmp::Ordering::Equal => continue,
+        };
+        if args.verbose {
+            println!("> dates in file names are read as {} in {}, based on {} files with EXIF dates",
+                     order.name(), dir.display(), day_first + month_first);
+        }
+        args.learned_filename_date_orders.insert(dir, order);
+    }
+}
+
+/// Add the first number which doesn't match any of the used names to the file name,
+/// e.g. `IMG_1 (2).jpg`, then add the new name to the used names
+fn find_unused_file_name(file_name: &OsStr, used_names: &mut HashSet<String>) -> OsString {
+    let mut counter = 2;
+    loop {
+        let new_name = get_numbered_file_name(file_name, counter);
+        if used_names.insert(new_name.to_string_lossy().to_lowercase()) {
+            return new_name;
+        }
+        counter += 1;
+    }
+}
+
+/// Add a number to the name of a file, before its extension, e.g. `IMG_1 (2).jpg` for `IMG_1.jpg`
+fn get_numbered_file_name(file_name: &OsStr, counter: u32) -> OsString {
+    let file_path = Path::new(file_name);
+    let stem = file_path.file_stem().unwrap_or(file_name);
+
+    let mut new_name = stem.to_os_string();
+    new_name.push(format!(" ({})", counter));
+    if let Some(extension) = file_path.extension() {
+        new_name.push(".");
+        new_name.push(extension);
+    }
+    new_name
+}
+
+/// Extensions which aren't valid UTF-8 are kept with their invalid characters replaced,
+/// so that such files are reported as unknown files instead of files without an extension
 fn get_extension(file: &DirEntry) -> Option<String> {
     file.path()
         .extension()
-        .and_then(|os| os.to_str().map(String::from))
+        .map(|os| os.to_string_lossy().to_string())
+}
+
+/// The source dir and the lowercase file name without the extension, see [PairKey]
+fn get_pair_key(file: &SupportedFile) -> Option<PairKey> {
+    let source_dir = file.file_path.parent()?.to_path_buf();
+    let stem = file.file_path.file_stem()?.to_string_lossy().to_lowercase();
+    Some((source_dir, stem))
+}
+
+fn get_pair_dir(file: &SupportedFile) -> PairDir {
+    (file.date_str.clone(), file.date, file.device_name.clone())
+}
+
+fn is_jpeg_file(file: &SupportedFile) -> bool {
+    matches!(file.get_extension_lowercase().as_str(), "jpg" | "jpeg")
+}
+
+fn is_raw_file(file: &SupportedFile) -> bool {
+    RAW_EXTENSIONS.contains(&file.get_extension_lowercase().as_str())
+}
+
+/// Whether this is the sidecar of a RAW photo, named after the RAW file's full name, e.g. `DSC_0001.NEF.xmp`
+fn is_raw_sidecar_file(file: &SupportedFile) -> bool {
+    matches!(file.file_type, FileType::Sidecar) && Path::new(&file.file_name)
+        .file_stem()
+        .map(Path::new)
+        .and_then(Path::extension)
+        .is_some_and(|extension| RAW_EXTENSIONS.contains(&extension.to_string_lossy().to_lowercase().as_str()))
+}
+
+/// Keep only the source files modified or created since the start of the last successful run
+/// of their source dir into the target dir, see [Args::since_last_run].
+/// All files of the source dirs without an earlier run are kept
+fn filter_files_since_last_run(source_files: BTreeMap<String, Vec<DirEntry>>, args: &Args) -> BTreeMap<String, Vec<DirEntry>> {
+    let last_run_times = read_last_run_times(&args.target_dir);
+
+    source_files
+        .into_iter()
+        .map(|(source, files)| {
+            let last_run_time = match last_run_times.get(&source) {
+                Some(last_run_time) => *last_run_time,
+                None => {
+                    println!("No earlier run of {} was found, reading all of its files", source);
+                    return (source, files);
+                }
+            };
+
+            let total_count = files.len();
+            let new_files: Vec<DirEntry> = files
+                .into_iter()
+                .filter(|file| file.metadata().is_ok_and(|metadata| is_changed_since(&metadata, last_run_time)))
+                .collect();
+            println!("Reading {} of {} files of {}, which changed since the last run on {}",
+                     new_files.len(), total_count, source, last_run_time.format("%Y-%m-%d %H:%M:%S"));
+            (source, new_files)
+        })
+        .collect()
+}
+
+/// Read the Google Takeout JSON files in the source dirs into [Args::takeout_index], if enabled,
+/// and leave them out of the source files, so that they're neither sorted nor counted as unknown files.
+/// JSON files which don't belong to any of the photos or videos are kept as before
+fn read_takeout_metadata(source_dirs: BTreeMap<String, Vec<DirEntry>>, args: &mut Args) -> BTreeMap<String, Vec<DirEntry>> {
+    if !args.read_takeout_json {
+        return source_dirs;
+    }
+
+    let (json_files, media_files): (Vec<PathBuf>, HashSet<PathBuf>) = source_dirs
+        .values()
+        .flatten()
+        .map(|dir_entry| dir_entry.path())
+        .partition_map(|file_path| if has_json_extension(&file_path) {
+            itertools::Either::Left(file_path)
+        } else {
+            itertools::Either::Right(file_path)
+        });
+    if json_files.is_empty() {
+        return source_dirs;
+    }
+
+    let (takeout_index, matched_json_files) = TakeoutIndex::read(&json_files, &media_files);
+    if args.verbose {
+        println!("> read the Google Takeout metadata of {} files", matched_json_files.len());
+    }
+    args.takeout_index = takeout_index;
+
+    let matched_json_files: HashSet<PathBuf> = matched_json_files.into_iter().collect();
+    source_dirs
+        .into_iter()
+        .map(|(dir_name, dir_entries)| {
+            let dir_entries = dir_entries
+                .into_iter()
+                .filter(|dir_entry| !matched_json_files.contains(&dir_entry.path()))
+                .collect();
+            (dir_name, dir_entries)
+        })
+        .collect()
+}
+
+/// Find where a photo without GPS data was taken from the GPX tracks in config, see [Args::gpx_track]
+fn find_gpx_location(exif_data: &ExifDateDevice, args: &Args) -> Option<GpsLocation> {
+    args.gpx_track.as_ref()?.find_capture_location(
+        exif_data.capture_time.as_ref()?,
+        args.gpx_time_offset,
+        args.gpx_max_gap_minutes)
+}
+
+/// Classify animated GIFs and short videos without sound (e.g. Boomerangs) as animations,
+/// so that they're counted separately and can be kept apart from photos and videos, see [Args::animations_dir_name]
+fn classify_animation(file_type: FileType, file_path: &Path, args: &Args) -> FileType {
+    match file_type {
+        FileType::Image if is_animated_gif(file_path) => FileType::Animation,
+        FileType::Video if args.animation_max_seconds > 0 && is_short_silent_video(file_path, args.animation_max_seconds) =>
+            FileType::Animation,
+        _ => file_type,
+    }
 }
 
 /// Determine the type of file based on the file extension
@@ -2160,20 +6293,30 @@ fn get_file_type(extension_opt: &Option<String>, args: &Args) -> FileType {
             match extension.to_lowercase().as_str() {
                 // "Supported" image extensions
                 "jpg" | "jpeg" | "png" | "tiff" | "heic"| "heif"| "webp" |
-                    // Partially supported image extensions
-                    "crw" | "nef" | "nrw" =>
+                    // RAW image extensions
+                    "nef" | "nrw" | "cr2" | "arw" | "dng" | "orf" | "rw2" | "raf" |
+                    // Partially supported image extensions, animated GIFs are classified later, see [classify_animation]
+                    "crw" | "gif" =>
                     FileType::Image,
 
                 // "Supported" video extensions
                 "avif" |
                     // Partially supported video extensions
-                    "mp4" | "mov" | "3gp" | "avi" =>
+                    "mp4" | "mov" | "3gp" | "avi" | "mts" | "m2ts" | "mkv" | "webm" =>
                     FileType::Video,
 
                 // Partially supported audio extensions
                 "amr" | "ogg" | "m4a" =>
                     FileType::Audio,
 
+                // Sidecars are placed next to their photo or video, see [TargetDateDeviceTree::keep_sidecars_together]
+                ext if args.sidecar_extensions.iter().any(|sidecar_ext| sidecar_ext == ext) =>
+                    FileType::Sidecar,
+
+                // Documents are only sorted if enabled, see [Args::documents_dir_name]
+                ext if args.documents_dir_name.is_some() && args.document_extensions.iter().any(|doc_ext| doc_ext == ext) =>
+                    FileType::Document,
+
                 // User-configured extensions
                 _ => {
                     if !args.custom_extensions.is_empty() {
@@ -2195,3 +6338,118 @@ fn get_file_type(extension_opt: &Option<String>, args: &Args) -> FileType {
         None => FileType::Unknown("".to_owned()),
     }
 }
+
+/// The result of checking the start of a file's contents, see [check_file_contents]
+struct ContentCheck {
+    file_type: FileType,
+    // set if the extension doesn't match the file's contents
+    warning: Option<String>,
+    // set if the file has a known extension but its contents don't start as expected
+    has_invalid_header: bool,
+}
+
+impl ContentCheck {
+    /// The result for files whose contents are not checked
+    fn unchanged(file_type: FileType) -> ContentCheck {
+        ContentCheck {
+            file_type,
+            warning: None,
+            has_invalid_header: false,
+        }
+    }
+}
+
+/// Check if a file of a known type is empty or can't be opened, e.g. because of its permissions.
+/// Files with unknown extensions are skipped anyway, so they're not checked
+fn check_unreadable_file(file_path: &Path, metadata: &Metadata, file_type: &FileType) -> Option<UnreadableReason> {
+    if matches!(file_type, FileType::Unknown(_)) {
+        return None;
+    }
+    if metadata.len() == 0 {
+        return Some(UnreadableReason::Empty);
+    }
+    fs::File::open(file_path)
+        .err()
+        .map(|e| UnreadableReason::OpenFailed(e.to_string()))
+}
+
+/// Check if the file is a cloud placeholder whose contents are not stored locally, and download it
+/// if configured, see [Args::cloud_placeholders]. Returns the reason for skipping it, if it's not available
+fn check_cloud_placeholder(file_path: &Path, metadata: &Metadata, file_type: &FileType, args: &Args) -> Option<String> {
+    if !is_cloud_placeholder(metadata) {
+        return None;
+    }
+
+    match args.cloud_placeholders {
+        CloudPlaceholdersAction::Skip =>
+            Some(SKIP_REASON_CLOUD_PLACEHOLDER.to_string()),
+        // Files with unknown extensions are skipped anyway, so there's no need to download them
+        CloudPlaceholdersAction::Download if matches!(file_type, FileType::Unknown(_)) =>
+            None,
+        CloudPlaceholdersAction::Download => match download_cloud_file(file_path) {
+            Ok(_) => None,
+            Err(e) => {
+                if args.verbose {
+                    eprintln!("Cloud file download error: {:?}: ERROR {:?}", file_path, e)
+                };
+                Some(SKIP_REASON_CLOUD_DOWNLOAD_FAILED.to_string())
+            }
+        },
+    }
+}
+
+/// Read the start of the file to detect its actual type (see [detect_file_type]) and use it
+/// instead of the type determined from the extension, if they don't match.
+/// Files without an extension are sorted according to their contents, if these are recognized,
+/// while files with unknown extensions are left as they are.
+/// If the contents are not recognized but the extension is one we know the signature for,
+/// the file header is flagged as invalid so the file can be checked for corruption
+fn check_file_contents(
+    file_path: &Path,
+    extension_opt: &Option<String>,
+    file_type: FileType,
+    args: &Args
+) -> ContentCheck {
+    let extension = extension_opt.as_deref().unwrap_or("");
+    let check_corrupt = args.corrupt_files != CorruptFilesAction::Ignore;
+
+    if !args.detect_type_from_content && !check_corrupt {
+        return ContentCheck::unchanged(file_type);
+    }
+
+    if let FileType::Unknown(_) = file_type {
+        if !extension.is_empty() {
+            return ContentCheck::unchanged(file_type);
+        }
+    }
+
+    match detect_file_type(file_path) {
+        Some(detected_type) if args.detect_type_from_content => {
+            let detected_file_type = match detected_type.media_type {
+                IMAGE => FileType::Image,
+                VIDEO => FileType::Video,
+                _ => FileType::Audio,
+            };
+
+            let warning = if extension.is_empty() || detected_type.matches_extension(extension) {
+                None
+            } else {
+                Some(format!("{} (extension '{}', but contents look like '{}')",
+                             file_path.display(), extension, detected_type.format))
+            };
+
+            ContentCheck {
+                file_type: detected_file_type,
+                warning,
+                has_invalid_header: false,
+            }
+        }
+        Some(_) => ContentCheck::unchanged(file_type),
+        // Content not recognized, trust the extension
+        None => ContentCheck {
+            file_type,
+            warning: None,
+            has_invalid_header: check_corrupt && has_known_signature(extension),
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::build_info::*;
+
+/// Reading the file type, EXIF data and other metadata of a source file
+pub const STAGE_READ_METADATA: &str = "read_metadata";
+/// Hashing a written file for the checksum manifest of its folder
+pub const STAGE_HASH: &str = "hash";
+/// Copying a file to the target folder
+pub const STAGE_COPY: &str = "copy";
+/// Deleting the source file after copying it, when moving files
+pub const STAGE_DELETE_SOURCE: &str = "delete_source";
+
+/// Fine-grained timings collected during a run, in addition to the totals shown in the stats,
+/// so that slow runs can be diagnosed from the report saved with `--metrics <path>`:
+/// the time spent in each stage of processing a file, how busy each thread was,
+/// and how many files finished each stage in each second of the run
+#[derive(Debug)]
+pub struct RunMetrics {
+    started: Instant,
+    stages: Mutex<BTreeMap<&'static str, StageTime>>,
+    /// The time of each thread in each stage, since e.g. the main thread may read and write files
+    threads: Mutex<BTreeMap<(String, &'static str), ThreadTime>>,
+    /// The number of files which finished a stage, for each second since the start of the run
+    throughput: Mutex<BTreeMap<(&'static str, u64), u64>>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct StageTime {
+    total: Duration,
+    count: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ThreadTime {
+    busy: Duration,
+    files: u64,
+    first_start: Instant,
+    last_end: Instant,
+}
+
+impl RunMetrics {
+    pub fn new() -> RunMetrics {
+        RunMetrics {
+            started: Instant::now(),
+            stages: Mutex::new(BTreeMap::new()),
+            threads: Mutex::new(BTreeMap::new()),
+            throughput: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Run the operation for a single file and record how long it took as part of this stage
+    pub fn measure<T>(&self, stage: &'static str, operation: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = operation();
+        let end = Instant::now();
+        let elapsed = end - start;
+
+        let mut stages = lock_metric(&self.stages);
+        let stage_time = stages.entry(stage).or_default();
+        stage_time.total += elapsed;
+        stage_time.count += 1;
+        drop(stages);
+
+        let current_thread = thread::current();
+        let thread_name = current_thread
+            .name()
+            .map(String::from)
+            .unwrap_or_else(|| format!("{:?}", current_thread.id()));
+        lock_metric(&self.threads)
+            .entry((thread_name, stage))
+            .and_modify(|thread_time| {
+                thread_time.busy += elapsed;
+                thread_time.files += 1;
+                thread_time.last_end = end;
+            })
+            .or_insert(ThreadTime { busy: elapsed, files: 1, first_start: start, last_end: end });
+
+        let second = (end - self.started).as_secs();
+        *lock_metric(&self.throughput).entry((stage, second)).or_insert(0) += 1;
+
+        result
+    }
+
+    /// Collect the metrics and the totals shown in the stats, which are passed in by name
+    pub fn report(&self, timings: BTreeMap<&'static str, Duration>, counts: BTreeMap<&'static str, i64>) -> MetricsReport {
+        let stages = lock_metric(&self.stages)
+            .iter()
+            .map(|(stage, stage_time)| StageReport {
+                stage,
+                files: stage_time.count,
+                seconds: stage_time.total.as_secs_f64(),
+                average_ms: stage_time.total.as_secs_f64() * 1000.0 / stage_time.count.max(1) as f64,
+            })
+            .collect();
+
+        // Each thread is only compared to the time between its first and its last operation in a stage,
+        // since e.g. the files are only written after the dry run is confirmed
+        let threads = lock_metric(&self.threads)
+            .iter()
+            .map(|((thread, stage), thread_time)| {
+                let active = (thread_time.last_end - thread_time.first_start).as_secs_f64();
+                ThreadReport {
+                    thread: thread.clone(),
+                    stage,
+                    files: thread_time.files,
+                    busy_seconds: thread_time.busy.as_secs_f64(),
+                    active_seconds: active,
+                    utilization: if active > 0.0 { (thread_time.busy.as_secs_f64() / active).min(1.0) } else { 1.0 },
+                }
+            })
+            .collect();
+
+        let files_per_second = lock_metric(&self.throughput)
+            .iter()
+            .map(|((stage, second), files)| ThroughputSample { stage, second: *second, files: *files })
+            .collect();
+
+        MetricsReport {
+            version: VERSION,
+            timings: timings
+                .into_iter()
+                .map(|(name, duration)| (name, duration.as_secs_f64()))
+                .collect(),
+            counts,
+            stages,
+            threads,
+            files_per_second,
+        }
+    }
+}
+
+impl Default for RunMetrics {
+    fn default() -> Self {
+        RunMetrics::new()
+    }
+}
+
+/// The metrics of a run, saved as JSON
+#[derive(Debug, Serialize)]
+pub struct MetricsReport {
+    pub version: &'static str,
+    /// The durations shown in the stats at the end of each run, in seconds
+    pub timings: BTreeMap<&'static str, f64>,
+    /// The file and folder counts shown in the stats at the end of each run
+    pub counts: BTreeMap<&'static str, i64>,
+    pub stages: Vec<StageReport>,
+    pub threads: Vec<ThreadReport>,
+    pub files_per_second: Vec<ThroughputSample>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StageReport {
+    pub stage: &'static str,
+    pub files: u64,
+    pub seconds: f64,
+    pub average_ms: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ThreadReport {
+    pub thread: String,
+    pub stage: &'static str,
+    pub files: u64,
+    /// The time spent processing files
+    pub busy_seconds: f64,
+    /// The time between the start of the first file and the end of the last file processed by this thread in this stage
+    pub active_seconds: f64,
+    /// The share of the active time spent in this stage, while the rest is spent in other stages or waiting
+    pub utilization: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ThroughputSample {
+    pub stage: &'static str,
+    /// Seconds since the start of the run
+    pub second: u64,
+    pub files: u64,
+}
+
+impl MetricsReport {
+    pub fn save(&self, report_path: &Path) -> Result<(), io::Error> {
+        fs::write(report_path, serde_json::to_string_pretty(self)?)
+    }
+}
+
+/// The metrics are only used for reporting, so a lock poisoned by a panicking thread can still be read
+fn lock_metric<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
@@ -0,0 +1,103 @@
+use std::borrow::Cow;
+use std::io;
+use std::path::Path;
+
+use chrono::Local;
+use serde::Serialize;
+
+use crate::journal::JOURNAL_DIR_NAME;
+use crate::jsonl::JsonLinesFile;
+
+/// The log of all operations done in a target folder, kept next to the journals of each run.
+/// Unlike the journals, this is never changed by `undo`, so it keeps the full history of the archive
+pub const OPERATION_LOG_FILE_NAME: &str = "operations.log";
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    CreateDir,
+    Copy,
+    Move,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationStatus {
+    Ok,
+    Overwritten,
+    Skipped,
+    Error,
+}
+
+/// A single operation, written as one JSON line, e.g.:
+/// ```text
+/// {"timestamp":"2022-05-01T18:30:12.123+03:00","operation":"move","source":"D:\\Pics\\IMG_0001.JPG","target":"E:\\Pictures\\2019.01.28\\IMG_0001.JPG","status":"ok"}
+/// {"timestamp":"2022-05-01T18:30:12.456+03:00","operation":"copy","source":"D:\\Pics\\IMG_0002.JPG","target":"E:\\Pictures\\2019.01.28\\IMG_0002.JPG","status":"skipped","reason":"target file exists"}
+/// ```
+#[derive(Debug, Serialize)]
+pub struct OperationRecord<'a> {
+    timestamp: String,
+    operation: Operation,
+    /// Paths which aren't valid UTF-8 can't be saved as JSON, so they're logged with their invalid characters replaced
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<Cow<'a, str>>,
+    target: Cow<'a, str>,
+    status: OperationStatus,
+    /// Why the operation was skipped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl<'a> OperationRecord<'a> {
+    pub fn new(operation: Operation, source: Option<&'a Path>, target: &'a Path, status: OperationStatus) -> Self {
+        OperationRecord {
+            timestamp: Local::now().to_rfc3339(),
+            operation,
+            source: source.map(Path::to_string_lossy),
+            target: target.to_string_lossy(),
+            status,
+            reason: None,
+            error: None,
+        }
+    }
+
+    pub fn with_reason(mut self, reason: &'a str) -> Self {
+        self.reason = Some(reason);
+        self
+    }
+
+    pub fn with_error(mut self, error: String) -> Self {
+        self.error = Some(error);
+        self
+    }
+}
+
+/// The operation log of a target folder, to which each run appends its operations
+#[derive(Debug)]
+pub struct OperationLog {
+    lines: JsonLinesFile,
+}
+
+impl OperationLog {
+    /// Open the operation log of the target folder, creating it if it doesn't exist yet
+    pub fn open(target_dir: &Path) -> Result<OperationLog, io::Error> {
+        let log_path = target_dir.join(JOURNAL_DIR_NAME).join(OPERATION_LOG_FILE_NAME);
+        Ok(OperationLog { lines: JsonLinesFile::open_append(&log_path)? })
+    }
+
+    /// A log which doesn't record anything, used for dry runs or if the log is disabled
+    pub fn disabled() -> OperationLog {
+        OperationLog { lines: JsonLinesFile::disabled() }
+    }
+
+    pub fn record(&mut self, record: OperationRecord) {
+        self.lines.append(&record)
+    }
+
+    /// The first error encountered while writing the log, if any
+    pub fn error(&self) -> Option<&io::Error> {
+        self.lines.error.as_ref()
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::progress::ProgressBar;
+
+/// Collects the output rows of each target folder while its files are written, so that folders written
+/// at the same time by different threads are still printed one after another, in the order of the dir tree.
+/// Folders are numbered in this order: the rows of the next folder to print are printed right away,
+/// while the rows of the later folders are kept until all folders before them are finished
+#[derive(Debug, Default)]
+pub struct FolderOutput {
+    state: Mutex<FolderOutputState>,
+    /// Shown below the printed rows, if set
+    progress: Option<ProgressBar>,
+}
+
+#[derive(Debug, Default)]
+struct FolderOutputState {
+    /// The index of the folder whose rows are printed right away
+    next_folder: usize,
+    /// The rows of the later folders, and whether all files of each of them were written
+    pending: BTreeMap<usize, (Vec<String>, bool)>,
+}
+
+impl FolderOutput {
+    pub fn new() -> FolderOutput {
+        FolderOutput::default()
+    }
+
+    /// Show the progress bar below the printed rows, see [Self::advance]
+    pub fn with_progress(progress: ProgressBar) -> FolderOutput {
+        FolderOutput {
+            progress: Some(progress),
+            ..FolderOutput::default()
+        }
+    }
+
+    /// Add the size of a written file to the progress bar, if any
+    pub fn advance(&self, amount: u64) {
+        if let Some(progress) = &self.progress {
+            progress.advance(amount);
+        }
+    }
+
+    /// Print the row if it belongs to the next folder, otherwise keep it until that folder is printed
+    pub fn push(&self, folder_index: usize, row: String) {
+        let mut state = self.lock_state();
+        if folder_index == state.next_folder {
+            self.print_row(&row);
+        } else {
+            state.pending.entry(folder_index).or_default().0.push(row);
+        }
+    }
+
+    /// Mark all rows of the folder as added, then print the rows of the following folders
+    /// up to the first one which isn't finished yet
+    pub fn finish(&self, folder_index: usize) {
+        let mut state = self.lock_state();
+        let state = &mut *state;
+        state.pending.entry(folder_index).or_default().1 = true;
+
+        while let Some((rows, is_finished)) = state.pending.remove(&state.next_folder) {
+            rows.iter().for_each(|row| self.print_row(row));
+            // The next rows of this folder are printed right away
+            if !is_finished {
+                break;
+            }
+            state.next_folder += 1;
+        }
+    }
+
+    /// Print the rows of all folders which weren't printed yet, e.g. after a folder failed to finish,
+    /// and remove the progress bar
+    pub fn flush(&self) {
+        let mut state = self.lock_state();
+        let pending = std::mem::take(&mut state.pending);
+        pending
+            .into_values()
+            .flat_map(|(rows, _)| rows)
+            .for_each(|row| self.print_row(&row));
+
+        if let Some(progress) = &self.progress {
+            progress.finish();
+        }
+    }
+
+    fn print_row(&self, row: &str) {
+        match &self.progress {
+            Some(progress) => progress.print_line(row),
+            None => println!("{}", row),
+        }
+    }
+
+    /// The rows are only printed, so a lock poisoned by a panicking thread can still be used
+    fn lock_state(&self) -> MutexGuard<'_, FolderOutputState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
@@ -0,0 +1,79 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// The file saved next to the config file by a dry run, with the plan which was reviewed
+pub const PLAN_LOCK_FILE_NAME: &str = "imgsorter.plan.json";
+
+/// What a run will do with a single source file
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PlannedFile {
+    pub source_path: PathBuf,
+    pub size: u64,
+    /// Nanoseconds since the Unix epoch, or 0 if not available
+    pub modified: u128,
+    pub target_path: PathBuf,
+    /// Set if the file will not be written
+    pub skip_reason: Option<String>,
+}
+
+/// The plan of a dry run, i.e. a hash of where each source file will be written,
+/// together with the list of source files, to describe what changed since the dry run
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlanLock {
+    pub hash: String,
+    pub source_files: BTreeSet<PathBuf>,
+}
+
+impl PlanLock {
+    pub fn new(mut planned_files: Vec<PlannedFile>) -> PlanLock {
+        planned_files.sort();
+
+        let mut hasher = Sha256::new();
+        for file in &planned_files {
+            hasher.update(file.source_path.to_string_lossy().as_bytes());
+            hasher.update(b"\0");
+            hasher.update(file.size.to_le_bytes());
+            hasher.update(file.modified.to_le_bytes());
+            hasher.update(file.target_path.to_string_lossy().as_bytes());
+            hasher.update(b"\0");
+            hasher.update(file.skip_reason.as_deref().unwrap_or("").as_bytes());
+            hasher.update(b"\0");
+        }
+
+        PlanLock {
+            hash: format!("{:x}", hasher.finalize()),
+            source_files: planned_files.into_iter().map(|file| file.source_path).collect(),
+        }
+    }
+
+    /// Read the plan saved by the last dry run, if there is one which can be read
+    pub fn read(plan_path: &Path) -> Option<PlanLock> {
+        fs::read_to_string(plan_path)
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+    }
+
+    pub fn save(&self, plan_path: &Path) -> Result<(), io::Error> {
+        fs::write(plan_path, serde_json::to_string(self)?)
+    }
+
+    /// Remove the saved plan, e.g. after it was executed
+    pub fn remove(plan_path: &Path) -> Result<(), io::Error> {
+        match fs::remove_file(plan_path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// The source files which are only in the current plan, and the ones which are only in the saved plan
+    pub fn compare_source_files<'a>(&'a self, current: &'a PlanLock) -> (Vec<&'a PathBuf>, Vec<&'a PathBuf>) {
+        let added = current.source_files.difference(&self.source_files).collect();
+        let removed = self.source_files.difference(&current.source_files).collect();
+        (added, removed)
+    }
+}
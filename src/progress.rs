@@ -0,0 +1,164 @@
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::humanize::{format_duration_seconds, format_file_size};
+
+/// The bar is redrawn at most this often, since drawing it is slower than parsing a small file
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The number of characters of the bar itself, between the brackets
+const BAR_WIDTH: usize = 30;
+
+/// Without a terminal, e.g. when the output is redirected to a file, a line is printed at each step of this many percents
+const PLAIN_PERCENT_STEP: u64 = 10;
+
+/// Clear the current line of the terminal, see [ProgressBar::print_line]
+const CLEAR_LINE: &str = "\r\x1b[2K";
+
+/// What the progress is counted in, which decides how the rate is shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressUnit {
+    /// e.g. `120 files/s`
+    Files,
+    /// e.g. `25.40 MB/s`
+    Bytes,
+}
+
+/// Shows the progress of a long operation as a bar with its percentage, rate and remaining time, e.g.
+/// ```text
+/// Reading files [##########--------------------] 33% 1200/3600 | 240 files/s | ETA 0:00:10
+/// ```
+/// The bar is redrawn on the last line of the terminal, below the lines printed with [Self::print_line].
+/// If the output is not a terminal, a plain line is printed every [PLAIN_PERCENT_STEP] percents instead.
+/// The progress can be advanced from several threads at once
+#[derive(Debug)]
+pub struct ProgressBar {
+    label: String,
+    unit: ProgressUnit,
+    total: u64,
+    done: AtomicU64,
+    started: Instant,
+    is_terminal: bool,
+    state: Mutex<ProgressState>,
+}
+
+#[derive(Debug, Default)]
+struct ProgressState {
+    last_drawn: Option<Instant>,
+    /// Whether the bar is currently shown on the last line, and must be cleared before printing another line
+    is_drawn: bool,
+    /// The last percentage printed without a terminal
+    last_plain_percent: Option<u64>,
+}
+
+impl ProgressBar {
+    pub fn new(label: &str, total: u64, unit: ProgressUnit) -> ProgressBar {
+        ProgressBar {
+            label: label.to_string(),
+            unit,
+            total,
+            done: AtomicU64::new(0),
+            started: Instant::now(),
+            is_terminal: io::stdout().is_terminal(),
+            state: Mutex::new(ProgressState::default()),
+        }
+    }
+
+    /// Add this many files or bytes to the progress and redraw the bar if needed
+    pub fn advance(&self, amount: u64) {
+        let done = self.done.fetch_add(amount, Ordering::Relaxed) + amount;
+        let mut state = self.lock_state();
+
+        if self.is_terminal {
+            let is_due = state.last_drawn.is_none_or(|last_drawn| last_drawn.elapsed() >= REDRAW_INTERVAL);
+            if is_due || done >= self.total {
+                self.draw(&mut state, done);
+            }
+        } else {
+            let percent = self.percent(done) / PLAIN_PERCENT_STEP * PLAIN_PERCENT_STEP;
+            if state.last_plain_percent.is_none_or(|last_percent| percent > last_percent) {
+                state.last_plain_percent = Some(percent);
+                println!("{}", self.format_status(done));
+            }
+        }
+    }
+
+    /// Print a line above the bar, so that the bar stays on the last line
+    pub fn print_line(&self, line: &str) {
+        let mut state = self.lock_state();
+        if state.is_drawn {
+            print!("{}", CLEAR_LINE);
+            state.is_drawn = false;
+        }
+        println!("{}", line);
+
+        // Redraw it right away, so that it doesn't disappear while the next file is slow to finish
+        if self.is_terminal && state.last_drawn.is_some() {
+            self.draw(&mut state, self.done.load(Ordering::Relaxed));
+        }
+    }
+
+    /// Leave the final state of the bar on its own line, so that the next output starts on a new line
+    pub fn finish(&self) {
+        let mut state = self.lock_state();
+        if state.is_drawn {
+            self.draw(&mut state, self.done.load(Ordering::Relaxed));
+            println!();
+            state.is_drawn = false;
+        }
+    }
+
+    fn draw(&self, state: &mut ProgressState, done: u64) {
+        let filled = (self.percent(done) as usize * BAR_WIDTH / 100).min(BAR_WIDTH);
+        print!("{}{} [{}{}] {}",
+               CLEAR_LINE,
+               self.label,
+               "#".repeat(filled),
+               "-".repeat(BAR_WIDTH - filled),
+               self.format_numbers(done));
+        let _ = io::stdout().flush();
+        state.last_drawn = Some(Instant::now());
+        state.is_drawn = true;
+    }
+
+    fn format_status(&self, done: u64) -> String {
+        format!("{}: {}", self.label, self.format_numbers(done))
+    }
+
+    /// The percentage, count, rate and remaining time, e.g. `33% 1200/3600 | 240 files/s | ETA 0:00:10`
+    fn format_numbers(&self, done: u64) -> String {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let rate = if elapsed > 0.0 { done as f64 / elapsed } else { 0.0 };
+
+        let (count, formatted_rate) = match self.unit {
+            ProgressUnit::Files => (
+                format!("{}/{}", done, self.total),
+                format!("{:.0} files/s", rate)),
+            ProgressUnit::Bytes => (
+                format!("{}/{}", format_file_size(done), format_file_size(self.total)),
+                format!("{}/s", format_file_size(rate as u64))),
+        };
+
+        // The remaining time is only estimated once something was done
+        let eta = if done > 0 && rate > 0.0 {
+            let remaining_seconds = (self.total.saturating_sub(done) as f64 / rate).round() as u64;
+            format_duration_seconds(Duration::from_secs(remaining_seconds))
+        } else {
+            String::from("-:--:--")
+        };
+
+        format!("{}% {} | {} | ETA {}", self.percent(done), count, formatted_rate, eta)
+    }
+
+    fn percent(&self, done: u64) -> u64 {
+        // Nothing to do counts as done
+        (done.min(self.total) * 100).checked_div(self.total).unwrap_or(100)
+    }
+
+    /// The bar is only drawn, so a lock poisoned by a panicking thread can still be used
+    fn lock_state(&self) -> MutexGuard<'_, ProgressState> {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
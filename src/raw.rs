@@ -0,0 +1,77 @@
+use std::convert::TryInto;
+use std::io::{Read, Seek, SeekFrom};
+
+use exif::Error;
+
+use crate::filetype::detect_file_type_from_bytes;
+
+/// The preview of a RAF file is a regular JPEG, so only its first part is read to find the EXIF segment
+const MAX_RAF_PREVIEW_HEADER_LEN: u64 = 1024 * 1024;
+
+/// Most RAW formats (CR2, ARW, DNG, NEF, etc) are regular TIFF files, which are read by kamadak-exif directly.
+/// Read the EXIF data of the formats which aren't, based on the `header` of the file:
+/// - ORF (Olympus) and RW2 (Panasonic) are TIFF files with a different "magic number"
+/// - RAF (Fujifilm) files have their own header, followed by a JPEG preview which contains the EXIF data
+///
+/// Returns the data in TIFF format, as expected by [exif::Reader::read_raw()],
+/// or None if this is not one of these formats
+pub fn read_raw_exif_data<R: Read + Seek>(reader: &mut R, header: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+    match detect_file_type_from_bytes(header).map(|detected_type| detected_type.format) {
+        Some("orf") | Some("rw2") => {
+            let mut tiff_data = Vec::new();
+            reader.seek(SeekFrom::Start(0))?;
+            reader.read_to_end(&mut tiff_data)?;
+
+            // Replace the magic number with the regular TIFF one, 42, in the byte order of the file
+            let byte_order = &tiff_data[..2];
+            let magic_number: [u8; 2] = if byte_order == b"II" { [42, 0] } else { [0, 42] };
+            tiff_data[2..4].copy_from_slice(&magic_number);
+
+            Ok(Some(tiff_data))
+        }
+        Some("raf") => read_raf_exif_data(reader, header).map(Some),
+        _ => Ok(None),
+    }
+}
+
+/// The RAF header has the offset (4) and length (4) of the JPEG preview at byte 84
+fn read_raf_exif_data<R: Read + Seek>(reader: &mut R, header: &[u8]) -> Result<Vec<u8>, Error> {
+    let read_u32 = |offset: usize| header
+        .get(offset..offset + 4)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(u32::from_be_bytes)
+        .ok_or(Error::InvalidFormat("RAF header is too small"));
+    let preview_offset = read_u32(84)? as u64;
+    let preview_len = read_u32(88)? as u64;
+
+    let mut preview = Vec::new();
+    reader.seek(SeekFrom::Start(preview_offset))?;
+    reader.take(preview_len.min(MAX_RAF_PREVIEW_HEADER_LEN)).read_to_end(&mut preview)?;
+
+    find_jpeg_exif_data(&preview).ok_or(Error::NotFound("RAF"))
+}
+
+/// Find the APP1 segment with the EXIF data among the segments at the start of a JPEG file
+/// and return its contents without the `Exif\0\0` prefix
+fn find_jpeg_exif_data(jpeg: &[u8]) -> Option<Vec<u8>> {
+    if jpeg.get(..2)? != [0xFF, 0xD8] {
+        return None;
+    }
+
+    // Each segment has a marker (2) and a length (2), which includes the length itself but not the marker
+    let mut position = 2;
+    loop {
+        let marker = jpeg.get(position..position + 2)?;
+        // Start of scan: the image data follows, there are no more metadata segments
+        if marker[0] != 0xFF || marker[1] == 0xDA {
+            return None;
+        }
+
+        let segment_len = u16::from_be_bytes(jpeg.get(position + 2..position + 4)?.try_into().ok()?) as usize;
+        let segment = jpeg.get(position + 4..position + 2 + segment_len)?;
+        if marker[1] == 0xE1 && segment.starts_with(b"Exif\0\0") {
+            return Some(segment[6..].to_vec());
+        }
+        position += 2 + segment_len;
+    }
+}
@@ -0,0 +1,83 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+/// The name of the PAR2 recovery set created inside each target date folder.
+/// `par2` creates additional `imgsorter.vol*.par2` volumes next to it
+pub const RECOVERY_FILE_NAME: &str = "imgsorter.par2";
+
+/// The external program used to create the PAR2 recovery files,
+/// see https://github.com/Parchive/par2cmdline
+const PAR2_PROGRAM: &str = "par2";
+
+/// Matches all files of a folder, which `par2` expands itself, together with the files of its subfolders
+const ALL_FILES_WILDCARD: &str = "*";
+
+/// Check if the `par2` program can be launched
+pub fn is_par2_available() -> bool {
+    Command::new(PAR2_PROGRAM)
+        .arg("-V")
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
+
+/// Create (or re-create) the PAR2 recovery files for all files inside `dir`, including subfolders.
+/// Any previous recovery files are removed first, since they would not cover newly added files.
+/// `redundancy` is the percentage of recovery data relative to the total size of the files.
+/// The files are found by `par2` itself, since a folder can have more files than fit on a command line
+pub fn create_recovery_files(dir: &Path, redundancy: u8) -> Result<(), String> {
+    remove_recovery_files(dir).map_err(|e| format!("could not remove old recovery files: {}", e))?;
+
+    if !has_files(dir).map_err(|e| format!("could not read folder: {}", e))? {
+        return Ok(());
+    }
+
+    let output = Command::new(PAR2_PROGRAM)
+        .arg("create")
+        .arg("-q")
+        .arg("-R")
+        .arg(format!("-r{}", redundancy))
+        .arg("-B")
+        .arg(dir)
+        .arg(dir.join(RECOVERY_FILE_NAME))
+        .arg("--")
+        .arg(dir.join(ALL_FILES_WILDCARD))
+        .output()
+        .map_err(|e| format!("could not run '{}': {}", PAR2_PROGRAM, e))?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+fn is_recovery_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(|name| name.starts_with("imgsorter") && name.ends_with(".par2"))
+        .unwrap_or(false)
+}
+
+fn remove_recovery_files(dir: &Path) -> Result<(), io::Error> {
+    for entry in fs::read_dir(dir)?.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if path.is_file() && is_recovery_file(&path) {
+            fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Check if the folder or any of its subfolders has a file which can be covered by recovery data
+fn has_files(dir: &Path) -> Result<bool, io::Error> {
+    for entry in fs::read_dir(dir)?.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if (path.is_dir() && has_files(&path)?) || (path.is_file() && !is_recovery_file(&path)) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
@@ -0,0 +1,233 @@
+use std::collections::{BTreeSet, HashSet};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::build_info::VERSION;
+use crate::exif::GpsLocation;
+
+/// The shell which will run the script, chosen from the extension of the script file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ScriptFormat {
+    /// A POSIX shell script, e.g. `plan.sh`
+    Shell,
+    /// A PowerShell script, e.g. `plan.ps1`
+    PowerShell,
+    /// A batch file for the Windows command prompt, e.g. `plan.cmd` or `plan.bat`
+    Cmd,
+}
+
+impl ScriptFormat {
+    /// Any extension other than `.ps1`, `.cmd` or `.bat` is written as a POSIX shell script
+    pub fn from_path(script_path: &Path) -> ScriptFormat {
+        let extension = script_path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+
+        match extension.as_deref() {
+            Some("ps1") => ScriptFormat::PowerShell,
+            Some("cmd") | Some("bat") => ScriptFormat::Cmd,
+            _ => ScriptFormat::Shell,
+        }
+    }
+}
+
+/// A single file which a run would write to the target folder
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptOperation {
+    pub source: PathBuf,
+    pub target: PathBuf,
+    /// Whether the source file is removed after it's copied
+    pub is_move: bool,
+    /// Whether the target file exists and will be replaced, see [crate::config::CollisionPolicy]
+    pub overwrite: bool,
+    /// Where the photo was taken, if read from EXIF, which is only saved in JSON plans
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<GpsLocation>,
+}
+
+/// The operations of a dry run saved as JSON, which are executed as they are by the `apply` command,
+/// e.g. to review the plan on one computer and write the files on another one
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExportedPlan {
+    /// The version of the program which saved the plan
+    pub version: String,
+    /// The journal of the run which applies the plan is saved here, so that it can be undone as usual
+    pub target_dir: PathBuf,
+    pub operations: Vec<ScriptOperation>,
+}
+
+impl ExportedPlan {
+    pub fn read(plan_path: &Path) -> Result<ExportedPlan, io::Error> {
+        let contents = fs::read_to_string(plan_path)?;
+        serde_json::from_str(&contents).map_err(io::Error::from)
+    }
+
+    /// Leave out the operations which were already completed, e.g. by an earlier run of the same plan
+    /// which was interrupted, see [crate::journal::RunMarker::plan]
+    pub fn skip_completed(&mut self, completed_targets: &HashSet<PathBuf>) {
+        self.operations.retain(|operation| !completed_targets.contains(&operation.target));
+    }
+
+    /// Check that each source file still exists and each target file is still free, unless it will be overwritten.
+    /// Returns a description of each operation which can't be executed as planned
+    pub fn validate(&self) -> Vec<String> {
+        self.operations
+            .iter()
+            .filter_map(|operation| {
+                if !operation.target.starts_with(&self.target_dir) {
+                    Some(format!("{} (the target file is outside the target folder {})",
+                                 operation.target.display(), self.target_dir.display()))
+                } else if !operation.source.is_file() {
+                    Some(format!("{} (the source file no longer exists)", operation.source.display()))
+                } else if !operation.overwrite && operation.target.exists() {
+                    Some(format!("{} (the target file already exists)", operation.target.display()))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+/// Write the operations as a script which creates the target folders, then copies or moves each file.
+/// Files are only written if the target file doesn't exist, unless the operation is marked as an overwrite,
+/// so that running the script twice, or after the program itself, doesn't replace any file.
+/// A `.json` file is saved as an [ExportedPlan] instead, which the program itself can apply later
+pub fn write_script(script_path: &Path, target_dir: &Path, operations: &[ScriptOperation]) -> Result<(), io::Error> {
+    let mut writer = BufWriter::new(File::create(script_path)?);
+
+    if script_path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("json")) {
+        let plan = ExportedPlan {
+            version: VERSION.to_string(),
+            target_dir: target_dir.to_path_buf(),
+            // Paths which aren't valid UTF-8 can't be saved as JSON, see [format_invalid_path_comment]
+            operations: operations
+                .iter()
+                .filter(|operation| operation.source.to_str().is_some() && operation.target.to_str().is_some())
+                .cloned()
+                .collect(),
+        };
+        serde_json::to_writer_pretty(&mut writer, &plan)?;
+        return writer.flush();
+    }
+
+    let format = ScriptFormat::from_path(script_path);
+
+    let target_dirs: BTreeSet<&Path> = operations
+        .iter()
+        .filter_map(|operation| operation.target.parent())
+        .collect();
+
+    let header = format!("Generated by imgsorter v{}: {} {} to {} into {} {}",
+                         VERSION,
+                         operations.len(),
+                         if operations.len() == 1 { "file" } else { "files" },
+                         if operations.iter().all(|operation| operation.is_move) { "move" } else { "write" },
+                         target_dirs.len(),
+                         if target_dirs.len() == 1 { "folder" } else { "folders" });
+    match format {
+        ScriptFormat::Shell => {
+            writeln!(writer, "#!/bin/sh")?;
+            writeln!(writer, "# {}", header)?;
+        }
+        ScriptFormat::PowerShell => {
+            // Windows PowerShell reads scripts without a byte order mark in the ANSI code page
+            write!(writer, "\u{feff}")?;
+            writeln!(writer, "# {}", header)?;
+        }
+        ScriptFormat::Cmd => {
+            writeln!(writer, "@echo off")?;
+            writeln!(writer, "rem {}", header)?;
+            // Read the file names as UTF-8
+            writeln!(writer, "chcp 65001 >nul")?;
+        }
+    }
+    writeln!(writer)?;
+
+    for target_dir in target_dirs {
+        writeln!(writer, "{}", format_create_dir(format, target_dir))?;
+    }
+    writeln!(writer)?;
+
+    for operation in operations {
+        writeln!(writer, "{}", format_operation(format, operation))?;
+    }
+
+    writer.flush()
+}
+
+fn format_create_dir(format: ScriptFormat, dir: &Path) -> String {
+    if dir.to_str().is_none() {
+        return format_invalid_path_comment(format, dir);
+    }
+
+    let dir = quote_path(format, dir);
+    match format {
+        ScriptFormat::Shell => format!("mkdir -p {}", dir),
+        ScriptFormat::PowerShell => format!("New-Item -ItemType Directory -Force -Path {} | Out-Null", dir),
+        ScriptFormat::Cmd => format!("if not exist {} mkdir {}", dir, dir),
+    }
+}
+
+fn format_operation(format: ScriptFormat, operation: &ScriptOperation) -> String {
+    if operation.source.to_str().is_none() || operation.target.to_str().is_none() {
+        return format_invalid_path_comment(format, &operation.source);
+    }
+
+    let source = quote_path(format, &operation.source);
+    let target = quote_path(format, &operation.target);
+
+    match format {
+        ScriptFormat::Shell => {
+            let command = if operation.is_move { "mv" } else { "cp -p" };
+            if operation.overwrite {
+                format!("{} -f {} {}", command, source, target)
+            } else {
+                format!("[ -e {} ] || {} {} {}", target, command, source, target)
+            }
+        }
+        ScriptFormat::PowerShell => {
+            let command = if operation.is_move { "Move-Item" } else { "Copy-Item" };
+            if operation.overwrite {
+                format!("{} -LiteralPath {} -Destination {} -Force", command, source, target)
+            } else {
+                format!("if (-not (Test-Path -LiteralPath {})) {{ {} -LiteralPath {} -Destination {} }}",
+                        target, command, source, target)
+            }
+        }
+        ScriptFormat::Cmd => {
+            let command = if operation.is_move { "move" } else { "copy" };
+            if operation.overwrite {
+                format!("{} /Y {} {} >nul", command, source, target)
+            } else {
+                format!("if not exist {} {} {} {} >nul", target, command, source, target)
+            }
+        }
+    }
+}
+
+/// Paths which aren't valid UTF-8 can't be written in the script without changing them,
+/// so the operation is left out and only mentioned in a comment instead
+fn format_invalid_path_comment(format: ScriptFormat, path: &Path) -> String {
+    let comment = match format {
+        ScriptFormat::Shell | ScriptFormat::PowerShell => "#",
+        ScriptFormat::Cmd => "rem",
+    };
+    format!("{} Left out, the path is not valid UTF-8: {}", comment, path.display())
+}
+
+/// Quote the path so that it's passed as a single argument, without expanding any special characters
+fn quote_path(format: ScriptFormat, path: &Path) -> String {
+    let path = path.to_string_lossy();
+    match format {
+        // Nothing is expanded inside single quotes, which can't be escaped, only closed and reopened
+        ScriptFormat::Shell => format!("'{}'", path.replace('\'', r"'\''")),
+        // Nothing is expanded inside single quotes, which are escaped by doubling them
+        ScriptFormat::PowerShell => format!("'{}'", path.replace('\'', "''")),
+        // Windows paths can't contain double quotes, but variables are still expanded inside them
+        ScriptFormat::Cmd => format!("\"{}\"", path.replace('%', "%%")),
+    }
+}
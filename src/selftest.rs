@@ -0,0 +1,438 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use chrono::{NaiveDate, NaiveDateTime};
+use filetime::FileTime;
+
+use crate::checksum::hash_file;
+use crate::config::DATE_DIR_FORMAT;
+use crate::exif::EXIF_DATE_FORMAT;
+use crate::utils::*;
+
+/// The start of the name of the temporary folder created by the `selftest` command, followed by the time it started
+pub const SELFTEST_DIR_PREFIX: &str = "imgsorter-selftest";
+
+/// Only the photos are compared, so that the files written by the program itself, e.g. its journal, are left out
+const SYNTHETIC_EXTENSION: &str = "jpg";
+
+/// The modified time of the photos which have a date in their EXIF data or name,
+/// so that a photo sorted by its modified time by mistake ends up in the wrong folder
+const DECOY_MODIFIED_TIME: &str = "2024:01:01 12:00:00";
+
+/// Where the program must read the date of a synthetic photo from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DateSource {
+    /// The DateTimeOriginal EXIF tag
+    Exif,
+    /// The file name, which must contain the date, e.g. `IMG_20180704_101500.jpg`
+    FileName,
+    /// The modified time of the file, since it has no other date
+    Modified,
+}
+
+/// A photo of the synthetic library, see [generate_synthetic_library]
+struct SyntheticPhoto {
+    path: &'static str,
+    /// The make and model written in the EXIF data, if any
+    device: Option<(&'static str, &'static str)>,
+    /// When the photo was taken, in [EXIF_DATE_FORMAT]
+    date_time: &'static str,
+    date_source: DateSource,
+}
+
+const SYNTHETIC_PHOTOS: &[SyntheticPhoto] = &[
+    // Two devices on the same day, which are split into device folders, unlike the days with a single device
+    SyntheticPhoto { path: "DCIM/100CANON/IMG_0001.jpg", device: Some(("Canon", "Canon EOS 80D")),
+        date_time: "2019:05:03 10:15:00", date_source: DateSource::Exif },
+    SyntheticPhoto { path: "DCIM/100CANON/IMG_0002.jpg", device: Some(("Canon", "Canon EOS 80D")),
+        date_time: "2019:05:03 18:40:12", date_source: DateSource::Exif },
+    SyntheticPhoto { path: "Phone/PXL_0001.jpg", device: Some(("Google", "Pixel 7")),
+        date_time: "2019:05:03 12:00:00", date_source: DateSource::Exif },
+    // The last second of a year and a leap day
+    SyntheticPhoto { path: "DCIM/100CANON/IMG_0003.jpg", device: Some(("Canon", "Canon EOS 80D")),
+        date_time: "2020:12:31 23:59:59", date_source: DateSource::Exif },
+    SyntheticPhoto { path: "Phone/PXL_0002.jpg", device: Some(("Google", "Pixel 7")),
+        date_time: "2020:02:29 08:30:00", date_source: DateSource::Exif },
+    // EXIF data with a device but without a date
+    SyntheticPhoto { path: "Phone/PXL_0003.jpg", device: Some(("Google", "Pixel 7")),
+        date_time: "2022:08:15 09:00:00", date_source: DateSource::Modified },
+    // No EXIF data at all
+    SyntheticPhoto { path: "Phone/IMG_20180704_101500.jpg", device: None,
+        date_time: "2018:07:04 10:15:00", date_source: DateSource::FileName },
+    SyntheticPhoto { path: "Scans/scan_001.jpg", device: None,
+        date_time: "2017:03:15 12:00:00", date_source: DateSource::Modified },
+    SyntheticPhoto { path: "Scans/scan_002.jpg", device: None,
+        date_time: "2017:03:15 12:05:00", date_source: DateSource::Modified },
+];
+
+/// Exact copies of some synthetic photos, with the same name in another folder, as `(copy, original)`
+const SYNTHETIC_DUPLICATES: &[(&str, &str)] = &[
+    ("Backup/IMG_0001.jpg", "DCIM/100CANON/IMG_0001.jpg"),
+    ("Backup/scan_001.jpg", "Scans/scan_001.jpg"),
+];
+
+/// A file of the synthetic library, with the folder it must be sorted into
+#[derive(Debug, Clone)]
+pub struct SyntheticFile {
+    /// The path of the file inside the library folder
+    pub path: PathBuf,
+    /// The date of the date folder the file must be sorted into
+    pub date: NaiveDate,
+    /// The device model whose folder the file must be sorted into, if the file has one
+    /// and other files from the same date have another one
+    pub device: Option<String>,
+}
+
+/// Create a small library of photos inside `library_dir`, with EXIF dates from different devices,
+/// dates only in the file name or the modified time, and duplicates. The photos are tiny JPEG files
+/// with only an EXIF segment and a comment which makes each one unique, so there's nothing to decode.
+/// Returns each file with the folder it must be sorted into
+pub fn generate_synthetic_library(library_dir: &Path) -> Result<Vec<SyntheticFile>, io::Error> {
+    let parse_date_time = |date_time: &str| NaiveDateTime::parse_from_str(date_time, EXIF_DATE_FORMAT)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("'{}': {}", date_time, e)));
+    let decoy_time = parse_date_time(DECOY_MODIFIED_TIME)?;
+
+    let mut synthetic_files = Vec::new();
+    for photo in SYNTHETIC_PHOTOS {
+        let date_time = parse_date_time(photo.date_time)?;
+        let exif_date_time = if photo.date_source == DateSource::Exif { Some(photo.date_time) } else { None };
+        let contents = build_jpeg(photo.device, exif_date_time, photo.path);
+
+        let modified_time = if photo.date_source == DateSource::Modified { date_time } else { decoy_time };
+        let file_path = library_dir.join(photo.path);
+        write_file(&file_path, &contents, modified_time)?;
+
+        synthetic_files.push(SyntheticFile {
+            path: PathBuf::from(photo.path),
+            date: date_time.date(),
+            device: photo.device.map(|(_, model)| model.to_string()),
+        });
+    }
+
+    // Device folders are only created for the dates with photos from several devices
+    let mut devices_by_date: BTreeMap<NaiveDate, BTreeSet<String>> = BTreeMap::new();
+    for synthetic_file in &synthetic_files {
+        if let Some(device) = &synthetic_file.device {
+            devices_by_date.entry(synthetic_file.date).or_default().insert(device.clone());
+        }
+    }
+    for synthetic_file in synthetic_files.iter_mut() {
+        if devices_by_date.get(&synthetic_file.date).is_some_and(|devices| devices.len() < 2) {
+            synthetic_file.device = None;
+        }
+    }
+
+    for (copy_path, original_path) in SYNTHETIC_DUPLICATES {
+        let original = synthetic_files
+            .iter()
+            .find(|file| file.path == Path::new(original_path))
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("'{}' is not a synthetic photo", original_path)))?;
+
+        let original_path = library_dir.join(original_path);
+        let modified_time = FileTime::from_last_modification_time(&fs::metadata(&original_path)?);
+        let file_path = library_dir.join(copy_path);
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(&original_path, &file_path)?;
+        filetime::set_file_mtime(&file_path, modified_time)?;
+
+        synthetic_files.push(SyntheticFile {
+            path: PathBuf::from(copy_path),
+            ..original
+        });
+    }
+
+    Ok(synthetic_files)
+}
+
+fn write_file(file_path: &Path, contents: &[u8], modified_time: NaiveDateTime) -> Result<(), io::Error> {
+    if let Some(parent) = file_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(file_path, contents)?;
+    // The date of the modified time is read in UTC
+    filetime::set_file_mtime(file_path, FileTime::from_unix_time(modified_time.timestamp(), 0))
+}
+
+/// Build a JPEG file with an EXIF segment, if there's anything to write in it, and a comment segment.
+/// Readers of EXIF data stop before the image data, so the file doesn't need any
+fn build_jpeg(device: Option<(&str, &str)>, exif_date_time: Option<&str>, comment: &str) -> Vec<u8> {
+    const SOI: [u8; 2] = [0xFF, 0xD8];
+    const APP1: [u8; 2] = [0xFF, 0xE1];
+    const COM: [u8; 2] = [0xFF, 0xFE];
+    const EOI: [u8; 2] = [0xFF, 0xD9];
+
+    // Segment lengths include the 2 bytes of the length itself
+    let append_segment = |jpeg: &mut Vec<u8>, marker: [u8; 2], data: &[u8]| {
+        jpeg.extend_from_slice(&marker);
+        jpeg.extend_from_slice(&((data.len() + 2) as u16).to_be_bytes());
+        jpeg.extend_from_slice(data);
+    };
+
+    let mut jpeg = SOI.to_vec();
+    if device.is_some() || exif_date_time.is_some() {
+        let mut exif = b"Exif\0\0".to_vec();
+        exif.extend(build_tiff(device, exif_date_time));
+        append_segment(&mut jpeg, APP1, &exif);
+    }
+    append_segment(&mut jpeg, COM, comment.as_bytes());
+    jpeg.extend_from_slice(&EOI);
+    jpeg
+}
+
+/// Build the little-endian TIFF structure of an EXIF segment, with the make and model in the first IFD
+/// and the date in the Exif IFD, see https://www.media.mit.edu/pia/Research/deepview/exif.html
+fn build_tiff(device: Option<(&str, &str)>, exif_date_time: Option<&str>) -> Vec<u8> {
+    const TAG_MAKE: u16 = 0x010F;
+    const TAG_MODEL: u16 = 0x0110;
+    const TAG_EXIF_IFD_POINTER: u16 = 0x8769;
+    const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+
+    let mut tiff = b"II*\0".to_vec();
+    tiff.extend_from_slice(&8u32.to_le_bytes());
+
+    let primary_entries: Vec<(u16, &str)> = device
+        .map(|(make, model)| vec![(TAG_MAKE, make), (TAG_MODEL, model)])
+        .unwrap_or_default();
+    let exif_entries: Vec<(u16, &str)> = exif_date_time
+        .map(|date_time| vec![(TAG_DATE_TIME_ORIGINAL, date_time)])
+        .unwrap_or_default();
+
+    let pointer_tag = if exif_entries.is_empty() { None } else { Some(TAG_EXIF_IFD_POINTER) };
+    let pointer_position = append_ifd(&mut tiff, &primary_entries, pointer_tag);
+    if let Some(pointer_position) = pointer_position {
+        let exif_ifd_offset = tiff.len() as u32;
+        tiff[pointer_position..pointer_position + 4].copy_from_slice(&exif_ifd_offset.to_le_bytes());
+        append_ifd(&mut tiff, &exif_entries, None);
+    }
+    tiff
+}
+
+/// Append an IFD with text entries, which must be sorted by tag, followed by the texts which don't fit
+/// inside their entry. The pointer to another IFD is added last, since its tag is larger than the others.
+/// Returns the position of the pointer value, which is only known once the other IFD is appended
+fn append_ifd(tiff: &mut Vec<u8>, entries: &[(u16, &str)], pointer_tag: Option<u16>) -> Option<usize> {
+    const TYPE_ASCII: u16 = 2;
+    const TYPE_LONG: u16 = 4;
+
+    let entry_count = entries.len() + pointer_tag.iter().count();
+    let mut data_offset = tiff.len() + 2 + 12 * entry_count + 4;
+    let mut data = Vec::new();
+
+    tiff.extend_from_slice(&(entry_count as u16).to_le_bytes());
+    for (tag, text) in entries {
+        let mut value = text.as_bytes().to_vec();
+        value.push(0);
+
+        tiff.extend_from_slice(&tag.to_le_bytes());
+        tiff.extend_from_slice(&TYPE_ASCII.to_le_bytes());
+        tiff.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        if value.len() <= 4 {
+            value.resize(4, 0);
+            tiff.extend_from_slice(&value);
+        } else {
+            tiff.extend_from_slice(&(data_offset as u32).to_le_bytes());
+            // Values start at even offsets
+            if value.len() % 2 == 1 {
+                value.push(0);
+            }
+            data_offset += value.len();
+            data.extend(value);
+        }
+    }
+
+    let pointer_position = pointer_tag.map(|tag| {
+        tiff.extend_from_slice(&tag.to_le_bytes());
+        tiff.extend_from_slice(&TYPE_LONG.to_le_bytes());
+        tiff.extend_from_slice(&1u32.to_le_bytes());
+        let position = tiff.len();
+        tiff.extend_from_slice(&0u32.to_le_bytes());
+        position
+    });
+
+    // There's no next IFD
+    tiff.extend_from_slice(&0u32.to_le_bytes());
+    tiff.extend(data);
+    pointer_position
+}
+
+/// The checksum of each photo inside a folder and its subfolders, by path relative to the folder
+pub type FolderHashes = BTreeMap<PathBuf, String>;
+
+/// Hash each photo inside the folder and its subfolders. A folder which doesn't exist has no photos
+pub fn hash_folder_photos(dir: &Path) -> Result<FolderHashes, io::Error> {
+    let mut hashes = FolderHashes::new();
+    if dir.exists() {
+        hash_photos_recursively(dir, dir, &mut hashes)?;
+    }
+    Ok(hashes)
+}
+
+fn hash_photos_recursively(root_dir: &Path, dir: &Path, hashes: &mut FolderHashes) -> Result<(), io::Error> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            hash_photos_recursively(root_dir, &path, hashes)?;
+        } else if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case(SYNTHETIC_EXTENSION)) {
+            let relative_path = path.strip_prefix(root_dir).unwrap_or(&path).to_path_buf();
+            hashes.insert(relative_path, hash_file(&path)?);
+        }
+    }
+    Ok(())
+}
+
+/// A single invariant checked by the `selftest` command, with the problems found, if any
+#[derive(Debug)]
+struct SelftestCheck {
+    description: String,
+    problems: Vec<String>,
+}
+
+/// The outcome of each invariant checked by the `selftest` command
+#[derive(Debug, Default)]
+pub struct SelftestReport {
+    checks: Vec<SelftestCheck>,
+}
+
+impl SelftestReport {
+    pub fn new() -> SelftestReport {
+        SelftestReport::default()
+    }
+
+    pub fn has_failures(&self) -> bool {
+        self.checks.iter().any(|check| !check.problems.is_empty())
+    }
+
+    /// Record the result of a check, which passed if it found no problems
+    pub fn add(&mut self, description: &str, problems: Vec<String>) {
+        self.checks.push(SelftestCheck { description: description.to_string(), problems });
+    }
+
+    /// Record an error which stopped a step of the test, e.g. a folder which could not be read
+    pub fn add_error(&mut self, description: &str, error: &io::Error) {
+        self.add(description, vec![error.to_string()]);
+    }
+
+    pub fn print_summary(&self) {
+        let passed_count = self.checks.iter().filter(|check| check.problems.is_empty()).count();
+
+        println!("──────────────────────────────────────────────");
+        for check in &self.checks {
+            if check.problems.is_empty() {
+                println!("{} {}", ColoredString::green("passed"), check.description);
+            } else {
+                println!("{} {}", ColoredString::red("failed"), check.description);
+                check.problems
+                    .iter()
+                    .for_each(|problem| println!("         {}", problem));
+            }
+        }
+        println!("──────────────────────────────────────────────");
+
+        let summary = format!("{} of {} checks passed", passed_count, self.checks.len());
+        if self.has_failures() {
+            println!("{}", ColoredString::red(summary.as_str()));
+        } else {
+            println!("{}", ColoredString::green(summary.as_str()));
+        }
+    }
+}
+
+/// List the files which were changed, removed or added between the two states of the same folder
+pub fn compare_folder_hashes(before: &FolderHashes, after: &FolderHashes) -> Vec<String> {
+    let changed_or_removed = before
+        .iter()
+        .filter_map(|(path, hash)| match after.get(path) {
+            Some(after_hash) if after_hash == hash => None,
+            Some(_) => Some(format!("{} was changed", path.display())),
+            None => Some(format!("{} was removed", path.display())),
+        });
+    let added = after
+        .keys()
+        .filter(|path| !before.contains_key(*path))
+        .map(|path| format!("{} was added", path.display()));
+
+    changed_or_removed.chain(added).collect()
+}
+
+/// List the target files which aren't an exact copy of any source file, e.g. incomplete copies
+pub fn find_unknown_targets(source_hashes: &FolderHashes, target_hashes: &FolderHashes) -> Vec<String> {
+    target_hashes
+        .iter()
+        .filter(|(_, hash)| !source_hashes.values().any(|source_hash| source_hash == *hash))
+        .map(|(path, _)| format!("{} is not a copy of any source file", path.display()))
+        .collect()
+}
+
+/// List the source files which weren't written to the target folder at all, nor an identical copy of them
+pub fn find_missing_targets(source_hashes: &FolderHashes, target_hashes: &FolderHashes) -> Vec<String> {
+    source_hashes
+        .iter()
+        .filter(|(_, hash)| !target_hashes.values().any(|target_hash| target_hash == *hash))
+        .map(|(path, _)| format!("{} was not written", path.display()))
+        .collect()
+}
+
+/// List the target files which have the same contents as another target file, except the first one
+pub fn find_repeated_targets(target_hashes: &FolderHashes) -> Vec<String> {
+    let mut first_paths: BTreeMap<&str, &Path> = BTreeMap::new();
+    target_hashes
+        .iter()
+        .filter_map(|(path, hash)| match first_paths.get(hash.as_str()) {
+            Some(first_path) => Some(format!("{} is identical to {}", path.display(), first_path.display())),
+            None => {
+                first_paths.insert(hash, path);
+                None
+            }
+        })
+        .collect()
+}
+
+/// List the synthetic files which don't have a copy in the folder of their date and device.
+/// The folders are the ones created with the default options, e.g. `2019.05.03/Canon EOS 80D`,
+/// where the device folder can also include the make of the device
+pub fn find_misplaced_targets(
+    synthetic_files: &[SyntheticFile],
+    source_hashes: &FolderHashes,
+    target_hashes: &FolderHashes,
+) -> Vec<String> {
+    let has_dir = |target_path: &Path, is_expected: &dyn Fn(&str) -> bool| target_path
+        .parent()
+        .into_iter()
+        .flat_map(Path::components)
+        .any(|component| match component {
+            Component::Normal(dir_name) => is_expected(&dir_name.to_string_lossy()),
+            _ => false,
+        });
+
+    synthetic_files
+        .iter()
+        .filter_map(|synthetic_file| {
+            let source_hash = source_hashes.get(&synthetic_file.path)?;
+            let date_dir = synthetic_file.date.format(DATE_DIR_FORMAT).to_string();
+            let is_placed = target_hashes
+                .iter()
+                .filter(|(_, target_hash)| *target_hash == source_hash)
+                .any(|(target_path, _)| {
+                    has_dir(target_path, &|dir_name| dir_name == date_dir)
+                        && synthetic_file.device
+                            .as_ref()
+                            .is_none_or(|device| has_dir(target_path, &|dir_name| dir_name.ends_with(device.as_str())))
+                });
+
+            if is_placed {
+                None
+            } else {
+                let expected_dir = match &synthetic_file.device {
+                    Some(device) => format!("{}/{}", date_dir, device),
+                    None => date_dir,
+                };
+                Some(format!("{} is not in the '{}' folder", synthetic_file.path.display(), expected_dir))
+            }
+        })
+        .collect()
+}
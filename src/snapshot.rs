@@ -0,0 +1,112 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, DirEntry, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, Local};
+use sha2::{Digest, Sha256};
+
+use crate::journal::JOURNAL_DIR_NAME;
+
+/// The file inside the journal folder which holds the snapshot hash of the sources of the last successful run
+const SOURCE_SNAPSHOT_FILE_NAME: &str = "source-snapshot";
+
+/// The file inside the journal folder which holds the start time of the last successful run of each source folder
+const LAST_RUN_TIMES_FILE_NAME: &str = "last-runs.json";
+
+/// Hash the state of the source files, i.e. the path, size and modified time of each file,
+/// so that a run can tell if anything changed in the sources since the last successful run.
+/// The contents of the files are not read, so this is fast even for large folders
+pub fn hash_source_snapshot<'a>(source_files: impl Iterator<Item = &'a DirEntry>) -> String {
+    let mut file_states: Vec<(PathBuf, u64, u128)> = source_files
+        .map(|entry| {
+            let metadata = entry.metadata().ok();
+            let size = metadata.as_ref().map_or(0, |metadata| metadata.len());
+            let modified = metadata
+                .and_then(|metadata| metadata.modified().ok())
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |since_epoch| since_epoch.as_nanos());
+            (entry.path(), size, modified)
+        })
+        .collect();
+    file_states.sort();
+
+    let mut hasher = Sha256::new();
+    for (path, size, modified) in file_states {
+        hasher.update(path.to_string_lossy().as_bytes());
+        hasher.update(b"\0");
+        hasher.update(size.to_le_bytes());
+        hasher.update(modified.to_le_bytes());
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Read the snapshot hash saved by the last successful run into the target folder, if any
+pub fn read_source_snapshot(target_dir: &Path) -> Option<String> {
+    fs::read_to_string(target_dir.join(JOURNAL_DIR_NAME).join(SOURCE_SNAPSHOT_FILE_NAME))
+        .ok()
+        .map(|snapshot| snapshot.trim().to_string())
+}
+
+pub fn save_source_snapshot(target_dir: &Path, snapshot: &str) -> Result<(), io::Error> {
+    let snapshot_dir = target_dir.join(JOURNAL_DIR_NAME);
+    fs::create_dir_all(&snapshot_dir)?;
+    fs::write(snapshot_dir.join(SOURCE_SNAPSHOT_FILE_NAME), format!("{}\n", snapshot))
+}
+
+/// Forget the snapshot of the last successful run, e.g. after it was undone
+pub fn remove_source_snapshot(target_dir: &Path) -> Result<(), io::Error> {
+    match fs::remove_file(target_dir.join(JOURNAL_DIR_NAME).join(SOURCE_SNAPSHOT_FILE_NAME)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Read the start time of the last successful run of each source folder into the target folder,
+/// see [save_last_run_times]. Sources without a valid time are left out, so all of their files are read
+pub fn read_last_run_times(target_dir: &Path) -> HashMap<String, DateTime<Local>> {
+    fs::read_to_string(target_dir.join(JOURNAL_DIR_NAME).join(LAST_RUN_TIMES_FILE_NAME))
+        .ok()
+        .and_then(|contents| serde_json::from_str::<BTreeMap<String, String>>(&contents).ok())
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(source, time)| Some((source, DateTime::parse_from_rfc3339(&time).ok()?.with_timezone(&Local))))
+        .collect()
+}
+
+/// Save the start time of this run as the last run of each of its source folders, keeping the times of the
+/// other sources, e.g. `{"D:\\Pics": "2023-05-14T02:00:00+02:00"}`. The start time is used, so that files
+/// added to the sources while the run was reading them are read again by the next run
+pub fn save_last_run_times<'a>(
+    target_dir: &Path,
+    sources: impl Iterator<Item = &'a String>,
+    run_start_time: DateTime<Local>
+) -> Result<(), io::Error> {
+    let mut last_run_times: BTreeMap<String, String> = read_last_run_times(target_dir)
+        .into_iter()
+        .map(|(source, time)| (source, time.to_rfc3339()))
+        .collect();
+    sources.for_each(|source| { last_run_times.insert(source.clone(), run_start_time.to_rfc3339()); });
+
+    let snapshot_dir = target_dir.join(JOURNAL_DIR_NAME);
+    fs::create_dir_all(&snapshot_dir)?;
+    fs::write(snapshot_dir.join(LAST_RUN_TIMES_FILE_NAME), serde_json::to_string_pretty(&last_run_times)?)
+}
+
+/// Forget the last run times of all sources, e.g. after a run was undone,
+/// since the files moved back to the sources keep their old modified times
+pub fn remove_last_run_times(target_dir: &Path) -> Result<(), io::Error> {
+    match fs::remove_file(target_dir.join(JOURNAL_DIR_NAME).join(LAST_RUN_TIMES_FILE_NAME)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Whether the file was modified or created at or after this time. The created time is also checked,
+/// since files copied from a memory card often keep the modified time they had on the card
+pub fn is_changed_since(metadata: &Metadata, time: DateTime<Local>) -> bool {
+    let time = SystemTime::from(time);
+    metadata.modified().is_ok_and(|modified| modified >= time)
+        || metadata.created().is_ok_and(|created| created >= time)
+}
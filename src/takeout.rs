@@ -0,0 +1,197 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{Local, NaiveDateTime, TimeZone};
+use serde::Deserialize;
+
+use crate::exif::{ExifDateDevice, GpsLocation};
+
+/// The format of [ExifDateDevice::capture_time], the same one used for photos
+const CAPTURE_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Newer exports name the metadata files e.g. `IMG_1234.jpg.supplemental-metadata.json`,
+/// while long names are cut short, e.g. `IMG_1234.jpg.supplemental-met.json`
+const SUPPLEMENTAL_METADATA_SUFFIX: &str = "supplemental-metadata";
+
+/// The shortest part of [SUPPLEMENTAL_METADATA_SUFFIX] which is recognized when it's cut short
+const MIN_SUPPLEMENTAL_METADATA_SUFFIX_LEN: usize = 3;
+
+/// Google Photos exports edited photos next to the original, e.g. `IMG_1234-edited.jpg`,
+/// which only has the metadata file of the original
+const EDITED_SUFFIX: &str = "-edited";
+
+/// The date and location of a photo or video exported with Google Takeout, read from the JSON file
+/// which Google Photos writes next to each of them, since the date in the file itself is often missing
+#[derive(Debug, Clone, PartialEq)]
+pub struct TakeoutMetadata {
+    /// The local time when the photo was taken, converted from the UTC time in the JSON file
+    pub taken_time: NaiveDateTime,
+    pub location: Option<GpsLocation>,
+}
+
+impl TakeoutMetadata {
+    /// Use the date and location of the metadata file where the file's own metadata has none
+    pub fn fill_missing(&self, file_data: &mut ExifDateDevice, date_format: &str) {
+        if file_data.date.is_none() {
+            file_data.date = Some(self.taken_time.format(date_format).to_string());
+            file_data.capture_time = Some(self.taken_time.format(CAPTURE_TIME_FORMAT).to_string());
+        }
+        if file_data.location.is_none() {
+            file_data.location = self.location;
+        }
+    }
+}
+
+/// The metadata of all photos and videos which have a Google Takeout JSON file in the same source dir
+#[derive(Clone, Default)]
+pub struct TakeoutIndex {
+    by_media_path: HashMap<PathBuf, TakeoutMetadata>,
+}
+
+impl TakeoutIndex {
+    /// Read the JSON files and match each of them to one of the media files.
+    /// Returns the index and the JSON files which were matched, while the others, e.g. album metadata,
+    /// files which can't be read or files without a matching photo, are left out of both
+    pub fn read(json_files: &[PathBuf], media_files: &HashSet<PathBuf>) -> (TakeoutIndex, Vec<PathBuf>) {
+        let mut by_media_path = HashMap::new();
+        let mut matched_json_files = Vec::new();
+
+        for json_file in json_files {
+            let (title, metadata) = match read_takeout_json(json_file) {
+                Some(takeout_json) => takeout_json,
+                None => continue,
+            };
+            let media_path = find_media_file_names(json_file, title.as_deref())
+                .into_iter()
+                .filter_map(|file_name| json_file.parent().map(|parent| parent.join(file_name)))
+                .find(|media_path| media_files.contains(media_path) && !by_media_path.contains_key(media_path));
+            if let Some(media_path) = media_path {
+                by_media_path.insert(media_path, metadata);
+                matched_json_files.push(json_file.clone());
+            }
+        }
+
+        (TakeoutIndex { by_media_path }, matched_json_files)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_media_path.is_empty()
+    }
+
+    /// Find the metadata of a photo or video, or of its original, if this is an edited copy
+    pub fn get(&self, media_path: &Path) -> Option<&TakeoutMetadata> {
+        self.by_media_path.get(media_path).or_else(|| {
+            let stem = media_path.file_stem()?.to_string_lossy();
+            let original_stem = stem.strip_suffix(EDITED_SUFFIX)?;
+            let original_name = match media_path.extension() {
+                Some(extension) => format!("{}.{}", original_stem, extension.to_string_lossy()),
+                None => original_stem.to_string(),
+            };
+            self.by_media_path.get(&media_path.with_file_name(original_name))
+        })
+    }
+}
+
+/// Only show the number of files, since exports can have thousands of them
+impl fmt::Debug for TakeoutIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TakeoutIndex {{ files: {} }}", self.by_media_path.len())
+    }
+}
+
+pub fn has_json_extension(file_path: &Path) -> bool {
+    file_path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("json"))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TakeoutJson {
+    title: Option<String>,
+    photo_taken_time: Option<TakeoutTime>,
+    geo_data: Option<TakeoutGeoData>,
+    geo_data_exif: Option<TakeoutGeoData>,
+}
+
+#[derive(Deserialize)]
+struct TakeoutTime {
+    /// Seconds since the Unix epoch, as a string
+    timestamp: String,
+}
+
+#[derive(Deserialize)]
+struct TakeoutGeoData {
+    latitude: f64,
+    longitude: f64,
+}
+
+/// Read the title, i.e. the original file name, and the metadata of a photo or video.
+/// Other JSON files, e.g. the metadata of albums, have no `photoTakenTime`
+fn read_takeout_json(json_file: &Path) -> Option<(Option<String>, TakeoutMetadata)> {
+    let contents = fs::read_to_string(json_file).ok()?;
+    let takeout_json: TakeoutJson = serde_json::from_str(&contents).ok()?;
+
+    let timestamp: i64 = takeout_json.photo_taken_time?.timestamp.trim().parse().ok()?;
+    let taken_time = Local.timestamp_opt(timestamp, 0).single()?.naive_local();
+
+    // Both are zero if there's no location, which is already treated as missing
+    let location = takeout_json.geo_data
+        .into_iter()
+        .chain(takeout_json.geo_data_exif)
+        .find_map(|geo_data| GpsLocation::new(geo_data.latitude, geo_data.longitude));
+
+    Some((takeout_json.title, TakeoutMetadata { taken_time, location }))
+}
+
+/// The possible names of the media file of a JSON file, most likely first:
+/// - `IMG_1234.jpg.json` or `IMG_1234.jpg.supplemental-metadata.json` for `IMG_1234.jpg`
+/// - `IMG_1234.jpg(1).json` for `IMG_1234(1).jpg`, the second photo with the same name
+/// - the title in the JSON file, for names which were cut short
+fn find_media_file_names(json_file: &Path, title: Option<&str>) -> Vec<String> {
+    let mut file_names = Vec::new();
+
+    if let Some(stem) = json_file.file_stem().map(|stem| stem.to_string_lossy().to_string()) {
+        let (stem, copy_number) = split_copy_number(&stem);
+        let stem = strip_supplemental_metadata_suffix(stem);
+        match copy_number {
+            Some(copy_number) => {
+                let media_path = Path::new(stem);
+                let media_stem = media_path.file_stem().unwrap_or_default().to_string_lossy();
+                match media_path.extension() {
+                    Some(extension) => file_names.push(format!("{}{}.{}", media_stem, copy_number, extension.to_string_lossy())),
+                    None => file_names.push(format!("{}{}", media_stem, copy_number)),
+                }
+            }
+            None => file_names.push(stem.to_string()),
+        }
+    }
+
+    if let Some(title) = title.filter(|title| !title.is_empty()) {
+        file_names.push(title.to_string());
+    }
+
+    file_names
+}
+
+/// Split a copy number like `(1)` from the end of the name, e.g. `IMG_1234.jpg(1)`
+fn split_copy_number(stem: &str) -> (&str, Option<&str>) {
+    if let Some(start) = stem.rfind('(').filter(|_| stem.ends_with(')')) {
+        let number = &stem[start + 1..stem.len() - 1];
+        if !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()) {
+            return (&stem[..start], Some(&stem[start..]));
+        }
+    }
+    (stem, None)
+}
+
+fn strip_supplemental_metadata_suffix(stem: &str) -> &str {
+    match stem.rsplit_once('.') {
+        Some((media_name, suffix))
+            if suffix.len() >= MIN_SUPPLEMENTAL_METADATA_SUFFIX_LEN && SUPPLEMENTAL_METADATA_SUFFIX.starts_with(suffix) =>
+            media_name,
+        _ => stem,
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use notify::event::ModifyKind;
+use notify::{Event, EventKind};
+
+/// The names of the files in each target folder, which are listed from the disk the first time they're needed,
+/// then kept up to date from the filesystem events of the target folder, see [crate::watch::TargetWatcher].
+/// This way, each batch of the `watch` command only lists the target folders it didn't list before,
+/// when checking which files already exist, instead of listing all of them again
+#[derive(Default)]
+pub struct TargetIndex {
+    // The lowercase names of the files and folders in each listed folder, by its canonical path
+    dirs: Mutex<HashMap<PathBuf, HashSet<String>>>,
+}
+
+impl TargetIndex {
+    pub fn new() -> TargetIndex {
+        TargetIndex::default()
+    }
+
+    /// The lowercase names of the files and folders in the folder, which is only listed the first time,
+    /// or `None` if the folder doesn't exist
+    pub fn file_names(&self, dir: &Path) -> Option<HashSet<String>> {
+        let dir = dir.canonicalize().ok()?;
+        let mut dirs = self.lock();
+        if let Some(file_names) = dirs.get(&dir) {
+            return Some(file_names.clone());
+        }
+
+        let file_names = list_file_names(&dir)?;
+        dirs.insert(dir, file_names.clone());
+        Some(file_names)
+    }
+
+    /// Update the folders which were already listed from a filesystem event: each path of the event is added
+    /// to its folder if it exists, otherwise it's removed from it, which covers new, removed and renamed files alike.
+    /// Everything is listed again if some events were lost
+    pub fn update(&self, event: &Event) {
+        if event.need_rescan() {
+            self.clear();
+            return;
+        }
+
+        // Changing the contents of a file doesn't change the names in its folder
+        if matches!(event.kind, EventKind::Access(_) | EventKind::Modify(ModifyKind::Data(_)) | EventKind::Modify(ModifyKind::Metadata(_))) {
+            return;
+        }
+
+        for path in &event.paths {
+            if path.symlink_metadata().is_ok() {
+                self.add_path(path);
+            } else {
+                self.remove_path(path);
+            }
+        }
+    }
+
+    /// Forget all listed folders, so that they're listed again the next time they're needed
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn add_path(&self, path: &Path) {
+        if let (Some(dir), Some(file_name)) = (path.parent(), path.file_name()) {
+            if let Some(file_names) = self.lock().get_mut(dir) {
+                file_names.insert(file_name.to_string_lossy().to_lowercase());
+            }
+        }
+    }
+
+    /// Remove the path from its folder, together with the listings of the folders inside it, if it was a folder
+    fn remove_path(&self, path: &Path) {
+        let mut dirs = self.lock();
+        if let (Some(dir), Some(file_name)) = (path.parent(), path.file_name()) {
+            if let Some(file_names) = dirs.get_mut(dir) {
+                file_names.remove(&file_name.to_string_lossy().to_lowercase());
+            }
+        }
+        dirs.retain(|dir, _| !dir.starts_with(path));
+    }
+
+    /// The index is only read and updated, so a lock poisoned by a panicking thread can still be used
+    fn lock(&self) -> MutexGuard<'_, HashMap<PathBuf, HashSet<String>>> {
+        self.dirs.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+// The listings can be very long, so only their number is printed, e.g. in the debug output of [crate::config::Args]
+impl fmt::Debug for TargetIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TargetIndex")
+            .field("listed_dirs", &self.lock().len())
+            .finish()
+    }
+}
+
+/// The lowercase names of the files and folders in the folder, or `None` if the folder doesn't exist
+pub fn list_file_names(dir: &Path) -> Option<HashSet<String>> {
+    let entries = fs::read_dir(dir).ok()?;
+    Some(entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().to_lowercase())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    use notify::event::{CreateKind, RemoveKind};
+
+    /// A new temporary target folder, removed at the end of the test
+    struct TargetDir {
+        dir: PathBuf,
+    }
+
+    impl TargetDir {
+        fn new(test_name: &str) -> TargetDir {
+            let dir = env::temp_dir().join(format!("imgsorter-target-index-{}-{}", test_name, std::process::id()));
+            fs::create_dir_all(dir.join("2016.05.04")).unwrap();
+            fs::write(dir.join("2016.05.04").join("IMG_1.jpg"), "photo").unwrap();
+            TargetDir { dir: dir.canonicalize().unwrap() }
+        }
+    }
+
+    impl Drop for TargetDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn names(file_names: &[&str]) -> Option<HashSet<String>> {
+        Some(file_names.iter().map(|file_name| file_name.to_string()).collect())
+    }
+
+    #[test]
+    fn lists_each_folder_only_once() {
+        let target = TargetDir::new("listing");
+        let date_dir = target.dir.join("2016.05.04");
+        let index = TargetIndex::new();
+
+        assert_eq!(index.file_names(&date_dir), names(&["img_1.jpg"]));
+        assert_eq!(index.file_names(&target.dir.join("2016.05.05")), None);
+
+        // Files added without an event are only found once the folder is listed again
+        fs::write(date_dir.join("IMG_2.jpg"), "photo").unwrap();
+        assert_eq!(index.file_names(&date_dir), names(&["img_1.jpg"]));
+        index.clear();
+        assert_eq!(index.file_names(&date_dir), names(&["img_1.jpg", "img_2.jpg"]));
+    }
+
+    #[test]
+    fn updates_the_listed_folders_from_events() {
+        let target = TargetDir::new("events");
+        let date_dir = target.dir.join("2016.05.04");
+        let index = TargetIndex::new();
+        index.file_names(&date_dir);
+
+        let new_path = date_dir.join("IMG_2.jpg");
+        fs::write(&new_path, "photo").unwrap();
+        index.update(&Event::new(EventKind::Create(CreateKind::File)).add_path(new_path.clone()));
+        assert_eq!(index.file_names(&date_dir), names(&["img_1.jpg", "img_2.jpg"]));
+
+        fs::remove_file(&new_path).unwrap();
+        index.update(&Event::new(EventKind::Remove(RemoveKind::File)).add_path(new_path));
+        assert_eq!(index.file_names(&date_dir), names(&["img_1.jpg"]));
+    }
+
+    #[test]
+    fn forgets_the_folders_inside_a_removed_folder() {
+        let target = TargetDir::new("removed-dir");
+        let date_dir = target.dir.join("2016.05.04");
+        let index = TargetIndex::new();
+        index.file_names(&target.dir);
+        index.file_names(&date_dir);
+
+        fs::remove_dir_all(&date_dir).unwrap();
+        index.update(&Event::new(EventKind::Remove(RemoveKind::Folder)).add_path(date_dir.clone()));
+        assert_eq!(index.file_names(&target.dir), names(&[]));
+
+        // A new folder with the same name is listed again, instead of using the listing of the removed one
+        fs::create_dir_all(&date_dir).unwrap();
+        fs::write(date_dir.join("IMG_3.jpg"), "photo").unwrap();
+        assert_eq!(index.file_names(&date_dir), names(&["img_3.jpg"]));
+    }
+}
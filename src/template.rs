@@ -0,0 +1,282 @@
+use chrono::{Datelike, NaiveDate, NaiveDateTime, Timelike};
+
+use crate::config::*;
+use crate::geocoding::Place;
+
+/// The placeholders which can be used in [Args::date_dir_template]
+pub const DATE_DIR_PLACEHOLDERS: &[&str] = &[
+    "{year}", "{month}", "{day}", "{date}", "{month_name}", "{week}", "{week_year}", "{quarter}", "{season}"];
+
+/// The placeholder which can also be used in [Args::date_dir_template] to place the device name
+/// anywhere in the target path, instead of a separate device dir inside the date dir
+pub const DEVICE_PLACEHOLDER: &str = "{device}";
+
+/// The placeholder which can also be used in [Args::date_dir_template] to group photos by the lens
+/// they were taken with, e.g. the main, ultrawide or selfie camera of a phone
+pub const LENS_PLACEHOLDER: &str = "{lens}";
+
+/// The placeholders which can also be used in [Args::date_dir_template] to group photos by where they were taken,
+/// which is looked up in a bundled dataset of cities, see [crate::geocoding::reverse_geocode]
+pub const PLACE_PLACEHOLDERS: &[&str] = &["{city}", "{country}"];
+
+/// The placeholders which can also be used in [Args::date_dir_template] to group images by their size in pixels
+pub const IMAGE_SIZE_PLACEHOLDERS: &[&str] = &["{width}", "{height}", "{mp}"];
+
+/// The placeholders which can be used in [Args::file_name_template]
+pub const FILE_NAME_PLACEHOLDERS: &[&str] = &[
+    "{date}", "{time}", "{year}", "{month}", "{day}", "{device}", "{orig}", "{width}", "{height}", "{mp}"];
+
+/// The placeholders which can be used in [Args::batch_dir_template], which are replaced with the time of the run
+pub const BATCH_DIR_PLACEHOLDERS: &[&str] = &["{today}", "{time}", "{year}", "{month}", "{day}"];
+
+/// Used for the image size placeholders of files whose size in pixels can't be read, e.g. videos
+const UNKNOWN_IMAGE_SIZE: &str = "0";
+
+/// Check that the template is not empty and only contains known placeholders.
+/// Returns a description of the first problem found, if any
+pub fn validate_date_dir_template(template: &str) -> Result<(), String> {
+    if template.trim().is_empty() {
+        return Err(String::from("Value must not be empty"));
+    }
+
+    for placeholder in find_placeholders(template)? {
+        if !DATE_DIR_PLACEHOLDERS.contains(&placeholder)
+            && !IMAGE_SIZE_PLACEHOLDERS.contains(&placeholder)
+            && !PLACE_PLACEHOLDERS.contains(&placeholder)
+            && placeholder != DEVICE_PLACEHOLDER
+            && placeholder != LENS_PLACEHOLDER {
+            return Err(format!("Unknown placeholder '{}', must be one of {}, {}, {}, {}, {}",
+                               placeholder, DATE_DIR_PLACEHOLDERS.join(", "), IMAGE_SIZE_PLACEHOLDERS.join(", "),
+                               PLACE_PLACEHOLDERS.join(", "), DEVICE_PLACEHOLDER, LENS_PLACEHOLDER));
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that the template only contains known placeholders and no path separators,
+/// since it's only used for the name of each file, not for its folder.
+/// Returns a description of the first problem found, if any
+pub fn validate_file_name_template(template: &str) -> Result<(), String> {
+    if template.contains(['/', '\\']) {
+        return Err(String::from("Value must not contain '/' or '\\'"));
+    }
+
+    for placeholder in find_placeholders(template)? {
+        if !FILE_NAME_PLACEHOLDERS.contains(&placeholder) {
+            return Err(format!("Unknown placeholder '{}', must be one of {}",
+                               placeholder, FILE_NAME_PLACEHOLDERS.join(", ")));
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that the template only contains known placeholders.
+/// Returns a description of the first problem found, if any
+pub fn validate_batch_dir_template(template: &str) -> Result<(), String> {
+    for placeholder in find_placeholders(template)? {
+        if !BATCH_DIR_PLACEHOLDERS.contains(&placeholder) {
+            return Err(format!("Unknown placeholder '{}', must be one of {}",
+                               placeholder, BATCH_DIR_PLACEHOLDERS.join(", ")));
+        }
+    }
+
+    Ok(())
+}
+
+/// Find all `{...}` placeholders in the template, or describe the first one which is not closed
+fn find_placeholders(template: &str) -> Result<Vec<&str>, String> {
+    let mut placeholders = Vec::new();
+
+    let mut remaining = template;
+    while let Some(start) = remaining.find('{') {
+        let end = match remaining[start..].find('}') {
+            Some(end) => start + end + 1,
+            None => return Err(format!("Missing '}}' after '{}'", &remaining[start..])),
+        };
+        placeholders.push(&remaining[start..end]);
+        remaining = &remaining[end..];
+    }
+
+    Ok(placeholders)
+}
+
+/// Check if the template uses any of the [IMAGE_SIZE_PLACEHOLDERS], which need the size of each image to be read
+pub fn uses_image_size_placeholders(template: &str) -> bool {
+    IMAGE_SIZE_PLACEHOLDERS.iter().any(|placeholder| template.contains(placeholder))
+}
+
+/// Check if the template uses any of the [PLACE_PLACEHOLDERS], which need the GPS location of each photo to be looked up
+pub fn uses_place_placeholders(template: &str) -> bool {
+    PLACE_PLACEHOLDERS.iter().any(|placeholder| template.contains(placeholder))
+}
+
+/// Check if the template uses the [DEVICE_PLACEHOLDER], in which case files are not placed in separate device dirs
+pub fn uses_device_placeholder(template: &str) -> bool {
+    template.contains(DEVICE_PLACEHOLDER)
+}
+
+/// Build the name of the target date dir for this date, based on the template, which is usually
+/// [Args::date_dir_template] or the override for the file's type, e.g. `2023.05.14` for `{year}.{month}.{day}` or `2023/05 Mai` for `{year}/{month} {month_name}`.
+/// Weeks are numbered according to ISO 8601, so the first days of January may belong to the last
+/// week of the previous year, which is why `{week_year}` should be used together with `{week}`.
+/// The image size placeholders use the width and height of the image in pixels, e.g. `4000x3000`
+/// for `{width}x{height}` or `12.0` for `{mp}` (megapixels, rounded to one decimal).
+/// The device placeholder is replaced last, so that a device name is never read as a placeholder,
+/// and uses [DEFAULT_UNKNOWN_DEVICE_DIR_NAME] for files without a device, e.g. `2023-05-14 - Pixel 5` for `{date} - {device}`.
+/// The same goes for the lens placeholder, e.g. `2023-05-14 - front camera 2.74mm f2.2` for `{date} - {lens}`,
+/// and for the place placeholders, e.g. `2021.08.12 - Lisbon` for `{year}.{month}.{day} - {city}`
+pub fn render_date_dir(
+    template: &str,
+    date: NaiveDate,
+    dimensions: Option<(u32, u32)>,
+    device_name: Option<&str>,
+    lens_name: Option<&str>,
+    place: Place,
+    args: &Args
+) -> String {
+    let (width, height, megapixels) = format_image_size(dimensions);
+
+    let iso_week = date.iso_week();
+    template
+        .replace("{width}", &width)
+        .replace("{height}", &height)
+        .replace("{mp}", &megapixels)
+        .replace("{week_year}", format!("{:04}", iso_week.year()).as_str())
+        .replace("{week}", format!("{:02}", iso_week.week()).as_str())
+        .replace("{quarter}", format!("Q{}", (date.month() - 1) / 3 + 1).as_str())
+        .replace("{season}", season_name(date.month(), &args.season_hemisphere, &args.month_names_locale))
+        .replace("{date}", format!("{:04}-{:02}-{:02}", date.year(), date.month(), date.day()).as_str())
+        .replace("{year}", format!("{:04}", date.year()).as_str())
+        .replace("{month_name}", month_name(date.month(), &args.month_names_locale))
+        .replace("{month}", format!("{:02}", date.month()).as_str())
+        .replace("{day}", format!("{:02}", date.day()).as_str())
+        .replace("{city}", place.city.unwrap_or(DEFAULT_UNKNOWN_DEVICE_DIR_NAME))
+        .replace("{country}", place.country.unwrap_or(DEFAULT_UNKNOWN_DEVICE_DIR_NAME))
+        .replace(LENS_PLACEHOLDER, lens_name.unwrap_or(DEFAULT_UNKNOWN_DEVICE_DIR_NAME))
+        .replace(DEVICE_PLACEHOLDER, device_name.unwrap_or(DEFAULT_UNKNOWN_DEVICE_DIR_NAME))
+}
+
+/// Build the new name of a file, without its extension, based on [Args::file_name_template],
+/// e.g. `2023-05-14_103000_Pixel 5_IMG_0001` for `{date}_{time}_{device}_{orig}`.
+/// The time is the time the photo was taken, while `{orig}` is the original name without its extension.
+/// The image size placeholders are replaced like in [render_date_dir], e.g. `IMG_0001_4000x3000` for `{orig}_{width}x{height}`.
+/// Like in [render_date_dir], the device is replaced last and [DEFAULT_UNKNOWN_DEVICE_DIR_NAME]
+/// is used for files without a device, while path separators in device names are replaced with `-`
+pub fn render_file_name(
+    template: &str,
+    taken_time: NaiveDateTime,
+    dimensions: Option<(u32, u32)>,
+    device_name: Option<&str>,
+    original_stem: &str,
+) -> String {
+    let date = taken_time.date();
+    let device_name = device_name
+        .unwrap_or(DEFAULT_UNKNOWN_DEVICE_DIR_NAME)
+        .replace(['/', '\\'], "-");
+    let (width, height, megapixels) = format_image_size(dimensions);
+
+    template
+        .replace("{width}", &width)
+        .replace("{height}", &height)
+        .replace("{mp}", &megapixels)
+        .replace("{date}", format!("{:04}-{:02}-{:02}", date.year(), date.month(), date.day()).as_str())
+        .replace("{time}", format!("{:02}{:02}{:02}", taken_time.hour(), taken_time.minute(), taken_time.second()).as_str())
+        .replace("{year}", format!("{:04}", date.year()).as_str())
+        .replace("{month}", format!("{:02}", date.month()).as_str())
+        .replace("{day}", format!("{:02}", date.day()).as_str())
+        .replace("{orig}", original_stem)
+        .replace(DEVICE_PLACEHOLDER, &device_name)
+}
+
+/// The width and height in pixels and the number of megapixels, rounded to one decimal,
+/// or [UNKNOWN_IMAGE_SIZE] for all of them if the size of the image is not known
+fn format_image_size(dimensions: Option<(u32, u32)>) -> (String, String, String) {
+    match dimensions {
+        Some((width, height)) => (
+            width.to_string(),
+            height.to_string(),
+            format!("{:.1}", (width as f64 * height as f64) / 1_000_000.0)),
+        None => (UNKNOWN_IMAGE_SIZE.to_string(), UNKNOWN_IMAGE_SIZE.to_string(), UNKNOWN_IMAGE_SIZE.to_string()),
+    }
+}
+
+/// Build the name of the batch dir of a run based on [Args::batch_dir_template] and the time the run started,
+/// e.g. `import 2023-05-14` for `import {today}` or `2023-05-14 103000` for `{today} {time}`
+pub fn render_batch_dir(template: &str, run_time: NaiveDateTime) -> String {
+    let date = run_time.date();
+    template
+        .replace("{today}", format!("{:04}-{:02}-{:02}", date.year(), date.month(), date.day()).as_str())
+        .replace("{time}", format!("{:02}{:02}{:02}", run_time.hour(), run_time.minute(), run_time.second()).as_str())
+        .replace("{year}", format!("{:04}", date.year()).as_str())
+        .replace("{month}", format!("{:02}", date.month()).as_str())
+        .replace("{day}", format!("{:02}", date.day()).as_str())
+}
+
+/// Place the date dir inside the year and month dirs of its date, according to [Args::date_dir_layout],
+/// e.g. `2021/2021.05/2021.05.14` for `2021.05.14` with [DateDirLayout::YearMonth]
+pub fn nest_date_dir(date_dir: String, date: NaiveDate, layout: &DateDirLayout) -> String {
+    match layout {
+        DateDirLayout::Flat => date_dir,
+        DateDirLayout::Year => format!("{:04}/{}", date.year(), date_dir),
+        DateDirLayout::YearMonth => format!("{:04}/{:04}.{:02}/{}", date.year(), date.year(), date.month(), date_dir),
+    }
+}
+
+/// The name of the season of the month (1 to 12) in the configured language.
+/// Seasons start on the first day of a month (i.e. meteorological seasons), so for the
+/// northern hemisphere, winter is December to February, spring is March to May and so on.
+/// The seasons of the southern hemisphere are the opposite
+pub fn season_name(month: u32, hemisphere: &Hemisphere, locale: &MonthNameLocale) -> &'static str {
+    // Winter, spring, summer, autumn
+    let season_names: [&str; 4] = match locale {
+        MonthNameLocale::English => ["Winter", "Spring", "Summer", "Autumn"],
+        MonthNameLocale::German => ["Winter", "Frühling", "Sommer", "Herbst"],
+        MonthNameLocale::French => ["Hiver", "Printemps", "Été", "Automne"],
+        MonthNameLocale::Spanish => ["Invierno", "Primavera", "Verano", "Otoño"],
+        MonthNameLocale::Italian => ["Inverno", "Primavera", "Estate", "Autunno"],
+        MonthNameLocale::Portuguese => ["Inverno", "Primavera", "Verão", "Outono"],
+        MonthNameLocale::Dutch => ["Winter", "Lente", "Zomer", "Herfst"],
+        MonthNameLocale::Romanian => ["Iarnă", "Primăvară", "Vară", "Toamnă"],
+    };
+
+    // December is 0, so that each season covers 3 consecutive values
+    let northern_season = (month % 12) / 3;
+    let season = match hemisphere {
+        Hemisphere::Northern => northern_season,
+        Hemisphere::Southern => (northern_season + 2) % 4,
+    };
+
+    season_names
+        .get(season as usize)
+        .copied()
+        .unwrap_or("")
+}
+
+/// The name of the month (1 to 12) in the configured language
+pub fn month_name(month: u32, locale: &MonthNameLocale) -> &'static str {
+    let month_names: [&str; 12] = match locale {
+        MonthNameLocale::English => ["January", "February", "March", "April", "May", "June",
+            "July", "August", "September", "October", "November", "December"],
+        MonthNameLocale::German => ["Januar", "Februar", "März", "April", "Mai", "Juni",
+            "Juli", "August", "September", "Oktober", "November", "Dezember"],
+        MonthNameLocale::French => ["Janvier", "Février", "Mars", "Avril", "Mai", "Juin",
+            "Juillet", "Août", "Septembre", "Octobre", "Novembre", "Décembre"],
+        MonthNameLocale::Spanish => ["Enero", "Febrero", "Marzo", "Abril", "Mayo", "Junio",
+            "Julio", "Agosto", "Septiembre", "Octubre", "Noviembre", "Diciembre"],
+        MonthNameLocale::Italian => ["Gennaio", "Febbraio", "Marzo", "Aprile", "Maggio", "Giugno",
+            "Luglio", "Agosto", "Settembre", "Ottobre", "Novembre", "Dicembre"],
+        MonthNameLocale::Portuguese => ["Janeiro", "Fevereiro", "Março", "Abril", "Maio", "Junho",
+            "Julho", "Agosto", "Setembro", "Outubro", "Novembro", "Dezembro"],
+        MonthNameLocale::Dutch => ["Januari", "Februari", "Maart", "April", "Mei", "Juni",
+            "Juli", "Augustus", "September", "Oktober", "November", "December"],
+        MonthNameLocale::Romanian => ["Ianuarie", "Februarie", "Martie", "Aprilie", "Mai", "Iunie",
+            "Iulie", "August", "Septembrie", "Octombrie", "Noiembrie", "Decembrie"],
+    };
+
+    month_names
+        .get(month.saturating_sub(1) as usize)
+        .copied()
+        .unwrap_or("")
+}
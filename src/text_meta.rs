@@ -0,0 +1,226 @@
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+
+/// Text chunks are small, anything larger than this is not worth reading for a date
+const MAX_TEXT_CHUNK_LEN: u32 = 1024 * 1024;
+
+/// XMP properties with the date the image was created, in order of preference
+const XMP_DATE_PROPERTIES: [&str; 3] = ["exif:DateTimeOriginal", "photoshop:DateCreated", "xmp:CreateDate"];
+
+/// Read the date an image was created from the text metadata of PNG and WebP files, for files without EXIF dates.
+/// Screenshots and images exported from editing apps often have one of these:
+/// - the `Creation Time` keyword of the PNG `tEXt` or `iTXt` chunks
+/// - the XMP metadata, which is stored in a PNG `iTXt` chunk or in the WebP `XMP ` chunk
+///
+/// Compressed PNG text chunks are not read
+pub fn read_text_metadata_date(file_path: &Path) -> Option<NaiveDateTime> {
+    let mut reader = BufReader::new(File::open(file_path).ok()?);
+    let mut signature = [0_u8; 12];
+    reader.read_exact(&mut signature).ok()?;
+
+    if signature.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+        reader.seek(SeekFrom::Start(8)).ok()?;
+        read_png_text_date(&mut reader)
+    } else if signature.starts_with(b"RIFF") && &signature[8..12] == b"WEBP" {
+        read_webp_xmp_date(&mut reader)
+    } else {
+        None
+    }
+}
+
+/// Each PNG chunk has a length (4), a type (4), the data and a CRC (4).
+/// Text chunks can be placed after the image data as well, so all chunks are checked
+fn read_png_text_date<R: Read + Seek>(reader: &mut R) -> Option<NaiveDateTime> {
+    let mut creation_time = None;
+    let mut xmp_date = None;
+
+    loop {
+        let mut header = [0_u8; 8];
+        reader.read_exact(&mut header).ok()?;
+        let chunk_len = u32::from_be_bytes(header[..4].try_into().ok()?);
+        let chunk_type = &header[4..8];
+
+        if chunk_type == b"IEND" {
+            break;
+        }
+        if (chunk_type == b"tEXt" || chunk_type == b"iTXt") && chunk_len <= MAX_TEXT_CHUNK_LEN {
+            let mut data = vec![0_u8; chunk_len as usize];
+            reader.read_exact(&mut data).ok()?;
+            reader.seek(SeekFrom::Current(4)).ok()?;
+
+            match read_png_text_chunk(chunk_type, &data) {
+                Some(("Creation Time", text)) => creation_time = creation_time.or_else(|| parse_text_date(&text)),
+                Some(("XML:com.adobe.xmp", text)) => xmp_date = xmp_date.or_else(|| find_xmp_date(&text)),
+                _ => (),
+            }
+        } else {
+            reader.seek(SeekFrom::Current(chunk_len as i64 + 4)).ok()?;
+        }
+    }
+
+    // XMP dates are written by editing apps which preserve the original date
+    xmp_date.or(creation_time)
+}
+
+/// Split a `tEXt` or `iTXt` chunk into its keyword and text. Returns None for compressed text.
+/// `tEXt`: keyword, null, text
+/// `iTXt`: keyword, null, compression flag (1), compression method (1), language, null, translated keyword, null, text
+fn read_png_text_chunk<'a>(chunk_type: &[u8], data: &'a [u8]) -> Option<(&'a str, String)> {
+    let keyword_end = data.iter().position(|byte| *byte == 0)?;
+    let keyword = std::str::from_utf8(&data[..keyword_end]).ok()?;
+    let rest = &data[keyword_end + 1..];
+
+    let text = if chunk_type == b"iTXt" {
+        if *rest.first()? != 0 {
+            return None;
+        }
+        let language_end = 2 + rest.get(2..)?.iter().position(|byte| *byte == 0)?;
+        let translated_keyword_end = language_end + 1 + rest.get(language_end + 1..)?.iter().position(|byte| *byte == 0)?;
+        String::from_utf8_lossy(rest.get(translated_keyword_end + 1..)?).to_string()
+    } else {
+        // tEXt chunks are Latin-1, which is the same as UTF-8 for the characters of a date
+        String::from_utf8_lossy(rest).to_string()
+    };
+
+    Some((keyword, text))
+}
+
+/// Each RIFF chunk has a type (4), a length (4, little-endian) and the data, padded to an even length
+fn read_webp_xmp_date<R: Read + Seek>(reader: &mut R) -> Option<NaiveDateTime> {
+    loop {
+        let mut header = [0_u8; 8];
+        reader.read_exact(&mut header).ok()?;
+        let chunk_len = u32::from_le_bytes(header[4..8].try_into().ok()?);
+
+        if &header[..4] == b"XMP " && chunk_len <= MAX_TEXT_CHUNK_LEN {
+            let mut data = vec![0_u8; chunk_len as usize];
+            reader.read_exact(&mut data).ok()?;
+            return find_xmp_date(&String::from_utf8_lossy(&data));
+        }
+        reader.seek(SeekFrom::Current(chunk_len as i64 + (chunk_len % 2) as i64)).ok()?;
+    }
+}
+
+/// Find the first date property in the XMP metadata, which can be written either
+/// as an attribute, e.g. `exif:DateTimeOriginal="2019-01-28T14:03:10"`,
+/// or as an element, e.g. `<exif:DateTimeOriginal>2019-01-28T14:03:10</exif:DateTimeOriginal>`
+fn find_xmp_date(xmp: &str) -> Option<NaiveDateTime> {
+    XMP_DATE_PROPERTIES.iter().find_map(|property| {
+        let value_start = [format!("{}=\"", property), format!("{}='", property), format!("<{}>", property)]
+            .iter()
+            .find_map(|prefix| xmp.find(prefix.as_str()).map(|position| position + prefix.len()))?;
+        let value = &xmp[value_start..];
+        let value_end = value.find(['"', '\'', '<']).unwrap_or(value.len());
+        parse_text_date(&value[..value_end])
+    })
+}
+
+/// Text dates are written in many formats, e.g.:
+/// - `2019-01-28T14:03:10+02:00` or `2019-01-28T14:03:10.123` (XMP, ISO 8601)
+/// - `Mon, 28 Jan 2019 14:03:10 +0200` (the recommended format of the PNG `Creation Time`)
+/// - `2019:01:28 14:03:10` (EXIF)
+///
+/// The local time is used, without converting from the time zone, like the EXIF dates of photos
+fn parse_text_date(text: &str) -> Option<NaiveDateTime> {
+    let text = text.trim();
+
+    if let Ok(date_time) = DateTime::parse_from_rfc2822(text) {
+        return Some(date_time.naive_local());
+    }
+
+    // Only the date and time are read, ignoring fractions of a second and time zones
+    let date_time_part = text.get(..19).unwrap_or(text);
+    ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y:%m:%d %H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(date_time_part, format).ok())
+        // XMP dates can be only a date, e.g. `2019-01-28`
+        .or_else(|| NaiveDate::parse_from_str(text.get(..10)?, "%Y-%m-%d").ok().map(|date| date.and_hms(0, 0, 0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn date_time(year: i32, month: u32, day: u32, hour: u32, min: u32, sec: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd(year, month, day).and_hms(hour, min, sec)
+    }
+
+    /// A PNG chunk with a dummy CRC, which is not checked
+    fn png_chunk(chunk_type: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(chunk_type);
+        chunk.extend_from_slice(data);
+        chunk.extend_from_slice(&[0; 4]);
+        chunk
+    }
+
+    fn itxt_data(keyword: &str, is_compressed: bool, text: &[u8]) -> Vec<u8> {
+        let mut data = keyword.as_bytes().to_vec();
+        // Null after the keyword, compression flag and method, then the empty language and translated keyword
+        data.extend_from_slice(&[0, is_compressed as u8, 0, 0, 0]);
+        data.extend_from_slice(text);
+        data
+    }
+
+    fn webp_chunk(chunk_type: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut chunk = chunk_type.to_vec();
+        chunk.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    #[test]
+    fn parses_rfc_2822_and_iso_dates() {
+        assert_eq!(parse_text_date("Mon, 28 Jan 2019 14:03:10 +0200"), Some(date_time(2019, 1, 28, 14, 3, 10)));
+        assert_eq!(parse_text_date("2019-01-28T14:03:10+02:00"), Some(date_time(2019, 1, 28, 14, 3, 10)));
+        assert_eq!(parse_text_date("2019-01-28T14:03:10.123"), Some(date_time(2019, 1, 28, 14, 3, 10)));
+        assert_eq!(parse_text_date("2019:01:28 14:03:10"), Some(date_time(2019, 1, 28, 14, 3, 10)));
+        assert_eq!(parse_text_date(" 2019-01-28 "), Some(date_time(2019, 1, 28, 0, 0, 0)));
+        assert_eq!(parse_text_date("yesterday"), None);
+    }
+
+    #[test]
+    fn finds_xmp_dates_in_attributes_and_elements() {
+        assert_eq!(find_xmp_date(r#"<rdf:Description exif:DateTimeOriginal="2019-01-28T14:03:10"/>"#),
+                   Some(date_time(2019, 1, 28, 14, 3, 10)));
+        assert_eq!(find_xmp_date("<xmp:CreateDate>2020-05-05</xmp:CreateDate>"), Some(date_time(2020, 5, 5, 0, 0, 0)));
+        assert_eq!(find_xmp_date("<xmp:ModifyDate>2020-05-05</xmp:ModifyDate>"), None);
+    }
+
+    #[test]
+    fn skips_compressed_png_text_chunks() {
+        let mut png = png_chunk(b"IHDR", &[0; 13]);
+        png.extend(png_chunk(b"iTXt", &itxt_data("Creation Time", true, b"\x78\x9c\x03\x00")));
+        png.extend(png_chunk(b"tEXt", b"Creation Time\0Mon, 28 Jan 2019 14:03:10 +0200"));
+        png.extend(png_chunk(b"IEND", &[]));
+
+        assert_eq!(read_png_text_date(&mut Cursor::new(png)), Some(date_time(2019, 1, 28, 14, 3, 10)));
+    }
+
+    #[test]
+    fn prefers_the_png_xmp_date() {
+        let mut png = png_chunk(b"tEXt", b"Creation Time\x002021-03-04 10:11:12");
+        png.extend(png_chunk(b"iTXt", &itxt_data("XML:com.adobe.xmp", false,
+                                                 b"<photoshop:DateCreated>2019-01-28T14:03:10</photoshop:DateCreated>")));
+        png.extend(png_chunk(b"IEND", &[]));
+
+        assert_eq!(read_png_text_date(&mut Cursor::new(png)), Some(date_time(2019, 1, 28, 14, 3, 10)));
+    }
+
+    #[test]
+    fn skips_the_padding_of_odd_length_riff_chunks() {
+        let mut webp = webp_chunk(b"VP8X", &[0; 9]);
+        webp.extend(webp_chunk(b"XMP ", br#"exif:DateTimeOriginal='2019-01-28T14:03:10'"#));
+
+        assert_eq!(read_webp_xmp_date(&mut Cursor::new(webp)), Some(date_time(2019, 1, 28, 14, 3, 10)));
+    }
+}
@@ -0,0 +1,79 @@
+use std::env;
+use std::path::Path;
+
+use exif::{In, Tag};
+
+use crate::exif::read_kamadak_exif;
+
+/// The height of each thumbnail in terminal rows, with the width following the aspect ratio
+const THUMBNAIL_ROWS: u32 = 5;
+
+/// The most thumbnails shown in a single list, since each one takes [THUMBNAIL_ROWS] rows
+/// and listing a large library would otherwise flood the terminal
+pub const MAX_LISTED_THUMBNAILS: usize = 50;
+
+/// Terminals which show images written with the inline images protocol of iTerm2, by their `TERM_PROGRAM`
+const INLINE_IMAGE_TERMINALS: &[&str] = &["iTerm.app", "WezTerm", "mintty"];
+
+const BASE64_CHARS: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Read the small JPEG preview which most cameras and phones embed in the EXIF data of their photos,
+/// without reading the photo itself
+pub fn read_exif_thumbnail(file_path: &Path) -> Option<Vec<u8>> {
+    let exif = read_kamadak_exif(file_path).ok()?;
+    let offset = exif.get_field(Tag::JPEGInterchangeFormat, In::THUMBNAIL)?.value.get_uint(0)? as usize;
+    let length = exif.get_field(Tag::JPEGInterchangeFormatLength, In::THUMBNAIL)?.value.get_uint(0)? as usize;
+    exif.buf()
+        .get(offset..offset.checked_add(length)?)
+        .filter(|thumbnail| thumbnail.starts_with(&[0xFF, 0xD8]))
+        .map(|thumbnail| thumbnail.to_vec())
+}
+
+/// Whether the terminal can show images inline, see [INLINE_IMAGE_TERMINALS].
+/// iTerm2 also sets `LC_TERMINAL`, which is passed on through SSH
+pub fn supports_inline_images() -> bool {
+    let term_program = env::var("TERM_PROGRAM").unwrap_or_default();
+    INLINE_IMAGE_TERMINALS.contains(&term_program.as_str())
+        || env::var("LC_TERMINAL").is_ok_and(|terminal| terminal == "iTerm2")
+}
+
+/// The escape sequence which shows a JPEG image inline, [THUMBNAIL_ROWS] high,
+/// using the inline images protocol of iTerm2, see https://iterm2.com/documentation-images.html
+pub fn format_inline_image(jpeg: &[u8]) -> String {
+    format!("\x1b]1337;File=inline=1;size={};height={};preserveAspectRatio=1:{}\x07",
+            jpeg.len(), THUMBNAIL_ROWS, encode_base64(jpeg))
+}
+
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk
+            .iter()
+            .enumerate()
+            .fold(0_u32, |group, (index, byte)| group | (*byte as u32) << (16 - index * 8));
+        // Each group of 3 bytes is written as 4 characters, padded with `=` for the missing bytes
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(BASE64_CHARS[(group >> (18 - index * 6)) as usize & 0x3F] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_base64_with_padding() {
+        assert_eq!(encode_base64(b""), "");
+        assert_eq!(encode_base64(b"M"), "TQ==");
+        assert_eq!(encode_base64(b"Ma"), "TWE=");
+        assert_eq!(encode_base64(b"Man"), "TWFu");
+        assert_eq!(encode_base64(b"Many"), "TWFueQ==");
+        assert_eq!(encode_base64(&[0xFF, 0xD8, 0xFF, 0xE0, 0x00]), "/9j/4AA=");
+    }
+}
@@ -1,10 +1,7 @@
-use std::cmp::max;
 use std::collections::HashSet;
 use std::ffi::OsString;
 use std::io::Write;
 
-use crate::config::*;
-
 pub struct ColoredString;
 
 /// Provides static methods for formatting colored text based on ANSI codes
@@ -43,374 +40,6 @@ pub enum OutputColor {
     Neutral,
     Good,
 }
-/// Struct responsible to store length sizes and format strings to produce
-/// pretty aligned strings when printing result of operations
-/// Sample output for dry-runs
-/// ```
-/// ---------------------------------------------------------------------------------
-/// TARGET FILE                     SOURCE PATH                  OPERATION STATUS    
-/// ---------------------------------------------------------------------------------
-/// [2019.01.28] (2 devices, 3 files, 3.34 MB) ................. [new folder will be created]
-///  ├── [Canon 100D] .......................................... [new folder will be created]
-///  │    ├── IMG-20190128.jpg <--- D:\Pics\IMG-20190128.jpg ... target file exists, will be skipped
-///  │    └── IMG-20190129.jpg <--- D:\Pics\IMG-20190129.jpg ... file will be copied
-///  └── IMG-20190127.jpg <-------- D:\Pics\IMG-20190127.jpg ... file will be copied
-/// ```
-/// Sample output for copy/move operations
-/// ```
-/// ──────────────────────────────────────────────────────────────────────────────────────────
-/// SOURCE PATH                   TARGET FILE                                OPERATION STATUS
-/// ──────────────────────────────────────────────────────────────────────────────────────────
-/// [Created folder 2019.01.28]
-/// D:\Pics\IMG-20190127.jpg ───> 2019.01.28\IMG-20190127.jpg .............. ok
-/// D:\Pics\IMG-20190128.jpg ───> 2019.01.28\Canon 100D\IMG-20190128.jpg ... already exists
-/// D:\Pics\IMG-20190129.jpg ───> 2019.01.28\Canon 100D\IMG-20190129.jpg ... ok
-/// ```
-pub struct Padder {
-    /// Whether there's a single source directory or multiple
-    /// This matters when outputting source paths - for single sources we'd only
-    /// need to output the filname, since the full path will always be the same
-    has_multiple_sources: bool,
-
-    /// The maximum length of filename of all source files,
-    /// without any path information, e.g. `IMG-20190128.jpg`
-    pub source_base_file_max_len: usize,
-
-    /// The maximum length of the absolute path length of all source files,
-    /// including the file name, e.g. `D:\Pics\IMG-20190128.jpg`
-    pub source_path_max_len: usize,
-
-    /// This maximum length of the relative target path from the parent target dir
-    /// This *does not* include the filename length, which can always be read
-    ///   from [source_base_file_max_len] (and adding 1 for the separator char)
-    /// So this will include either the "date\device name", or just the "date",
-    ///   e.g. `2019.01.28\Canon 100` or just `2019.01.28`
-    pub target_relative_path_max_len: usize,
-
-    // Length of any additional dir tree symbols which are prepended to the target file
-    pub extra_source_chars: usize,
-}
-
-impl Padder {
-    pub fn new(has_multiple_sources: bool) -> Padder {
-        Padder {
-            has_multiple_sources,
-            source_base_file_max_len: 0,
-            source_path_max_len: 0,
-            target_relative_path_max_len: 0,
-            extra_source_chars: 0,
-        }
-    }
-
-    /* --- Setter methods --- */
-
-    pub fn set_max_source_filename(&mut self, new_file_len: usize) {
-        self.source_base_file_max_len = max(self.source_base_file_max_len, new_file_len)
-    }
-
-    pub fn set_max_source_path(&mut self, new_path_len: usize) {
-        self.source_path_max_len = max(self.source_path_max_len, new_path_len)
-    }
-
-    pub fn set_max_target_path(&mut self, new_path_len: usize) {
-        self.target_relative_path_max_len = max(self.target_relative_path_max_len, new_path_len)
-    }
-
-    pub fn add_extra_source_chars(&mut self, new_len: usize) {
-        self.extra_source_chars += new_len
-    }
-
-    pub fn set_max_source_filename_from_str(&mut self, new_file_name: &str) {
-        self.set_max_source_filename(get_string_char_count(String::from(new_file_name)));
-    }
-
-    pub fn set_max_source_path_from_str(&mut self, new_path: &str) {
-        self.set_max_source_path(get_string_char_count(String::from(new_path)));
-    }
-
-    pub fn add_extra_source_chars_from_str(&mut self, extra: &str) {
-        self.add_extra_source_chars(get_string_char_count(String::from(extra)));
-    }
-
-    /* --- Getter methods - compute various lengths --- */
-
-    fn get_source_len(&self) -> usize {
-        if self.has_multiple_sources {
-            self.source_path_max_len
-        } else {
-            self.source_base_file_max_len
-        }
-    }
-
-    /// Calculates the max target length, which is composed of
-    /// any file tree symbols plus the base filename
-    /// [2019.01.28]
-    //   └── IMG-20190127.jpg
-    fn get_dryrun_max_target_len(&self) -> usize {
-        // max target length is composed of any file tree symbols plus the base filename
-        self.source_base_file_max_len + self.extra_source_chars
-    }
-
-    /// This calculates the max target path length, which is composed of
-    /// the relative target path plus the base filename
-    fn get_write_max_target_len(&self) -> usize {
-        // add +1 for the separator between the path and the filename
-        self.target_relative_path_max_len + 1 + self.source_base_file_max_len
-    }
-
-    // TODO 5j: cache the result of these get functions, don't calculate it each time
-
-    fn get_dryrun_total_padding_len(&self) -> usize {
-        // TODO 5j: this is the same as
-        //   get_dryrun_target_header_padding_len + 1 + get_dryrun_source_header_padding_len
-        self.get_dryrun_max_target_len()
-            + 1 // add +1 for the gap between the target filename and the operation separator
-            + SEPARATOR_DRY_RUN_LEFT_TO_RIGHT.chars().count()
-            + 1 // add +1 for the gap between the operation separator and the source file/path
-            + self.get_source_len()
-            + 1 // add +1 for the gap between the source file/path and the operation status
-            + SEPARATOR_OP_STATUS.chars().count()
-    }
-
-    fn get_dryrun_target_header_padding_len(&self) -> usize {
-        self.get_dryrun_max_target_len()
-            + 1 // add +1 for the gap between the target filename and the operation separator
-            + SEPARATOR_DRY_RUN_LEFT_TO_RIGHT.chars().count()
-    }
-
-    fn get_dryrun_source_header_padding_len(&self) -> usize {
-        self.get_source_len()
-            + 1 // add +1 for the gap between the source path and the status separator
-            + SEPARATOR_OP_STATUS.chars().count()
-    }
-
-    fn get_write_total_padding_len(&self) -> usize {
-        // TODO 5j: this is the same as
-        // get_write_target_header_padding_len + 1 + get_write_source_header_padding_len
-        self.get_source_len()
-            + 1 // add +1 for the gap between the source path and the operation separator
-            + SEPARATOR_COPY_MOVE.chars().count()
-            + 1 // add +1 for the gap between the operation separator and the target path
-            + self.get_write_max_target_len()
-            + 1 // add +1 for the gap between the target path and the operation status
-            + SEPARATOR_OP_STATUS.chars().count()
-    }
-
-    fn get_write_target_header_padding_len(&self) -> usize {
-        self.get_write_max_target_len()
-            + 1 // add +1 for the gap between the target path and the operation status
-            + SEPARATOR_OP_STATUS.chars().count()
-    }
-
-    fn get_write_source_header_padding_len(&self) -> usize {
-        self.get_source_len()
-            + 1 // add +1 for the gap between the source path and the operation separator
-            + SEPARATOR_COPY_MOVE.chars().count()
-    }
-
-    /// This separator should fill the space between the current filename and the
-    /// maximum target filename length (including the dir tree symbols in both cases)
-    /// The calculation is based assuming the target file is printed to the left of the separator
-    fn get_dryrun_file_separator_padding_len(&self, indented_target_filename: String) -> usize {
-        let indented_target_filename_length = get_string_char_count(indented_target_filename);
-
-        let max_target_len =
-            self.get_dryrun_max_target_len() + SEPARATOR_DRY_RUN_LEFT_TO_RIGHT.chars().count();
-
-        if max_target_len > indented_target_filename_length {
-            max_target_len - indented_target_filename_length
-        } else {
-            // if for some reason max_target_len is less than indented_target_filename_length,
-            // just return the minimum length for a separator
-            SEPARATOR_DRY_RUN_LEFT_TO_RIGHT.chars().count()
-        }
-    }
-
-    /// This should fill the space between the current filename and the maximum source filename length.
-    /// The calculation assumes the source path is printed to the left of the separator
-    fn get_write_file_separator_padding_len(&self, source_path: String) -> usize {
-        let source_path_length = get_string_char_count(source_path);
-        self.get_source_len()
-            + SEPARATOR_COPY_MOVE.chars().count()
-            - source_path_length
-    }
-
-    /// This should fill the space between the source path and the estimated result of the operation
-    fn get_dryrun_status_separator_padding_len(&self, source_path: String) -> usize {
-        let source_path_length = get_string_char_count(source_path);
-        self.get_source_len()
-            + SEPARATOR_OP_STATUS.chars().count()
-            - source_path_length
-    }
-
-    /// This should fill the space between the target path and the result of the operation
-    fn get_write_status_separator_padding_len(&self, target_path: String) -> usize {
-        let target_path_length = get_string_char_count(target_path);
-        self.get_write_max_target_len()
-            + SEPARATOR_OP_STATUS.chars().count()
-            - target_path_length
-    }
-
-    /* --- Formatter methods - produce padded strings for printing --- */
-
-    pub fn format_dryrun_header_separator(&self, status_width: usize) -> String {
-        // this is an en-dash, not a dash
-        "–".repeat(
-            self.get_dryrun_total_padding_len()
-            +1 // add +1 for the gap between the status separator and the status
-            + status_width,
-        )
-    }
-
-    pub fn format_write_header_separator(&self, status_width: usize) -> String {
-        // this is an em-dash, not a dash
-        "─".repeat(
-            self.get_write_total_padding_len()
-                + 1 // add +1 for the gap between the status separator and the status
-                + status_width
-        )
-    }
-
-    pub fn format_dryrun_header(&self, status_width: usize) -> String {
-        let padded_target = RightPadding::space(
-            String::from("TARGET FILE"),
-            self.get_dryrun_target_header_padding_len());
-
-        let padded_source = RightPadding::space(
-            String::from("SOURCE PATH"),
-            self.get_dryrun_source_header_padding_len());
-
-        let padded_status= RightPadding::space(
-            String::from("OPERATION STATUS"),
-            status_width);
-
-        format!("{} {} {}", padded_target, padded_source, padded_status)
-    }
-
-    pub fn format_write_header(&self, status_width: usize) -> String {
-        let padded_source = RightPadding::space(
-            String::from("SOURCE PATH"),
-            self.get_write_source_header_padding_len());
-
-        let padded_target = RightPadding::space(
-            String::from("TARGET FILE"),
-            self.get_write_target_header_padding_len());
-
-        let padded_status = RightPadding::space(
-            String::from("OPERATION STATUS"),
-            status_width);
-
-        format!("{} {} {}", padded_source, padded_target, padded_status)
-    }
-
-    /// Adds dot padding to the maximum padding length for the date dir, e.g.:
-    /// `[2019.01.28] (2 devices, 3 files, 3.34 MB) .................`
-    pub fn format_dryrun_date_dir(
-        &self,
-        date_dir_name_with_device_status: String,
-        args: &Args,
-    ) -> String {
-        if args.align_file_output {
-            RightPadding::dot(
-                date_dir_name_with_device_status,
-                self.get_dryrun_total_padding_len())
-        } else {
-            format!("{} {} ",
-                date_dir_name_with_device_status, SEPARATOR_OP_STATUS)
-        }
-    }
-
-    /// Adds dot padding to the maximum padding length for the device dir.
-    /// The device dirs will always have a single dir tree symbol prefix,
-    /// since we don't expect additional sublevels for the devices, e.g.:
-    /// `└── [Canon 100D] ..............................`
-    pub fn format_dryrun_device_dir(
-        &self,
-        device_dir_name: String,
-        is_last_dir: bool,
-        is_last_elem: bool,
-        args: &Args,
-    ) -> String {
-        let indented_device_dir_name: String = indent_string(
-            // There are no indent levels for device dirs, just add
-            0, format!("[{}] ", device_dir_name), is_last_dir, is_last_elem);
-
-        if args.align_file_output {
-            RightPadding::dot(
-                indented_device_dir_name,
-                // safe to unwrap for dry runs
-                self.get_dryrun_total_padding_len())
-        } else {
-            format!("{} {}", indented_device_dir_name, SEPARATOR_OP_STATUS)
-        }
-    }
-
-    /// Adds space padding to the maximum padding length for the snipping output.
-    /// ```
-    /// ├── IMG-20190128.jpg <--- D:\Pics\IMG-20190128.jpg ... target file exists, will be skipped
-    /// ·-- (snipped output for 2 files with same status)
-    /// └── IMG-20190129.jpg <--- D:\Pics\IMG-20190129.jpg ... file will be copied
-    /// ```
-    pub fn format_dryrun_snipped_output(
-        &self,
-        skip_count: usize,
-        indent_level: usize,
-        is_last_dir: bool,
-    ) -> String {
-        let snip_text = ColoredString::italic_dim(
-            format!("(snipped output for {} files with same status)", skip_count).as_str());
-
-        indent_string_snipped(indent_level, snip_text, is_last_dir)
-    }
-
-    pub fn format_dryrun_file_separator(&self, left_file: String, args: &Args) -> String {
-        if args.align_file_output {
-            let padded_separator = RightPadding::dash(
-                // Add a space to the left so there's a gap between the previous file and the separator
-                format!(" {}", SEPARATOR_DRY_RUN_RIGHT_TO_LEFT),
-                // add +1 for the space added before the separator
-                self.get_dryrun_file_separator_padding_len(left_file) + 1);
-            // Add a space to the right so there's a gap between the separator and the next file
-            ColoredString::cyan(format!("{} ", padded_separator).as_str())
-        } else {
-            ColoredString::cyan(format!(" {} ", SEPARATOR_DRY_RUN_RIGHT_TO_LEFT).as_str())
-        }
-    }
-
-    pub fn format_write_file_separator(&self, left_file: String) -> String {
-        let padded_separator = LeftPadding::em_dash(
-            // Add a space to the left so there's a gap between the file and the separator
-            format!("{} ", SEPARATOR_COPY_MOVE),
-            // add +1 for the space added before the separator
-            self.get_write_file_separator_padding_len(left_file) + 1);
-        // Add a space to the right so there's a gap between the separator and the source file
-        format!(" {}", padded_separator)
-    }
-
-    pub fn format_dryrun_status_separator_dotted(&self, left_file: String, args: &Args) -> String {
-        if args.align_file_output {
-            let padded_separator = RightPadding::dot(
-                // Add a space to the left so there's a gap between the target file and the separator
-                format!(" {}", SEPARATOR_OP_STATUS),
-                // add +1 for the space added before the separator
-                self.get_dryrun_status_separator_padding_len(left_file) + 1);
-            // Add a space to the right so there's a gap between the separator and the source file
-            format!("{} ", padded_separator)
-        } else {
-            ColoredString::cyan(format!(" {} ", SEPARATOR_OP_STATUS).as_str())
-        }
-    }
-
-    pub fn format_write_status_separator_dotted(&self, left_file: String) -> String {
-        let padded_separator = RightPadding::dot(
-            // Add a space to the left so there's a gap between the target file and the separator
-            format!(" {}", SEPARATOR_OP_STATUS),
-            // add +1 for the space added before the separator
-            self.get_write_status_separator_padding_len(left_file) + 1);
-        // Add a space to the right so there's a gap between the separator and the source file
-        format!("{} ", padded_separator)
-    }
-}
 
 pub struct RightPadding;
 pub struct LeftPadding;
@@ -470,11 +99,13 @@ pub const DIR_TREE_ENTRY_LAST: &str = " └── ";
 pub const DIR_TREE_SNIP: &str = " ·-- ";
 pub const DIR_TREE_INDENT_MID: &str = " │   ";
 pub const DIR_TREE_INDENT_LAST: &str = "     ";
+pub const HEADER_OPERATION_STATUS: &str = "OPERATION STATUS";
+pub const HEADER_LOCATION: &str = "LOCATION";
 
 /// Adds dir tree symbols in front of the string based on the indent level.
 /// If level > 0, string gets an equal number of [FILE_TREE_INDENT_*] prefixes.
 /// All strings get a [FILE_TREE_ENTRY_*] prefix. For example:
-/// ```
+/// ```text
 /// [2019.01.28]
 /// ├── [Canon 100D]
 /// │    ├── IMG-20190128.jpg
@@ -487,7 +118,7 @@ pub fn indent_string(indent_level: usize, file_name: String, is_last_dir: bool,
     format!("{}{}{}", indents_symbols.repeat(indent_level), entry_symbol, file_name)
 }
 
-fn indent_string_snipped(indent_level: usize, file_name: String, is_last_dir: bool) -> String {
+pub fn indent_string_snipped(indent_level: usize, file_name: String, is_last_dir: bool) -> String {
     let indents_symbols = if is_last_dir {DIR_TREE_INDENT_LAST} else {DIR_TREE_INDENT_MID};
     let entry_symbol = DIR_TREE_SNIP.to_string();
     format!("{}{}{}", indents_symbols.repeat(indent_level), entry_symbol, file_name)
@@ -537,6 +168,21 @@ pub fn get_string_char_count(s: String) -> usize {
     s.chars().count()
 }
 
+/// Count the characters which are shown in the terminal, without the color codes added by [ColoredString]
+pub fn get_visible_char_count(s: &str) -> usize {
+    let mut count = 0;
+    let mut is_escape_code = false;
+    for c in s.chars() {
+        match c {
+            '\x1b' => is_escape_code = true,
+            'm' if is_escape_code => is_escape_code = false,
+            _ if !is_escape_code => count += 1,
+            _ => {}
+        }
+    }
+    count
+}
+
 pub fn simple_percentage(part: usize, total: usize) -> usize {
     (part as f32/total as f32 * 100_f32) as usize
 }
@@ -545,15 +191,3 @@ pub fn simple_percentage(part: usize, total: usize) -> usize {
 pub fn get_integer_char_count(i: i32) -> usize {
     get_string_char_count(i.to_string())
 }
-
-/// Convert bytes to an appropriate multiple (MB or GB) and append its unit
-pub fn get_file_size_string(filesize: u64) -> String {
-    match filesize {
-        size if size == 0 =>
-            String::from("unknown"),
-        size if size < 1024u64.pow(3) =>
-            format!("{:.2} MB", (size as f64 / 1024u64.pow(2) as f64)),
-        size =>
-            format!("{:.2} GB", (size as f64/ 1024u64.pow(3) as f64))
-    }
-}
@@ -0,0 +1,123 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::checksum::hash_file;
+use crate::utils::*;
+
+/// The outcome of comparing each source file with its copy inside the target folder
+#[derive(Debug, Default)]
+pub struct SourceVerifyReport {
+    /// Target files with the same size and checksum as their source file
+    pub verified: usize,
+    /// Target files with a different size than their source file, e.g. incomplete copies
+    pub size_mismatch: Vec<PathBuf>,
+    /// Target files with the same size but a different checksum than their source file
+    pub checksum_mismatch: Vec<PathBuf>,
+    /// Target files which don't exist
+    pub missing: Vec<PathBuf>,
+    /// Source or target files which exist but could not be read
+    pub unreadable: Vec<(PathBuf, String)>,
+    /// Source files which are skipped when sorting, so they're not expected in the target folder
+    pub skipped: usize,
+}
+
+impl SourceVerifyReport {
+    pub fn has_errors(&self) -> bool {
+        !(self.size_mismatch.is_empty()
+            && self.checksum_mismatch.is_empty()
+            && self.missing.is_empty()
+            && self.unreadable.is_empty())
+    }
+
+    pub fn print_summary(&self) {
+        let max_digits = get_integer_char_count(*[
+            self.verified,
+            self.size_mismatch.len(),
+            self.checksum_mismatch.len(),
+            self.missing.len(),
+            self.unreadable.len(),
+            self.skipped]
+            .iter()
+            .max()
+            .unwrap_or(&0) as i32);
+
+        let colored_count = |count: usize, color: fn(&str) -> String| {
+            let padded = LeftPadding::space(count.to_string(), max_digits);
+            if count > 0 { color(padded.as_str()) } else { padded }
+        };
+
+        println!("──────────────────────────────────────────────");
+        println!("Files verified:     {}", colored_count(self.verified, ColoredString::green));
+        println!("Different size:     {}", colored_count(self.size_mismatch.len(), ColoredString::red));
+        println!("Different checksum: {}", colored_count(self.checksum_mismatch.len(), ColoredString::red));
+        println!("Files missing:      {}", colored_count(self.missing.len(), ColoredString::red));
+        println!("Files unreadable:   {}", colored_count(self.unreadable.len(), ColoredString::red));
+        println!("Files skipped:      {}", colored_count(self.skipped, ColoredString::orange));
+        println!("──────────────────────────────────────────────");
+    }
+
+    /// Compare the target file with its source file, first by size, then by checksum,
+    /// and record the result. Only problems are printed, unless `verbose` is enabled
+    pub fn compare(&mut self, source_path: &Path, target_path: &Path, display_name: &str, verbose: bool) {
+        match compare_files(source_path, target_path) {
+            Ok(FileComparison::Same) => {
+                if verbose {
+                    println!("{} ... {}", display_name, ColoredString::green("ok"));
+                }
+                self.verified += 1;
+            }
+            Ok(FileComparison::Missing) => {
+                println!("{} ... {}", display_name, ColoredString::red("missing"));
+                self.missing.push(target_path.to_path_buf());
+            }
+            Ok(FileComparison::DifferentSize { source_size, target_size }) => {
+                println!("{} ... {}", display_name, ColoredString::red(
+                    format!("size mismatch: {} bytes, source has {} bytes", target_size, source_size).as_str()));
+                self.size_mismatch.push(target_path.to_path_buf());
+            }
+            Ok(FileComparison::DifferentChecksum) => {
+                println!("{} ... {}", display_name, ColoredString::red("checksum mismatch"));
+                self.checksum_mismatch.push(target_path.to_path_buf());
+            }
+            Err((path, e)) => {
+                println!("{} ... {}", display_name, ColoredString::red(
+                    format!("could not read {}: {}", path.display(), e).as_str()));
+                self.unreadable.push((path, e.to_string()));
+            }
+        }
+    }
+}
+
+enum FileComparison {
+    Same,
+    Missing,
+    DifferentSize { source_size: u64, target_size: u64 },
+    DifferentChecksum,
+}
+
+/// Compare the sizes first, since this doesn't need to read the files. If comparing fails,
+/// return the path of the file which could not be read, together with the error
+fn compare_files(source_path: &Path, target_path: &Path) -> Result<FileComparison, (PathBuf, io::Error)> {
+    let target_size = match fs::metadata(target_path) {
+        Ok(metadata) => metadata.len(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(FileComparison::Missing),
+        Err(e) => return Err((target_path.to_path_buf(), e)),
+    };
+    let source_size = fs::metadata(source_path)
+        .map_err(|e| (source_path.to_path_buf(), e))?
+        .len();
+
+    if source_size != target_size {
+        return Ok(FileComparison::DifferentSize { source_size, target_size });
+    }
+
+    let source_hash = hash_file(source_path).map_err(|e| (source_path.to_path_buf(), e))?;
+    let target_hash = hash_file(target_path).map_err(|e| (target_path.to_path_buf(), e))?;
+
+    if source_hash == target_hash {
+        Ok(FileComparison::Same)
+    } else {
+        Ok(FileComparison::DifferentChecksum)
+    }
+}
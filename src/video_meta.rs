@@ -0,0 +1,402 @@
+use std::fs::{DirEntry, File};
+use std::convert::TryInto;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+use crate::config::*;
+use crate::exif::ExifDateDevice;
+use crate::filetype::detect_file_type;
+use crate::isobmff::{find_box, find_child_box, read_child_boxes};
+
+/// The top-level boxes which can start an ISO base media file (MP4, MOV, 3GP, etc),
+/// used to avoid reading other formats as if they were made of boxes
+const KNOWN_FIRST_BOXES: &[&[u8; 4]] = &[b"ftyp", b"moov", b"mdat", b"wide", b"free", b"skip", b"pnot"];
+
+/// The format of [ExifDateDevice::capture_time], the same one used for photos
+const CAPTURE_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// The movie box of a short video is a few KB, anything larger than this is not worth reading for its tracks
+const MAX_SHORT_VIDEO_MOOV_LEN: u64 = 4 * 1024 * 1024;
+
+/// The IDs of the Matroska (MKV and WebM) elements which are read, see https://www.matroska.org/technical/elements.html
+const EBML_HEADER_ID: u32 = 0x1A45DFA3;
+const SEGMENT_ID: u32 = 0x18538067;
+const INFO_ID: u32 = 0x1549A966;
+const DATE_UTC_ID: u32 = 0x4461;
+const TAGS_ID: u32 = 0x1254C367;
+const TAG_ID: u32 = 0x7373;
+const SIMPLE_TAG_ID: u32 = 0x67C8;
+const TAG_NAME_ID: u32 = 0x45A3;
+const TAG_STRING_ID: u32 = 0x4487;
+
+/// The Matroska tag with the time the recording began
+const DATE_RECORDED_TAG: &str = "DATE_RECORDED";
+
+/// The `Info` and `Tags` elements are small, anything larger than this is not worth reading for a date
+const MAX_MATROSKA_ELEMENT_LEN: u64 = 1024 * 1024;
+
+/// AVCHD camcorders write the recording time in the first frames of the video stream,
+/// so only the start of MTS files is searched for it
+const MAX_AVCHD_SEARCH_LEN: u64 = 4 * 1024 * 1024;
+
+/// The "Modified Digital Video Pack Metadata" which AVCHD camcorders write in the H.264 stream
+const AVCHD_METADATA_MARKER: &[u8; 4] = b"MDPM";
+
+/// The tags of the AVCHD metadata which contain the year and month, then the day and time, as BCD digits
+const AVCHD_DATE_TAG: u8 = 0x18;
+const AVCHD_TIME_TAG: u8 = 0x19;
+
+/// Read the date a video was recorded from its metadata, which depends on the container:
+/// - MP4, MOV, M4V and 3GP files: the `creation_time` of the movie header (the `mvhd` box inside the `moov` box).
+///   The time is stored in UTC, so it's converted to the local time, to match the photos taken at the same time.
+///   Many devices and editing apps leave the creation time unset (i.e. zero), in which case there's no date.
+/// - MKV and WebM files: the `DATE_RECORDED` tag, or otherwise the date the file was written (also in UTC)
+/// - MTS and M2TS files from AVCHD camcorders: the recording time written in the video stream,
+///   which is already the local time set on the camcorder
+///
+/// See also [crate::exif::read_kamadak_exif_date_and_device()]
+pub fn read_video_date(file: &DirEntry, args: &Args) -> ExifDateDevice {
+    let mut video_data = ExifDateDevice::new();
+    let file_path = file.path();
+
+    let recording_time = match detect_file_type(&file_path).map(|detected_type| detected_type.format) {
+        Some("mkv") => read_matroska_date(&file_path),
+        Some("m2ts") => read_avchd_date(&file_path),
+        _ => read_mvhd_creation_time(&file_path).map(|creation_time| creation_time.with_timezone(&Local).naive_local()),
+    };
+
+    match recording_time {
+        Some(local_time) => {
+            video_data.date = Some(local_time.format(DATE_DIR_FORMAT).to_string());
+            video_data.capture_time = Some(local_time.format(CAPTURE_TIME_FORMAT).to_string());
+        }
+        None => {
+            if args.debug {
+                println!("> could not read the creation time of video {:?}", file_path);
+            }
+            // Together with an unknown header, this means the video is most likely corrupt
+            video_data.read_error = Some(String::from("could not read the creation time"));
+        }
+    }
+
+    video_data
+}
+
+/// Check if the video is a short loop without sound, e.g. a Boomerang or an animation made by Google Photos,
+/// which is at most `max_seconds` long. Only MP4, MOV and 3GP videos are checked, since other containers
+/// are rarely used for these, see [Args::animation_max_seconds]
+pub fn is_short_silent_video(file_path: &Path, max_seconds: u32) -> bool {
+    read_short_video_has_sound(file_path, max_seconds) == Some(false)
+}
+
+/// Read whether a video which is at most `max_seconds` long has a sound track.
+/// Returns None for longer videos, or if the duration can't be read
+fn read_short_video_has_sound(file_path: &Path, max_seconds: u32) -> Option<bool> {
+    let (mut reader, moov_start, moov_end, mvhd_start) = open_movie_header(file_path)?;
+
+    // version (1), flags (3), creation and modification times (4 or 8 each), time scale (4), duration (4 or 8)
+    reader.seek(SeekFrom::Start(mvhd_start)).ok()?;
+    let mut version = [0_u8; 4];
+    reader.read_exact(&mut version).ok()?;
+    let times_len = if version[0] == 1 { 16 } else { 8 };
+    reader.seek(SeekFrom::Current(times_len)).ok()?;
+    let mut time_scale = [0_u8; 4];
+    reader.read_exact(&mut time_scale).ok()?;
+    let time_scale = u32::from_be_bytes(time_scale);
+    let duration = if version[0] == 1 {
+        let mut duration = [0_u8; 8];
+        reader.read_exact(&mut duration).ok()?;
+        u64::from_be_bytes(duration)
+    } else {
+        let mut duration = [0_u8; 4];
+        reader.read_exact(&mut duration).ok()?;
+        u32::from_be_bytes(duration) as u64
+    };
+    if time_scale == 0 || duration == 0 || duration > max_seconds as u64 * time_scale as u64 {
+        return None;
+    }
+
+    // The movie box of a short video is small, but don't read it all if it's not
+    if moov_end - moov_start > MAX_SHORT_VIDEO_MOOV_LEN {
+        return None;
+    }
+    let mut moov = vec![0_u8; (moov_end - moov_start) as usize];
+    reader.seek(SeekFrom::Start(moov_start)).ok()?;
+    reader.read_exact(&mut moov).ok()?;
+
+    // Each track has a handler, which is `soun` for sound tracks: version (1), flags (3), pre-defined (4), type (4)
+    let has_sound = read_child_boxes(&moov)
+        .into_iter()
+        .filter(|(box_type, _)| box_type == b"trak")
+        .filter_map(|(_, trak)| find_child_box(trak, b"mdia"))
+        .filter_map(|mdia| find_child_box(mdia, b"hdlr"))
+        .any(|hdlr| hdlr.get(8..12) == Some(b"soun"));
+    Some(has_sound)
+}
+
+/// Open an ISO base media file and find its movie box, and the movie header inside it.
+/// Returns the reader, the start and end positions of the movie box and the start of the movie header
+fn open_movie_header(file_path: &Path) -> Option<(BufReader<File>, u64, u64, u64)> {
+    let mut reader = BufReader::new(File::open(file_path).ok()?);
+    let file_len = reader.get_ref().metadata().ok()?.len();
+
+    let mut first_box_type = [0_u8; 4];
+    reader.seek(SeekFrom::Start(4)).ok()?;
+    reader.read_exact(&mut first_box_type).ok()?;
+    if !KNOWN_FIRST_BOXES.contains(&&first_box_type) {
+        return None;
+    }
+
+    // The `moov` box may come after the media data, which can be several GB, so seek over boxes instead of reading them
+    let (moov_start, moov_end) = find_box(&mut reader, 0, file_len, b"moov")?;
+    let (mvhd_start, _) = find_box(&mut reader, moov_start, moov_end, b"mvhd")?;
+
+    Some((reader, moov_start, moov_end, mvhd_start))
+}
+
+fn read_mvhd_creation_time(file_path: &Path) -> Option<DateTime<Utc>> {
+    let (mut reader, _, _, mvhd_start) = open_movie_header(file_path)?;
+
+    // version (1), flags (3), then the creation time as 32 bits for version 0 or 64 bits for version 1
+    reader.seek(SeekFrom::Start(mvhd_start)).ok()?;
+    let mut version = [0_u8; 4];
+    reader.read_exact(&mut version).ok()?;
+    let seconds_since_1904 = if version[0] == 1 {
+        let mut creation_time = [0_u8; 8];
+        reader.read_exact(&mut creation_time).ok()?;
+        u64::from_be_bytes(creation_time)
+    } else {
+        let mut creation_time = [0_u8; 4];
+        reader.read_exact(&mut creation_time).ok()?;
+        u32::from_be_bytes(creation_time) as u64
+    };
+
+    // QuickTime timestamps count the seconds since 1904-01-01 00:00:00 UTC
+    if seconds_since_1904 == 0 || seconds_since_1904 > i64::MAX as u64 {
+        return None;
+    }
+    let epoch_1904 = Utc.from_utc_datetime(&NaiveDate::from_ymd(1904, 1, 1).and_hms(0, 0, 0));
+    let creation_time = epoch_1904.checked_add_signed(Duration::seconds(seconds_since_1904 as i64))?;
+
+    // Some devices write the creation time in seconds since 1970 instead, which would be in the 1970s
+    if creation_time.date().naive_utc() < NaiveDate::from_ymd(1980, 1, 1) {
+        None
+    } else {
+        Some(creation_time)
+    }
+}
+
+/// Read the recording time of a Matroska file, preferring the `DATE_RECORDED` tag, which is usually
+/// copied from the camera, over the `DateUTC` of the segment info, which is the time the file was written
+/// (e.g. when a screen recording was saved).
+/// Both are read from the top-level elements of the segment, which are skipped by seeking, like the
+/// boxes of MP4 files. Live recordings (e.g. from browsers) often don't write the size of their clusters,
+/// in which case the elements after the first cluster can't be found
+fn read_matroska_date(file_path: &Path) -> Option<NaiveDateTime> {
+    let mut reader = BufReader::new(File::open(file_path).ok()?);
+    let file_len = reader.get_ref().metadata().ok()?.len();
+
+    let (header_id, header_start, header_len) = read_element_header(&mut reader, 0)?;
+    if header_id != EBML_HEADER_ID {
+        return None;
+    }
+    let segment_position = header_start.checked_add(header_len?)?;
+    let (segment_id, segment_start, segment_len) = read_element_header(&mut reader, segment_position)?;
+    if segment_id != SEGMENT_ID {
+        return None;
+    }
+    let segment_end = segment_len
+        .and_then(|segment_len| segment_start.checked_add(segment_len))
+        .map_or(file_len, |segment_end| segment_end.min(file_len));
+
+    let mut date_utc = None;
+    let mut date_recorded = None;
+    let mut position = segment_start;
+    while position < segment_end && date_recorded.is_none() {
+        let (element_id, element_start, element_len) = match read_element_header(&mut reader, position) {
+            Some((element_id, element_start, Some(element_len))) => (element_id, element_start, element_len),
+            _ => break,
+        };
+        if (element_id == INFO_ID || element_id == TAGS_ID) && element_len <= MAX_MATROSKA_ELEMENT_LEN {
+            let mut contents = vec![0_u8; element_len as usize];
+            if reader.read_exact(&mut contents).is_err() {
+                break;
+            }
+            if element_id == INFO_ID {
+                date_utc = read_matroska_date_utc(&contents);
+            } else {
+                date_recorded = read_matroska_date_recorded(&contents);
+            }
+        }
+        position = match element_start.checked_add(element_len) {
+            Some(element_end) => element_end,
+            None => break,
+        };
+    }
+
+    date_recorded.or_else(|| date_utc.map(|date_utc| date_utc.with_timezone(&Local).naive_local()))
+}
+
+/// The `DateUTC` of the segment info is stored as nanoseconds since 2001-01-01 00:00:00 UTC.
+/// Some apps leave it at zero, in which case there's no date
+fn read_matroska_date_utc(info: &[u8]) -> Option<DateTime<Utc>> {
+    let date = read_child_elements(info)
+        .into_iter()
+        .find(|(element_id, _)| *element_id == DATE_UTC_ID)
+        .map(|(_, contents)| contents)?;
+    let nanoseconds = i64::from_be_bytes(date.try_into().ok()?);
+    if nanoseconds == 0 {
+        return None;
+    }
+
+    let epoch_2001 = Utc.from_utc_datetime(&NaiveDate::from_ymd(2001, 1, 1).and_hms(0, 0, 0));
+    epoch_2001.checked_add_signed(Duration::nanoseconds(nanoseconds))
+}
+
+/// Find the `DATE_RECORDED` tag among the tags of the file, e.g. `2021-05-14 10:00:00`.
+/// Dates with a time zone are converted to the local time, while dates without one are used as they are
+fn read_matroska_date_recorded(tags: &[u8]) -> Option<NaiveDateTime> {
+    read_child_elements(tags)
+        .into_iter()
+        .filter(|(element_id, _)| *element_id == TAG_ID)
+        .flat_map(|(_, tag)| read_child_elements(tag))
+        .filter(|(element_id, _)| *element_id == SIMPLE_TAG_ID)
+        .find_map(|(_, simple_tag)| {
+            let children = read_child_elements(simple_tag);
+            let find_string = |id: u32| children
+                .iter()
+                .find(|(element_id, _)| *element_id == id)
+                .map(|(_, contents)| String::from_utf8_lossy(contents).trim_end_matches('\0').trim().to_string());
+
+            if find_string(TAG_NAME_ID)?.eq_ignore_ascii_case(DATE_RECORDED_TAG) {
+                parse_tag_date(&find_string(TAG_STRING_ID)?)
+            } else {
+                None
+            }
+        })
+}
+
+fn parse_tag_date(date: &str) -> Option<NaiveDateTime> {
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(date) {
+        return Some(date_time.with_timezone(&Local).naive_local());
+    }
+
+    ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(date, format).ok())
+}
+
+/// Read the ID and size of the Matroska element at this position and return them together with the position
+/// of its contents. The size is None if it's unknown, which is allowed for the segment and its clusters
+fn read_element_header<R: Read + Seek>(reader: &mut R, position: u64) -> Option<(u32, u64, Option<u64>)> {
+    reader.seek(SeekFrom::Start(position)).ok()?;
+
+    // Both are variable length integers, whose length is given by the number of leading zeros of the first byte.
+    // The length marker is part of the ID, but not of the size
+    let (id, id_len) = read_variable_int(reader, 4)?;
+    let (size, size_len) = read_variable_int(reader, 8)?;
+    let id = (id | (1 << (7 * id_len))) as u32;
+    let is_unknown_size = size == (1 << (7 * size_len)) - 1;
+
+    Some((id, position + (id_len + size_len) as u64, if is_unknown_size { None } else { Some(size) }))
+}
+
+/// Read a variable length integer without its length marker and return it together with its length in bytes
+fn read_variable_int<R: Read>(reader: &mut R, max_len: u32) -> Option<(u64, u32)> {
+    let mut first_byte = [0_u8; 1];
+    reader.read_exact(&mut first_byte).ok()?;
+    let len = first_byte[0].leading_zeros() + 1;
+    if len > max_len {
+        return None;
+    }
+
+    let mut value = (first_byte[0] as u64) & ((1 << (8 - len)) - 1);
+    for _ in 1..len {
+        let mut next_byte = [0_u8; 1];
+        reader.read_exact(&mut next_byte).ok()?;
+        value = (value << 8) | next_byte[0] as u64;
+    }
+
+    Some((value, len))
+}
+
+/// Split the Matroska elements which were already read in memory into their ID and contents.
+/// Stops at the first element which doesn't fit in the data
+fn read_child_elements(data: &[u8]) -> Vec<(u32, &[u8])> {
+    let mut elements = Vec::new();
+    let mut reader = Cursor::new(data);
+    let mut position = 0;
+    while position < data.len() as u64 {
+        let (element_id, element_start, element_len) = match read_element_header(&mut reader, position) {
+            Some((element_id, element_start, Some(element_len))) => (element_id, element_start, element_len),
+            _ => break,
+        };
+        let element_end = match element_start.checked_add(element_len) {
+            Some(element_end) if element_end <= data.len() as u64 => element_end,
+            _ => break,
+        };
+        elements.push((element_id, &data[element_start as usize..element_end as usize]));
+        position = element_end;
+    }
+
+    elements
+}
+
+/// Read the recording time which AVCHD camcorders write in the "MDPM" metadata of the H.264 stream of MTS files.
+/// The metadata is made of 5 byte entries, a tag followed by 4 bytes of data, and the date is split in two:
+/// - tag 0x18: the time zone, then the year (2 bytes) and the month
+/// - tag 0x19: the day, hour, minute and second
+///
+/// All of these are BCD digits, e.g. 0x20 0x21 for 2021
+fn read_avchd_date(file_path: &Path) -> Option<NaiveDateTime> {
+    let mut data = Vec::new();
+    File::open(file_path).ok()?.take(MAX_AVCHD_SEARCH_LEN).read_to_end(&mut data).ok()?;
+
+    let marker_end = data
+        .windows(AVCHD_METADATA_MARKER.len())
+        .position(|window| window == AVCHD_METADATA_MARKER)?
+        + AVCHD_METADATA_MARKER.len();
+    let metadata = remove_emulation_prevention_bytes(data.get(marker_end..)?.iter().take(1 + 5 * 255));
+    let entry_count = *metadata.first()? as usize;
+
+    let find_entry = |tag: u8| metadata[1..]
+        .chunks_exact(5)
+        .take(entry_count)
+        .find(|entry| entry[0] == tag)
+        .map(|entry| [entry[1], entry[2], entry[3], entry[4]]);
+    let date = find_entry(AVCHD_DATE_TAG)?;
+    let time = find_entry(AVCHD_TIME_TAG)?;
+
+    NaiveDate::from_ymd_opt(
+        (from_bcd(date[1])? * 100 + from_bcd(date[2])?) as i32,
+        from_bcd(date[3])?,
+        from_bcd(time[0])?)?
+        .and_hms_opt(from_bcd(time[1])?, from_bcd(time[2])?, from_bcd(time[3])?)
+}
+
+/// H.264 streams insert a 0x03 byte after two zero bytes, so that the data never looks like the start of a new unit
+fn remove_emulation_prevention_bytes<'a>(data: impl Iterator<Item = &'a u8>) -> Vec<u8> {
+    let mut unescaped: Vec<u8> = Vec::new();
+    let mut zero_count = 0;
+    for &byte in data {
+        if byte == 0x03 && zero_count >= 2 {
+            zero_count = 0;
+            continue;
+        }
+        zero_count = if byte == 0x00 { zero_count + 1 } else { 0 };
+        unescaped.push(byte);
+    }
+    unescaped
+}
+
+/// Decode a byte of two BCD digits, e.g. 0x59 is 59
+fn from_bcd(byte: u8) -> Option<u32> {
+    let (tens, units) = (byte >> 4, byte & 0x0F);
+    if tens > 9 || units > 9 {
+        None
+    } else {
+        Some((tens * 10 + units) as u32)
+    }
+}
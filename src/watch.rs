@@ -0,0 +1,122 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Arc;
+use std::time::Duration;
+
+use notify::event::{CreateKind, ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::target_index::TargetIndex;
+
+/// Watches the source folders for new or changed files, see [crate::config::Command::Watch]
+pub struct SourceWatcher {
+    // The watcher stops sending events once it's dropped, so it's kept together with its events
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+    /// Files inside this folder are ignored, so that the files written by the program don't start another batch
+    ignored_dir: PathBuf,
+}
+
+impl SourceWatcher {
+    pub fn new(source_dirs: &[PathBuf], recursive: bool, ignored_dir: &Path) -> notify::Result<SourceWatcher> {
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            // The receiver is only dropped when the program exits
+            let _ = sender.send(event);
+        })?;
+
+        let recursive_mode = if recursive { RecursiveMode::Recursive } else { RecursiveMode::NonRecursive };
+        for source_dir in source_dirs {
+            watcher.watch(source_dir, recursive_mode)?;
+        }
+
+        Ok(SourceWatcher {
+            _watcher: watcher,
+            events,
+            ignored_dir: ignored_dir.to_path_buf(),
+        })
+    }
+
+    /// Wait until files are added or changed, then keep waiting until none were added or changed
+    /// for the debounce time, so that files which are still being written, e.g. by a sync app,
+    /// are only read once they're complete. Returns the files which still exist at that point,
+    /// or an error if the source folders can't be watched anymore
+    pub fn next_batch(&self, debounce: Duration) -> notify::Result<Vec<PathBuf>> {
+        let mut changed_files: BTreeSet<PathBuf> = BTreeSet::new();
+
+        // Block until the first change, without a timeout
+        while changed_files.is_empty() {
+            match self.events.recv() {
+                Ok(event) => self.add_changed_files(event?, &mut changed_files),
+                Err(_) => return Err(notify::Error::generic("the source folders are no longer watched")),
+            }
+        }
+
+        loop {
+            match self.events.recv_timeout(debounce) {
+                Ok(event) => self.add_changed_files(event?, &mut changed_files),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) =>
+                    return Err(notify::Error::generic("the source folders are no longer watched")),
+            }
+        }
+
+        // Files which were only temporary, or were moved away in the meantime, are not read
+        Ok(changed_files.into_iter().filter(|path| path.is_file()).collect())
+    }
+
+    fn add_changed_files(&self, event: Event, changed_files: &mut BTreeSet<PathBuf>) {
+        let is_new_or_changed = matches!(event.kind,
+            EventKind::Create(CreateKind::File)
+            | EventKind::Create(CreateKind::Any)
+            | EventKind::Modify(ModifyKind::Data(_))
+            | EventKind::Modify(ModifyKind::Any)
+            | EventKind::Modify(ModifyKind::Name(RenameMode::To))
+            | EventKind::Modify(ModifyKind::Name(RenameMode::Both))
+            | EventKind::Modify(ModifyKind::Name(RenameMode::Any)));
+        if !is_new_or_changed {
+            return;
+        }
+
+        // Renames which include both paths list the new path last
+        let changed_path = match event.kind {
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => event.paths.last(),
+            _ => event.paths.first(),
+        };
+
+        if let Some(changed_path) = changed_path.filter(|path| !path.starts_with(&self.ignored_dir)) {
+            changed_files.insert(changed_path.clone());
+        }
+    }
+}
+
+/// Keeps a [TargetIndex] of the target folder up to date from its filesystem events, including the events
+/// of the files written by the program itself, so that each batch of the `watch` command knows which files
+/// already exist in the target folder without listing its folders again
+pub struct TargetWatcher {
+    // The watcher stops sending events once it's dropped, so it's kept together with the index
+    _watcher: RecommendedWatcher,
+    pub index: Arc<TargetIndex>,
+}
+
+impl TargetWatcher {
+    /// The target folder must already exist to be watched
+    pub fn new(target_dir: &Path) -> notify::Result<TargetWatcher> {
+        let index = Arc::new(TargetIndex::new());
+        let watched_index = Arc::clone(&index);
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| match event {
+            Ok(event) => watched_index.update(&event),
+            // Some changes may have been missed, so the folders are listed again
+            Err(_) => watched_index.clear(),
+        })?;
+
+        // The paths of the events start with the watched path, so they match the canonical paths of the index
+        watcher.watch(&target_dir.canonicalize()?, RecursiveMode::Recursive)?;
+
+        Ok(TargetWatcher {
+            _watcher: watcher,
+            index,
+        })
+    }
+}